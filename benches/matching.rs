@@ -0,0 +1,44 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use face_auth::bench::{synthetic_database, synthetic_encoding, DEFAULT_SAMPLE_COUNTS, PREFILTER_SHORTLIST};
+use face_auth::embeddings::{EmbeddingIndex, EmbeddingPrecision};
+use face_auth::matching;
+
+fn distance_kernels(c: &mut Criterion) {
+    let a = synthetic_encoding(1);
+    let b = synthetic_encoding(2);
+    let a32: Vec<f32> = a.iter().map(|&x| x as f32).collect();
+    let b32: Vec<f32> = b.iter().map(|&x| x as f32).collect();
+    let q: Vec<i8> = a.iter().map(|x| (x / 0.2 * 127.0).round() as i8).collect();
+
+    let mut group = c.benchmark_group("distance");
+    group.bench_function("face_distance_f64", |bench| bench.iter(|| matching::face_distance(black_box(&a), black_box(&b))));
+    group.bench_function("squared_euclidean_f32", |bench| bench.iter(|| matching::squared_euclidean_f32(black_box(&a32), black_box(&b32))));
+    group.bench_function("squared_euclidean_i8", |bench| bench.iter(|| matching::squared_euclidean_i8(black_box(&q), 0.2 / 127.0, black_box(&b32))));
+    group.bench_function("cosine_distance_f32", |bench| bench.iter(|| matching::cosine_distance_f32(black_box(&a32), black_box(&b32))));
+    group.finish();
+}
+
+fn find_best_match(c: &mut Criterion) {
+    let probe = synthetic_encoding(7);
+    let mut group = c.benchmark_group("find_best_match");
+    for samples in DEFAULT_SAMPLE_COUNTS {
+        let db = synthetic_database(samples);
+        group.bench_with_input(BenchmarkId::new("F64", samples), &db, |bench, db| {
+            bench.iter(|| db.find_best_match(black_box(&probe), 0.6))
+        });
+        for precision in [EmbeddingPrecision::F32, EmbeddingPrecision::Int8] {
+            let index = EmbeddingIndex::from_profiles(db.users.values(), precision);
+            group.bench_with_input(BenchmarkId::new(format!("{:?}", precision), samples), &index, |bench, index| {
+                bench.iter(|| index.find_best_match(black_box(&probe), 0.6))
+            });
+        }
+        let prefiltered = EmbeddingIndex::from_profiles(db.users.values(), EmbeddingPrecision::F32).with_prefilter(PREFILTER_SHORTLIST);
+        group.bench_with_input(BenchmarkId::new("F32 prefilter", samples), &prefiltered, |bench, index| {
+            bench.iter(|| index.find_best_match(black_box(&probe), 0.6))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, distance_kernels, find_best_match);
+criterion_main!(benches);
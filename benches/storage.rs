@@ -0,0 +1,82 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use face_auth::bench::{synthetic_database, synthetic_profile, SAMPLES_PER_USER};
+use face_auth::compression::{Compression, BENCH_LEVELS};
+use face_auth::face_storage::{ExportedCredential, FaceDatabase};
+use face_auth::snapshot::DatabaseSnapshot;
+use std::fs;
+
+fn database_load_save(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let mut group = c.benchmark_group("database");
+    group.sample_size(10);
+    for samples in [1_000, 10_000] {
+        let db = synthetic_database(samples);
+        let json_path = dir.path().join(format!("{}.json", samples));
+        let snapshot_path = dir.path().join(format!("{}.snap", samples));
+        db.save(&json_path).unwrap();
+        db.write_snapshot(&snapshot_path).unwrap();
+
+        group.bench_with_input(BenchmarkId::new("save", samples), &db, |bench, db| bench.iter(|| db.save(&json_path).unwrap()));
+        group.bench_function(BenchmarkId::new("load", samples), |bench| bench.iter(|| FaceDatabase::load(&json_path).unwrap()));
+        group.bench_function(BenchmarkId::new("open_lazy", samples), |bench| bench.iter(|| FaceDatabase::open_lazy(&json_path, 16).unwrap()));
+        group.bench_function(BenchmarkId::new("open_snapshot", samples), |bench| bench.iter(|| DatabaseSnapshot::open(&snapshot_path).unwrap()));
+    }
+    group.finish();
+}
+
+fn export_import(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("credentials.json");
+    let credential = ExportedCredential {
+        user_id: "user000000".to_string(),
+        user_data: synthetic_profile(0, SAMPLES_PER_USER),
+        exported_at: None,
+        version: Some("1.0".to_string()),
+        extra: Default::default(),
+    };
+
+    c.bench_function("export_credential", |bench| {
+        bench.iter(|| fs::write(&path, serde_json::to_vec_pretty(black_box(&credential)).unwrap()).unwrap())
+    });
+    c.bench_function("import_credential", |bench| {
+        bench.iter(|| serde_json::from_slice::<ExportedCredential>(&fs::read(&path).unwrap()).unwrap())
+    });
+}
+
+fn compression_levels(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let db = synthetic_database(10_000);
+    let json = serde_json::to_vec_pretty(&ExportedCredential {
+        user_id: "user000000".to_string(),
+        user_data: synthetic_profile(0, SAMPLES_PER_USER),
+        exported_at: None,
+        version: Some("1.0".to_string()),
+        extra: Default::default(),
+    }).unwrap();
+
+    let mut group = c.benchmark_group("compression");
+    group.sample_size(10);
+    for level in BENCH_LEVELS {
+        let compression = Compression::zstd(level);
+        let snapshot_path = dir.path().join(format!("{}.snap", level));
+        db.write_snapshot_with(&snapshot_path, compression).unwrap();
+        let export = compression.compress(&json).unwrap().into_owned();
+        println!(
+            "zstd-{}: snapshot {} bytes, export {} of {} bytes",
+            level,
+            fs::metadata(&snapshot_path).unwrap().len(),
+            export.len(),
+            json.len()
+        );
+
+        group.bench_function(BenchmarkId::new("open_snapshot", level), |bench| bench.iter(|| DatabaseSnapshot::open(&snapshot_path).unwrap()));
+        group.bench_function(BenchmarkId::new("compress_export", level), |bench| bench.iter(|| compression.compress(black_box(&json)).unwrap().len()));
+        group.bench_function(BenchmarkId::new("decompress_export", level), |bench| {
+            bench.iter(|| face_auth::compression::decompress(black_box(&export)).unwrap().len())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, database_load_save, export_import, compression_levels);
+criterion_main!(benches);
@@ -1,17 +1,23 @@
 use anyhow::Result;
-use face_auth::FaceAuth;
+use face_auth::{AuthRequest, FaceAuth};
 use std::io::{self, Write};
 
 /// Simple interactive example of the face authentication library
 #[tokio::main]
 async fn main() -> Result<()> {
+    // Show the Python backend's progress messages
+    tracing_subscriber::fmt()
+        .with_target(false)
+        .without_time()
+        .init();
+
     println!("===========================================");
     println!("  Face Auth Library - Example Application");
     println!("===========================================\n");
 
     // Get custom paths from environment variables or use defaults
-    let generated_dir = &"generated";
-    let source_dir = &"source";
+    let generated_dir = "generated";
+    let source_dir = "source";
     println!("📁 Configuration:");
     println!("   Generated directory: {}", generated_dir);
     println!("   Source directory: {}", source_dir);
@@ -49,24 +55,37 @@ async fn main() -> Result<()> {
                 let username = username.trim();
 
                 println!("\nRegistering user '{}'...", username);
-                match face_auth.register_user(username, 3, &generated_dir).await {
-                    Ok(true) => {
+                match face_auth.register_user(username, 3, generated_dir).await {
+                    Ok(outcome) if outcome.is_registered() => {
                         println!("✓ Registration successful!");
-                        println!("File saved to: {}/{}.json", generated_dir, username);
-                        println!("\nTo enable authentication, run:");
-                        println!("  cp {}/{}.json {}/", generated_dir, username, source_dir);
+                        for sample in &outcome.samples {
+                            match sample.quality {
+                                Some(quality) => println!("  Sample {}: quality {:.2}", sample.index, quality.score),
+                                None => println!("  Sample {}: {:?}", sample.index, sample.status),
+                            }
+                        }
+                        if let Some(path) = &outcome.generated_file {
+                            println!("File saved to: {}", path.display());
+                        }
+                        match face_auth.promote_user(username, generated_dir, source_dir).await {
+                            Ok(path) => println!("✓ Enabled for authentication: {}", path.display()),
+                            Err(e) => println!("✗ Could not enable for authentication: {}", e),
+                        }
                     }
-                    Ok(false) => println!("✗ Registration failed"),
+                    Ok(_) => println!("✗ Registration failed"),
                     Err(e) => println!("✗ Error: {}", e),
                 }
             }
             "2" => {
                 println!("\nAuthenticating...");
-                match face_auth.authenticate_user(0.6, &source_dir).await {
+                match face_auth.authenticate(&AuthRequest::camera(0.6).with_source_dir(source_dir)).await {
                     Ok(result) => {
                         if result.is_authenticated {
                             println!("\n✓ Authentication successful!");
-                            if let Some(user) = result.user_id {
+                            if let Some(greeting) = &result.greeting {
+                                println!("  Welcome, {}! (visit #{})", greeting.display_name, greeting.visit_count);
+                            }
+                            if let Some(user) = &result.user_id {
                                 println!("  User: {}", user);
                             }
                             if let Some(confidence) = result.confidence {
@@ -116,8 +135,15 @@ async fn main() -> Result<()> {
             }
             "5" => {
                 println!("\nListing users...");
-                if let Err(e) = face_auth.list_users().await {
-                    println!("✗ Error: {}", e);
+                match face_auth.list_users().await {
+                    Ok(users) if users.is_empty() => println!("No users registered"),
+                    Ok(users) => {
+                        for user in users {
+                            let name = user.metadata.display_name.as_deref().unwrap_or(&user.username);
+                            println!("  - {} ({}): {} samples", name, user.username, user.sample_count);
+                        }
+                    }
+                    Err(e) => println!("✗ Error: {}", e),
                 }
             }
             "6" => {
@@ -0,0 +1,12 @@
+#![no_main]
+
+use face_auth::face_storage::FaceDatabase;
+use face_auth::matching::DEFAULT_TOLERANCE;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|json: &str| {
+    if let Ok(db) = FaceDatabase::from_json(json) {
+        assert!(db.users.iter().all(|(key, profile)| *key == profile.user_id));
+        let _ = db.find_best_match(&[0.0; face_auth::face_storage::ENCODING_DIMENSIONS], DEFAULT_TOLERANCE);
+    }
+});
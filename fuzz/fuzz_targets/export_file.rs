@@ -0,0 +1,11 @@
+#![no_main]
+
+use face_auth::face_storage::ExportedCredential;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|json: &str| {
+    if let Ok(export) = ExportedCredential::from_json(json) {
+        assert_eq!(export.user_id, export.user_data.user_id);
+        assert!(export.user_data.validate().is_ok());
+    }
+});
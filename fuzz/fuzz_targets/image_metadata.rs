@@ -0,0 +1,13 @@
+#![no_main]
+
+use face_auth::provenance::scrub_metadata;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|image: &[u8]| {
+    if let Ok(scrubbed) = scrub_metadata(image) {
+        assert!(scrubbed.bytes.len() <= image.len());
+        // Scrubbing is idempotent: nothing is left to strip the second time
+        let again = scrub_metadata(&scrubbed.bytes).unwrap();
+        assert!(!again.stripped);
+    }
+});
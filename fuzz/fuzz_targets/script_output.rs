@@ -0,0 +1,13 @@
+#![no_main]
+
+use face_auth::standalone_python::ScriptOutput;
+use face_auth::RegistrationOutcome;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|stdout: &str| {
+    if let Ok(result) = ScriptOutput::from_stdout(stdout).auth_result(0.6, 10) {
+        assert!(result.confidence.is_none_or(|c| (0.0..=1.0).contains(&c)));
+        assert!(result.distance.is_none_or(|d| d.is_finite() && d >= 0.0));
+    }
+    let _ = ScriptOutput::from_stdout(stdout).result::<RegistrationOutcome>();
+});
@@ -10,11 +10,20 @@ import numpy as np
 import json
 import os
 import time
+import uuid
 from datetime import datetime
 from typing import List, Dict, Tuple, Optional
 import pickle
 import argparse
 
+def new_sample_id() -> str:
+    """UUIDv7 sample id: millisecond timestamp, then random bits (mirrors src/sample_id.rs)"""
+    value = (int(time.time() * 1000) << 80) | int.from_bytes(os.urandom(10), "big")
+    value = (value & ~(0xF << 76)) | (0x7 << 76)
+    value = (value & ~(0x3 << 62)) | (0x2 << 62)
+    return str(uuid.UUID(int=value))
+
+
 class HighAccuracyFaceAuth:
     def __init__(self, db_path: str = "python_face_database.json"):
         self.db_path = db_path
@@ -173,7 +182,7 @@ class HighAccuracyFaceAuth:
                         "encoding": encodings[0].tolist(),  # Convert numpy array to list for JSON
                         "timestamp": datetime.now().isoformat(),
                         "image_path": image_path,
-                        "sample_id": f"{user_id}_{timestamp}"
+                        "sample_id": new_sample_id()
                     })
                     print(f"✅ Sample {i+1} processed successfully!")
                 else:
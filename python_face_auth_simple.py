@@ -4,6 +4,7 @@ Simplified Python Face Authentication for integration with Rust
 Auto-captures without manual interaction
 """
 
+import base64
 import face_recognition
 import cv2
 import numpy as np
@@ -11,15 +12,135 @@ import json
 import os
 import time
 import sys
+import uuid
 from datetime import datetime
 from typing import List, Dict, Tuple, Optional
 import argparse
 
+# Prefix of the single machine-readable line the Rust side parses
+RESULT_PREFIX = "FACE_AUTH_RESULT "
+
+# Prefix of the line telling how many frames of a video file were read
+# (mirrors VIDEO_POSITION_PREFIX in src/standalone_python.rs)
+VIDEO_POSITION_PREFIX = "FACE_AUTH_VIDEO_POSITION "
+
+# Width frames are scaled down to for the motion and face presence checks in watch mode
+WATCH_FRAME_WIDTH = 160
+
+# Samples closer than this to the previous one are the same frame again
+# (mirrors DUPLICATE_SAMPLE_EPSILON in src/registration.rs)
+DUPLICATE_SAMPLE_EPSILON = 0.04
+DUPLICATE_SAMPLE_RETRIES = 2
+
+# Samples of unfinished registrations, one file per user, so a restarted
+# registration continues (mirrors StorageLayout::registration_progress_dir in src/face_storage.rs)
+REGISTRATION_PROGRESS_DIR = "registration_progress"
+
+def emit_result(result: Dict) -> None:
+    """Print the operation result as one JSON line for the Rust caller"""
+    print(RESULT_PREFIX + json.dumps(result), flush=True)
+
+def new_sample_id() -> str:
+    """UUIDv7 sample id: millisecond timestamp, then random bits (mirrors src/sample_id.rs)"""
+    value = (int(time.time() * 1000) << 80) | int.from_bytes(os.urandom(10), "big")
+    value = (value & ~(0xF << 76)) | (0x7 << 76)
+    value = (value & ~(0x3 << 62)) | (0x2 << 62)
+    return str(uuid.UUID(int=value))
+
+def elapsed_ms(started: float) -> int:
+    """Milliseconds since a time.time() reading"""
+    return int((time.time() - started) * 1000)
+
+def box_overlap(a: Tuple[int, int, int, int], b: Tuple[int, int, int, int]) -> float:
+    """Intersection over union of two (x, y, width, height) boxes"""
+    width = min(a[0] + a[2], b[0] + b[2]) - max(a[0], b[0])
+    height = min(a[1] + a[3], b[1] + b[3]) - max(a[1], b[1])
+    if width <= 0 or height <= 0:
+        return 0.0
+    intersection = width * height
+    return intersection / float(a[2] * a[3] + b[2] * b[3] - intersection)
+
+class FaceTracker:
+    """Links face boxes in consecutive frames into tracks by their overlap"""
+
+    def __init__(self, min_overlap: float = 0.3, max_missed: int = 10):
+        self.min_overlap = min_overlap
+        self.max_missed = max_missed
+        self.tracks = []
+        self.next_id = 1
+
+    def update(self, boxes: List[Tuple[int, int, int, int]]) -> Tuple[List[Dict], List[Dict]]:
+        """Assign the boxes of a new frame to tracks, returning the tracks that
+        entered and those that left"""
+        pairs = sorted(
+            ((box_overlap(track["box"], box), t, b) for t, track in enumerate(self.tracks) for b, box in enumerate(boxes)),
+            reverse=True
+        )
+        matched_tracks, matched_boxes = set(), set()
+        for overlap, t, b in pairs:
+            if overlap < self.min_overlap:
+                break
+            if t in matched_tracks or b in matched_boxes:
+                continue
+            matched_tracks.add(t)
+            matched_boxes.add(b)
+            track = self.tracks[t]
+            track.update({"box": boxes[b], "missed": 0, "frames": track["frames"] + 1})
+
+        exited = []
+        for t, track in enumerate(self.tracks):
+            if t not in matched_tracks:
+                track["missed"] += 1
+                if track["missed"] > self.max_missed:
+                    exited.append(track)
+        self.tracks = [track for track in self.tracks if track not in exited]
+
+        entered = []
+        for b, box in enumerate(boxes):
+            if b not in matched_boxes:
+                entered.append({"id": self.next_id, "box": box, "missed": 0, "frames": 1, "user_id": None})
+                self.next_id += 1
+        self.tracks.extend(entered)
+        return entered, exited
+
 class SimpleFaceAuth:
-    def __init__(self, db_path: str = "python_face_database.json"):
-        self.db_path = db_path
+    def __init__(self, db_path: str = "python_face_database.json", data_dir: str = ".", camera_index: int = 0,
+                 strict_privacy: bool = False, blur: str = "off", video_file: str = None, video_start: int = 0):
+        self.data_dir = os.path.abspath(data_dir)
+        self.db_path = os.path.join(self.data_dir, db_path)
+        self.captures_dir = os.path.join(self.data_dir, "captured_images")
+        self.camera_index = camera_index
+        # Prerecorded video read in place of the camera
+        self.video_file = video_file
+        # First frame of the video not read by an earlier capture
+        self.video_start = video_start
+        self.exports_dir = os.path.join(self.data_dir, "exported_credentials")
+        self.thumbnails_dir = os.path.join(self.data_dir, "thumbnails")
+        self.progress_dir = os.path.join(self.data_dir, REGISTRATION_PROGRESS_DIR)
+        # Frame adjustments of the active threshold profile: {"equalize": bool, "gamma": float}
+        self.preprocessing = {}
+        # With strict privacy no image touches disk: frames are kept here, keyed by the path they'd have had
+        self.strict_privacy = strict_privacy
+        self.memory_images = {}
+        # Faces blurred in captures once matched: "off", "bystanders" or "all"
+        self.blur = blur
         self.load_database()
 
+    def open_capture(self) -> "cv2.VideoCapture":
+        """Open the camera, or the video file standing in for it at its first unread frame"""
+        if self.video_file:
+            cap = cv2.VideoCapture(self.video_file)
+            if self.video_start:
+                cap.set(cv2.CAP_PROP_POS_FRAMES, self.video_start)
+            return cap
+        return cv2.VideoCapture(self.camera_index)
+
+    def report_video_position(self, cap: "cv2.VideoCapture") -> None:
+        """Tell the Rust caller how far into the video file captures got, so the next run continues there"""
+        if self.video_file:
+            self.video_start = int(cap.get(cv2.CAP_PROP_POS_FRAMES))
+            print(VIDEO_POSITION_PREFIX + str(self.video_start), flush=True)
+
     def load_database(self):
         """Load face database or create new one"""
         try:
@@ -45,15 +166,154 @@ class SimpleFaceAuth:
         except Exception as e:
             print(f"Error saving database: {e}")
 
+    def load_progress(self, user_id: str, num_samples: int) -> Dict:
+        """Samples stored by an interrupted registration of the same size, or none"""
+        path = os.path.join(self.progress_dir, f"{user_id}.json")
+        try:
+            with open(path, 'r') as f:
+                progress = json.load(f)
+        except (OSError, ValueError):
+            return {"samples": [], "face_encodings": [], "best_image": None}
+        if progress.get("samples_requested") != num_samples:
+            print(f"Discarding an interrupted registration of {progress.get('samples_requested')} samples")
+            return {"samples": [], "face_encodings": [], "best_image": None}
+        return progress
+
+    def save_progress(self, user_id: str, num_samples: int, sample_reports: List, face_encodings: List, best_image) -> None:
+        """Record the samples so far, replacing the file atomically"""
+        os.makedirs(self.progress_dir, exist_ok=True)
+        path = os.path.join(self.progress_dir, f"{user_id}.json")
+        progress = {
+            "username": user_id,
+            "samples_requested": num_samples,
+            "samples": sample_reports,
+            "face_encodings": face_encodings,
+            # Frames in memory don't survive the process
+            "best_image": None if self.strict_privacy or best_image[1] is None else list(best_image),
+            "updated_at": datetime.now().isoformat()
+        }
+        with open(path + ".tmp", 'w') as f:
+            json.dump(progress, f)
+        os.replace(path + ".tmp", path)
+
+    def clear_progress(self, user_id: str) -> None:
+        try:
+            os.remove(os.path.join(self.progress_dir, f"{user_id}.json"))
+        except FileNotFoundError:
+            pass
+
+    def write_image(self, path: str, frame: np.ndarray) -> None:
+        """Save a BGR frame, or keep it in memory with strict privacy"""
+        if self.strict_privacy:
+            self.memory_images[path] = frame
+            return
+        os.makedirs(os.path.dirname(path), exist_ok=True)
+        cv2.imwrite(path, frame)
+
+    def has_image(self, path: str) -> bool:
+        return path in self.memory_images or os.path.exists(path)
+
+    def load_rgb(self, path: str) -> np.ndarray:
+        frame = self.memory_images.get(path)
+        return face_recognition.load_image_file(path) if frame is None else cv2.cvtColor(frame, cv2.COLOR_BGR2RGB)
+
+    def discard_image(self, path: str) -> None:
+        """Drop a frame that's no longer needed"""
+        if self.memory_images.pop(path, None) is None and os.path.exists(path):
+            os.remove(path)
+
+    def blur_faces(self, path: str) -> None:
+        """Blur faces in a capture kept on disk: every face but the largest one,
+        the person being authenticated, or every face with blur "all"
+
+        Only frames in the captures directory are touched, never images passed in."""
+        if self.blur == "off" or path in self.memory_images or not os.path.exists(path):
+            return
+        if not os.path.abspath(path).startswith(self.captures_dir + os.sep):
+            return
+        frame = cv2.imread(path)
+        if frame is None:
+            return
+        if self.redact_frame(frame, keep_largest=self.blur == "bystanders"):
+            cv2.imwrite(path, frame)
+
+    @staticmethod
+    def redact_frame(frame, keep_largest: bool = False, style: str = "blur", block: int = 12,
+                     padding: float = 0.25) -> List:
+        """Blur or pixelate the faces in a BGR frame in place, all but the
+        largest one with `keep_largest`; returns every face found"""
+        faces = face_recognition.face_locations(cv2.cvtColor(frame, cv2.COLOR_BGR2RGB), model="hog")
+        redacted = list(faces)
+        if keep_largest and redacted:
+            redacted.remove(max(redacted, key=lambda box: (box[2] - box[0]) * (box[1] - box[3])))
+        height, width = frame.shape[:2]
+        for top, right, bottom, left in redacted:
+            # Pad the box so hair and face outline don't stay recognizable
+            pad = int((bottom - top) * padding)
+            top, bottom = max(0, top - pad), min(height, bottom + pad)
+            left, right = max(0, left - pad), min(width, right + pad)
+            region = frame[top:bottom, left:right]
+            if style == "pixelate":
+                small = cv2.resize(region, (max(1, (right - left) // block), max(1, (bottom - top) // block)),
+                                   interpolation=cv2.INTER_LINEAR)
+                frame[top:bottom, left:right] = cv2.resize(small, (right - left, bottom - top),
+                                                           interpolation=cv2.INTER_NEAREST)
+            else:
+                kernel = max(3, ((right - left) // 3) | 1)
+                frame[top:bottom, left:right] = cv2.GaussianBlur(region, (kernel, kernel), 0)
+        return [{"box": list(face), "redacted": face in redacted} for face in faces]
+
+    def redact_image(self, image_path: str, image_format: str, keep_largest: bool, style: str, block: int,
+                     padding: float) -> Optional[Dict]:
+        """Redact faces in any image, returning the result encoded as `image_format`
+        in base64 so it never touches the disk"""
+        frame = self.memory_images.get(image_path)
+        if frame is None:
+            frame = cv2.imread(image_path)
+        if frame is None:
+            print(f"Error: could not read image {image_path}")
+            return None
+        frame = frame.copy()
+        faces = self.redact_frame(frame, keep_largest, style, block, padding)
+        encoded, data = cv2.imencode("." + image_format, frame)
+        if not encoded:
+            print(f"Error: could not encode the redacted image as {image_format}")
+            return None
+        return {"faces": faces, "image": base64.b64encode(data.tobytes()).decode("ascii")}
+
+    def stored_path(self, path: str) -> Optional[str]:
+        """Absolute path of a frame on disk, None for frames only held in memory"""
+        return None if path in self.memory_images else os.path.abspath(path)
+
+    def receive_image(self) -> None:
+        """Decode an image sent on stdin into memory, as the frame named '-'"""
+        frame = cv2.imdecode(np.frombuffer(sys.stdin.buffer.read(), np.uint8), cv2.IMREAD_COLOR)
+        if frame is None:
+            print("Error: could not decode the image on stdin")
+            return
+        self.memory_images["-"] = frame
+
     def auto_capture_image(self, save_path: str, delay_seconds: int = 2) -> bool:
         """Auto-capture image from camera after delay"""
         print(f"Initializing camera for auto-capture...")
 
-        cap = cv2.VideoCapture(0)
+        cap = self.open_capture()
         if not cap.isOpened():
             print("Error: Could not open camera")
             return False
 
+        if self.video_file:
+            # A recording needs no warm-up or countdown: each capture is its next frame
+            ret, frame = cap.read()
+            self.report_video_position(cap)
+            cap.release()
+            if not ret:
+                print("Error: No frames left in the video file")
+                return False
+            self.write_image(save_path, frame)
+            print(f"Image captured: {save_path}")
+            return True
+
         print(f"Camera ready! Auto-capturing in {delay_seconds} seconds...")
         print("Look directly at the camera and stay still...")
 
@@ -81,7 +341,7 @@ class SimpleFaceAuth:
         # Capture the image
         ret, frame = cap.read()
         if ret:
-            cv2.imwrite(save_path, frame)
+            self.write_image(save_path, frame)
             print(f"Image captured: {save_path}")
 
             # Show captured image briefly
@@ -98,34 +358,108 @@ class SimpleFaceAuth:
             cv2.destroyAllWindows()
             return False
 
-    def detect_and_encode_face(self, image_path: str) -> Optional[np.ndarray]:
+    def assess_quality(self, image: np.ndarray, face_location: Tuple[int, int, int, int]) -> Dict[str, float]:
+        """Score a detected face by sharpness and size within the frame (0.0-1.0)"""
+        top, right, bottom, left = face_location
+        face = image[top:bottom, left:right]
+        gray = cv2.cvtColor(face, cv2.COLOR_RGB2GRAY)
+        sharpness = float(cv2.Laplacian(gray, cv2.CV_64F).var())
+        face_ratio = ((bottom - top) * (right - left)) / float(image.shape[0] * image.shape[1])
+        score = 0.5 * min(1.0, sharpness / 100.0) + 0.5 * min(1.0, face_ratio / 0.1)
+        return {
+            "score": round(score, 3),
+            "sharpness": round(sharpness, 1),
+            "face_ratio": round(face_ratio, 4)
+        }
+
+    def preprocess(self, image: np.ndarray) -> np.ndarray:
+        """Apply the active profile's histogram equalization and gamma correction"""
+        if self.preprocessing.get("equalize"):
+            lab = cv2.cvtColor(image, cv2.COLOR_RGB2LAB)
+            clahe = cv2.createCLAHE(clipLimit=2.0, tileGridSize=(8, 8))
+            lab[:, :, 0] = clahe.apply(lab[:, :, 0])
+            image = cv2.cvtColor(lab, cv2.COLOR_LAB2RGB)
+        gamma = self.preprocessing.get("gamma")
+        if gamma:
+            table = np.array([((i / 255.0) ** gamma) * 255 for i in range(256)]).astype("uint8")
+            image = cv2.LUT(image, table)
+        return image
+
+    def measure_brightness(self, image_path: str) -> Optional[float]:
+        """Mean gray level of a frame (0-255)"""
+        if not self.has_image(image_path):
+            return None
+        gray = cv2.cvtColor(self.load_rgb(image_path), cv2.COLOR_RGB2GRAY)
+        return round(float(gray.mean()), 1)
+
+    def detect_and_encode_face(self, image_path: str, timings: Dict = None) -> Optional[np.ndarray]:
         """Detect and encode a single face"""
+        encoding, _ = self.detect_and_encode_face_with_quality(image_path, timings)
+        return encoding
+
+    def detect_and_encode_face_with_quality(self, image_path: str, timings: Dict = None) -> Tuple[Optional[np.ndarray], Optional[Dict[str, float]]]:
+        """Detect and encode a single face, also returning its quality assessment
+
+        When given, `timings` receives the detect_ms and encode_ms of this call.
+        """
+        timings = timings if timings is not None else {}
         try:
+            started = time.time()
+
             # Load image
-            image = face_recognition.load_image_file(image_path)
+            image = self.preprocess(self.load_rgb(image_path))
 
             # Find face locations
             face_locations = face_recognition.face_locations(image, model="hog")
+            timings["detect_ms"] = elapsed_ms(started)
 
             if not face_locations:
                 print("No face detected in image")
-                return None
+                return None, None
 
             if len(face_locations) > 1:
                 print(f"Multiple faces detected ({len(face_locations)}), using the first one")
 
             # Generate face encoding
+            started = time.time()
             face_encodings = face_recognition.face_encodings(image, face_locations)
+            timings["encode_ms"] = elapsed_ms(started)
 
             if face_encodings:
                 print(f"Face encoding generated successfully")
-                return face_encodings[0]
+                return face_encodings[0], self.assess_quality(image, face_locations[0])
             else:
                 print("Failed to generate face encoding")
-                return None
+                return None, None
 
         except Exception as e:
             print(f"Error processing image: {e}")
+            return None, None
+
+    def save_thumbnail(self, image_path: str, user_id: str, size: int = 96) -> Optional[str]:
+        """Save a small square crop of the face for admin and welcome screens"""
+        if self.strict_privacy:
+            return None
+        try:
+            image = face_recognition.load_image_file(image_path)
+            face_locations = face_recognition.face_locations(image, model="hog")
+            if not face_locations:
+                return None
+
+            top, right, bottom, left = face_locations[0]
+            pad = int(0.2 * (bottom - top))
+            top, left = max(0, top - pad), max(0, left - pad)
+            bottom, right = min(image.shape[0], bottom + pad), min(image.shape[1], right + pad)
+
+            face = cv2.cvtColor(image[top:bottom, left:right], cv2.COLOR_RGB2BGR)
+            thumbnail = cv2.resize(face, (size, size), interpolation=cv2.INTER_AREA)
+
+            os.makedirs(self.thumbnails_dir, exist_ok=True)
+            thumbnail_path = os.path.join(self.thumbnails_dir, f"{user_id}.jpg")
+            cv2.imwrite(thumbnail_path, thumbnail, [cv2.IMWRITE_JPEG_QUALITY, 85])
+            return thumbnail_path
+        except Exception as e:
+            print(f"Warning: Failed to create thumbnail: {e}")
             return None
 
     def register_user(self, user_id: str, num_samples: int = 3, generated_dir: str = "generated") -> bool:
@@ -134,111 +468,194 @@ class SimpleFaceAuth:
         print(f"Will capture {num_samples} samples")
         print(f"Generated directory: {generated_dir}")
 
-        os.makedirs("captured_images", exist_ok=True)
         os.makedirs(generated_dir, exist_ok=True)
-        face_encodings = []
-
-        for i in range(num_samples):
+        progress = self.load_progress(user_id, num_samples)
+        face_encodings = progress["face_encodings"]
+        sample_reports = progress["samples"]
+        best_image = tuple(progress["best_image"] or (-1.0, None))
+        if sample_reports:
+            print(f"Resuming after sample {len(sample_reports)}/{num_samples}")
+
+        for i in range(len(sample_reports), num_samples):
             print(f"\n--- Sample {i+1}/{num_samples} ---")
 
-            # Capture image
-            timestamp = datetime.now().strftime("%Y%m%d_%H%M%S_%f")
-            image_path = f"captured_images/registration_{user_id}_{timestamp}_sample{i+1}.jpg"
-
-            if not self.auto_capture_image(image_path, delay_seconds=2):
-                print(f"Failed to capture sample {i+1}")
-                continue
+            for _ in range(DUPLICATE_SAMPLE_RETRIES + 1):
+                # Capture image
+                timestamp = datetime.now().strftime("%Y%m%d_%H%M%S_%f")
+                image_path = os.path.join(self.captures_dir, f"registration_{user_id}_{timestamp}_sample{i+1}.jpg")
+                if not self.auto_capture_image(image_path, delay_seconds=2):
+                    status = "capture_failed"
+                    break
+
+                # Process image
+                encoding, quality = self.detect_and_encode_face_with_quality(image_path)
+                if encoding is None:
+                    status = "no_face"
+                    break
+                previous = [np.array(face_encodings[-1]["encoding"])] if face_encodings else []
+                if previous and face_recognition.face_distance(previous, encoding)[0] < DUPLICATE_SAMPLE_EPSILON:
+                    # Another copy of the same frame adds nothing; ask for a different pose
+                    print("Sample is nearly identical to the previous one - please turn your head slightly")
+                    self.discard_image(image_path)
+                    status = "duplicate"
+                    continue
+                status = "stored"
+                break
 
-            # Process image
-            encoding = self.detect_and_encode_face(image_path)
-            if encoding is not None:
+            if status == "stored":
                 face_encodings.append({
                     "encoding": encoding.tolist(),
                     "timestamp": datetime.now().isoformat(),
-                    "image_path": image_path,
-                    "sample_id": f"{user_id}_{timestamp}"
+                    "image_path": None if self.strict_privacy else os.path.relpath(image_path, self.data_dir),
+                    "sample_id": new_sample_id(),
+                    "quality": quality["score"]
                 })
-                print(f"Sample {i+1} processed successfully")
+                sample_reports.append({"index": i + 1, "status": "stored", "quality": quality})
+                if quality["score"] > best_image[0]:
+                    best_image = (quality["score"], image_path)
+                print(f"Sample {i+1} processed successfully (quality: {quality['score']:.2f})")
             else:
-                print(f"Failed to process sample {i+1}")
+                print(f"Failed to {'capture' if status == 'capture_failed' else 'process'} sample {i+1} ({status})")
+                sample_reports.append({"index": i + 1, "status": status, "quality": None})
+            self.save_progress(user_id, num_samples, sample_reports, face_encodings, best_image)
+
+        outcome = {
+            "username": user_id,
+            "samples_requested": num_samples,
+            "samples_captured": len(face_encodings),
+            "samples": sample_reports,
+            "generated_file": None,
+            "thumbnail_file": None,
+            "fully_enrolled": False
+        }
 
         if not face_encodings:
             print("No valid face samples captured")
+            self.clear_progress(user_id)
+            emit_result(outcome)
             return False
 
         # Store in database
         if "users" not in self.database:
             self.database["users"] = {}
 
+        outcome["thumbnail_file"] = self.save_thumbnail(best_image[1], user_id)
+        for sample in face_encodings:
+            if sample["image_path"]:
+                self.blur_faces(os.path.join(self.data_dir, sample["image_path"]))
+        self.memory_images.clear()
+
+        # Metadata set by the Rust side survives re-registration
+        metadata = self.database["users"].get(user_id, {}).get("metadata")
+
         self.database["users"][user_id] = {
             "user_id": user_id,
             "face_encodings": face_encodings,
             "enrollment_date": datetime.now().isoformat(),
             "sample_count": len(face_encodings)
         }
+        if metadata:
+            self.database["users"][user_id]["metadata"] = metadata
 
         self.save_database()
 
         # Save user's face encodings to specified generated directory
         generated_file = os.path.join(generated_dir, f"{user_id}.json")
-        user_data = {
-            "user_id": user_id,
-            "face_encodings": face_encodings,
-            "enrollment_date": datetime.now().isoformat(),
-            "sample_count": len(face_encodings)
-        }
+        user_data = dict(self.database["users"][user_id])
 
         try:
             with open(generated_file, 'w') as f:
                 json.dump(user_data, f, indent=2)
             print(f"✅ User data saved to: {generated_file}")
+            outcome["generated_file"] = os.path.abspath(generated_file)
         except Exception as e:
             print(f"⚠️ Warning: Failed to save to {generated_dir}/ directory: {e}")
 
+        outcome["fully_enrolled"] = outcome["generated_file"] is not None and len(face_encodings) == num_samples
+
         print(f"Registration complete! {len(face_encodings)} samples stored for {user_id}")
+        self.clear_progress(user_id)
+        emit_result(outcome)
         return True
 
-    def authenticate_user(self, tolerance: float = 0.6, source_dir: str = "source") -> bool:
+    def authenticate_user(self, tolerance: float = 0.6, source_dir: str = "source", image_path: str = None, preprocessing: Dict = None) -> bool:
         """Authenticate user by matching against files in specified source directory"""
+        result = self.match_user(tolerance, source_dir, image_path, preprocessing)
+        emit_result(result)
+        return result["is_match"]
+
+    def match_user(self, tolerance: float, source_dir: str, image_path: str = None, preprocessing: Dict = None) -> Dict:
+        """Capture a face (or use image_path) and compare it against the source directory, returning the decision"""
+        result = self.match_frame(tolerance, source_dir, image_path, preprocessing)
+        if result["image_path"]:
+            self.blur_faces(result["image_path"])
+        return result
+
+    def match_frame(self, tolerance: float, source_dir: str, image_path: str = None, preprocessing: Dict = None) -> Dict:
+        """Decision of match_user, before the frame is blurred"""
+        self.preprocessing = preprocessing or {}
+        result = {
+            "is_match": False,
+            "matched_user": None,
+            "closest_user": None,
+            "distance": None,
+            "confidence": None,
+            "threshold": tolerance,
+            "runner_up": None,
+            "brightness": None,
+            "image_path": None,
+            "timings": {}
+        }
+        timings = result["timings"]
+
         print("Starting authentication...")
         print(f"Source directory: {source_dir}")
 
-        # Capture authentication image
-        timestamp = datetime.now().strftime("%Y%m%d_%H%M%S")
-        auth_image_path = f"captured_images/authentication_{timestamp}.jpg"
+        # Nobody to match: don't make the user face the camera for nothing
+        json_files = [f for f in os.listdir(source_dir) if f.endswith('.json')] if os.path.isdir(source_dir) else []
+        if not json_files:
+            print(f"No user files found in '{source_dir}' directory")
+            print(f"Please register a user and promote their credential to '{source_dir}'")
+            result["denial"] = {"code": "no_users_enrolled"}
+            return result
+
+        if image_path:
+            # Replaying a recorded frame
+            if not self.has_image(image_path):
+                print(f"Error: image '{image_path}' does not exist")
+                return result
+            auth_image_path = image_path
+        else:
+            # Capture authentication image
+            timestamp = datetime.now().strftime("%Y%m%d_%H%M%S")
+            auth_image_path = os.path.join(self.captures_dir, f"authentication_{timestamp}.jpg")
 
-        os.makedirs("captured_images", exist_ok=True)
+            started = time.time()
+            captured = self.auto_capture_image(auth_image_path, delay_seconds=2)
+            timings["capture_ms"] = elapsed_ms(started)
+            if not captured:
+                print("Failed to capture authentication image")
+                return result
 
-        if not self.auto_capture_image(auth_image_path, delay_seconds=2):
-            print("Failed to capture authentication image")
-            return False
+        result["image_path"] = self.stored_path(auth_image_path)
+        result["brightness"] = self.measure_brightness(auth_image_path)
 
         # Process authentication image
-        auth_encoding = self.detect_and_encode_face(auth_image_path)
+        auth_encoding = self.detect_and_encode_face(auth_image_path, timings)
         if auth_encoding is None:
             print("No face detected in authentication image")
-            return False
-
-        # Load face encodings from specified source directory
-        if not os.path.exists(source_dir):
-            print(f"Error: '{source_dir}' directory does not exist")
-            print(f"Please create '{source_dir}' directory and add user face encoding files")
-            return False
-
-        # Get all JSON files from source/ directory
-        json_files = [f for f in os.listdir(source_dir) if f.endswith('.json')]
-
-        if not json_files:
-            print(f"No user files found in '{source_dir}' directory")
-            print(f"Please add user face encoding JSON files to '{source_dir}' directory")
-            return False
+            result["denial"] = {"code": "no_face"}
+            return result
 
         print(f"Found {len(json_files)} user file(s) in '{source_dir}' directory")
         print(f"Comparing against users from source/ directory...")
 
         best_match = None
         best_distance = float('inf')
+        runner_up = (None, float('inf'))
+        best_samples = []
         users_loaded = 0
+        match_started = time.time()
 
         for json_file in json_files:
             file_path = os.path.join(source_dir, json_file)
@@ -251,7 +668,8 @@ class SimpleFaceAuth:
                     print(f"Warning: No user_id in {json_file}, skipping")
                     continue
 
-                face_encodings_data = user_data.get("face_encodings", [])
+                # Samples quarantined as outliers are held out until reviewed
+                face_encodings_data = [s for s in user_data.get("face_encodings", []) if not s.get("quarantine")]
                 if not face_encodings_data:
                     print(f"Warning: No face encodings in {json_file}, skipping")
                     continue
@@ -264,16 +682,35 @@ class SimpleFaceAuth:
                 print(f"User {user_id}: distance = {min_distance:.3f}")
 
                 if min_distance < best_distance:
+                    runner_up = (best_match, best_distance)
                     best_distance = min_distance
                     best_match = user_id
+                    best_samples = sorted(
+                        ({"sample_id": sample.get("sample_id", ""), "distance": float(distance)}
+                         for sample, distance in zip(face_encodings_data, distances)),
+                        key=lambda sample: sample["distance"])
+                elif min_distance < runner_up[1]:
+                    runner_up = (user_id, min_distance)
 
             except Exception as e:
                 print(f"Error loading {json_file}: {e}")
                 continue
 
+        timings["match_ms"] = elapsed_ms(match_started)
+        # Lets the caller explain the decision sample by sample
+        result["sample_distances"] = best_samples
+        if runner_up[0]:
+            # Lets the caller refuse matches that a lookalike nearly won
+            result["runner_up"] = {
+                "user_id": runner_up[0],
+                "distance": float(runner_up[1]),
+                "is_match": bool(runner_up[1] <= tolerance)
+            }
+
         if users_loaded == 0:
             print("No valid user files could be loaded from source/ directory")
-            return False
+            result["denial"] = {"code": "no_users_enrolled"}
+            return result
 
         # Check if match is within tolerance
         if best_match and best_distance <= tolerance:
@@ -282,13 +719,25 @@ class SimpleFaceAuth:
             print(f"User: {best_match}")
             print(f"Distance: {best_distance:.3f}")
             print(f"Confidence: {confidence:.1%}")
-            return True
+            result.update({
+                "is_match": True,
+                "matched_user": best_match,
+                "closest_user": best_match,
+                "distance": float(best_distance),
+                "confidence": float(confidence)
+            })
+            return result
         else:
             print(f"Authentication failed!")
             if best_match:
                 print(f"Closest match: {best_match} (distance: {best_distance:.3f})")
                 print(f"Threshold: {tolerance:.3f}")
-            return False
+                result.update({
+                    "closest_user": best_match,
+                    "distance": float(best_distance),
+                    "confidence": float(max(0, 1 - best_distance))
+                })
+            return result
 
     def export_user(self, user_id: str, export_path: str = None) -> bool:
         """Export a user's face data to a file"""
@@ -299,11 +748,10 @@ class SimpleFaceAuth:
         # Auto-generate filename if not provided
         if export_path is None:
             # Create exports directory if it doesn't exist
-            export_dir = "exported_credentials"
-            os.makedirs(export_dir, exist_ok=True)
+            os.makedirs(self.exports_dir, exist_ok=True)
 
             timestamp = datetime.now().strftime("%Y%m%d_%H%M%S")
-            export_path = f"{export_dir}/{user_id}_credentials_{timestamp}.json"
+            export_path = os.path.join(self.exports_dir, f"{user_id}_credentials_{timestamp}.json")
 
         user_data = {
             "user_id": user_id,
@@ -348,6 +796,306 @@ class SimpleFaceAuth:
             print(f"Error importing user: {e}")
             return False
 
+    def warm_up(self, camera: bool = False) -> Dict:
+        """Run the detector and encoder once so their models are loaded, optionally opening the camera"""
+        started = time.time()
+        blank = np.zeros((64, 64, 3), dtype=np.uint8)
+        face_recognition.face_locations(blank)
+        face_recognition.face_encodings(blank, known_face_locations=[(0, 64, 64, 0)])
+
+        camera_ready = None
+        if camera:
+            cap = self.open_capture()
+            camera_ready = bool(cap.isOpened() and cap.read()[0])
+            cap.release()
+            if not camera_ready:
+                print("Warning: Could not read from camera during warm-up")
+
+        return {"camera_ready": camera_ready, "elapsed_ms": elapsed_ms(started)}
+
+    def capture_images(self, label: str, count: int) -> Dict:
+        """Capture frames into the captures directory without encoding them, None where capture failed"""
+        images = []
+        for i in range(count):
+            timestamp = datetime.now().strftime("%Y%m%d_%H%M%S_%f")
+            image_path = os.path.join(self.captures_dir, f"{label}_{timestamp}_frame{i+1}.jpg")
+            images.append(image_path if self.auto_capture_image(image_path, delay_seconds=2) else None)
+        return {"images": images}
+
+    def prefilter(self, image_path: str, embed: bool, out_dir: str, size: int = 150) -> Dict:
+        """Device-side half of hybrid mode: detect and score the face, then either
+        encode it or write an aligned chip for the server to encode"""
+        image = face_recognition.load_image_file(image_path)
+        face_locations = face_recognition.face_locations(image, model="hog")
+        if not face_locations:
+            print("No face detected in image")
+            return {"face": False, "quality": None, "encoding": None, "chip": None}
+
+        result = {"face": True, "quality": self.assess_quality(image, face_locations[0]), "encoding": None, "chip": None}
+        if embed:
+            result["encoding"] = face_recognition.face_encodings(image, face_locations[:1])[0].tolist()
+        else:
+            result["chip"] = self.face_chips([image_path], out_dir, size)["chips"][0]
+        return result
+
+    def encode_images(self, image_paths: List[str]) -> Dict:
+        """Encode the first face of each image, None where no face was found"""
+        encodings = []
+        for image_path in image_paths:
+            encoding = self.detect_and_encode_face(image_path)
+            encodings.append(encoding.tolist() if encoding is not None else None)
+        return {"encodings": encodings}
+
+    def encode_document(self, image_path: str) -> Dict:
+        """Encode the portrait on an identity document photo
+
+        Portraits are small, so detection upsamples the image. Glare from
+        laminated cards is filled in from the surrounding pixels before
+        detection; the largest face is the main portrait, not a ghost image.
+        """
+        image = face_recognition.load_image_file(image_path)
+        saturated = (image.min(axis=2) >= 250).astype(np.uint8)
+        glare_removed = False
+        if saturated.mean() > 0.01:
+            mask = cv2.dilate(saturated * 255, np.ones((5, 5), np.uint8))
+            image = cv2.inpaint(image, mask, 3, cv2.INPAINT_TELEA)
+            glare_removed = True
+
+        face_locations = face_recognition.face_locations(image, number_of_times_to_upsample=2, model="hog")
+        if not face_locations:
+            lab = cv2.cvtColor(image, cv2.COLOR_RGB2LAB)
+            lab[:, :, 0] = cv2.createCLAHE(clipLimit=2.0, tileGridSize=(8, 8)).apply(lab[:, :, 0])
+            image = cv2.cvtColor(lab, cv2.COLOR_LAB2RGB)
+            face_locations = face_recognition.face_locations(image, number_of_times_to_upsample=2, model="hog")
+        if not face_locations:
+            print("No face detected on the document")
+            return {"encoding": None, "faces": 0, "glare": round(float(saturated.mean()), 4), "glare_removed": glare_removed}
+
+        top, right, bottom, left = max(face_locations, key=lambda box: (box[2] - box[0]) * (box[1] - box[3]))
+        glare = float(saturated[top:bottom, left:right].mean())
+        encoding = face_recognition.face_encodings(image, [(top, right, bottom, left)], num_jitters=5)[0]
+        return {"encoding": encoding.tolist(), "faces": len(face_locations), "glare": round(glare, 4), "glare_removed": glare_removed}
+
+    def face_chips(self, image_paths: List[str], out_dir: str, size: int = 150) -> Dict:
+        """Write dlib-style aligned face chips (5-point alignment, 0.25 padding) to out_dir"""
+        import dlib
+
+        os.makedirs(out_dir, exist_ok=True)
+        chips = []
+        for image_path in image_paths:
+            try:
+                image = face_recognition.load_image_file(image_path)
+                face_locations = face_recognition.face_locations(image, model="hog")
+                if not face_locations:
+                    print(f"No face detected in {image_path}")
+                    chips.append(None)
+                    continue
+
+                top, right, bottom, left = face_locations[0]
+                shape = face_recognition.api.pose_predictor_5_point(image, dlib.rectangle(left, top, right, bottom))
+                chip = dlib.get_face_chip(image, shape, size=size, padding=0.25)
+
+                chip_path = os.path.join(out_dir, os.path.splitext(os.path.basename(image_path))[0] + ".jpg")
+                cv2.imwrite(chip_path, cv2.cvtColor(chip, cv2.COLOR_RGB2BGR))
+                chips.append(chip_path)
+            except Exception as e:
+                print(f"Error creating chip for {image_path}: {e}")
+                chips.append(None)
+        return {"chips": chips}
+
+    def crop_faces(self, image_path: str, out_dir: str, padding: float = 0.25) -> Optional[Dict]:
+        """Write a padded crop of every face in an image to out_dir, largest first"""
+        frame = cv2.imread(image_path)
+        if frame is None:
+            print(f"Error: could not read image {image_path}")
+            return None
+        os.makedirs(out_dir, exist_ok=True)
+        faces = face_recognition.face_locations(cv2.cvtColor(frame, cv2.COLOR_BGR2RGB), model="hog")
+        faces.sort(key=lambda box: (box[2] - box[0]) * (box[1] - box[3]), reverse=True)
+        height, width = frame.shape[:2]
+        crops = []
+        for index, (top, right, bottom, left) in enumerate(faces):
+            pad = int((bottom - top) * padding)
+            crop = frame[max(0, top - pad):min(height, bottom + pad), max(0, left - pad):min(width, right + pad)]
+            crop_path = os.path.join(out_dir, f"face_{index}.png")
+            if not cv2.imwrite(crop_path, crop):
+                print(f"Error: could not write {crop_path}")
+                return None
+            crops.append(os.path.abspath(crop_path))
+        return {"crops": crops}
+
+    def watch(self, tolerance: float, source_dir: str, preprocessing: Dict, min_changed: float,
+              pixel_threshold: int, face_check: bool, cooldown: float, tracker: "FaceTracker" = None,
+              attention: bool = False) -> bool:
+        """Authenticate continuously, running the full match only on frames where
+        something moved and a cascade detector sees a face
+
+        With a tracker, each face is followed across frames and matched once
+        when it enters the view instead of after every cooldown. With attention,
+        the landmarks of every tracked face are reported on each frame."""
+        cap = self.open_capture()
+        if not cap.isOpened():
+            print("Error: Could not open " + ("video file" if self.video_file else "camera"))
+            return False
+        # A video is analyzed frame by frame, so its cooldown is counted in frames, not seconds
+        cooldown_frames = int(round(cooldown * (cap.get(cv2.CAP_PROP_FPS) or 25))) if self.video_file else None
+
+        cascade = None
+        if face_check or tracker is not None:
+            cascade = cv2.CascadeClassifier(os.path.join(cv2.data.haarcascades, "haarcascade_frontalface_default.xml"))
+        stats = {"frames": 0, "skipped_still": 0, "skipped_empty": 0, "tracked": 0, "attempts": 0}
+        previous = None
+        print("Watching the camera...")
+
+        def emit(result: Dict) -> None:
+            # The caller may stop after any result, so it learns how far into a video it got first
+            self.report_video_position(cap)
+            emit_result(result)
+
+        try:
+            while True:
+                ret, frame = cap.read()
+                if not ret and self.video_file:
+                    # Faces still in view at the end of the video leave with it
+                    for track in tracker.tracks if tracker is not None else []:
+                        emit({
+                            "track": {"event": "exited", "track_id": track["id"], "user_id": track["user_id"], "frames": track["frames"]},
+                            "scene": dict(stats)
+                        })
+                    emit({"end": True, "scene": dict(stats)})
+                    return True
+                if not ret:
+                    print("Error: Failed to read from camera")
+                    return False
+                stats["frames"] += 1
+
+                # Cheap checks on a small grayscale copy before any encoding
+                scale = frame.shape[1] / WATCH_FRAME_WIDTH
+                gray = cv2.cvtColor(cv2.resize(frame, (WATCH_FRAME_WIDTH, int(frame.shape[0] / scale))), cv2.COLOR_BGR2GRAY)
+                gray = cv2.GaussianBlur(gray, (5, 5), 0)
+                changed = 1.0 if previous is None else np.count_nonzero(cv2.absdiff(gray, previous) > pixel_threshold) / gray.size
+                previous = gray
+                if changed < min_changed:
+                    stats["skipped_still"] += 1
+                    continue
+                faces = []
+                if cascade is not None:
+                    faces = [tuple(int(v) for v in face) for face in cascade.detectMultiScale(gray, scaleFactor=1.2, minNeighbors=4, minSize=(20, 20))]
+
+                if tracker is None:
+                    if cascade is not None and not faces:
+                        stats["skipped_empty"] += 1
+                        continue
+                    stats["attempts"] += 1
+                    image_path = self.save_watch_frame(frame)
+                    result = self.match_user(tolerance, source_dir, image_path, preprocessing)
+                    self.memory_images.pop(image_path, None)
+                    result["scene"] = dict(stats)
+                    emit(result)
+
+                    if cooldown_frames is not None:
+                        for _ in range(cooldown_frames):
+                            if not cap.grab():
+                                break
+                            stats["frames"] += 1
+                        continue
+                    # Keep reading so the next frame checked is a current one
+                    until = time.time() + cooldown
+                    while time.time() < until:
+                        if cap.read()[0]:
+                            stats["frames"] += 1
+                    continue
+
+                entered, exited = tracker.update(faces)
+                if not faces:
+                    stats["skipped_empty"] += 1
+                elif not entered:
+                    stats["tracked"] += 1
+                for track in exited:
+                    emit({
+                        "track": {"event": "exited", "track_id": track["id"], "user_id": track["user_id"], "frames": track["frames"]},
+                        "scene": dict(stats)
+                    })
+                for track in entered:
+                    # Identify just this face, cut out with some margin
+                    x, y, w, h = (int(v * scale) for v in track["box"])
+                    margin_x, margin_y = w // 2, h // 2
+                    crop = frame[max(0, y - margin_y):y + h + margin_y, max(0, x - margin_x):x + w + margin_x]
+                    stats["attempts"] += 1
+                    image_path = self.save_watch_frame(crop)
+                    result = self.match_user(tolerance, source_dir, image_path, preprocessing)
+                    self.memory_images.pop(image_path, None)
+                    track["user_id"] = result["matched_user"]
+                    result["track"] = {"event": "entered", "track_id": track["id"]}
+                    result["scene"] = dict(stats)
+                    emit(result)
+                if attention:
+                    self.emit_track_landmarks(frame, scale, tracker, stats)
+        finally:
+            cap.release()
+
+    def emit_track_landmarks(self, frame: np.ndarray, scale: float, tracker: "FaceTracker", stats: Dict) -> None:
+        """Report eye, nose and chin positions of the faces tracked in this frame"""
+        visible = [track for track in tracker.tracks if track["missed"] == 0]
+        if not visible:
+            return
+        rgb = cv2.cvtColor(frame, cv2.COLOR_BGR2RGB)
+        locations = []
+        for track in visible:
+            x, y, w, h = (int(v * scale) for v in track["box"])
+            locations.append((y, x + w, y + h, x))
+        for track, landmarks in zip(visible, face_recognition.face_landmarks(rgb, locations)):
+            if not all(part in landmarks for part in ("left_eye", "right_eye", "nose_tip", "chin")):
+                continue
+            center = lambda points: [float(np.mean([p[0] for p in points])), float(np.mean([p[1] for p in points]))]
+            # Eyes in image order, whichever side of the subject they are
+            left_eye, right_eye = sorted([center(landmarks["left_eye"]), center(landmarks["right_eye"])])
+            chin = max(landmarks["chin"], key=lambda p: p[1])
+            emit_result({
+                "track": {
+                    "event": "landmarks",
+                    "track_id": track["id"],
+                    "landmarks": {"left_eye": left_eye, "right_eye": right_eye, "nose_tip": center(landmarks["nose_tip"]), "chin": [float(chin[0]), float(chin[1])]},
+                },
+                "scene": dict(stats)
+            })
+
+    def save_watch_frame(self, frame: np.ndarray) -> str:
+        """Save a frame picked in watch mode to the captures directory"""
+        timestamp = datetime.now().strftime("%Y%m%d_%H%M%S_%f")
+        image_path = os.path.join(self.captures_dir, f"watch_{timestamp}.jpg")
+        self.write_image(image_path, frame)
+        return image_path
+
+    def serve(self) -> None:
+        """Answer JSON requests from stdin, one per line, keeping models loaded between them"""
+        emit_result({"ready": True, "pid": os.getpid()})
+        while True:
+            line = sys.stdin.readline()
+            if not line:
+                break
+            line = line.strip()
+            if not line:
+                continue
+            try:
+                request = json.loads(line)
+                op = request.get("op")
+                if op == "ping":
+                    emit_result({"ok": True})
+                elif op == "warm":
+                    emit_result(self.warm_up(request.get("camera", False)))
+                elif op == "auth":
+                    emit_result(self.match_user(
+                        request.get("tolerance", 0.6),
+                        request.get("source_dir", "source"),
+                        request.get("image"),
+                        request.get("preprocessing")
+                    ))
+                else:
+                    emit_result({"error": f"Unknown operation: {op}"})
+            except Exception as e:
+                emit_result({"error": str(e)})
+
     def list_users(self) -> None:
         """List all users in the database"""
         if not self.database["users"]:
@@ -357,28 +1105,61 @@ class SimpleFaceAuth:
         print(f"Users in database ({len(self.database['users'])} total):")
         for user_id, user_data in self.database["users"].items():
             num_encodings = len(user_data.get("face_encodings", []))
-            created = user_data.get("created_at", "Unknown")
-            print(f"  - {user_id}: {num_encodings} face samples (created: {created})")
+            created = user_data.get("enrollment_date", "Unknown")
+            display_name = user_data.get("metadata", {}).get("display_name")
+            label = f"{user_id} ({display_name})" if display_name else user_id
+            print(f"  - {label}: {num_encodings} face samples (created: {created})")
 
 def main():
     parser = argparse.ArgumentParser(description="Simple Face Authentication")
-    parser.add_argument("--mode", choices=["register", "auth", "export", "import", "list"], required=True)
+    parser.add_argument("--mode", choices=["register", "auth", "export", "import", "list", "serve", "warm", "encode", "document", "chips", "capture", "prefilter", "watch", "redact", "crops"], required=True)
     parser.add_argument("--user", type=str, default="user")
     parser.add_argument("--samples", type=int, default=3)
     parser.add_argument("--tolerance", type=float, default=0.6)
     parser.add_argument("--file", type=str, help="File path for export/import operations")
     parser.add_argument("--generated-dir", type=str, default="generated", help="Directory to save registered user files")
     parser.add_argument("--source-dir", type=str, default="source", help="Directory to load user files for authentication")
+    parser.add_argument("--data-dir", type=str, default=".", help="Directory for the database, captured images and exports")
+    parser.add_argument("--camera", action="store_true", help="Also open the camera in warm mode")
+    parser.add_argument("--camera-index", type=int, default=0, help="OpenCV index of the camera to capture from")
+    parser.add_argument("--video-file", type=str, help="Read frames from this video file instead of the camera")
+    parser.add_argument("--video-start", type=int, default=0, help="First frame of the video file to read")
+    parser.add_argument("--strict-privacy", action="store_true", help="Keep every image in memory, never writing frames or thumbnails")
+    parser.add_argument("--blur", choices=["off", "bystanders", "all"], default="off", help="Blur faces in captures kept on disk once matched")
+    parser.add_argument("--format", choices=["jpg", "png"], default="jpg", help="Encoding of the image redact mode returns")
+    parser.add_argument("--keep-largest", action="store_true", help="Leave the largest face alone in redact mode")
+    parser.add_argument("--style", choices=["blur", "pixelate"], default="blur", help="How redact mode hides faces")
+    parser.add_argument("--block", type=int, default=12, help="Pixel block size of the pixelate style")
+    parser.add_argument("--padding", type=float, default=0.25, help="Share of the face height added around redacted and cropped faces")
+    parser.add_argument("--image", type=str, help="Authenticate against this image instead of capturing from the camera")
+    parser.add_argument("--images", type=str, nargs="+", default=[], help="Images for encode and chips modes")
+    parser.add_argument("--out-dir", type=str, default="chips", help="Directory chips and crops modes write to")
+    parser.add_argument("--size", type=int, default=150, help="Side length of face chips in pixels")
+    parser.add_argument("--equalize", action="store_true", help="Equalize the histogram of authentication frames")
+    parser.add_argument("--gamma", type=float, help="Gamma correction applied to authentication frames")
+    parser.add_argument("--min-change", type=float, default=0.01, help="Fraction of pixels that must change between frames in watch mode")
+    parser.add_argument("--pixel-threshold", type=int, default=25, help="Gray level difference counted as a changed pixel in watch mode")
+    parser.add_argument("--no-face-check", action="store_true", help="Match every changed frame in watch mode, not only those with a face")
+    parser.add_argument("--cooldown", type=float, default=2.0, help="Seconds to skip after each attempt in watch mode")
+    parser.add_argument("--track", action="store_true", help="Follow faces across frames in watch mode, matching each once")
+    parser.add_argument("--track-overlap", type=float, default=0.3, help="Box overlap that continues a track")
+    parser.add_argument("--track-max-missed", type=int, default=10, help="Frames a track survives without its face")
+    parser.add_argument("--attention", action="store_true", help="Report landmarks of tracked faces on every frame")
+    parser.add_argument("--embed", action="store_true", help="Encode the face in prefilter mode instead of writing a chip")
 
     args = parser.parse_args()
 
-    face_auth = SimpleFaceAuth()
+    face_auth = SimpleFaceAuth(data_dir=args.data_dir, camera_index=args.camera_index, strict_privacy=args.strict_privacy,
+                               blur=args.blur, video_file=args.video_file, video_start=args.video_start)
+    if args.image == "-":
+        face_auth.receive_image()
 
     if args.mode == "register":
         success = face_auth.register_user(args.user, args.samples, args.generated_dir)
         sys.exit(0 if success else 1)
     elif args.mode == "auth":
-        success = face_auth.authenticate_user(args.tolerance, args.source_dir)
+        preprocessing = {"equalize": args.equalize, "gamma": args.gamma}
+        success = face_auth.authenticate_user(args.tolerance, args.source_dir, args.image, preprocessing)
         sys.exit(0 if success else 1)
     elif args.mode == "export":
         success = face_auth.export_user(args.user, args.file)
@@ -392,6 +1173,59 @@ def main():
     elif args.mode == "list":
         face_auth.list_users()
         sys.exit(0)
+    elif args.mode == "warm":
+        emit_result(face_auth.warm_up(args.camera))
+        sys.exit(0)
+    elif args.mode == "encode":
+        emit_result(face_auth.encode_images(args.images))
+        sys.exit(0)
+    elif args.mode == "document":
+        if not args.image:
+            print("Error: --image required for document mode")
+            sys.exit(1)
+        emit_result(face_auth.encode_document(args.image))
+        sys.exit(0)
+    elif args.mode == "chips":
+        emit_result(face_auth.face_chips(args.images, args.out_dir, args.size))
+        sys.exit(0)
+    elif args.mode == "prefilter":
+        if not args.image:
+            print("Error: --image required for prefilter mode")
+            sys.exit(1)
+        emit_result(face_auth.prefilter(args.image, args.embed, args.out_dir, args.size))
+        sys.exit(0)
+    elif args.mode == "redact":
+        if not args.image:
+            print("Error: --image required for redact mode")
+            sys.exit(1)
+        result = face_auth.redact_image(args.image, args.format, args.keep_largest, args.style, args.block,
+                                        args.padding)
+        if result is None:
+            sys.exit(1)
+        emit_result(result)
+        sys.exit(0)
+    elif args.mode == "crops":
+        if not args.image:
+            print("Error: --image required for crops mode")
+            sys.exit(1)
+        result = face_auth.crop_faces(args.image, args.out_dir, args.padding)
+        if result is None:
+            sys.exit(1)
+        emit_result(result)
+        sys.exit(0)
+    elif args.mode == "capture":
+        emit_result(face_auth.capture_images(args.user, args.samples))
+        sys.exit(0)
+    elif args.mode == "watch":
+        preprocessing = {"equalize": args.equalize, "gamma": args.gamma}
+        tracker = FaceTracker(args.track_overlap, args.track_max_missed) if args.track else None
+        success = face_auth.watch(args.tolerance, args.source_dir, preprocessing, args.min_change,
+                                  args.pixel_threshold, not args.no_face_check, args.cooldown, tracker,
+                                  args.attention)
+        sys.exit(0 if success else 1)
+    elif args.mode == "serve":
+        face_auth.serve()
+        sys.exit(0)
 
 if __name__ == "__main__":
     main()
\ No newline at end of file
@@ -49,7 +49,7 @@ if [ -f "./target/release/face_auth" ]; then
     echo "   ✅ EXISTS at ./target/release/face_auth"
     echo "   📏 Size: $SIZE"
 else
-    echo "   ❌ NOT BUILT (run: cargo build --release)"
+    echo "   ❌ NOT BUILT (run: cargo build --release --features cli)"
 fi
 echo ""
 
@@ -61,7 +61,7 @@ echo "════════════════════════
 ALL_GOOD=true
 
 if [ ! -f "./target/release/face_auth" ]; then
-    echo "⚠️  Need to build: cargo build --release"
+    echo "⚠️  Need to build: cargo build --release --features cli"
     ALL_GOOD=false
 fi
 
@@ -0,0 +1,339 @@
+use anyhow::{Result, anyhow, bail};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::badge::{self, Badge, WiegandFormat};
+use crate::face_storage::UserMetadata;
+
+/// Custom metadata field holding the user's group for [`OutputRule::groups`]
+pub const GROUP_FIELD: &str = "group";
+
+/// Where the kernel's sysfs GPIO interface lives
+pub const SYSFS_GPIO_ROOT: &str = "/sys/class/gpio";
+
+/// Wiegand data pulse width and the gap between bits
+const WIEGAND_PULSE: Duration = Duration::from_micros(50);
+const WIEGAND_INTERVAL: Duration = Duration::from_millis(2);
+
+/// Something to switch when access is granted
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Output {
+    /// Drive a GPIO pin active for `pulse_ms`, e.g. a door strike relay
+    Gpio {
+        pin: u32,
+        pulse_ms: u64,
+        /// Active means low, for relay boards that switch on a low input
+        #[serde(default)]
+        active_low: bool,
+    },
+    /// Run a program; `{user}` in the arguments is replaced by the username
+    Command { program: String, #[serde(default)] args: Vec<String> },
+    /// POST `{"user", "device_id", "timestamp"}` as JSON
+    Url { url: String },
+    /// Send the user's badge to a panel's Wiegand reader input by toggling
+    /// its DATA0/DATA1 lines
+    Wiegand {
+        d0_pin: u32,
+        d1_pin: u32,
+        #[serde(default)]
+        format: WiegandFormat,
+        /// For badge ids without a facility code
+        #[serde(default)]
+        facility_code: Option<u32>,
+    },
+    /// Act as an OSDP reader on a serial port (configured beforehand, e.g.
+    /// with `stty -F /dev/ttyUSB0 9600 raw`), reporting the user's badge as a
+    /// card read on the panel's next poll; secure channel isn't supported
+    Osdp {
+        device: PathBuf,
+        /// Reader address on the bus
+        #[serde(default)]
+        address: u8,
+        #[serde(default)]
+        format: WiegandFormat,
+        #[serde(default)]
+        facility_code: Option<u32>,
+    },
+}
+
+/// Outputs to trigger for some users or groups
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OutputRule {
+    #[serde(default)]
+    pub users: Vec<String>,
+    /// Matched against the user's `group` metadata field
+    #[serde(default)]
+    pub groups: Vec<String>,
+    pub outputs: Vec<String>,
+}
+
+impl OutputRule {
+    fn applies(&self, user: &str, metadata: Option<&UserMetadata>) -> bool {
+        let group = metadata.and_then(|m| m.custom.get(GROUP_FIELD));
+        self.users.iter().any(|u| u == user) || group.is_some_and(|g| self.groups.contains(g))
+    }
+}
+
+/// Named outputs and which users trigger them, as stored in a config file
+///
+/// ```json
+/// {
+///   "outputs": {
+///     "front-door": { "kind": "gpio", "pin": 17, "pulse_ms": 3000 },
+///     "server-room": { "kind": "url", "url": "http://10.0.0.5/open" }
+///   },
+///   "rules": [{ "groups": ["it"], "outputs": ["front-door", "server-room"] }],
+///   "default": ["front-door"]
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ActuatorConfig {
+    pub outputs: HashMap<String, Output>,
+    #[serde(default)]
+    pub rules: Vec<OutputRule>,
+    /// Outputs for granted users no rule applies to
+    #[serde(default)]
+    pub default: Vec<String>,
+}
+
+impl ActuatorConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path).map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        let config: Self = serde_json::from_str(&json).map_err(|e| anyhow!("Invalid actuator config {}: {}", path.display(), e))?;
+        config.validate().map_err(|e| anyhow!("Invalid actuator config {}: {}", path.display(), e))?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let referenced = self.rules.iter().flat_map(|r| &r.outputs).chain(&self.default);
+        if let Some(unknown) = referenced.into_iter().find(|name| !self.outputs.contains_key(*name)) {
+            bail!("Unknown output '{}'", unknown);
+        }
+        Ok(())
+    }
+}
+
+/// Triggers outputs when a user is granted access, turning the crate into a
+/// door controller, e.g. on a Raspberry Pi
+///
+/// Outputs run on a background thread so a slow relay or URL doesn't delay
+/// the decision; failures are logged.
+#[derive(Debug, Clone)]
+pub struct Actuator {
+    config: ActuatorConfig,
+    gpio_root: PathBuf,
+    agent: ureq::Agent,
+    /// Card reads waiting for the panel's next poll, by OSDP output
+    osdp: HashMap<String, Arc<Mutex<VecDeque<Vec<bool>>>>>,
+}
+
+impl Actuator {
+    /// Create the actuator, opening the serial port of every OSDP output and
+    /// answering the panel's polls on it from then on
+    pub fn new(config: ActuatorConfig) -> Result<Self> {
+        config.validate()?;
+        let mut osdp = HashMap::new();
+        for (name, output) in &config.outputs {
+            if let Output::Osdp { device, address, .. } = output {
+                osdp.insert(name.clone(), answer_polls(device, *address)?);
+            }
+        }
+        let agent = ureq::Agent::config_builder()
+            .http_status_as_error(false)
+            .timeout_global(Some(Duration::from_secs(5)))
+            .build()
+            .into();
+        Ok(Self { config, gpio_root: PathBuf::from(SYSFS_GPIO_ROOT), agent, osdp })
+    }
+
+    /// Use GPIO pins under `root` instead of [`SYSFS_GPIO_ROOT`]
+    pub fn with_gpio_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.gpio_root = root.into();
+        self
+    }
+
+    /// Names of the outputs `user` triggers: those of every rule that applies,
+    /// else the default ones
+    pub fn outputs_for(&self, user: &str, metadata: Option<&UserMetadata>) -> Vec<&str> {
+        let mut outputs: Vec<&str> = Vec::new();
+        for rule in self.config.rules.iter().filter(|r| r.applies(user, metadata)) {
+            for output in &rule.outputs {
+                if !outputs.contains(&output.as_str()) {
+                    outputs.push(output);
+                }
+            }
+        }
+        if outputs.is_empty() {
+            outputs = self.config.default.iter().map(String::as_str).collect();
+        }
+        outputs
+    }
+
+    /// Trigger the outputs of `user` in the background
+    pub fn trigger(&self, user: &str, metadata: Option<&UserMetadata>, device_id: Option<&str>) -> JoinHandle<()> {
+        let outputs: Vec<(String, Output)> = self.outputs_for(user, metadata).into_iter()
+            .map(|name| (name.to_string(), self.config.outputs[name].clone()))
+            .collect();
+        let actuator = self.clone();
+        let user = user.to_string();
+        let metadata = metadata.cloned();
+        let device_id = device_id.map(str::to_string);
+        thread::spawn(move || {
+            for (name, output) in outputs {
+                match actuator.run(&name, &output, &user, metadata.as_ref(), device_id.as_deref()) {
+                    Ok(()) => tracing::info!(output = %name, user = %user, "Output triggered"),
+                    Err(e) => tracing::warn!(output = %name, user = %user, "Failed to trigger output: {:#}", e),
+                }
+            }
+        })
+    }
+
+    fn run(&self, name: &str, output: &Output, user: &str, metadata: Option<&UserMetadata>, device_id: Option<&str>) -> Result<()> {
+        match output {
+            Output::Gpio { pin, pulse_ms, active_low } => {
+                let (on, off) = if *active_low { ("0", "1") } else { ("1", "0") };
+                let value = self.export_pin(*pin)?;
+                fs::write(&value, on)?;
+                thread::sleep(Duration::from_millis(*pulse_ms));
+                fs::write(&value, off)?;
+                Ok(())
+            }
+            Output::Command { program, args } => {
+                let status = Command::new(program)
+                    .args(args.iter().map(|arg| arg.replace("{user}", user)))
+                    .env("FACE_AUTH_USER", user)
+                    .stdin(Stdio::null())
+                    .status()
+                    .map_err(|e| anyhow!("Failed to run {}: {}", program, e))?;
+                if !status.success() {
+                    bail!("{} exited with {}", program, status);
+                }
+                Ok(())
+            }
+            Output::Url { url } => {
+                let body = serde_json::json!({ "user": user, "device_id": device_id, "timestamp": Utc::now() });
+                let response = self.agent.post(url).send_json(&body).map_err(|e| anyhow!("{} unreachable: {}", url, e))?;
+                if !response.status().is_success() {
+                    bail!("{} answered {}", url, response.status());
+                }
+                Ok(())
+            }
+            Output::Wiegand { d0_pin, d1_pin, format, facility_code } => {
+                let bits = Badge::from_metadata(metadata, *facility_code)?.wiegand_bits(*format)?;
+                let (d0, d1) = (self.export_pin(*d0_pin)?, self.export_pin(*d1_pin)?);
+                fs::write(&d0, "1")?;
+                fs::write(&d1, "1")?;
+                for bit in bits {
+                    let line = if bit { &d1 } else { &d0 };
+                    fs::write(line, "0")?;
+                    thread::sleep(WIEGAND_PULSE);
+                    fs::write(line, "1")?;
+                    thread::sleep(WIEGAND_INTERVAL);
+                }
+                Ok(())
+            }
+            Output::Osdp { format, facility_code, .. } => {
+                let bits = Badge::from_metadata(metadata, *facility_code)?.wiegand_bits(*format)?;
+                self.osdp[name].lock().unwrap_or_else(PoisonError::into_inner).push_back(bits);
+                Ok(())
+            }
+        }
+    }
+
+    /// Export `pin` as an output if needed and return its value file
+    fn export_pin(&self, pin: u32) -> Result<PathBuf> {
+        let dir = self.gpio_root.join(format!("gpio{}", pin));
+        if !dir.exists() {
+            fs::write(self.gpio_root.join("export"), pin.to_string())
+                .map_err(|e| anyhow!("Failed to export GPIO {}: {}", pin, e))?;
+        }
+        fs::write(dir.join("direction"), "out").map_err(|e| anyhow!("Failed to configure GPIO {}: {}", pin, e))?;
+        Ok(dir.join("value"))
+    }
+}
+
+/// Answer the panel's polls on `device` as the reader at `address`, returning
+/// the queue of card reads to report
+fn answer_polls(device: &Path, address: u8) -> Result<Arc<Mutex<VecDeque<Vec<bool>>>>> {
+    let mut port = OpenOptions::new().read(true).write(true).open(device)
+        .map_err(|e| anyhow!("Failed to open {}: {}", device.display(), e))?;
+    let mut replies = port.try_clone()?;
+    let pending = Arc::new(Mutex::new(VecDeque::new()));
+    let queue = Arc::clone(&pending);
+    let device = device.display().to_string();
+    thread::spawn(move || {
+        let mut incoming = VecDeque::new();
+        let mut chunk = [0u8; 256];
+        loop {
+            let read = match port.read(&mut chunk) {
+                Ok(0) => {
+                    thread::sleep(Duration::from_millis(10));
+                    continue;
+                }
+                Ok(read) => read,
+                Err(e) => {
+                    tracing::warn!(device = %device, "OSDP port closed: {}", e);
+                    return;
+                }
+            };
+            incoming.extend(&chunk[..read]);
+            while let Some(command) = badge::next_osdp_command(&mut incoming) {
+                let reply = badge::osdp_respond(address, &command, &mut queue.lock().unwrap_or_else(PoisonError::into_inner));
+                if let Some(Err(e)) = reply.map(|reply| replies.write_all(&reply)) {
+                    tracing::warn!(device = %device, "Failed to answer OSDP poll: {}", e);
+                }
+            }
+        }
+    });
+    Ok(pending)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_granted_users_pulse_their_outputs() {
+        let root = tempfile::tempdir().unwrap();
+        let gpio = root.path().join("gpio17");
+        for pin in ["gpio17", "gpio22", "gpio23"] {
+            fs::create_dir(root.path().join(pin)).unwrap();
+        }
+        let marker = root.path().join("opened");
+        let config: ActuatorConfig = serde_json::from_value(serde_json::json!({
+            "outputs": {
+                "front-door": { "kind": "gpio", "pin": 17, "pulse_ms": 1 },
+                "log": { "kind": "command", "program": "sh", "args": ["-c", format!("echo {{user}} >> {}", marker.display())] },
+                "panel": { "kind": "wiegand", "d0_pin": 22, "d1_pin": 23, "facility_code": 18 }
+            },
+            "rules": [{ "groups": ["it"], "outputs": ["log", "front-door", "panel"] }],
+            "default": ["front-door"]
+        })).unwrap();
+        let actuator = Actuator::new(config.clone()).unwrap().with_gpio_root(root.path());
+
+        let mut it = UserMetadata { badge_id: Some("4660".into()), ..Default::default() };
+        it.custom.insert(GROUP_FIELD.into(), "it".into());
+        assert_eq!(actuator.outputs_for("ann", Some(&it)), ["log", "front-door", "panel"]);
+        assert_eq!(actuator.outputs_for("bob", None), ["front-door"]);
+
+        actuator.trigger("ann", Some(&it), None).join().unwrap();
+        assert_eq!(fs::read_to_string(gpio.join("direction")).unwrap(), "out");
+        assert_eq!(fs::read_to_string(gpio.join("value")).unwrap(), "0", "the pulse ends");
+        assert_eq!(fs::read_to_string(&marker).unwrap(), "ann\n");
+        assert_eq!(fs::read_to_string(root.path().join("gpio23/value")).unwrap(), "1", "Wiegand lines idle high");
+
+        let mut broken = config;
+        broken.default.push("back-door".into());
+        assert!(Actuator::new(broken).is_err());
+    }
+}
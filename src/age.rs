@@ -0,0 +1,125 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+use crate::policy::{DecisionPolicy, PolicyDecision, PolicyInput};
+
+/// How old a face looks, never a verified age
+///
+/// Models are typically off by several years, more so for some age groups
+/// and demographics, so decisions should use the bounds rather than `years`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AgeEstimate {
+    /// Most likely apparent age
+    pub years: f64,
+    /// Lower bound of the interval the age lies in with probability `confidence`
+    pub low: f64,
+    pub high: f64,
+    /// Probability (0.0-1.0) that the apparent age lies in `low..=high`
+    pub confidence: f64,
+}
+
+impl AgeEstimate {
+    /// Estimate from a model's probabilities over ages 0, 1, 2, ... years,
+    /// with the central interval holding `confidence` of the probability
+    pub fn from_distribution(probabilities: &[f64], confidence: f64) -> Option<Self> {
+        let total: f64 = probabilities.iter().sum();
+        if probabilities.is_empty() || !total.is_finite() || total <= 0.0 {
+            return None;
+        }
+        let years = probabilities.iter().enumerate().map(|(age, p)| age as f64 * p).sum::<f64>() / total;
+        let tail = (1.0 - confidence) / 2.0;
+        let quantile = |q: f64| {
+            let mut cumulative = 0.0;
+            for (age, p) in probabilities.iter().enumerate() {
+                cumulative += p / total;
+                if cumulative >= q {
+                    return age as f64;
+                }
+            }
+            (probabilities.len() - 1) as f64
+        };
+        Some(Self { years, low: quantile(tail), high: quantile(1.0 - tail), confidence })
+    }
+
+    /// Whether the face appears to be at least `years` old: `None` when the
+    /// interval straddles `years` and the estimate can't tell
+    pub fn appears_at_least(&self, years: f64) -> Option<bool> {
+        if self.low >= years {
+            Some(true)
+        } else if self.high < years {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+/// Estimates the apparent age of the face in an image
+///
+/// Configured with [`FaceAuthBuilder::age_estimator`](crate::FaceAuthBuilder::age_estimator),
+/// estimates are attached to results and passed to the decision policy.
+/// With `native-ml`, [`OnnxAgeEstimator`](crate::native::OnnxAgeEstimator)
+/// runs an age model in-process.
+pub trait AgeEstimator: fmt::Debug + Send + Sync {
+    fn estimate(&self, image: &Path) -> Result<AgeEstimate>;
+}
+
+/// Decision policy for "appears over 18" checks, e.g. at vending machines
+///
+/// Accepts faces whose whole interval is at or above the minimum, denies
+/// those entirely below it and asks for an ID check in between, or when no
+/// estimate is available.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinimumAgePolicy {
+    pub years: f64,
+}
+
+impl MinimumAgePolicy {
+    pub fn new(years: f64) -> Self {
+        Self { years }
+    }
+}
+
+impl DecisionPolicy for MinimumAgePolicy {
+    fn decide(&self, input: &PolicyInput) -> Result<PolicyDecision> {
+        let Some(age) = input.age else {
+            return Ok(PolicyDecision::StepUp { reason: "Age could not be estimated".into() });
+        };
+        Ok(match age.appears_at_least(self.years) {
+            Some(true) => PolicyDecision::Accept,
+            Some(false) => PolicyDecision::Deny { reason: format!("Appears younger than {}", self.years) },
+            None => PolicyDecision::StepUp { reason: format!("May be younger than {}", self.years) },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::AuthContext;
+
+    #[test]
+    fn test_minimum_age_uses_the_interval_bounds() {
+        let mut probabilities = vec![0.0; 40];
+        probabilities[19] = 0.1;
+        probabilities[20] = 0.3;
+        probabilities[21] = 0.4;
+        probabilities[22] = 0.2;
+        let estimate = AgeEstimate::from_distribution(&probabilities, 0.8).unwrap();
+        assert!((estimate.years - 20.7).abs() < 1e-9);
+        assert_eq!((estimate.low, estimate.high), (19.0, 22.0));
+        assert_eq!(estimate.appears_at_least(18.0), Some(true));
+        assert_eq!(estimate.appears_at_least(21.0), None);
+        assert_eq!(estimate.appears_at_least(25.0), Some(false));
+        assert!(AgeEstimate::from_distribution(&[0.0, 0.0], 0.9).is_none());
+
+        let input = |age| PolicyInput { candidates: Vec::new(), quality: None, liveness: None, device_id: None, context: AuthContext::new(), age, ages: Vec::new(), modalities: None };
+        let policy = MinimumAgePolicy::new(18.0);
+        assert_eq!(policy.decide(&input(Some(estimate))).unwrap(), PolicyDecision::Accept);
+        assert!(matches!(MinimumAgePolicy::new(21.0).decide(&input(Some(estimate))).unwrap(), PolicyDecision::StepUp { .. }));
+        assert!(matches!(MinimumAgePolicy::new(25.0).decide(&input(Some(estimate))).unwrap(), PolicyDecision::Deny { .. }));
+        assert!(matches!(policy.decide(&input(None)).unwrap(), PolicyDecision::StepUp { .. }));
+    }
+}
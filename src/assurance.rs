@@ -0,0 +1,136 @@
+use anyhow::{Result, bail};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+use crate::error::FaceAuthError;
+
+/// Proof that a live person was in front of the camera
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum Liveness {
+    /// A passive check on the frame itself, e.g. texture or depth analysis
+    Passive,
+    /// The user completed a challenge such as blinking or turning their head
+    Active,
+}
+
+/// How strongly an authentication establishes who is at the camera, ordered
+/// from weakest to strongest
+///
+/// Downstream authorization can demand a minimum level per action, e.g. L1 to
+/// open a door but L3 to approve a payment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssuranceLevel {
+    /// Face match only
+    L1,
+    /// Face match and a passed passive liveness check
+    L2,
+    /// Face match and a passed active liveness challenge
+    L3,
+}
+
+impl AssuranceLevel {
+    /// Level of a face match with the given liveness evidence
+    pub fn for_match(liveness: Option<Liveness>) -> Self {
+        match liveness {
+            None => AssuranceLevel::L1,
+            Some(Liveness::Passive) => AssuranceLevel::L2,
+            Some(Liveness::Active) => AssuranceLevel::L3,
+        }
+    }
+}
+
+impl fmt::Display for AssuranceLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AssuranceLevel::L1 => "L1",
+            AssuranceLevel::L2 => "L2",
+            AssuranceLevel::L3 => "L3",
+        })
+    }
+}
+
+/// A granted authentication kept for a while, carrying its assurance level
+/// so later actions can check it without asking for the face again
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub user_id: String,
+    pub assurance: AssuranceLevel,
+    pub authenticated_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Session {
+    pub fn new(user_id: impl Into<String>, assurance: AssuranceLevel, ttl: Duration) -> Self {
+        Self::starting_at(user_id, assurance, ttl, Utc::now())
+    }
+
+    /// A session authenticated at `now`, e.g. a result's
+    /// [`decided_at`](crate::FaceAuthResult::decided_at)
+    pub fn starting_at(user_id: impl Into<String>, assurance: AssuranceLevel, ttl: Duration, now: DateTime<Utc>) -> Self {
+        let ttl = chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::MAX);
+        Self {
+            user_id: user_id.into(),
+            assurance,
+            authenticated_at: now,
+            expires_at: now.checked_add_signed(ttl).unwrap_or(DateTime::<Utc>::MAX_UTC),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_at(Utc::now())
+    }
+
+    pub fn is_expired_at(&self, now: DateTime<Utc>) -> bool {
+        now >= self.expires_at
+    }
+
+    /// Check the session still holds and reached `level` before allowing an action
+    ///
+    /// Fails with [`FaceAuthError::InsufficientAssurance`] when the level is too low.
+    pub fn require(&self, level: AssuranceLevel) -> Result<()> {
+        self.require_at(level, Utc::now())
+    }
+
+    /// [`Session::require`] at `now`, e.g. [`FaceAuth::now`](crate::FaceAuth::now)
+    /// when the instance runs on a [`Clock`](crate::Clock) of its own
+    pub fn require_at(&self, level: AssuranceLevel, now: DateTime<Utc>) -> Result<()> {
+        if self.is_expired_at(now) {
+            bail!("Session of '{}' expired at {}", self.user_id, self.expires_at);
+        }
+        if self.assurance < level {
+            return Err(FaceAuthError::InsufficientAssurance { required: level, actual: self.assurance }.into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sessions_enforce_minimum_levels() {
+        assert_eq!(AssuranceLevel::for_match(None), AssuranceLevel::L1);
+        assert!(AssuranceLevel::for_match(Some(Liveness::Active)) > AssuranceLevel::L2);
+        assert_eq!(serde_json::to_value(AssuranceLevel::L2).unwrap(), "l2");
+
+        let session = Session::new("ann", AssuranceLevel::L2, Duration::from_secs(300));
+        assert!(session.require(AssuranceLevel::L1).is_ok());
+        let error = session.require(AssuranceLevel::L3).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<FaceAuthError>(),
+            Some(FaceAuthError::InsufficientAssurance { required: AssuranceLevel::L3, actual: AssuranceLevel::L2 })
+        ));
+        assert!(Session::new("ann", AssuranceLevel::L3, Duration::ZERO).require(AssuranceLevel::L1).is_err());
+
+        let start = DateTime::parse_from_rfc3339("2024-05-01T08:00:00Z").unwrap().with_timezone(&Utc);
+        let session = Session::starting_at("ann", AssuranceLevel::L3, Duration::from_secs(60), start);
+        assert!(session.require_at(AssuranceLevel::L3, start + chrono::Duration::seconds(59)).is_ok());
+        assert!(session.is_expired_at(start + chrono::Duration::seconds(60)));
+    }
+}
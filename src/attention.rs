@@ -0,0 +1,172 @@
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Vertical position of the nose tip between the eyes (0.0) and the chin
+/// (1.0) of a face looking straight at the camera
+const NEUTRAL_NOSE_HEIGHT: f64 = 0.4;
+
+/// Whether a tracked face is turned towards the camera
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Attention {
+    LookingAtCamera,
+    LookingAway,
+}
+
+/// Image positions (x, y in pixels) of the landmarks head pose is estimated from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FaceLandmarks {
+    /// Center of the eye on the left of the image
+    pub left_eye: [f64; 2],
+    pub right_eye: [f64; 2],
+    pub nose_tip: [f64; 2],
+    /// Lowest point of the chin
+    pub chin: [f64; 2],
+}
+
+/// Coarse head rotation in degrees; positive yaw turns towards the right of
+/// the image, positive pitch looks up
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HeadPose {
+    pub yaw: f64,
+    pub pitch: f64,
+}
+
+impl HeadPose {
+    /// Pose from where the nose tip sits relative to the eyes and chin, in
+    /// the frame of the eye line so a tilted head doesn't count as turned
+    pub fn from_landmarks(landmarks: &FaceLandmarks) -> Option<Self> {
+        let [lx, ly] = landmarks.left_eye;
+        let [rx, ry] = landmarks.right_eye;
+        let eye_distance = (rx - lx).hypot(ry - ly);
+        if eye_distance < f64::EPSILON {
+            return None;
+        }
+        let (ux, uy) = ((rx - lx) / eye_distance, (ry - ly) / eye_distance);
+        let center = [(lx + rx) / 2.0, (ly + ry) / 2.0];
+        // Offsets along the eye line and perpendicular to it, pointing down the face
+        let project = |[x, y]: [f64; 2]| {
+            let (dx, dy) = (x - center[0], y - center[1]);
+            (dx * ux + dy * uy, dy * ux - dx * uy)
+        };
+        let (nose_across, nose_down) = project(landmarks.nose_tip);
+        let (_, chin_down) = project(landmarks.chin);
+        if chin_down < f64::EPSILON {
+            return None;
+        }
+        let yaw = (2.0 * nose_across / eye_distance).clamp(-1.0, 1.0).asin().to_degrees();
+        let nose_height = nose_down / chin_down;
+        let pitch = (2.5 * (NEUTRAL_NOSE_HEIGHT - nose_height)).clamp(-1.0, 1.0).asin().to_degrees();
+        Some(Self { yaw, pitch })
+    }
+}
+
+/// When a tracked face counts as looking at the camera
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttentionConfig {
+    /// Largest head turn, in degrees, still counted as looking
+    pub max_yaw: f64,
+    pub max_pitch: f64,
+    /// Consecutive frames a new state must hold before it's reported, so a
+    /// glance doesn't flip the signal
+    pub min_frames: u32,
+}
+
+impl Default for AttentionConfig {
+    fn default() -> Self {
+        Self { max_yaw: 25.0, max_pitch: 20.0, min_frames: 3 }
+    }
+}
+
+impl AttentionConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !(self.max_yaw > 0.0 && self.max_pitch > 0.0) {
+            bail!("Attention angles must be positive");
+        }
+        if self.min_frames == 0 {
+            bail!("Attention needs at least one frame");
+        }
+        Ok(())
+    }
+
+    pub fn classify(&self, pose: &HeadPose) -> Attention {
+        if pose.yaw.abs() <= self.max_yaw && pose.pitch.abs() <= self.max_pitch {
+            Attention::LookingAtCamera
+        } else {
+            Attention::LookingAway
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TrackAttention {
+    reported: Option<Attention>,
+    pending: Attention,
+    frames: u32,
+}
+
+/// Turns per-frame head poses of tracked faces into attention changes
+#[derive(Debug)]
+pub struct AttentionTracker {
+    config: AttentionConfig,
+    tracks: HashMap<u64, TrackAttention>,
+}
+
+impl AttentionTracker {
+    pub fn new(config: AttentionConfig) -> Self {
+        Self { config, tracks: HashMap::new() }
+    }
+
+    /// Record the pose of `track_id` in one frame, returning its new state
+    /// once that has held for `min_frames` frames
+    pub fn update(&mut self, track_id: u64, pose: &HeadPose) -> Option<Attention> {
+        let attention = self.config.classify(pose);
+        let track = self.tracks.entry(track_id).or_insert(TrackAttention { reported: None, pending: attention, frames: 0 });
+        if track.pending == attention {
+            track.frames += 1;
+        } else {
+            *track = TrackAttention { pending: attention, frames: 1, ..*track };
+        }
+        if track.frames < self.config.min_frames || track.reported == Some(attention) {
+            return None;
+        }
+        track.reported = Some(attention);
+        Some(attention)
+    }
+
+    /// Forget a track that left the view
+    pub fn remove(&mut self, track_id: u64) {
+        self.tracks.remove(&track_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attention_follows_head_pose_after_min_frames() {
+        let frontal = FaceLandmarks { left_eye: [40.0, 50.0], right_eye: [80.0, 50.0], nose_tip: [60.0, 70.0], chin: [60.0, 100.0] };
+        let pose = HeadPose::from_landmarks(&frontal).unwrap();
+        assert!(pose.yaw.abs() < 1e-9 && pose.pitch.abs() < 1e-9, "{:?}", pose);
+
+        let turned = FaceLandmarks { nose_tip: [75.0, 70.0], ..frontal };
+        let away = HeadPose::from_landmarks(&turned).unwrap();
+        assert!(away.yaw > 45.0, "{:?}", away);
+        // The same face rolled 90 degrees is still frontal
+        let rolled = FaceLandmarks { left_eye: [50.0, 40.0], right_eye: [50.0, 80.0], nose_tip: [30.0, 60.0], chin: [0.0, 60.0] };
+        assert!(HeadPose::from_landmarks(&rolled).unwrap().yaw.abs() < 1e-9);
+
+        let mut tracker = AttentionTracker::new(AttentionConfig { min_frames: 2, ..Default::default() });
+        assert_eq!(tracker.update(1, &pose), None);
+        assert_eq!(tracker.update(1, &pose), Some(Attention::LookingAtCamera));
+        assert_eq!(tracker.update(1, &pose), None);
+        assert_eq!(tracker.update(1, &away), None);
+        assert_eq!(tracker.update(1, &pose), None, "a glance away isn't reported");
+        assert_eq!(tracker.update(1, &away), None);
+        assert_eq!(tracker.update(1, &away), Some(Attention::LookingAway));
+        tracker.remove(1);
+        assert_eq!(tracker.update(1, &away), None);
+    }
+}
@@ -0,0 +1,213 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::ops::RangeBounds;
+use std::path::{Path, PathBuf};
+
+use crate::context::AuthContext;
+use crate::denial::DenialReason;
+use crate::provenance::ImageProvenance;
+
+/// Kind of audited operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOperation {
+    Authentication,
+    Registration,
+    /// A backup was restored over the stored data
+    Restore,
+    /// Someone not granted access entered right behind a user who was
+    Tailgating,
+    /// A selfie was compared with the portrait on an identity document
+    DocumentVerification,
+    /// A sealed secret was requested with a live face
+    SecretUnlock,
+    /// An operation interrupted by a crash was rolled back on start
+    Recovery,
+    /// A face was matched to check the setup, without acting on the result
+    TestMatch,
+}
+
+/// Result of an audited operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Granted,
+    Denied,
+    Failed,
+}
+
+/// One line of the audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    pub operation: AuditOperation,
+    /// Matched user, or the closest candidate of a denied attempt
+    pub user: Option<String>,
+    pub outcome: AuditOutcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distance: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+    /// Decision that would have been enforced outside shadow mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shadow_decision: Option<bool>,
+    /// Why a denied attempt was denied
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub denial: Option<DenialReason>,
+    /// Context the caller attached to the attempt
+    #[serde(default, skip_serializing_if = "AuthContext::is_empty")]
+    pub context: AuthContext,
+    /// Origin of an uploaded image the attempt was made with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<ImageProvenance>,
+    /// Manipulation score of an uploaded image, see
+    /// [`FaceAuthResult::manipulation_score`](crate::FaceAuthResult::manipulation_score)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manipulation_score: Option<f64>,
+    /// File name of the captured frame the decision was made on, which
+    /// garbage collection keeps
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capture: Option<String>,
+}
+
+impl AuditEntry {
+    pub fn new(operation: AuditOperation, user: Option<String>, outcome: AuditOutcome) -> Self {
+        Self::at(Utc::now(), operation, user, outcome)
+    }
+
+    /// An entry stamped with `timestamp` instead of the system time
+    pub fn at(timestamp: DateTime<Utc>, operation: AuditOperation, user: Option<String>, outcome: AuditOutcome) -> Self {
+        Self {
+            timestamp,
+            operation,
+            user,
+            outcome,
+            distance: None,
+            threshold: None,
+            device_id: None,
+            shadow_decision: None,
+            denial: None,
+            context: AuthContext::default(),
+            provenance: None,
+            manipulation_score: None,
+            capture: None,
+        }
+    }
+}
+
+/// Append-only audit log stored as JSON lines
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append one entry
+    pub fn append(&self, entry: &AuditEntry) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        Ok(())
+    }
+
+    /// Make sure every appended entry has reached the disk
+    pub fn sync(&self) -> Result<()> {
+        match fs::File::open(&self.path) {
+            Ok(file) => Ok(file.sync_all()?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// All entries in order; unreadable lines are skipped with a warning
+    pub fn entries(&self) -> Result<Vec<AuditEntry>> {
+        let file = match fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut entries = Vec::new();
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => tracing::warn!("Skipping audit log line {}: {}", number + 1, e),
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Authentication attempts attributed to `user` within `range`
+    pub fn auth_history(&self, user: &str, range: impl RangeBounds<DateTime<Utc>>) -> Result<Vec<AuditEntry>> {
+        Ok(self.entries()?
+            .into_iter()
+            .filter(|e| e.operation == AuditOperation::Authentication)
+            .filter(|e| e.user.as_deref() == Some(user))
+            .filter(|e| range.contains(&e.timestamp))
+            .collect())
+    }
+
+    /// Rewrite the log with every reference to `old` pointing at `new`
+    ///
+    /// Returns `None` when the log does not mention `old`.
+    pub fn renamed_contents(&self, old: &str, new: &str) -> Result<Option<Vec<u8>>> {
+        let mut entries = self.entries()?;
+        let mut changed = false;
+        for entry in entries.iter_mut().filter(|e| e.user.as_deref() == Some(old)) {
+            entry.user = Some(new.to_string());
+            changed = true;
+        }
+        if !changed {
+            return Ok(None);
+        }
+
+        let mut contents = Vec::new();
+        for entry in &entries {
+            writeln!(contents, "{}", serde_json::to_string(entry)?)?;
+        }
+        Ok(Some(contents))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_auth_history_filters_user_and_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::new(dir.path().join("audit.log"));
+
+        let mut old = AuditEntry::new(AuditOperation::Authentication, Some("ann".into()), AuditOutcome::Denied);
+        old.timestamp = Utc::now() - Duration::days(2);
+        log.append(&old).unwrap();
+        log.append(&AuditEntry::new(AuditOperation::Authentication, Some("ann".into()), AuditOutcome::Granted)).unwrap();
+        log.append(&AuditEntry::new(AuditOperation::Registration, Some("ann".into()), AuditOutcome::Granted)).unwrap();
+        log.append(&AuditEntry::new(AuditOperation::Authentication, Some("bob".into()), AuditOutcome::Granted)).unwrap();
+
+        assert_eq!(log.auth_history("ann", ..).unwrap().len(), 2);
+        let recent = log.auth_history("ann", Utc::now() - Duration::days(1)..).unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].outcome, AuditOutcome::Granted);
+    }
+}
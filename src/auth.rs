@@ -0,0 +1,3493 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::io::Write;
+use std::ops::RangeBounds;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+#[cfg(all(feature = "actuator", target_os = "linux"))]
+use crate::actuator::Actuator;
+use crate::age::{AgeEstimate, AgeEstimator};
+use crate::assurance::{AssuranceLevel, Session};
+use crate::audit::{AuditEntry, AuditLog, AuditOperation, AuditOutcome};
+use crate::auth_flow::{AuthFlow, AuthFlowConfig};
+use crate::camera::CameraSource;
+use crate::clock::{Clock, SystemClock};
+use crate::compression::{self, Compression};
+use crate::backend::FaceBackend;
+use crate::backup::{self, BackupManifest, BackupSchedule, BackupSettings};
+use crate::cross_match::{self, CrossMatch};
+use crate::context::AuthContext;
+use crate::crypto;
+use crate::denial::DenialReason;
+use crate::device::{Busy, DeviceArbiter, DeviceContention, DeviceLease, DeviceOperation};
+use crate::embeddings::{CredentialIndex, EmbeddingPrecision};
+use crate::error::FaceAuthError;
+use crate::feedback::{Feedback, FeedbackEvent, FeedbackHandle};
+use crate::explain::Explanation;
+use crate::far::{FarBudget, FarMonitor, FarReport};
+use crate::fusion::{self, CameraScore, FusionStrategy};
+use crate::face_storage::{self, DatabaseStats, FaceDatabase, FaceSample, SampleSummary, StorageLayout, UserMetadata, UserProfile, UserSummary};
+use crate::gc::{self, GcReport};
+use crate::health::{HealthMonitor, HealthStatus, Heartbeat};
+use crate::hooks::{self, Hook, HookPoint, HookVerdict};
+use crate::journal::{Journal, RecoveredOperation, Transaction};
+#[cfg(feature = "mqtt")]
+use crate::home_assistant::HomeAssistant;
+use crate::kiosk::{EnrollmentFlow, EnrollmentFlowBuilder, EnrollmentState, FlowStore};
+use crate::kyc::{DocumentCalibration, DocumentMatch};
+use crate::manipulation::ManipulationDetector;
+use crate::matching::{self, MatchCandidate, SampleDistance};
+use crate::messages::MessageCatalog;
+use crate::platform::PlatformBiometrics;
+use crate::policy::{DecisionPolicy, PolicyDecision, PolicyInput};
+use crate::profiles::{ProfileSwitcher, ThresholdProfile, ThresholdProfiles};
+use crate::provenance::{self, ImageProvenance};
+#[cfg(feature = "qr")]
+use crate::qr;
+use crate::random::{OsRandom, RandomSource};
+#[cfg(feature = "nfc")]
+use crate::nfc::{self, NfcReader};
+use crate::notify::{Alert, AlertRules, Alerts, Notifier};
+use crate::outbox::{AccessEvent, EventSink, Outbox, OutboxConfig};
+use crate::moderation::{EnrollmentQueue, EnrollmentReview, PendingEnrollment};
+use crate::multimodal::{self, ModalityScores, SecondaryBiometric};
+use crate::redact::{self, Redaction, RedactionPolicy};
+use crate::registration::{self, EnrollmentProgress, RegistrationOutcome, SampleOutcome, SampleStatus};
+use crate::sample_id::{self, SampleIdRewrite};
+use crate::replay::{self, RecordedDecision, ReplayReport, SessionRecorder};
+use crate::scene::{SceneGate, SceneStats};
+use crate::signing::{ResultClaims, SignedResult, SignedSession};
+#[cfg(feature = "source-watch")]
+use crate::source_watch::{SourceEvent, SourceWatcher};
+use crate::standalone_python::{StandaloneAuthResult, StandalonePythonFaceAuth};
+#[cfg(feature = "telemetry")]
+use crate::telemetry::{TelemetryConfig, TelemetryReporter};
+use crate::timing::TimingBreakdown;
+use crate::tracking::{TrackEvent, TrackingConfig};
+use crate::v1::{AuthRequest, Frame};
+#[cfg(feature = "transfer")]
+use crate::transfer::{Role, SecureChannel};
+#[cfg(feature = "secure-sketch")]
+use crate::vault::{SealedSecret, UnlockPolicy};
+use crate::welcome::Greeting;
+use crate::worker_pool::{WorkerPool, WorkerStatus};
+
+/// Samples captured by [`FaceAuth::reenroll`]
+const REENROLL_SAMPLES: u32 = 3;
+
+/// Samples kept per user by [`FaceAuth::reenroll`]; the oldest are dropped first
+const MAX_REENROLLED_SAMPLES: usize = 10;
+
+/// Main face authentication interface
+///
+/// ```no_run
+/// use face_auth::{AuthRequest, FaceAuth};
+///
+/// #[tokio::main]
+/// async fn main() -> anyhow::Result<()> {
+///     let auth = FaceAuth::builder()
+///         .data_dir("face_auth_data")
+///         .build()?;
+///
+///     // Register a user
+///     auth.register_user("john", 3, "generated").await?;
+///
+///     // Authenticate
+///     let result = auth.authenticate(&AuthRequest::camera(0.6).with_source_dir("source")).await?;
+///
+///     if result.is_authenticated {
+///         println!("Welcome, {}!", result.user_id.unwrap_or_default());
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+///
+/// Cloning is cheap and clones share the same backend and storage lock, so one
+/// instance can be built at startup and handed to every request handler or thread.
+#[derive(Clone)]
+pub struct FaceAuth {
+    inner: Arc<FaceAuthInner>,
+}
+
+struct FaceAuthInner {
+    backend: Arc<dyn FaceBackend>,
+    layout: StorageLayout,
+    auto_promote: bool,
+    thumbnail_key: Option<[u8; crypto::KEY_LEN]>,
+    backup_key: Option<[u8; crypto::KEY_LEN]>,
+    signing_key: Option<[u8; crypto::KEY_LEN]>,
+    locale: String,
+    messages: MessageCatalog,
+    feedback: Option<FeedbackHandle>,
+    device_id: Option<String>,
+    enforcement: EnforcementMode,
+    storage_mode: StorageMode,
+    privacy: PrivacyMode,
+    audit: AuditLog,
+    recorder: Option<SessionRecorder>,
+    workers: Option<WorkerPool>,
+    export_retention: Option<Duration>,
+    latency_budget: Option<Duration>,
+    max_image_age: Option<Duration>,
+    ambiguity: Option<AmbiguityPolicy>,
+    profiles: Option<ProfileSwitcher>,
+    cameras: Vec<u32>,
+    fusion: FusionStrategy,
+    device: DeviceArbiter,
+    clock: Arc<dyn Clock>,
+    random: Arc<dyn RandomSource>,
+    compression: Compression,
+    tailgating_window: Option<Duration>,
+    embedding_prefilter: Option<usize>,
+    #[cfg(all(feature = "actuator", target_os = "linux"))]
+    actuator: Option<Actuator>,
+    #[cfg(feature = "mqtt")]
+    home_assistant: Option<HomeAssistant>,
+    hooks: Vec<Hook>,
+    policy: Option<Arc<dyn DecisionPolicy>>,
+    manipulation: Option<(Arc<dyn ManipulationDetector>, Option<f64>)>,
+    age_estimator: Option<Arc<dyn AgeEstimator>>,
+    secondary_biometric: Option<Arc<dyn SecondaryBiometric>>,
+    document_calibration: DocumentCalibration,
+    platform_fallback: Option<(Arc<dyn PlatformBiometrics>, String)>,
+    outbox: Option<Outbox>,
+    alerts: Option<Alerts>,
+    far: Option<FarMonitor>,
+    #[cfg(feature = "secure-sketch")]
+    unlock_policy: UnlockPolicy,
+    #[cfg(feature = "secure-sketch")]
+    vault_key: Option<[u8; crypto::KEY_LEN]>,
+    /// Embeddings of the credentials last matched by
+    /// [`FaceAuth::authenticate_embedding`], refreshed as their files change
+    credential_index: Mutex<Option<CredentialIndex>>,
+    /// When and to whom access was last granted, for tailgating detection
+    last_granted: Mutex<Option<(Instant, String)>>,
+    #[cfg(feature = "telemetry")]
+    telemetry: Option<TelemetryReporter>,
+    #[cfg(feature = "nfc")]
+    provisioning_key: Option<[u8; crypto::KEY_LEN]>,
+    #[cfg(feature = "nfc")]
+    nfc_tag_lifetime: Duration,
+    health: HealthMonitor,
+    /// Held across read-modify-write cycles of the database, stats and audit log
+    storage_lock: Mutex<()>,
+    journal: Journal,
+    /// Operations rolled back on start
+    recovered: Vec<RecoveredOperation>,
+}
+
+/// Authentication result
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct FaceAuthResult {
+    pub is_authenticated: bool,
+    pub user_id: Option<String>,
+    pub confidence: Option<f64>,
+    pub distance: Option<f64>,
+    pub threshold: Option<f64>,
+    pub processing_time_ms: Option<u32>,
+    /// Metadata of the matched user
+    pub metadata: Option<UserMetadata>,
+    /// Welcome-screen data for the matched user
+    pub greeting: Option<Greeting>,
+    /// Face-only decision computed in shadow mode, where `is_authenticated` is always false
+    pub shadow_decision: Option<bool>,
+    /// Why the attempt was denied, when known
+    pub denial: Option<DenialReason>,
+    /// Threshold profile the decision was made with, if profiles are configured
+    pub profile: Option<String>,
+    /// Users an ambiguous face may belong to, closest first, when
+    /// [`AmbiguityPolicy::second_factor`] leaves the decision to a badge or PIN
+    pub candidates: Vec<String>,
+    /// What each camera saw, when several are fused into this decision
+    pub cameras: Vec<CameraScore>,
+    /// Closest enrolled user, also when the face didn't match
+    pub closest_user: Option<String>,
+    /// Distance to each sample of the closest user, closest first, when the
+    /// backend reports them; see [`FaceAuth::explain`]
+    pub sample_distances: Vec<SampleDistance>,
+    /// Context the caller attached, see [`AuthRequest::context`]
+    pub context: AuthContext,
+    /// How strongly the decision establishes the user, when access was granted
+    pub assurance: Option<AssuranceLevel>,
+    /// Signed claims about the decision, when built with [`FaceAuthBuilder::signing_key`]
+    pub signed: Option<SignedResult>,
+    /// How likely the uploaded image was manipulated (0.0-1.0), when a
+    /// detector is configured with [`FaceAuthBuilder::manipulation_detector`]
+    pub manipulation_score: Option<f64>,
+    /// Apparent age of the face, an estimate with bounds, when built with
+    /// [`FaceAuthBuilder::age_estimator`]
+    pub age: Option<AgeEstimate>,
+    /// Apparent age of every face in the frame, largest first, so bystanders
+    /// are estimated too; the first one is [`FaceAuthResult::age`]
+    pub ages: Vec<AgeEstimate>,
+    /// Face and secondary biometric scores of a match, when built with
+    /// [`FaceAuthBuilder::secondary_biometric`]
+    pub modalities: Option<ModalityScores>,
+    /// Platform prompt that decided instead of a face match, see
+    /// [`FaceAuthBuilder::platform_fallback`]
+    pub platform: Option<String>,
+    /// When the decision was made, on the instance's [`Clock`]
+    pub decided_at: DateTime<Utc>,
+    /// Time spent per stage
+    pub timings: TimingBreakdown,
+    /// Grant waiting for a liveness challenge, see [`FaceAuth::resolve_challenge`]
+    #[serde(skip)]
+    pub(crate) held: Option<Box<PendingDecision>>,
+}
+
+/// A decision that has been made but not yet acted on: nothing is recorded,
+/// actuated, published or signed until [`FaceAuth::record_decision`]
+#[derive(Debug, Clone)]
+pub(crate) struct PendingDecision {
+    result: FaceAuthResult,
+    source_dir: String,
+    closest_user: Option<String>,
+    runner_up_distance: Option<f64>,
+    provenance: Option<ImageProvenance>,
+    /// File name of the frame the backend captured for the decision
+    capture: Option<String>,
+}
+
+impl FaceAuthResult {
+    /// Whether [`FaceAuthResult::candidates`] must confirm with a second
+    /// factor: the face alone couldn't tell them apart, or the decision
+    /// policy asked for it
+    pub fn needs_second_factor(&self) -> bool {
+        matches!(self.denial, Some(DenialReason::Ambiguous { .. } | DenialReason::StepUpRequired { .. })) && !self.candidates.is_empty()
+    }
+
+    /// Session for the granted user, valid for `ttl` from the decision,
+    /// carrying the assurance level so later actions can demand a minimum one
+    pub fn session(&self, ttl: Duration) -> Option<Session> {
+        let (true, Some(user), Some(assurance)) = (self.is_authenticated, &self.user_id, self.assurance) else {
+            return None;
+        };
+        Some(Session::starting_at(user, assurance, ttl, self.decided_at))
+    }
+}
+
+impl From<StandaloneAuthResult> for FaceAuthResult {
+    fn from(result: StandaloneAuthResult) -> Self {
+        Self {
+            is_authenticated: result.is_match.unwrap_or(false),
+            user_id: result.matched_user,
+            confidence: result.confidence,
+            distance: result.distance,
+            threshold: result.threshold,
+            processing_time_ms: result.processing_time_ms,
+            metadata: None,
+            greeting: None,
+            shadow_decision: None,
+            denial: result.denial.filter(|_| !result.is_match.unwrap_or(false)),
+            profile: None,
+            candidates: Vec::new(),
+            cameras: Vec::new(),
+            closest_user: result.closest_user,
+            sample_distances: result.sample_distances,
+            context: AuthContext::default(),
+            assurance: None,
+            signed: None,
+            manipulation_score: None,
+            age: None,
+            ages: Vec::new(),
+            modalities: None,
+            platform: None,
+            decided_at: Utc::now(),
+            timings: result.timings,
+            held: None,
+        }
+    }
+}
+
+/// What [`FaceAuth::warm_up`] prepared
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct WarmUpReport {
+    /// Pool workers started with their models loaded
+    pub workers_ready: usize,
+    /// Whether the camera delivered a frame
+    pub camera_ready: Option<bool>,
+    /// Credentials loaded from the source directory
+    pub users_loaded: usize,
+    pub elapsed_ms: u64,
+}
+
+/// Whether face decisions grant access
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EnforcementMode {
+    /// A match authenticates the user
+    #[default]
+    Enforce,
+    /// The full decision is computed and audited but access is always denied,
+    /// so a deployment can measure accuracy before relying on face-only access
+    Shadow,
+}
+
+/// Refuses matches that another enrolled user nearly won, e.g. identical twins
+///
+/// Without a policy the closest user always wins, however close the runner-up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AmbiguityPolicy {
+    /// A match is ambiguous when the runner-up is less than this much farther away
+    pub margin: f64,
+    /// Report both users in [`FaceAuthResult::candidates`] so a second factor
+    /// can decide; otherwise the attempt is just denied
+    pub second_factor: bool,
+}
+
+impl AmbiguityPolicy {
+    pub fn new(margin: f64) -> Self {
+        Self { margin, second_factor: false }
+    }
+
+    pub fn with_second_factor(mut self) -> Self {
+        self.second_factor = true;
+        self
+    }
+}
+
+/// Whether images may be written to disk
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PrivacyMode {
+    /// Captured frames, upload copies and thumbnails are stored as files
+    #[default]
+    Standard,
+    /// No image or thumbnail is ever written anywhere, including by the
+    /// Python script: frames stay in memory from capture to match. Building
+    /// fails when the backend can't guarantee this, or with features that
+    /// need images on disk (session recording, manipulation detection, age
+    /// estimation); remote enrollment fails with [`FaceAuthError::PrivacyStrict`].
+    Strict,
+}
+
+/// Which faces are blurred in frames kept on disk, such as the captures
+/// behind failed attempts and session recordings
+///
+/// Blurring happens after matching, so it doesn't affect decisions.
+/// Bystanders the detector misses, e.g. far in the background, stay visible.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FrameBlur {
+    /// Frames are kept as captured
+    #[default]
+    Off,
+    /// Every face except the largest, the person being authenticated
+    Bystanders,
+    /// Every face, leaving only the surroundings for debugging
+    AllFaces,
+}
+
+/// Whether an instance may change the stored credentials and statistics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StorageMode {
+    #[default]
+    ReadWrite,
+    /// For authentication-only devices such as door terminals: enrollment,
+    /// imports, renames, restores and metadata changes fail with
+    /// [`FaceAuthError::ReadOnly`], authentication statistics aren't written
+    /// and captured frames are deleted after matching. Audit entries are
+    /// still written when possible; failing to write them is only logged.
+    ReadOnly,
+}
+
+/// Builder for configuring a [`FaceAuth`] instance
+#[derive(Debug)]
+pub struct FaceAuthBuilder {
+    data_dir: Option<PathBuf>,
+    generated_dir: PathBuf,
+    source_dir: PathBuf,
+    auto_promote: bool,
+    thumbnail_key: Option<[u8; crypto::KEY_LEN]>,
+    backup_key: Option<[u8; crypto::KEY_LEN]>,
+    signing_key: Option<[u8; crypto::KEY_LEN]>,
+    locale: String,
+    messages: Option<MessageCatalog>,
+    feedback: Option<FeedbackHandle>,
+    device_id: Option<String>,
+    enforcement: EnforcementMode,
+    storage_mode: StorageMode,
+    privacy: PrivacyMode,
+    frame_blur: FrameBlur,
+    record_sessions: bool,
+    python_workers: usize,
+    slow_worker_threshold: Option<Duration>,
+    python_worker_timeout: Option<Duration>,
+    repair_python_dependencies: bool,
+    export_retention: Option<Duration>,
+    latency_budget: Option<Duration>,
+    max_image_age: Option<Duration>,
+    ambiguity: Option<AmbiguityPolicy>,
+    threshold_profiles: Option<ThresholdProfiles>,
+    cameras: Vec<u32>,
+    fusion: FusionStrategy,
+    device_contention: DeviceContention,
+    clock: Arc<dyn Clock>,
+    random: Arc<dyn RandomSource>,
+    compression: Compression,
+    tailgating_window: Option<Duration>,
+    embedding_prefilter: Option<usize>,
+    #[cfg(all(feature = "actuator", target_os = "linux"))]
+    actuator: Option<Actuator>,
+    #[cfg(feature = "mqtt")]
+    home_assistant: Option<HomeAssistant>,
+    hooks: Vec<Hook>,
+    policy: Option<Arc<dyn DecisionPolicy>>,
+    manipulation: Option<(Arc<dyn ManipulationDetector>, Option<f64>)>,
+    age_estimator: Option<Arc<dyn AgeEstimator>>,
+    secondary_biometric: Option<Arc<dyn SecondaryBiometric>>,
+    document_calibration: DocumentCalibration,
+    platform_fallback: Option<(Arc<dyn PlatformBiometrics>, String)>,
+    event_sinks: Vec<Arc<dyn EventSink>>,
+    outbox_config: OutboxConfig,
+    notifiers: Vec<Arc<dyn Notifier>>,
+    alert_rules: AlertRules,
+    far_budget: Option<FarBudget>,
+    #[cfg(feature = "secure-sketch")]
+    unlock_policy: UnlockPolicy,
+    #[cfg(feature = "secure-sketch")]
+    vault_key: Option<[u8; crypto::KEY_LEN]>,
+    #[cfg(feature = "telemetry")]
+    telemetry: Option<TelemetryConfig>,
+    #[cfg(feature = "nfc")]
+    provisioning_key: Option<[u8; crypto::KEY_LEN]>,
+    #[cfg(feature = "nfc")]
+    nfc_tag_lifetime: Duration,
+    backend: Option<Arc<dyn FaceBackend>>,
+    camera_source: Option<CameraSource>,
+}
+
+impl Default for FaceAuthBuilder {
+    fn default() -> Self {
+        Self {
+            data_dir: None,
+            generated_dir: PathBuf::from("generated"),
+            source_dir: PathBuf::from("source"),
+            auto_promote: false,
+            thumbnail_key: None,
+            backup_key: None,
+            signing_key: None,
+            locale: "en".to_string(),
+            messages: None,
+            feedback: None,
+            device_id: None,
+            enforcement: EnforcementMode::Enforce,
+            storage_mode: StorageMode::ReadWrite,
+            privacy: PrivacyMode::Standard,
+            frame_blur: FrameBlur::Off,
+            record_sessions: false,
+            python_workers: 0,
+            slow_worker_threshold: None,
+            python_worker_timeout: None,
+            repair_python_dependencies: false,
+            export_retention: None,
+            latency_budget: None,
+            max_image_age: None,
+            ambiguity: None,
+            threshold_profiles: None,
+            cameras: Vec::new(),
+            fusion: FusionStrategy::default(),
+            device_contention: DeviceContention::default(),
+            clock: Arc::new(SystemClock),
+            random: Arc::new(OsRandom),
+            compression: Compression::None,
+            tailgating_window: None,
+            embedding_prefilter: None,
+            #[cfg(all(feature = "actuator", target_os = "linux"))]
+            actuator: None,
+            #[cfg(feature = "mqtt")]
+            home_assistant: None,
+            hooks: Vec::new(),
+            policy: None,
+            manipulation: None,
+            age_estimator: None,
+            secondary_biometric: None,
+            document_calibration: DocumentCalibration::default(),
+            platform_fallback: None,
+            event_sinks: Vec::new(),
+            outbox_config: OutboxConfig::default(),
+            notifiers: Vec::new(),
+            alert_rules: AlertRules::default(),
+            far_budget: None,
+            #[cfg(feature = "secure-sketch")]
+            unlock_policy: UnlockPolicy::default(),
+            #[cfg(feature = "secure-sketch")]
+            vault_key: None,
+            #[cfg(feature = "telemetry")]
+            telemetry: None,
+            #[cfg(feature = "nfc")]
+            provisioning_key: None,
+            #[cfg(feature = "nfc")]
+            nfc_tag_lifetime: nfc::DEFAULT_TAG_LIFETIME,
+            backend: None,
+            camera_source: None,
+        }
+    }
+}
+
+impl FaceAuthBuilder {
+    /// Directory for the database, captured images and exports
+    /// (defaults to the current working directory)
+    pub fn data_dir(mut self, data_dir: impl Into<PathBuf>) -> Self {
+        self.data_dir = Some(data_dir.into());
+        self
+    }
+
+    /// Directory registrations are written to (defaults to `generated`)
+    pub fn generated_dir(mut self, generated_dir: impl Into<PathBuf>) -> Self {
+        self.generated_dir = generated_dir.into();
+        self
+    }
+
+    /// Directory credentials are authenticated against (defaults to `source`)
+    pub fn source_dir(mut self, source_dir: impl Into<PathBuf>) -> Self {
+        self.source_dir = source_dir.into();
+        self
+    }
+
+    /// Copy each successful registration into the source directory
+    /// so the user can authenticate immediately
+    pub fn auto_promote(mut self, auto_promote: bool) -> Self {
+        self.auto_promote = auto_promote;
+        self
+    }
+
+    /// Encrypt user thumbnails at rest with this key
+    pub fn thumbnail_key(mut self, key: [u8; crypto::KEY_LEN]) -> Self {
+        self.thumbnail_key = Some(key);
+        self
+    }
+
+    /// Encrypt backups with this key; required by [`FaceAuth::backup`] and [`FaceAuth::restore`]
+    pub fn backup_key(mut self, key: [u8; crypto::KEY_LEN]) -> Self {
+        self.backup_key = Some(key);
+        self
+    }
+
+    /// Sign every result with this device key so a backend can verify it,
+    /// see [`SignedResult`]
+    pub fn signing_key(mut self, key: [u8; crypto::KEY_LEN]) -> Self {
+        self.signing_key = Some(key);
+        self
+    }
+
+    /// Encrypt credentials written to NFC tags with this key, and import only
+    /// tags written with it; shared by the kiosks of one site, see [`crate::nfc`]
+    #[cfg(feature = "nfc")]
+    pub fn provisioning_key(mut self, key: [u8; crypto::KEY_LEN]) -> Self {
+        self.provisioning_key = Some(key);
+        self
+    }
+
+    /// How long NFC tags written here are accepted, [`nfc::DEFAULT_TAG_LIFETIME`]
+    /// by default
+    #[cfg(feature = "nfc")]
+    pub fn nfc_tag_lifetime(mut self, lifetime: Duration) -> Self {
+        self.nfc_tag_lifetime = lifetime;
+        self
+    }
+
+    /// Apply the settings stored in a backup, e.g. when setting up a replacement device
+    pub fn backup_settings(mut self, settings: &BackupSettings) -> Self {
+        self.device_id = settings.device_id.clone();
+        self.locale = settings.locale.clone();
+        self.auto_promote = settings.auto_promote;
+        self.enforcement = if settings.shadow_mode { EnforcementMode::Shadow } else { EnforcementMode::Enforce };
+        self.thumbnail_key = settings.thumbnail_key;
+        self
+    }
+
+    /// Locale reported in greetings and used for [`FaceAuth::messages`] (defaults to `en`)
+    pub fn locale(mut self, locale: impl Into<String>) -> Self {
+        self.locale = locale.into();
+        self
+    }
+
+    /// Use `messages` instead of the built-in catalog for the locale, e.g. one
+    /// loaded with [`MessageCatalog::load`] for a language the library doesn't ship
+    pub fn messages(mut self, messages: MessageCatalog) -> Self {
+        self.messages = Some(messages);
+        self
+    }
+
+    /// Tell `feedback` when to look at the camera, when capturing is done and
+    /// whether access was granted, e.g. to drive text-to-speech on a kiosk
+    pub fn feedback(mut self, feedback: impl Feedback + 'static) -> Self {
+        self.feedback = Some(FeedbackHandle(Arc::new(feedback)));
+        self
+    }
+
+    /// Identifier of this device, recorded in the audit log
+    pub fn device_id(mut self, device_id: impl Into<String>) -> Self {
+        self.device_id = Some(device_id.into());
+        self
+    }
+
+    /// Enforce face decisions or only record them (shadow mode)
+    pub fn enforcement(mut self, enforcement: EnforcementMode) -> Self {
+        self.enforcement = enforcement;
+        self
+    }
+
+    /// Allow or refuse changes to the stored data (see [`StorageMode::ReadOnly`])
+    pub fn storage_mode(mut self, storage_mode: StorageMode) -> Self {
+        self.storage_mode = storage_mode;
+        self
+    }
+
+    /// Keep every image in memory, see [`PrivacyMode::Strict`]
+    pub fn privacy_mode(mut self, privacy: PrivacyMode) -> Self {
+        self.privacy = privacy;
+        self
+    }
+
+    /// Blur bystanders' faces, or all faces, in frames kept on disk
+    pub fn frame_blur(mut self, blur: FrameBlur) -> Self {
+        self.frame_blur = blur;
+        self
+    }
+
+    /// Keep the frame and decision of every authentication for later replay
+    ///
+    /// Off by default: recordings contain raw face images.
+    pub fn record_sessions(mut self, record_sessions: bool) -> Self {
+        self.record_sessions = record_sessions;
+        self
+    }
+
+    /// Remove exported credentials older than `retention` in [`FaceAuth::gc`]
+    ///
+    /// Exports are kept forever by default.
+    pub fn export_retention(mut self, retention: Duration) -> Self {
+        self.export_retention = Some(retention);
+        self
+    }
+
+    /// Keep up to this many Python processes running to match frames
+    /// concurrently (see [`FaceAuth::authenticate`])
+    ///
+    /// Without workers every frame starts a fresh script. Requests beyond
+    /// `max_workers` block their thread until a worker is free, so async
+    /// callers matching frames concurrently run them with
+    /// `tokio::task::spawn_blocking`.
+    pub fn python_workers(mut self, max_workers: usize) -> Self {
+        self.python_workers = max_workers;
+        self
+    }
+
+    /// Count worker responses slower than `threshold` in
+    /// [`FaceAuth::worker_status`], and restart a worker that is slow
+    /// several times in a row
+    pub fn slow_worker_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_worker_threshold = Some(threshold);
+        self
+    }
+
+    /// Kill and replace a worker that hasn't answered a request or health
+    /// check after `timeout`, [`crate::worker_pool::DEFAULT_REQUEST_TIMEOUT`]
+    /// unless set
+    pub fn python_worker_timeout(mut self, timeout: Duration) -> Self {
+        self.python_worker_timeout = Some(timeout);
+        self
+    }
+
+    /// Reinstall the pinned Python dependencies from the heartbeat after a
+    /// worker failed to import them, see [`WorkerPool::with_dependency_repair`]
+    ///
+    /// Off by default; [`FaceAuth::repair_python_dependencies`] reinstalls on
+    /// demand.
+    pub fn repair_python_dependencies(mut self, repair: bool) -> Self {
+        self.repair_python_dependencies = repair;
+        self
+    }
+
+    /// Log a warning with the timing breakdown whenever an authentication
+    /// takes longer than `budget`
+    pub fn latency_budget(mut self, budget: Duration) -> Self {
+        self.latency_budget = Some(budget);
+        self
+    }
+
+    /// Deny images whose EXIF capture time is older than `max_age`, see
+    /// [`FaceAuth::authenticate`]; images without one are accepted
+    pub fn max_image_age(mut self, max_age: Duration) -> Self {
+        self.max_image_age = Some(max_age);
+        self
+    }
+
+    /// Deny (or hand to a second factor) matches whose runner-up is within
+    /// `policy.margin`, instead of silently picking the closer lookalike
+    pub fn ambiguity_policy(mut self, policy: AmbiguityPolicy) -> Self {
+        self.ambiguity = Some(policy);
+        self
+    }
+
+    /// Decide with per-environment tolerances instead of the one passed to
+    /// each authentication
+    ///
+    /// The profile is picked by the measured brightness of each frame unless
+    /// one is fixed with [`FaceAuth::set_threshold_profile`].
+    pub fn threshold_profiles(mut self, profiles: ThresholdProfiles) -> Self {
+        self.threshold_profiles = Some(profiles);
+        self
+    }
+
+    /// Capture from several cameras at one authentication point, e.g. two
+    /// angles at a gate, and fuse their views into one decision
+    ///
+    /// Cameras are OpenCV indices and are captured from in parallel. A camera
+    /// that fails is logged and left out; authentication fails only if all do.
+    pub fn cameras(mut self, cameras: impl IntoIterator<Item = u32>, fusion: FusionStrategy) -> Self {
+        self.cameras = cameras.into_iter().collect();
+        self.fusion = fusion;
+        self
+    }
+
+    /// Whether a capture waits while another one holds the camera, e.g. an
+    /// authentication during a registration, or is refused right away (the
+    /// default); see [`crate::device`]
+    pub fn device_contention(mut self, contention: DeviceContention) -> Self {
+        self.device_contention = contention;
+        self
+    }
+
+    /// Where timestamps come from: sample ids, audit entries, results and
+    /// their sessions; the system clock by default, see [`crate::clock`]
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Where sample id suffixes and server nonces are drawn from; the
+    /// operating system's generator by default, see [`crate::random`]
+    pub fn random(mut self, random: impl RandomSource + 'static) -> Self {
+        self.random = Arc::new(random);
+        self
+    }
+
+    /// Compress exported credentials, transferred credentials and recorded
+    /// session frames, e.g. to fit an export into a QR code or an NFC tag
+    ///
+    /// Compressed files are read back transparently, so imports accept both.
+    /// Off by default; see [`crate::compression`].
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Report [`TrackEvent::TailgatingSuspected`] from [`FaceAuth::track_faces`]
+    /// when a face that isn't granted access enters within `window` of
+    /// someone who was
+    pub fn tailgating_window(mut self, window: Duration) -> Self {
+        self.tailgating_window = Some(window);
+        self
+    }
+
+    /// Score embeddings passed to [`FaceAuth::authenticate_embedding`] only
+    /// against the `shortlist` users whose centroids are closest, see
+    /// [`EmbeddingIndex::with_prefilter`](crate::EmbeddingIndex::with_prefilter)
+    ///
+    /// Without it every user is scored.
+    pub fn embedding_prefilter(mut self, shortlist: usize) -> Self {
+        self.embedding_prefilter = Some(shortlist);
+        self
+    }
+
+    /// Pulse a relay, run a command or call a URL whenever access is granted
+    #[cfg(all(feature = "actuator", target_os = "linux"))]
+    pub fn actuator(mut self, actuator: Actuator) -> Self {
+        self.actuator = Some(actuator);
+        self
+    }
+
+    /// Run a user-supplied executable at a point of every authentication,
+    /// e.g. to check a booking system; hooks run in the order they're added
+    pub fn hook(mut self, hook: Hook) -> Self {
+        self.hooks.push(hook);
+        self
+    }
+
+    /// Let `policy` deny matches or require a second factor, e.g. for
+    /// high-value transactions
+    pub fn decision_policy(mut self, policy: impl DecisionPolicy + 'static) -> Self {
+        self.policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Score the faces in uploaded images with `detector` before matching, see
+    /// [`FaceAuthResult::manipulation_score`]; with `max_score`, images
+    /// scoring higher are denied
+    pub fn manipulation_detector(mut self, detector: impl ManipulationDetector + 'static, max_score: Option<f64>) -> Self {
+        self.manipulation = Some((Arc::new(detector), max_score));
+        self
+    }
+
+    /// Estimate the apparent age of every detected face, see
+    /// [`FaceAuthResult::age`]; the estimate is passed to the decision
+    /// policy, e.g. a [`MinimumAgePolicy`](crate::MinimumAgePolicy)
+    pub fn age_estimator(mut self, estimator: impl AgeEstimator + 'static) -> Self {
+        self.age_estimator = Some(Arc::new(estimator));
+        self
+    }
+
+    /// Score every face match with another modality too, e.g. the caller's
+    /// voice, see [`FaceAuthResult::modalities`]; both scores are passed to
+    /// the decision policy, e.g. a [`MultiModalPolicy`](crate::MultiModalPolicy)
+    pub fn secondary_biometric(mut self, biometric: impl SecondaryBiometric + 'static) -> Self {
+        self.secondary_biometric = Some(Arc::new(biometric));
+        self
+    }
+
+    /// How confident a live match must be for [`FaceAuth::seal_secret`] and
+    /// [`FaceAuth::unlock_secret`]
+    #[cfg(feature = "secure-sketch")]
+    pub fn unlock_policy(mut self, policy: UnlockPolicy) -> Self {
+        self.unlock_policy = policy;
+        self
+    }
+
+    /// Seal secrets under this key as well as the face-derived one; required
+    /// by [`FaceAuth::seal_secret`] and [`FaceAuth::unlock_secret`]
+    ///
+    /// Keep it outside the data directory, e.g. in the OS keyring or a TPM.
+    #[cfg(feature = "secure-sketch")]
+    pub fn vault_key(mut self, key: [u8; crypto::KEY_LEN]) -> Self {
+        self.vault_key = Some(key);
+        self
+    }
+
+    /// Ask the platform's biometric prompt, e.g. Windows Hello, to verify
+    /// `username` when the camera or face model is unavailable
+    ///
+    /// The result looks like any other, with [`FaceAuthResult::platform`]
+    /// naming the prompt that decided.
+    pub fn platform_fallback(mut self, biometrics: impl PlatformBiometrics + 'static, username: impl Into<String>) -> Self {
+        self.platform_fallback = Some((Arc::new(biometrics), username.into()));
+        self
+    }
+
+    /// Deliver every access decision to `sink`, e.g. a `WebhookSink`
+    ///
+    /// Events are queued on disk and retried with backoff while the sink is
+    /// unreachable, so none are lost to network outages.
+    pub fn event_sink(mut self, sink: impl EventSink + 'static) -> Self {
+        self.event_sinks.push(Arc::new(sink));
+        self
+    }
+
+    /// Backoff and disk bound of the event queue
+    pub fn outbox_config(mut self, config: OutboxConfig) -> Self {
+        self.outbox_config = config;
+        self
+    }
+
+    /// Send operational alerts, such as repeated failed attempts or the
+    /// camera going offline, through `notifier`, e.g. an `EmailNotifier`
+    pub fn notifier(mut self, notifier: impl Notifier + 'static) -> Self {
+        self.notifiers.push(Arc::new(notifier));
+        self
+    }
+
+    /// When alerts are raised and how often they may repeat
+    pub fn alert_rules(mut self, rules: AlertRules) -> Self {
+        self.alert_rules = rules;
+        self
+    }
+
+    /// Track the false accepts expected from this deployment's tolerance and
+    /// size, and alert through the [notifiers](FaceAuthBuilder::notifier)
+    /// when a period would exceed `budget`, see [`crate::far`]
+    ///
+    /// Without notifiers the alert is logged as a warning.
+    pub fn far_budget(mut self, budget: FarBudget) -> Self {
+        self.far_budget = Some(budget);
+        self
+    }
+
+    /// Notifiers and alert rules from a TOML file, see [`NotificationConfig`](crate::NotificationConfig)
+    #[cfg(feature = "notifications")]
+    pub fn notification_config(mut self, config: &crate::NotificationConfig) -> Result<Self> {
+        self.notifiers.extend(config.notifiers()?);
+        self.alert_rules = config.rules();
+        Ok(self)
+    }
+
+    /// How [`FaceAuth::match_document`] turns distances into match scores
+    pub fn document_calibration(mut self, calibration: DocumentCalibration) -> Self {
+        self.document_calibration = calibration;
+        self
+    }
+
+    /// Publish every decision to Home Assistant as "person at door" and
+    /// "recognized: <user>" sensors
+    #[cfg(feature = "mqtt")]
+    pub fn home_assistant(mut self, home_assistant: HomeAssistant) -> Self {
+        self.home_assistant = Some(home_assistant);
+        self
+    }
+
+    /// Opt in to reporting aggregate success/failure counts and latency to
+    /// `config.endpoint`; no names, images or embeddings are ever sent
+    #[cfg(feature = "telemetry")]
+    pub fn telemetry(mut self, config: TelemetryConfig) -> Self {
+        self.telemetry = Some(config);
+        self
+    }
+
+    /// Use `backend` instead of the Python script, e.g. a [`crate::FakeBackend`]
+    /// in tests
+    ///
+    /// The backend's data directory replaces [`FaceAuthBuilder::data_dir`], and
+    /// [`FaceAuthBuilder::python_workers`] is ignored.
+    pub fn backend(mut self, backend: impl FaceBackend + 'static) -> Self {
+        self.backend = Some(Arc::new(backend));
+        self
+    }
+
+    /// Capture from `source` instead of the default camera
+    ///
+    /// With a [`CameraSource::VideoFile`], [`FaceAuth::watch`] and
+    /// [`FaceAuth::track_faces`] run through the recorded footage and return
+    /// when it ends, see [`crate::camera`].
+    pub fn camera_source(mut self, source: CameraSource) -> Self {
+        self.camera_source = Some(source);
+        self
+    }
+
+    /// Build the FaceAuth instance
+    pub fn build(self) -> Result<FaceAuth> {
+        self.compression.validate()?;
+        if self.record_sessions && self.storage_mode == StorageMode::ReadOnly {
+            return Err(anyhow::anyhow!("Session recording is not available in read-only mode"));
+        }
+        if self.privacy == PrivacyMode::Strict {
+            let conflicts = [
+                (self.record_sessions, "session recording"),
+                (self.manipulation.is_some(), "manipulation detection"),
+                (self.age_estimator.is_some(), "age estimation"),
+            ];
+            if let Some((_, feature)) = conflicts.iter().find(|(enabled, _)| *enabled) {
+                return Err(anyhow::anyhow!("{} reads images from disk and is not available in strict privacy mode", feature));
+            }
+        }
+        let (mut backend, workers): (Arc<dyn FaceBackend>, _) = match self.backend {
+            Some(backend) => (backend, None),
+            None => {
+                let python_auth = match self.data_dir {
+                    Some(data_dir) => StandalonePythonFaceAuth::with_data_dir(data_dir)?,
+                    None => StandalonePythonFaceAuth::new()?,
+                };
+                let workers = (self.python_workers > 0).then(|| {
+                    let mut pool = WorkerPool::new(python_auth.clone(), self.python_workers).with_clock(self.clock.clone());
+                    if self.repair_python_dependencies {
+                        pool = pool.with_dependency_repair();
+                    }
+                    if let Some(timeout) = self.python_worker_timeout {
+                        pool = pool.with_request_timeout(timeout);
+                    }
+                    match self.slow_worker_threshold {
+                        Some(threshold) => pool.with_slow_threshold(threshold),
+                        None => pool,
+                    }
+                });
+                (Arc::new(python_auth), workers)
+            }
+        };
+        backend.set_privacy_mode(self.privacy)?;
+        backend.set_frame_blur(self.frame_blur)?;
+        // The default camera is the one every backend captures from already
+        if let Some(source) = self.camera_source.as_ref().filter(|source| **source != CameraSource::default()) {
+            backend = backend.with_camera_source(source)?;
+        }
+        let layout = StorageLayout {
+            data_dir: backend.data_dir().to_path_buf(),
+            generated_dir: std::path::absolute(&self.generated_dir)?,
+            source_dir: std::path::absolute(&self.source_dir)?,
+        };
+        let audit = AuditLog::new(layout.audit_log_path());
+        let outbox = (!self.event_sinks.is_empty())
+            .then(|| Outbox::start(layout.outbox_dir(), self.event_sinks, self.outbox_config))
+            .transpose()?;
+        let alerts = (!self.notifiers.is_empty())
+            .then(|| Alerts::start(self.notifiers, self.alert_rules, self.device_id.clone(), &layout.data_dir));
+        let far = self.far_budget
+            .map(|budget| FarMonitor::load(budget, layout.far_budget_path(), &layout.source_profiles()?))
+            .transpose()?;
+        let journal = Journal::new(layout.journal_dir());
+        let camera_lock = layout.camera_lock_path();
+        let recovered = match self.storage_mode {
+            StorageMode::ReadWrite => journal.recover()?,
+            StorageMode::ReadOnly => Vec::new(),
+        };
+        for operation in &recovered {
+            let mut entry = AuditEntry::at(self.clock.now(), AuditOperation::Recovery, operation.username.clone(), AuditOutcome::Failed);
+            entry.device_id = self.device_id.clone();
+            audit.append(&entry)?;
+        }
+        let inner = FaceAuthInner {
+            workers,
+            backend,
+            audit,
+            recorder: self.record_sessions.then(|| SessionRecorder::new(layout.recordings_dir()).with_compression(self.compression)),
+            layout,
+            auto_promote: self.auto_promote,
+            thumbnail_key: self.thumbnail_key,
+            backup_key: self.backup_key,
+            signing_key: self.signing_key,
+            messages: self.messages.unwrap_or_else(|| MessageCatalog::builtin(&self.locale)),
+            feedback: self.feedback,
+            locale: self.locale,
+            device_id: self.device_id,
+            enforcement: self.enforcement,
+            storage_mode: self.storage_mode,
+            privacy: self.privacy,
+            export_retention: self.export_retention,
+            latency_budget: self.latency_budget,
+            max_image_age: self.max_image_age,
+            ambiguity: self.ambiguity,
+            profiles: self.threshold_profiles.map(ProfileSwitcher::new).transpose()?,
+            cameras: self.cameras,
+            fusion: self.fusion,
+            device: DeviceArbiter::new(self.device_contention, Some(camera_lock)),
+            clock: self.clock,
+            random: self.random,
+            compression: self.compression,
+            tailgating_window: self.tailgating_window,
+            embedding_prefilter: self.embedding_prefilter,
+            credential_index: Mutex::new(None),
+            #[cfg(all(feature = "actuator", target_os = "linux"))]
+            actuator: self.actuator,
+            #[cfg(feature = "mqtt")]
+            home_assistant: self.home_assistant,
+            hooks: self.hooks,
+            policy: self.policy,
+            manipulation: self.manipulation,
+            age_estimator: self.age_estimator,
+            secondary_biometric: self.secondary_biometric,
+            document_calibration: self.document_calibration,
+            platform_fallback: self.platform_fallback,
+            outbox,
+            alerts,
+            far,
+            #[cfg(feature = "secure-sketch")]
+            unlock_policy: self.unlock_policy,
+            #[cfg(feature = "secure-sketch")]
+            vault_key: self.vault_key,
+            last_granted: Mutex::new(None),
+            #[cfg(feature = "telemetry")]
+            telemetry: self.telemetry.map(TelemetryReporter::start),
+            #[cfg(feature = "nfc")]
+            provisioning_key: self.provisioning_key,
+            #[cfg(feature = "nfc")]
+            nfc_tag_lifetime: self.nfc_tag_lifetime,
+            health: HealthMonitor::default(),
+            storage_lock: Mutex::new(()),
+            journal,
+            recovered,
+        };
+        Ok(FaceAuth { inner: Arc::new(inner) })
+    }
+}
+
+impl FaceAuth {
+    /// Create a new FaceAuth instance
+    pub fn new() -> Result<Self> {
+        Self::builder().build()
+    }
+
+    /// Start configuring a FaceAuth instance
+    pub fn builder() -> FaceAuthBuilder {
+        FaceAuthBuilder::default()
+    }
+
+    /// Register a new user with face samples
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - The username to register
+    /// * `samples` - Number of face samples to capture (default: 3)
+    /// * `generated_dir` - Directory path where user data will be saved
+    ///
+    /// # Returns
+    ///
+    /// Returns what was captured, the quality of each sample and where the
+    /// credential file was written. Failed captures are reported in the outcome;
+    /// an error means the backend itself failed.
+    ///
+    /// If an earlier registration of `username` with the same number of
+    /// samples was interrupted less than [`registration::PROGRESS_TTL`] ago,
+    /// the samples it stored are kept and capturing continues after them, see
+    /// [`FaceAuth::get_enrollment_progress`]. The registration fails, storing
+    /// nothing, if the samples captured then don't match the kept ones.
+    /// Older, unreadable or differently sized progress is discarded.
+    ///
+    /// The Python backend and the hybrid backend, which captures through it,
+    /// resume this way. Cloud backends capture every frame before enrolling
+    /// them with the service in one request, so an interrupted cloud
+    /// registration stores nothing and the next one starts over.
+    pub async fn register_user(&self, username: &str, samples: u32, generated_dir: &str) -> Result<RegistrationOutcome> {
+        self.ensure_writable("register users")?;
+        let _camera = self.lease_camera(DeviceOperation::Enrollment).await?;
+        // The script rewrites the whole database during registration
+        let _storage = self.lock_storage();
+        let transaction = self.begin_enrollment("registration", username, Path::new(generated_dir))?;
+        let resumed = self.resumable_progress(username, samples);
+        if let Some(progress) = &resumed {
+            tracing::info!(username, next_sample = progress.next_index(), samples, "Resuming interrupted registration");
+        }
+        self.notify(FeedbackEvent::LookAtCamera);
+        let captured = self.track(self.inner.backend.register_user(username, samples, generated_dir));
+        self.record_camera(captured.is_ok());
+        let mut outcome = captured?;
+        self.notify(FeedbackEvent::CaptureDone);
+        if let (Some(progress), Some(generated_file)) = (&resumed, &outcome.generated_file) {
+            // Rolled back with the transaction
+            check_resumed_samples(progress, &UserProfile::load(generated_file)?)?;
+        }
+
+        if let (Some(key), Some(_)) = (&self.inner.thumbnail_key, &outcome.thumbnail_file) {
+            outcome.thumbnail_file = Some(self.inner.layout.encrypt_thumbnail(username, key)?);
+        }
+
+        if self.inner.auto_promote && outcome.is_registered() {
+            let promoted = face_storage::promote_credential(username, Path::new(generated_dir), &self.inner.layout.source_dir)?;
+            outcome.promoted_file = Some(promoted);
+        }
+        transaction.commit()?;
+
+        let audit_outcome = if outcome.is_registered() { AuditOutcome::Granted } else { AuditOutcome::Failed };
+        let mut entry = self.audit_entry(AuditOperation::Registration, Some(username.to_string()), audit_outcome);
+        entry.device_id = self.inner.device_id.clone();
+        self.inner.audit.append(&entry)?;
+
+        Ok(outcome)
+    }
+
+    /// Progress a registration of `username` with `samples` samples continues
+    ///
+    /// Progress that is unreadable, of another size or older than
+    /// [`registration::PROGRESS_TTL`] is removed, so the backend starts over.
+    fn resumable_progress(&self, username: &str, samples: u32) -> Option<EnrollmentProgress> {
+        let path = self.inner.layout.registration_progress_path(username);
+        let progress = match EnrollmentProgress::load(&path) {
+            Ok(progress) => progress?,
+            Err(e) => {
+                tracing::warn!(username, "Discarding unreadable registration progress: {:#}", e);
+                let _ = std::fs::remove_file(&path);
+                return None;
+            }
+        };
+        let ttl = chrono::Duration::from_std(registration::PROGRESS_TTL).unwrap_or(chrono::Duration::MAX);
+        let fresh = progress.last_update().is_some_and(|updated| self.now() - updated < ttl);
+        if progress.resumes(samples) && fresh {
+            return Some(progress);
+        }
+        tracing::info!(username, samples = progress.samples_requested, updated_at = progress.updated_at.as_str(), "Discarding interrupted registration");
+        let _ = std::fs::remove_file(&path);
+        None
+    }
+
+    /// Samples stored by an interrupted registration of `username`, `None` if
+    /// there is none
+    pub async fn get_enrollment_progress(&self, username: &str) -> Result<Option<EnrollmentProgress>> {
+        face_storage::validate_username(username)?;
+        EnrollmentProgress::load(self.inner.layout.registration_progress_path(username))
+    }
+
+    /// Validate a generated credential and copy it into the source directory
+    ///
+    /// The file is written atomically, so a concurrent authentication never
+    /// sees a partially written credential.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - The user to promote
+    /// * `from_generated` - Directory the user was registered into
+    /// * `to_source` - Directory used for authentication
+    ///
+    /// # Returns
+    ///
+    /// Returns the path of the promoted credential file
+    pub async fn promote_user(&self, username: &str, from_generated: &str, to_source: &str) -> Result<PathBuf> {
+        self.ensure_writable("promote users")?;
+        face_storage::promote_credential(username, Path::new(from_generated), Path::new(to_source))
+    }
+
+    /// Refresh a user's own enrollment at a kiosk without an admin
+    ///
+    /// The live face must first match `username`'s current credential within
+    /// `tolerance`, otherwise this fails with [`FaceAuthError::NotVerified`].
+    /// Fresh samples are then captured, and only those that also match the
+    /// current template are added, so nobody can swap in another face after
+    /// the check. The newest samples are kept, up to a fixed maximum.
+    pub async fn reenroll(&self, username: &str, tolerance: f64) -> Result<RegistrationOutcome> {
+        self.ensure_writable("re-enroll users")?;
+        face_storage::validate_username(username)?;
+        let source_dir = self.inner.layout.source_dir.to_string_lossy().into_owned();
+        let current = UserProfile::load(face_storage::credential_path(&source_dir, username))
+            .map_err(|_| anyhow::anyhow!("User '{}' is not enrolled", username))?;
+
+        let _camera = self.lease_camera(DeviceOperation::Enrollment).await?;
+        self.notify(FeedbackEvent::LookAtCamera);
+        let verification = self.track(self.inner.backend.authenticate_user(tolerance, &source_dir));
+        self.record_camera(verification.is_ok());
+        let verification = verification?;
+        if verification.matched_user.as_deref() != Some(username) || verification.is_match != Some(true) {
+            let mut entry = self.audit_entry(AuditOperation::Registration, Some(username.to_string()), AuditOutcome::Denied);
+            entry.device_id = self.inner.device_id.clone();
+            entry.distance = verification.distance;
+            entry.denial = verification.denial.clone();
+            self.inner.audit.append(&entry)?;
+            self.notify(FeedbackEvent::AccessDenied { reason: verification.denial.clone() });
+            return Err(FaceAuthError::NotVerified { username: username.to_string(), denial: verification.denial }.into());
+        }
+
+        let _storage = self.lock_storage();
+        let transaction = self.begin_enrollment("re-enrollment", username, &self.inner.layout.generated_dir)?;
+        let staging = self.inner.layout.data_dir.join("reenrollment");
+        let staging_dir = staging.to_string_lossy().into_owned();
+        let captured = self.track(self.inner.backend.register_user(username, REENROLL_SAMPLES, &staging_dir));
+        self.record_camera(captured.is_ok());
+        self.notify(FeedbackEvent::CaptureDone);
+        let mut outcome = captured?;
+        let fresh = outcome.generated_file.as_ref().map(UserProfile::load).transpose()?;
+        if let Some(thumbnail) = outcome.thumbnail_file.take() {
+            outcome.thumbnail_file = if thumbnail.starts_with(&staging) {
+                let kept = self.inner.layout.thumbnail_path(username);
+                std::fs::create_dir_all(self.inner.layout.thumbnails_dir())
+                    .and_then(|_| std::fs::rename(&thumbnail, &kept))
+                    .ok()
+                    .map(|_| kept)
+            } else {
+                Some(thumbnail)
+            };
+        }
+        let _ = std::fs::remove_dir_all(&staging);
+
+        let fresh_samples = fresh.map(|profile| profile.face_encodings).unwrap_or_default();
+        let accepted: Vec<bool> = fresh_samples.iter()
+            .map(|sample| matching::best_match([&current], &sample.encoding, tolerance).is_some_and(|c| c.is_match))
+            .collect();
+        // Stored outcomes are reported in the same order as the samples they produced
+        let mut verdicts = accepted.iter();
+        outcome.samples.retain(|sample| sample.status == SampleStatus::Stored && verdicts.next() == Some(&true));
+        let added: Vec<FaceSample> = fresh_samples.into_iter()
+            .zip(&accepted)
+            .filter(|(_, accepted)| **accepted)
+            .map(|(sample, _)| FaceSample { sample_id: self.new_sample_id(), ..sample })
+            .collect();
+
+        let mut updated = current.clone();
+        let restored = if added.is_empty() {
+            // The backend replaced the database entry while capturing
+            &current
+        } else {
+            updated.face_encodings.extend(added.iter().cloned());
+            let excess = updated.face_encodings.len().saturating_sub(MAX_REENROLLED_SAMPLES);
+            updated.face_encodings.drain(..excess);
+            updated.sample_count = updated.face_encodings.len();
+            &updated
+        };
+        let db_path = self.inner.layout.database_path();
+        let mut db = FaceDatabase::load(&db_path)?.unwrap_or_default();
+        db.users.insert(username.to_string(), restored.clone());
+        db.save(&db_path)?;
+        self.inner.layout.update_user(username, |profile| *profile = restored.clone())?;
+        transaction.commit()?;
+        if added.is_empty() {
+            return Err(anyhow::anyhow!("None of the new samples matched '{}'; enrollment unchanged", username));
+        }
+
+        if let (Some(key), Some(_)) = (&self.inner.thumbnail_key, &outcome.thumbnail_file) {
+            outcome.thumbnail_file = Some(self.inner.layout.encrypt_thumbnail(username, key)?);
+        }
+        outcome.samples_captured = added.len() as u32;
+        outcome.generated_file = None;
+        outcome.promoted_file = Some(face_storage::credential_path(&source_dir, username));
+
+        let mut entry = self.audit_entry(AuditOperation::Registration, Some(username.to_string()), AuditOutcome::Granted);
+        entry.device_id = self.inner.device_id.clone();
+        entry.distance = verification.distance;
+        self.inner.audit.append(&entry)?;
+        tracing::info!(username, added = added.len(), samples = updated.sample_count, "Re-enrolled user");
+        Ok(outcome)
+    }
+
+    /// Queue photos submitted remotely, e.g. by an HR portal, for enrollment
+    ///
+    /// Nothing is enrolled until an admin calls [`FaceAuth::approve_enrollment`].
+    pub async fn submit_enrollment(
+        &self,
+        username: &str,
+        photos: &[Vec<u8>],
+        submitted_by: Option<String>,
+        metadata: UserMetadata,
+    ) -> Result<PendingEnrollment> {
+        self.ensure_writable("submit enrollments")?;
+        if self.inner.privacy == PrivacyMode::Strict {
+            return Err(FaceAuthError::PrivacyStrict { operation: "submit enrollments" }.into());
+        }
+        let pending = self.enrollment_queue().submit(username, photos, submitted_by, metadata)?;
+        tracing::info!(id = pending.id, username, photos = photos.len(), "Enrollment submitted for moderation");
+        Ok(pending)
+    }
+
+    /// Submitted enrollments awaiting a decision, oldest first
+    pub async fn pending_enrollments(&self) -> Result<Vec<PendingEnrollment>> {
+        self.enrollment_queue().list()
+    }
+
+    /// Check that a pending enrollment's photos show a face that isn't
+    /// already enrolled under another name (within `tolerance`) and that no
+    /// one is enrolled under its username yet
+    ///
+    /// The review is stored with the enrollment for the admin to see.
+    pub async fn review_enrollment(&self, id: &str, tolerance: f64) -> Result<EnrollmentReview> {
+        let (pending, _) = self.review(id, tolerance)?;
+        Ok(pending.review.expect("review was just stored"))
+    }
+
+    /// Review a pending enrollment, returning it with the review and the photos' encodings
+    fn review(&self, id: &str, tolerance: f64) -> Result<(PendingEnrollment, Vec<Option<Vec<f64>>>)> {
+        let queue = self.enrollment_queue();
+        let mut pending = queue.get(id)?;
+        let encodings = self.track(self.inner.backend.encode_images(&queue.photo_paths(&pending)))?;
+        let (enrolled, others): (Vec<UserProfile>, Vec<UserProfile>) = self.inner.layout.source_profiles()?
+            .into_iter()
+            .partition(|profile| profile.user_id == pending.username);
+        let name_taken = !enrolled.is_empty()
+            || FaceDatabase::load(self.inner.layout.database_path())?.is_some_and(|db| db.users.contains_key(&pending.username));
+        let duplicate_of = encodings.iter().flatten()
+            .filter_map(|encoding| matching::best_match(&others, encoding, tolerance))
+            .filter(|candidate| candidate.is_match)
+            .min_by(|a, b| a.distance.total_cmp(&b.distance));
+        let review = EnrollmentReview {
+            reviewed_at: self.now(),
+            usable_photos: encodings.iter().flatten().count(),
+            rejected_photos: pending.photos.iter().zip(&encodings).filter(|(_, e)| e.is_none()).map(|(p, _)| p.clone()).collect(),
+            duplicate_of,
+            name_taken,
+        };
+        pending.review = Some(review);
+        queue.save(&pending)?;
+        Ok((pending, encodings))
+    }
+
+    /// Enroll a pending submission after it passes the review, writing its
+    /// credential to `generated_dir` (and promoting it with `auto_promote`)
+    ///
+    /// Fails without enrolling anyone if the review doesn't pass, e.g. because
+    /// the username is taken; the submission then stays queued until approved
+    /// again or rejected. Photos go through the registration quality gate:
+    /// near-repeats of another photo are dropped and photos far from the
+    /// others are quarantined as outliers.
+    pub async fn approve_enrollment(&self, id: &str, tolerance: f64, generated_dir: &str) -> Result<RegistrationOutcome> {
+        self.ensure_writable("approve enrollments")?;
+        let (pending, encodings) = self.review(id, tolerance)?;
+        let review = pending.review.as_ref().expect("review was just stored");
+        if let Some(problem) = review.problem() {
+            return Err(FaceAuthError::EnrollmentReviewFailed { id: id.to_string(), problem }.into());
+        }
+
+        let now = self.local_timestamp();
+        let username = &pending.username;
+        let mut outcome = RegistrationOutcome {
+            username: username.clone(),
+            samples_requested: pending.photos.len() as u32,
+            samples_captured: review.usable_photos as u32,
+            samples: Vec::new(),
+            generated_file: None,
+            fully_enrolled: review.rejected_photos.is_empty(),
+            thumbnail_file: None,
+            promoted_file: None,
+        };
+        let mut samples: Vec<FaceSample> = Vec::new();
+        for (index, encoding) in (1..).zip(encodings) {
+            let status = match encoding {
+                Some(encoding) if registration::repeats_sample(&samples, &encoding) => SampleStatus::Duplicate,
+                Some(encoding) => {
+                    samples.push(FaceSample {
+                        encoding,
+                        timestamp: now.clone(),
+                        sample_id: self.new_sample_id(),
+                        image_path: None,
+                        quality: None,
+                        template_version: None,
+                        quarantine: None,
+                    });
+                    SampleStatus::Stored
+                }
+                None => SampleStatus::NoFace,
+            };
+            outcome.samples.push(SampleOutcome { index, status, quality: None });
+        }
+        outcome.samples_captured = samples.len() as u32;
+        outcome.fully_enrolled = samples.len() == pending.photos.len();
+        let mut profile = UserProfile {
+            user_id: username.clone(),
+            sample_count: samples.len(),
+            face_encodings: samples,
+            enrollment_date: Some(now),
+            metadata: pending.metadata.clone(),
+            extra: Default::default(),
+        };
+        for outlier in profile.detect_outliers() {
+            tracing::warn!(id, username = username.as_str(), sample_id = outlier.sample_id, "Quarantined an outlying enrollment photo");
+        }
+        let outcome = self.store_enrollment("enrollment approval", profile, Path::new(generated_dir), outcome)?;
+        self.enrollment_queue().remove(id)?;
+        tracing::info!(id, username = username.as_str(), "Remote enrollment approved");
+        Ok(outcome)
+    }
+
+    /// Discard a pending enrollment and its photos
+    pub async fn reject_enrollment(&self, id: &str) -> Result<()> {
+        self.ensure_writable("reject enrollments")?;
+        let pending = self.enrollment_queue().get(id)?;
+        let mut entry = self.audit_entry(AuditOperation::Registration, Some(pending.username), AuditOutcome::Denied);
+        entry.device_id = self.inner.device_id.clone();
+        self.inner.audit.append(&entry)?;
+        self.enrollment_queue().remove(id)
+    }
+
+    /// Start a guided kiosk enrollment of `username`, see [`crate::kiosk`]
+    pub fn enrollment_flow(&self, username: &str) -> EnrollmentFlowBuilder {
+        EnrollmentFlowBuilder::new(self.clone(), username, self.inner.layout.generated_dir.clone())
+    }
+
+    /// Pick up an unfinished [`EnrollmentFlow`] at the step it was saved at,
+    /// e.g. after the kiosk restarted
+    pub fn resume_enrollment(&self, id: &str) -> Result<EnrollmentFlow> {
+        EnrollmentFlow::resume(self.clone(), id)
+    }
+
+    /// Enrollment flows neither confirmed nor cancelled, oldest first
+    pub fn unfinished_enrollments(&self) -> Result<Vec<EnrollmentState>> {
+        self.enrollment_flow_store().list()
+    }
+
+    pub(crate) fn enrollment_flow_store(&self) -> FlowStore {
+        FlowStore::new(self.inner.layout.enrollment_flows_dir())
+    }
+
+    /// Capture one sample of `username` without enrolling it, for an
+    /// [`EnrollmentFlow`]
+    ///
+    /// The backend only captures as part of a registration, so it registers
+    /// into a staging directory and the user's database entry is put back,
+    /// journaled so a crash meanwhile leaves the database as it was.
+    pub(crate) async fn capture_enrollment_sample(&self, username: &str) -> Result<(SampleOutcome, Option<FaceSample>)> {
+        self.ensure_writable("capture enrollment samples")?;
+        let _camera = self.lease_camera(DeviceOperation::Enrollment).await?;
+        let _storage = self.lock_storage();
+        let staging = self.inner.layout.data_dir.join("enrollment_staging");
+        let staging_dir = staging.to_string_lossy().into_owned();
+        let transaction = self.begin_enrollment("kiosk capture", username, &staging)?;
+        let db_path = self.inner.layout.database_path();
+        let previous = FaceDatabase::load(&db_path)?.unwrap_or_default().users.remove(username);
+        // Each kiosk pose is a fresh capture; the flow itself is what resumes
+        let _ = std::fs::remove_file(self.inner.layout.registration_progress_path(username));
+
+        self.notify(FeedbackEvent::LookAtCamera);
+        let captured = self.track(self.inner.backend.register_user(username, 1, &staging_dir));
+        self.record_camera(captured.is_ok());
+        self.notify(FeedbackEvent::CaptureDone);
+        let staged = captured.as_ref().ok()
+            .and_then(|outcome| outcome.generated_file.as_ref())
+            .map(UserProfile::load)
+            .transpose();
+        let _ = std::fs::remove_dir_all(&staging);
+
+        let mut db = FaceDatabase::load(&db_path)?.unwrap_or_default();
+        match previous {
+            Some(profile) => db.users.insert(username.to_string(), profile),
+            None => db.users.remove(username),
+        };
+        db.save(&db_path)?;
+        transaction.commit()?;
+        let outcome = captured?.samples.pop()
+            .unwrap_or(SampleOutcome { index: 1, status: SampleStatus::CaptureFailed, quality: None });
+        let sample = staged?.and_then(|profile| profile.face_encodings.into_iter().next());
+        Ok((outcome, sample))
+    }
+
+    /// Enroll the captures of a reviewed [`EnrollmentFlow`], recording when
+    /// the user consented in the credential
+    pub(crate) async fn enroll_captures(&self, flow: &EnrollmentState) -> Result<RegistrationOutcome> {
+        self.ensure_writable("enroll users")?;
+        let samples: Vec<FaceSample> = flow.captures.iter().flatten().filter_map(|capture| capture.sample.clone()).collect();
+        let mut extra = serde_json::Map::new();
+        if let Some(consented_at) = flow.consented_at {
+            extra.insert("consented_at".to_string(), serde_json::to_value(consented_at)?);
+        }
+        let outcome = RegistrationOutcome {
+            username: flow.username.clone(),
+            samples_requested: flow.poses.len() as u32,
+            samples_captured: samples.len() as u32,
+            samples: flow.captures.iter().flatten().map(|capture| capture.outcome.clone()).collect(),
+            generated_file: None,
+            fully_enrolled: samples.len() == flow.poses.len(),
+            thumbnail_file: None,
+            promoted_file: None,
+        };
+        let profile = UserProfile {
+            user_id: flow.username.clone(),
+            sample_count: samples.len(),
+            face_encodings: samples,
+            enrollment_date: Some(self.local_timestamp()),
+            metadata: flow.metadata.clone(),
+            extra,
+        };
+        let outcome = self.store_enrollment("kiosk enrollment", profile, &flow.generated_dir, outcome)?;
+        tracing::info!(flow = flow.id, username = flow.username.as_str(), samples = outcome.samples_captured, "Kiosk enrollment confirmed");
+        Ok(outcome)
+    }
+
+    /// Enroll a new user from embeddings computed elsewhere, e.g. on the
+    /// user's phone, so no image of the face ever reaches this machine
+    ///
+    /// `model_id` names the model that produced `embeddings` and must be the
+    /// backend's [`FaceBackend::template_version`], otherwise this fails with
+    /// [`FaceAuthError::IncompatibleModel`]. Only users not enrolled yet can be
+    /// enrolled this way: nothing proves the embeddings come from an enrolled
+    /// user's face, so existing users refresh their samples with
+    /// [`FaceAuth::reenroll`]. Embeddings matching another enrolled user are
+    /// refused, and the samples go through the registration quality gate like
+    /// approved enrollments. The credential is written to the generated
+    /// directory (and promoted with `auto_promote`).
+    pub async fn enroll_embeddings(&self, username: &str, embeddings: &[Vec<f64>], model_id: &str) -> Result<RegistrationOutcome> {
+        self.ensure_writable("enroll embeddings")?;
+        face_storage::validate_username(username)?;
+        let expected = self.inner.backend.template_version();
+        if expected.starts_with(face_storage::REMOTE_TEMPLATE_PREFIX) {
+            return Err(anyhow::anyhow!("Templates of this backend are held by a cloud service and can't be enrolled locally"));
+        }
+        if model_id != expected {
+            return Err(FaceAuthError::IncompatibleModel { expected, actual: model_id.to_string() }.into());
+        }
+        if embeddings.is_empty() {
+            return Err(anyhow::anyhow!("An enrollment needs at least one embedding"));
+        }
+
+        let others = {
+            let _storage = self.lock_storage();
+            self.ensure_not_enrolled(username)?;
+            self.inner.layout.source_profiles()?
+        };
+        let duplicate_of = embeddings.iter()
+            .filter_map(|embedding| matching::best_match(&others, embedding, matching::DEFAULT_TOLERANCE))
+            .filter(|candidate| candidate.is_match)
+            .min_by(|a, b| a.distance.total_cmp(&b.distance));
+        if let Some(duplicate) = duplicate_of {
+            return Err(anyhow::anyhow!("Face already enrolled as '{}' (distance {:.3})", duplicate.user_id, duplicate.distance));
+        }
+
+        let timestamp = self.local_timestamp();
+        let template_version = (model_id != face_storage::DLIB_TEMPLATE_VERSION).then(|| model_id.to_string());
+        let mut samples: Vec<FaceSample> = Vec::new();
+        let mut outcomes = Vec::new();
+        for (index, embedding) in (1..).zip(embeddings) {
+            let status = if registration::repeats_sample(&samples, embedding) {
+                SampleStatus::Duplicate
+            } else {
+                samples.push(FaceSample {
+                    encoding: embedding.clone(),
+                    timestamp: timestamp.clone(),
+                    sample_id: self.new_sample_id(),
+                    image_path: None,
+                    quality: None,
+                    template_version: template_version.clone(),
+                    quarantine: None,
+                });
+                SampleStatus::Stored
+            };
+            outcomes.push(SampleOutcome { index, status, quality: None });
+        }
+        let mut profile = UserProfile {
+            user_id: username.to_string(),
+            sample_count: samples.len(),
+            face_encodings: samples,
+            enrollment_date: Some(timestamp),
+            metadata: UserMetadata::default(),
+            extra: Default::default(),
+        };
+        profile.validate()?;
+        for outlier in profile.detect_outliers() {
+            tracing::warn!(username, sample_id = outlier.sample_id, "Quarantined an outlying embedding");
+        }
+
+        let outcome = RegistrationOutcome {
+            username: username.to_string(),
+            samples_requested: embeddings.len() as u32,
+            samples_captured: profile.face_encodings.len() as u32,
+            samples: outcomes,
+            generated_file: None,
+            fully_enrolled: profile.face_encodings.len() == embeddings.len(),
+            thumbnail_file: None,
+            promoted_file: None,
+        };
+        let outcome = self.store_enrollment("embedding enrollment", profile, &self.inner.layout.generated_dir, outcome)?;
+        tracing::info!(username, model_id, samples = outcome.samples_captured, "Enrolled embeddings");
+        Ok(outcome)
+    }
+
+    /// Write a new user's enrollment assembled outside the backend to the
+    /// database and `generated_dir` (promoting it with `auto_promote`) and
+    /// audit it
+    ///
+    /// Fails if the name is taken meanwhile, so an enrollment never replaces
+    /// another user's.
+    fn store_enrollment(&self, operation: &str, profile: UserProfile, generated_dir: &Path, mut outcome: RegistrationOutcome) -> Result<RegistrationOutcome> {
+        let username = profile.user_id.clone();
+        let _storage = self.lock_storage();
+        self.ensure_not_enrolled(&username)?;
+        let transaction = self.begin_enrollment(operation, &username, generated_dir)?;
+        let generated_file = std::path::absolute(face_storage::credential_path(generated_dir, &username))?;
+        profile.save(&generated_file)?;
+        outcome.generated_file = Some(generated_file);
+        let db_path = self.inner.layout.database_path();
+        let mut db = FaceDatabase::load(&db_path)?.unwrap_or_default();
+        db.users.insert(username.clone(), profile);
+        db.save(&db_path)?;
+        if self.inner.auto_promote {
+            let promoted = face_storage::promote_credential(&username, generated_dir, &self.inner.layout.source_dir)?;
+            outcome.promoted_file = Some(promoted);
+        }
+        transaction.commit()?;
+
+        let mut entry = self.audit_entry(AuditOperation::Registration, Some(username), AuditOutcome::Granted);
+        entry.device_id = self.inner.device_id.clone();
+        self.inner.audit.append(&entry)?;
+        Ok(outcome)
+    }
+
+    /// Fail if `username` is in the database or the source directory
+    pub(crate) fn ensure_not_enrolled(&self, username: &str) -> Result<()> {
+        let in_database = FaceDatabase::load(self.inner.layout.database_path())?.is_some_and(|db| db.users.contains_key(username));
+        if in_database || self.inner.layout.source_profiles()?.iter().any(|profile| profile.user_id == username) {
+            return Err(anyhow::anyhow!("'{}' is already enrolled; enrolled users refresh their samples by re-enrolling", username));
+        }
+        Ok(())
+    }
+
+    /// Journal the files enrolling `username` into `generated_dir` writes, so
+    /// a failure or crash part way leaves the previous enrollment intact
+    fn begin_enrollment(&self, operation: &str, username: &str, generated_dir: &Path) -> Result<Transaction> {
+        face_storage::validate_username(username)?;
+        let layout = &self.inner.layout;
+        let mut transaction = self.inner.journal.begin(operation, Some(username))?;
+        transaction.protect_all([
+            layout.database_path(),
+            face_storage::credential_path(generated_dir, username),
+            face_storage::credential_path(&layout.source_dir, username),
+            layout.thumbnail_path(username),
+            layout.encrypted_thumbnail_path(username),
+        ])?;
+        Ok(transaction)
+    }
+
+    /// Access events not yet delivered to every event sink
+    pub fn pending_events(&self) -> Result<usize> {
+        self.inner.outbox.as_ref().map_or(Ok(0), Outbox::pending)
+    }
+
+    /// Operations a crash interrupted, rolled back when this instance was
+    /// built
+    pub fn recovered_operations(&self) -> &[RecoveredOperation] {
+        &self.inner.recovered
+    }
+
+    fn enrollment_queue(&self) -> EnrollmentQueue {
+        EnrollmentQueue::new(self.inner.layout.pending_enrollments_dir())
+    }
+
+    /// Authenticate the face in `request`'s frame
+    ///
+    /// Camera frames are captured from every configured camera and fused.
+    /// Files and uploads run on the worker pool when configured with
+    /// [`FaceAuthBuilder::python_workers`], so frames from several doors are
+    /// matched in parallel instead of queueing behind one process; EXIF and
+    /// other metadata are stripped before the frame is used, an upload only
+    /// touches the disk once they are, and the frame's [`ImageProvenance`] is
+    /// recorded in the audit log.
+    pub async fn authenticate(&self, request: &AuthRequest) -> Result<FaceAuthResult> {
+        let source_dir = match &request.source_dir {
+            Some(dir) => dir.to_string_lossy(),
+            None => self.source_dir().to_string_lossy(),
+        };
+        match &request.frame {
+            Frame::Camera => self.authenticate_capture(request, &source_dir).await,
+            Frame::File(image) => {
+                let received = std::fs::read(image)?;
+                self.authenticate_received(request, &source_dir, &received, &image.display().to_string(), Some(image))
+            }
+            Frame::Upload { image, source } => self.authenticate_received(request, &source_dir, image, source, None),
+        }
+    }
+
+    /// Match `request` like [`FaceAuth::authenticate`], e.g. to check the
+    /// setup from an admin page, without acting on the result
+    ///
+    /// No door opens, and no statistics, feedback, events or alerts are
+    /// produced; the attempt is only audited as [`AuditOperation::TestMatch`].
+    pub async fn test_match(&self, request: &AuthRequest) -> Result<FaceAuthResult> {
+        let mut request = request.clone();
+        request.match_only = true;
+        self.authenticate(&request).await
+    }
+
+    /// Verify that the face in `request` is `username`'s, comparing it with
+    /// that user's credential only (1:1) instead of everyone enrolled
+    ///
+    /// Faster and more private than [`FaceAuth::authenticate`] at large
+    /// sites; the claim usually comes from a badge, see
+    /// [`FaceAuth::verify_badge`]. Only `username` can be granted, whatever
+    /// the backend matched. A face that doesn't match is denied with
+    /// [`DenialReason::ClaimNotVerified`], as is anyone claiming a user
+    /// without a credential in the source directory, so the denial doesn't
+    /// tell who is enrolled.
+    pub async fn verify_user(&self, username: &str, request: &AuthRequest) -> Result<FaceAuthResult> {
+        face_storage::validate_username(username)?;
+        let source_dir = request.source_dir.clone().unwrap_or_else(|| self.source_dir().to_path_buf());
+        let credential = face_storage::credential_path(&source_dir, username);
+        // The backend matches against a directory holding only the claimed
+        // user, or no one if they aren't enrolled
+        let claim = self.inner.layout.claims_dir().join(self.inner.random.hex(8));
+        std::fs::create_dir_all(&claim)?;
+        let copied = match std::fs::copy(&credential, face_storage::credential_path(&claim, username)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+        let result = match copied {
+            Ok(()) => {
+                let mut request = request.clone().with_source_dir(&claim);
+                request.claimed_user = Some(username.to_string());
+                self.authenticate(&request).await
+            }
+            Err(e) => Err(e.into()),
+        };
+        let _ = std::fs::remove_dir_all(&claim);
+        result
+    }
+
+    /// Read the claimed user from the QR code on a badge, then verify the
+    /// face in `request` against them with [`FaceAuth::verify_user`]
+    ///
+    /// `badge` is an encoded image (JPEG or PNG) of the badge; fails if it
+    /// holds no readable QR code. See [`crate::qr`].
+    #[cfg(feature = "qr")]
+    pub async fn verify_badge(&self, badge: &[u8], request: &AuthRequest) -> Result<FaceAuthResult> {
+        let username = qr::decode_qr(badge)?.ok_or_else(|| anyhow::anyhow!("No QR code found on the badge"))?;
+        self.verify_user(&username, request).await
+    }
+
+    /// Authenticate a user by capturing their face
+    ///
+    /// # Arguments
+    ///
+    /// * `tolerance` - Face matching tolerance (0.0-1.0, lower = stricter)
+    /// * `source_dir` - Directory path where user data is loaded from
+    ///
+    /// # Returns
+    ///
+    /// Returns authentication result with user information
+    #[deprecated(since = "0.2.0", note = "use `FaceAuth::authenticate` with `AuthRequest::camera`")]
+    pub async fn authenticate_user(&self, tolerance: f64, source_dir: &str) -> Result<FaceAuthResult> {
+        self.authenticate(&AuthRequest::camera(tolerance).with_source_dir(source_dir)).await
+    }
+
+    /// Authenticate a user by capturing their face, with `context` such as the
+    /// door or a transaction amount
+    #[deprecated(since = "0.2.0", note = "use `FaceAuth::authenticate` with `AuthRequest::camera(..).with_context(..)`")]
+    pub async fn authenticate_user_with_context(&self, tolerance: f64, source_dir: &str, context: &AuthContext) -> Result<FaceAuthResult> {
+        self.authenticate(&AuthRequest::camera(tolerance).with_source_dir(source_dir).with_context(context.clone())).await
+    }
+
+    /// Authenticate a frame captured elsewhere, e.g. uploaded by a door terminal
+    #[deprecated(since = "0.2.0", note = "use `FaceAuth::authenticate` with `AuthRequest::file`")]
+    pub async fn authenticate_image(&self, tolerance: f64, source_dir: &str, image: &Path) -> Result<FaceAuthResult> {
+        self.authenticate(&AuthRequest::file(tolerance, image).with_source_dir(source_dir)).await
+    }
+
+    /// Authenticate an image received as bytes, e.g. over REST, from `source`
+    #[deprecated(since = "0.2.0", note = "use `FaceAuth::authenticate` with `AuthRequest::upload`")]
+    pub async fn authenticate_upload(&self, tolerance: f64, source_dir: &str, image: &[u8], source: &str) -> Result<FaceAuthResult> {
+        self.authenticate(&AuthRequest::upload(tolerance, image, source).with_source_dir(source_dir)).await
+    }
+
+    /// Capture from the configured cameras and authenticate, falling back to
+    /// the platform's prompt if that fails
+    async fn authenticate_capture(&self, request: &AuthRequest, source_dir: &str) -> Result<FaceAuthResult> {
+        let (tolerance, context) = (request.tolerance, &request.context);
+        if let Some(denial) = self.before_capture(context) {
+            return self.reject(request, source_dir, None, denial);
+        }
+        let _camera = match self.inner.device.acquire(DeviceOperation::Authentication).await {
+            Ok(lease) => lease,
+            Err(busy) => return Ok(self.device_busy(tolerance, context, busy)),
+        };
+        self.notify(FeedbackEvent::LookAtCamera);
+        self.prepare_frame();
+        let captured = match self.inner.cameras.as_slice() {
+            [] => self.track(self.inner.backend.authenticate_user(tolerance, source_dir)).map(|raw| (raw, Vec::new())),
+            cameras => self.track(self.capture_cameras(cameras, tolerance, source_dir)),
+        };
+        let (raw, cameras) = match (captured, &self.inner.platform_fallback) {
+            (Ok(captured), _) => captured,
+            (Err(e), Some((platform, username))) => {
+                self.record_camera(false);
+                return self.authenticate_with_platform(e, platform.as_ref(), username, source_dir, request);
+            }
+            (Err(e), None) => {
+                self.record_camera(false);
+                return Err(e);
+            }
+        };
+        self.record_camera(true);
+        self.notify(FeedbackEvent::CaptureDone);
+        let mut pending = self.track(self.decide_authentication(raw, source_dir, context, None))?;
+        pending.result.cameras = cameras;
+        self.conclude(pending, request)
+    }
+
+    /// Let the platform's prompt verify `username` after face matching failed
+    /// with `error`
+    fn authenticate_with_platform(
+        &self,
+        error: anyhow::Error,
+        platform: &dyn PlatformBiometrics,
+        username: &str,
+        source_dir: &str,
+        request: &AuthRequest,
+    ) -> Result<FaceAuthResult> {
+        tracing::warn!(platform = platform.name(), "Face matching unavailable, falling back: {:#}", error);
+        let started = Instant::now();
+        let verified = match platform.verify(&format!("Verify that you are {}", username)) {
+            Ok(verified) => verified,
+            Err(e) => return Err(error.context(format!("{} fallback failed: {:#}", platform.name(), e))),
+        };
+        let raw = StandaloneAuthResult {
+            success: true,
+            is_match: Some(verified),
+            confidence: None,
+            distance: None,
+            threshold: None,
+            matched_user: verified.then(|| username.to_string()),
+            closest_user: Some(username.to_string()),
+            runner_up: None,
+            sample_distances: Vec::new(),
+            brightness: None,
+            image_path: None,
+            processing_time_ms: Some(started.elapsed().as_millis() as u32),
+            timings: TimingBreakdown::default(),
+            liveness: None,
+            denial: (!verified).then(|| DenialReason::PlatformRefused { platform: platform.name().to_string() }),
+            raw_output: String::new(),
+        };
+        let mut pending = self.track(self.decide_authentication(raw, source_dir, &request.context, None))?;
+        pending.result.platform = Some(platform.name().to_string());
+        self.conclude(pending, request)
+    }
+
+    /// Authenticate with every configured camera at once and fuse the views
+    fn capture_cameras(&self, cameras: &[u32], tolerance: f64, source_dir: &str) -> Result<(StandaloneAuthResult, Vec<CameraScore>)> {
+        let backend = &self.inner.backend;
+        let outcomes: Vec<(u32, Result<StandaloneAuthResult>)> = std::thread::scope(|scope| {
+            let captures: Vec<_> = cameras.iter()
+                .map(|&camera| (camera, scope.spawn(move || backend.authenticate_camera(camera, tolerance, source_dir))))
+                .collect();
+            captures.into_iter()
+                .map(|(camera, capture)| (camera, capture.join().unwrap_or_else(|_| Err(anyhow::anyhow!("Camera {} panicked", camera)))))
+                .collect()
+        });
+
+        let mut views = Vec::new();
+        let mut first_error = None;
+        for (camera, outcome) in outcomes {
+            match outcome {
+                Ok(view) => views.push((camera, view)),
+                Err(e) => {
+                    tracing::warn!(camera, "Camera failed: {:#}", e);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        match (views.is_empty(), first_error) {
+            (true, Some(e)) => Err(e),
+            _ => Ok(fusion::fuse(views, self.inner.fusion)),
+        }
+    }
+
+    /// Camera authentications with retries, an optional liveness challenge
+    /// and a cooldown, against the instance's source directory; see
+    /// [`crate::auth_flow`]
+    pub fn auth_flow(&self, config: AuthFlowConfig) -> AuthFlow {
+        AuthFlow::new(self.clone(), config)
+    }
+
+    /// Current time on the instance's [`Clock`], e.g. for
+    /// [`Session::require_at`]
+    pub fn now(&self) -> DateTime<Utc> {
+        self.inner.clock.now()
+    }
+
+    /// [`FaceAuth::now`] in local time, as sample and enrollment timestamps
+    /// are stored
+    fn local_timestamp(&self) -> String {
+        self.now().with_timezone(&chrono::Local).format("%Y-%m-%dT%H:%M:%S%.6f").to_string()
+    }
+
+    /// [`FaceAuthResult::session`] signed with the device key, for sessions
+    /// the application stores between actions
+    ///
+    /// Only results this instance signed turn into sessions, so a result
+    /// altered after the decision, e.g. to a higher assurance level, fails
+    /// with [`FaceAuthError::InvalidSignature`]. None for a denial.
+    pub fn signed_session(&self, result: &FaceAuthResult, ttl: Duration) -> Result<Option<SignedSession>> {
+        let Some(key) = &self.inner.signing_key else {
+            anyhow::bail!("Signed sessions need a signing key");
+        };
+        let Some(session) = result.session(ttl) else {
+            return Ok(None);
+        };
+        let signed = result.signed.as_ref().ok_or(FaceAuthError::InvalidSignature { device_id: self.inner.device_id.clone() })?;
+        let claims = signed.verify(key)?;
+        if !claims.authenticated || claims.user_id.as_deref() != Some(session.user_id.as_str())
+            || claims.assurance != Some(session.assurance) || claims.timestamp != session.authenticated_at
+        {
+            return Err(FaceAuthError::InvalidSignature { device_id: self.inner.device_id.clone() }.into());
+        }
+        Ok(Some(session.sign(key)?))
+    }
+
+    #[cfg(feature = "server")]
+    pub(crate) fn clock(&self) -> Arc<dyn Clock> {
+        self.inner.clock.clone()
+    }
+
+    #[cfg(feature = "server")]
+    pub(crate) fn random(&self) -> Arc<dyn RandomSource> {
+        self.inner.random.clone()
+    }
+
+    fn audit_entry(&self, operation: AuditOperation, user: Option<String>, outcome: AuditOutcome) -> AuditEntry {
+        AuditEntry::at(self.now(), operation, user, outcome)
+    }
+
+    /// A fresh UUIDv7 sample id, from the instance's clock and random source
+    pub(crate) fn new_sample_id(&self) -> String {
+        sample_id::generate(self.now(), &*self.inner.random)
+    }
+
+    pub(crate) fn source_dir(&self) -> &Path {
+        &self.inner.layout.source_dir
+    }
+
+    pub(crate) fn generated_dir(&self) -> &Path {
+        &self.inner.layout.generated_dir
+    }
+
+    /// Blur or pixelate faces in any image with this instance's backend
+    ///
+    /// Nothing is matched or recorded; see [`crate::redact`] for use without
+    /// a `FaceAuth`.
+    pub async fn redact_faces(&self, image: &[u8], policy: &RedactionPolicy) -> Result<Redaction> {
+        self.track(redact::redact_faces_with(self.inner.backend.as_ref(), image, policy))
+    }
+
+    /// Compare a live selfie with the portrait on a photo of an identity
+    /// document, for remote identity verification
+    ///
+    /// The portrait is found on the document even when small or partly
+    /// washed out by glare, and the distance is turned into a calibrated
+    /// probability, see [`FaceAuthBuilder::document_calibration`]. Nobody is
+    /// enrolled; the comparison is recorded in the audit log.
+    pub async fn match_document(&self, selfie: &Path, document_photo: &Path) -> Result<DocumentMatch> {
+        let selfie = self.track(self.inner.backend.encode_images(&[selfie.to_path_buf()]))?.pop().flatten();
+        let document = self.track(self.inner.backend.encode_document(document_photo))?;
+        let matched = DocumentMatch::compare(selfie.as_deref(), &document, &self.inner.document_calibration);
+        tracing::info!(score = matched.score, glare = matched.glare, issue = ?matched.issue, "Selfie compared with identity document");
+
+        let outcome = if matched.is_match { AuditOutcome::Granted } else { AuditOutcome::Denied };
+        let mut entry = self.audit_entry(AuditOperation::DocumentVerification, None, outcome);
+        entry.distance = matched.distance;
+        entry.threshold = Some(self.inner.document_calibration.midpoint);
+        entry.device_id = self.inner.device_id.clone();
+        match self.inner.audit.append(&entry) {
+            Err(e) if self.inner.storage_mode == StorageMode::ReadOnly => tracing::warn!("Failed to write audit entry: {:#}", e),
+            other => other?,
+        }
+        Ok(matched)
+    }
+
+    /// Seal `secret`, e.g. a disk-encryption passphrase, for
+    /// [`FaceAuth::unlock_secret`] once a live face matches `username`
+    ///
+    /// The match must meet the [`UnlockPolicy`]. The key is derived from the
+    /// captured face and the [`FaceAuthBuilder::vault_key`], never from stored
+    /// samples, and is itself never stored. Sealing again replaces the
+    /// previous secret.
+    #[cfg(feature = "secure-sketch")]
+    pub async fn seal_secret(&self, username: &str, secret: &[u8]) -> Result<()> {
+        self.ensure_writable("seal secrets")?;
+        face_storage::validate_username(username)?;
+        let key = self.vault_key()?;
+        if !face_storage::credential_path(&self.inner.layout.source_dir, username).exists() {
+            return Err(anyhow::anyhow!("User '{}' is not enrolled", username));
+        }
+        let (result, probe) = self.match_for_vault(username).await?;
+        self.check_vault_match(username, &result)?;
+        let probe = probe.ok_or_else(|| anyhow::anyhow!("No captured frame to seal with; sealing doesn't work in strict privacy mode"))?;
+        let sealed = SealedSecret::seal(&probe, secret, self.inner.unlock_policy.params, key)?;
+
+        let _storage = self.lock_storage();
+        sealed.save(self.inner.layout.sealed_secret_path(username))?;
+        tracing::info!(username, "Sealed secret");
+        Ok(())
+    }
+
+    /// Release the secret sealed for `username` once a live face matches them
+    ///
+    /// The match must meet the [`UnlockPolicy`] tolerance and assurance level,
+    /// and the captured frame must reproduce the face-derived key. Otherwise
+    /// this fails with [`FaceAuthError::NotVerified`] or
+    /// [`FaceAuthError::InsufficientAssurance`]. Every attempt is audited.
+    /// The match only releases the secret; it is not an authentication, so
+    /// no door is opened and no access event is sent.
+    #[cfg(feature = "secure-sketch")]
+    pub async fn unlock_secret(&self, username: &str) -> Result<Vec<u8>> {
+        face_storage::validate_username(username)?;
+        let key = self.vault_key()?;
+        let path = self.inner.layout.sealed_secret_path(username);
+        if !path.exists() {
+            return Err(anyhow::anyhow!("No secret is sealed for '{}'", username));
+        }
+        let sealed = SealedSecret::load(&path)?;
+        let (result, probe) = self.match_for_vault(username).await?;
+        let checked = self.check_vault_match(username, &result);
+        let secret = match (&checked, probe) {
+            (Ok(()), Some(probe)) => sealed.open(&probe, key)?,
+            _ => None,
+        };
+
+        let outcome = if secret.is_some() { AuditOutcome::Granted } else { AuditOutcome::Denied };
+        let mut entry = self.audit_entry(AuditOperation::SecretUnlock, Some(username.to_string()), outcome);
+        entry.distance = result.distance;
+        entry.threshold = Some(self.inner.unlock_policy.tolerance);
+        entry.device_id = self.inner.device_id.clone();
+        entry.denial = result.denial.clone();
+        self.inner.audit.append(&entry)?;
+        match (secret, checked) {
+            (Some(secret), _) => {
+                tracing::info!(username, "Released sealed secret");
+                Ok(secret)
+            }
+            (None, Err(e)) => Err(e),
+            (None, Ok(())) => Err(FaceAuthError::NotVerified { username: username.to_string(), denial: result.denial }.into()),
+        }
+    }
+
+    /// Capture and match a live face for the vault, with the embedding of the
+    /// frame; the match is decided but never recorded or acted on
+    #[cfg(feature = "secure-sketch")]
+    async fn match_for_vault(&self, username: &str) -> Result<(FaceAuthResult, Option<Vec<f64>>)> {
+        let request = AuthRequest::camera(self.inner.unlock_policy.tolerance);
+        let (tolerance, context) = (request.tolerance, &request.context);
+        let source_dir = self.inner.layout.source_dir.to_string_lossy().into_owned();
+        if let Some(denial) = self.before_capture(context) {
+            let denial = self.reject(&request, &source_dir, None, denial)?.denial;
+            return Err(FaceAuthError::NotVerified { username: username.to_string(), denial }.into());
+        }
+
+        let _camera = self.lease_camera(DeviceOperation::Authentication).await?;
+        self.notify(FeedbackEvent::LookAtCamera);
+        self.prepare_frame();
+        let raw = self.track(self.inner.backend.authenticate_user(tolerance, &source_dir))?;
+        self.notify(FeedbackEvent::CaptureDone);
+        // Encoded before the frame may be removed; strict privacy keeps no frame to encode
+        let probe = match &raw.image_path {
+            Some(frame) => self.track(self.inner.backend.encode_images(std::slice::from_ref(frame)))?.pop().flatten(),
+            None => None,
+        };
+        let pending = self.track(self.decide_authentication(raw, &source_dir, context, None))?;
+        Ok((pending.result, probe))
+    }
+
+    /// Fail unless `result` matched `username` at the [`UnlockPolicy`]'s
+    /// assurance level
+    #[cfg(feature = "secure-sketch")]
+    fn check_vault_match(&self, username: &str, result: &FaceAuthResult) -> Result<()> {
+        let required = self.inner.unlock_policy.required_assurance();
+        let verified = result.is_authenticated && result.user_id.as_deref() == Some(username);
+        match result.assurance {
+            Some(actual) if verified && actual < required => Err(FaceAuthError::InsufficientAssurance { required, actual }.into()),
+            Some(_) if verified => Ok(()),
+            _ => Err(FaceAuthError::NotVerified { username: username.to_string(), denial: result.denial.clone() }.into()),
+        }
+    }
+
+    /// Scrub and authenticate a received image; `path` is where it already
+    /// is on disk, used as is when there's nothing to strip
+    fn authenticate_received(
+        &self,
+        request: &AuthRequest,
+        source_dir: &str,
+        received: &[u8],
+        source: &str,
+        path: Option<&Path>,
+    ) -> Result<FaceAuthResult> {
+        let (tolerance, context) = (request.tolerance, &request.context);
+        let scrubbed = provenance::scrub_metadata(received)?;
+        let provenance = ImageProvenance::new(source, received, &scrubbed);
+        if let Some(denial) = self.before_capture(context) {
+            return self.reject(request, source_dir, Some(provenance), denial);
+        }
+        if let (Some(max_age), Some(captured_at)) = (self.inner.max_image_age, scrubbed.captured_at) {
+            if self.now().signed_duration_since(captured_at).to_std().is_ok_and(|age| age > max_age) {
+                tracing::info!(source, %captured_at, "Rejected an image older than the freshness window");
+                return self.reject(request, source_dir, Some(provenance), DenialReason::ImageTooOld { captured_at });
+            }
+        }
+
+        if self.inner.privacy == PrivacyMode::Strict && (path.is_none() || scrubbed.stripped) {
+            self.prepare_frame();
+            let raw = self.inner.backend.authenticate_image_bytes(tolerance, source_dir, &scrubbed.bytes);
+            let pending = self.track(self.decide_authentication(self.track(raw)?, source_dir, context, Some(provenance)))?;
+            return self.conclude(pending, request);
+        }
+
+        // The Python matcher reads frames from disk; a scrubbed copy is removed when dropped
+        let copy = match path {
+            Some(_) if !scrubbed.stripped => None,
+            _ => {
+                let mut copy = tempfile::Builder::new()
+                    .prefix(".upload_")
+                    .suffix(&format!(".{}", scrubbed.extension))
+                    .tempfile_in(self.inner.backend.data_dir())?;
+                copy.write_all(&scrubbed.bytes)?;
+                Some(copy)
+            }
+        };
+        let image = copy.as_ref().map(|copy| copy.path()).or(path).expect("either the original or a copy exists");
+        self.authenticate_scrubbed(request, source_dir, image, provenance)
+    }
+
+    fn authenticate_scrubbed(&self, request: &AuthRequest, source_dir: &str, image: &Path, provenance: ImageProvenance) -> Result<FaceAuthResult> {
+        let (tolerance, context) = (request.tolerance, &request.context);
+        let manipulation = match &self.inner.manipulation {
+            Some((detector, max_score)) => {
+                let score = self.track(self.manipulation_score(&**detector, image))?;
+                if let Some((score, maximum)) = score.zip(*max_score).filter(|&(score, maximum)| score > maximum) {
+                    tracing::info!(source = provenance.source, score, maximum, "Rejected a probe that looks manipulated");
+                    let denial = DenialReason::ManipulationSuspected { score, maximum };
+                    let mut pending = self.decide_authentication(unmatched(tolerance, denial), source_dir, context, Some(provenance))?;
+                    pending.result.manipulation_score = Some(score);
+                    return self.conclude(pending, request);
+                }
+                score
+            }
+            None => None,
+        };
+        self.prepare_frame();
+        let raw = match &self.inner.workers {
+            Some(workers) => workers.authenticate_image(tolerance, source_dir, image),
+            None => self.inner.backend.authenticate_image(tolerance, source_dir, image),
+        };
+        let mut pending = self.track(self.decide_authentication(self.track(raw)?, source_dir, context, Some(provenance)))?;
+        pending.result.manipulation_score = manipulation;
+        self.conclude(pending, request)
+    }
+
+    /// Highest manipulation score of the faces in `image`, each scored on a
+    /// crop of its own; `None` when there is no face to score
+    ///
+    /// A score the detector can't express as a number counts as certainly
+    /// manipulated.
+    fn manipulation_score(&self, detector: &dyn ManipulationDetector, image: &Path) -> Result<Option<f64>> {
+        let (_crops_dir, crops) = self.crop_faces(image)?;
+        let mut highest: Option<f64> = None;
+        for crop in crops {
+            let score = detector.score(&crop)?;
+            let score = if score.is_nan() {
+                tracing::warn!("Manipulation detector returned no usable score, treating the face as manipulated");
+                1.0
+            } else {
+                score.clamp(0.0, 1.0)
+            };
+            highest = Some(highest.map_or(score, |highest| highest.max(score)));
+        }
+        Ok(highest)
+    }
+
+    /// Apparent age of every face in `image`, largest first, each estimated
+    /// on a crop of its own
+    fn estimate_ages(&self, estimator: &dyn AgeEstimator, image: &Path) -> Result<Vec<AgeEstimate>> {
+        let (_crops_dir, crops) = self.crop_faces(image)?;
+        crops.iter().map(|crop| estimator.estimate(crop)).collect()
+    }
+
+    /// Crops of every face in `image`, largest first, in a temporary
+    /// directory removed once it's dropped
+    fn crop_faces(&self, image: &Path) -> Result<(tempfile::TempDir, Vec<PathBuf>)> {
+        let crops_dir = tempfile::Builder::new().prefix("face_auth_crops_").tempdir()?;
+        let crops = self.inner.backend.crop_faces(image, crops_dir.path())?;
+        Ok((crops_dir, crops))
+    }
+
+    /// Authenticate whoever steps in front of the camera, until `on_result`
+    /// returns `false`
+    ///
+    /// For always-on devices such as door terminals. Frames only reach the
+    /// encoder once something moved and a face is visible, see [`SceneGate`];
+    /// each attempt goes through enforcement and auditing like
+    /// [`FaceAuth::authenticate`]. Returns how many frames were skipped, also
+    /// when a [`CameraSource::VideoFile`] ends.
+    pub async fn watch<F>(&self, tolerance: f64, source_dir: &str, gate: &SceneGate, mut on_result: F) -> Result<SceneStats>
+    where
+        F: FnMut(FaceAuthResult) -> bool,
+    {
+        let _camera = self.lease_camera(DeviceOperation::Watch).await?;
+        self.prepare_frame();
+        let stats = self.inner.backend.watch(tolerance, source_dir, gate, &mut |raw, stats| {
+            self.record_camera(true);
+            let result = self.finish_authentication(raw, source_dir, &AuthContext::new(), None)?;
+            tracing::debug!(frames = stats.frames, attempts = stats.attempts, "Scene change authenticated");
+            Ok(on_result(result))
+        });
+        let stats = self.track(stats)?;
+        tracing::info!(frames = stats.frames, attempts = stats.attempts, skipped = stats.skipped_fraction(), "Stopped watching the camera");
+        Ok(stats)
+    }
+
+    /// Follow each face in front of the camera across frames, identifying it
+    /// once when it enters the view, until `on_event` returns `false`
+    ///
+    /// Unlike [`FaceAuth::watch`], a person standing in view is matched only
+    /// once, and leaving is reported too. Entries go through enforcement and
+    /// auditing like [`FaceAuth::authenticate`].
+    pub async fn track_faces<F>(
+        &self,
+        tolerance: f64,
+        source_dir: &str,
+        gate: &SceneGate,
+        tracking: &TrackingConfig,
+        mut on_event: F,
+    ) -> Result<SceneStats>
+    where
+        F: FnMut(TrackEvent<FaceAuthResult>) -> bool,
+    {
+        let _camera = self.lease_camera(DeviceOperation::Watch).await?;
+        self.prepare_frame();
+        let stats = self.inner.backend.track_faces(tolerance, source_dir, gate, tracking, &mut |event, _| {
+            let event = event.try_map(|raw| {
+                self.record_camera(true);
+                self.finish_authentication(raw, source_dir, &AuthContext::new(), None)
+            })?;
+            if let TrackEvent::Exited { track_id, user_id, frames } = &event {
+                tracing::debug!(track_id, user = user_id.as_deref().unwrap_or("-"), frames, "Track left the view");
+            }
+            let tailgating = match &event {
+                TrackEvent::Entered { track_id, result } if !result.is_authenticated => self.check_tailgating(*track_id)?,
+                _ => None,
+            };
+            Ok(on_event(event) && tailgating.is_none_or(&mut on_event))
+        });
+        let stats = self.track(stats)?;
+        tracing::info!(frames = stats.frames, attempts = stats.attempts, skipped = stats.skipped_fraction(), "Stopped tracking faces");
+        Ok(stats)
+    }
+
+    /// Tailgating event for a track denied within the tailgating window of the
+    /// last granted access
+    fn check_tailgating(&self, track_id: u64) -> Result<Option<TrackEvent<FaceAuthResult>>> {
+        let Some(window) = self.inner.tailgating_window else {
+            return Ok(None);
+        };
+        let last_granted = self.inner.last_granted.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let Some((granted_at, followed_user)) = last_granted.filter(|(at, _)| at.elapsed() <= window) else {
+            return Ok(None);
+        };
+        let after = granted_at.elapsed();
+        tracing::warn!(track_id, followed_user = %followed_user, after_ms = after.as_millis() as u64, "Tailgating suspected");
+        let mut entry = self.audit_entry(AuditOperation::Tailgating, Some(followed_user.clone()), AuditOutcome::Denied);
+        entry.device_id = self.inner.device_id.clone();
+        match self.inner.audit.append(&entry) {
+            Err(e) if self.inner.storage_mode == StorageMode::ReadOnly => tracing::warn!("Failed to write audit entry: {:#}", e),
+            other => other?,
+        }
+        Ok(Some(TrackEvent::TailgatingSuspected { track_id, followed_user, after, device_id: self.inner.device_id.clone() }))
+    }
+
+    /// Why a [`HookPoint::BeforeCapture`] hook refuses the attempt, if one does
+    fn before_capture(&self, context: &AuthContext) -> Option<DenialReason> {
+        let device_id = self.inner.device_id.as_deref();
+        match hooks::run_hooks::<()>(&self.inner.hooks, HookPoint::BeforeCapture, device_id, context, None) {
+            HookVerdict::Allow => None,
+            HookVerdict::Deny { reason } => Some(DenialReason::HookDenied { reason }),
+        }
+    }
+
+    /// Deny an attempt before any face is matched
+    fn reject(&self, request: &AuthRequest, source_dir: &str, provenance: Option<ImageProvenance>, denial: DenialReason) -> Result<FaceAuthResult> {
+        let pending = self.decide_authentication(unmatched(request.tolerance, denial), source_dir, &request.context, provenance)?;
+        self.conclude(pending, request)
+    }
+
+    /// Refuse a camera attempt because another capture holds the camera
+    ///
+    /// No face was seen, so unlike `reject` it isn't audited or
+    /// counted as a failed attempt.
+    fn device_busy(&self, tolerance: f64, context: &AuthContext, busy: Busy) -> FaceAuthResult {
+        tracing::info!(operation = %busy.operation, eta = ?busy.eta, "Camera busy, authentication refused");
+        let denial = DenialReason::DeviceBusy { operation: busy.operation, eta: busy.eta };
+        self.notify(FeedbackEvent::AccessDenied { reason: Some(denial.clone()) });
+        let mut result = FaceAuthResult::from(unmatched(tolerance, denial));
+        result.decided_at = self.now();
+        result.context = context.clone();
+        result
+    }
+
+    /// Take the camera for `operation` until the lease is dropped, failing
+    /// with [`FaceAuthError::DeviceBusy`] while another capture holds it
+    async fn lease_camera(&self, operation: DeviceOperation) -> Result<DeviceLease<'_>> {
+        self.inner.device.acquire(operation).await.map_err(|busy| {
+            tracing::info!(%operation, holder = %busy.operation, eta = ?busy.eta, "Camera busy");
+            FaceAuthError::DeviceBusy { operation: busy.operation, eta: busy.eta }.into()
+        })
+    }
+
+    /// Apply the preprocessing of the threshold profile expected for the next frame
+    fn prepare_frame(&self) {
+        if let Some(profiles) = &self.inner.profiles {
+            self.inner.backend.set_preprocessing(&profiles.for_next_frame().preprocessing);
+        }
+    }
+
+    /// Use the named threshold profile for every authentication, e.g. when a
+    /// gate switches to its night setup, or go back to picking one by
+    /// brightness with `None`
+    pub fn set_threshold_profile(&self, name: Option<&str>) -> Result<()> {
+        let profiles = self.inner.profiles.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("No threshold profiles are configured"))?;
+        profiles.set_fixed(name)?;
+        tracing::info!(profile = name.unwrap_or("automatic"), "Threshold profile selected");
+        Ok(())
+    }
+
+    /// Profile the next authentication is expected to use
+    pub fn threshold_profile(&self) -> Option<ThresholdProfile> {
+        self.inner.profiles.as_ref().map(|profiles| profiles.for_next_frame().clone())
+    }
+
+    /// Authenticate an embedding computed elsewhere, e.g. by a device in
+    /// hybrid mode that never sends the image
+    ///
+    /// Matching happens in Rust against the credentials in `source_dir`; the
+    /// backend is not involved. The credentials are kept in a
+    /// [`CredentialIndex`] between calls, and only the files changed since,
+    /// e.g. by enrolling or removing a user, are read again.
+    pub async fn authenticate_embedding(&self, tolerance: f64, source_dir: &str, encoding: &[f64]) -> Result<FaceAuthResult> {
+        let started = Instant::now();
+        let (ranked, enrolled) = {
+            let mut cached = self.inner.credential_index.lock().unwrap_or_else(PoisonError::into_inner);
+            let index = match cached.as_mut() {
+                Some(index) if index.dir() == Path::new(source_dir) => index,
+                _ => cached.insert(CredentialIndex::new(source_dir, EmbeddingPrecision::F32, self.inner.embedding_prefilter)),
+            };
+            index.refresh()?;
+            (index.index().ranked_matches(encoding, tolerance), index.profile_count())
+        };
+        let mut ranked = ranked.into_iter();
+        let candidate = ranked.next();
+        let mut raw = StandaloneAuthResult {
+            success: true,
+            is_match: Some(candidate.as_ref().is_some_and(|c| c.is_match)),
+            confidence: candidate.as_ref().map(MatchCandidate::confidence),
+            distance: candidate.as_ref().map(|c| c.distance),
+            threshold: Some(tolerance),
+            matched_user: candidate.as_ref().filter(|c| c.is_match).map(|c| c.user_id.clone()),
+            closest_user: candidate.map(|c| c.user_id),
+            runner_up: ranked.next(),
+            sample_distances: Vec::new(),
+            brightness: None,
+            image_path: None,
+            processing_time_ms: Some(started.elapsed().as_millis() as u32),
+            timings: TimingBreakdown { match_ms: started.elapsed().as_millis() as u64, ..Default::default() },
+            liveness: None,
+            denial: None,
+            raw_output: String::new(),
+        };
+        if enrolled == 0 {
+            raw.denial = Some(DenialReason::NoUsersEnrolled);
+        }
+        self.finish_authentication(raw, source_dir, &AuthContext::new(), None)
+    }
+
+    /// Load models, open the camera and read the enrolled credentials ahead of
+    /// the first authentication
+    ///
+    /// Without this the first request pays for model loading, which can take
+    /// more than ten seconds. Long-running services should call it at startup.
+    pub async fn warm_up(&self) -> Result<WarmUpReport> {
+        let started = Instant::now();
+        let mut report = WarmUpReport {
+            users_loaded: self.inner.layout.source_profiles()?.len(),
+            ..Default::default()
+        };
+
+        if let Some(workers) = &self.inner.workers {
+            report.workers_ready = workers.warm_up()?;
+        }
+        report.camera_ready = self.inner.backend.warm_up(true)?.camera_ready;
+        if let Some(ready) = report.camera_ready {
+            self.record_camera(ready);
+        }
+
+        report.elapsed_ms = started.elapsed().as_millis() as u64;
+        tracing::info!(?report, "Warm-up complete");
+        Ok(report)
+    }
+
+    /// User-facing strings in the configured locale, e.g. to show
+    /// [`FaceAuthResult::denial`] on a kiosk
+    pub fn messages(&self) -> &MessageCatalog {
+        &self.inner.messages
+    }
+
+    /// Occupancy of the worker pool and its crashes, restarts and slow
+    /// responses, if one is configured
+    pub fn worker_status(&self) -> Option<WorkerStatus> {
+        self.inner.workers.as_ref().map(WorkerPool::status)
+    }
+
+    /// Reinstall the pinned Python dependencies of the worker pool now, see
+    /// [`WorkerPool::repair_dependencies`]
+    pub fn repair_python_dependencies(&self) -> Result<()> {
+        let workers = self.inner.workers.as_ref().ok_or_else(|| anyhow::anyhow!("No Python worker pool configured"))?;
+        workers.repair_dependencies()
+    }
+
+    /// Apply enforcement, statistics and auditing to a backend decision
+    fn finish_authentication(
+        &self,
+        raw: StandaloneAuthResult,
+        source_dir: &str,
+        context: &AuthContext,
+        provenance: Option<ImageProvenance>,
+    ) -> Result<FaceAuthResult> {
+        let pending = self.decide_authentication(raw, source_dir, context, provenance)?;
+        self.record_decision(pending)
+    }
+
+    /// Record `pending` as `request` asks: only its claimed user is granted,
+    /// and with [`AuthRequest::hold_for_challenge`] a grant below
+    /// [`AssuranceLevel::L3`] is held for a liveness challenge instead
+    fn conclude(&self, mut pending: PendingDecision, request: &AuthRequest) -> Result<FaceAuthResult> {
+        if let Some(claimed) = &request.claimed_user {
+            let result = &mut pending.result;
+            let other = result.is_authenticated && result.user_id.as_ref() != Some(claimed);
+            // Not telling "not enrolled" from "not a match" keeps usernames from being probed
+            if other || matches!(result.denial, Some(DenialReason::NoUsersEnrolled | DenialReason::BelowThreshold { .. })) {
+                result.is_authenticated = false;
+                result.assurance = None;
+                result.user_id = None;
+                result.metadata = None;
+                result.greeting = None;
+                result.denial = Some(DenialReason::ClaimNotVerified);
+            }
+        }
+        if request.match_only {
+            return self.record_test_match(pending);
+        }
+        if !(request.hold_for_challenge && pending.result.is_authenticated && pending.result.assurance < Some(AssuranceLevel::L3)) {
+            return self.record_decision(pending);
+        }
+        tracing::info!(user = pending.result.user_id.as_deref().unwrap_or("-"), "Match held for a liveness challenge");
+        let mut held = pending.result.clone();
+        held.is_authenticated = false;
+        held.assurance = None;
+        held.held = Some(Box::new(pending));
+        Ok(held)
+    }
+
+    /// Act on a match held by [`AuthRequest::hold_for_challenge`] once the
+    /// user took the liveness challenge: granted at [`AssuranceLevel::L3`] if
+    /// they passed, denied with [`DenialReason::LivenessFailed`] otherwise
+    pub(crate) fn resolve_challenge(&self, held: FaceAuthResult, passed: bool) -> Result<FaceAuthResult> {
+        let mut pending = *held.held.ok_or_else(|| anyhow::anyhow!("No match is held for a liveness challenge"))?;
+        let result = &mut pending.result;
+        if passed {
+            result.assurance = Some(AssuranceLevel::L3);
+        } else {
+            result.is_authenticated = false;
+            result.assurance = None;
+            result.denial = Some(DenialReason::LivenessFailed);
+            result.user_id = None;
+            result.metadata = None;
+            result.greeting = None;
+        }
+        self.record_decision(pending)
+    }
+
+    /// Decide on a backend result: enforcement, ambiguity, hooks and the
+    /// decision policy, without recording or acting on the decision
+    fn decide_authentication(
+        &self,
+        mut raw: StandaloneAuthResult,
+        source_dir: &str,
+        context: &AuthContext,
+        provenance: Option<ImageProvenance>,
+    ) -> Result<PendingDecision> {
+        let started = Instant::now();
+        let profile = self.inner.profiles.as_ref().map(|profiles| profiles.for_frame(raw.brightness));
+        if let Some(profile) = profile {
+            apply_profile(&mut raw, profile);
+        }
+        let closest_user = raw.closest_user.clone();
+        let runner_up = raw.runner_up.clone();
+        let runner_up_distance = runner_up.as_ref().map(|r| r.distance);
+        let liveness = raw.liveness;
+        // A failed check denies even a match the backend reported alongside it
+        let liveness_passed = match (&raw.denial, liveness) {
+            (Some(DenialReason::LivenessFailed), _) => Some(false),
+            (_, Some(_)) => Some(true),
+            (_, None) => None,
+        };
+        let ages = match (&self.inner.age_estimator, &raw.image_path) {
+            (Some(estimator), Some(frame)) => self.estimate_ages(&**estimator, frame).unwrap_or_else(|e| {
+                tracing::warn!("Age estimation failed: {:#}", e);
+                Vec::new()
+            }),
+            _ => Vec::new(),
+        };
+        let age = ages.first().copied();
+        if let (Some(recorder), Some(frame)) = (&self.inner.recorder, &raw.image_path) {
+            recorder.record(frame, RecordedDecision::from(&raw), self.inner.device_id.clone())?;
+        }
+        let read_only = self.inner.storage_mode == StorageMode::ReadOnly;
+        let capture = raw.image_path.as_ref()
+            .filter(|frame| !read_only && frame.starts_with(self.inner.layout.captures_dir()))
+            .and_then(|frame| frame.file_name())
+            .map(|name| name.to_string_lossy().into_owned());
+        if let (true, Some(frame)) = (read_only, &raw.image_path) {
+            // Only frames the backend captured itself, never images passed in by the caller
+            if frame.starts_with(self.inner.layout.captures_dir()) {
+                let _ = std::fs::remove_file(frame);
+            }
+        }
+        let mut result: FaceAuthResult = raw.into();
+        result.decided_at = self.now();
+        result.profile = profile.map(|p| p.name.clone());
+        result.context = context.clone();
+        result.age = age;
+        result.ages = ages.clone();
+
+        if result.is_authenticated && liveness_passed == Some(false) {
+            result.is_authenticated = false;
+            result.user_id = None;
+            result.denial = Some(DenialReason::LivenessFailed);
+        }
+
+        if let (false, None, Some(distance), Some(threshold)) = (result.is_authenticated, &result.denial, result.distance, result.threshold) {
+            result.denial = Some(DenialReason::BelowThreshold { distance, threshold });
+        }
+
+        if let (Some(policy), true, Some(distance), Some(runner_up)) = (self.inner.ambiguity, result.is_authenticated, result.distance, runner_up.clone()) {
+            if runner_up.distance - distance < policy.margin {
+                tracing::info!(
+                    user = result.user_id.as_deref().unwrap_or("-"),
+                    runner_up = %runner_up.user_id,
+                    distance,
+                    runner_up_distance = runner_up.distance,
+                    "Ambiguous match"
+                );
+                result.is_authenticated = false;
+                result.denial = Some(DenialReason::Ambiguous { distance, runner_up_distance: runner_up.distance });
+                let matched = result.user_id.take();
+                if policy.second_factor {
+                    result.candidates = matched.into_iter().chain([runner_up.user_id]).collect();
+                }
+            }
+        }
+
+        if let (true, Some(biometric), Some(user), Some(distance), Some(threshold)) =
+            (result.is_authenticated, &self.inner.secondary_biometric, &result.user_id, result.distance, result.threshold)
+        {
+            let secondary = biometric.score(user, context).unwrap_or_else(|e| {
+                tracing::warn!("{} scoring failed: {:#}", biometric.name(), e);
+                None
+            });
+            result.modalities = Some(ModalityScores {
+                modality: biometric.name().to_string(),
+                face: multimodal::face_score(distance, threshold),
+                secondary,
+            });
+        }
+
+        if result.is_authenticated {
+            if let HookVerdict::Deny { reason } = hooks::run_hooks(&self.inner.hooks, HookPoint::AfterDecision, self.inner.device_id.as_deref(), context, Some(&result)) {
+                result.is_authenticated = false;
+                result.user_id = None;
+                result.denial = Some(DenialReason::HookDenied { reason });
+            }
+        }
+
+        if let (true, Some(policy), Some(user), Some(distance)) = (result.is_authenticated, &self.inner.policy, result.user_id.clone(), result.distance) {
+            let input = PolicyInput {
+                candidates: [MatchCandidate { user_id: user, distance, is_match: true }].into_iter().chain(runner_up).collect(),
+                quality: None,
+                liveness: liveness_passed,
+                device_id: self.inner.device_id.clone(),
+                context: context.clone(),
+                age,
+                ages,
+                modalities: result.modalities.clone(),
+            };
+            let decision = policy.decide(&input).unwrap_or_else(|e| {
+                tracing::warn!("Decision policy failed: {:#}", e);
+                PolicyDecision::Deny { reason: "Decision policy failed".into() }
+            });
+            match decision {
+                PolicyDecision::Accept => {}
+                PolicyDecision::Deny { reason } => {
+                    result.is_authenticated = false;
+                    result.denial = Some(DenialReason::PolicyDenied { reason });
+                    result.user_id = None;
+                }
+                PolicyDecision::StepUp { reason } => {
+                    result.is_authenticated = false;
+                    result.denial = Some(DenialReason::StepUpRequired { reason });
+                    result.candidates = result.user_id.take().into_iter().collect();
+                }
+            }
+        }
+
+        if self.inner.enforcement == EnforcementMode::Shadow {
+            tracing::info!(
+                would_grant = result.is_authenticated,
+                user = result.user_id.as_deref().unwrap_or("-"),
+                distance = result.distance.unwrap_or(f64::NAN),
+                "Shadow mode decision"
+            );
+            result.shadow_decision = Some(result.is_authenticated);
+            if result.is_authenticated {
+                result.denial = Some(DenialReason::ShadowMode);
+            }
+            result.is_authenticated = false;
+        }
+
+        result.assurance = result.is_authenticated.then(|| AssuranceLevel::for_match(liveness));
+        result.timings.policy_ms = started.elapsed().as_millis() as u64;
+        Ok(PendingDecision { result, source_dir: source_dir.to_string(), closest_user, runner_up_distance, provenance, capture })
+    }
+
+    /// Audit a decision of [`FaceAuth::test_match`] as a test, without any
+    /// other effect
+    fn record_test_match(&self, pending: PendingDecision) -> Result<FaceAuthResult> {
+        let PendingDecision { result, closest_user, provenance, capture, .. } = pending;
+        let outcome = if result.is_authenticated { AuditOutcome::Granted } else { AuditOutcome::Denied };
+        let mut entry = self.audit_entry(AuditOperation::TestMatch, closest_user, outcome);
+        entry.distance = result.distance;
+        entry.threshold = result.threshold;
+        entry.device_id = self.inner.device_id.clone();
+        entry.denial = result.denial.clone();
+        entry.context = result.context.clone();
+        entry.provenance = provenance;
+        entry.manipulation_score = result.manipulation_score;
+        entry.capture = capture;
+        let _storage = self.lock_storage();
+        match self.inner.audit.append(&entry) {
+            Err(e) if self.inner.storage_mode == StorageMode::ReadOnly => tracing::warn!("Failed to write audit entry: {:#}", e),
+            other => other?,
+        }
+        Ok(result)
+    }
+
+    /// Record a decision and act on it: statistics, the audit log, feedback,
+    /// signing, the actuator, event sinks and alerts
+    ///
+    /// The actuator only opens for a grant that was recorded and signed, so
+    /// no door opens without an audit entry.
+    fn record_decision(&self, pending: PendingDecision) -> Result<FaceAuthResult> {
+        let started = Instant::now();
+        let PendingDecision { mut result, source_dir, closest_user, runner_up_distance, provenance, capture } = pending;
+        let (source_dir, context) = (source_dir.as_str(), result.context.clone());
+        let read_only = self.inner.storage_mode == StorageMode::ReadOnly;
+        let _storage = self.lock_storage();
+        let matched_user = result.user_id.clone()
+            .filter(|user| result.is_authenticated && face_storage::validate_username(user).is_ok());
+        if let Some(user) = &matched_user {
+            let profile = face_storage::credential_path(source_dir, user);
+            result.metadata = UserProfile::load(profile).ok().map(|p| p.metadata);
+
+            let previous = if read_only {
+                self.inner.layout.user_stats(user)?
+            } else {
+                self.inner.layout.record_authentication(user, result.distance)?
+            };
+            result.greeting = Some(Greeting::new(user, result.metadata.as_ref(), &previous, &self.inner.locale));
+        }
+
+        let outcome = if result.is_authenticated { AuditOutcome::Granted } else { AuditOutcome::Denied };
+        let mut entry = self.audit_entry(AuditOperation::Authentication, closest_user, outcome);
+        entry.distance = result.distance;
+        entry.threshold = result.threshold;
+        entry.device_id = self.inner.device_id.clone();
+        entry.shadow_decision = result.shadow_decision;
+        entry.denial = result.denial.clone();
+        entry.context = context;
+        entry.provenance = provenance;
+        entry.manipulation_score = result.manipulation_score;
+        entry.capture = capture;
+        match self.inner.audit.append(&entry) {
+            Err(e) if read_only => tracing::warn!("Failed to write audit entry: {:#}", e),
+            other => other?,
+        }
+
+        self.notify(match (&result.is_authenticated, &result.greeting) {
+            (true, Some(greeting)) => FeedbackEvent::AccessGranted { display_name: greeting.display_name.clone() },
+            (true, None) => FeedbackEvent::AccessGranted { display_name: result.user_id.clone().unwrap_or_default() },
+            (false, _) => FeedbackEvent::AccessDenied { reason: result.denial.clone() },
+        });
+
+        if result.is_authenticated {
+            self.inner.health.record_success();
+            if let Some(user) = &result.user_id {
+                *self.inner.last_granted.lock().unwrap_or_else(PoisonError::into_inner) = Some((Instant::now(), user.clone()));
+            }
+        }
+        if let Some(key) = &self.inner.signing_key {
+            let claims = ResultClaims {
+                user_id: result.user_id.clone().filter(|_| result.is_authenticated),
+                authenticated: result.is_authenticated,
+                timestamp: result.decided_at,
+                device_id: self.inner.device_id.clone(),
+                assurance: result.assurance,
+            };
+            result.signed = Some(claims.sign(key)?);
+        }
+        #[cfg(all(feature = "actuator", target_os = "linux"))]
+        if let (Some(actuator), Some(user)) = (&self.inner.actuator, &matched_user) {
+            actuator.trigger(user, result.metadata.as_ref(), self.inner.device_id.as_deref());
+        }
+        result.timings.policy_ms += started.elapsed().as_millis() as u64;
+        #[cfg(feature = "telemetry")]
+        if let Some(telemetry) = &self.inner.telemetry {
+            telemetry.counters().record(result.is_authenticated, result.timings.total_ms());
+        }
+        #[cfg(feature = "mqtt")]
+        if let Some(home_assistant) = &self.inner.home_assistant {
+            if let Err(e) = home_assistant.publish_decision(result.user_id.as_deref(), result.is_authenticated) {
+                tracing::warn!("Failed to publish to Home Assistant: {:#}", e);
+            }
+        }
+        if let Some(outbox) = &self.inner.outbox {
+            let mut event = AccessEvent::new(
+                self.inner.device_id.clone(),
+                result.user_id.clone().filter(|_| result.is_authenticated),
+                result.is_authenticated,
+            );
+            event.timestamp = result.decided_at;
+            event.distance = result.distance;
+            event.denial = result.denial.clone();
+            event.assurance = result.assurance;
+            event.signed = result.signed.clone();
+            if let Err(e) = outbox.enqueue(&event) {
+                tracing::warn!("Failed to queue access event: {:#}", e);
+            }
+        }
+        if let Some(alerts) = &self.inner.alerts {
+            alerts.record_attempt(result.is_authenticated, result.denial.as_ref());
+        }
+        if let (Some(far), Some(tolerance)) = (&self.inner.far, result.threshold) {
+            self.record_far(far, tolerance, runner_up_distance, source_dir);
+        }
+        if let Some(budget) = self.inner.latency_budget {
+            if result.timings.total_ms() > budget.as_millis() as u64 {
+                tracing::warn!(timings = ?result.timings, budget_ms = budget.as_millis() as u64, "Authentication exceeded latency budget");
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn record_far(&self, far: &FarMonitor, tolerance: f64, runner_up: Option<f64>, source_dir: &str) {
+        if far.needs_reseed() {
+            match face_storage::load_credentials_dir(Path::new(source_dir)) {
+                Ok(profiles) => far.reseed(&profiles),
+                Err(e) => tracing::warn!("Failed to load credentials for false-accept estimate: {:#}", e),
+            }
+        }
+        let report = far.record_attempt(tolerance, runner_up);
+        if self.inner.storage_mode == StorageMode::ReadWrite {
+            if let Err(e) = far.save() {
+                tracing::warn!("Failed to save false-accept budget: {:#}", e);
+            }
+        }
+        if !far.over_budget(&report) {
+            return;
+        }
+        let alert = Alert::FalseAcceptBudget {
+            projected: report.projected_false_accepts,
+            budget: report.budget,
+            tolerance,
+            recommended_tolerance: report.recommended_tolerance,
+        };
+        match &self.inner.alerts {
+            Some(alerts) => alerts.raise(alert),
+            None => tracing::warn!(
+                enrolled_users = report.enrolled_users,
+                recommended_tolerance = ?report.recommended_tolerance,
+                "{}", alert.summary()
+            ),
+        }
+    }
+
+    /// False accepts expected at `tolerance` this period, from the credentials
+    /// in the source directory and the attempts so far
+    ///
+    /// Fails unless built with [`FaceAuthBuilder::far_budget`].
+    pub async fn far_report(&self, tolerance: f64) -> Result<FarReport> {
+        let far = self.inner.far.as_ref().ok_or_else(|| anyhow::anyhow!("No false-accept budget configured"))?;
+        far.reseed(&self.inner.layout.source_profiles()?);
+        Ok(far.report_at(tolerance))
+    }
+
+    /// Replay recorded sessions with a new tolerance and enrolled set
+    ///
+    /// Returns the original and new decision for every recorded frame, so a
+    /// threshold or model upgrade can be validated without re-capturing users.
+    /// Sessions are only available when built with
+    /// [`FaceAuthBuilder::record_sessions`].
+    pub async fn replay_sessions(&self, tolerance: f64, source_dir: &str) -> Result<ReplayReport> {
+        let recorder = SessionRecorder::new(self.inner.layout.recordings_dir());
+        replay::replay(self.inner.backend.as_ref(), &recorder, tolerance, source_dir)
+    }
+
+    /// Authentication attempts attributed to a user within a time range
+    ///
+    /// Denied attempts are attributed to the closest candidate, which answers
+    /// "why did the door deny me yesterday". Pass `..` for the full history.
+    pub async fn auth_history(&self, username: &str, range: impl RangeBounds<DateTime<Utc>>) -> Result<Vec<AuditEntry>> {
+        self.inner.audit.auth_history(username, range)
+    }
+
+    /// Rename a registered user
+    ///
+    /// Updates the database entry, sample ids, the credential files in the
+    /// configured generated and source directories and exported credentials,
+    /// so a typo at enrollment doesn't require re-enrolling the person.
+    pub async fn rename_user(&self, old_username: &str, new_username: &str) -> Result<()> {
+        self.ensure_writable("rename users")?;
+        let _storage = self.lock_storage();
+        self.inner.layout.rename_user(old_username, new_username, self.inner.compression)
+    }
+
+    /// Small JPEG of the user's face captured at enrollment
+    ///
+    /// Lets admin dashboards and welcome screens show who was matched
+    /// without keeping the full enrollment photos around.
+    pub async fn user_thumbnail(&self, username: &str) -> Result<Vec<u8>> {
+        self.inner.layout.thumbnail(username, self.inner.thumbnail_key.as_ref())
+    }
+
+    /// Export a user's face data to a file
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - The username to export
+    /// * `filename` - Optional filename (auto-generated if empty)
+    ///
+    /// With [`FaceAuthBuilder::compression`] the file is compressed, and
+    /// auto-generated names end in `.json.zst`.
+    pub async fn export_user(&self, username: &str, filename: &str) -> Result<bool> {
+        if !self.inner.compression.is_enabled() {
+            return self.inner.backend.export_user(username, filename);
+        }
+        let path = match filename {
+            "" => self.inner.layout.exports_dir().join(format!(
+                "{}_credentials_{}.json.{}",
+                username,
+                self.now().with_timezone(&chrono::Local).format("%Y%m%d_%H%M%S"),
+                compression::EXTENSION,
+            )),
+            filename => PathBuf::from(filename),
+        };
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        if !self.inner.backend.export_user(username, &path.to_string_lossy())? {
+            return Ok(false);
+        }
+        let json = std::fs::read(&path)?;
+        face_storage::write_atomic(&path, &self.inner.compression.compress(&json)?)?;
+        Ok(true)
+    }
+
+    /// Import a user's face data from a file
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - Path to the file to import, compressed or not
+    pub async fn import_user(&self, filename: &str) -> Result<bool> {
+        self.ensure_writable("import users")?;
+        let _storage = self.lock_storage();
+        let mut transaction = self.inner.journal.begin("import", None)?;
+        transaction.protect(self.inner.layout.database_path())?;
+        let path = match Path::new(filename) {
+            path if path.exists() => path.to_path_buf(),
+            path => self.inner.backend.data_dir().join(path),
+        };
+        let imported = match std::fs::read(&path) {
+            Ok(bytes) if compression::is_compressed(&bytes) => {
+                // The backend reads plain JSON, so it gets the export unpacked
+                let unpacked = tempfile::Builder::new().prefix(".import_").suffix(".json").tempfile_in(self.inner.backend.data_dir())?;
+                std::fs::write(unpacked.path(), compression::decompress_at_most(&bytes, face_storage::MAX_EXPORT_LEN)?)?;
+                self.inner.backend.import_user(&unpacked.path().to_string_lossy())?
+            }
+            _ => self.inner.backend.import_user(filename)?,
+        };
+        transaction.commit()?;
+        Ok(imported)
+    }
+
+    /// Send a user's credential to a device waiting in [`receive_user`](Self::receive_user)
+    ///
+    /// Both devices must be given the same passphrase; the credential is
+    /// encrypted end to end and only accepted by a peer that knows it.
+    #[cfg(feature = "transfer")]
+    pub async fn send_user(&self, username: &str, peer_addr: impl tokio::net::ToSocketAddrs, passphrase: &str) -> Result<()> {
+        let export = tempfile::Builder::new().prefix(".transfer_").suffix(".json").tempfile_in(self.inner.backend.data_dir())?;
+        if !self.inner.backend.export_user(username, &export.path().to_string_lossy())? {
+            return Err(anyhow::anyhow!("User '{}' not found", username));
+        }
+        let credential = std::fs::read(export.path());
+        drop(export);
+
+        let stream = tokio::net::TcpStream::connect(peer_addr).await?;
+        let mut channel = SecureChannel::handshake(stream, Role::Sender, passphrase).await?;
+        channel.send(&self.inner.compression.compress(&credential?)?).await?;
+        match channel.receive().await?.as_slice() {
+            b"ok" => Ok(()),
+            _ => Err(anyhow::anyhow!("Peer rejected the credential; its log tells why")),
+        }
+    }
+
+    /// Wait on `listen_addr` for one credential sent with [`send_user`](Self::send_user)
+    /// and import it, returning the username
+    ///
+    /// Only a single connection is accepted, so a wrong passphrase ends the
+    /// transfer instead of allowing repeated guesses.
+    #[cfg(feature = "transfer")]
+    pub async fn receive_user(&self, listen_addr: impl tokio::net::ToSocketAddrs, passphrase: &str) -> Result<String> {
+        self.ensure_writable("import users")?;
+        let listener = tokio::net::TcpListener::bind(listen_addr).await?;
+        tracing::info!("Waiting for a credential on {}", listener.local_addr()?);
+        let (stream, peer) = listener.accept().await?;
+        let mut channel = SecureChannel::handshake(stream, Role::Receiver, passphrase).await?;
+        let credential = channel.receive().await?;
+
+        let imported = compression::decompress_at_most(&credential, face_storage::MAX_EXPORT_LEN).and_then(|credential| {
+            let export = face_storage::ExportedCredential::from_json(&String::from_utf8_lossy(&credential))?;
+            face_storage::validate_username(&export.user_id)?;
+            let received = tempfile::Builder::new().prefix(".transfer_").suffix(".json").tempfile_in(self.inner.backend.data_dir())?;
+            std::fs::write(received.path(), &credential)?;
+            let _storage = self.lock_storage();
+            self.inner.backend.import_user(&received.path().to_string_lossy()).map(|_| export.user_id)
+        });
+        match imported {
+            Ok(username) => {
+                channel.send(b"ok").await?;
+                tracing::info!("Received '{}' from {}", username, peer);
+                Ok(username)
+            }
+            Err(e) => {
+                // The reason names local paths and backend errors, so it stays in the log
+                tracing::warn!("Rejected a credential from {}: {:#}", peer, e);
+                let _ = channel.send(b"rejected").await;
+                Err(e)
+            }
+        }
+    }
+
+    /// What [`write_nfc_tag`](Self::write_nfc_tag) puts on a tag of
+    /// `capacity` bytes: the user's most recent samples that fit, encrypted as
+    /// an NDEF message, see [`crate::nfc`]
+    ///
+    /// Requires a [`FaceAuthBuilder::provisioning_key`]. The tag expires after
+    /// the [`FaceAuthBuilder::nfc_tag_lifetime`].
+    #[cfg(feature = "nfc")]
+    pub async fn nfc_tag_data(&self, username: &str, capacity: usize) -> Result<nfc::NfcTag> {
+        let key = self.provisioning_key()?;
+        let export = tempfile::Builder::new().prefix(".nfc_").suffix(".json").tempfile_in(self.inner.backend.data_dir())?;
+        if !self.inner.backend.export_user(username, &export.path().to_string_lossy())? {
+            return Err(anyhow::anyhow!("User '{}' not found", username));
+        }
+        let profile = face_storage::ExportedCredential::load(export.path())?.user_data;
+        let issued_at = self.now();
+        let lifetime = chrono::Duration::from_std(self.inner.nfc_tag_lifetime)?;
+        let credential = nfc::TagCredential {
+            user_id: profile.user_id,
+            serial: self.inner.random.hex(8),
+            issued_at,
+            expires_at: issued_at.checked_add_signed(lifetime).unwrap_or(DateTime::<Utc>::MAX_UTC),
+            samples: profile.face_encodings.into_iter().rev()
+                .filter(|sample| !sample.is_quarantined() && !sample.is_remote())
+                .map(|sample| nfc::TagSample { encoding: sample.encoding, template_version: sample.template_version })
+                .collect(),
+        };
+        let (data, samples) = nfc::tag_data(&credential, key, capacity)?;
+        Ok(nfc::NfcTag { data, serial: credential.serial, expires_at: credential.expires_at, samples })
+    }
+
+    /// Write a user's credential to the NFC tag on `reader`, or on the first
+    /// reader with a tag
+    ///
+    /// As many of the user's samples are written as fit on the tag; fails
+    /// without writing if none does. The returned serial revokes the tag, see
+    /// [`revoke_nfc_tag`](Self::revoke_nfc_tag).
+    #[cfg(feature = "nfc")]
+    pub async fn write_nfc_tag(&self, username: &str, reader: Option<&str>) -> Result<nfc::NfcTag> {
+        let reader = NfcReader::connect(reader)?;
+        let tag = self.nfc_tag_data(username, reader.capacity()?).await?;
+        reader.write(&tag.data)?;
+        Ok(tag)
+    }
+
+    /// Import the credential in a tag's data area, returning the username
+    ///
+    /// Fails unless it was written with this instance's
+    /// [`FaceAuthBuilder::provisioning_key`], and for expired or revoked tags.
+    #[cfg(feature = "nfc")]
+    pub async fn import_nfc_tag_data(&self, data: &[u8]) -> Result<String> {
+        self.ensure_writable("import users")?;
+        let credential = nfc::unseal(&nfc::parse_ndef(data)?, self.provisioning_key()?)?;
+        face_storage::validate_username(&credential.user_id)?;
+        if credential.is_expired(self.now()) {
+            return Err(anyhow::anyhow!("The tag of '{}' expired on {}", credential.user_id, credential.expires_at));
+        }
+        if self.revoked_nfc_tags()?.contains(&credential.serial) {
+            return Err(anyhow::anyhow!("The tag {} of '{}' was revoked", credential.serial, credential.user_id));
+        }
+
+        let timestamp = credential.issued_at.with_timezone(&chrono::Local).format("%Y-%m-%dT%H:%M:%S%.6f").to_string();
+        let face_encodings: Vec<FaceSample> = credential.samples.into_iter().map(|sample| FaceSample {
+            encoding: sample.encoding,
+            timestamp: timestamp.clone(),
+            image_path: None,
+            sample_id: self.new_sample_id(),
+            quality: None,
+            template_version: sample.template_version,
+            quarantine: None,
+        }).collect();
+        let export = face_storage::ExportedCredential {
+            user_id: credential.user_id.clone(),
+            user_data: UserProfile {
+                user_id: credential.user_id.clone(),
+                sample_count: face_encodings.len(),
+                face_encodings,
+                enrollment_date: Some(timestamp),
+                metadata: UserMetadata::default(),
+                extra: Default::default(),
+            },
+            exported_at: None,
+            version: None,
+            extra: Default::default(),
+        };
+        let mut file = tempfile::Builder::new().prefix(".nfc_received_").suffix(".json").tempfile_in(self.inner.backend.data_dir())?;
+        serde_json::to_writer(&mut file, &export)?;
+        self.import_user(&file.path().to_string_lossy()).await.map(|_| credential.user_id)
+    }
+
+    /// Refuse the NFC tag with `serial` on this device from now on, e.g. for
+    /// a lost badge
+    ///
+    /// The list is kept per device, in
+    /// [`StorageLayout::revoked_nfc_tags_path`]; revoke the tag on every kiosk.
+    #[cfg(feature = "nfc")]
+    pub fn revoke_nfc_tag(&self, serial: &str) -> Result<()> {
+        self.ensure_writable("revoke NFC tags")?;
+        let _storage = self.lock_storage();
+        let mut revoked = self.revoked_nfc_tags()?;
+        if !revoked.iter().any(|revoked| revoked == serial) {
+            revoked.push(serial.to_string());
+            face_storage::write_atomic(&self.inner.layout.revoked_nfc_tags_path(), &serde_json::to_vec_pretty(&revoked)?)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "nfc")]
+    fn revoked_nfc_tags(&self) -> Result<Vec<String>> {
+        match std::fs::read(self.inner.layout.revoked_nfc_tags_path()) {
+            Ok(content) => Ok(serde_json::from_slice(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Import the credential on the NFC tag on `reader`, or on the first
+    /// reader with a tag, returning the username
+    #[cfg(feature = "nfc")]
+    pub async fn read_nfc_tag(&self, reader: Option<&str>) -> Result<String> {
+        let data = NfcReader::connect(reader)?.read()?;
+        self.import_nfc_tag_data(&data).await
+    }
+
+    /// Remove orphaned files: captured frames no audit entry names and no
+    /// sample uses, generated credentials of users removed from the database
+    /// and the source directory, stale temporary files and credential copies
+    /// of interrupted verifications, and exports past the
+    /// [`FaceAuthBuilder::export_retention`]
+    ///
+    /// Writers are held off meanwhile. The report lists the removed files and
+    /// the space reclaimed.
+    pub async fn gc(&self) -> Result<GcReport> {
+        self.ensure_writable("collect garbage")?;
+        let _storage = self.lock_storage();
+        let audit = self.inner.audit.entries()?;
+        // File ages come from modification times, so they are measured on the system clock
+        let report = gc::collect_garbage(&self.inner.layout, &audit, std::time::SystemTime::now(), self.inner.export_retention)?;
+        tracing::info!(files = report.removed.len(), bytes = report.reclaimed_bytes(), "Removed orphaned files");
+        Ok(report)
+    }
+
+    /// Write an encrypted snapshot of the database, credentials, audit log,
+    /// thumbnails, settings and thumbnail key to `path`
+    ///
+    /// Requires a [`FaceAuthBuilder::backup_key`]. Writers are held off while
+    /// the files are read, so the snapshot is consistent.
+    pub async fn backup(&self, path: impl AsRef<Path>) -> Result<BackupManifest> {
+        self.write_backup(path.as_ref())
+    }
+
+    /// Restore a backup written by [`FaceAuth::backup`] into this instance's directories
+    ///
+    /// Settings aren't applied to the running instance; pass the returned
+    /// manifest's settings to [`FaceAuthBuilder::backup_settings`] when
+    /// building the replacement.
+    pub async fn restore(&self, path: impl AsRef<Path>) -> Result<BackupManifest> {
+        self.ensure_writable("restore backups")?;
+        let key = self.backup_key()?;
+        let _storage = self.lock_storage();
+        let manifest = backup::restore_backup(&self.inner.layout, key, path.as_ref())?;
+        let mut entry = self.audit_entry(AuditOperation::Restore, None, AuditOutcome::Granted);
+        entry.device_id = self.inner.device_id.clone();
+        self.inner.audit.append(&entry)?;
+        Ok(manifest)
+    }
+
+    /// Back up into `dir` now and every `interval`, keeping the newest `keep` backups
+    ///
+    /// Meant for long-running deployments; backups stop when the returned
+    /// schedule is dropped.
+    pub fn schedule_backups(&self, dir: impl Into<PathBuf>, interval: Duration, keep: usize) -> Result<BackupSchedule> {
+        self.backup_key()?;
+        let auth = self.clone();
+        BackupSchedule::start(dir, interval, keep, move |path| auth.track(auth.write_backup(path)).map(drop))
+    }
+
+    /// Report credentials another process adds to, replaces in or removes
+    /// from the source directory, see [`crate::source_watch`]
+    ///
+    /// Changes are logged and refresh the false-accept estimate before
+    /// `on_event` sees them. Watching stops when the returned watcher is
+    /// dropped.
+    #[cfg(feature = "source-watch")]
+    pub fn watch_source_dir(&self, debounce: Duration, on_event: impl Fn(&SourceEvent) + Send + 'static) -> Result<SourceWatcher> {
+        let auth = self.clone();
+        SourceWatcher::start(&self.inner.layout.source_dir, debounce, move |event| {
+            tracing::info!(?event, "Source credentials changed");
+            if let Some(far) = &auth.inner.far {
+                far.mark_stale();
+            }
+            on_event(event);
+        })
+    }
+
+    /// Uptime, camera and database state, last successful authentication and
+    /// errors not yet reported by a heartbeat
+    pub async fn health(&self) -> Result<HealthStatus> {
+        self.health_status()
+    }
+
+    fn record_camera(&self, ready: bool) {
+        self.inner.health.record_camera(ready);
+        if let Some(alerts) = &self.inner.alerts {
+            alerts.record_camera(ready);
+        }
+    }
+
+    fn health_status(&self) -> Result<HealthStatus> {
+        let database_bytes = std::fs::metadata(self.inner.layout.database_path()).map(|m| m.len()).unwrap_or(0);
+        let mut status = HealthStatus {
+            device_id: self.inner.device_id.clone(),
+            uptime_secs: 0,
+            camera_ready: None,
+            database_bytes,
+            enrolled_users: self.inner.layout.stats()?.enrolled_users,
+            last_successful_auth: None,
+            pending_errors: Vec::new(),
+        };
+        self.inner.health.fill(&mut status);
+        Ok(status)
+    }
+
+    /// Pass the [`HealthStatus`] to `publish` now and every `interval`, e.g.
+    /// [`crate::health::http_publisher`] or an MQTT client
+    ///
+    /// Errors are cleared once published. Every heartbeat also runs
+    /// [`WorkerPool::health_check`], restarting Python workers that died
+    /// since the last one. Heartbeats stop when the returned handle is dropped.
+    pub fn start_heartbeat(&self, interval: Duration, publish: impl Fn(&HealthStatus) -> Result<()> + Send + 'static) -> Heartbeat {
+        let auth = self.clone();
+        Heartbeat::start(interval, move || {
+            if let Some(workers) = &auth.inner.workers {
+                workers.health_check();
+            }
+            let status = auth.health_status()?;
+            publish(&status)?;
+            auth.inner.health.clear_errors(status.pending_errors.len());
+            Ok(())
+        })
+    }
+
+    fn write_backup(&self, path: &Path) -> Result<BackupManifest> {
+        let key = self.backup_key()?;
+        let settings = BackupSettings {
+            device_id: self.inner.device_id.clone(),
+            locale: self.inner.locale.clone(),
+            auto_promote: self.inner.auto_promote,
+            shadow_mode: self.inner.enforcement == EnforcementMode::Shadow,
+            thumbnail_key: self.inner.thumbnail_key,
+        };
+        let _storage = self.lock_storage();
+        backup::write_backup(&self.inner.layout, &settings, key, path)
+    }
+
+    fn backup_key(&self) -> Result<&[u8; crypto::KEY_LEN]> {
+        self.inner.backup_key.as_ref().ok_or_else(|| anyhow::anyhow!("No backup key configured"))
+    }
+
+    #[cfg(feature = "secure-sketch")]
+    fn vault_key(&self) -> Result<&[u8; crypto::KEY_LEN]> {
+        self.inner.vault_key.as_ref().ok_or_else(|| anyhow::anyhow!("No vault key configured"))
+    }
+
+    #[cfg(feature = "nfc")]
+    fn provisioning_key(&self) -> Result<&[u8; crypto::KEY_LEN]> {
+        self.inner.provisioning_key.as_ref().ok_or_else(|| anyhow::anyhow!("No provisioning key configured"))
+    }
+
+    /// List all registered users
+    pub async fn list_users(&self) -> Result<Vec<UserSummary>> {
+        self.inner.layout.list_users()
+    }
+
+    /// Totals for dashboards: users, enrolled users, samples, authentications,
+    /// average match distance and last activity
+    pub async fn stats(&self) -> Result<DatabaseStats> {
+        self.inner.layout.stats()
+    }
+
+    /// Stored samples of a user with their capture time, quality and model,
+    /// e.g. to find one captured with someone else in frame
+    pub async fn list_samples(&self, username: &str) -> Result<Vec<SampleSummary>> {
+        self.inner.layout.samples(username)
+    }
+
+    /// Entries of the audit log, oldest first
+    pub async fn audit_entries(&self) -> Result<Vec<AuditEntry>> {
+        self.inner.audit.entries()
+    }
+
+    /// Delete a single bad sample instead of re-enrolling the user
+    pub async fn remove_sample(&self, username: &str, sample_id: &str) -> Result<()> {
+        self.ensure_writable("remove samples")?;
+        let _storage = self.lock_storage();
+        self.inner.layout.remove_sample(username, sample_id)?;
+        tracing::info!(username, sample_id, "Removed sample");
+        Ok(())
+    }
+
+    /// Give samples with legacy `<user>_<timestamp>` ids UUIDv7s in every
+    /// stored copy, see [`StorageLayout::migrate_sample_ids`]
+    pub async fn migrate_sample_ids(&self) -> Result<Vec<SampleIdRewrite>> {
+        self.ensure_writable("migrate sample ids")?;
+        let _storage = self.lock_storage();
+        let rewrites = self.inner.layout.migrate_sample_ids(self.now(), &*self.inner.random, self.inner.compression)?;
+        tracing::info!(rewritten = rewrites.len(), "Migrated legacy sample ids");
+        Ok(rewrites)
+    }
+
+    /// Quarantine samples of `username` that lie far from the others, e.g. a
+    /// mislabelled capture or one with someone else in frame
+    ///
+    /// Quarantined samples no longer match until released with
+    /// [`FaceAuth::release_sample`] or deleted with [`FaceAuth::remove_sample`].
+    pub async fn detect_outliers(&self, username: &str) -> Result<Vec<SampleSummary>> {
+        self.ensure_writable("quarantine samples")?;
+        let _storage = self.lock_storage();
+        self.inner.layout.detect_outliers(username)
+    }
+
+    /// Return a reviewed sample to matching
+    pub async fn release_sample(&self, username: &str, sample_id: &str) -> Result<()> {
+        self.ensure_writable("release samples")?;
+        let _storage = self.lock_storage();
+        self.inner.layout.release_sample(username, sample_id)?;
+        tracing::info!(username, sample_id, "Released quarantined sample");
+        Ok(())
+    }
+
+    /// Pairs of enrolled users whose samples come within `tolerance` of each
+    /// other, closest first, with a suggested resolution
+    ///
+    /// Catches duplicate enrollments, misfiled samples and look-alikes such as
+    /// twins before they cause false accepts.
+    pub async fn audit_cross_matches(&self, tolerance: f64) -> Result<Vec<CrossMatch>> {
+        let profiles = self.inner.layout.source_profiles()?;
+        Ok(cross_match::find_cross_matches(&profiles, tolerance))
+    }
+
+    /// Replace a user's metadata (display name, external id, custom fields)
+    ///
+    /// The metadata is stored with the user's profile, so it travels with
+    /// promoted and exported credentials.
+    pub async fn set_user_metadata(&self, username: &str, metadata: UserMetadata) -> Result<()> {
+        self.ensure_writable("change user metadata")?;
+        let _storage = self.lock_storage();
+        self.inner.layout.update_user(username, |profile| profile.metadata = metadata.clone())
+    }
+
+    /// Wait for writes in progress, flush the audit log to disk and stop the
+    /// Python workers, e.g. when a service is asked to stop
+    pub async fn shutdown(&self) -> Result<()> {
+        let _storage = self.lock_storage();
+        if let Some(workers) = &self.inner.workers {
+            workers.shutdown();
+        }
+        self.inner.audit.sync()?;
+        tracing::info!("Shut down");
+        Ok(())
+    }
+
+    /// Why `result` was granted or denied: per-sample distances, the
+    /// threshold, camera fusion, and what would have reversed it
+    pub fn explain(&self, result: &FaceAuthResult) -> Explanation {
+        Explanation::new(result, self.inner.fusion)
+    }
+
+    /// Check if the backend (by default the Python executable) is working
+    pub async fn check_system(&self) -> Result<()> {
+        self.inner.backend.check()
+    }
+
+    /// Remember a failure for the next heartbeat
+    fn track<T>(&self, result: Result<T>) -> Result<T> {
+        if let Err(e) = &result {
+            self.inner.health.record_error(e);
+        }
+        result
+    }
+
+    fn notify(&self, event: FeedbackEvent) {
+        if let Some(feedback) = &self.inner.feedback {
+            feedback.0.notify(&event);
+        }
+    }
+
+    fn ensure_writable(&self, operation: &'static str) -> Result<()> {
+        match self.inner.storage_mode {
+            StorageMode::ReadWrite => Ok(()),
+            StorageMode::ReadOnly => Err(FaceAuthError::ReadOnly { operation }.into()),
+        }
+    }
+
+    /// Serialize writers of the database, stats and audit log across clones
+    ///
+    /// Every write is atomic on disk, so a panic while the lock was held
+    /// leaves nothing to repair and poisoning is ignored.
+    fn lock_storage(&self) -> MutexGuard<'_, ()> {
+        self.inner.storage_lock.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Fail unless every sample `profile` gained after resuming `progress` is
+/// within the default tolerance of a sample stored before the interruption,
+/// so a resumed registration can't mix two people's faces
+fn check_resumed_samples(progress: &EnrollmentProgress, profile: &UserProfile) -> Result<()> {
+    let kept: Vec<&FaceSample> = progress.face_encodings.iter().filter(|sample| !sample.is_remote()).collect();
+    let resumed: HashSet<&str> = progress.face_encodings.iter().map(|sample| sample.sample_id.as_str()).collect();
+    for sample in profile.face_encodings.iter().filter(|sample| !resumed.contains(sample.sample_id.as_str()) && !sample.is_remote()) {
+        let closest = kept.iter().map(|kept| matching::face_distance(&kept.encoding, &sample.encoding)).min_by(f64::total_cmp);
+        if closest.is_some_and(|distance| distance > matching::DEFAULT_TOLERANCE) {
+            return Err(anyhow::anyhow!(
+                "The samples captured after resuming don't match the {} stored before the interruption; register '{}' again",
+                kept.len(),
+                profile.user_id,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Raw result of an attempt denied before any face was matched
+fn unmatched(tolerance: f64, denial: DenialReason) -> StandaloneAuthResult {
+    StandaloneAuthResult {
+        success: true,
+        is_match: Some(false),
+        confidence: None,
+        distance: None,
+        threshold: Some(tolerance),
+        matched_user: None,
+        closest_user: None,
+        runner_up: None,
+        sample_distances: Vec::new(),
+        brightness: None,
+        image_path: None,
+        processing_time_ms: None,
+        timings: TimingBreakdown::default(),
+        liveness: None,
+        denial: Some(denial),
+        raw_output: String::new(),
+    }
+}
+
+/// Decide a backend result with the tolerance of `profile` instead of the
+/// one the backend matched with
+fn apply_profile(raw: &mut StandaloneAuthResult, profile: &ThresholdProfile) {
+    raw.threshold = Some(profile.tolerance);
+    if let Some(runner_up) = &mut raw.runner_up {
+        runner_up.is_match = runner_up.distance <= profile.tolerance;
+    }
+    // Frames rejected before matching stay rejected
+    if raw.denial.is_some() {
+        return;
+    }
+    if let (Some(distance), Some(closest)) = (raw.distance, &raw.closest_user) {
+        let is_match = distance <= profile.tolerance;
+        raw.is_match = Some(is_match);
+        raw.matched_user = is_match.then(|| closest.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_face_auth_is_shareable() {
+        fn assert_shareable<T: Clone + Send + Sync + 'static>() {}
+        assert_shareable::<FaceAuth>();
+    }
+
+    #[tokio::test]
+    async fn test_face_auth_creation() {
+        let result = FaceAuth::new();
+        assert!(result.is_ok());
+    }
+}
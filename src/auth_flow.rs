@@ -0,0 +1,271 @@
+//! Authentication as a sequence of states a UI can render
+//!
+//! An [`AuthFlow`] wraps [`FaceAuth::authenticate`] with
+//! what every front end otherwise re-implements: capturing again when the
+//! frame had no usable face, an optional liveness challenge before granting,
+//! and a cooldown after a denial. Each [`AuthState`] is reported as it is
+//! entered, so the CLI, a daemon and a GUI show the same phases:
+//!
+//! ```no_run
+//! use face_auth::{AuthFlowConfig, AuthState, FaceAuth, MessageCatalog};
+//!
+//! # async fn door(auth: FaceAuth) -> anyhow::Result<()> {
+//! let messages = MessageCatalog::english();
+//! let mut flow = auth.auth_flow(AuthFlowConfig::default().with_liveness_challenge(true));
+//! flow.on_state(move |state| println!("{}", state.message(&messages)));
+//! if let AuthState::LivenessChallenge { .. } = flow.attempt().await? {
+//!     let blinked = true; // from the kiosk's own challenge
+//!     flow.complete_challenge(blinked)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::{Result, bail};
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::assurance::AssuranceLevel;
+use crate::context::AuthContext;
+use crate::denial::DenialReason;
+use crate::feedback::FeedbackEvent;
+use crate::messages::{CaptureTip, MessageCatalog};
+use crate::v1::AuthRequest;
+use crate::{FaceAuth, FaceAuthResult};
+
+/// Retries, liveness and cooldown of an [`AuthFlow`]
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct AuthFlowConfig {
+    /// Face matching tolerance (0.0-1.0, lower = stricter)
+    pub tolerance: f64,
+    /// Extra captures after one without a face or of too low quality
+    pub max_quality_retries: u32,
+    /// Ask for an active liveness challenge before granting a match that
+    /// didn't prove liveness itself; until it is taken, the match is neither
+    /// audited nor acted on
+    pub liveness_challenge: bool,
+    /// Attempts are refused for this long after a denial
+    pub cooldown: Duration,
+}
+
+impl Default for AuthFlowConfig {
+    fn default() -> Self {
+        Self { tolerance: 0.6, max_quality_retries: 2, liveness_challenge: false, cooldown: Duration::from_secs(5) }
+    }
+}
+
+impl AuthFlowConfig {
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn with_max_quality_retries(mut self, retries: u32) -> Self {
+        self.max_quality_retries = retries;
+        self
+    }
+
+    pub fn with_liveness_challenge(mut self, challenge: bool) -> Self {
+        self.liveness_challenge = challenge;
+        self
+    }
+
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+}
+
+/// Phase of an [`AuthFlow`]
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum AuthState {
+    /// Waiting for the next attempt
+    Ready,
+    /// The camera is capturing; `retry` is 0 for the first capture of an attempt
+    Capturing { retry: u32 },
+    /// The last capture was unusable and another follows
+    Retrying { retry: u32, reason: DenialReason },
+    /// `user_id` matched; waiting for [`AuthFlow::complete_challenge`]
+    LivenessChallenge { user_id: String },
+    Granted { user_id: String, assurance: AssuranceLevel },
+    Denied { reason: Option<DenialReason> },
+    /// Attempts are refused until `until`
+    CoolingDown { until: DateTime<Utc> },
+}
+
+impl AuthState {
+    /// Whether a new attempt can start from this state
+    pub fn accepts_attempt(&self) -> bool {
+        self.accepts_attempt_at(Utc::now())
+    }
+
+    /// [`AuthState::accepts_attempt`] at `now`
+    pub fn accepts_attempt_at(&self, now: DateTime<Utc>) -> bool {
+        match self {
+            AuthState::Ready | AuthState::Granted { .. } | AuthState::Denied { .. } => true,
+            AuthState::CoolingDown { until } => now >= *until,
+            _ => false,
+        }
+    }
+
+    /// What to show or say in this state, in the catalog's locale
+    pub fn message(&self, messages: &MessageCatalog) -> String {
+        match self {
+            AuthState::Ready => messages.tip(CaptureTip::LookAtCamera),
+            AuthState::Capturing { .. } => messages.tip(CaptureTip::HoldStill),
+            AuthState::Retrying { reason, .. } => messages.tip(reason.tip().unwrap_or(CaptureTip::TryAgain)),
+            AuthState::LivenessChallenge { .. } => messages.get("flow.liveness_challenge", &[]),
+            AuthState::Granted { user_id, .. } => {
+                FeedbackEvent::AccessGranted { display_name: user_id.clone() }.message(messages)
+            }
+            AuthState::Denied { reason } => FeedbackEvent::AccessDenied { reason: reason.clone() }.message(messages),
+            AuthState::CoolingDown { until } => {
+                let seconds = (*until - Utc::now()).num_seconds().max(0) + 1;
+                messages.get("flow.cooling_down", &[("seconds", seconds.to_string())])
+            }
+        }
+    }
+}
+
+type Listener = Arc<dyn Fn(&AuthState) + Send + Sync>;
+
+/// Drives camera authentications through [`AuthState`]s, see the
+/// [module docs](self)
+///
+/// One flow serves one camera; attempts run one after the other.
+pub struct AuthFlow {
+    auth: FaceAuth,
+    config: AuthFlowConfig,
+    context: AuthContext,
+    state: AuthState,
+    result: Option<FaceAuthResult>,
+    listener: Option<Listener>,
+}
+
+impl fmt::Debug for AuthFlow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthFlow").field("config", &self.config).field("state", &self.state).finish_non_exhaustive()
+    }
+}
+
+impl AuthFlow {
+    pub(crate) fn new(auth: FaceAuth, config: AuthFlowConfig) -> Self {
+        Self { auth, config, context: AuthContext::new(), state: AuthState::Ready, result: None, listener: None }
+    }
+
+    /// Context attached to every attempt, see [`AuthContext`]
+    pub fn with_context(mut self, context: AuthContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Receive every state as it is entered, on the thread driving the flow
+    pub fn on_state(&mut self, listener: impl Fn(&AuthState) + Send + Sync + 'static) {
+        self.listener = Some(Arc::new(listener));
+    }
+
+    pub fn state(&self) -> &AuthState {
+        &self.state
+    }
+
+    /// Decision of the last capture that wasn't retried; not authenticated
+    /// while a liveness challenge is pending
+    pub fn result(&self) -> Option<&FaceAuthResult> {
+        self.result.as_ref()
+    }
+
+    /// Capture and match until a decision, retrying unusable captures
+    ///
+    /// Ends in [`AuthState::Granted`], [`AuthState::LivenessChallenge`], or
+    /// after a denial in [`AuthState::CoolingDown`] ([`AuthState::Denied`]
+    /// without a cooldown). While cooling down, nothing is captured and the
+    /// state is returned unchanged. A match waiting for its challenge has no
+    /// side effects yet: nothing is actuated, audited, published or signed.
+    pub async fn attempt(&mut self) -> Result<&AuthState> {
+        if let AuthState::CoolingDown { .. } = self.state {
+            if !self.state.accepts_attempt_at(self.auth.now()) {
+                return Ok(&self.state);
+            }
+        } else if !self.state.accepts_attempt_at(self.auth.now()) {
+            bail!("Authentication flow is busy: {:?}", self.state);
+        }
+
+        let mut request = AuthRequest::camera(self.config.tolerance).with_context(self.context.clone());
+        request.hold_for_challenge = self.config.liveness_challenge;
+        let mut retry = 0;
+        let result = loop {
+            self.enter(AuthState::Capturing { retry });
+            let result = self.auth.authenticate(&request).await?;
+            match &result.denial {
+                Some(reason @ (DenialReason::NoFace | DenialReason::QualityTooLow { .. })) if retry < self.config.max_quality_retries => {
+                    retry += 1;
+                    self.enter(AuthState::Retrying { retry, reason: reason.clone() });
+                }
+                _ => break result,
+            }
+        };
+
+        let next = match (&result.user_id, result.assurance) {
+            (Some(user_id), _) if result.held.is_some() => AuthState::LivenessChallenge { user_id: user_id.clone() },
+            (Some(user_id), Some(assurance)) if result.is_authenticated => AuthState::Granted { user_id: user_id.clone(), assurance },
+            _ => AuthState::Denied { reason: result.denial.clone() },
+        };
+        self.result = Some(result);
+        self.decide(next)
+    }
+
+    /// Finish the liveness challenge the user was just given: the match is
+    /// granted at [`AssuranceLevel::L3`] if they passed and denied otherwise
+    ///
+    /// Only now is the decision audited, signed and acted on.
+    pub fn complete_challenge(&mut self, passed: bool) -> Result<&AuthState> {
+        let AuthState::LivenessChallenge { user_id } = &self.state else {
+            bail!("No liveness challenge is pending");
+        };
+        let user_id = user_id.clone();
+        let held = self.result.clone().expect("a challenge follows a result");
+        let result = self.auth.resolve_challenge(held, passed)?;
+        let next = match result.assurance {
+            Some(assurance) if result.is_authenticated => AuthState::Granted { user_id, assurance },
+            _ => AuthState::Denied { reason: result.denial.clone() },
+        };
+        self.result = Some(result);
+        self.decide(next)
+    }
+
+    /// Return to [`AuthState::Ready`], dropping a pending challenge but not a
+    /// cooldown; the dropped challenge's match is recorded as failed
+    pub fn reset(&mut self) {
+        if let (AuthState::LivenessChallenge { .. }, Some(held)) = (&self.state, self.result.take()) {
+            match self.auth.resolve_challenge(held, false) {
+                Ok(result) => self.result = Some(result),
+                Err(e) => tracing::warn!("Failed to record the abandoned liveness challenge: {:#}", e),
+            }
+        }
+        if !matches!(self.state, AuthState::CoolingDown { .. }) || self.state.accepts_attempt_at(self.auth.now()) {
+            self.enter(AuthState::Ready);
+        }
+    }
+
+    fn decide(&mut self, state: AuthState) -> Result<&AuthState> {
+        // Nobody to match, or a camera in use, isn't the user's fault
+        let denied = matches!(&state, AuthState::Denied { reason } if !matches!(reason, Some(DenialReason::NoUsersEnrolled | DenialReason::DeviceBusy { .. })));
+        self.enter(state);
+        if denied && !self.config.cooldown.is_zero() {
+            let until = self.auth.now() + chrono::Duration::from_std(self.config.cooldown)?;
+            self.enter(AuthState::CoolingDown { until });
+        }
+        Ok(&self.state)
+    }
+
+    fn enter(&mut self, state: AuthState) {
+        self.state = state;
+        if let Some(listener) = &self.listener {
+            listener(&self.state);
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use anyhow::{Result, anyhow, bail};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::auth::{FrameBlur, PrivacyMode};
+use crate::camera::CameraSource;
+use crate::face_storage;
+use crate::kyc::DocumentFace;
+use crate::profiles::Preprocessing;
+use crate::redact::{Redaction, RedactionPolicy};
+use crate::registration::RegistrationOutcome;
+use crate::scene::{SceneGate, SceneStats};
+use crate::standalone_python::{StandaloneAuthResult, StandalonePythonFaceAuth, WarmUpOutcome};
+use crate::tracking::{TrackEvent, TrackingConfig};
+
+/// Capture, encoding and matching behind [`crate::FaceAuth`]
+///
+/// Everything else (promotion, statistics, auditing, enforcement) is done by
+/// `FaceAuth` on top of the backend. The Python script is the production
+/// implementation; [`crate::FakeBackend`] serves canned embeddings for tests.
+pub trait FaceBackend: fmt::Debug + Send + Sync {
+    /// Directory the backend keeps its database, captures and exports in
+    fn data_dir(&self) -> &Path;
+
+    /// Capture `samples` faces and write the user's credential to `generated_dir`
+    fn register_user(&self, username: &str, samples: u32, generated_dir: &str) -> Result<RegistrationOutcome>;
+
+    /// Capture a face and match it against the credentials in `source_dir`
+    fn authenticate_user(&self, tolerance: f64, source_dir: &str) -> Result<StandaloneAuthResult>;
+
+    /// Like [`FaceBackend::authenticate_user`], capturing from camera `camera`
+    ///
+    /// Backends with a single camera only accept camera 0.
+    fn authenticate_camera(&self, camera: u32, tolerance: f64, source_dir: &str) -> Result<StandaloneAuthResult> {
+        match camera {
+            0 => self.authenticate_user(tolerance, source_dir),
+            _ => Err(anyhow!("This backend has no camera {}", camera)),
+        }
+    }
+
+    /// Match a previously captured frame against the credentials in `source_dir`
+    fn authenticate_image(&self, tolerance: f64, source_dir: &str, image: &Path) -> Result<StandaloneAuthResult>;
+
+    /// Authenticate continuously from the camera, matching only frames that
+    /// pass `gate`, until `on_result` returns `false`
+    ///
+    /// Returns the frame counts as of the last attempt.
+    fn watch(
+        &self,
+        _tolerance: f64,
+        _source_dir: &str,
+        _gate: &SceneGate,
+        _on_result: &mut dyn FnMut(StandaloneAuthResult, &SceneStats) -> Result<bool>,
+    ) -> Result<SceneStats> {
+        Err(anyhow!("This backend cannot watch the camera continuously"))
+    }
+
+    /// Like [`FaceBackend::watch`], but following each face across frames
+    /// and identifying it once when it enters the view
+    fn track_faces(
+        &self,
+        _tolerance: f64,
+        _source_dir: &str,
+        _gate: &SceneGate,
+        _tracking: &TrackingConfig,
+        _on_event: &mut dyn FnMut(TrackEvent<StandaloneAuthResult>, &SceneStats) -> Result<bool>,
+    ) -> Result<SceneStats> {
+        Err(anyhow!("This backend cannot track faces"))
+    }
+
+    /// Encoding of the first face in each image, `None` where no face was found
+    ///
+    /// Used to enroll from submitted photos. Backends that keep templates
+    /// remotely can't produce local encodings and return an error.
+    fn encode_images(&self, _images: &[PathBuf]) -> Result<Vec<Option<Vec<f64>>>> {
+        Err(anyhow!("This backend cannot encode photos"))
+    }
+
+    /// Model whose templates the backend matches against, as stored in
+    /// [`FaceSample::template_version`](crate::FaceSample::template_version)
+    fn template_version(&self) -> String {
+        face_storage::DLIB_TEMPLATE_VERSION.to_string()
+    }
+
+    /// Encoding of the portrait on a photo of an identity document
+    fn encode_document(&self, _document: &Path) -> Result<DocumentFace> {
+        Err(anyhow!("This backend cannot read identity documents"))
+    }
+
+    /// Blur or pixelate faces in an encoded image, see [`crate::redact`]
+    fn redact_faces(&self, _image: &[u8], _policy: &RedactionPolicy) -> Result<Redaction> {
+        Err(anyhow!("This backend cannot redact faces"))
+    }
+
+    /// Write a crop of every face in `image` to `out_dir`, largest first,
+    /// for models that look at a face rather than a whole frame
+    fn crop_faces(&self, _image: &Path, _out_dir: &Path) -> Result<Vec<PathBuf>> {
+        Err(anyhow!("This backend cannot crop faces"))
+    }
+
+    /// Match an encoded image held in memory without writing it to disk,
+    /// for uploads in [`PrivacyMode::Strict`]
+    fn authenticate_image_bytes(&self, _tolerance: f64, _source_dir: &str, _image: &[u8]) -> Result<StandaloneAuthResult> {
+        Err(anyhow!("This backend only matches images stored on disk"))
+    }
+
+    /// Adjust frames this way before detection from now on
+    ///
+    /// Backends that can't adjust frames ignore it.
+    fn set_preprocessing(&self, _preprocessing: &Preprocessing) {}
+
+    /// Store or stop storing captured frames and thumbnails; fails for
+    /// [`PrivacyMode::Strict`] when the backend can't keep them in memory
+    fn set_privacy_mode(&self, mode: PrivacyMode) -> Result<()> {
+        match mode {
+            PrivacyMode::Standard => Ok(()),
+            PrivacyMode::Strict => bail!("This backend can't keep images off disk"),
+        }
+    }
+
+    /// Blur faces in the frames the backend keeps on disk from now on
+    fn set_frame_blur(&self, blur: FrameBlur) -> Result<()> {
+        match blur {
+            FrameBlur::Off => Ok(()),
+            _ => bail!("This backend can't blur faces in stored frames"),
+        }
+    }
+
+    /// Backend sharing this one's storage but capturing from `source`,
+    /// including in [`FaceBackend::watch`] and [`FaceBackend::track_faces`],
+    /// which end with a video file instead of failing
+    ///
+    /// This backend keeps capturing from its camera. Captures from a video
+    /// file continue where the previous capture stopped.
+    fn with_camera_source(&self, _source: &CameraSource) -> Result<Arc<dyn FaceBackend>> {
+        bail!("This backend can only capture from its own camera")
+    }
+
+    /// Load models ahead of the first request, optionally opening the camera
+    fn warm_up(&self, camera: bool) -> Result<WarmUpOutcome>;
+
+    /// Write a user's database entry to `filename` (generated if empty)
+    fn export_user(&self, username: &str, filename: &str) -> Result<bool>;
+
+    /// Add the user in an export file to the database
+    fn import_user(&self, filename: &str) -> Result<bool>;
+
+    /// Check that the backend can run
+    fn check(&self) -> Result<()>;
+}
+
+impl FaceBackend for StandalonePythonFaceAuth {
+    fn data_dir(&self) -> &Path {
+        StandalonePythonFaceAuth::data_dir(self)
+    }
+
+    fn register_user(&self, username: &str, samples: u32, generated_dir: &str) -> Result<RegistrationOutcome> {
+        StandalonePythonFaceAuth::register_user(self, username, samples, generated_dir)
+    }
+
+    fn authenticate_user(&self, tolerance: f64, source_dir: &str) -> Result<StandaloneAuthResult> {
+        StandalonePythonFaceAuth::authenticate_user(self, tolerance, source_dir)
+    }
+
+    fn authenticate_camera(&self, camera: u32, tolerance: f64, source_dir: &str) -> Result<StandaloneAuthResult> {
+        self.with_camera(camera).authenticate_user(tolerance, source_dir)
+    }
+
+    fn authenticate_image(&self, tolerance: f64, source_dir: &str, image: &Path) -> Result<StandaloneAuthResult> {
+        StandalonePythonFaceAuth::authenticate_image(self, tolerance, source_dir, image)
+    }
+
+    fn watch(
+        &self,
+        tolerance: f64,
+        source_dir: &str,
+        gate: &SceneGate,
+        on_result: &mut dyn FnMut(StandaloneAuthResult, &SceneStats) -> Result<bool>,
+    ) -> Result<SceneStats> {
+        StandalonePythonFaceAuth::watch(self, tolerance, source_dir, gate, on_result)
+    }
+
+    fn track_faces(
+        &self,
+        tolerance: f64,
+        source_dir: &str,
+        gate: &SceneGate,
+        tracking: &TrackingConfig,
+        on_event: &mut dyn FnMut(TrackEvent<StandaloneAuthResult>, &SceneStats) -> Result<bool>,
+    ) -> Result<SceneStats> {
+        StandalonePythonFaceAuth::track_faces(self, tolerance, source_dir, gate, tracking, on_event)
+    }
+
+    fn encode_images(&self, images: &[PathBuf]) -> Result<Vec<Option<Vec<f64>>>> {
+        StandalonePythonFaceAuth::encode_images(self, images)
+    }
+
+    fn encode_document(&self, document: &Path) -> Result<DocumentFace> {
+        StandalonePythonFaceAuth::encode_document(self, document)
+    }
+
+    fn redact_faces(&self, image: &[u8], policy: &RedactionPolicy) -> Result<Redaction> {
+        StandalonePythonFaceAuth::redact_faces(self, image, policy)
+    }
+
+    fn crop_faces(&self, image: &Path, out_dir: &Path) -> Result<Vec<PathBuf>> {
+        StandalonePythonFaceAuth::crop_faces(self, image, out_dir)
+    }
+
+    fn authenticate_image_bytes(&self, tolerance: f64, source_dir: &str, image: &[u8]) -> Result<StandaloneAuthResult> {
+        StandalonePythonFaceAuth::authenticate_image_bytes(self, tolerance, source_dir, image)
+    }
+
+    fn set_preprocessing(&self, preprocessing: &Preprocessing) {
+        StandalonePythonFaceAuth::set_preprocessing(self, preprocessing.clone())
+    }
+
+    fn with_camera_source(&self, source: &CameraSource) -> Result<Arc<dyn FaceBackend>> {
+        Ok(Arc::new(StandalonePythonFaceAuth::with_camera_source(self, source.clone())))
+    }
+
+    fn set_privacy_mode(&self, mode: PrivacyMode) -> Result<()> {
+        StandalonePythonFaceAuth::set_strict_privacy(self, mode == PrivacyMode::Strict);
+        Ok(())
+    }
+
+    fn set_frame_blur(&self, blur: FrameBlur) -> Result<()> {
+        StandalonePythonFaceAuth::set_frame_blur(self, blur);
+        Ok(())
+    }
+
+    fn warm_up(&self, camera: bool) -> Result<WarmUpOutcome> {
+        StandalonePythonFaceAuth::warm_up(self, camera)
+    }
+
+    fn export_user(&self, username: &str, filename: &str) -> Result<bool> {
+        StandalonePythonFaceAuth::export_user(self, username, filename)
+    }
+
+    fn import_user(&self, filename: &str) -> Result<bool> {
+        StandalonePythonFaceAuth::import_user(self, filename)
+    }
+
+    fn check(&self) -> Result<()> {
+        self.check_executable()
+    }
+}
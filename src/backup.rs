@@ -0,0 +1,273 @@
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::crypto::{self, KEY_LEN};
+use crate::face_storage::{write_atomic, StorageLayout};
+
+const MAGIC: &[u8; 8] = b"FACEBKUP";
+const VERSION: u32 = 1;
+
+/// Extension of backup files written by [`BackupSchedule`]
+pub const BACKUP_EXTENSION: &str = "fab";
+
+// Layout: magic[8] version:u32 (little-endian), then sealed with the backup key:
+//   manifest_len:u32 manifest JSON, followed by the files' contents in manifest order
+
+/// Directory a backed-up file belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupRoot {
+    Data,
+    Generated,
+    Source,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupEntry {
+    pub root: BackupRoot,
+    /// Relative to the root, with `/` separators
+    pub path: String,
+    pub len: u64,
+}
+
+/// Settings of the backed-up instance, so a replacement device can be set up
+/// the same way
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BackupSettings {
+    pub device_id: Option<String>,
+    pub locale: String,
+    pub auto_promote: bool,
+    pub shadow_mode: bool,
+    /// Needed to read thumbnails encrypted at rest
+    pub thumbnail_key: Option<[u8; KEY_LEN]>,
+}
+
+/// Contents list of a backup
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub created_at: DateTime<Utc>,
+    pub settings: BackupSettings,
+    pub files: Vec<BackupEntry>,
+}
+
+/// Files of `layout` that make up a backup: the database, audit log,
+/// thumbnails, exports, recordings and every credential file
+pub fn backup_sources(layout: &StorageLayout) -> Vec<(BackupRoot, PathBuf)> {
+    vec![
+        (BackupRoot::Data, layout.database_path()),
+        (BackupRoot::Data, layout.audit_log_path()),
+        (BackupRoot::Data, layout.thumbnails_dir()),
+        (BackupRoot::Data, layout.exports_dir()),
+        (BackupRoot::Data, layout.recordings_dir()),
+        (BackupRoot::Generated, layout.generated_dir.clone()),
+        (BackupRoot::Source, layout.source_dir.clone()),
+    ]
+}
+
+fn root_dir(layout: &StorageLayout, root: BackupRoot) -> &Path {
+    match root {
+        BackupRoot::Data => &layout.data_dir,
+        BackupRoot::Generated => &layout.generated_dir,
+        BackupRoot::Source => &layout.source_dir,
+    }
+}
+
+/// Encrypt the files of `layout` and `settings` into a backup at `path`
+///
+/// Callers must keep writers out while this runs for the snapshot to be consistent.
+pub fn write_backup(layout: &StorageLayout, settings: &BackupSettings, key: &[u8; KEY_LEN], path: &Path) -> Result<BackupManifest> {
+    let mut files = Vec::new();
+    let mut contents = Vec::new();
+    for (root, source) in backup_sources(layout) {
+        let mut found = Vec::new();
+        collect_files(&source, &mut found)?;
+        for file in found {
+            let relative = file.strip_prefix(root_dir(layout, root)).unwrap_or(&file);
+            let data = fs::read(&file).with_context(|| format!("Failed to read {}", file.display()))?;
+            files.push(BackupEntry {
+                root,
+                path: relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/"),
+                len: data.len() as u64,
+            });
+            contents.push(data);
+        }
+    }
+    let manifest = BackupManifest { created_at: Utc::now(), settings: settings.clone(), files };
+
+    let manifest_json = serde_json::to_vec(&manifest)?;
+    let mut payload = (manifest_json.len() as u32).to_le_bytes().to_vec();
+    payload.extend_from_slice(&manifest_json);
+    for data in contents {
+        payload.extend_from_slice(&data);
+    }
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&crypto::seal(key, &payload)?);
+    write_atomic(path, &bytes)?;
+    Ok(manifest)
+}
+
+/// Decrypt the backup at `path` and write its files into `layout`
+///
+/// Files that exist in `layout` but not in the backup are left alone.
+pub fn restore_backup(layout: &StorageLayout, key: &[u8; KEY_LEN], path: &Path) -> Result<BackupManifest> {
+    let bytes = fs::read(path).with_context(|| format!("Failed to read backup {}", path.display()))?;
+    if bytes.len() < 12 || &bytes[..8] != MAGIC {
+        bail!("{} is not a face_auth backup", path.display());
+    }
+    let version = u32::from_le_bytes(bytes[8..12].try_into()?);
+    if version != VERSION {
+        bail!("Unsupported backup version {}", version);
+    }
+    let payload = crypto::open(key, &bytes[12..])?;
+
+    let manifest_len = payload.get(..4).map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+        .ok_or_else(|| anyhow!("Backup is truncated"))?;
+    let manifest_json = payload.get(4..4 + manifest_len).ok_or_else(|| anyhow!("Backup is truncated"))?;
+    let manifest: BackupManifest = serde_json::from_slice(manifest_json)?;
+
+    // Check every entry before touching the filesystem
+    let mut offset = 4 + manifest_len;
+    let mut writes = Vec::with_capacity(manifest.files.len());
+    for entry in &manifest.files {
+        let relative = Path::new(&entry.path);
+        if !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+            bail!("Backup contains an invalid path: {}", entry.path);
+        }
+        let end = offset + entry.len as usize;
+        let data = payload.get(offset..end).ok_or_else(|| anyhow!("Backup is truncated"))?;
+        writes.push((root_dir(layout, entry.root).join(relative), data));
+        offset = end;
+    }
+    for (target, data) in writes {
+        write_atomic(&target, data)?;
+    }
+    Ok(manifest)
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    if path.is_file() {
+        files.push(path.to_path_buf());
+    } else if path.is_dir() {
+        let mut entries = fs::read_dir(path)?.map(|e| e.map(|e| e.path())).collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+        for entry in entries {
+            // Skip leftovers of interrupted atomic writes
+            if !entry.file_name().is_some_and(|n| n.to_string_lossy().ends_with(".tmp")) {
+                collect_files(&entry, files)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Delete the oldest backups in `dir`, keeping the newest `keep`
+///
+/// Returns the removed files.
+pub fn rotate_backups(dir: &Path, keep: usize) -> Result<Vec<PathBuf>> {
+    let mut backups: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == BACKUP_EXTENSION))
+        .collect();
+    // Names embed the creation time, so they sort chronologically
+    backups.sort();
+    let excess = backups.len().saturating_sub(keep);
+    let removed: Vec<PathBuf> = backups.drain(..excess).collect();
+    for path in &removed {
+        fs::remove_file(path)?;
+    }
+    Ok(removed)
+}
+
+/// Background thread writing a backup every `interval` and rotating old ones
+///
+/// Stops when dropped.
+pub struct BackupSchedule {
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl BackupSchedule {
+    /// Run `backup` with a fresh file name in `dir` now and then every `interval`
+    pub fn start(
+        dir: impl Into<PathBuf>,
+        interval: Duration,
+        keep: usize,
+        backup: impl Fn(&Path) -> Result<()> + Send + 'static,
+    ) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread = thread::spawn({
+            let stop = stop.clone();
+            move || loop {
+                let path = dir.join(format!("face_auth_backup_{}.{}", Utc::now().format("%Y%m%d_%H%M%S%.3f"), BACKUP_EXTENSION));
+                match backup(&path).and_then(|_| rotate_backups(&dir, keep)) {
+                    Ok(_) => tracing::info!("Wrote backup {}", path.display()),
+                    Err(e) => tracing::error!("Scheduled backup failed: {:#}", e),
+                }
+                let (stopped, wake) = &*stop;
+                let stopped = stopped.lock().unwrap_or_else(PoisonError::into_inner);
+                let (stopped, _) = wake.wait_timeout_while(stopped, interval, |stopped| !*stopped)
+                    .unwrap_or_else(PoisonError::into_inner);
+                if *stopped {
+                    break;
+                }
+            }
+        });
+        Ok(Self { stop, thread: Some(thread) })
+    }
+}
+
+impl Drop for BackupSchedule {
+    fn drop(&mut self) {
+        let (stopped, wake) = &*self.stop;
+        *stopped.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        wake.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_restores_files_and_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let layout = |name: &str| {
+            let root = dir.path().join(name);
+            StorageLayout { data_dir: root.join("data"), generated_dir: root.join("generated"), source_dir: root.join("source") }
+        };
+        let (device, replacement) = (layout("device"), layout("replacement"));
+        write_atomic(&device.database_path(), b"{\"users\":{}}").unwrap();
+        write_atomic(&device.source_dir.join("ann_credentials.json"), b"ann").unwrap();
+        write_atomic(&device.thumbnails_dir().join("ann.jpg.enc"), b"thumbnail").unwrap();
+        let settings = BackupSettings { device_id: Some("door-1".into()), locale: "de".into(), thumbnail_key: Some([3; KEY_LEN]), ..Default::default() };
+        let key = [9; KEY_LEN];
+
+        let backup = dir.path().join("backups").join("first.fab");
+        let written = write_backup(&device, &settings, &key, &backup).unwrap();
+        assert_eq!(written.files.len(), 3);
+        assert!(restore_backup(&replacement, &[1; KEY_LEN], &backup).is_err());
+
+        let restored = restore_backup(&replacement, &key, &backup).unwrap();
+        assert_eq!(restored.settings, settings);
+        assert_eq!(fs::read(replacement.source_dir.join("ann_credentials.json")).unwrap(), b"ann");
+        assert_eq!(fs::read(replacement.thumbnails_dir().join("ann.jpg.enc")).unwrap(), b"thumbnail");
+
+        for name in ["second.fab", "third.fab", "notes.txt"] {
+            fs::write(dir.path().join("backups").join(name), b"").unwrap();
+        }
+        let removed = rotate_backups(&dir.path().join("backups"), 2).unwrap();
+        assert_eq!(removed, vec![dir.path().join("backups").join("first.fab")]);
+    }
+}
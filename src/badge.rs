@@ -0,0 +1,201 @@
+use anyhow::{Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::face_storage::UserMetadata;
+
+/// Card data formats understood by access-control panels
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WiegandFormat {
+    /// 26-bit H10301: 8-bit facility code, 16-bit card number
+    #[default]
+    H10301,
+    /// 34-bit H10306: 16-bit facility code, 16-bit card number
+    H10306,
+}
+
+impl WiegandFormat {
+    fn field_bits(self) -> (u32, u32) {
+        match self {
+            WiegandFormat::H10301 => (8, 16),
+            WiegandFormat::H10306 => (16, 16),
+        }
+    }
+}
+
+/// Badge a user is presented to the panel as, from their `badge_id` metadata:
+/// `"<card>"` or `"<facility>:<card>"`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Badge {
+    pub facility: u32,
+    pub card: u32,
+}
+
+impl Badge {
+    /// Parse a badge id; `default_facility` applies when it has no facility code
+    pub fn parse(badge_id: &str, default_facility: Option<u32>) -> Result<Self> {
+        let number = |s: &str| s.trim().parse::<u32>().map_err(|_| anyhow!("Invalid badge id '{}'", badge_id));
+        match badge_id.split_once(':') {
+            Some((facility, card)) => Ok(Self { facility: number(facility)?, card: number(card)? }),
+            None => Ok(Self { facility: default_facility.unwrap_or(0), card: number(badge_id)? }),
+        }
+    }
+
+    pub fn from_metadata(metadata: Option<&UserMetadata>, default_facility: Option<u32>) -> Result<Self> {
+        let badge_id = metadata.and_then(|m| m.badge_id.as_deref()).ok_or_else(|| anyhow!("User has no badge_id"))?;
+        Self::parse(badge_id, default_facility)
+    }
+
+    /// Wiegand frame, first bit first: even parity over the first half of the
+    /// data, facility code, card number, odd parity over the second half
+    pub fn wiegand_bits(&self, format: WiegandFormat) -> Result<Vec<bool>> {
+        let (facility_bits, card_bits) = format.field_bits();
+        if self.facility >= 1 << facility_bits || self.card >= 1 << card_bits {
+            bail!("Badge {}:{} doesn't fit the {:?} format", self.facility, self.card, format);
+        }
+        let bits_of = |value: u32, width: u32| (0..width).rev().map(move |i| value >> i & 1 == 1);
+        let data: Vec<bool> = bits_of(self.facility, facility_bits).chain(bits_of(self.card, card_bits)).collect();
+        let (first, second) = data.split_at(data.len() / 2);
+        let ones = |half: &[bool]| half.iter().filter(|b| **b).count();
+
+        let mut frame = Vec::with_capacity(data.len() + 2);
+        frame.push(ones(first) % 2 == 1);
+        frame.extend(&data);
+        frame.push(ones(second) % 2 == 0);
+        Ok(frame)
+    }
+}
+
+const OSDP_SOM: u8 = 0x53;
+const OSDP_REPLY_FLAG: u8 = 0x80;
+const OSDP_CTRL_CRC: u8 = 0x04;
+const OSDP_CTRL_SECURE: u8 = 0x08;
+const OSDP_CMD_POLL: u8 = 0x60;
+const OSDP_REPLY_ACK: u8 = 0x40;
+const OSDP_REPLY_NAK: u8 = 0x41;
+const OSDP_REPLY_RAW: u8 = 0x50;
+const OSDP_NAK_UNKNOWN_COMMAND: u8 = 0x03;
+const OSDP_NAK_UNSUPPORTED_SECURITY: u8 = 0x05;
+const OSDP_RAW_WIEGAND: u8 = 0x01;
+/// Broadcast address every peripheral answers
+const OSDP_BROADCAST: u8 = 0x7F;
+
+/// A command from the panel, addressed to a reader
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct OsdpCommand {
+    pub address: u8,
+    pub sequence: u8,
+    pub code: u8,
+    secure: bool,
+}
+
+/// CRC-16 as used by OSDP (polynomial 0x1021, initial value 0x1D0F)
+fn osdp_crc(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0x1D0F, |mut crc: u16, byte| {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { crc << 1 ^ 0x1021 } else { crc << 1 };
+        }
+        crc
+    })
+}
+
+/// Take the next complete command off the front of `buffer`, dropping noise
+/// and packets with a bad checksum
+pub(crate) fn next_osdp_command(buffer: &mut VecDeque<u8>) -> Option<OsdpCommand> {
+    loop {
+        while buffer.front().is_some_and(|b| *b != OSDP_SOM) {
+            buffer.pop_front();
+        }
+        if buffer.len() < 6 {
+            return None;
+        }
+        let len = buffer[2] as usize | (buffer[3] as usize) << 8;
+        if len < 7 {
+            buffer.pop_front();
+            continue;
+        }
+        if buffer.len() < len {
+            return None;
+        }
+        let packet: Vec<u8> = buffer.drain(..len).collect();
+        let ctrl = packet[4];
+        let valid = if ctrl & OSDP_CTRL_CRC != 0 {
+            len >= 8 && osdp_crc(&packet[..len - 2]) == u16::from_le_bytes([packet[len - 2], packet[len - 1]])
+        } else {
+            packet[..len - 1].iter().fold(0u8, |sum, b| sum.wrapping_add(*b)).wrapping_neg() == packet[len - 1]
+        };
+        if !valid || packet[1] & OSDP_REPLY_FLAG != 0 {
+            continue;
+        }
+        let secure = ctrl & OSDP_CTRL_SECURE != 0;
+        let code = if secure { packet.get(5 + packet[5] as usize) } else { packet.get(5) };
+        if let Some(&code) = code {
+            return Some(OsdpCommand { address: packet[1], sequence: ctrl & 0x03, code, secure });
+        }
+    }
+}
+
+/// Reply packet with a CRC
+fn osdp_reply(address: u8, sequence: u8, code: u8, data: &[u8]) -> Vec<u8> {
+    let len = 8 + data.len();
+    let mut packet = vec![OSDP_SOM, address | OSDP_REPLY_FLAG, len as u8, (len >> 8) as u8, sequence & 0x03 | OSDP_CTRL_CRC, code];
+    packet.extend_from_slice(data);
+    packet.extend_from_slice(&osdp_crc(&packet).to_le_bytes());
+    packet
+}
+
+/// Reply of the reader at `address` to `command`, if it's addressed to it
+///
+/// Polls are answered with the oldest pending card read as an `osdp_RAW`
+/// Wiegand frame, or an ACK when there is none. Secure channel and other
+/// commands are refused with a NAK.
+pub(crate) fn osdp_respond(address: u8, command: &OsdpCommand, pending: &mut VecDeque<Vec<bool>>) -> Option<Vec<u8>> {
+    if command.address != address && command.address != OSDP_BROADCAST {
+        return None;
+    }
+    let (code, data) = if command.secure {
+        (OSDP_REPLY_NAK, vec![OSDP_NAK_UNSUPPORTED_SECURITY])
+    } else if command.code != OSDP_CMD_POLL {
+        (OSDP_REPLY_NAK, vec![OSDP_NAK_UNKNOWN_COMMAND])
+    } else if let Some(bits) = pending.pop_front() {
+        let mut data = vec![0, OSDP_RAW_WIEGAND];
+        data.extend_from_slice(&(bits.len() as u16).to_le_bytes());
+        data.extend(bits.chunks(8).map(|chunk| chunk.iter().enumerate().fold(0u8, |byte, (i, bit)| byte | (*bit as u8) << (7 - i))));
+        (OSDP_REPLY_RAW, data)
+    } else {
+        (OSDP_REPLY_ACK, Vec::new())
+    };
+    Some(osdp_reply(address, command.sequence, code, &data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_badges_encode_for_wiegand_and_osdp() {
+        let badge = Badge::parse("18:4660", None).unwrap();
+        let bits = badge.wiegand_bits(WiegandFormat::H10301).unwrap();
+        let frame: String = bits.iter().map(|b| if *b { '1' } else { '0' }).collect();
+        assert_eq!(frame, "10001001000010010001101001");
+        assert_eq!(Badge::parse("4660", Some(300)).unwrap().wiegand_bits(WiegandFormat::H10306).unwrap().len(), 34);
+        assert!(Badge::parse("4660", Some(300)).unwrap().wiegand_bits(WiegandFormat::H10301).is_err());
+        assert_eq!(osdp_crc(b"123456789"), 0xE5CC);
+
+        let mut poll = vec![OSDP_SOM, 0x05, 8, 0, 2 | OSDP_CTRL_CRC, OSDP_CMD_POLL];
+        poll.extend(osdp_crc(&poll).to_le_bytes());
+        let mut incoming: VecDeque<u8> = [0xFF, 0x00].into_iter().chain(poll).collect();
+        let mut pending = VecDeque::from([bits]);
+        let command = next_osdp_command(&mut incoming).unwrap();
+        assert_eq!((command.address, command.sequence, command.code), (0x05, 2, OSDP_CMD_POLL));
+        assert!(incoming.is_empty());
+
+        let raw = osdp_respond(0x05, &command, &mut pending).unwrap();
+        assert_eq!(&raw[..6], [OSDP_SOM, 0x85, 16, 0, 0x06, OSDP_REPLY_RAW]);
+        assert_eq!(&raw[6..10], [0, OSDP_RAW_WIEGAND, 26, 0]);
+        assert_eq!(osdp_respond(0x05, &command, &mut pending).unwrap()[5], OSDP_REPLY_ACK);
+        assert_eq!(osdp_respond(0x06, &command, &mut pending), None);
+    }
+}
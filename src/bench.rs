@@ -0,0 +1,248 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::compression::{Compression, BENCH_LEVELS};
+use crate::embeddings::{EmbeddingIndex, EmbeddingPrecision};
+use crate::face_storage::{ExportedCredential, FaceDatabase, FaceSample, UserProfile, ENCODING_DIMENSIONS};
+use crate::snapshot::DatabaseSnapshot;
+
+/// Sample counts measured by default
+pub const DEFAULT_SAMPLE_COUNTS: [usize; 3] = [1_000, 10_000, 100_000];
+
+/// Samples registered per synthetic user, matching the script's default
+pub const SAMPLES_PER_USER: usize = 3;
+
+/// Users the centroid prefilter passes on to exact scoring
+pub const PREFILTER_SHORTLIST: usize = 20;
+
+/// Deterministic pseudo-random encoding in the value range of real embeddings
+pub fn synthetic_encoding(seed: usize) -> Vec<f64> {
+    (0..ENCODING_DIMENSIONS).map(|i| ((seed * 131 + i) as f64 * 0.618).sin() * 0.2).collect()
+}
+
+/// A profile with `samples` synthetic encodings
+pub fn synthetic_profile(user: usize, samples: usize) -> UserProfile {
+    let user_id = format!("user{:06}", user);
+    UserProfile {
+        face_encodings: (0..samples).map(|sample| FaceSample {
+            encoding: synthetic_encoding(user * samples + sample),
+            timestamp: "2024-01-01T00:00:00".to_string(),
+            image_path: None,
+            sample_id: format!("{}_{}", user_id, sample),
+            quality: None,
+            template_version: None,
+            quarantine: None,
+        }).collect(),
+        enrollment_date: Some("2024-01-01T00:00:00".to_string()),
+        sample_count: samples,
+        metadata: Default::default(),
+        extra: Default::default(),
+        user_id,
+    }
+}
+
+/// Database holding `samples` samples, [`SAMPLES_PER_USER`] per user
+pub fn synthetic_database(samples: usize) -> FaceDatabase {
+    let mut db = FaceDatabase::default();
+    for user in 0..samples.div_ceil(SAMPLES_PER_USER) {
+        let profile = synthetic_profile(user, SAMPLES_PER_USER);
+        db.users.insert(profile.user_id.clone(), profile);
+    }
+    db
+}
+
+/// Timing of one benchmarked operation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchResult {
+    pub name: String,
+    pub iterations: u32,
+    pub mean: Duration,
+    /// Size of the file the operation wrote or read, for comparing
+    /// compression levels
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+}
+
+impl BenchResult {
+    pub fn per_second(&self) -> f64 {
+        1.0 / self.mean.as_secs_f64().max(f64::EPSILON)
+    }
+
+    fn with_size_of(mut self, path: &Path) -> Result<Self> {
+        self.bytes = Some(fs::metadata(path)?.len());
+        Ok(self)
+    }
+}
+
+/// Results of [`run`], printable as a table
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BenchReport {
+    pub results: Vec<BenchResult>,
+}
+
+/// An operation that got slower than its baseline allows
+#[derive(Debug, Clone, PartialEq)]
+pub struct Regression {
+    pub name: String,
+    pub baseline: Duration,
+    pub current: Duration,
+}
+
+impl BenchReport {
+    /// Operations whose mean exceeds the baseline's by more than `tolerance`
+    /// (0.2 = 20% slower); operations missing from either report are ignored
+    pub fn regressions(&self, baseline: &BenchReport, tolerance: f64) -> Vec<Regression> {
+        self.results.iter()
+            .filter_map(|current| {
+                let base = baseline.results.iter().find(|b| b.name == current.name)?;
+                (current.mean.as_secs_f64() > base.mean.as_secs_f64() * (1.0 + tolerance)).then(|| Regression {
+                    name: current.name.clone(),
+                    baseline: base.mean,
+                    current: current.mean,
+                })
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<40} {:>10} {:>14} {:>12} {:>12}", "operation", "iterations", "mean", "ops/s", "bytes")?;
+        for result in &self.results {
+            writeln!(
+                f,
+                "{:<40} {:>10} {:>14} {:>12.1} {:>12}",
+                result.name,
+                result.iterations,
+                format!("{:.3?}", result.mean),
+                result.per_second(),
+                result.bytes.map(|bytes| bytes.to_string()).unwrap_or_default()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Run `operation` repeatedly for about `budget` (at least once after a warm-up run)
+pub fn measure(name: impl Into<String>, budget: Duration, mut operation: impl FnMut()) -> BenchResult {
+    operation();
+    let started = Instant::now();
+    let mut iterations = 0u32;
+    while iterations == 0 || (started.elapsed() < budget && iterations < 10_000) {
+        operation();
+        iterations += 1;
+    }
+    BenchResult { name: name.into(), iterations, mean: started.elapsed() / iterations, bytes: None }
+}
+
+/// Measure matching, database load/save and export/import at each sample count
+///
+/// Snapshots and exports are also measured compressed at each of
+/// [`BENCH_LEVELS`], with the file sizes, to show what each level costs.
+///
+/// Files are written under `dir`. This is the `face_auth bench` command; the
+/// criterion suite in `benches/` covers the same operations.
+pub fn run(sample_counts: &[usize], budget: Duration, dir: &Path) -> Result<BenchReport> {
+    fs::create_dir_all(dir)?;
+    let mut report = BenchReport::default();
+    let probe = synthetic_encoding(7);
+
+    for &samples in sample_counts {
+        let db = synthetic_database(samples);
+        let label = |what: &str| format!("{} ({} samples)", what, samples);
+
+        report.results.push(measure(label("match f64"), budget, || {
+            std::hint::black_box(db.find_best_match(&probe, 0.6));
+        }));
+        for precision in [EmbeddingPrecision::F32, EmbeddingPrecision::Int8] {
+            let index = EmbeddingIndex::from_profiles(db.users.values(), precision);
+            report.results.push(measure(label(&format!("match {:?} index", precision)), budget, || {
+                std::hint::black_box(index.find_best_match(&probe, 0.6));
+            }));
+        }
+        let prefiltered = db.embedding_index(EmbeddingPrecision::F32).with_prefilter(PREFILTER_SHORTLIST);
+        report.results.push(measure(label("match F32 index, centroid prefilter"), budget, || {
+            std::hint::black_box(prefiltered.find_best_match(&probe, 0.6));
+        }));
+
+        let json_path = dir.join(format!("bench_{}.json", samples));
+        report.results.push(measure(label("database save"), budget, || {
+            db.save(&json_path).expect("save benchmark database");
+        }));
+        report.results.push(measure(label("database load"), budget, || {
+            std::hint::black_box(FaceDatabase::load(&json_path).expect("load benchmark database"));
+        }));
+
+        let snapshot_path = dir.join(format!("bench_{}.snap", samples));
+        db.write_snapshot(&snapshot_path)?;
+        report.results.push(measure(label("snapshot open + match"), budget, || {
+            let snapshot = DatabaseSnapshot::open(&snapshot_path).expect("open benchmark snapshot");
+            std::hint::black_box(snapshot.find_best_match(&probe, 0.6));
+        }).with_size_of(&snapshot_path)?);
+        for level in BENCH_LEVELS {
+            let compressed_path = dir.join(format!("bench_{}_{}.snap", samples, level));
+            db.write_snapshot_with(&compressed_path, Compression::zstd(level))?;
+            report.results.push(measure(label(&format!("snapshot zstd-{} open + match", level)), budget, || {
+                let snapshot = DatabaseSnapshot::open(&compressed_path).expect("open benchmark snapshot");
+                std::hint::black_box(snapshot.find_best_match(&probe, 0.6));
+            }).with_size_of(&compressed_path)?);
+        }
+    }
+
+    let credential = ExportedCredential {
+        user_id: "user000000".to_string(),
+        user_data: synthetic_profile(0, SAMPLES_PER_USER),
+        exported_at: None,
+        version: Some("1.0".to_string()),
+        extra: Default::default(),
+    };
+    let export_path = dir.join("bench_credentials.json");
+    report.results.push(measure("export credential", budget, || {
+        fs::write(&export_path, serde_json::to_vec_pretty(&credential).unwrap()).expect("write export");
+    }).with_size_of(&export_path)?);
+    report.results.push(measure("import credential", budget, || {
+        let bytes = fs::read(&export_path).expect("read export");
+        std::hint::black_box(serde_json::from_slice::<ExportedCredential>(&bytes).expect("parse export"));
+    }));
+    for level in BENCH_LEVELS {
+        let compression = Compression::zstd(level);
+        let compressed_path = dir.join(format!("bench_credentials_{}.json.zst", level));
+        report.results.push(measure(format!("export credential zstd-{}", level), budget, || {
+            let json = serde_json::to_vec_pretty(&credential).unwrap();
+            fs::write(&compressed_path, compression.compress(&json).expect("compress export")).expect("write export");
+        }).with_size_of(&compressed_path)?);
+        report.results.push(measure(format!("import credential zstd-{}", level), budget, || {
+            std::hint::black_box(ExportedCredential::load(&compressed_path).expect("read export"));
+        }));
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_small_bench() {
+        let dir = tempfile::tempdir().unwrap();
+        let report = run(&[10], Duration::from_millis(1), dir.path()).unwrap();
+        assert_eq!(report.results.len(), 9 + 3 * BENCH_LEVELS.len());
+        assert!(report.results.iter().all(|r| r.iterations > 0));
+        assert!(report.to_string().contains("match F32 index (10 samples)"));
+
+        let size = |name: &str| report.results.iter().find(|r| r.name == name).and_then(|r| r.bytes).unwrap();
+        assert!(size("export credential zstd-3") < size("export credential"));
+
+        let mut slower = report.clone();
+        slower.results[0].mean = report.results[0].mean * 2;
+        let regressions = slower.regressions(&report, 0.5);
+        assert_eq!(regressions.len(), 1);
+        assert_eq!(regressions[0].name, report.results[0].name);
+        assert!(report.regressions(&slower, 0.5).is_empty());
+    }
+}
@@ -0,0 +1,30 @@
+//! Where frames come from
+//!
+//! Captures, [`FaceAuth::watch`](crate::FaceAuth::watch) and
+//! [`FaceAuth::track_faces`](crate::FaceAuth::track_faces) read the camera by
+//! default. A [`CameraSource::VideoFile`] feeds them a prerecorded video
+//! instead, frame by frame through the same scene gate, tracking and decision
+//! pipeline, for offline analysis of footage and reproducible tests of the
+//! multi-frame logic. Streaming from a video ends with the video instead of
+//! failing, and cooldowns count video frames instead of seconds, so the same
+//! file always gives the same attempts.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Camera or video file a backend captures from, see
+/// [`FaceAuthBuilder::camera_source`](crate::FaceAuthBuilder::camera_source)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CameraSource {
+    /// OpenCV camera index
+    Device(u32),
+    /// Any video OpenCV can open, read at full speed
+    VideoFile(PathBuf),
+}
+
+impl Default for CameraSource {
+    fn default() -> Self {
+        Self::Device(0)
+    }
+}
@@ -0,0 +1,229 @@
+use anyhow::{Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::Utc;
+use hmac::{Hmac, KeyInit, Mac};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+
+use super::{CloudFaceService, CloudMatch};
+use crate::face_storage::{REMOTE_TEMPLATE_PREFIX, UserProfile};
+
+const SERVICE: &str = "AWS Rekognition";
+
+/// Most faces a search may return; verification looks for the user's faces among them
+const MAX_SEARCH_FACES: u32 = 100;
+
+#[derive(Clone)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+impl std::fmt::Debug for AwsCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AwsCredentials")
+            .field("access_key_id", &self.access_key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AwsCredentials {
+    /// Credentials from `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`
+    pub fn from_env() -> Result<Self> {
+        let var = |name: &str| std::env::var(name).map_err(|_| anyhow!("{} is not set", name));
+        Ok(Self {
+            access_key_id: var("AWS_ACCESS_KEY_ID")?,
+            secret_access_key: var("AWS_SECRET_ACCESS_KEY")?,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+        })
+    }
+}
+
+/// Amazon Rekognition face collection
+///
+/// Enrolling indexes each face into the collection with the username as
+/// external image id; identifying searches the whole collection.
+#[derive(Debug, Clone)]
+pub struct AwsRekognition {
+    region: String,
+    collection_id: String,
+    credentials: AwsCredentials,
+    agent: ureq::Agent,
+}
+
+impl AwsRekognition {
+    pub fn new(region: impl Into<String>, collection_id: impl Into<String>, credentials: AwsCredentials) -> Self {
+        Self {
+            region: region.into(),
+            collection_id: collection_id.into(),
+            credentials,
+            agent: super::agent(),
+        }
+    }
+
+    /// Collection in the region of `AWS_REGION` (or `AWS_DEFAULT_REGION`) with credentials from the environment
+    pub fn from_env(collection_id: impl Into<String>) -> Result<Self> {
+        let region = std::env::var("AWS_REGION").or_else(|_| std::env::var("AWS_DEFAULT_REGION"))
+            .map_err(|_| anyhow!("AWS_REGION is not set"))?;
+        Ok(Self::new(region, collection_id, AwsCredentials::from_env()?))
+    }
+
+    fn call(&self, action: &str, request: Value) -> Result<Value> {
+        let host = format!("rekognition.{}.amazonaws.com", self.region);
+        let body = serde_json::to_vec(&request)?;
+        let mut headers = vec![
+            ("content-type", "application/x-amz-json-1.1".to_string()),
+            ("host", host.clone()),
+            ("x-amz-date", Utc::now().format("%Y%m%dT%H%M%SZ").to_string()),
+            ("x-amz-target", format!("RekognitionService.{}", action)),
+        ];
+        if let Some(token) = &self.credentials.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let authorization = authorization(&self.credentials, &self.region, "rekognition", "POST", "/", &headers, &body);
+
+        let mut request = self.agent.post(format!("https://{}/", host)).header("authorization", authorization);
+        for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+            request = request.header(*name, value);
+        }
+        super::read_response(SERVICE, request.send(&body[..])?)
+    }
+
+    fn create_collection(&self) -> Result<()> {
+        match self.call("CreateCollection", json!({ "CollectionId": self.collection_id })) {
+            Err(e) if super::error_code(&e) == Some("ResourceAlreadyExistsException") => Ok(()),
+            other => other.map(drop),
+        }
+    }
+
+    /// Faces in the collection matching the face in `image`, best first; `None` if it has no face
+    fn search(&self, image: &[u8], max_faces: u32) -> Result<Option<Vec<Value>>> {
+        let response = self.call("SearchFacesByImage", json!({
+            "CollectionId": self.collection_id,
+            "Image": { "Bytes": BASE64.encode(image) },
+            "MaxFaces": max_faces,
+            "FaceMatchThreshold": 0,
+        }));
+        match response {
+            // Rekognition reports an image without faces as an invalid parameter
+            Err(e) if super::error_code(&e) == Some("InvalidParameterException") => Ok(None),
+            Err(e) => Err(e),
+            Ok(response) => Ok(Some(response["FaceMatches"].as_array().cloned().unwrap_or_default())),
+        }
+    }
+}
+
+impl CloudFaceService for AwsRekognition {
+    fn template_version(&self) -> String {
+        format!("{}aws-rekognition:{}/{}", REMOTE_TEMPLATE_PREFIX, self.region, self.collection_id)
+    }
+
+    fn enroll(&self, username: &str, images: &[Vec<u8>]) -> Result<Vec<Option<String>>> {
+        self.create_collection()?;
+        images.iter()
+            .map(|image| {
+                let response = self.call("IndexFaces", json!({
+                    "CollectionId": self.collection_id,
+                    "Image": { "Bytes": BASE64.encode(image) },
+                    "ExternalImageId": username,
+                    "MaxFaces": 1,
+                    "QualityFilter": "AUTO",
+                }))?;
+                Ok(response["FaceRecords"][0]["Face"]["FaceId"].as_str().map(str::to_string))
+            })
+            .collect()
+    }
+
+    fn verify(&self, profile: &UserProfile, image: &[u8]) -> Result<Option<f64>> {
+        let Some(matches) = self.search(image, MAX_SEARCH_FACES)? else {
+            return Ok(None);
+        };
+        let best = matches.iter()
+            .filter(|m| profile.face_encodings.iter().any(|s| m["Face"]["FaceId"].as_str() == Some(s.sample_id.as_str())))
+            .filter_map(|m| m["Similarity"].as_f64())
+            .fold(0.0, f64::max);
+        Ok(Some(best / 100.0))
+    }
+
+    fn identify(&self, image: &[u8]) -> Result<Option<CloudMatch>> {
+        let Some(matches) = self.search(image, 1)? else {
+            return Ok(None);
+        };
+        Ok(matches.first().and_then(|m| Some(CloudMatch {
+            username: m["Face"]["ExternalImageId"].as_str()?.to_string(),
+            similarity: m["Similarity"].as_f64()? / 100.0,
+        })))
+    }
+
+    fn check(&self) -> Result<()> {
+        match self.call("DescribeCollection", json!({ "CollectionId": self.collection_id })) {
+            // Created on first enrollment
+            Err(e) if super::error_code(&e) == Some("ResourceNotFoundException") => Ok(()),
+            other => other.map(drop),
+        }
+    }
+}
+
+/// Signature Version 4 `Authorization` header for a request without query string
+fn authorization(
+    credentials: &AwsCredentials,
+    region: &str,
+    service: &str,
+    method: &str,
+    path: &str,
+    headers: &[(&str, String)],
+    body: &[u8],
+) -> String {
+    let mut headers: Vec<(String, &str)> = headers.iter().map(|(name, value)| (name.to_ascii_lowercase(), value.trim())).collect();
+    headers.sort();
+    let amz_date = headers.iter().find(|(name, _)| name == "x-amz-date").map_or("", |(_, value)| *value);
+    let date = &amz_date[..amz_date.len().min(8)];
+
+    let signed_headers = headers.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join(";");
+    let canonical_headers: String = headers.iter().map(|(name, value)| format!("{}:{}\n", name, value)).collect();
+    let canonical_request = format!("{}\n{}\n\n{}\n{}\n{}", method, path, canonical_headers, signed_headers, hex(&Sha256::digest(body)));
+
+    let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+    let string_to_sign = format!("AWS4-HMAC-SHA256\n{}\n{}\n{}", amz_date, scope, hex(&Sha256::digest(canonical_request.as_bytes())));
+
+    let key = [date, region, service, "aws4_request"].iter()
+        .fold(format!("AWS4{}", credentials.secret_access_key).into_bytes(), |key, part| hmac(&key, part.as_bytes()));
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key_id, scope, signed_headers, hex(&hmac(&key, string_to_sign.as_bytes()))
+    )
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_matches_aws_test_suite() {
+        // get-vanilla from the AWS Signature Version 4 test suite
+        let credentials = AwsCredentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: None,
+        };
+        let headers = [("Host", "example.amazonaws.com".to_string()), ("X-Amz-Date", "20150830T123600Z".to_string())];
+        assert_eq!(
+            authorization(&credentials, "us-east-1", "service", "GET", "/", &headers, b""),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+}
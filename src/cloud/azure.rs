@@ -0,0 +1,172 @@
+use anyhow::{Result, anyhow};
+use serde_json::{Value, json};
+
+use super::{CloudFaceService, CloudMatch};
+use crate::face_storage::{REMOTE_TEMPLATE_PREFIX, UserProfile};
+
+const SERVICE: &str = "Azure Face";
+const RECOGNITION_MODEL: &str = "recognition_04";
+const DETECTION_MODEL: &str = "detection_03";
+
+/// Azure AI Face large person group
+///
+/// Each enrollment creates a person named after the user and retrains the
+/// group; sample ids are `<person id>/<persisted face id>`. Identification
+/// and verification need face ids, which Azure only returns to resources
+/// approved for Limited Access.
+#[derive(Clone)]
+pub struct AzureFace {
+    endpoint: String,
+    key: String,
+    person_group_id: String,
+    agent: ureq::Agent,
+}
+
+impl std::fmt::Debug for AzureFace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AzureFace")
+            .field("endpoint", &self.endpoint)
+            .field("person_group_id", &self.person_group_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AzureFace {
+    /// `endpoint` is the resource URL, e.g. `https://<name>.cognitiveservices.azure.com`
+    pub fn new(endpoint: impl Into<String>, key: impl Into<String>, person_group_id: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into().trim_end_matches('/').to_string(),
+            key: key.into(),
+            person_group_id: person_group_id.into(),
+            agent: super::agent(),
+        }
+    }
+
+    /// Resource from `AZURE_FACE_ENDPOINT` and `AZURE_FACE_KEY`
+    pub fn from_env(person_group_id: impl Into<String>) -> Result<Self> {
+        let var = |name: &str| std::env::var(name).map_err(|_| anyhow!("{} is not set", name));
+        Ok(Self::new(var("AZURE_FACE_ENDPOINT")?, var("AZURE_FACE_KEY")?, person_group_id))
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/face/v1.0/{}", self.endpoint, path)
+    }
+
+    fn group_url(&self, path: &str) -> String {
+        self.url(&format!("largepersongroups/{}{}", self.person_group_id, path))
+    }
+
+    fn get(&self, url: &str) -> Result<Value> {
+        let response = self.agent.get(url).header("Ocp-Apim-Subscription-Key", &self.key).call()?;
+        super::read_response(SERVICE, response)
+    }
+
+    fn post_json(&self, url: &str, body: Value) -> Result<Value> {
+        let response = self.agent.post(url).header("Ocp-Apim-Subscription-Key", &self.key).send_json(body)?;
+        super::read_response(SERVICE, response)
+    }
+
+    fn post_image(&self, url: &str, image: &[u8]) -> Result<Value> {
+        let response = self.agent.post(url)
+            .header("Ocp-Apim-Subscription-Key", &self.key)
+            .header("Content-Type", "application/octet-stream")
+            .send(image)?;
+        super::read_response(SERVICE, response)
+    }
+
+    fn create_group(&self) -> Result<()> {
+        let body = json!({ "name": self.person_group_id, "recognitionModel": RECOGNITION_MODEL });
+        let response = self.agent.put(self.group_url(""))
+            .header("Ocp-Apim-Subscription-Key", &self.key)
+            .send_json(body)?;
+        match super::read_response(SERVICE, response) {
+            Err(e) if super::error_code(&e) == Some("LargePersonGroupExists") => Ok(()),
+            other => other.map(drop),
+        }
+    }
+
+    /// Id of the first face detected in `image`
+    fn detect(&self, image: &[u8]) -> Result<Option<String>> {
+        let url = self.url(&format!(
+            "detect?returnFaceId=true&recognitionModel={}&detectionModel={}",
+            RECOGNITION_MODEL, DETECTION_MODEL
+        ));
+        Ok(self.post_image(&url, image)?[0]["faceId"].as_str().map(str::to_string))
+    }
+}
+
+impl CloudFaceService for AzureFace {
+    fn template_version(&self) -> String {
+        format!("{}azure-face:{}", REMOTE_TEMPLATE_PREFIX, self.person_group_id)
+    }
+
+    fn enroll(&self, username: &str, images: &[Vec<u8>]) -> Result<Vec<Option<String>>> {
+        self.create_group()?;
+        let person = self.post_json(&self.group_url("/persons"), json!({ "name": username }))?;
+        let person_id = person["personId"].as_str()
+            .ok_or_else(|| anyhow!("Azure Face returned no person id"))?
+            .to_string();
+
+        let url = self.group_url(&format!("/persons/{}/persistedfaces?detectionModel={}", person_id, DETECTION_MODEL));
+        let mut face_ids = Vec::new();
+        for image in images {
+            let face = match self.post_image(&url, image) {
+                Ok(face) => face["persistedFaceId"].as_str().map(|id| format!("{}/{}", person_id, id)),
+                // Images without exactly one detectable face are rejected as bad arguments
+                Err(e) if super::error_code(&e) == Some("InvalidImage") => {
+                    tracing::warn!("Azure Face found no usable face: {:#}", e);
+                    None
+                }
+                Err(e) => return Err(e),
+            };
+            face_ids.push(face);
+        }
+
+        if face_ids.iter().any(Option::is_some) {
+            let response = self.agent.post(self.group_url("/train")).header("Ocp-Apim-Subscription-Key", &self.key).send_empty()?;
+            super::read_response(SERVICE, response)?;
+        }
+        Ok(face_ids)
+    }
+
+    fn verify(&self, profile: &UserProfile, image: &[u8]) -> Result<Option<f64>> {
+        let person_id = profile.face_encodings.iter()
+            .find_map(|s| s.sample_id.split_once('/').map(|(person, _)| person.to_string()))
+            .ok_or_else(|| anyhow!("User '{}' has no Azure Face person", profile.user_id))?;
+        let Some(face_id) = self.detect(image)? else {
+            return Ok(None);
+        };
+        let response = self.post_json(&self.url("verify"), json!({
+            "faceId": face_id,
+            "personId": person_id,
+            "largePersonGroupId": self.person_group_id,
+        }))?;
+        Ok(Some(response["confidence"].as_f64().unwrap_or(0.0)))
+    }
+
+    fn identify(&self, image: &[u8]) -> Result<Option<CloudMatch>> {
+        let Some(face_id) = self.detect(image)? else {
+            return Ok(None);
+        };
+        let response = self.post_json(&self.url("identify"), json!({
+            "faceIds": [face_id],
+            "largePersonGroupId": self.person_group_id,
+            "maxNumOfCandidatesReturned": 1,
+            "confidenceThreshold": 0.0,
+        }))?;
+        let candidate = &response[0]["candidates"][0];
+        let (Some(person_id), Some(confidence)) = (candidate["personId"].as_str(), candidate["confidence"].as_f64()) else {
+            return Ok(None);
+        };
+        let person = self.get(&self.group_url(&format!("/persons/{}", person_id)))?;
+        Ok(person["name"].as_str().map(|name| CloudMatch { username: name.to_string(), similarity: confidence }))
+    }
+
+    fn check(&self) -> Result<()> {
+        match self.get(&self.group_url("")) {
+            // Created on first enrollment
+            Err(e) if super::error_code(&e) == Some("LargePersonGroupNotFound") => Ok(()),
+            other => other.map(drop),
+        }
+    }
+}
@@ -0,0 +1,406 @@
+#[cfg(feature = "cloud-aws")]
+pub mod aws;
+#[cfg(feature = "cloud-azure")]
+pub mod azure;
+
+use anyhow::{Result, anyhow};
+use chrono::Local;
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::backend::FaceBackend;
+use crate::denial::DenialReason;
+use crate::error::FaceAuthError;
+use crate::face_storage::{self, FaceDatabase, FaceSample, StorageLayout, UserProfile};
+use crate::registration::{RegistrationOutcome, SampleOutcome, SampleStatus};
+use crate::standalone_python::{StandaloneAuthResult, StandalonePythonFaceAuth, WarmUpOutcome};
+use crate::timing::TimingBreakdown;
+
+#[cfg(feature = "cloud-aws")]
+pub use aws::{AwsCredentials, AwsRekognition};
+#[cfg(feature = "cloud-azure")]
+pub use azure::AzureFace;
+
+/// Best match a service found for a face
+#[derive(Debug, Clone, PartialEq)]
+pub struct CloudMatch {
+    pub username: String,
+    /// Service similarity scaled to 0.0-1.0
+    pub similarity: f64,
+}
+
+/// Enroll, verify and identify operations of a managed face service
+pub trait CloudFaceService: fmt::Debug + Send + Sync {
+    /// Template version stored on enrolled samples, starting with
+    /// [`face_storage::REMOTE_TEMPLATE_PREFIX`]
+    fn template_version(&self) -> String;
+
+    /// Index the face in each image for `username`, returning the service's
+    /// face id per image (`None` where no face was found)
+    fn enroll(&self, username: &str, images: &[Vec<u8>]) -> Result<Vec<Option<String>>>;
+
+    /// Similarity of the face in `image` to the enrolled faces of `profile`,
+    /// `None` if the image contains no face
+    fn verify(&self, profile: &UserProfile, image: &[u8]) -> Result<Option<f64>>;
+
+    /// Closest enrolled user to the face in `image`, `None` if the image
+    /// contains no face or nobody is enrolled
+    fn identify(&self, image: &[u8]) -> Result<Option<CloudMatch>>;
+
+    /// Check that the service is reachable with the configured credentials
+    fn check(&self) -> Result<()>;
+}
+
+/// [`FaceBackend`] capturing with the Python script and matching with a [`CloudFaceService`]
+///
+/// Credential files keep one sample per face the service indexed, with the
+/// service's face id as `sample_id` and no local encoding, so promotion,
+/// export and auditing work as with the local backends.
+///
+/// Distances reported to [`crate::FaceAuth`] are `1 - similarity`, so a
+/// tolerance of 0.1 accepts matches the service scores at 90% or more.
+/// Only users whose promoted credential in the source directory holds faces
+/// of this service are granted.
+#[derive(Debug)]
+pub struct CloudBackend<S> {
+    layout: StorageLayout,
+    capture: StandalonePythonFaceAuth,
+    service: S,
+}
+
+impl<S: CloudFaceService> CloudBackend<S> {
+    pub fn new(capture: StandalonePythonFaceAuth, service: S) -> Self {
+        let data_dir = capture.data_dir().to_path_buf();
+        Self {
+            layout: StorageLayout {
+                generated_dir: data_dir.join("generated"),
+                source_dir: data_dir.join("source"),
+                data_dir,
+            },
+            capture,
+            service,
+        }
+    }
+
+    pub fn service(&self) -> &S {
+        &self.service
+    }
+
+    /// 1:1 check of the face in `image` against one user's enrolled faces
+    pub fn verify_user(&self, tolerance: f64, source_dir: &str, username: &str, image: &Path) -> Result<StandaloneAuthResult> {
+        face_storage::validate_username(username)?;
+        let started = Instant::now();
+        let profile = UserProfile::load(face_storage::credential_path(source_dir, username))?;
+        let similarity = self.service.verify(&profile, &read_image(image)?)?;
+        Ok(self.result(tolerance, image, similarity.map(|similarity| CloudMatch { username: username.to_string(), similarity }), started))
+    }
+
+    fn result(&self, tolerance: f64, image: &Path, found: Option<CloudMatch>, started: Instant) -> StandaloneAuthResult {
+        let mut result = StandaloneAuthResult {
+            success: true,
+            is_match: Some(false),
+            confidence: None,
+            distance: None,
+            threshold: Some(tolerance),
+            matched_user: None,
+            closest_user: None,
+            runner_up: None,
+            sample_distances: Vec::new(),
+            brightness: None,
+            image_path: Some(image.to_path_buf()),
+            processing_time_ms: Some(started.elapsed().as_millis() as u32),
+            timings: TimingBreakdown::default(),
+            liveness: None,
+            denial: None,
+            raw_output: String::new(),
+        };
+        if let Some(found) = found {
+            let distance = 1.0 - found.similarity;
+            result.is_match = Some(distance <= tolerance);
+            result.confidence = Some(found.similarity);
+            result.distance = Some(distance);
+            result.matched_user = (distance <= tolerance).then(|| found.username.clone());
+            result.closest_user = Some(found.username);
+        }
+        result
+    }
+
+    /// Whether anyone in `source_dir` is enrolled with this service
+    fn has_promoted_users(&self, source_dir: &str) -> Result<bool> {
+        let version = self.service.template_version();
+        Ok(face_storage::load_credentials_dir(Path::new(source_dir))?.iter()
+            .any(|profile| profile.face_encodings.iter().any(|s| s.template_version.as_deref() == Some(version.as_str()))))
+    }
+
+    /// Denial without capturing or calling the service
+    fn no_users_enrolled(&self, tolerance: f64) -> StandaloneAuthResult {
+        let mut result = self.result(tolerance, Path::new(""), None, Instant::now());
+        result.image_path = None;
+        result.denial = Some(DenialReason::NoUsersEnrolled);
+        result
+    }
+
+    /// Whether `username` has a promoted credential enrolled with this service
+    fn is_promoted(&self, source_dir: &str, username: &str) -> bool {
+        let version = self.service.template_version();
+        face_storage::validate_username(username).is_ok()
+            && UserProfile::load(face_storage::credential_path(source_dir, username))
+                .is_ok_and(|profile| profile.face_encodings.iter().any(|s| s.template_version.as_deref() == Some(version.as_str())))
+    }
+}
+
+impl<S: CloudFaceService> FaceBackend for CloudBackend<S> {
+    fn data_dir(&self) -> &Path {
+        &self.layout.data_dir
+    }
+
+    fn template_version(&self) -> String {
+        self.service.template_version()
+    }
+
+    fn register_user(&self, username: &str, samples: u32, generated_dir: &str) -> Result<RegistrationOutcome> {
+        face_storage::validate_username(username)?;
+        let now = Local::now().format("%Y-%m-%dT%H:%M:%S%.6f").to_string();
+        let frames = self.capture.capture_frames(&format!("registration_{}", username), samples)?;
+        let images = frames.iter().flatten().map(|frame| read_image(frame)).collect::<Result<Vec<_>>>()?;
+        let mut face_ids = self.service.enroll(username, &images)?.into_iter();
+
+        let version = self.service.template_version();
+        let mut outcome = RegistrationOutcome {
+            username: username.to_string(),
+            samples_requested: samples,
+            samples_captured: 0,
+            samples: Vec::new(),
+            generated_file: None,
+            fully_enrolled: false,
+            thumbnail_file: None,
+            promoted_file: None,
+        };
+        let mut face_encodings = Vec::new();
+        for (frame, index) in frames.into_iter().zip(1..) {
+            let status = match frame.map(|frame| (frame, face_ids.next().flatten())) {
+                None => SampleStatus::CaptureFailed,
+                Some((_, None)) => SampleStatus::NoFace,
+                Some((frame, Some(face_id))) => {
+                    face_encodings.push(FaceSample {
+                        encoding: Vec::new(),
+                        timestamp: now.clone(),
+                        image_path: Some(frame.to_string_lossy().into_owned()),
+                        sample_id: face_id,
+                        quality: None,
+                        template_version: Some(version.clone()),
+                        quarantine: None,
+                    });
+                    SampleStatus::Stored
+                }
+            };
+            outcome.samples.push(SampleOutcome { index, status, quality: None });
+        }
+
+        outcome.samples_captured = face_encodings.len() as u32;
+        if face_encodings.is_empty() {
+            return Ok(outcome);
+        }
+
+        let profile = UserProfile {
+            user_id: username.to_string(),
+            sample_count: face_encodings.len(),
+            face_encodings,
+            enrollment_date: Some(now),
+            metadata: Default::default(),
+            extra: Default::default(),
+        };
+        let generated_file = std::path::absolute(face_storage::credential_path(generated_dir, username))?;
+        profile.save(&generated_file)?;
+
+        let mut db = FaceDatabase::load(self.layout.database_path())?.unwrap_or_default();
+        db.users.insert(username.to_string(), profile);
+        db.save(self.layout.database_path())?;
+
+        outcome.fully_enrolled = outcome.samples_captured == samples;
+        outcome.generated_file = Some(generated_file);
+        Ok(outcome)
+    }
+
+    fn authenticate_user(&self, tolerance: f64, source_dir: &str) -> Result<StandaloneAuthResult> {
+        if !self.has_promoted_users(source_dir)? {
+            return Ok(self.no_users_enrolled(tolerance));
+        }
+        let frame = self.capture.capture_frames("auth", 1)?.into_iter().flatten().next()
+            .ok_or_else(|| anyhow!("Failed to capture a frame from the camera"))?;
+        self.authenticate_image(tolerance, source_dir, &frame)
+    }
+
+    fn authenticate_image(&self, tolerance: f64, source_dir: &str, image: &Path) -> Result<StandaloneAuthResult> {
+        if !self.has_promoted_users(source_dir)? {
+            return Ok(self.no_users_enrolled(tolerance));
+        }
+        let started = Instant::now();
+        let mut result = self.result(tolerance, image, self.service.identify(&read_image(image)?)?, started);
+        // The service also knows users who were enrolled but never promoted
+        if let Some(user) = result.matched_user.take() {
+            if self.is_promoted(source_dir, &user) {
+                result.matched_user = Some(user);
+            } else {
+                result.is_match = Some(false);
+            }
+        }
+        Ok(result)
+    }
+
+    fn warm_up(&self, camera: bool) -> Result<WarmUpOutcome> {
+        let started = Instant::now();
+        self.service.check()?;
+        let camera_ready = match camera {
+            true => self.capture.warm_up(true)?.camera_ready,
+            false => None,
+        };
+        Ok(WarmUpOutcome { camera_ready, elapsed_ms: started.elapsed().as_millis() as u64 })
+    }
+
+    fn export_user(&self, username: &str, filename: &str) -> Result<bool> {
+        self.layout.export_user(username, filename)
+    }
+
+    fn import_user(&self, filename: &str) -> Result<bool> {
+        self.layout.import_user(filename)
+    }
+
+    fn check(&self) -> Result<()> {
+        self.capture.check_executable()?;
+        self.service.check()
+    }
+}
+
+fn read_image(path: &Path) -> Result<Vec<u8>> {
+    fs::read(path).map_err(|e| anyhow!("Failed to read image {}: {}", path.display(), e))
+}
+
+/// HTTP agent that hands error responses back instead of failing, so their
+/// bodies can be turned into [`FaceAuthError::CloudRequestFailed`]
+fn agent() -> ureq::Agent {
+    ureq::Agent::config_builder()
+        .http_status_as_error(false)
+        .timeout_global(Some(Duration::from_secs(30)))
+        .build()
+        .into()
+}
+
+/// JSON body of a response (`null` when empty), or the service's error
+fn read_response(service: &'static str, mut response: ureq::http::Response<ureq::Body>) -> Result<Value> {
+    let status = response.status().as_u16();
+    let text = response.body_mut().read_to_string()?;
+    let body = match serde_json::from_str::<Value>(&text) {
+        Ok(body) => body,
+        Err(_) if text.trim().is_empty() => Value::Null,
+        Err(e) if status < 400 => return Err(anyhow!("Invalid {} response: {}", service, e)),
+        Err(_) => Value::String(text),
+    };
+    if status < 400 {
+        return Ok(body);
+    }
+
+    // AWS: {"__type": "ns#Code", "Message": ...}, Azure: {"error": {"code": ..., "message": ...}}
+    let error = body.get("error").unwrap_or(&body);
+    let field = |keys: &[&str]| keys.iter().find_map(|key| error.get(key)).and_then(Value::as_str).map(str::to_string);
+    Err(FaceAuthError::CloudRequestFailed {
+        service,
+        status,
+        code: field(&["__type", "code"]).map(|c| c.rsplit('#').next().unwrap_or_default().to_string()).unwrap_or_default(),
+        message: field(&["Message", "message"]).or_else(|| body.as_str().map(str::to_string)).unwrap_or_default(),
+    }.into())
+}
+
+/// Service error code of a failed request, if `err` is one
+fn error_code(err: &anyhow::Error) -> Option<&str> {
+    match err.downcast_ref::<FaceAuthError>() {
+        Some(FaceAuthError::CloudRequestFailed { code, .. }) => Some(code),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Service that identifies frames by their file content
+    #[derive(Debug)]
+    struct FakeService;
+
+    impl CloudFaceService for FakeService {
+        fn template_version(&self) -> String {
+            "remote:fake".to_string()
+        }
+
+        fn enroll(&self, username: &str, images: &[Vec<u8>]) -> Result<Vec<Option<String>>> {
+            Ok(images.iter().enumerate().map(|(i, _)| Some(format!("{}-{}", username, i))).collect())
+        }
+
+        fn verify(&self, profile: &UserProfile, image: &[u8]) -> Result<Option<f64>> {
+            Ok(self.identify(image)?.map(|m| if m.username == profile.user_id { m.similarity } else { 0.0 }))
+        }
+
+        fn identify(&self, image: &[u8]) -> Result<Option<CloudMatch>> {
+            let content = String::from_utf8_lossy(image);
+            Ok(content.split_once(':').map(|(user, similarity)| CloudMatch {
+                username: user.to_string(),
+                similarity: similarity.parse().unwrap(),
+            }))
+        }
+
+        fn check(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_similarity_maps_to_distance_and_requires_promotion() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = CloudBackend::new(StandalonePythonFaceAuth::from_parts("python3", "unused.py", dir.path()), FakeService);
+        let source = dir.path().join("source");
+        let profile = UserProfile {
+            user_id: "ann".to_string(),
+            sample_count: 1,
+            face_encodings: vec![FaceSample {
+                encoding: Vec::new(),
+                timestamp: String::new(),
+                image_path: None,
+                sample_id: "ann-0".to_string(),
+                quality: None,
+                template_version: Some("remote:fake".to_string()),
+                quarantine: None,
+            }],
+            enrollment_date: None,
+            metadata: Default::default(),
+            extra: Default::default(),
+        };
+        profile.validate().unwrap();
+        profile.save(face_storage::credential_path(&source, "ann")).unwrap();
+
+        let frame = |name: &str, content: &str| {
+            let path = dir.path().join(name);
+            fs::write(&path, content).unwrap();
+            path
+        };
+        let source = source.to_str().unwrap();
+        let ann = backend.authenticate_image(0.1, source, &frame("ann.jpg", "ann:0.95")).unwrap();
+        assert_eq!(ann.matched_user.as_deref(), Some("ann"));
+        assert!((ann.distance.unwrap() - 0.05).abs() < 1e-9);
+
+        let weak = backend.authenticate_image(0.1, source, &frame("weak.jpg", "ann:0.8")).unwrap();
+        assert_eq!((weak.is_match, weak.closest_user.as_deref()), (Some(false), Some("ann")));
+
+        // Enrolled with the service but not promoted
+        let bob = backend.authenticate_image(0.1, source, &frame("bob.jpg", "bob:0.99")).unwrap();
+        assert_eq!((bob.is_match, bob.matched_user), (Some(false), None));
+
+        let nobody = backend.authenticate_image(0.1, source, &frame("empty.jpg", "")).unwrap();
+        assert!(nobody.closest_user.is_none());
+
+        let verified = backend.verify_user(0.1, source, "ann", &frame("bob2.jpg", "bob:0.99")).unwrap();
+        assert_eq!(verified.is_match, Some(false));
+    }
+}
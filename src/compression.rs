@@ -0,0 +1,138 @@
+//! zstd compression of stored and exported data
+//!
+//! Database snapshots, exported credentials and recorded session frames can
+//! be written zstd-compressed, see [`FaceAuthBuilder::compression`](crate::FaceAuthBuilder::compression).
+//! Reading is transparent: compressed files are recognized by the zstd magic
+//! number and decompressed, everything else is read as before, so devices
+//! with and without compression can share files.
+//!
+//! Embeddings compress to roughly half their JSON size. Images are kept as
+//! they are: JPEG and PNG data is compressed already and zstd barely shrinks
+//! it, so recorded JPEG and PNG frames, like captured images, are stored
+//! unchanged, see [`is_compressed_image`]. `face_auth bench` reports the size
+//! and time of each level.
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::io::Read;
+
+/// Level used by [`Compression::zstd_default`], zstd's own default
+pub const DEFAULT_LEVEL: i32 = 3;
+
+/// Levels `face_auth bench` compares
+pub const BENCH_LEVELS: [i32; 4] = [1, 3, 9, 19];
+
+/// File name extension of compressed recorded frames
+pub const EXTENSION: &str = "zst";
+
+/// Most bytes [`decompress`] inflates data to, so a small crafted file can't
+/// exhaust memory
+pub const MAX_DECOMPRESSED_LEN: usize = 1 << 30;
+
+const MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const JPEG_MAGIC: [u8; 3] = [0xff, 0xd8, 0xff];
+const PNG_MAGIC: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+/// Whether and how data is compressed when written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "algorithm")]
+pub enum Compression {
+    #[default]
+    None,
+    /// zstd at `level`: 1 is fastest, 19 and up trade a lot of time for a
+    /// little size
+    Zstd { level: i32 },
+}
+
+impl Compression {
+    pub fn zstd(level: i32) -> Self {
+        Compression::Zstd { level }
+    }
+
+    pub fn zstd_default() -> Self {
+        Compression::zstd(DEFAULT_LEVEL)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        *self != Compression::None
+    }
+
+    /// Fail on a level zstd doesn't have
+    pub fn validate(&self) -> Result<()> {
+        if let Compression::Zstd { level } = *self {
+            if !zstd::compression_level_range().contains(&level) {
+                bail!("zstd level {} is out of range {:?}", level, zstd::compression_level_range());
+            }
+        }
+        Ok(())
+    }
+
+    /// `bytes` as they should be written
+    pub fn compress<'a>(&self, bytes: &'a [u8]) -> Result<Cow<'a, [u8]>> {
+        match *self {
+            Compression::None => Ok(Cow::Borrowed(bytes)),
+            Compression::Zstd { level } => {
+                self.validate()?;
+                Ok(Cow::Owned(zstd::bulk::compress(bytes, level)?))
+            }
+        }
+    }
+}
+
+/// Whether `bytes` start with the zstd magic number
+pub fn is_compressed(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+/// Whether `bytes` are a JPEG or PNG image, which zstd can't shrink further
+pub fn is_compressed_image(bytes: &[u8]) -> bool {
+    bytes.starts_with(&JPEG_MAGIC) || bytes.starts_with(&PNG_MAGIC)
+}
+
+/// `bytes` decompressed if they are zstd, unchanged otherwise
+///
+/// Fails if they inflate to more than [`MAX_DECOMPRESSED_LEN`] bytes.
+pub fn decompress(bytes: &[u8]) -> Result<Cow<'_, [u8]>> {
+    decompress_at_most(bytes, MAX_DECOMPRESSED_LEN)
+}
+
+/// [`decompress`], failing if `bytes` inflate to more than `limit` bytes,
+/// e.g. for data received from another device
+pub fn decompress_at_most(bytes: &[u8], limit: usize) -> Result<Cow<'_, [u8]>> {
+    if !is_compressed(bytes) {
+        return Ok(Cow::Borrowed(bytes));
+    }
+    let decoder = zstd::stream::read::Decoder::new(bytes).context("Failed to decompress zstd data")?;
+    let mut decompressed = Vec::new();
+    decoder.take(limit as u64 + 1).read_to_end(&mut decompressed).context("Failed to decompress zstd data")?;
+    if decompressed.len() > limit {
+        bail!("zstd data decompresses to more than {} bytes", limit);
+    }
+    Ok(Cow::Owned(decompressed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compressed_data_round_trips_and_plain_data_passes_through() {
+        let json = serde_json::to_vec_pretty(&crate::bench::synthetic_profile(0, 3)).unwrap();
+        let compressed = Compression::zstd_default().compress(&json).unwrap();
+        assert!(is_compressed(&compressed));
+        assert!(compressed.len() < json.len() / 2);
+        assert_eq!(decompress(&compressed).unwrap(), json.as_slice());
+
+        assert!(matches!(Compression::None.compress(&json).unwrap(), Cow::Borrowed(_)));
+        assert!(matches!(decompress(&json).unwrap(), Cow::Borrowed(_)));
+        assert!(Compression::zstd(99).compress(&json).is_err());
+        assert!(decompress(&[0x28, 0xb5, 0x2f, 0xfd, 0]).is_err());
+
+        // A small file can't inflate past the limit
+        let bomb = Compression::zstd_default().compress(&[0u8; 1 << 20]).unwrap();
+        assert!(bomb.len() < 1024);
+        assert_eq!(decompress_at_most(&bomb, 1 << 20).unwrap().len(), 1 << 20);
+        assert!(decompress_at_most(&bomb, (1 << 20) - 1).is_err());
+    }
+}
@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Caller-supplied key-value context of an authentication, such as the door,
+/// the requested resource or a transaction amount
+///
+/// Passed to the decision policy and hooks, and recorded in the audit log and
+/// the result, so rules like "high-value transactions need a closer match"
+/// can be expressed and audited.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct AuthContext(BTreeMap<String, String>);
+
+impl AuthContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, key: impl Into<String>, value: impl ToString) -> Self {
+        self.0.insert(key.into(), value.to_string());
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    /// Value of `key` parsed as a number, e.g. a transaction amount
+    pub fn number(&self, key: &str) -> Option<f64> {
+        self.get(key)?.trim().parse().ok()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+}
+
+impl<K: Into<String>, V: ToString> FromIterator<(K, V)> for AuthContext {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        iter.into_iter().fold(Self::new(), |context, (key, value)| context.with(key, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_serializes_as_a_flat_object() {
+        let context = AuthContext::new().with("door", "lobby").with("amount", 2500.5);
+        assert_eq!(serde_json::to_value(&context).unwrap(), serde_json::json!({"amount": "2500.5", "door": "lobby"}));
+        assert_eq!(context.number("amount"), Some(2500.5));
+        assert_eq!(context.number("door"), None);
+        assert_eq!(context, [("door", "lobby"), ("amount", "2500.5")].into_iter().collect());
+    }
+}
@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+
+use crate::face_storage::{FaceSample, UserProfile};
+use crate::matching::{centroid, face_distance};
+
+/// Centroid distance below which two users are taken to be one person enrolled twice
+pub const DUPLICATE_ENROLLMENT_DISTANCE: f64 = 0.3;
+
+/// Two enrolled users whose samples are close enough to be confused
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CrossMatch {
+    pub first_user: String,
+    pub second_user: String,
+    /// Smallest distance between a sample of each user
+    pub closest_distance: f64,
+    pub first_sample: String,
+    pub second_sample: String,
+    /// Distance between the users' average templates
+    pub centroid_distance: f64,
+    pub resolution: CrossMatchResolution,
+}
+
+/// Suggested fix for a [`CrossMatch`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CrossMatchResolution {
+    /// All samples are alike: the same person was enrolled under both names
+    RemoveDuplicate,
+    /// One sample looks more like the other user than its owner, e.g. a
+    /// photo filed under the wrong name
+    RemoveSample { user: String, sample_id: String },
+    /// Different people who look alike, such as twins
+    StricterThreshold,
+}
+
+impl CrossMatch {
+    /// What the admin should do, in English
+    pub fn suggestion(&self) -> String {
+        match &self.resolution {
+            CrossMatchResolution::RemoveDuplicate => format!(
+                "'{}' and '{}' look like the same person; delete one of them",
+                self.first_user, self.second_user
+            ),
+            CrossMatchResolution::RemoveSample { user, sample_id } => format!(
+                "Sample '{}' of '{}' looks like the other user; review and remove it",
+                sample_id, user
+            ),
+            CrossMatchResolution::StricterThreshold => format!(
+                "'{}' and '{}' look alike; use a stricter tolerance or a second factor for them",
+                self.first_user, self.second_user
+            ),
+        }
+    }
+}
+
+/// Pairs of users with samples within `tolerance` of each other, closest first
+///
+/// Quarantined and remote samples are ignored. Compares every sample of every
+/// pair of users, so it is meant for occasional admin audits.
+pub fn find_cross_matches(profiles: &[UserProfile], tolerance: f64) -> Vec<CrossMatch> {
+    let users: Vec<(&UserProfile, Vec<&FaceSample>, Vec<f64>)> = profiles.iter()
+        .filter_map(|profile| {
+            let samples: Vec<&FaceSample> = profile.face_encodings.iter()
+                .filter(|s| !s.is_remote() && !s.is_quarantined())
+                .collect();
+            let centroid = centroid(samples.iter().map(|s| s.encoding.as_slice()))?;
+            Some((profile, samples, centroid))
+        })
+        .collect();
+
+    let mut matches = Vec::new();
+    for (i, (first, first_samples, first_centroid)) in users.iter().enumerate() {
+        for (second, second_samples, second_centroid) in &users[i + 1..] {
+            if first_centroid.len() != second_centroid.len() {
+                continue;
+            }
+            let closest = first_samples.iter()
+                .flat_map(|a| second_samples.iter().map(move |b| (*a, *b, face_distance(&a.encoding, &b.encoding))))
+                .min_by(|x, y| x.2.total_cmp(&y.2));
+            let Some((first_sample, second_sample, closest_distance)) = closest.filter(|c| c.2 <= tolerance) else {
+                continue;
+            };
+
+            let centroid_distance = face_distance(first_centroid, second_centroid);
+            // A sample closer to the other user's average than to its owner's is likely misfiled
+            let misfiled = |sample: &FaceSample, own: &[f64], other: &[f64]| {
+                face_distance(&sample.encoding, other) < face_distance(&sample.encoding, own)
+            };
+            let resolution = if centroid_distance <= DUPLICATE_ENROLLMENT_DISTANCE {
+                CrossMatchResolution::RemoveDuplicate
+            } else if first_samples.len() > 1 && misfiled(first_sample, first_centroid, second_centroid) {
+                CrossMatchResolution::RemoveSample { user: first.user_id.clone(), sample_id: first_sample.sample_id.clone() }
+            } else if second_samples.len() > 1 && misfiled(second_sample, second_centroid, first_centroid) {
+                CrossMatchResolution::RemoveSample { user: second.user_id.clone(), sample_id: second_sample.sample_id.clone() }
+            } else {
+                CrossMatchResolution::StricterThreshold
+            };
+            matches.push(CrossMatch {
+                first_user: first.user_id.clone(),
+                second_user: second.user_id.clone(),
+                closest_distance,
+                first_sample: first_sample.sample_id.clone(),
+                second_sample: second_sample.sample_id.clone(),
+                centroid_distance,
+                resolution,
+            });
+        }
+    }
+    matches.sort_by(|a, b| a.closest_distance.total_cmp(&b.closest_distance));
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(user_id: &str, encodings: &[[f64; 2]]) -> UserProfile {
+        let mut profile: UserProfile = serde_json::from_value(serde_json::json!({ "user_id": user_id, "face_encodings": [] })).unwrap();
+        profile.face_encodings = encodings.iter().enumerate().map(|(i, e)| FaceSample {
+            encoding: e.to_vec(),
+            timestamp: String::new(),
+            image_path: None,
+            sample_id: format!("{}_{}", user_id, i + 1),
+            quality: None,
+            template_version: None,
+            quarantine: None,
+        }).collect();
+        profile
+    }
+
+    #[test]
+    fn test_cross_matches_suggest_resolutions() {
+        let profiles = [
+            profile("ann", &[[0.0, 0.0], [0.1, 0.0]]),
+            profile("ann_again", &[[0.05, 0.05], [0.0, 0.1]]),
+            profile("bob", &[[3.0, 0.0], [3.1, 0.0], [0.2, 0.0]]),
+            profile("twin_a", &[[0.0, 6.0], [0.1, 6.0]]),
+            profile("twin_b", &[[0.5, 6.0], [0.6, 6.0]]),
+            profile("carl", &[[9.0, 9.0]]),
+        ];
+        let matches = find_cross_matches(&profiles, 0.6);
+        let resolution = |a: &str, b: &str| {
+            matches.iter().find(|m| m.first_user == a && m.second_user == b).map(|m| m.resolution.clone())
+        };
+
+        assert_eq!(resolution("ann", "ann_again"), Some(CrossMatchResolution::RemoveDuplicate));
+        assert_eq!(
+            resolution("ann", "bob"),
+            Some(CrossMatchResolution::RemoveSample { user: "bob".into(), sample_id: "bob_3".into() })
+        );
+        assert_eq!(resolution("twin_a", "twin_b"), Some(CrossMatchResolution::StricterThreshold));
+        assert!(matches.iter().all(|m| m.first_user != "carl" && m.second_user != "carl"));
+        assert!(matches.windows(2).all(|w| w[0].closest_distance <= w[1].closest_distance));
+    }
+}
@@ -0,0 +1,66 @@
+use anyhow::{Result, anyhow};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+/// Size of a symmetric key in bytes
+pub const KEY_LEN: usize = 32;
+
+/// Bytes [`seal`] adds to the plaintext: the nonce and the tag
+pub const SEAL_OVERHEAD: usize = NONCE_LEN + 16;
+
+const NONCE_LEN: usize = 12;
+
+/// Encrypt `plaintext` with ChaCha20-Poly1305; the random nonce is prepended
+pub fn seal(key: &[u8; KEY_LEN], plaintext: &[u8]) -> Result<Vec<u8>> {
+    seal_bound(key, plaintext, b"")
+}
+
+/// [`seal`], authenticating `context` along with the plaintext, so the data
+/// only opens with the same context, see [`open_bound`]
+pub fn seal_bound(key: &[u8; KEY_LEN], plaintext: &[u8], context: &[u8]) -> Result<Vec<u8>> {
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, Payload { msg: plaintext, aad: context })
+        .map_err(|_| anyhow!("Encryption failed"))?;
+
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+/// Decrypt data produced by [`seal`]
+pub fn open(key: &[u8; KEY_LEN], sealed: &[u8]) -> Result<Vec<u8>> {
+    open_bound(key, sealed, b"")
+}
+
+/// Decrypt data produced by [`seal_bound`] with the same `context`
+pub fn open_bound(key: &[u8; KEY_LEN], sealed: &[u8], context: &[u8]) -> Result<Vec<u8>> {
+    if sealed.len() < NONCE_LEN {
+        return Err(anyhow!("Encrypted data is truncated"));
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    cipher
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: context })
+        .map_err(|_| anyhow!("Decryption failed: wrong key or corrupted data"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_roundtrip_and_wrong_key() {
+        let key = [7u8; KEY_LEN];
+        let sealed = seal(&key, b"thumbnail").unwrap();
+        assert_eq!(open(&key, &sealed).unwrap(), b"thumbnail");
+        assert!(open(&[8u8; KEY_LEN], &sealed).is_err());
+        assert_eq!(sealed.len(), b"thumbnail".len() + SEAL_OVERHEAD);
+
+        let bound = seal_bound(&key, b"thumbnail", b"context").unwrap();
+        assert_eq!(open_bound(&key, &bound, b"context").unwrap(), b"thumbnail");
+        assert!(open_bound(&key, &bound, b"other").is_err());
+        assert!(open(&key, &bound).is_err());
+    }
+}
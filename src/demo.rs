@@ -0,0 +1,373 @@
+//! Synthetic faces and a scripted walkthrough for trying an installation
+//! without a camera
+//!
+//! [`PEOPLE`] are drawn procedurally: shaded head, eyes, brows, nose and mouth
+//! on a plain background, with each photo slightly shifted, scaled and lit
+//! differently, as uncompressed BMP files the backend reads like any other
+//! photo. [`run`] enrolls the first people from their photos, then
+//! authenticates a held-out photo of each and of the last one, a stranger
+//! who must be denied. `face_auth demo` runs it against a throwaway data
+//! directory.
+//!
+//! The walkthrough is only tested with [`FakeBackend`](crate::FakeBackend),
+//! which serves canned embeddings for the photos. The drawings are not known
+//! to be found by the real backend's face detector: if it finds no face in
+//! them, enrollment reports the photos as unusable, which says nothing about
+//! the installation. Check a real camera with `face_auth doctor` instead.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::auth::{FaceAuth, FaceAuthResult};
+use crate::face_storage::UserMetadata;
+use crate::v1::AuthRequest;
+
+/// Photos each demo user is enrolled with; the next one is their probe
+pub const ENROLLMENT_PHOTOS: u32 = 3;
+
+const WIDTH: usize = 240;
+const HEIGHT: usize = 300;
+
+type Rgb = [f64; 3];
+
+/// A synthetic person, see the [module docs](self)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DemoPerson {
+    pub name: &'static str,
+    /// Whether [`run`] enrolls them, or only checks that they are denied
+    pub enrolled: bool,
+    skin: Rgb,
+    hair: Rgb,
+    iris: Rgb,
+    shirt: Rgb,
+    /// Half width and half height of the face
+    face: (f64, f64),
+    /// Distance of each eye from the middle of the face
+    eye_offset: f64,
+    eye_size: f64,
+    nose_length: f64,
+    mouth_width: f64,
+    brow_thickness: f64,
+}
+
+/// The demo cast: two users to enroll and a stranger
+pub const PEOPLE: [DemoPerson; 3] = [
+    DemoPerson {
+        name: "alex",
+        enrolled: true,
+        skin: [224.0, 182.0, 150.0],
+        hair: [70.0, 45.0, 30.0],
+        iris: [60.0, 100.0, 140.0],
+        shirt: [40.0, 90.0, 160.0],
+        face: (62.0, 82.0),
+        eye_offset: 25.0,
+        eye_size: 9.0,
+        nose_length: 26.0,
+        mouth_width: 22.0,
+        brow_thickness: 4.0,
+    },
+    DemoPerson {
+        name: "sam",
+        enrolled: true,
+        skin: [150.0, 100.0, 70.0],
+        hair: [20.0, 18.0, 18.0],
+        iris: [50.0, 35.0, 20.0],
+        shirt: [170.0, 50.0, 50.0],
+        face: (70.0, 76.0),
+        eye_offset: 29.0,
+        eye_size: 8.0,
+        nose_length: 20.0,
+        mouth_width: 28.0,
+        brow_thickness: 6.0,
+    },
+    DemoPerson {
+        name: "robin",
+        enrolled: false,
+        skin: [240.0, 205.0, 180.0],
+        hair: [190.0, 140.0, 60.0],
+        iris: [70.0, 120.0, 70.0],
+        shirt: [60.0, 140.0, 80.0],
+        face: (56.0, 88.0),
+        eye_offset: 22.0,
+        eye_size: 10.0,
+        nose_length: 30.0,
+        mouth_width: 18.0,
+        brow_thickness: 3.0,
+    },
+];
+
+/// How one photo of a person differs from the others
+struct Variant {
+    shift: (f64, f64),
+    scale: f64,
+    /// Horizontal direction the light comes from, -1 left to 1 right
+    light: f64,
+    smile: f64,
+}
+
+fn variant(index: u32) -> Variant {
+    let i = index as f64;
+    Variant {
+        shift: ((i * 2.3).sin() * 6.0, (i * 1.7).cos() * 4.0),
+        scale: 1.0 + (i * 0.9).sin() * 0.05,
+        light: (i * 1.3).sin() * 0.5,
+        smile: (i * 2.1).cos() * 0.5 + 0.5,
+    }
+}
+
+impl DemoPerson {
+    /// Photo number `index` of this person, as a 24-bit BMP
+    pub fn render(&self, index: u32) -> Vec<u8> {
+        let v = variant(index);
+        let mut pixels = vec![[0.0; 3]; WIDTH * HEIGHT];
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            let (x, y) = ((i % WIDTH) as f64, (i / WIDTH) as f64);
+            // Coordinates relative to the middle of the face, undoing shift and scale
+            let fx = (x - WIDTH as f64 / 2.0 - v.shift.0) / v.scale;
+            let fy = (y - HEIGHT as f64 * 0.42 - v.shift.1) / v.scale;
+            *pixel = self.shade(fx, fy, &v, y / HEIGHT as f64);
+        }
+        bmp(&pixels)
+    }
+
+    fn shade(&self, x: f64, y: f64, v: &Variant, height: f64) -> Rgb {
+        let (rx, ry) = self.face;
+        let background = mix([200.0, 205.0, 215.0], [150.0, 158.0, 172.0], height);
+        let mut color = background;
+
+        // Shoulders and neck
+        if ellipse(x, y - ry * 1.9, rx * 2.1, ry * 0.9) < 1.0 {
+            color = self.shirt;
+        } else if x.abs() < rx * 0.45 && y > 0.0 && y < ry * 1.3 {
+            color = scale(self.skin, 0.8);
+        }
+        // Hair behind the head
+        if ellipse(x, y + ry * 0.12, rx * 1.12, ry * 1.1) < 1.0 && y < ry * 0.2 {
+            color = self.hair;
+        }
+
+        let face = ellipse(x, y, rx, ry);
+        if face >= 1.0 {
+            return color;
+        }
+        // Fringe across the forehead
+        if y < -ry * 0.62 + (x / rx).powi(2) * ry * 0.25 {
+            return self.hair;
+        }
+
+        // Lambertian shading of an ellipsoid lit from the front and one side
+        let (nx, ny) = (x / rx, y / ry);
+        let nz = (1.0 - face).sqrt();
+        let light = (v.light, -0.3, 1.0);
+        let norm = (light.0 * light.0 + light.1 * light.1 + light.2 * light.2).sqrt();
+        let diffuse = ((nx * light.0 + ny * light.1 + nz * light.2) / norm).max(0.0);
+        let mut skin = scale(self.skin, 0.35 + 0.7 * diffuse);
+
+        let eye_y = -ry * 0.12;
+        for side in [-1.0, 1.0] {
+            let (ex, ey) = (x - side * self.eye_offset, y - eye_y);
+            // Brow
+            let brow_y = ey + self.eye_size * 1.9 - (ex / (self.eye_size * 1.8)).powi(2) * 3.0;
+            if ex.abs() < self.eye_size * 1.8 && brow_y.abs() < self.brow_thickness / 2.0 {
+                return scale(self.hair, 0.9);
+            }
+            // Socket shadow, white of the eye, iris and pupil
+            let eye = ellipse(ex, ey, self.eye_size * 1.5, self.eye_size * 0.75);
+            if eye < 1.0 {
+                let iris = (ex * ex + ey * ey).sqrt();
+                return if iris < self.eye_size * 0.3 {
+                    [15.0, 12.0, 12.0]
+                } else if iris < self.eye_size * 0.65 {
+                    self.iris
+                } else {
+                    [235.0, 232.0, 228.0]
+                };
+            }
+            if ellipse(ex, ey, self.eye_size * 2.0, self.eye_size * 1.3) < 1.0 {
+                skin = scale(skin, 0.82);
+            }
+        }
+
+        // Nose: a shadow down the side away from the light, then nostrils
+        let nose_top = eye_y + self.eye_size;
+        let nose_bottom = nose_top + self.nose_length;
+        if y > nose_top && y < nose_bottom {
+            let side = if v.light > 0.0 { -1.0 } else { 1.0 };
+            let width = 3.0 + (y - nose_top) / self.nose_length * 6.0;
+            if (x - side * width).abs() < 2.0 {
+                skin = scale(skin, 0.7);
+            }
+        }
+        for side in [-1.0, 1.0] {
+            if ellipse(x - side * 6.0, y - nose_bottom, 3.5, 2.0) < 1.0 {
+                return scale(self.skin, 0.35);
+            }
+        }
+
+        // Mouth, curving up with the smile
+        let mouth_y = nose_bottom + ry * 0.22;
+        let curve = -(x / self.mouth_width).powi(2) * 4.0 * v.smile;
+        let lips = ellipse(x, y - mouth_y - curve, self.mouth_width, 5.0);
+        if lips < 1.0 {
+            return if (y - mouth_y - curve).abs() < 1.0 { [90.0, 30.0, 35.0] } else { mix(skin, [170.0, 70.0, 75.0], 0.7) };
+        }
+        skin
+    }
+}
+
+/// Squared normalized distance from the middle of an ellipse, below 1.0 inside
+fn ellipse(x: f64, y: f64, rx: f64, ry: f64) -> f64 {
+    (x / rx).powi(2) + (y / ry).powi(2)
+}
+
+fn mix(a: Rgb, b: Rgb, t: f64) -> Rgb {
+    [0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * t)
+}
+
+fn scale(color: Rgb, factor: f64) -> Rgb {
+    color.map(|c| c * factor)
+}
+
+/// Encode top-down RGB pixels as a 24-bit BMP
+fn bmp(pixels: &[Rgb]) -> Vec<u8> {
+    let row = (WIDTH * 3).next_multiple_of(4);
+    let size = 54 + row * HEIGHT;
+    let mut out = Vec::with_capacity(size);
+    out.extend_from_slice(b"BM");
+    out.extend_from_slice(&(size as u32).to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&54u32.to_le_bytes());
+    out.extend_from_slice(&40u32.to_le_bytes());
+    out.extend_from_slice(&(WIDTH as i32).to_le_bytes());
+    out.extend_from_slice(&(HEIGHT as i32).to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&24u16.to_le_bytes());
+    out.extend_from_slice(&[0; 24]);
+    // Rows are stored bottom-up, pixels as BGR
+    for y in (0..HEIGHT).rev() {
+        for pixel in &pixels[y * WIDTH..(y + 1) * WIDTH] {
+            out.extend(pixel.iter().rev().map(|c| c.round().clamp(0.0, 255.0) as u8));
+        }
+        out.resize(out.len() + row - WIDTH * 3, 0);
+    }
+    out
+}
+
+/// Photo to authenticate and the user it should match, `None` for a stranger
+#[derive(Debug, Clone, PartialEq)]
+pub struct DemoProbe {
+    pub image: PathBuf,
+    pub expected: Option<String>,
+}
+
+/// Photos [`run`] uses: enrollment photos per user, then the probes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DemoImages {
+    pub enrollments: Vec<(String, Vec<PathBuf>)>,
+    pub probes: Vec<DemoProbe>,
+}
+
+impl DemoImages {
+    /// Render the photos of [`PEOPLE`] into `dir`, e.g. `alex_1.bmp`
+    pub fn generate(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        let write = |person: &DemoPerson, index: u32| -> Result<PathBuf> {
+            let path = dir.join(format!("{}_{}.bmp", person.name, index));
+            fs::write(&path, person.render(index))?;
+            Ok(path)
+        };
+        let mut images = Self::default();
+        for person in &PEOPLE {
+            if person.enrolled {
+                let photos = (1..=ENROLLMENT_PHOTOS).map(|i| write(person, i)).collect::<Result<_>>()?;
+                images.enrollments.push((person.name.to_string(), photos));
+            }
+            images.probes.push(DemoProbe {
+                image: write(person, ENROLLMENT_PHOTOS + 1)?,
+                expected: person.enrolled.then(|| person.name.to_string()),
+            });
+        }
+        Ok(images)
+    }
+}
+
+/// One step of the walkthrough
+#[derive(Debug, Clone)]
+pub enum DemoStep {
+    Enrolled { user: String, photos: usize, usable: u32 },
+    Authenticated { probe: DemoProbe, result: Box<FaceAuthResult> },
+}
+
+impl DemoStep {
+    /// Whether the step went as a working installation would, given a
+    /// backend that detects the drawn faces, see the [module docs](self)
+    pub fn passed(&self) -> bool {
+        match self {
+            DemoStep::Enrolled { photos, usable, .. } => *usable as usize == *photos,
+            DemoStep::Authenticated { probe, result } => match &probe.expected {
+                Some(user) => result.is_authenticated && result.user_id.as_ref() == Some(user),
+                None => !result.is_authenticated,
+            },
+        }
+    }
+}
+
+/// Enroll the users of `images` and authenticate every probe at `tolerance`
+///
+/// `auth` should be built with `auto_promote` on a data directory of its own:
+/// the demo users are enrolled like real ones. Steps are passed to `on_step`
+/// as they complete.
+pub async fn run(auth: &FaceAuth, images: &DemoImages, tolerance: f64, mut on_step: impl FnMut(&DemoStep)) -> Result<Vec<DemoStep>> {
+    let mut steps = Vec::new();
+    let generated_dir = auth.generated_dir().display().to_string();
+    for (user, photos) in &images.enrollments {
+        let photos: Vec<Vec<u8>> = photos.iter().map(fs::read).collect::<Result<_, _>>()?;
+        let pending = auth.submit_enrollment(user, &photos, Some("demo".to_string()), UserMetadata::default()).await?;
+        // No face in the drawings is a failed step, not a failed demo
+        let review = auth.review_enrollment(&pending.id, tolerance).await?;
+        let usable = if review.usable_photos == 0 {
+            auth.reject_enrollment(&pending.id).await?;
+            0
+        } else {
+            auth.approve_enrollment(&pending.id, tolerance, &generated_dir).await
+                .with_context(|| format!("Failed to enroll demo user '{}'", user))?
+                .samples_captured
+        };
+        steps.push(DemoStep::Enrolled { user: user.clone(), photos: photos.len(), usable });
+        on_step(steps.last().expect("just pushed"));
+    }
+    for probe in &images.probes {
+        let result = auth.authenticate(&AuthRequest::file(tolerance, &probe.image)).await?;
+        steps.push(DemoStep::Authenticated { probe: probe.clone(), result: Box::new(result) });
+        on_step(steps.last().expect("just pushed"));
+    }
+    Ok(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_photos_are_valid_bmps_that_differ() {
+        let photo = PEOPLE[0].render(1);
+        assert_eq!(&photo[..2], b"BM");
+        assert_eq!(u32::from_le_bytes(photo[2..6].try_into().unwrap()) as usize, photo.len());
+        assert_eq!(photo.len(), 54 + WIDTH * 3 * HEIGHT);
+        assert_eq!(photo, PEOPLE[0].render(1));
+        assert_ne!(photo, PEOPLE[0].render(2));
+        assert_ne!(photo, PEOPLE[1].render(1));
+    }
+
+    #[test]
+    fn test_generate_holds_out_a_probe_per_person() {
+        let dir = tempfile::tempdir().unwrap();
+        let images = DemoImages::generate(dir.path()).unwrap();
+        assert_eq!(images.enrollments.len(), 2);
+        assert!(images.enrollments.iter().all(|(_, photos)| photos.len() == ENROLLMENT_PHOTOS as usize));
+        let expected: Vec<_> = images.probes.iter().map(|p| p.expected.as_deref()).collect();
+        assert_eq!(expected, [Some("alex"), Some("sam"), None]);
+        assert!(dir.path().join("robin_4.bmp").exists());
+    }
+}
@@ -0,0 +1,114 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::device::DeviceOperation;
+use crate::messages;
+
+/// Why an authentication was denied
+///
+/// Serialized with a `code` tag (e.g. `{"code": "below_threshold", "distance": 0.71, "threshold": 0.6}`)
+/// so kiosk UIs can show specific guidance; [`fmt::Display`] gives the English
+/// message, and [`MessageCatalog::denial`](crate::messages::MessageCatalog::denial)
+/// a localized one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum DenialReason {
+    /// No face was found in the frame
+    NoFace,
+    /// Nobody is enrolled in the source directory yet, so there was no one to match
+    NoUsersEnrolled,
+    /// The face wasn't verified as the user claimed for 1:1 verification,
+    /// e.g. by their badge; also when that user isn't enrolled, which isn't
+    /// revealed
+    ClaimNotVerified,
+    /// The closest enrolled user was too far from the probe
+    BelowThreshold { distance: f64, threshold: f64 },
+    /// The liveness check judged the frame to be a spoof
+    LivenessFailed,
+    /// Too many failed attempts; retry after `until` if known
+    LockedOut {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        until: Option<DateTime<Utc>>,
+    },
+    /// The user is not allowed in at this time
+    OutsideSchedule,
+    /// The face matched a watchlist entry
+    WatchlistHit { entry: String },
+    /// The frame was too blurry, dark or small to match reliably (scores 0.0-1.0)
+    QualityTooLow { score: f64, minimum: f64 },
+    /// The face decision was only recorded, see `EnforcementMode::Shadow`
+    ShadowMode,
+    /// Another enrolled user was almost as close as the best match, e.g. a twin;
+    /// see `AmbiguityPolicy`
+    Ambiguous { distance: f64, runner_up_distance: f64 },
+    /// A plugin hook refused the attempt, see `Hook`
+    HookDenied { reason: String },
+    /// The decision policy refused the match, see `DecisionPolicy`
+    PolicyDenied { reason: String },
+    /// The decision policy wants a second factor before granting access
+    StepUpRequired { reason: String },
+    /// The uploaded image was taken longer ago than the freshness window allows
+    ImageTooOld { captured_at: DateTime<Utc> },
+    /// A manipulation detector judged the image to be a deepfake or otherwise
+    /// altered (scores 0.0-1.0)
+    ManipulationSuspected { score: f64, maximum: f64 },
+    /// The platform's biometric prompt, used because face matching was
+    /// unavailable, didn't verify the user
+    PlatformRefused { platform: String },
+    /// The camera was in use by another capture, expected to be done at `eta`
+    /// if known; see `DeviceContention`
+    DeviceBusy {
+        operation: DeviceOperation,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        eta: Option<DateTime<Utc>>,
+    },
+}
+
+impl DenialReason {
+    /// The serialized `code`, e.g. `no_face`
+    pub fn code(&self) -> &'static str {
+        match self {
+            DenialReason::NoFace => "no_face",
+            DenialReason::NoUsersEnrolled => "no_users_enrolled",
+            DenialReason::ClaimNotVerified => "claim_not_verified",
+            DenialReason::BelowThreshold { .. } => "below_threshold",
+            DenialReason::LivenessFailed => "liveness_failed",
+            DenialReason::LockedOut { .. } => "locked_out",
+            DenialReason::OutsideSchedule => "outside_schedule",
+            DenialReason::WatchlistHit { .. } => "watchlist_hit",
+            DenialReason::QualityTooLow { .. } => "quality_too_low",
+            DenialReason::ShadowMode => "shadow_mode",
+            DenialReason::Ambiguous { .. } => "ambiguous",
+            DenialReason::HookDenied { .. } => "hook_denied",
+            DenialReason::PolicyDenied { .. } => "policy_denied",
+            DenialReason::StepUpRequired { .. } => "step_up_required",
+            DenialReason::ImageTooOld { .. } => "image_too_old",
+            DenialReason::ManipulationSuspected { .. } => "manipulation_suspected",
+            DenialReason::PlatformRefused { .. } => "platform_refused",
+            DenialReason::DeviceBusy { .. } => "device_busy",
+        }
+    }
+}
+
+impl fmt::Display for DenialReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&messages::english().denial(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reasons_serialize_with_code_tag() {
+        let reason = DenialReason::BelowThreshold { distance: 0.71, threshold: 0.6 };
+        let json = serde_json::to_value(&reason).unwrap();
+        assert_eq!(json, serde_json::json!({"code": "below_threshold", "distance": 0.71, "threshold": 0.6}));
+        assert_eq!(json["code"], reason.code());
+        assert_eq!(serde_json::from_str::<DenialReason>(r#"{"code": "no_face"}"#).unwrap(), DenialReason::NoFace);
+        assert_eq!(serde_json::to_value(DenialReason::LockedOut { until: None }).unwrap(), serde_json::json!({"code": "locked_out"}));
+    }
+}
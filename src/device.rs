@@ -0,0 +1,333 @@
+//! One capture at a time per camera device
+//!
+//! Registration and authentication both capture from the camera, and two at
+//! once would have two Python processes fighting over the webcam. Captures
+//! therefore take turns: each holds a lease on the device until it's done.
+//! Within a [`FaceAuth`](crate::FaceAuth) instance and its clones the lease is
+//! kept in memory; other instances and processes sharing the data directory
+//! are kept out by an OS lock on
+//! [`StorageLayout::camera_lock_path`](crate::StorageLayout::camera_lock_path).
+//! An attempt while another holds the camera is refused with what is using it
+//! and when it should be free, or waits for it without blocking the async
+//! executor, see [`DeviceContention`]. The estimate comes from how long
+//! earlier operations of the same kind took.
+
+#[cfg(feature = "python-backend")]
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "python-backend")]
+use std::collections::HashMap;
+use std::fmt;
+#[cfg(feature = "python-backend")]
+use std::fs::{self, File, OpenOptions, TryLockError};
+#[cfg(feature = "python-backend")]
+use std::future::Future;
+#[cfg(feature = "python-backend")]
+use std::path::PathBuf;
+#[cfg(feature = "python-backend")]
+use std::pin::Pin;
+#[cfg(feature = "python-backend")]
+use std::sync::{Mutex, MutexGuard, PoisonError};
+#[cfg(feature = "python-backend")]
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+#[cfg(feature = "python-backend")]
+use std::time::Instant;
+
+/// How often a queued capture checks whether another process released the
+/// camera
+#[cfg(feature = "python-backend")]
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What is using the camera
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum DeviceOperation {
+    /// Registration, re-enrollment or a kiosk enrollment sample
+    Enrollment,
+    Authentication,
+    /// [`FaceAuth::watch`](crate::FaceAuth::watch) or
+    /// [`FaceAuth::track_faces`](crate::FaceAuth::track_faces), until stopped
+    Watch,
+}
+
+impl fmt::Display for DeviceOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DeviceOperation::Enrollment => "enrollment",
+            DeviceOperation::Authentication => "authentication",
+            DeviceOperation::Watch => "watch",
+        })
+    }
+}
+
+/// What a capture does when another one holds the camera
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum DeviceContention {
+    /// Refuse right away
+    #[default]
+    Reject,
+    /// Wait up to this long for the camera, then refuse
+    Queue(Duration),
+}
+
+/// The camera was in use by `operation`, expected to be done at `eta`
+#[cfg(feature = "python-backend")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Busy {
+    pub(crate) operation: DeviceOperation,
+    pub(crate) eta: Option<DateTime<Utc>>,
+}
+
+#[cfg(feature = "python-backend")]
+#[derive(Debug)]
+struct Holder {
+    operation: DeviceOperation,
+    started: Instant,
+    /// Whether the lock file is locked, so other processes wait too
+    os_locked: bool,
+}
+
+/// What holds the camera, written next to the lock file for other processes
+#[cfg(feature = "python-backend")]
+#[derive(Debug, Serialize, Deserialize)]
+struct LockRecord {
+    operation: DeviceOperation,
+    started: DateTime<Utc>,
+}
+
+#[cfg(feature = "python-backend")]
+#[derive(Debug, Default)]
+struct State {
+    holder: Option<Holder>,
+    /// Smoothed duration of past operations, by kind
+    typical: HashMap<DeviceOperation, Duration>,
+    /// Opened on the first capture
+    lock_file: Option<File>,
+    /// Bumped on every release, so a waiting capture can tell it missed one
+    releases: u64,
+    /// Captures waiting for a release
+    waiters: Vec<Waker>,
+}
+
+/// Hands the camera to one capture at a time, see the [module docs](self)
+#[cfg(feature = "python-backend")]
+#[derive(Debug, Default)]
+pub(crate) struct DeviceArbiter {
+    contention: DeviceContention,
+    /// Lock file shared with other processes, `None` to arbitrate in memory only
+    lock_path: Option<PathBuf>,
+    state: Mutex<State>,
+}
+
+#[cfg(feature = "python-backend")]
+impl DeviceArbiter {
+    pub(crate) fn new(contention: DeviceContention, lock_path: Option<PathBuf>) -> Self {
+        Self { contention, lock_path, ..Self::default() }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Take the camera for `operation` until the lease is dropped
+    ///
+    /// With [`DeviceContention::Queue`] the capture waits for a release in
+    /// this process, and checks every [`POLL_INTERVAL`] for one in another.
+    pub(crate) async fn acquire(&self, operation: DeviceOperation) -> Result<DeviceLease<'_>, Busy> {
+        let max_wait = match self.contention {
+            DeviceContention::Reject => Duration::ZERO,
+            DeviceContention::Queue(max_wait) => max_wait,
+        };
+        let deadline = Instant::now() + max_wait;
+        loop {
+            let releases = {
+                let mut state = self.state();
+                match self.try_acquire(&mut state, operation) {
+                    Ok(()) => return Ok(DeviceLease { arbiter: self }),
+                    Err(busy) if Instant::now() >= deadline => return Err(busy),
+                    Err(_) => state.releases,
+                }
+            };
+            let until = deadline.min(Instant::now() + POLL_INTERVAL);
+            Released { arbiter: self, releases, until, timer_started: false }.await;
+        }
+    }
+
+    fn try_acquire(&self, state: &mut State, operation: DeviceOperation) -> Result<(), Busy> {
+        if let Some(holder) = &state.holder {
+            // An operation running over its usual time has no estimate left
+            let remaining = state.typical.get(&holder.operation).and_then(|typical| typical.checked_sub(holder.started.elapsed()));
+            let eta = remaining.and_then(|remaining| chrono::Duration::from_std(remaining).ok()).map(|remaining| Utc::now() + remaining);
+            return Err(Busy { operation: holder.operation, eta });
+        }
+
+        let mut os_locked = false;
+        if let Some(path) = &self.lock_path {
+            if state.lock_file.is_none() {
+                match OpenOptions::new().create(true).truncate(false).write(true).open(path) {
+                    Ok(file) => state.lock_file = Some(file),
+                    Err(e) => tracing::debug!("Can't open {}, arbitrating the camera in this process only: {}", path.display(), e),
+                }
+            }
+            if let Some(file) = &state.lock_file {
+                match file.try_lock() {
+                    Ok(()) => os_locked = true,
+                    Err(TryLockError::WouldBlock) => return Err(self.held_elsewhere(state)),
+                    Err(TryLockError::Error(e)) => tracing::debug!("Can't lock {}: {}", path.display(), e),
+                }
+            }
+            if os_locked {
+                let record = LockRecord { operation, started: Utc::now() };
+                if let Err(e) = serde_json::to_vec(&record).map_err(Into::into).and_then(|json| crate::face_storage::write_atomic(&path.with_extension("json"), &json)) {
+                    tracing::debug!("Can't record the camera's holder: {:#}", e);
+                }
+            }
+        }
+        state.holder = Some(Holder { operation, started: Instant::now(), os_locked });
+        Ok(())
+    }
+
+    /// Busy with what another process recorded holding the camera for
+    fn held_elsewhere(&self, state: &State) -> Busy {
+        let record = self.lock_path.as_ref()
+            .and_then(|path| fs::read(path.with_extension("json")).ok())
+            .and_then(|json| serde_json::from_slice::<LockRecord>(&json).ok());
+        let Some(record) = record else {
+            // Locked but not yet recorded, so it has only just started; most
+            // captures are authentications
+            return Busy { operation: DeviceOperation::Authentication, eta: None };
+        };
+        let eta = state.typical.get(&record.operation)
+            .and_then(|typical| chrono::Duration::from_std(*typical).ok())
+            .map(|typical| record.started + typical)
+            .filter(|eta| *eta > Utc::now());
+        Busy { operation: record.operation, eta }
+    }
+}
+
+/// Ready once the camera was released after `releases` or at `until`,
+/// whichever comes first
+///
+/// A release in this process wakes the task; otherwise a timer thread does,
+/// so the executor's thread is never blocked while waiting.
+#[cfg(feature = "python-backend")]
+struct Released<'a> {
+    arbiter: &'a DeviceArbiter,
+    releases: u64,
+    until: Instant,
+    timer_started: bool,
+}
+
+#[cfg(feature = "python-backend")]
+impl Future for Released<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.arbiter.state();
+        if state.releases != self.releases || Instant::now() >= self.until {
+            return Poll::Ready(());
+        }
+        if !state.waiters.iter().any(|waiter| waiter.will_wake(cx.waker())) {
+            state.waiters.push(cx.waker().clone());
+        }
+        drop(state);
+        if !self.timer_started {
+            self.timer_started = true;
+            let (waker, until) = (cx.waker().clone(), self.until);
+            std::thread::spawn(move || {
+                std::thread::sleep(until.saturating_duration_since(Instant::now()));
+                waker.wake();
+            });
+        }
+        Poll::Pending
+    }
+}
+
+/// The camera, held until dropped
+#[cfg(feature = "python-backend")]
+#[derive(Debug)]
+pub(crate) struct DeviceLease<'a> {
+    arbiter: &'a DeviceArbiter,
+}
+
+#[cfg(feature = "python-backend")]
+impl Drop for DeviceLease<'_> {
+    fn drop(&mut self) {
+        let mut state = self.arbiter.state();
+        if let Some(holder) = state.holder.take() {
+            let took = holder.started.elapsed();
+            let typical = state.typical.get(&holder.operation).map_or(took, |typical| (*typical * 3 + took) / 4);
+            state.typical.insert(holder.operation, typical);
+            if let Some(file) = state.lock_file.as_ref().filter(|_| holder.os_locked) {
+                if let Err(e) = file.unlock() {
+                    tracing::warn!("Failed to unlock the camera: {}", e);
+                }
+            }
+        }
+        state.releases += 1;
+        state.waiters.drain(..).for_each(Waker::wake);
+    }
+}
+
+#[cfg(all(test, feature = "python-backend"))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_second_capture_is_refused_with_an_estimate() {
+        let arbiter = DeviceArbiter::new(DeviceContention::Reject, None);
+        let lease = arbiter.acquire(DeviceOperation::Enrollment).await.unwrap();
+        assert_eq!(arbiter.acquire(DeviceOperation::Authentication).await.unwrap_err(), Busy { operation: DeviceOperation::Enrollment, eta: None });
+        drop(lease);
+
+        arbiter.state().typical.insert(DeviceOperation::Enrollment, Duration::from_secs(60));
+        let _lease = arbiter.acquire(DeviceOperation::Enrollment).await.unwrap();
+        let busy = arbiter.acquire(DeviceOperation::Authentication).await.unwrap_err();
+        let eta = busy.eta.expect("estimated from the typical enrollment");
+        assert!(eta > Utc::now() + chrono::Duration::seconds(55) && eta <= Utc::now() + chrono::Duration::seconds(60));
+    }
+
+    #[tokio::test]
+    async fn test_queued_capture_waits_for_the_device() {
+        // A single-threaded runtime: a blocking wait would never see the release
+        let arbiter = DeviceArbiter::new(DeviceContention::Queue(Duration::from_secs(10)), None);
+        let lease = arbiter.acquire(DeviceOperation::Enrollment).await.unwrap();
+        let release = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(lease);
+        };
+        let (waited, ()) = tokio::join!(arbiter.acquire(DeviceOperation::Authentication), release);
+        assert!(waited.is_ok());
+        drop(waited);
+        assert!(arbiter.state().typical.contains_key(&DeviceOperation::Enrollment));
+
+        let impatient = DeviceArbiter::new(DeviceContention::Queue(Duration::from_millis(20)), None);
+        let _lease = impatient.acquire(DeviceOperation::Watch).await.unwrap();
+        assert_eq!(impatient.acquire(DeviceOperation::Enrollment).await.unwrap_err().operation, DeviceOperation::Watch);
+    }
+
+    #[tokio::test]
+    async fn test_instances_sharing_a_lock_file_take_turns() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock = dir.path().join(".camera.lock");
+        let first = DeviceArbiter::new(DeviceContention::Reject, Some(lock.clone()));
+        let second = DeviceArbiter::new(DeviceContention::Reject, Some(lock.clone()));
+
+        let lease = first.acquire(DeviceOperation::Enrollment).await.unwrap();
+        assert_eq!(second.acquire(DeviceOperation::Authentication).await.unwrap_err().operation, DeviceOperation::Enrollment);
+        drop(lease);
+        let lease = second.acquire(DeviceOperation::Authentication).await.unwrap();
+
+        // Released by the other instance, noticed by polling
+        let queued = DeviceArbiter::new(DeviceContention::Queue(Duration::from_secs(10)), Some(lock));
+        let release = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(lease);
+        };
+        let (waited, ()) = tokio::join!(queued.acquire(DeviceOperation::Watch), release);
+        assert!(waited.is_ok());
+    }
+}
@@ -0,0 +1,484 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::face_storage::{self, UserProfile, ENCODING_DIMENSIONS};
+use crate::matching::{self, MatchCandidate};
+
+/// Numeric representation of embeddings held in an [`EmbeddingIndex`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmbeddingPrecision {
+    /// Half the memory of the stored `f64` values, no measurable accuracy loss
+    #[default]
+    F32,
+    /// One byte per dimension plus a scale per sample
+    Int8,
+}
+
+#[derive(Debug, Clone)]
+enum Vectors {
+    F32(Vec<f32>),
+    /// Symmetric per-sample quantization: value = q * scale
+    Int8 { values: Vec<i8>, scales: Vec<f32> },
+}
+
+/// All samples of all users in one contiguous buffer, for matching large databases
+///
+/// Credential files keep `f64` encodings for the Python script; the index is
+/// built from them on load and never written back.
+///
+/// The index also keeps the centroid of every user's samples. With
+/// [`EmbeddingIndex::with_prefilter`], a probe is first compared with the
+/// centroids only, and just the closest users' samples are scored exactly,
+/// which divides the cost of matching by about the number of samples per user.
+#[derive(Debug, Clone)]
+pub struct EmbeddingIndex {
+    dimensions: usize,
+    user_ids: Vec<String>,
+    user_slots: HashMap<String, u32>,
+    /// Index into `user_ids` for every sample
+    owners: Vec<u32>,
+    vectors: Vectors,
+    /// Mean of each user's samples, `dimensions` values per user
+    centroids: Vec<f32>,
+    /// Samples per user, to update the centroids
+    sample_counts: Vec<u32>,
+    /// Users scored exactly after the centroid pass, `None` to score everyone
+    shortlist: Option<usize>,
+}
+
+impl EmbeddingIndex {
+    pub fn new(dimensions: usize, precision: EmbeddingPrecision) -> Self {
+        let vectors = match precision {
+            EmbeddingPrecision::F32 => Vectors::F32(Vec::new()),
+            EmbeddingPrecision::Int8 => Vectors::Int8 { values: Vec::new(), scales: Vec::new() },
+        };
+        Self {
+            dimensions,
+            user_ids: Vec::new(),
+            user_slots: HashMap::new(),
+            owners: Vec::new(),
+            vectors,
+            centroids: Vec::new(),
+            sample_counts: Vec::new(),
+            shortlist: None,
+        }
+    }
+
+    /// Score only the `shortlist` users whose centroids are closest to the
+    /// probe
+    ///
+    /// A user whose samples are spread widely can have one sample closer than
+    /// their centroid suggests, so keep the shortlist well above one, e.g. 20;
+    /// databases with at most `shortlist` users are always scored in full.
+    pub fn with_prefilter(mut self, shortlist: usize) -> Self {
+        self.shortlist = Some(shortlist.max(1));
+        self
+    }
+
+    /// Convert the `f64` samples of `profiles`
+    ///
+    /// Quarantined samples and those whose dimensions differ from the first
+    /// sample are skipped.
+    pub fn from_profiles<'a>(profiles: impl IntoIterator<Item = &'a UserProfile>, precision: EmbeddingPrecision) -> Self {
+        let profiles: Vec<&UserProfile> = profiles.into_iter().collect();
+        let dimensions = profiles.iter()
+            .flat_map(|p| p.face_encodings.first())
+            .map(|s| s.encoding.len())
+            .next()
+            .unwrap_or(ENCODING_DIMENSIONS);
+
+        let mut index = Self::new(dimensions, precision);
+        for profile in profiles {
+            index.push_profile(profile);
+        }
+        index
+    }
+
+    fn push_profile(&mut self, profile: &UserProfile) {
+        for sample in profile.face_encodings.iter().filter(|s| !s.is_quarantined()) {
+            if let Err(e) = self.push(&profile.user_id, &sample.encoding) {
+                tracing::warn!("Skipping sample {}: {}", sample.sample_id, e);
+            }
+        }
+    }
+
+    /// Replace a user's samples after their enrollment changed, e.g. a sample
+    /// was added, removed or quarantined
+    pub fn update_user(&mut self, profile: &UserProfile) {
+        self.remove_user(&profile.user_id);
+        self.push_profile(profile);
+    }
+
+    /// Drop every sample of `user_id`; `false` if the user had none
+    pub fn remove_user(&mut self, user_id: &str) -> bool {
+        let Some(slot) = self.user_slots.remove(user_id) else {
+            return false;
+        };
+        let dimensions = self.dimensions;
+        let keep: Vec<bool> = self.owners.iter().map(|&owner| owner != slot).collect();
+        match &mut self.vectors {
+            Vectors::F32(values) => retain_chunks(values, dimensions, &keep),
+            Vectors::Int8 { values, scales } => {
+                retain_chunks(values, dimensions, &keep);
+                retain_chunks(scales, 1, &keep);
+            }
+        }
+        self.owners.retain(|&owner| owner != slot);
+        self.owners.iter_mut().filter(|owner| **owner > slot).for_each(|owner| *owner -= 1);
+
+        let slot = slot as usize;
+        self.user_ids.remove(slot);
+        self.sample_counts.remove(slot);
+        self.centroids.drain(slot * dimensions..(slot + 1) * dimensions);
+        self.user_slots.values_mut().filter(|other| **other as usize > slot).for_each(|other| *other -= 1);
+        true
+    }
+
+    /// Add one sample of `user_id`
+    pub fn push(&mut self, user_id: &str, encoding: &[f64]) -> Result<()> {
+        if encoding.len() != self.dimensions {
+            return Err(anyhow!("Encoding has {} dimensions, index has {}", encoding.len(), self.dimensions));
+        }
+
+        let slot = match self.user_slots.get(user_id) {
+            Some(&slot) => slot,
+            None => {
+                let slot = self.user_ids.len() as u32;
+                self.user_ids.push(user_id.to_string());
+                self.user_slots.insert(user_id.to_string(), slot);
+                self.centroids.resize(self.centroids.len() + self.dimensions, 0.0);
+                self.sample_counts.push(0);
+                slot
+            }
+        };
+        self.owners.push(slot);
+
+        // Running mean, so adding a sample doesn't revisit the others
+        let count = &mut self.sample_counts[slot as usize];
+        *count += 1;
+        let weight = 1.0 / *count as f32;
+        let centroid = &mut self.centroids[slot as usize * self.dimensions..(slot as usize + 1) * self.dimensions];
+        centroid.iter_mut().zip(encoding).for_each(|(c, &x)| *c += (x as f32 - *c) * weight);
+
+        match &mut self.vectors {
+            Vectors::F32(values) => values.extend(encoding.iter().map(|&x| x as f32)),
+            Vectors::Int8 { values, scales } => {
+                let max = encoding.iter().fold(0.0f64, |m, x| m.max(x.abs()));
+                let scale = if max > 0.0 { max / 127.0 } else { 1.0 };
+                values.extend(encoding.iter().map(|&x| (x / scale).round() as i8));
+                scales.push(scale as f32);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    pub fn precision(&self) -> EmbeddingPrecision {
+        match self.vectors {
+            Vectors::F32(_) => EmbeddingPrecision::F32,
+            Vectors::Int8 { .. } => EmbeddingPrecision::Int8,
+        }
+    }
+
+    /// Number of samples
+    pub fn len(&self) -> usize {
+        self.owners.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.owners.is_empty()
+    }
+
+    pub fn user_count(&self) -> usize {
+        self.user_ids.len()
+    }
+
+    /// Bytes used by the embedding values, not counting the centroids
+    pub fn memory_bytes(&self) -> usize {
+        match &self.vectors {
+            Vectors::F32(values) => values.len() * size_of::<f32>(),
+            Vectors::Int8 { values, scales } => values.len() + scales.len() * size_of::<f32>(),
+        }
+    }
+
+    /// Mean of the samples of `user_id`
+    pub fn centroid(&self, user_id: &str) -> Option<Vec<f64>> {
+        let slot = *self.user_slots.get(user_id)? as usize;
+        Some(self.centroids[slot * self.dimensions..(slot + 1) * self.dimensions].iter().map(|&c| c.into()).collect())
+    }
+
+    /// Which users take part in exact scoring, `None` for all of them
+    fn shortlisted(&self, probe: &[f32]) -> Option<Vec<bool>> {
+        let shortlist = self.shortlist.filter(|&shortlist| shortlist < self.user_count())?;
+        let mut distances: Vec<(usize, f32)> = self.centroids.chunks_exact(self.dimensions)
+            .map(|centroid| matching::squared_euclidean_f32(centroid, probe))
+            .enumerate()
+            .collect();
+        distances.select_nth_unstable_by(shortlist - 1, |a, b| a.1.total_cmp(&b.1));
+        let mut shortlisted = vec![false; self.user_count()];
+        for &(slot, _) in &distances[..shortlist] {
+            shortlisted[slot] = true;
+        }
+        Some(shortlisted)
+    }
+
+    /// Squared Euclidean distance between sample `i` and `probe`
+    fn squared_distance(&self, i: usize, probe: &[f32]) -> f32 {
+        let range = i * self.dimensions..(i + 1) * self.dimensions;
+        match &self.vectors {
+            Vectors::F32(values) => matching::squared_euclidean_f32(&values[range], probe),
+            Vectors::Int8 { values, scales } => matching::squared_euclidean_i8(&values[range], scales[i], probe),
+        }
+    }
+
+    /// Closest user to `probe`, like [`crate::matching::best_match`]
+    ///
+    /// With a prefilter, only among the shortlisted users.
+    pub fn find_best_match(&self, probe: &[f64], tolerance: f64) -> Option<MatchCandidate> {
+        if probe.len() != self.dimensions {
+            return None;
+        }
+        let probe: Vec<f32> = probe.iter().map(|&x| x as f32).collect();
+        let shortlisted = self.shortlisted(&probe);
+
+        (0..self.len())
+            .filter(|&i| shortlisted.as_ref().is_none_or(|shortlisted| shortlisted[self.owners[i] as usize]))
+            .map(|i| (i, self.squared_distance(i, &probe)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, squared)| self.candidate(self.owners[i] as usize, squared, tolerance))
+    }
+
+    /// Every user's closest sample to `probe`, closest user first, like
+    /// [`crate::matching::ranked_matches`]
+    ///
+    /// With a prefilter, only the shortlisted users are ranked.
+    pub fn ranked_matches(&self, probe: &[f64], tolerance: f64) -> Vec<MatchCandidate> {
+        if probe.len() != self.dimensions {
+            return Vec::new();
+        }
+        let probe: Vec<f32> = probe.iter().map(|&x| x as f32).collect();
+        let shortlisted = self.shortlisted(&probe);
+
+        let mut closest = vec![f32::INFINITY; self.user_count()];
+        for i in 0..self.len() {
+            let owner = self.owners[i] as usize;
+            if shortlisted.as_ref().is_none_or(|shortlisted| shortlisted[owner]) {
+                closest[owner] = closest[owner].min(self.squared_distance(i, &probe));
+            }
+        }
+        let mut ranked: Vec<MatchCandidate> = closest.into_iter()
+            .enumerate()
+            .filter(|(_, squared)| squared.is_finite())
+            .map(|(slot, squared)| self.candidate(slot, squared, tolerance))
+            .collect();
+        ranked.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        ranked
+    }
+
+    fn candidate(&self, slot: usize, squared: f32, tolerance: f64) -> MatchCandidate {
+        let distance = f64::from(squared.sqrt());
+        MatchCandidate { user_id: self.user_ids[slot].clone(), distance, is_match: distance <= tolerance }
+    }
+}
+
+/// An [`EmbeddingIndex`] over the credential files of a directory, kept in
+/// step with them
+///
+/// [`CredentialIndex::refresh`] compares each file's modification time and
+/// length with those it was indexed at, and only re-reads the files that
+/// changed: an enrolled user is added with [`EmbeddingIndex::update_user`]
+/// and a removed one dropped with [`EmbeddingIndex::remove_user`], whoever
+/// wrote the files.
+#[derive(Debug)]
+pub struct CredentialIndex {
+    dir: PathBuf,
+    index: EmbeddingIndex,
+    /// Stamp of every file when it was indexed, and the user it held if it
+    /// was a valid credential
+    files: HashMap<PathBuf, (FileStamp, Option<String>)>,
+}
+
+type FileStamp = (SystemTime, u64);
+
+impl CredentialIndex {
+    pub fn new(dir: impl Into<PathBuf>, precision: EmbeddingPrecision, prefilter: Option<usize>) -> Self {
+        let mut index = EmbeddingIndex::new(ENCODING_DIMENSIONS, precision);
+        if let Some(shortlist) = prefilter {
+            index = index.with_prefilter(shortlist);
+        }
+        Self { dir: dir.into(), index, files: HashMap::new() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn index(&self) -> &EmbeddingIndex {
+        &self.index
+    }
+
+    /// Users with a valid credential file, including those whose samples are
+    /// all quarantined
+    pub fn profile_count(&self) -> usize {
+        self.files.values().filter(|(_, user)| user.is_some()).count()
+    }
+
+    /// Bring the index up to date with the credential files
+    pub fn refresh(&mut self) -> Result<()> {
+        let mut seen = HashSet::new();
+        for path in face_storage::credential_files(&self.dir)? {
+            let Ok(metadata) = fs::metadata(&path) else { continue };
+            let stamp = (metadata.modified()?, metadata.len());
+            if self.files.get(&path).is_some_and(|(indexed, _)| *indexed == stamp) {
+                seen.insert(path);
+                continue;
+            }
+            if let Some((_, Some(user))) = self.files.remove(&path) {
+                self.index.remove_user(&user);
+            }
+            let user = match UserProfile::load(&path).and_then(|p| p.validate().map(|_| p)) {
+                Ok(profile) => {
+                    self.rebuild_if_first(&profile);
+                    self.index.update_user(&profile);
+                    Some(profile.user_id)
+                }
+                Err(e) => {
+                    tracing::warn!(path = %path.display(), "Skipping credential file: {:#}", e);
+                    None
+                }
+            };
+            self.files.insert(path.clone(), (stamp, user));
+            seen.insert(path);
+        }
+
+        let removed: Vec<PathBuf> = self.files.keys().filter(|path| !seen.contains(*path)).cloned().collect();
+        for path in removed {
+            if let Some((_, Some(user))) = self.files.remove(&path) {
+                self.index.remove_user(&user);
+            }
+        }
+        Ok(())
+    }
+
+    /// Take the dimensions of the first indexed profile, as
+    /// [`EmbeddingIndex::from_profiles`] does
+    fn rebuild_if_first(&mut self, profile: &UserProfile) {
+        let Some(sample) = profile.face_encodings.first() else { return };
+        if self.index.is_empty() && sample.encoding.len() != self.index.dimensions {
+            let shortlist = self.index.shortlist;
+            self.index = EmbeddingIndex::new(sample.encoding.len(), self.index.precision());
+            self.index.shortlist = shortlist;
+        }
+    }
+}
+
+/// Keep the `dimensions`-long chunks of `values` whose entry in `keep` is set
+fn retain_chunks<T: Copy>(values: &mut Vec<T>, dimensions: usize, keep: &[bool]) {
+    let mut write = 0;
+    for (read, _) in keep.iter().enumerate().filter(|(_, keep)| **keep) {
+        values.copy_within(read * dimensions..(read + 1) * dimensions, write * dimensions);
+        write += 1;
+    }
+    values.truncate(write * dimensions);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::{synthetic_encoding as encoding, synthetic_profile};
+
+    #[test]
+    fn test_precisions_agree_and_shrink_memory() {
+        let ann: Vec<f64> = (0..ENCODING_DIMENSIONS).map(|i| (i as f64 * 0.37).sin() * 0.2).collect();
+        let bob: Vec<f64> = (0..ENCODING_DIMENSIONS).map(|i| (i as f64 * 0.11).cos() * 0.2).collect();
+        let probe: Vec<f64> = ann.iter().map(|x| x + 0.005).collect();
+
+        for precision in [EmbeddingPrecision::F32, EmbeddingPrecision::Int8] {
+            let mut index = EmbeddingIndex::new(ENCODING_DIMENSIONS, precision);
+            index.push("ann", &ann).unwrap();
+            index.push("bob", &bob).unwrap();
+            index.push("ann", &bob.iter().map(|x| -x).collect::<Vec<_>>()).unwrap();
+            assert!(index.push("bob", &[0.0; 3]).is_err());
+
+            let candidate = index.find_best_match(&probe, 0.6).unwrap();
+            assert_eq!(candidate.user_id, "ann");
+            assert!((candidate.distance - 0.005 * (ENCODING_DIMENSIONS as f64).sqrt()).abs() < 0.01);
+            assert_eq!(index.user_count(), 2);
+            assert!(index.memory_bytes() <= 3 * ENCODING_DIMENSIONS * size_of::<f32>());
+        }
+    }
+
+    #[test]
+    fn test_centroid_prefilter_shortlists_users() {
+        let mut index = EmbeddingIndex::new(ENCODING_DIMENSIONS, EmbeddingPrecision::F32).with_prefilter(2);
+        for user in 0..10 {
+            for sample in 0..3 {
+                index.push(&format!("user{}", user), &encoding(user * 3 + sample)).unwrap();
+            }
+        }
+        let exact = matching::centroid((12..15).map(encoding).collect::<Vec<_>>().iter().map(Vec::as_slice)).unwrap();
+        assert!(matching::face_distance(&index.centroid("user4").unwrap(), &exact) < 1e-4);
+
+        // A probe near one of user4's samples shortlists them and scores exactly
+        let probe: Vec<f64> = encoding(13).iter().map(|x| x + 0.001).collect();
+        let candidate = index.find_best_match(&probe, 0.6).unwrap();
+        assert_eq!(candidate.user_id, "user4");
+        assert!(candidate.distance < 0.02);
+
+        // Re-enrolled with other samples, the user's centroid follows them
+        let mut profile: UserProfile = serde_json::from_value(serde_json::json!({ "user_id": "user4", "face_encodings": [] })).unwrap();
+        profile.face_encodings = serde_json::from_value(serde_json::json!([{ "encoding": encoding(100), "timestamp": "", "sample_id": "user4_new" }])).unwrap();
+        index.update_user(&profile);
+        assert_eq!(index.len(), 28);
+        assert!(matching::face_distance(&index.centroid("user4").unwrap(), &encoding(100)) < 1e-4);
+        assert_eq!(index.find_best_match(&encoding(100), 0.6).unwrap().user_id, "user4");
+        assert!(index.find_best_match(&probe, 0.6).unwrap().distance > 0.02, "old samples are gone");
+
+        assert!(index.remove_user("user0"));
+        assert!(!index.remove_user("user0"));
+        assert_eq!((index.len(), index.user_count()), (25, 9));
+        assert_eq!(index.find_best_match(&encoding(29), 0.6).unwrap().user_id, "user9");
+
+        let ranked = index.ranked_matches(&encoding(29), 0.6);
+        assert_eq!(ranked.len(), 2, "only the shortlist is ranked");
+        assert_eq!(ranked[0], index.find_best_match(&encoding(29), 0.6).unwrap());
+        assert!(ranked[0].distance <= ranked[1].distance);
+    }
+
+    #[test]
+    fn test_credential_index_follows_enrollments_and_removals() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |profile: &UserProfile| {
+            fs::write(face_storage::credential_path(dir.path(), &profile.user_id), serde_json::to_vec(profile).unwrap()).unwrap();
+        };
+        let (ann, bob) = (synthetic_profile(0, 3), synthetic_profile(1, 3));
+        write(&ann);
+        write(&bob);
+        fs::write(dir.path().join("broken.json"), b"{").unwrap();
+
+        let mut credentials = CredentialIndex::new(dir.path(), EmbeddingPrecision::F32, Some(1));
+        credentials.refresh().unwrap();
+        assert_eq!((credentials.profile_count(), credentials.index().len()), (2, 6));
+        let ranked = credentials.index().ranked_matches(&encoding(4), 0.6);
+        assert_eq!(ranked.len(), 1);
+        assert_eq!((ranked[0].user_id.as_str(), ranked[0].distance), (bob.user_id.as_str(), 0.0));
+
+        // Bob re-enrolled with a single other sample, Ann removed
+        let mut reenrolled = synthetic_profile(1, 1);
+        reenrolled.face_encodings[0].encoding = encoding(50);
+        write(&reenrolled);
+        fs::remove_file(face_storage::credential_path(dir.path(), &ann.user_id)).unwrap();
+        credentials.refresh().unwrap();
+        assert_eq!((credentials.profile_count(), credentials.index().len()), (1, 1));
+        assert_eq!(credentials.index().find_best_match(&encoding(50), 0.6).unwrap().distance, 0.0);
+        assert!(credentials.index().centroid(&ann.user_id).is_none());
+    }
+}
@@ -0,0 +1,111 @@
+use chrono::{DateTime, Utc};
+use std::fmt;
+
+use crate::assurance::AssuranceLevel;
+use crate::device::DeviceOperation;
+use crate::denial::DenialReason;
+
+/// Errors raised by the face authentication library
+///
+/// Functions keep returning `anyhow::Result`; these variants can be recovered
+/// with `err.downcast_ref::<FaceAuthError>()` when callers need the details.
+#[derive(Debug)]
+pub enum FaceAuthError {
+    /// The Python script exited unsuccessfully
+    ScriptFailed {
+        operation: &'static str,
+        exit_code: Option<i32>,
+        output_tail: Vec<String>,
+    },
+    /// A cloud face service rejected a request
+    CloudRequestFailed {
+        service: &'static str,
+        status: u16,
+        /// Service error code, e.g. `InvalidParameterException`
+        code: String,
+        message: String,
+    },
+    /// The peer of an enrollment transfer used a different passphrase
+    PassphraseMismatch,
+    /// A mutation was attempted on an instance built with `StorageMode::ReadOnly`
+    ReadOnly { operation: &'static str },
+    /// An operation would store images on an instance built with `PrivacyMode::Strict`
+    PrivacyStrict { operation: &'static str },
+    /// An embedding was produced by a different model than the one enrolled
+    /// templates are matched with
+    IncompatibleModel { expected: String, actual: String },
+    /// The live face did not match the user whose enrollment was to be changed
+    NotVerified { username: String, denial: Option<DenialReason> },
+    /// A session was used for an action needing a higher assurance level
+    InsufficientAssurance { required: AssuranceLevel, actual: AssuranceLevel },
+    /// A signed result or session was altered or not signed with the device's key
+    InvalidSignature { device_id: Option<String> },
+    /// The camera was in use by another capture, expected to be done at `eta`
+    /// if known
+    DeviceBusy { operation: DeviceOperation, eta: Option<DateTime<Utc>> },
+    /// A username was empty, hidden or contained a path separator
+    InvalidUsername { username: String },
+    /// A remote enrollment submission was refused before it was queued
+    InvalidEnrollment { reason: String },
+    /// No enrollment with this id is pending: it was never submitted or has
+    /// been approved or rejected already
+    EnrollmentNotPending { id: String },
+    /// The review of a pending enrollment found a problem, e.g. a taken username
+    EnrollmentReviewFailed { id: String, problem: String },
+}
+
+impl fmt::Display for FaceAuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FaceAuthError::ScriptFailed { operation, exit_code, output_tail } => {
+                match exit_code {
+                    Some(code) => write!(f, "Standalone Python {} failed (exit code {})", operation, code)?,
+                    None => write!(f, "Standalone Python {} was terminated by a signal", operation)?,
+                }
+                if !output_tail.is_empty() {
+                    write!(f, "; last output:")?;
+                    for line in output_tail {
+                        write!(f, "\n  {}", line)?;
+                    }
+                }
+                Ok(())
+            }
+            FaceAuthError::CloudRequestFailed { service, status, code, message } => {
+                write!(f, "{} request failed ({} {}): {}", service, status, code, message)
+            }
+            FaceAuthError::PassphraseMismatch => write!(f, "Transfer passphrase does not match the peer's"),
+            FaceAuthError::ReadOnly { operation } => write!(f, "Cannot {} in read-only mode", operation),
+            FaceAuthError::PrivacyStrict { operation } => write!(f, "Cannot {} in strict privacy mode: images would be stored", operation),
+            FaceAuthError::IncompatibleModel { expected, actual } => {
+                write!(f, "Embedding was computed with '{}', enrolled templates use '{}'", actual, expected)
+            }
+            FaceAuthError::NotVerified { username, denial } => {
+                write!(f, "Live face did not match '{}'", username)?;
+                match denial {
+                    Some(denial) => write!(f, ": {}", denial),
+                    None => Ok(()),
+                }
+            }
+            FaceAuthError::InsufficientAssurance { required, actual } => {
+                write!(f, "Assurance level {} required, the session has {}", required, actual)
+            }
+            FaceAuthError::InvalidSignature { device_id } => match device_id {
+                Some(device_id) => write!(f, "Result signature of device '{}' is invalid", device_id),
+                None => write!(f, "Signature is invalid"),
+            },
+            FaceAuthError::DeviceBusy { operation, eta } => {
+                write!(f, "The camera is busy with {}", operation)?;
+                match eta {
+                    Some(eta) => write!(f, " until about {}", eta.format("%H:%M:%S UTC")),
+                    None => Ok(()),
+                }
+            }
+            FaceAuthError::InvalidUsername { username } => write!(f, "Invalid username: '{}'", username),
+            FaceAuthError::InvalidEnrollment { reason } => write!(f, "{}", reason),
+            FaceAuthError::EnrollmentNotPending { id } => write!(f, "No pending enrollment '{}'", id),
+            FaceAuthError::EnrollmentReviewFailed { id, problem } => write!(f, "Enrollment '{}' failed review: {}", id, problem),
+        }
+    }
+}
+
+impl std::error::Error for FaceAuthError {}
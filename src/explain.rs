@@ -0,0 +1,252 @@
+//! Why an authentication was granted or denied
+//!
+//! [`FaceAuth::explain`](crate::FaceAuth::explain) turns a [`FaceAuthResult`]
+//! into an [`Explanation`]: the distance of each of the closest user's
+//! samples, the threshold and profile, how camera views were fused, and what
+//! would have turned the decision around. It serializes for tooling, and its
+//! `Display` is meant to be pasted into a support ticket:
+//!
+//! ```text
+//! Denied: closest user ann at distance 0.652, above the threshold 0.600
+//! Confidence: 44% (50% at the threshold)
+//! Samples of ann, closest first:
+//!   ann_2  0.652  (decisive)
+//!   ann_1  0.701
+//! To grant: a threshold of at least 0.652, or a face 0.052 closer to ann's closest sample
+//! ```
+
+use serde::Serialize;
+use std::fmt;
+
+use crate::auth::FaceAuthResult;
+use crate::denial::DenialReason;
+use crate::fusion::{CameraScore, FusionStrategy};
+
+/// Confidence under an exponential model of the distance: 1.0 for identical
+/// encodings, 0.5 exactly at the threshold and falling off quickly beyond it
+///
+/// Unlike the linear `1 - distance` of [`FaceAuthResult::confidence`], it
+/// reads the same at every threshold: above 50% was granted.
+pub fn confidence(distance: f64, threshold: f64) -> f64 {
+    if threshold <= 0.0 {
+        return if distance <= 0.0 { 1.0 } else { 0.0 };
+    }
+    (-(distance / threshold).powi(2) * std::f64::consts::LN_2).exp()
+}
+
+/// One enrolled sample of the user the decision is about
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SampleContribution {
+    pub sample_id: String,
+    pub distance: f64,
+    /// Whether this sample alone is within the threshold
+    pub within_threshold: bool,
+    /// Whether the decision used this sample's distance (the closest one)
+    pub decisive: bool,
+}
+
+/// Smallest change that would have reversed a decision
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum DecisionFlip {
+    /// Denied on distance: granted with a threshold of at least `threshold`,
+    /// or a face `margin` closer to the user's closest sample
+    RaiseThreshold { threshold: f64, margin: f64 },
+    /// Granted: denied with any threshold below `threshold`, or a face
+    /// `margin` further from every sample
+    LowerThreshold { threshold: f64, margin: f64 },
+    /// Denied for a reason no distance or threshold changes
+    Resolve { reason: DenialReason },
+}
+
+/// Human- and machine-readable account of a decision, see the [module docs](self)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct Explanation {
+    pub granted: bool,
+    /// The matched user, or the closest one if denied
+    pub user_id: Option<String>,
+    pub distance: Option<f64>,
+    pub threshold: Option<f64>,
+    /// See [`confidence`]
+    pub confidence: Option<f64>,
+    /// Threshold profile that set `threshold`
+    pub profile: Option<String>,
+    /// Samples of `user_id`, closest first; empty if the backend doesn't report them
+    pub samples: Vec<SampleContribution>,
+    /// How camera views were combined, when there were several
+    pub fusion: Option<FusionStrategy>,
+    pub cameras: Vec<CameraScore>,
+    pub denial: Option<DenialReason>,
+    pub flip: Option<DecisionFlip>,
+}
+
+impl Explanation {
+    pub(crate) fn new(result: &FaceAuthResult, fusion: FusionStrategy) -> Self {
+        let threshold = result.threshold;
+        let distance = result.distance;
+        let samples = result.sample_distances.iter().enumerate()
+            .map(|(i, sample)| SampleContribution {
+                sample_id: sample.sample_id.clone(),
+                distance: sample.distance,
+                within_threshold: threshold.is_some_and(|t| sample.distance <= t),
+                // Averaged views decide on a mean, not on one sample
+                decisive: i == 0 && (result.cameras.is_empty() || fusion == FusionStrategy::Best),
+            })
+            .collect();
+
+        let flip = match &result.denial {
+            Some(DenialReason::BelowThreshold { distance, threshold }) => {
+                Some(DecisionFlip::RaiseThreshold { threshold: *distance, margin: distance - threshold })
+            }
+            Some(reason) => Some(DecisionFlip::Resolve { reason: reason.clone() }),
+            None => match (distance, threshold) {
+                (Some(d), Some(t)) if result.is_authenticated => Some(DecisionFlip::LowerThreshold { threshold: d, margin: t - d }),
+                (Some(d), Some(t)) if d > t => Some(DecisionFlip::RaiseThreshold { threshold: d, margin: d - t }),
+                _ => None,
+            },
+        };
+
+        Self {
+            granted: result.is_authenticated,
+            user_id: result.user_id.clone().or_else(|| result.closest_user.clone()),
+            distance,
+            threshold,
+            confidence: distance.zip(threshold).map(|(d, t)| confidence(d, t)),
+            profile: result.profile.clone(),
+            samples,
+            fusion: (!result.cameras.is_empty()).then_some(fusion),
+            cameras: result.cameras.clone(),
+            denial: result.denial.clone(),
+            flip,
+        }
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let user = self.user_id.as_deref().unwrap_or("nobody");
+        match (self.granted, self.distance, self.threshold) {
+            (true, Some(d), Some(t)) => write!(f, "Granted: {} at distance {:.3}, within the threshold {:.3}", user, d, t)?,
+            (false, Some(d), Some(t)) => write!(f, "Denied: closest user {} at distance {:.3}, {} the threshold {:.3}", user, d, if d > t { "above" } else { "within" }, t)?,
+            (true, ..) => write!(f, "Granted: {}", user)?,
+            (false, ..) => write!(f, "Denied: no enrolled face to compare")?,
+        }
+        if let Some(profile) = &self.profile {
+            write!(f, " (profile {})", profile)?;
+        }
+        writeln!(f)?;
+        // A distance denial is already the first line
+        if let Some(denial) = self.denial.as_ref().filter(|d| !matches!(d, DenialReason::BelowThreshold { .. })) {
+            writeln!(f, "Reason: {}", denial)?;
+        }
+        if let Some(confidence) = self.confidence {
+            writeln!(f, "Confidence: {:.0}% (50% at the threshold)", confidence * 100.0)?;
+        }
+        if !self.samples.is_empty() {
+            writeln!(f, "Samples of {}, closest first:", user)?;
+            for sample in &self.samples {
+                writeln!(f, "  {}  {:.3}{}", sample.sample_id, sample.distance, if sample.decisive { "  (decisive)" } else { "" })?;
+            }
+        }
+        if let Some(fusion) = self.fusion {
+            let rule = match fusion {
+                FusionStrategy::Best => "closest view",
+                FusionStrategy::Average => "average of the views agreeing on the user",
+            };
+            writeln!(f, "Cameras fused by {}:", rule)?;
+            for camera in &self.cameras {
+                match (&camera.closest_user, camera.distance) {
+                    (Some(user), Some(distance)) => writeln!(f, "  camera {}: {} at {:.3}", camera.camera, user, distance)?,
+                    _ => writeln!(f, "  camera {}: no face", camera.camera)?,
+                }
+            }
+        }
+        match &self.flip {
+            Some(DecisionFlip::RaiseThreshold { threshold, margin }) => {
+                write!(f, "To grant: a threshold of at least {:.3}, or a face {:.3} closer to {}'s closest sample", threshold, margin, user)
+            }
+            Some(DecisionFlip::LowerThreshold { threshold, margin }) => {
+                write!(f, "To deny: a threshold below {:.3}, or a face {:.3} further from every sample", threshold, margin)
+            }
+            Some(DecisionFlip::Resolve { reason }) => write!(f, "To grant: resolve \"{}\"", reason),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::SampleDistance;
+    use crate::standalone_python::StandaloneAuthResult;
+
+    fn result(distance: f64, threshold: f64) -> FaceAuthResult {
+        let is_match = distance <= threshold;
+        let mut result = FaceAuthResult::from(StandaloneAuthResult {
+            success: true,
+            is_match: Some(is_match),
+            confidence: Some(1.0 - distance),
+            distance: Some(distance),
+            threshold: Some(threshold),
+            matched_user: is_match.then(|| "ann".to_string()),
+            closest_user: Some("ann".to_string()),
+            runner_up: None,
+            sample_distances: vec![
+                SampleDistance { sample_id: "ann_2".into(), distance },
+                SampleDistance { sample_id: "ann_1".into(), distance: distance + 0.05 },
+            ],
+            brightness: None,
+            image_path: None,
+            processing_time_ms: None,
+            timings: Default::default(),
+            liveness: None,
+            denial: None,
+            raw_output: String::new(),
+        });
+        if !is_match {
+            result.denial = Some(DenialReason::BelowThreshold { distance, threshold });
+        }
+        result
+    }
+
+    #[test]
+    fn test_confidence_is_half_at_the_threshold() {
+        assert_eq!(confidence(0.0, 0.6), 1.0);
+        assert!((confidence(0.6, 0.6) - 0.5).abs() < 1e-12);
+        assert!((confidence(1.2, 0.6) - 1.0 / 16.0).abs() < 1e-12);
+        assert!(confidence(0.3, 0.6) > confidence(0.3, 0.4));
+    }
+
+    #[test]
+    fn test_denial_explains_the_threshold_needed() {
+        let explanation = Explanation::new(&result(0.65, 0.6), FusionStrategy::Best);
+        assert!(!explanation.granted);
+        let Some(DecisionFlip::RaiseThreshold { threshold, margin }) = explanation.flip else {
+            panic!("expected a threshold to raise: {:?}", explanation.flip);
+        };
+        assert!((threshold - 0.65).abs() < 1e-12 && (margin - 0.05).abs() < 1e-12);
+        assert!(explanation.samples[0].decisive && !explanation.samples[1].decisive);
+        assert!(!explanation.samples[0].within_threshold);
+
+        let json = serde_json::to_value(&explanation).unwrap();
+        assert_eq!(json["flip"]["change"], "raise_threshold");
+        let text = explanation.to_string();
+        assert!(text.starts_with("Denied: closest user ann at distance 0.650, above the threshold 0.600"));
+        assert!(text.contains("To grant: a threshold of at least 0.650"));
+    }
+
+    #[test]
+    fn test_grant_and_other_denials() {
+        let explanation = Explanation::new(&result(0.4, 0.6), FusionStrategy::Best);
+        assert!(explanation.granted);
+        assert!(matches!(explanation.flip, Some(DecisionFlip::LowerThreshold { threshold, .. }) if threshold == 0.4));
+        assert!(explanation.samples.iter().all(|s| s.within_threshold));
+
+        let mut locked = result(0.4, 0.6);
+        locked.is_authenticated = false;
+        locked.denial = Some(DenialReason::OutsideSchedule);
+        let explanation = Explanation::new(&locked, FusionStrategy::Best);
+        assert_eq!(explanation.flip, Some(DecisionFlip::Resolve { reason: DenialReason::OutsideSchedule }));
+    }
+}
@@ -0,0 +1,1211 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
+
+use crate::audit::AuditLog;
+use crate::compression::{self, Compression};
+use crate::crypto;
+use crate::embeddings::{EmbeddingIndex, EmbeddingPrecision};
+use crate::error::FaceAuthError;
+use crate::lazy_database::LazyDatabase;
+use crate::matching::{self, MatchCandidate};
+use crate::outliers::SampleQuarantine;
+use crate::random::RandomSource;
+use crate::sample_id::{self, SampleIdRewrite};
+use crate::schema;
+use crate::snapshot::{self, DatabaseSnapshot};
+
+/// Length of the face_recognition (dlib) embedding
+pub const ENCODING_DIMENSIONS: usize = 128;
+
+/// Prefix of template versions whose templates are held by a cloud service;
+/// such samples keep the service's face id as `sample_id` and no encoding
+pub const REMOTE_TEMPLATE_PREFIX: &str = "remote:";
+
+/// Template version reported for samples without one: the script's dlib model
+pub const DLIB_TEMPLATE_VERSION: &str = "dlib-128";
+
+/// One stored face sample of a user
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FaceSample {
+    pub encoding: Vec<f64>,
+    pub timestamp: String,
+    #[serde(default)]
+    pub image_path: Option<String>,
+    pub sample_id: String,
+    /// Quality score assigned at capture time (0.0-1.0)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<f64>,
+    /// Model that produced the encoding; `None` is the script's dlib model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_version: Option<String>,
+    /// Set while the sample is held out of matching pending review
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quarantine: Option<SampleQuarantine>,
+}
+
+impl FaceSample {
+    /// Whether the template lives in a cloud service rather than in `encoding`
+    pub fn is_remote(&self) -> bool {
+        self.template_version.as_deref().is_some_and(|v| v.starts_with(REMOTE_TEMPLATE_PREFIX))
+    }
+
+    pub fn is_quarantined(&self) -> bool {
+        self.quarantine.is_some()
+    }
+}
+
+/// Descriptive information about a user, kept apart from the username
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    /// Identifier in an external system (employee id, HR record, ...)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub badge_id: Option<String>,
+    /// Integrator-defined fields
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub custom: HashMap<String, String>,
+}
+
+impl UserMetadata {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Summary of a registered user for listings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserSummary {
+    pub username: String,
+    pub sample_count: usize,
+    pub enrollment_date: Option<String>,
+    pub metadata: UserMetadata,
+}
+
+impl From<&UserProfile> for UserSummary {
+    fn from(profile: &UserProfile) -> Self {
+        Self {
+            username: profile.user_id.clone(),
+            sample_count: profile.face_encodings.len(),
+            enrollment_date: profile.enrollment_date.clone(),
+            metadata: profile.metadata.clone(),
+        }
+    }
+}
+
+/// Details of one stored sample for admin listings; the encoding is left out
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SampleSummary {
+    pub sample_id: String,
+    /// Capture time, if the stored timestamp could be parsed
+    pub captured_at: Option<DateTime<Utc>>,
+    pub quality: Option<f64>,
+    /// `template_version`, or [`DLIB_TEMPLATE_VERSION`] if unset
+    pub model_version: String,
+    pub image_path: Option<String>,
+    pub quarantine: Option<SampleQuarantine>,
+}
+
+impl From<&FaceSample> for SampleSummary {
+    fn from(sample: &FaceSample) -> Self {
+        Self {
+            sample_id: sample.sample_id.clone(),
+            captured_at: parse_python_timestamp(&sample.timestamp),
+            quality: sample.quality,
+            model_version: sample.template_version.clone().unwrap_or_else(|| DLIB_TEMPLATE_VERSION.to_string()),
+            image_path: sample.image_path.clone(),
+            quarantine: sample.quarantine.clone(),
+        }
+    }
+}
+
+/// Per-user credential file as written to `generated/` and loaded from `source/`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserProfile {
+    pub user_id: String,
+    pub face_encodings: Vec<FaceSample>,
+    #[serde(default)]
+    pub enrollment_date: Option<String>,
+    #[serde(default)]
+    pub sample_count: usize,
+    #[serde(default, skip_serializing_if = "UserMetadata::is_empty")]
+    pub metadata: UserMetadata,
+    /// Fields written by newer versions are preserved on rewrite
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl UserProfile {
+    /// Load a credential file
+    ///
+    /// Errors locate the offending value, see [`schema::parse_credential`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        schema::parse_credential(&content)
+            .map_err(|e| anyhow!("Invalid credential file {}: {}", path.display(), e))
+    }
+
+    /// Atomically write the credential file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        write_atomic(path.as_ref(), serde_json::to_string_pretty(self)?.as_bytes())
+    }
+
+    /// Change the owner of the profile, rewriting legacy sample ids derived
+    /// from the old name
+    pub fn rename(&mut self, new_username: &str) {
+        let old_prefix = format!("{}_", self.user_id);
+        for sample in &mut self.face_encodings {
+            if let Some(suffix) = sample.sample_id.strip_prefix(&old_prefix) {
+                sample.sample_id = format!("{}_{}", new_username, suffix);
+            }
+        }
+        self.user_id = new_username.to_string();
+    }
+
+    /// Check that the profile can be used for authentication, see
+    /// [`schema::check_profile`]
+    pub fn validate(&self) -> Result<()> {
+        match schema::check_profile(self).into_iter().next() {
+            Some(issue) => Err(anyhow!("Invalid credential of user '{}': {}", self.user_id, issue)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The Python script's own database (`python_face_database.json` in the data directory)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FaceDatabase {
+    #[serde(default)]
+    pub users: BTreeMap<String, UserProfile>,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub accuracy_threshold: Option<f64>,
+    #[serde(default)]
+    pub created: Option<String>,
+    /// Authentication statistics per user, maintained by the Rust side
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub stats: BTreeMap<String, UserStats>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Totals reported by [`StorageLayout::stats`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DatabaseStats {
+    /// Users in the database or the source directory
+    pub total_users: usize,
+    /// Users with a valid credential in the source directory
+    pub enrolled_users: usize,
+    pub total_samples: usize,
+    /// Successful authentications recorded
+    pub total_authentications: u64,
+    pub average_match_distance: Option<f64>,
+    /// Most recent enrollment or successful authentication
+    pub last_activity: Option<DateTime<Utc>>,
+}
+
+/// Authentication statistics of one user
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UserStats {
+    pub authentication_count: u64,
+    pub last_authentication: Option<DateTime<Utc>>,
+    /// Sum of match distances, for averaging
+    #[serde(default)]
+    pub total_distance: f64,
+}
+
+impl FaceDatabase {
+    /// Load the database, returning `None` if it has not been created yet
+    pub fn load(path: impl AsRef<Path>) -> Result<Option<Self>> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(path)?;
+        Self::from_json(&content)
+            .map(Some)
+            .map_err(|e| anyhow!("Invalid database {}: {}", path.display(), e))
+    }
+
+    /// Parse a database, rejecting user entries filed under another user's name
+    pub fn from_json(json: &str) -> Result<Self> {
+        let db: Self = serde_json::from_str(json)?;
+        if let Some((key, profile)) = db.users.iter().find(|(key, profile)| **key != profile.user_id) {
+            return Err(anyhow!("Entry '{}' holds the profile of '{}'", key, profile.user_id));
+        }
+        Ok(db)
+    }
+
+    /// Atomically write the database
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        write_atomic(path.as_ref(), serde_json::to_string_pretty(self)?.as_bytes())
+    }
+
+    /// Convert every user's samples into a compact matching index
+    pub fn embedding_index(&self, precision: EmbeddingPrecision) -> EmbeddingIndex {
+        EmbeddingIndex::from_profiles(self.users.values(), precision)
+    }
+
+    /// Load only the samples of a database as a matching index
+    ///
+    /// The `f64` encodings are converted while loading and then dropped, so a
+    /// large database costs half (`F32`) or an eighth (`Int8`) of the memory.
+    pub fn load_index(path: impl AsRef<Path>, precision: EmbeddingPrecision) -> Result<Option<EmbeddingIndex>> {
+        Ok(Self::load(path)?.map(|db| db.embedding_index(precision)))
+    }
+
+    /// Open the database without decoding user profiles up front
+    ///
+    /// Suited to verification, which only needs the claimed user's profile;
+    /// up to `cache_capacity` decoded profiles are kept.
+    pub fn open_lazy(path: impl AsRef<Path>, cache_capacity: usize) -> Result<Option<LazyDatabase>> {
+        LazyDatabase::open(path, cache_capacity)
+    }
+
+    /// Write a compact binary snapshot of the samples for read-only devices
+    pub fn write_snapshot(&self, path: impl AsRef<Path>) -> Result<()> {
+        snapshot::write_snapshot(self, path.as_ref())
+    }
+
+    /// [`FaceDatabase::write_snapshot`], compressed for devices short on storage
+    pub fn write_snapshot_with(&self, path: impl AsRef<Path>, compression: Compression) -> Result<()> {
+        snapshot::write_snapshot_with(self, path.as_ref(), compression)
+    }
+
+    /// Memory-map a snapshot written by [`FaceDatabase::write_snapshot`]
+    ///
+    /// For devices that only authenticate: no JSON parsing at startup and
+    /// no heap copy of the embeddings, unless the snapshot is compressed.
+    pub fn open_snapshot(path: impl AsRef<Path>) -> Result<DatabaseSnapshot> {
+        DatabaseSnapshot::open(path.as_ref())
+    }
+
+    /// Closest user to `probe` using the exact `f64` encodings
+    ///
+    /// For repeated matching keep an [`EmbeddingIndex`] instead.
+    pub fn find_best_match(&self, probe: &[f64], tolerance: f64) -> Option<MatchCandidate> {
+        matching::best_match(self.users.values(), probe, tolerance)
+    }
+}
+
+/// Most bytes an export file may decompress to; one user's samples take a
+/// few kilobytes
+pub const MAX_EXPORT_LEN: usize = 16 << 20;
+
+/// File written by `export_user`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportedCredential {
+    pub user_id: String,
+    pub user_data: UserProfile,
+    #[serde(default)]
+    pub exported_at: Option<String>,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ExportedCredential {
+    /// Read an export file, which may come from another device and may be
+    /// compressed
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read(path)
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        compression::decompress_at_most(&content, MAX_EXPORT_LEN)
+            .and_then(|content| Self::from_json(&String::from_utf8_lossy(&content)))
+            .map_err(|e| anyhow!("Invalid export file {}: {}", path.display(), e))
+    }
+
+    /// An export file as stored, and whether it was compressed, so a rewrite
+    /// can keep it that way
+    fn load_for_rewrite(path: &Path) -> Result<(Self, bool)> {
+        let content = fs::read(path)?;
+        let export = serde_json::from_slice(&compression::decompress_at_most(&content, MAX_EXPORT_LEN)?)?;
+        Ok((export, compression::is_compressed(&content)))
+    }
+
+    /// The export as written over a copy stored `compressed`: plain stays
+    /// plain, compressed is compressed at `compression`'s level, or zstd's
+    /// default without one
+    fn to_bytes(&self, compressed: bool, compression: Compression) -> Result<Vec<u8>> {
+        let json = serde_json::to_vec_pretty(self)?;
+        match (compressed, compression) {
+            (false, _) => Ok(json),
+            (true, Compression::None) => Ok(Compression::zstd_default().compress(&json)?.into_owned()),
+            (true, compression) => Ok(compression.compress(&json)?.into_owned()),
+        }
+    }
+
+    /// Parse an export and check that it holds a usable profile of `user_id`
+    pub fn from_json(json: &str) -> Result<Self> {
+        let export: Self = serde_json::from_str(json)?;
+        if export.user_id != export.user_data.user_id {
+            return Err(anyhow!("Export for '{}' holds the profile of '{}'", export.user_id, export.user_data.user_id));
+        }
+        export.user_data.validate()?;
+        Ok(export)
+    }
+}
+
+/// Where the library and the Python script keep their files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageLayout {
+    /// Working directory of the Python script
+    pub data_dir: PathBuf,
+    /// Registration output
+    pub generated_dir: PathBuf,
+    /// Credentials used for authentication
+    pub source_dir: PathBuf,
+}
+
+impl StorageLayout {
+    pub fn database_path(&self) -> PathBuf {
+        self.data_dir.join("python_face_database.json")
+    }
+
+    pub fn exports_dir(&self) -> PathBuf {
+        self.data_dir.join("exported_credentials")
+    }
+
+    pub fn captures_dir(&self) -> PathBuf {
+        self.data_dir.join("captured_images")
+    }
+
+    pub fn audit_log_path(&self) -> PathBuf {
+        self.data_dir.join("audit.log")
+    }
+
+    /// Single-user credential copies of 1:1 verifications in progress, see
+    /// [`FaceAuth::verify_user`](crate::FaceAuth::verify_user)
+    pub fn claims_dir(&self) -> PathBuf {
+        self.data_dir.join(".claims")
+    }
+
+    /// Locked while a capture uses the camera, so processes sharing the data
+    /// directory take turns, see [`crate::device`]
+    pub fn camera_lock_path(&self) -> PathBuf {
+        self.data_dir.join(".camera.lock")
+    }
+
+    /// Serials of NFC tags this device refuses, see
+    /// [`FaceAuth::revoke_nfc_tag`](crate::FaceAuth::revoke_nfc_tag)
+    #[cfg(feature = "nfc")]
+    pub fn revoked_nfc_tags_path(&self) -> PathBuf {
+        self.data_dir.join("revoked_nfc_tags.json")
+    }
+
+    /// Recorded authentication sessions for replay
+    pub fn recordings_dir(&self) -> PathBuf {
+        self.data_dir.join("recordings")
+    }
+
+    /// Remotely submitted enrollments awaiting moderation
+    pub fn pending_enrollments_dir(&self) -> PathBuf {
+        self.data_dir.join("pending_enrollments")
+    }
+
+    /// Kiosk enrollment flows not yet confirmed or cancelled
+    pub fn enrollment_flows_dir(&self) -> PathBuf {
+        self.data_dir.join("enrollment_flows")
+    }
+
+    /// Samples of registrations a crash interrupted, see [`crate::EnrollmentProgress`]
+    pub fn registration_progress_dir(&self) -> PathBuf {
+        self.data_dir.join("registration_progress")
+    }
+
+    pub fn registration_progress_path(&self, username: &str) -> PathBuf {
+        self.registration_progress_dir().join(format!("{}.json", username))
+    }
+
+    /// Access events waiting to be delivered
+    pub fn outbox_dir(&self) -> PathBuf {
+        self.data_dir.join("outbox")
+    }
+
+    /// False accepts expected in the current period, see [`crate::far`]
+    pub fn far_budget_path(&self) -> PathBuf {
+        self.data_dir.join("far_budget.json")
+    }
+
+    /// Undo records of multi-step operations in progress
+    pub fn journal_dir(&self) -> PathBuf {
+        self.data_dir.join("journal")
+    }
+
+    /// Secrets sealed under face-derived keys
+    pub fn vault_dir(&self) -> PathBuf {
+        self.data_dir.join("vault")
+    }
+
+    pub fn sealed_secret_path(&self, username: &str) -> PathBuf {
+        self.vault_dir().join(format!("{}.json", username))
+    }
+
+    pub fn thumbnails_dir(&self) -> PathBuf {
+        self.data_dir.join("thumbnails")
+    }
+
+    /// Plain thumbnail as written by the Python script
+    pub fn thumbnail_path(&self, username: &str) -> PathBuf {
+        self.thumbnails_dir().join(format!("{}.jpg", username))
+    }
+
+    /// Thumbnail encrypted at rest
+    pub fn encrypted_thumbnail_path(&self, username: &str) -> PathBuf {
+        self.thumbnails_dir().join(format!("{}.jpg.enc", username))
+    }
+
+    /// Encrypt a freshly written plain thumbnail in place, returning the new path
+    pub fn encrypt_thumbnail(&self, username: &str, key: &[u8; crypto::KEY_LEN]) -> Result<PathBuf> {
+        let plain_path = self.thumbnail_path(username);
+        let sealed = crypto::seal(key, &fs::read(&plain_path)?)?;
+        let encrypted_path = self.encrypted_thumbnail_path(username);
+        write_atomic(&encrypted_path, &sealed)?;
+        fs::remove_file(plain_path)?;
+        Ok(encrypted_path)
+    }
+
+    /// JPEG thumbnail of a user, decrypting it when stored encrypted
+    pub fn thumbnail(&self, username: &str, key: Option<&[u8; crypto::KEY_LEN]>) -> Result<Vec<u8>> {
+        validate_username(username)?;
+        let encrypted_path = self.encrypted_thumbnail_path(username);
+        if encrypted_path.exists() {
+            let key = key.ok_or_else(|| anyhow!("Thumbnail of '{}' is encrypted but no key is configured", username))?;
+            return crypto::open(key, &fs::read(encrypted_path)?);
+        }
+        fs::read(self.thumbnail_path(username))
+            .map_err(|e| anyhow!("No thumbnail for user '{}': {}", username, e))
+    }
+
+    /// Write a user's database entry to `filename`, or a timestamped file in
+    /// the exports directory when empty; `false` if the user is unknown
+    pub fn export_user(&self, username: &str, filename: &str) -> Result<bool> {
+        let Some(profile) = FaceDatabase::load(self.database_path())?.unwrap_or_default().users.remove(username) else {
+            return Ok(false);
+        };
+        let path = if filename.is_empty() {
+            self.exports_dir().join(format!("{}_credentials_{}.json", username, Local::now().format("%Y%m%d_%H%M%S")))
+        } else {
+            PathBuf::from(filename)
+        };
+        let export = ExportedCredential {
+            user_id: username.to_string(),
+            user_data: profile,
+            exported_at: Some(Local::now().format("%Y-%m-%dT%H:%M:%S%.6f").to_string()),
+            version: Some("1.0".to_string()),
+            extra: Default::default(),
+        };
+        write_atomic(&path, serde_json::to_string_pretty(&export)?.as_bytes())?;
+        Ok(true)
+    }
+
+    /// Add the user in an export file to the database, resolving relative
+    /// names against the data directory
+    pub fn import_user(&self, filename: &str) -> Result<bool> {
+        let path = if Path::new(filename).exists() { PathBuf::from(filename) } else { self.data_dir.join(filename) };
+        let export = ExportedCredential::load(path)?;
+        let mut db = FaceDatabase::load(self.database_path())?.unwrap_or_default();
+        db.users.insert(export.user_id, export.user_data);
+        db.save(self.database_path())?;
+        Ok(true)
+    }
+
+    /// Rename a user everywhere the library stores them
+    ///
+    /// Covers the database entry, the generated and source credential files,
+    /// exported credentials, thumbnails and audit log references. All new files are written before any old file is
+    /// removed; if a write fails, the files written so far are rolled back.
+    /// Compressed exports are rewritten at `compression`'s level.
+    pub fn rename_user(&self, old: &str, new: &str, compression: Compression) -> Result<()> {
+        validate_username(old)?;
+        validate_username(new)?;
+        if old == new {
+            return Err(anyhow!("User is already named '{}'", new));
+        }
+
+        let mut writes: Vec<PendingWrite> = Vec::new();
+
+        let db_path = self.database_path();
+        if let Some(mut db) = FaceDatabase::load(&db_path)? {
+            if db.users.contains_key(old) && db.users.contains_key(new) {
+                return Err(anyhow!("User '{}' already exists in the database", new));
+            }
+            let mut changed = false;
+            if let Some(mut profile) = db.users.remove(old) {
+                profile.rename(new);
+                db.users.insert(new.to_string(), profile);
+                changed = true;
+            }
+            if let Some(stats) = db.stats.remove(old) {
+                db.stats.insert(new.to_string(), stats);
+                changed = true;
+            }
+            if changed {
+                let previous = fs::read(&db_path)?;
+                writes.push(PendingWrite {
+                    contents: serde_json::to_string_pretty(&db)?.into_bytes(),
+                    path: db_path,
+                    previous: Some(previous),
+                    replaces: None,
+                });
+            }
+        }
+
+        for dir in [&self.generated_dir, &self.source_dir] {
+            let from = credential_path(dir, old);
+            if !from.exists() {
+                continue;
+            }
+            let to = credential_path(dir, new);
+            if to.exists() {
+                return Err(anyhow!("{} already exists", to.display()));
+            }
+            let mut profile = UserProfile::load(&from)?;
+            profile.rename(new);
+            writes.push(PendingWrite {
+                path: to,
+                contents: serde_json::to_string_pretty(&profile)?.into_bytes(),
+                previous: None,
+                replaces: Some(from),
+            });
+        }
+
+        let export_prefix = format!("{}_credentials_", old);
+        if let Ok(entries) = fs::read_dir(self.exports_dir()) {
+            for entry in entries.flatten() {
+                let file_name = entry.file_name().to_string_lossy().into_owned();
+                let Some(suffix) = file_name.strip_prefix(&export_prefix) else { continue };
+                let from = entry.path();
+                let Ok((mut export, compressed)) = ExportedCredential::load_for_rewrite(&from) else { continue };
+                if export.user_id != old {
+                    continue;
+                }
+                export.user_id = new.to_string();
+                export.user_data.rename(new);
+                let to = self.exports_dir().join(format!("{}_credentials_{}", new, suffix));
+                writes.push(PendingWrite {
+                    path: to,
+                    contents: export.to_bytes(compressed, compression)?,
+                    previous: None,
+                    replaces: Some(from),
+                });
+            }
+        }
+
+        if writes.is_empty() {
+            return Err(anyhow!("User '{}' not found", old));
+        }
+
+        let audit_log = AuditLog::new(self.audit_log_path());
+        if let Some(contents) = audit_log.renamed_contents(old, new)? {
+            writes.push(PendingWrite {
+                contents,
+                previous: Some(fs::read(audit_log.path())?),
+                path: audit_log.path().to_path_buf(),
+                replaces: None,
+            });
+        }
+
+        for (from, to) in [
+            (self.thumbnail_path(old), self.thumbnail_path(new)),
+            (self.encrypted_thumbnail_path(old), self.encrypted_thumbnail_path(new)),
+        ] {
+            if from.exists() {
+                writes.push(PendingWrite { contents: fs::read(&from)?, path: to, previous: None, replaces: Some(from) });
+            }
+        }
+
+        apply_writes(&writes)
+    }
+
+    /// Apply `update` to every stored copy of a user's profile
+    ///
+    /// The database entry and the generated and source credential files are
+    /// rewritten together; a failed write restores the copies already written.
+    pub fn update_user(&self, username: &str, update: impl Fn(&mut UserProfile)) -> Result<()> {
+        validate_username(username)?;
+        let mut writes: Vec<PendingWrite> = Vec::new();
+
+        let db_path = self.database_path();
+        if let Some(mut db) = FaceDatabase::load(&db_path)? {
+            if let Some(profile) = db.users.get_mut(username) {
+                update(profile);
+                writes.push(PendingWrite {
+                    contents: serde_json::to_string_pretty(&db)?.into_bytes(),
+                    previous: Some(fs::read(&db_path)?),
+                    path: db_path,
+                    replaces: None,
+                });
+            }
+        }
+
+        for dir in [&self.generated_dir, &self.source_dir] {
+            let path = credential_path(dir, username);
+            if !path.exists() {
+                continue;
+            }
+            let mut profile = UserProfile::load(&path)?;
+            update(&mut profile);
+            writes.push(PendingWrite {
+                contents: serde_json::to_string_pretty(&profile)?.into_bytes(),
+                previous: Some(fs::read(&path)?),
+                path,
+                replaces: None,
+            });
+        }
+
+        if writes.is_empty() {
+            return Err(anyhow!("User '{}' not found", username));
+        }
+        apply_writes(&writes)
+    }
+
+    /// Give samples with legacy `<user>_<timestamp>` ids UUIDv7s, see
+    /// [`crate::sample_id`]
+    ///
+    /// The database, the generated and source credential files and the
+    /// exports are rewritten together, and every copy of a sample gets the
+    /// same new id, so they keep referring to one sample. Renamed ids keep the
+    /// sample's capture time, falling back to `now`. Files that fail to parse
+    /// are skipped with a warning. Compressed exports are rewritten at
+    /// `compression`'s level.
+    pub fn migrate_sample_ids(&self, now: DateTime<Utc>, random: &dyn RandomSource, compression: Compression) -> Result<Vec<SampleIdRewrite>> {
+        let mut rewriter = sample_id::Rewriter::new(now, random);
+        let mut writes: Vec<PendingWrite> = Vec::new();
+
+        let db_path = self.database_path();
+        if let Some(mut db) = FaceDatabase::load(&db_path)? {
+            let mut changed = false;
+            for profile in db.users.values_mut() {
+                changed |= rewriter.rewrite(profile);
+            }
+            if changed {
+                writes.push(PendingWrite {
+                    contents: serde_json::to_string_pretty(&db)?.into_bytes(),
+                    previous: Some(fs::read(&db_path)?),
+                    path: db_path,
+                    replaces: None,
+                });
+            }
+        }
+
+        let mut paths = credential_files(&self.generated_dir)?;
+        if self.source_dir != self.generated_dir {
+            paths.extend(credential_files(&self.source_dir)?);
+        }
+        for path in paths {
+            let mut profile = match UserProfile::load(&path) {
+                Ok(profile) => profile,
+                Err(e) => {
+                    tracing::warn!("Skipping credential file: {}", e);
+                    continue;
+                }
+            };
+            if rewriter.rewrite(&mut profile) {
+                writes.push(PendingWrite {
+                    contents: serde_json::to_string_pretty(&profile)?.into_bytes(),
+                    previous: Some(fs::read(&path)?),
+                    path,
+                    replaces: None,
+                });
+            }
+        }
+
+        let exports: Vec<PathBuf> = fs::read_dir(self.exports_dir())
+            .map(|entries| entries.flatten().map(|entry| entry.path()).collect())
+            .unwrap_or_default();
+        for path in exports {
+            let Ok((mut export, compressed)) = ExportedCredential::load_for_rewrite(&path) else {
+                tracing::warn!("Skipping export {}", path.display());
+                continue;
+            };
+            if rewriter.rewrite(&mut export.user_data) {
+                writes.push(PendingWrite {
+                    contents: export.to_bytes(compressed, compression)?,
+                    previous: Some(fs::read(&path)?),
+                    path,
+                    replaces: None,
+                });
+            }
+        }
+
+        apply_writes(&writes)?;
+        Ok(rewriter.finish())
+    }
+
+    /// Authentication statistics of a user, without changing them
+    pub fn user_stats(&self, username: &str) -> Result<UserStats> {
+        let db = FaceDatabase::load(self.database_path())?.unwrap_or_default();
+        Ok(db.stats.get(username).cloned().unwrap_or_default())
+    }
+
+    /// Count a successful authentication, returning the statistics before it
+    pub fn record_authentication(&self, username: &str, distance: Option<f64>) -> Result<UserStats> {
+        let db_path = self.database_path();
+        let mut db = FaceDatabase::load(&db_path)?.unwrap_or_default();
+        let stats = db.stats.entry(username.to_string()).or_default();
+        let previous = stats.clone();
+
+        stats.authentication_count += 1;
+        stats.last_authentication = Some(Utc::now());
+        stats.total_distance += distance.unwrap_or(0.0);
+
+        db.save(&db_path)?;
+        Ok(previous)
+    }
+
+    /// Valid credential profiles in the source directory
+    ///
+    /// Files that fail to parse or validate are skipped with a warning.
+    pub fn source_profiles(&self) -> Result<Vec<UserProfile>> {
+        load_credentials_dir(&self.source_dir)
+    }
+
+    /// Totals across the database, the source directory and authentication statistics
+    pub fn stats(&self) -> Result<DatabaseStats> {
+        let db = FaceDatabase::load(self.database_path())?.unwrap_or_default();
+        let source = self.source_profiles()?;
+
+        let mut samples: BTreeMap<&str, usize> = db.users.iter()
+            .map(|(name, profile)| (name.as_str(), profile.face_encodings.len()))
+            .collect();
+        // The source copy is what authentication uses, so it wins
+        for profile in &source {
+            samples.insert(&profile.user_id, profile.face_encodings.len());
+        }
+
+        let total_authentications: u64 = db.stats.values().map(|s| s.authentication_count).sum();
+        let total_distance: f64 = db.stats.values().map(|s| s.total_distance).sum();
+
+        let last_authentication = db.stats.values().filter_map(|s| s.last_authentication).max();
+        let last_enrollment = db.users.values()
+            .chain(source.iter())
+            .filter_map(|p| p.enrollment_date.as_deref())
+            .filter_map(parse_python_timestamp)
+            .max();
+
+        Ok(DatabaseStats {
+            total_users: samples.len(),
+            enrolled_users: source.len(),
+            total_samples: samples.values().sum(),
+            total_authentications,
+            average_match_distance: (total_authentications > 0)
+                .then(|| total_distance / total_authentications as f64),
+            last_activity: last_authentication.max(last_enrollment),
+        })
+    }
+
+    /// Registered users known to the database
+    pub fn list_users(&self) -> Result<Vec<UserSummary>> {
+        let db = FaceDatabase::load(self.database_path())?.unwrap_or_default();
+        Ok(db.users.values().map(UserSummary::from).collect())
+    }
+
+    /// A user's profile as used for authentication: the source credential,
+    /// or the database entry if the user hasn't been promoted
+    fn current_profile(&self, username: &str) -> Result<UserProfile> {
+        validate_username(username)?;
+        let source = credential_path(&self.source_dir, username);
+        if source.exists() {
+            return UserProfile::load(source);
+        }
+        FaceDatabase::load(self.database_path())?
+            .and_then(|mut db| db.users.remove(username))
+            .ok_or_else(|| anyhow!("User '{}' not found", username))
+    }
+
+    pub fn samples(&self, username: &str) -> Result<Vec<SampleSummary>> {
+        Ok(self.current_profile(username)?.face_encodings.iter().map(SampleSummary::from).collect())
+    }
+
+    /// Delete one sample from every stored copy of a user's profile, along
+    /// with its captured image
+    ///
+    /// A user's last sample can't be removed; delete or re-enroll the user instead.
+    pub fn remove_sample(&self, username: &str, sample_id: &str) -> Result<()> {
+        let profile = self.current_profile(username)?;
+        let sample = profile.face_encodings.iter().find(|s| s.sample_id == sample_id)
+            .ok_or_else(|| anyhow!("User '{}' has no sample '{}'", username, sample_id))?;
+        if profile.face_encodings.len() == 1 {
+            return Err(anyhow!("'{}' is the last sample of '{}'", sample_id, username));
+        }
+        let image = sample.image_path.as_ref().map(|path| self.data_dir.join(path));
+
+        self.update_user(username, |profile| {
+            profile.face_encodings.retain(|s| s.sample_id != sample_id);
+            profile.sample_count = profile.face_encodings.len();
+        })?;
+        if let Some(image) = image.filter(|image| image.starts_with(self.captures_dir())) {
+            let _ = fs::remove_file(image);
+        }
+        Ok(())
+    }
+
+    /// Quarantine a user's outlier samples in every stored copy; see
+    /// [`UserProfile::detect_outliers`]
+    pub fn detect_outliers(&self, username: &str) -> Result<Vec<SampleSummary>> {
+        let flagged = self.current_profile(username)?.detect_outliers();
+        if !flagged.is_empty() {
+            self.update_user(username, |profile| {
+                for sample in &mut profile.face_encodings {
+                    if let Some(outlier) = flagged.iter().find(|o| o.sample_id == sample.sample_id) {
+                        sample.quarantine = outlier.quarantine.clone();
+                    }
+                }
+            })?;
+        }
+        Ok(flagged)
+    }
+
+    /// Return a quarantined sample to matching in every stored copy
+    pub fn release_sample(&self, username: &str, sample_id: &str) -> Result<()> {
+        self.current_profile(username)?.release_sample(sample_id)?;
+        self.update_user(username, |profile| {
+            let _ = profile.release_sample(sample_id);
+        })
+    }
+}
+
+/// Write all staged files, restoring earlier writes if one fails, then remove superseded files
+fn apply_writes(writes: &[PendingWrite]) -> Result<()> {
+    for (i, write) in writes.iter().enumerate() {
+        if let Err(e) = write_atomic(&write.path, &write.contents) {
+            for written in &writes[..i] {
+                let _ = match &written.previous {
+                    Some(previous) => write_atomic(&written.path, previous),
+                    None => fs::remove_file(&written.path).map_err(Into::into),
+                };
+            }
+            return Err(e);
+        }
+    }
+    for write in writes {
+        if let Some(old_path) = &write.replaces {
+            fs::remove_file(old_path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Load every valid `*.json` credential in `dir`, skipping broken files with a warning
+pub fn load_credentials_dir(dir: &Path) -> Result<Vec<UserProfile>> {
+    let mut profiles = Vec::new();
+    for path in credential_files(dir)? {
+        match UserProfile::load(&path).and_then(|p| p.validate().map(|_| p)) {
+            Ok(profile) => profiles.push(profile),
+            Err(e) => tracing::warn!(path = %path.display(), "Skipping credential file: {:#}", e),
+        }
+    }
+    Ok(profiles)
+}
+
+/// Paths of the `*.json` files in `dir`, sorted; none if it doesn't exist
+pub(crate) fn credential_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Parse the naive local timestamps written by Python's `datetime.now().isoformat()`
+pub fn parse_python_timestamp(timestamp: &str) -> Option<DateTime<Utc>> {
+    if let Ok(parsed) = DateTime::parse_from_rfc3339(timestamp) {
+        return Some(parsed.with_timezone(&Utc));
+    }
+    let naive = NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%dT%H:%M:%S%.f").ok()?;
+    naive.and_local_timezone(Local).earliest().map(|t| t.with_timezone(&Utc))
+}
+
+/// A file rewrite staged by a multi-file operation
+struct PendingWrite {
+    path: PathBuf,
+    contents: Vec<u8>,
+    /// Contents to restore on rollback when `path` is rewritten in place
+    previous: Option<Vec<u8>>,
+    /// File superseded by `path`, removed once every write succeeded
+    replaces: Option<PathBuf>,
+}
+
+/// Path of a user's credential file inside a credentials directory
+pub fn credential_path(dir: impl AsRef<Path>, username: &str) -> PathBuf {
+    dir.as_ref().join(format!("{}.json", username))
+}
+
+/// Reject usernames that cannot safely be used as file names
+pub fn validate_username(username: &str) -> Result<()> {
+    if username.is_empty()
+        || username.starts_with('.')
+        || username.contains(['/', '\\', '\0'])
+    {
+        return Err(FaceAuthError::InvalidUsername { username: username.to_string() }.into());
+    }
+    Ok(())
+}
+
+/// Write `contents` to a temporary file next to `path` and rename it into place
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    write_atomic_with(path, contents, false)
+}
+
+/// [`write_atomic`], readable by the owner only on Unix, e.g. for sealed secrets
+pub fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    write_atomic_with(path, contents, true)
+}
+
+fn write_atomic_with(path: &Path, contents: &[u8], private: bool) -> Result<()> {
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+
+    let file_name = path.file_name().ok_or_else(|| anyhow!("Invalid path: {}", path.display()))?;
+    let tmp_path = dir.join(format!(".{}.tmp", file_name.to_string_lossy()));
+
+    let result = (|| -> Result<()> {
+        use std::io::Write;
+        let mut options = fs::File::options();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        if private {
+            use std::os::unix::fs::OpenOptionsExt;
+            // A leftover temp file keeps its mode, so start from a fresh one
+            let _ = fs::remove_file(&tmp_path);
+            options.mode(0o600);
+        }
+        #[cfg(not(unix))]
+        let _ = private;
+        let mut file = options.open(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Validate a generated credential and atomically copy it into the source directory
+///
+/// Returns the path of the promoted file.
+pub fn promote_credential(username: &str, generated_dir: &Path, source_dir: &Path) -> Result<PathBuf> {
+    validate_username(username)?;
+
+    let from = credential_path(generated_dir, username);
+    let profile = UserProfile::load(&from)?;
+    if profile.user_id != username {
+        return Err(anyhow!(
+            "{} belongs to user '{}', not '{}'",
+            from.display(), profile.user_id, username
+        ));
+    }
+    profile.validate()?;
+
+    let to = credential_path(source_dir, username);
+    write_atomic(&to, &fs::read(&from)?)?;
+    Ok(to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_promote_validates_and_copies() {
+        let generated = tempfile::tempdir().unwrap();
+        let source = tempfile::tempdir().unwrap();
+
+        let profile = UserProfile::load("source/osman.json").unwrap();
+        profile.save(credential_path(generated.path(), "osman")).unwrap();
+
+        let promoted = promote_credential("osman", generated.path(), source.path()).unwrap();
+        assert_eq!(UserProfile::load(promoted).unwrap(), profile);
+
+        // A credential stored under the wrong name is rejected
+        profile.save(credential_path(generated.path(), "mallory")).unwrap();
+        assert!(promote_credential("mallory", generated.path(), source.path()).is_err());
+        assert!(promote_credential("../osman", generated.path(), source.path()).is_err());
+    }
+
+    #[test]
+    fn test_rename_updates_every_copy() {
+        let root = tempfile::tempdir().unwrap();
+        let layout = StorageLayout {
+            data_dir: root.path().to_path_buf(),
+            generated_dir: root.path().join("generated"),
+            source_dir: root.path().join("source"),
+        };
+
+        let profile = UserProfile::load("source/osman.json").unwrap();
+        profile.save(credential_path(&layout.generated_dir, "osman")).unwrap();
+        profile.save(credential_path(&layout.source_dir, "osman")).unwrap();
+        let mut db = FaceDatabase::default();
+        db.users.insert("osman".into(), profile.clone());
+        db.save(layout.database_path()).unwrap();
+        let export = ExportedCredential { user_id: "osman".into(), user_data: profile, exported_at: None, version: None, extra: Default::default() };
+        fs::create_dir_all(layout.exports_dir()).unwrap();
+        fs::write(layout.exports_dir().join("osman_credentials_1.json.zst"), export.to_bytes(true, Compression::None).unwrap()).unwrap();
+
+        layout.rename_user("osman", "usman", Compression::zstd(19)).unwrap();
+
+        assert!(!credential_path(&layout.source_dir, "osman").exists());
+        let renamed = UserProfile::load(credential_path(&layout.source_dir, "usman")).unwrap();
+        assert_eq!(renamed.user_id, "usman");
+        assert!(renamed.face_encodings.iter().all(|s| s.sample_id.starts_with("usman_")));
+        let db = FaceDatabase::load(layout.database_path()).unwrap().unwrap();
+        assert!(db.users.contains_key("usman") && !db.users.contains_key("osman"));
+        // Rewritten at the configured level
+        let (export, _) = ExportedCredential::load_for_rewrite(&layout.exports_dir().join("usman_credentials_1.json.zst")).unwrap();
+        assert_eq!(export.user_id, "usman");
+        let stored = fs::read(layout.exports_dir().join("usman_credentials_1.json.zst")).unwrap();
+        assert_eq!(stored.as_slice(), &*Compression::zstd(19).compress(&serde_json::to_vec_pretty(&export).unwrap()).unwrap());
+
+        assert!(layout.rename_user("osman", "other", Compression::None).is_err());
+    }
+
+    #[test]
+    fn test_remove_sample_from_every_copy() {
+        let root = tempfile::tempdir().unwrap();
+        let layout = StorageLayout {
+            data_dir: root.path().to_path_buf(),
+            generated_dir: root.path().join("generated"),
+            source_dir: root.path().join("source"),
+        };
+        let profile = UserProfile::load("source/osman.json").unwrap();
+        profile.save(credential_path(&layout.source_dir, "osman")).unwrap();
+        let mut db = FaceDatabase::default();
+        db.users.insert("osman".into(), profile.clone());
+        db.save(layout.database_path()).unwrap();
+
+        let samples = layout.samples("osman").unwrap();
+        assert_eq!(samples.len(), profile.face_encodings.len());
+        assert_eq!(samples[0].model_version, DLIB_TEMPLATE_VERSION);
+        layout.remove_sample("osman", &samples[0].sample_id).unwrap();
+
+        let remaining = &UserProfile::load(credential_path(&layout.source_dir, "osman")).unwrap().face_encodings;
+        assert_eq!(remaining.len(), samples.len() - 1);
+        assert!(remaining.iter().all(|s| s.sample_id != samples[0].sample_id));
+        assert_eq!(FaceDatabase::load(layout.database_path()).unwrap().unwrap().users["osman"].sample_count, remaining.len());
+        assert!(layout.remove_sample("osman", "missing").is_err());
+        for sample in &remaining[1..] {
+            layout.remove_sample("osman", &sample.sample_id).unwrap();
+        }
+        assert!(layout.remove_sample("osman", &remaining[0].sample_id).is_err(), "the last sample stays");
+    }
+
+    #[test]
+    fn test_stats_combine_source_and_authentications() {
+        let root = tempfile::tempdir().unwrap();
+        let layout = StorageLayout {
+            data_dir: root.path().to_path_buf(),
+            generated_dir: root.path().join("generated"),
+            source_dir: PathBuf::from("source"),
+        };
+
+        layout.record_authentication("osman", Some(0.3)).unwrap();
+        layout.record_authentication("osman", Some(0.5)).unwrap();
+
+        let stats = layout.stats().unwrap();
+        assert_eq!(stats.enrolled_users, 4);
+        assert_eq!(stats.total_authentications, 2);
+        assert!((stats.average_match_distance.unwrap() - 0.4).abs() < 1e-9);
+        assert!(stats.last_activity.is_some());
+    }
+
+    fn arbitrary_profile() -> impl Strategy<Value = UserProfile> {
+        let sample = (proptest::collection::vec(any::<i16>(), 0..4), "[a-z0-9_]{0,12}").prop_map(|(values, sample_id)| FaceSample {
+            encoding: values.into_iter().map(|v| f64::from(v) / 256.0).collect(),
+            timestamp: String::new(),
+            image_path: None,
+            sample_id,
+            quality: None,
+            template_version: None,
+            quarantine: None,
+        });
+        ("[a-z][a-z0-9_]{0,15}", proptest::collection::vec(sample, 0..4)).prop_map(|(user_id, face_encodings)| UserProfile {
+            sample_count: face_encodings.len(),
+            user_id,
+            face_encodings,
+            enrollment_date: None,
+            metadata: UserMetadata::default(),
+            extra: Default::default(),
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn test_database_roundtrips(profiles in proptest::collection::vec(arbitrary_profile(), 0..4)) {
+            let db = FaceDatabase {
+                users: profiles.into_iter().map(|p| (p.user_id.clone(), p)).collect(),
+                ..Default::default()
+            };
+            let json = serde_json::to_string(&db).unwrap();
+            prop_assert_eq!(FaceDatabase::from_json(&json).unwrap(), db);
+        }
+
+        #[test]
+        fn test_truncated_or_arbitrary_input_is_rejected_without_panic(profile in arbitrary_profile(), cut in any::<usize>(), noise in ".*") {
+            let export = serde_json::to_string(&ExportedCredential {
+                user_id: profile.user_id.clone(),
+                user_data: profile,
+                exported_at: None,
+                version: None,
+                extra: Default::default(),
+            }).unwrap();
+            let truncated = &export[..cut % export.len()];
+            prop_assert!(ExportedCredential::from_json(truncated).is_err());
+            prop_assert!(FaceDatabase::from_json(truncated).is_err());
+
+            // Whatever parses must satisfy the checks made on import
+            if let Ok(export) = ExportedCredential::from_json(&noise) {
+                prop_assert!(export.user_data.validate().is_ok());
+            }
+            let _ = FaceDatabase::from_json(&noise);
+        }
+    }
+
+    #[test]
+    fn test_export_must_hold_a_valid_profile_of_its_user() {
+        let profile = UserProfile::load("source/osman.json").unwrap();
+        let export = |user_id: &str, user_data: &UserProfile| serde_json::to_string(&ExportedCredential {
+            user_id: user_id.to_string(),
+            user_data: user_data.clone(),
+            exported_at: None,
+            version: Some("1.0".into()),
+            extra: Default::default(),
+        }).unwrap();
+
+        assert!(ExportedCredential::from_json(&export("osman", &profile)).is_ok());
+        assert!(ExportedCredential::from_json(&export("mallory", &profile)).is_err());
+        let mut short = profile.clone();
+        short.face_encodings[0].encoding.pop();
+        assert!(ExportedCredential::from_json(&export("osman", &short)).is_err());
+
+        let misfiled = serde_json::json!({ "users": { "mallory": profile } }).to_string();
+        assert!(FaceDatabase::from_json(&misfiled).is_err());
+    }
+}
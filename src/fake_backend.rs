@@ -0,0 +1,603 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::Local;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::assurance::Liveness;
+#[cfg(feature = "attention")]
+use crate::attention::{AttentionTracker, HeadPose};
+use crate::auth::{FrameBlur, PrivacyMode};
+use crate::backend::FaceBackend;
+use crate::camera::CameraSource;
+use crate::denial::DenialReason;
+use crate::face_storage::{self, FaceDatabase, FaceSample, StorageLayout, UserProfile};
+use crate::kyc::DocumentFace;
+use crate::matching;
+use crate::redact::{DetectedFace, FaceBox, Redaction, RedactionPolicy, RedactionStyle};
+use crate::registration::{EnrollmentProgress, RegistrationOutcome, SampleOutcome, SampleStatus, DUPLICATE_SAMPLE_EPSILON, DUPLICATE_SAMPLE_RETRIES};
+use crate::sample_id;
+use crate::scene::{SceneGate, SceneStats};
+use crate::standalone_python::{StandaloneAuthResult, WarmUpOutcome};
+use crate::timing::TimingBreakdown;
+use crate::tracking::{TrackEvent, TrackingConfig};
+
+/// Deterministic backend that serves canned embeddings instead of running
+/// a camera and the Python script
+///
+/// Frames are named fixtures. A frame file starts with its fixture name,
+/// so captures written by the fake camera can be recorded and replayed like
+/// real ones; blurring appends a line saying which faces were blurred. Clones share the camera queue, so a test can keep a clone to
+/// feed frames after handing the backend to [`crate::FaceAuth`]; a backend
+/// reading a video file has a queue of its own.
+#[derive(Debug, Clone)]
+pub struct FakeBackend {
+    layout: StorageLayout,
+    frames: Arc<HashMap<String, Vec<f64>>>,
+    camera: Arc<Mutex<VecDeque<String>>>,
+    /// Queues of cameras other than camera 0
+    cameras: Arc<Mutex<HashMap<u32, VecDeque<String>>>>,
+    /// Liveness check every frame with a face passes, if any
+    liveness: Arc<Mutex<Option<Liveness>>>,
+    /// Faces besides the user's in every frame with a face
+    bystanders: Arc<AtomicUsize>,
+    /// Captures kept in memory instead of files in strict privacy mode,
+    /// by the path they would have had
+    memory_frames: Arc<Mutex<HashMap<PathBuf, String>>>,
+    strict_privacy: Arc<AtomicBool>,
+    frame_blur: Arc<Mutex<FrameBlur>>,
+    /// Head poses of the tracked face in the next frames with a face
+    #[cfg(feature = "attention")]
+    head_poses: Arc<Mutex<VecDeque<HeadPose>>>,
+}
+
+impl FakeBackend {
+    pub fn new(data_dir: impl AsRef<Path>) -> Result<Self> {
+        let data_dir = std::path::absolute(data_dir.as_ref())?;
+        fs::create_dir_all(&data_dir)?;
+        Ok(Self {
+            layout: StorageLayout {
+                generated_dir: data_dir.join("generated"),
+                source_dir: data_dir.join("source"),
+                data_dir,
+            },
+            frames: Arc::default(),
+            camera: Arc::default(),
+            cameras: Arc::default(),
+            liveness: Arc::default(),
+            bystanders: Arc::default(),
+            memory_frames: Arc::default(),
+            strict_privacy: Arc::default(),
+            frame_blur: Arc::default(),
+            #[cfg(feature = "attention")]
+            head_poses: Arc::default(),
+        })
+    }
+
+    /// Backend serving the frames of a fixture file mapping frame names to embeddings
+    pub fn from_fixtures(data_dir: impl AsRef<Path>, fixtures: impl AsRef<Path>) -> Result<Self> {
+        let fixtures = fixtures.as_ref();
+        let content = fs::read_to_string(fixtures)
+            .with_context(|| format!("Failed to read fixtures {}", fixtures.display()))?;
+        let frames: HashMap<String, Vec<f64>> = serde_json::from_str(&content)
+            .with_context(|| format!("Invalid fixtures {}", fixtures.display()))?;
+        Ok(frames.into_iter().fold(Self::new(data_dir)?, |backend, (name, embedding)| backend.with_frame(name, embedding)))
+    }
+
+    /// Add a frame whose face encodes to `embedding`; an empty embedding is a frame without a face
+    pub fn with_frame(mut self, name: impl Into<String>, embedding: Vec<f64>) -> Self {
+        Arc::make_mut(&mut self.frames).insert(name.into(), embedding);
+        self
+    }
+
+    /// Report frames with a face as having passed `liveness`
+    pub fn set_liveness(&self, liveness: Option<Liveness>) {
+        *self.liveness.lock().unwrap_or_else(PoisonError::into_inner) = liveness;
+    }
+
+    /// Show `count` bystanders besides the user in every frame with a face,
+    /// who are cropped like the user but never matched
+    pub fn set_bystanders(&self, count: usize) {
+        self.bystanders.store(count, Ordering::Relaxed);
+    }
+
+    /// Head poses the tracked face takes in the next frames with a face,
+    /// in order; frames without a queued pose report no landmarks
+    #[cfg(feature = "attention")]
+    pub fn queue_head_poses(&self, poses: impl IntoIterator<Item = HeadPose>) {
+        self.head_poses.lock().unwrap_or_else(PoisonError::into_inner).extend(poses);
+    }
+
+    /// Frames the camera delivers next, in order
+    pub fn queue_frames<I, S>(&self, names: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.camera().extend(names.into_iter().map(Into::into));
+    }
+
+    /// Frames camera `camera` delivers next; camera 0 is the one
+    /// [`FakeBackend::queue_frames`] feeds
+    pub fn queue_camera_frames<I, S>(&self, camera: u32, names: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        match camera {
+            0 => self.queue_frames(names),
+            _ => self.cameras.lock().unwrap_or_else(PoisonError::into_inner)
+                .entry(camera).or_default()
+                .extend(names.into_iter().map(Into::into)),
+        }
+    }
+
+    /// Frames queued but not captured yet
+    pub fn queued_frames(&self) -> usize {
+        self.camera().len()
+    }
+
+    /// Write a frame file that [`FaceBackend::authenticate_image`] recognizes
+    pub fn write_frame(&self, name: &str, path: impl AsRef<Path>) -> Result<PathBuf> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, name)?;
+        Ok(path.to_path_buf())
+    }
+
+    fn camera(&self) -> MutexGuard<'_, VecDeque<String>> {
+        self.camera.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Next frame from the camera, saved like the script saves its captures
+    fn capture(&self) -> Result<Option<PathBuf>> {
+        let Some(name) = self.camera().pop_front() else {
+            return Ok(None);
+        };
+        self.save_capture(&name).map(Some)
+    }
+
+    fn save_capture(&self, name: &str) -> Result<PathBuf> {
+        let path = self.layout.captures_dir().join(format!("{}_{}.jpg", name, Local::now().format("%Y%m%d_%H%M%S%.6f")));
+        if self.strict_privacy.load(Ordering::Relaxed) {
+            self.memory_frames().insert(path.clone(), name.to_string());
+            return Ok(path);
+        }
+        self.write_frame(name, path)
+    }
+
+    fn memory_frames(&self) -> MutexGuard<'_, HashMap<PathBuf, String>> {
+        self.memory_frames.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Where a frame is stored, `None` for frames only held in memory
+    fn stored_path(&self, frame: &Path) -> Option<PathBuf> {
+        (!self.memory_frames().contains_key(frame)).then(|| frame.to_path_buf())
+    }
+
+    /// Drop a frame that isn't needed any more
+    fn discard(&self, frame: &Path) -> Result<()> {
+        if self.memory_frames().remove(frame).is_none() {
+            fs::remove_file(frame)?;
+        }
+        Ok(())
+    }
+
+    fn embedding(&self, frame: &Path) -> Result<Option<&[f64]>> {
+        self.fixture(&self.frame_name(frame)?)
+    }
+
+    /// Fixture a frame file or in-memory capture names
+    fn frame_name(&self, frame: &Path) -> Result<String> {
+        let content = match self.memory_frames().get(frame) {
+            Some(name) => name.clone(),
+            None => fs::read_to_string(frame)
+                .with_context(|| format!("Failed to read frame {}", frame.display()))?,
+        };
+        Ok(content.lines().next().unwrap_or_default().trim().to_string())
+    }
+
+    /// Blur a capture kept on disk like the script does once it's matched
+    fn blur(&self, frame: &Path) -> Result<()> {
+        let marker = match *self.frame_blur.lock().unwrap_or_else(PoisonError::into_inner) {
+            FrameBlur::Off => return Ok(()),
+            FrameBlur::Bystanders => "blurred: bystanders",
+            FrameBlur::AllFaces => "blurred: all",
+        };
+        if !frame.starts_with(self.layout.captures_dir()) || !frame.exists() {
+            return Ok(());
+        }
+        let content = fs::read_to_string(frame)?;
+        fs::write(frame, format!("{}\n{}", content.trim_end(), marker))?;
+        Ok(())
+    }
+
+    fn fixture(&self, name: &str) -> Result<Option<&[f64]>> {
+        let embedding = self.frames.get(name.trim())
+            .ok_or_else(|| anyhow!("Unknown fixture frame '{}'", name.trim()))?;
+        Ok((!embedding.is_empty()).then_some(embedding.as_slice()))
+    }
+
+    fn load_database(&self) -> Result<FaceDatabase> {
+        Ok(FaceDatabase::load(self.layout.database_path())?.unwrap_or_default())
+    }
+
+    fn match_probe(&self, tolerance: f64, source_dir: &str, probe: Option<&[f64]>, image_path: Option<PathBuf>) -> Result<StandaloneAuthResult> {
+        let mut result = StandaloneAuthResult {
+            success: true,
+            is_match: Some(false),
+            confidence: None,
+            distance: None,
+            threshold: Some(tolerance),
+            matched_user: None,
+            closest_user: None,
+            runner_up: None,
+            sample_distances: Vec::new(),
+            brightness: None,
+            image_path,
+            processing_time_ms: Some(0),
+            timings: TimingBreakdown::default(),
+            liveness: None,
+            denial: None,
+            raw_output: String::new(),
+        };
+        // Like the script, an empty source directory is reported before the face
+        let profiles = face_storage::load_credentials_dir(Path::new(source_dir))?;
+        if profiles.is_empty() {
+            result.denial = Some(DenialReason::NoUsersEnrolled);
+            return Ok(result);
+        }
+        let Some(probe) = probe else {
+            result.denial = Some(DenialReason::NoFace);
+            return Ok(result);
+        };
+        result.liveness = *self.liveness.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let mut ranked = matching::ranked_matches(&profiles, probe, tolerance).into_iter();
+        if let Some(candidate) = ranked.next() {
+            if let Some(profile) = profiles.iter().find(|profile| profile.user_id == candidate.user_id) {
+                result.sample_distances = matching::sample_distances(profile, probe);
+            }
+            result.is_match = Some(candidate.is_match);
+            result.confidence = Some(candidate.confidence());
+            result.distance = Some(candidate.distance);
+            result.matched_user = candidate.is_match.then(|| candidate.user_id.clone());
+            result.closest_user = Some(candidate.user_id);
+        }
+        result.runner_up = ranked.next();
+        Ok(result)
+    }
+}
+
+impl FaceBackend for FakeBackend {
+    fn data_dir(&self) -> &Path {
+        &self.layout.data_dir
+    }
+
+    fn register_user(&self, username: &str, samples: u32, generated_dir: &str) -> Result<RegistrationOutcome> {
+        face_storage::validate_username(username)?;
+        let now = Local::now().format("%Y-%m-%dT%H:%M:%S%.6f").to_string();
+        let mut outcome = RegistrationOutcome {
+            username: username.to_string(),
+            samples_requested: samples,
+            samples_captured: 0,
+            samples: Vec::new(),
+            generated_file: None,
+            fully_enrolled: false,
+            thumbnail_file: None,
+            promoted_file: None,
+        };
+        let progress_path = self.layout.registration_progress_path(username);
+        let mut progress = EnrollmentProgress::load(&progress_path)?
+            .filter(|progress| progress.resumes(samples))
+            .unwrap_or_else(|| EnrollmentProgress {
+                username: username.to_string(),
+                samples_requested: samples,
+                samples: Vec::new(),
+                face_encodings: Vec::new(),
+                updated_at: now.clone(),
+            });
+        let mut face_encodings = std::mem::take(&mut progress.face_encodings);
+        outcome.samples = std::mem::take(&mut progress.samples);
+
+        for index in outcome.samples.len() as u32 + 1..=samples {
+            let mut status = SampleStatus::CaptureFailed;
+            for _ in 0..=DUPLICATE_SAMPLE_RETRIES {
+                let Some(frame) = self.capture()? else {
+                    status = SampleStatus::CaptureFailed;
+                    break;
+                };
+                let Some(embedding) = self.embedding(&frame)? else {
+                    status = SampleStatus::NoFace;
+                    break;
+                };
+                if face_encodings.last().is_some_and(|previous: &FaceSample| matching::face_distance(&previous.encoding, embedding) < DUPLICATE_SAMPLE_EPSILON) {
+                    self.discard(&frame)?;
+                    status = SampleStatus::Duplicate;
+                    continue;
+                }
+                let image_path = self.stored_path(&frame).map(|path| path.to_string_lossy().into_owned());
+                self.memory_frames().remove(&frame);
+                self.blur(&frame)?;
+                face_encodings.push(FaceSample {
+                    encoding: embedding.to_vec(),
+                    timestamp: now.clone(),
+                    image_path,
+                    sample_id: sample_id::generate_now(),
+                    quality: None,
+                    template_version: None,
+                    quarantine: None,
+                });
+                status = SampleStatus::Stored;
+                break;
+            }
+            outcome.samples.push(SampleOutcome { index, status, quality: None });
+            EnrollmentProgress {
+                samples: outcome.samples.clone(),
+                face_encodings: face_encodings.clone(),
+                updated_at: Local::now().format("%Y-%m-%dT%H:%M:%S%.6f").to_string(),
+                ..progress.clone()
+            }.save(&progress_path)?;
+        }
+
+        outcome.samples_captured = face_encodings.len() as u32;
+        if face_encodings.is_empty() {
+            let _ = fs::remove_file(&progress_path);
+            return Ok(outcome);
+        }
+
+        let profile = UserProfile {
+            user_id: username.to_string(),
+            sample_count: face_encodings.len(),
+            face_encodings,
+            enrollment_date: Some(now),
+            metadata: Default::default(),
+            extra: Default::default(),
+        };
+        let generated_file = std::path::absolute(face_storage::credential_path(generated_dir, username))?;
+        profile.save(&generated_file)?;
+
+        let mut db = self.load_database()?;
+        db.users.insert(username.to_string(), profile);
+        db.save(self.layout.database_path())?;
+        let _ = fs::remove_file(&progress_path);
+
+        outcome.fully_enrolled = outcome.samples_captured == samples;
+        outcome.generated_file = Some(generated_file);
+        Ok(outcome)
+    }
+
+    fn authenticate_user(&self, tolerance: f64, source_dir: &str) -> Result<StandaloneAuthResult> {
+        match self.capture()? {
+            Some(frame) => self.authenticate_image(tolerance, source_dir, &frame),
+            None => Err(anyhow!("Fake camera has no frames queued")),
+        }
+    }
+
+    fn authenticate_camera(&self, camera: u32, tolerance: f64, source_dir: &str) -> Result<StandaloneAuthResult> {
+        if camera == 0 {
+            return self.authenticate_user(tolerance, source_dir);
+        }
+        let name = self.cameras.lock().unwrap_or_else(PoisonError::into_inner)
+            .get_mut(&camera)
+            .and_then(VecDeque::pop_front)
+            .ok_or_else(|| anyhow!("Fake camera {} has no frames queued", camera))?;
+        self.authenticate_image(tolerance, source_dir, &self.save_capture(&name)?)
+    }
+
+    fn authenticate_image(&self, tolerance: f64, source_dir: &str, image: &Path) -> Result<StandaloneAuthResult> {
+        let probe = self.embedding(image)?;
+        let image_path = self.stored_path(image);
+        self.memory_frames().remove(image);
+        let result = self.match_probe(tolerance, source_dir, probe, image_path)?;
+        self.blur(image)?;
+        Ok(result)
+    }
+
+    /// The image is the fixture name, like a frame file's content
+    fn authenticate_image_bytes(&self, tolerance: f64, source_dir: &str, image: &[u8]) -> Result<StandaloneAuthResult> {
+        self.match_probe(tolerance, source_dir, self.fixture(&String::from_utf8_lossy(image))?, None)
+    }
+
+    /// A video file names one fixture per line, one frame each; the returned
+    /// backend captures its frames instead of the queued ones
+    fn with_camera_source(&self, source: &CameraSource) -> Result<Arc<dyn FaceBackend>> {
+        match source {
+            CameraSource::Device(0) => Ok(Arc::new(self.clone())),
+            CameraSource::Device(camera) => Err(anyhow!("The fake backend streams from camera 0 only, not {}", camera)),
+            CameraSource::VideoFile(path) => {
+                let video = fs::read_to_string(path).with_context(|| format!("Failed to read video {}", path.display()))?;
+                let frames = video.lines().map(str::trim).filter(|line| !line.is_empty()).map(String::from).collect();
+                Ok(Arc::new(Self { camera: Arc::new(Mutex::new(frames)), ..self.clone() }))
+            }
+        }
+    }
+
+    /// Captures are kept in memory in strict mode
+    fn set_privacy_mode(&self, mode: PrivacyMode) -> Result<()> {
+        self.strict_privacy.store(mode == PrivacyMode::Strict, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn set_frame_blur(&self, blur: FrameBlur) -> Result<()> {
+        *self.frame_blur.lock().unwrap_or_else(PoisonError::into_inner) = blur;
+        Ok(())
+    }
+
+    /// Consecutive frames with the same fixture name are a still scene and
+    /// fixtures without a face are empty ones; stops when the queue runs out
+    fn watch(
+        &self,
+        tolerance: f64,
+        source_dir: &str,
+        gate: &SceneGate,
+        on_result: &mut dyn FnMut(StandaloneAuthResult, &SceneStats) -> Result<bool>,
+    ) -> Result<SceneStats> {
+        let mut stats = SceneStats::default();
+        let mut previous = None;
+        while let Some(name) = self.camera().pop_front() {
+            stats.frames += 1;
+            if previous.replace(name.clone()).is_some_and(|previous| previous == name) {
+                stats.skipped_still += 1;
+                continue;
+            }
+            if gate.face_check && self.frames.get(&name).is_some_and(Vec::is_empty) {
+                stats.skipped_empty += 1;
+                continue;
+            }
+            stats.attempts += 1;
+            let result = self.authenticate_image(tolerance, source_dir, &self.save_capture(&name)?)?;
+            if !on_result(result, &stats)? {
+                break;
+            }
+        }
+        Ok(stats)
+    }
+
+    /// A track is a run of frames of the same fixture, tolerating up to
+    /// `max_missed_frames` frames without a face in between; tracks still in
+    /// view when the queue runs out exit
+    fn track_faces(
+        &self,
+        tolerance: f64,
+        source_dir: &str,
+        _gate: &SceneGate,
+        tracking: &TrackingConfig,
+        on_event: &mut dyn FnMut(TrackEvent<StandaloneAuthResult>, &SceneStats) -> Result<bool>,
+    ) -> Result<SceneStats> {
+        struct Track {
+            id: u64,
+            name: String,
+            user_id: Option<String>,
+            frames: u64,
+            missed: u32,
+        }
+        let exited = |track: Track| TrackEvent::Exited { track_id: track.id, user_id: track.user_id, frames: track.frames };
+
+        let mut stats = SceneStats::default();
+        let mut current: Option<Track> = None;
+        let mut next_id = 1;
+        #[cfg(feature = "attention")]
+        let mut attention = tracking.attention.clone().map(AttentionTracker::new);
+        #[cfg(feature = "attention")]
+        let mut look = |track_id: u64| -> Option<TrackEvent<StandaloneAuthResult>> {
+            let pose = self.head_poses.lock().unwrap_or_else(PoisonError::into_inner).pop_front()?;
+            let attention = attention.as_mut()?.update(track_id, &pose)?;
+            Some(TrackEvent::AttentionChanged { track_id, attention, pose })
+        };
+        while let Some(name) = self.camera().pop_front() {
+            stats.frames += 1;
+            if self.frames.get(&name).is_some_and(Vec::is_empty) {
+                stats.skipped_empty += 1;
+                let lost = current.as_mut().is_some_and(|track| {
+                    track.missed += 1;
+                    track.missed > tracking.max_missed_frames
+                });
+                if let Some(track) = current.take_if(|_| lost) {
+                    if !on_event(exited(track), &stats)? {
+                        return Ok(stats);
+                    }
+                }
+                continue;
+            }
+            if let Some(track) = current.as_mut().filter(|track| track.name == name) {
+                track.frames += 1;
+                track.missed = 0;
+                stats.tracked += 1;
+                #[cfg(feature = "attention")]
+                if let Some(event) = look(track.id) {
+                    if !on_event(event, &stats)? {
+                        return Ok(stats);
+                    }
+                }
+                continue;
+            }
+            if let Some(track) = current.take() {
+                if !on_event(exited(track), &stats)? {
+                    return Ok(stats);
+                }
+            }
+
+            stats.attempts += 1;
+            let result = self.authenticate_image(tolerance, source_dir, &self.save_capture(&name)?)?;
+            let track_id = next_id;
+            next_id += 1;
+            current = Some(Track { id: track_id, name, user_id: result.matched_user.clone(), frames: 1, missed: 0 });
+            if !on_event(TrackEvent::Entered { track_id, result }, &stats)? {
+                return Ok(stats);
+            }
+            #[cfg(feature = "attention")]
+            if let Some(event) = look(track_id) {
+                if !on_event(event, &stats)? {
+                    return Ok(stats);
+                }
+            }
+        }
+        if let Some(track) = current {
+            on_event(exited(track), &stats)?;
+        }
+        Ok(stats)
+    }
+
+    fn encode_images(&self, images: &[PathBuf]) -> Result<Vec<Option<Vec<f64>>>> {
+        images.iter().map(|image| Ok(self.embedding(image)?.map(<[f64]>::to_vec))).collect()
+    }
+
+    fn encode_document(&self, document: &Path) -> Result<DocumentFace> {
+        let encoding = self.embedding(document)?.map(<[f64]>::to_vec);
+        Ok(DocumentFace { faces: usize::from(encoding.is_some()), encoding, ..Default::default() })
+    }
+
+    /// The crop of a fixture with a face is a file naming the fixture too,
+    /// followed by one naming `bystander` for each bystander
+    fn crop_faces(&self, image: &Path, out_dir: &Path) -> Result<Vec<PathBuf>> {
+        let name = self.frame_name(image)?;
+        if self.fixture(&name)?.is_none() {
+            return Ok(Vec::new());
+        }
+        fs::create_dir_all(out_dir)?;
+        let bystanders = std::iter::repeat_n("bystander", self.bystanders.load(Ordering::Relaxed));
+        std::iter::once(name.as_str()).chain(bystanders).enumerate().map(|(index, face)| {
+            let crop = out_dir.join(format!("face_{}", index));
+            fs::write(&crop, format!("{}\ncropped", face))?;
+            Ok(crop)
+        }).collect()
+    }
+
+    /// Images are fixture names too; a fixture with a face has one, which is
+    /// also the largest
+    fn redact_faces(&self, image: &[u8], policy: &RedactionPolicy) -> Result<Redaction> {
+        let content = String::from_utf8_lossy(image);
+        let has_face = self.fixture(content.lines().next().unwrap_or_default())?.is_some();
+        let faces: Vec<DetectedFace> = has_face.then_some(DetectedFace {
+            bounds: FaceBox { top: 0, right: 100, bottom: 100, left: 0 },
+            redacted: !policy.keep_largest,
+        }).into_iter().collect();
+        let image = match policy.style {
+            _ if !faces.iter().any(|face| face.redacted) => image.to_vec(),
+            RedactionStyle::Blur => format!("{}\nredacted: blur", content.trim_end()).into_bytes(),
+            RedactionStyle::Pixelate { .. } => format!("{}\nredacted: pixelate", content.trim_end()).into_bytes(),
+        };
+        Ok(Redaction { image, faces })
+    }
+
+    fn warm_up(&self, camera: bool) -> Result<WarmUpOutcome> {
+        Ok(WarmUpOutcome { camera_ready: camera.then_some(true), elapsed_ms: 0 })
+    }
+
+    fn export_user(&self, username: &str, filename: &str) -> Result<bool> {
+        self.layout.export_user(username, filename)
+    }
+
+    fn import_user(&self, filename: &str) -> Result<bool> {
+        self.layout.import_user(filename)
+    }
+
+    fn check(&self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,358 @@
+//! Expected false accepts of a deployment against a budget
+//!
+//! The false-accept rate of a tolerance depends on how close strangers'
+//! faces are to the enrolled ones, and the chance that some enrolled user
+//! matches an impostor grows with every user added. A [`FarMonitor`]
+//! estimates both: impostor scores come from comparing enrolled users with
+//! each other and from the runner-up of every live attempt (never the person
+//! in front of the camera). Each attempt then consumes its chance of a false
+//! accept from the [`FarBudget`] of the current period, and a projection
+//! above the budget comes with the tolerance that would have kept within it.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::face_storage::{write_atomic, UserProfile};
+use crate::matching::face_distance;
+
+/// Live impostor scores kept; older ones are dropped first
+pub const MAX_LIVE_SCORES: usize = 10_000;
+
+/// Impostor scores computed from the enrolled users, at most
+pub const MAX_ENROLLED_SCORES: usize = 50_000;
+
+/// How often the enrolled users are compared again while attempts come in
+const RESEED_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Expected false accepts a deployment accepts per period
+#[derive(Debug, Clone, PartialEq)]
+pub struct FarBudget {
+    pub max_false_accepts: f64,
+    pub period: Duration,
+    /// Impostor scores needed before the estimate is trusted enough to alert
+    pub min_impostor_scores: usize,
+}
+
+impl Default for FarBudget {
+    fn default() -> Self {
+        Self { max_false_accepts: 1.0, period: Duration::from_secs(30 * 24 * 60 * 60), min_impostor_scores: 100 }
+    }
+}
+
+impl FarBudget {
+    pub fn new(max_false_accepts: f64, period: Duration) -> Self {
+        Self { max_false_accepts, period, ..Self::default() }
+    }
+
+    pub fn with_min_impostor_scores(mut self, scores: usize) -> Self {
+        self.min_impostor_scores = scores;
+        self
+    }
+}
+
+/// Distances between faces of different people, as the matcher sees them:
+/// a face against the closest sample of another user
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImpostorScores {
+    sorted: Vec<f64>,
+}
+
+impl ImpostorScores {
+    pub fn new(mut scores: Vec<f64>) -> Self {
+        scores.retain(|s| s.is_finite());
+        scores.sort_by(f64::total_cmp);
+        Self { sorted: scores }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sorted.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sorted.is_empty()
+    }
+
+    /// Share of impostor comparisons within `tolerance`
+    pub fn far(&self, tolerance: f64) -> f64 {
+        if self.sorted.is_empty() {
+            return 0.0;
+        }
+        self.sorted.partition_point(|s| *s <= tolerance) as f64 / self.sorted.len() as f64
+    }
+
+    /// Largest tolerance whose rate stays at or below `far`
+    pub fn tolerance_for(&self, far: f64) -> Option<f64> {
+        let allowed = (far * self.sorted.len() as f64).floor() as usize;
+        let first_excluded = *self.sorted.get(allowed)?;
+        // Just below the first score that would be accepted
+        Some((first_excluded - 1e-3).max(0.0))
+    }
+}
+
+/// Impostor scores between enrolled users: every sample against the closest
+/// sample of every other user
+///
+/// Quarantined samples are ignored. Users are sampled evenly when there are
+/// more than `max_scores` comparisons.
+pub fn enrolled_impostor_scores(profiles: &[UserProfile], max_scores: usize) -> Vec<f64> {
+    let samples: Vec<Vec<&[f64]>> = profiles.iter()
+        .map(|profile| profile.face_encodings.iter().filter(|s| !s.is_quarantined()).map(|s| s.encoding.as_slice()).collect())
+        .collect();
+    let total: usize = samples.iter().map(Vec::len).sum::<usize>() * profiles.len().saturating_sub(1);
+    let stride = total.div_ceil(max_scores.max(1)).max(1);
+    let mut scores = Vec::new();
+    let mut index = 0usize;
+    for (i, probes) in samples.iter().enumerate() {
+        for probe in probes {
+            for (j, other) in samples.iter().enumerate() {
+                if i == j {
+                    continue;
+                }
+                index += 1;
+                if !index.is_multiple_of(stride) {
+                    continue;
+                }
+                let closest = other.iter()
+                    .filter(|sample| sample.len() == probe.len())
+                    .map(|sample| face_distance(sample, probe))
+                    .min_by(f64::total_cmp);
+                scores.extend(closest);
+            }
+        }
+    }
+    scores
+}
+
+/// Expected false accepts against the budget, see the [module docs](self)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FarReport {
+    /// Tolerance of the last attempt
+    pub tolerance: f64,
+    pub enrolled_users: usize,
+    pub impostor_scores: usize,
+    /// Chance that an impostor matches one given user
+    pub far_per_comparison: f64,
+    /// Chance that an impostor matches any enrolled user
+    pub far_per_attempt: f64,
+    pub period_start: DateTime<Utc>,
+    pub attempts: u64,
+    /// False accepts expected so far this period, counting every attempt as
+    /// a possible impostor
+    pub expected_false_accepts: f64,
+    /// Expected false accepts over the whole period at the current rate
+    pub projected_false_accepts: f64,
+    pub budget: f64,
+    /// Tolerance that would keep the projection within budget, when the
+    /// current one doesn't
+    pub recommended_tolerance: Option<f64>,
+}
+
+impl FarReport {
+    pub fn within_budget(&self) -> bool {
+        self.projected_false_accepts <= self.budget
+    }
+}
+
+/// Consumption of the current period, kept across restarts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FarState {
+    period_start: DateTime<Utc>,
+    attempts: u64,
+    expected_false_accepts: f64,
+    /// Runner-up distances of live attempts, oldest first
+    live_scores: Vec<f64>,
+}
+
+impl FarState {
+    fn new() -> Self {
+        Self { period_start: Utc::now(), attempts: 0, expected_false_accepts: 0.0, live_scores: Vec::new() }
+    }
+}
+
+#[derive(Debug)]
+struct Seed {
+    enrolled_users: usize,
+    scores: Vec<f64>,
+    at: Instant,
+    /// Credentials changed since, see [`FarMonitor::mark_stale`]
+    stale: bool,
+}
+
+/// Tracks false-accept consumption of one deployment, see the [module docs](self)
+#[derive(Debug)]
+pub struct FarMonitor {
+    budget: FarBudget,
+    path: PathBuf,
+    state: Mutex<(FarState, Seed)>,
+}
+
+impl FarMonitor {
+    /// Resume the period stored at `path` and compare the enrolled `profiles`
+    pub fn load(budget: FarBudget, path: impl Into<PathBuf>, profiles: &[UserProfile]) -> Result<Self> {
+        let path = path.into();
+        let state = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                tracing::warn!("Starting a new false-accept period, {} is invalid: {}", path.display(), e);
+                FarState::new()
+            }),
+            Err(_) => FarState::new(),
+        };
+        let seed = Seed { enrolled_users: profiles.len(), scores: enrolled_impostor_scores(profiles, MAX_ENROLLED_SCORES), at: Instant::now(), stale: false };
+        Ok(Self { budget, path, state: Mutex::new((state, seed)) })
+    }
+
+    pub fn budget(&self) -> &FarBudget {
+        &self.budget
+    }
+
+    /// Whether the enrolled users should be compared again
+    pub fn needs_reseed(&self) -> bool {
+        let seed = &self.state.lock().unwrap_or_else(PoisonError::into_inner).1;
+        seed.stale || seed.at.elapsed() >= RESEED_INTERVAL
+    }
+
+    /// Compare the enrolled users again before the next attempt
+    pub fn mark_stale(&self) {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner).1.stale = true;
+    }
+
+    /// Replace the impostor scores of enrolled users, e.g. after enrollments
+    pub fn reseed(&self, profiles: &[UserProfile]) {
+        let scores = enrolled_impostor_scores(profiles, MAX_ENROLLED_SCORES);
+        self.state.lock().unwrap_or_else(PoisonError::into_inner).1 = Seed { enrolled_users: profiles.len(), scores, at: Instant::now(), stale: false };
+    }
+
+    /// Count an attempt at `tolerance`; `runner_up` is the distance of the
+    /// second-closest user, an impostor score whoever was in front of the camera
+    pub fn record_attempt(&self, tolerance: f64, runner_up: Option<f64>) -> FarReport {
+        let mut guard = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let (state, seed) = &mut *guard;
+        let now = Utc::now();
+        if (now - state.period_start).to_std().unwrap_or_default() >= self.budget.period {
+            let live_scores = std::mem::take(&mut state.live_scores);
+            *state = FarState { live_scores, ..FarState::new() };
+        }
+        if let Some(distance) = runner_up {
+            if state.live_scores.len() == MAX_LIVE_SCORES {
+                state.live_scores.remove(0);
+            }
+            state.live_scores.push(distance);
+        }
+        let scores = impostor_scores(state, seed);
+        let far_per_attempt = per_attempt(scores.far(tolerance), seed.enrolled_users);
+        state.attempts += 1;
+        state.expected_false_accepts += far_per_attempt;
+        self.report(state, seed, &scores, tolerance)
+    }
+
+    /// Estimate at `tolerance` without counting an attempt
+    pub fn report_at(&self, tolerance: f64) -> FarReport {
+        let guard = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let scores = impostor_scores(&guard.0, &guard.1);
+        self.report(&guard.0, &guard.1, &scores, tolerance)
+    }
+
+    /// Whether `report` should be raised as an alert
+    pub fn over_budget(&self, report: &FarReport) -> bool {
+        report.impostor_scores >= self.budget.min_impostor_scores && !report.within_budget()
+    }
+
+    /// Persist the period so a restart doesn't reset it
+    pub fn save(&self) -> Result<()> {
+        let json = serde_json::to_vec(&self.state.lock().unwrap_or_else(PoisonError::into_inner).0)?;
+        write_atomic(&self.path, &json)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn report(&self, state: &FarState, seed: &Seed, scores: &ImpostorScores, tolerance: f64) -> FarReport {
+        let far_per_comparison = scores.far(tolerance);
+        let far_per_attempt = per_attempt(far_per_comparison, seed.enrolled_users);
+        // Early in a period a few attempts shouldn't extrapolate wildly
+        let elapsed = (Utc::now() - state.period_start).to_std().unwrap_or_default().max(self.budget.period / 30);
+        let scale = self.budget.period.as_secs_f64() / elapsed.as_secs_f64();
+        let projected_false_accepts = state.expected_false_accepts * scale.max(1.0);
+        let recommended_tolerance = (projected_false_accepts > self.budget.max_false_accepts && state.attempts > 0)
+            .then(|| {
+                let projected_attempts = state.attempts as f64 * scale.max(1.0);
+                let allowed_per_attempt = self.budget.max_false_accepts / projected_attempts;
+                let allowed = 1.0 - (1.0 - allowed_per_attempt).powf(1.0 / seed.enrolled_users.max(1) as f64);
+                scores.tolerance_for(allowed).map(|t| t.min(tolerance))
+            })
+            .flatten();
+        FarReport {
+            tolerance,
+            enrolled_users: seed.enrolled_users,
+            impostor_scores: scores.len(),
+            far_per_comparison,
+            far_per_attempt,
+            period_start: state.period_start,
+            attempts: state.attempts,
+            expected_false_accepts: state.expected_false_accepts,
+            projected_false_accepts,
+            budget: self.budget.max_false_accepts,
+            recommended_tolerance,
+        }
+    }
+}
+
+fn impostor_scores(state: &FarState, seed: &Seed) -> ImpostorScores {
+    ImpostorScores::new(seed.scores.iter().chain(&state.live_scores).copied().collect())
+}
+
+/// Chance that an impostor matches at least one of `users`
+fn per_attempt(far_per_comparison: f64, users: usize) -> f64 {
+    1.0 - (1.0 - far_per_comparison).powi(users.min(i32::MAX as usize) as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(user_id: &str, encodings: &[[f64; 2]]) -> UserProfile {
+        let samples: Vec<_> = encodings.iter().enumerate()
+            .map(|(i, e)| serde_json::json!({ "encoding": e, "timestamp": "", "sample_id": format!("{}_{}", user_id, i) }))
+            .collect();
+        serde_json::from_value(serde_json::json!({ "user_id": user_id, "face_encodings": samples })).unwrap()
+    }
+
+    #[test]
+    fn test_impostor_scores_use_the_closest_sample_of_other_users() {
+        let profiles = [profile("ann", &[[0.0, 0.0], [0.1, 0.0]]), profile("bob", &[[0.5, 0.0]])];
+        let scores = ImpostorScores::new(enrolled_impostor_scores(&profiles, MAX_ENROLLED_SCORES));
+        // ann's two samples against bob, bob against ann's closest sample
+        assert_eq!(scores.sorted, [0.4, 0.4, 0.5]);
+        assert!((scores.far(0.45) - 2.0 / 3.0).abs() < 1e-12);
+        assert_eq!(scores.far(0.3), 0.0);
+        assert!(scores.tolerance_for(0.0).unwrap() < 0.4);
+        assert_eq!(enrolled_impostor_scores(&profiles, 1).len(), 1);
+    }
+
+    #[test]
+    fn test_loose_tolerance_exceeds_the_budget_and_a_tighter_one_is_recommended() {
+        let dir = tempfile::tempdir().unwrap();
+        // Ten users spread out along a line, 0.1 apart
+        let profiles: Vec<_> = (0..10).map(|i| profile(&format!("user{}", i), &[[i as f64 * 0.1, 0.0]])).collect();
+        let budget = FarBudget::new(1.0, Duration::from_secs(3600)).with_min_impostor_scores(10);
+        let monitor = FarMonitor::load(budget, dir.path().join("far.json"), &profiles).unwrap();
+
+        let mut report = monitor.report_at(0.6);
+        for _ in 0..20 {
+            report = monitor.record_attempt(0.6, Some(0.45));
+        }
+        assert!(monitor.over_budget(&report), "{:?}", report);
+        let recommended = report.recommended_tolerance.unwrap();
+        assert!(recommended < 0.1, "{}", recommended);
+        assert_eq!(monitor.report_at(recommended).far_per_comparison, 0.0);
+
+        monitor.save().unwrap();
+        let resumed = FarMonitor::load(FarBudget::default(), dir.path().join("far.json"), &profiles).unwrap();
+        assert_eq!(resumed.report_at(0.6).attempts, 20);
+    }
+}
@@ -0,0 +1,133 @@
+use crate::denial::DenialReason;
+use crate::messages::{CaptureTip, MessageCatalog};
+
+/// Moment of an enrollment or authentication worth telling the user about
+#[derive(Debug, Clone, PartialEq)]
+pub enum FeedbackEvent {
+    /// The camera is about to capture
+    LookAtCamera,
+    /// Capturing finished; matching or enrollment follows
+    CaptureDone,
+    AccessGranted { display_name: String },
+    AccessDenied { reason: Option<DenialReason> },
+}
+
+impl FeedbackEvent {
+    /// What to say or show for this event in the catalog's locale
+    pub fn message(&self, messages: &MessageCatalog) -> String {
+        match self {
+            FeedbackEvent::LookAtCamera => messages.tip(CaptureTip::LookAtCamera),
+            FeedbackEvent::CaptureDone => messages.get("feedback.capture_done", &[]),
+            FeedbackEvent::AccessGranted { display_name } => {
+                messages.get("feedback.access_granted", &[("name", display_name.clone())])
+            }
+            FeedbackEvent::AccessDenied { reason } => {
+                let denied = messages.get("feedback.access_denied", &[]);
+                match reason.as_ref().and_then(DenialReason::tip) {
+                    Some(tip) => format!("{} {}", denied, messages.tip(tip)),
+                    None => denied,
+                }
+            }
+        }
+    }
+}
+
+/// Receives [`FeedbackEvent`]s, e.g. to play a sound or speak to the user
+///
+/// Called on the thread running the operation, so implementations should
+/// hand slow work off instead of blocking. Closures taking a
+/// `&FeedbackEvent` implement it.
+pub trait Feedback: Send + Sync {
+    fn notify(&self, event: &FeedbackEvent);
+}
+
+impl<F: Fn(&FeedbackEvent) + Send + Sync> Feedback for F {
+    fn notify(&self, event: &FeedbackEvent) {
+        self(event)
+    }
+}
+
+/// Shared [`Feedback`] that can sit in `Debug` structs
+#[cfg(feature = "python-backend")]
+#[derive(Clone)]
+pub(crate) struct FeedbackHandle(pub(crate) std::sync::Arc<dyn Feedback>);
+
+#[cfg(feature = "python-backend")]
+impl std::fmt::Debug for FeedbackHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("FeedbackHandle")
+    }
+}
+
+#[cfg(feature = "speech")]
+pub use speech::SpeechFeedback;
+
+#[cfg(feature = "speech")]
+mod speech {
+    use std::process::{Command, Stdio};
+
+    use super::{Feedback, FeedbackEvent};
+    use crate::messages::MessageCatalog;
+
+    /// Speaks events with the platform's text-to-speech command: `say` on
+    /// macOS, `espeak` elsewhere
+    ///
+    /// Speech runs in the background; if the command is missing, a warning is logged.
+    #[derive(Debug, Clone)]
+    pub struct SpeechFeedback {
+        messages: MessageCatalog,
+        program: String,
+        args: Vec<String>,
+    }
+
+    impl SpeechFeedback {
+        pub fn new(messages: MessageCatalog) -> Self {
+            let (program, args) = if cfg!(target_os = "macos") {
+                ("say", Vec::new())
+            } else {
+                let language = messages.locale().split(['-', '_']).next().unwrap_or("en").to_string();
+                ("espeak", vec!["-v".to_string(), language])
+            };
+            Self { messages, program: program.to_string(), args }
+        }
+
+        /// Speak with `program`, which gets `args` followed by the text
+        pub fn with_command(mut self, program: impl Into<String>, args: Vec<String>) -> Self {
+            self.program = program.into();
+            self.args = args;
+            self
+        }
+    }
+
+    impl Feedback for SpeechFeedback {
+        fn notify(&self, event: &FeedbackEvent) {
+            let text = event.message(&self.messages);
+            let spawned = Command::new(&self.program)
+                .args(&self.args)
+                .arg(&text)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn();
+            match spawned {
+                // Reap the process without waiting for it to finish speaking
+                Ok(mut child) => drop(std::thread::spawn(move || child.wait())),
+                Err(e) => tracing::warn!("Failed to run {}: {}", self.program, e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_messages_are_localized() {
+        let german = MessageCatalog::builtin("de");
+        let granted = FeedbackEvent::AccessGranted { display_name: "Ann".to_string() };
+        assert_eq!(granted.message(&german), "Willkommen, Ann");
+        let denied = FeedbackEvent::AccessDenied { reason: Some(DenialReason::NoFace) };
+        assert_eq!(denied.message(&MessageCatalog::english()), "Access denied. Look straight at the camera");
+    }
+}
@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+
+use crate::standalone_python::StandaloneAuthResult;
+
+/// How the views of several cameras at one authentication point combine
+/// into one decision, see [`crate::FaceAuthBuilder::cameras`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FusionStrategy {
+    /// The closest view decides, so one camera blinded by glare or seeing a
+    /// turned head doesn't matter
+    #[default]
+    Best,
+    /// The user closest in most views wins, with the average distance of
+    /// those views; steadier against one lucky frame
+    Average,
+}
+
+/// What one camera saw in a fused authentication
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraScore {
+    pub camera: u32,
+    /// Closest user in this view, `None` if no face was found
+    pub closest_user: Option<String>,
+    pub distance: Option<f64>,
+}
+
+/// Fuse per-camera results into one; `views` must not be empty
+pub(crate) fn fuse(views: Vec<(u32, StandaloneAuthResult)>, strategy: FusionStrategy) -> (StandaloneAuthResult, Vec<CameraScore>) {
+    let scores: Vec<CameraScore> = views.iter()
+        .map(|(camera, view)| CameraScore { camera: *camera, closest_user: view.closest_user.clone(), distance: view.distance })
+        .collect();
+    let processing_time_ms = views.iter().filter_map(|(_, view)| view.processing_time_ms).max();
+    let with_face: Vec<(usize, &str, f64)> = views.iter().enumerate()
+        .filter_map(|(i, (_, view))| Some((i, view.closest_user.as_deref()?, view.distance?)))
+        .collect();
+
+    let (chosen, mean) = match strategy {
+        FusionStrategy::Best => (closest(&with_face), None),
+        FusionStrategy::Average => {
+            let mut users: Vec<(&str, usize, f64)> = Vec::new();
+            for &(_, user, distance) in &with_face {
+                match users.iter_mut().find(|(u, ..)| *u == user) {
+                    Some((_, votes, sum)) => {
+                        *votes += 1;
+                        *sum += distance;
+                    }
+                    None => users.push((user, 1, distance)),
+                }
+            }
+            let winner = users.into_iter()
+                .map(|(user, votes, sum)| (user, votes, sum / votes as f64))
+                .min_by(|a, b| b.1.cmp(&a.1).then(a.2.total_cmp(&b.2)));
+            match winner {
+                Some((user, _, mean)) => (closest(with_face.iter().filter(|c| c.1 == user)), Some(mean)),
+                None => (None, None),
+            }
+        }
+    };
+
+    let mut fused = views.into_iter().nth(chosen.unwrap_or(0)).map(|(_, view)| view).expect("at least one camera view");
+    if let Some(mean) = mean {
+        let is_match = fused.threshold.is_some_and(|threshold| mean <= threshold);
+        fused.distance = Some(mean);
+        fused.confidence = Some((1.0 - mean).max(0.0));
+        fused.is_match = Some(is_match);
+        fused.matched_user = fused.closest_user.clone().filter(|_| is_match);
+    }
+    fused.processing_time_ms = processing_time_ms;
+    (fused, scores)
+}
+
+/// Index of the view with the smallest distance
+fn closest<'a>(candidates: impl IntoIterator<Item = &'a (usize, &'a str, f64)>) -> Option<usize> {
+    candidates.into_iter().min_by(|a, b| a.2.total_cmp(&b.2)).map(|c| c.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timing::TimingBreakdown;
+
+    fn view(user: Option<&str>, distance: Option<f64>) -> StandaloneAuthResult {
+        let is_match = distance.is_some_and(|d| d <= 0.6);
+        StandaloneAuthResult {
+            success: true,
+            is_match: Some(is_match),
+            confidence: distance.map(|d| 1.0 - d),
+            distance,
+            threshold: Some(0.6),
+            matched_user: user.filter(|_| is_match).map(str::to_string),
+            closest_user: user.map(str::to_string),
+            runner_up: None,
+            sample_distances: Vec::new(),
+            brightness: None,
+            image_path: None,
+            processing_time_ms: Some(100),
+            timings: TimingBreakdown::default(),
+            liveness: None,
+            denial: None,
+            raw_output: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_views_fuse_by_strategy() {
+        let views = || vec![
+            (0, view(Some("ann"), Some(0.55))),
+            (1, view(None, None)),
+            (2, view(Some("ann"), Some(0.75))),
+            (3, view(Some("bob"), Some(0.5))),
+        ];
+
+        let (best, scores) = fuse(views(), FusionStrategy::Best);
+        assert_eq!(best.matched_user.as_deref(), Some("bob"));
+        assert_eq!(scores.len(), 4);
+        assert_eq!(scores[1], CameraScore { camera: 1, closest_user: None, distance: None });
+
+        let (average, _) = fuse(views(), FusionStrategy::Average);
+        assert_eq!(average.closest_user.as_deref(), Some("ann"));
+        assert!((average.distance.unwrap() - 0.65).abs() < 1e-9);
+        assert_eq!((average.is_match, average.matched_user), (Some(false), None));
+
+        let (blind, _) = fuse(vec![(0, view(None, None)), (1, view(None, None))], FusionStrategy::Average);
+        assert_eq!(blind.distance, None);
+    }
+}
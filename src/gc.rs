@@ -0,0 +1,342 @@
+//! Removing orphaned files
+//!
+//! Long-running deployments pile up files nothing refers to any more: frames
+//! captured for attempts that never reached the audit log, generated
+//! credentials of users since removed, temporary files of interrupted writes
+//! and verifications, and old exports. [`collect_garbage`] finds and removes
+//! them, see [`FaceAuth::gc`](crate::FaceAuth::gc).
+//!
+//! Files are aged by their modification time against a `now` on the same
+//! system clock, never against an instance's [`Clock`](crate::Clock).
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::audit::AuditEntry;
+use crate::face_storage::{self, FaceDatabase, StorageLayout, UserProfile};
+
+/// Captured frames younger than this may belong to an attempt that isn't
+/// audited yet
+pub const CAPTURE_GRACE: Duration = Duration::from_secs(300);
+
+/// Temporary files older than this were left by an interrupted write
+pub const STALE_TEMP_AGE: Duration = Duration::from_secs(3600);
+
+/// Prefixes of the dot-files imports, transfers and NFC tags are unpacked to
+/// in the data directory, all ending in `.json`
+const TEMP_JSON_PREFIXES: [&str; 3] = [".import_", ".transfer_", ".nfc_"];
+
+/// Why a file is garbage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Orphan {
+    /// Captured frame of no audited attempt, used by no sample
+    Capture,
+    /// Generated credential of a user in neither the database nor the source
+    /// directory
+    GeneratedCredential,
+    /// `*.tmp` file, `temp_capture.jpg` or unpacked import left behind
+    TempFile,
+    /// Credential copy of a 1:1 verification that never finished, see
+    /// [`StorageLayout::claims_dir`]
+    Claim,
+    /// Export older than the retention
+    Export,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrphanedFile {
+    pub path: PathBuf,
+    pub kind: Orphan,
+    pub bytes: u64,
+}
+
+/// Files removed by a collection
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GcReport {
+    pub removed: Vec<OrphanedFile>,
+}
+
+impl GcReport {
+    pub fn reclaimed_bytes(&self) -> u64 {
+        self.removed.iter().map(|file| file.bytes).sum()
+    }
+
+    pub fn count(&self, kind: Orphan) -> usize {
+        self.removed.iter().filter(|file| file.kind == kind).count()
+    }
+}
+
+/// Files of `layout` that are garbage at `now`
+///
+/// `audit` is the audit log's entries, which name the captures they were
+/// decided on; exports are kept forever without an `export_retention`.
+pub fn find_orphans(layout: &StorageLayout, audit: &[AuditEntry], now: SystemTime, export_retention: Option<Duration>) -> Result<Vec<OrphanedFile>> {
+    let mut orphans = Vec::new();
+
+    let mut temp_dirs = vec![
+        layout.data_dir.clone(),
+        layout.generated_dir.clone(),
+        layout.source_dir.clone(),
+        layout.captures_dir(),
+        layout.exports_dir(),
+        layout.thumbnails_dir(),
+        layout.registration_progress_dir(),
+    ];
+    temp_dirs.sort();
+    temp_dirs.dedup();
+    for dir in &temp_dirs {
+        for (path, bytes, age) in files(dir, now)? {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if is_temp_file(&name) && age >= STALE_TEMP_AGE {
+                orphans.push(OrphanedFile { path, kind: Orphan::TempFile, bytes });
+            }
+        }
+    }
+    let is_temp = |path: &Path| orphans.iter().any(|o: &OrphanedFile| o.path == path);
+
+    let mut referenced = referenced_file_names(layout)?;
+    referenced.extend(audit.iter().filter_map(|entry| entry.capture.as_ref()).map(OsString::from));
+    let mut captures = Vec::new();
+    for (path, bytes, age) in files(&layout.captures_dir(), now)? {
+        if age < CAPTURE_GRACE || is_temp(&path) || path.file_name().is_some_and(|name| referenced.contains(name)) {
+            continue;
+        }
+        captures.push(OrphanedFile { path, kind: Orphan::Capture, bytes });
+    }
+
+    let mut claims = Vec::new();
+    for claim in subdirectories(&layout.claims_dir())? {
+        for (path, bytes, age) in files(&claim, now)? {
+            if age >= STALE_TEMP_AGE {
+                claims.push(OrphanedFile { path, kind: Orphan::Claim, bytes });
+            }
+        }
+    }
+
+    let users: HashSet<String> = FaceDatabase::load(layout.database_path())?.unwrap_or_default().users.into_keys().collect();
+    let mut credentials = Vec::new();
+    if layout.generated_dir != layout.source_dir {
+        for path in face_storage::credential_files(&layout.generated_dir)? {
+            let Ok(profile) = UserProfile::load(&path) else { continue };
+            if !users.contains(&profile.user_id) && !face_storage::credential_path(&layout.source_dir, &profile.user_id).exists() {
+                let bytes = fs::metadata(&path)?.len();
+                credentials.push(OrphanedFile { path, kind: Orphan::GeneratedCredential, bytes });
+            }
+        }
+    }
+
+    let mut exports = Vec::new();
+    if let Some(retention) = export_retention {
+        for (path, bytes, age) in files(&layout.exports_dir(), now)? {
+            if age > retention && !is_temp(&path) {
+                exports.push(OrphanedFile { path, kind: Orphan::Export, bytes });
+            }
+        }
+    }
+
+    orphans.extend(captures);
+    orphans.extend(claims);
+    orphans.extend(credentials);
+    orphans.extend(exports);
+    Ok(orphans)
+}
+
+/// Remove the files of `layout` that are garbage at `now`, see [`find_orphans`]
+///
+/// A file that can't be removed is skipped with a warning. Claim directories
+/// are removed once empty.
+pub fn collect_garbage(layout: &StorageLayout, audit: &[AuditEntry], now: SystemTime, export_retention: Option<Duration>) -> Result<GcReport> {
+    let mut report = GcReport::default();
+    for orphan in find_orphans(layout, audit, now, export_retention)? {
+        match fs::remove_file(&orphan.path) {
+            Ok(()) => {
+                tracing::debug!(path = %orphan.path.display(), kind = ?orphan.kind, "Removed orphaned file");
+                if let (Orphan::Claim, Some(claim)) = (orphan.kind, orphan.path.parent()) {
+                    let _ = fs::remove_dir(claim);
+                }
+                report.removed.push(orphan);
+            }
+            Err(e) => tracing::warn!("Failed to remove {}: {}", orphan.path.display(), e),
+        }
+    }
+    Ok(report)
+}
+
+/// Whether a file name is one of the temporary files writes leave behind
+/// when interrupted
+///
+/// Lock files and records such as `.camera.lock` are dot-files too, but are
+/// never matched.
+fn is_temp_file(name: &str) -> bool {
+    name.ends_with(".tmp")
+        || name == "temp_capture.jpg"
+        || (name.ends_with(".json") && TEMP_JSON_PREFIXES.iter().any(|prefix| name.starts_with(prefix)))
+}
+
+/// Directories directly in `dir`
+fn subdirectories(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut dirs: Vec<PathBuf> = entries.flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+        .map(|entry| entry.path())
+        .collect();
+    dirs.sort();
+    Ok(dirs)
+}
+
+/// Regular files directly in `dir` with their size and age at `now`
+fn files(dir: &Path, now: SystemTime) -> Result<Vec<(PathBuf, u64, Duration)>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut files = Vec::new();
+    for entry in entries.flatten() {
+        let Ok(metadata) = entry.metadata() else { continue };
+        if !metadata.is_file() {
+            continue;
+        }
+        // Files from the future are as young as can be
+        let age = metadata.modified().ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .unwrap_or_default();
+        files.push((entry.path(), metadata.len(), age));
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Names of the files that samples, enrollments in progress and pending
+/// enrollments refer to
+fn referenced_file_names(layout: &StorageLayout) -> Result<HashSet<OsString>> {
+    let mut documents = vec![layout.database_path()];
+    for dir in [&layout.generated_dir, &layout.source_dir] {
+        documents.extend(face_storage::credential_files(dir)?);
+    }
+    for dir in [layout.enrollment_flows_dir(), layout.pending_enrollments_dir(), layout.registration_progress_dir()] {
+        documents.extend(face_storage::credential_files(&dir)?);
+    }
+
+    let mut names = HashSet::new();
+    for path in documents {
+        let Ok(content) = fs::read_to_string(&path) else { continue };
+        match serde_json::from_str::<serde_json::Value>(&content) {
+            Ok(document) => collect_file_names(&document, &mut names),
+            Err(e) => tracing::warn!("Can't tell which captures {} refers to: {}", path.display(), e),
+        }
+    }
+    Ok(names)
+}
+
+fn collect_file_names(value: &serde_json::Value, names: &mut HashSet<OsString>) {
+    match value {
+        serde_json::Value::String(s) => {
+            if let Some(name) = Path::new(s).file_name() {
+                names.insert(name.to_os_string());
+            }
+        }
+        serde_json::Value::Array(values) => values.iter().for_each(|v| collect_file_names(v, names)),
+        serde_json::Value::Object(map) => map.values().for_each(|v| collect_file_names(v, names)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::{AuditOperation, AuditOutcome};
+    use std::time::SystemTime;
+
+    fn write(path: &Path, contents: &str, age: Duration) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    #[test]
+    fn test_orphans_are_collected() {
+        let root = tempfile::tempdir().unwrap();
+        let layout = StorageLayout {
+            data_dir: root.path().to_path_buf(),
+            generated_dir: root.path().join("generated"),
+            source_dir: root.path().join("source"),
+        };
+        let hour = Duration::from_secs(3600);
+        let profile = |user: &str, image: &str| serde_json::json!({
+            "user_id": user,
+            "face_encodings": [{ "encoding": [0.1], "timestamp": "2024-03-01T09:00:00", "sample_id": "s1", "image_path": image }],
+        }).to_string();
+
+        let captures = layout.captures_dir();
+        write(&captures.join("authentication_audited.jpg"), "frame", 2 * hour);
+        write(&captures.join("authentication_orphan.jpg"), "frame", 3 * hour);
+        write(&captures.join("authentication_recent.jpg"), "frame", Duration::from_secs(10));
+        write(&captures.join("registration_ann_sample1.jpg"), "frame", 5 * hour);
+        write(&root.path().join("temp_capture.jpg"), "frame", 2 * hour);
+        write(&layout.generated_dir.join(".ann.json.tmp"), "{", 2 * hour);
+        write(&layout.generated_dir.join(".bob.json.tmp"), "{", Duration::from_secs(10));
+        write(&face_storage::credential_path(&layout.generated_dir, "ann"), &profile("ann", "./captured_images/registration_ann_sample1.jpg"), hour);
+        write(&face_storage::credential_path(&layout.generated_dir, "gone"), &profile("gone", "x.jpg"), hour);
+        write(&face_storage::credential_path(&layout.source_dir, "ann"), &profile("ann", "x.jpg"), hour);
+        write(&layout.exports_dir().join("ann_credentials_old.json"), "{}", 100 * hour);
+        write(&layout.exports_dir().join("ann_credentials_new.json"), "{}", hour);
+        write(&root.path().join(".import_x1.json"), "{}", 2 * hour);
+        write(&root.path().join(".nfc_received_x2.json"), "{}", 2 * hour);
+        write(&root.path().join(".transfer_x3.json"), "{}", Duration::from_secs(10));
+        write(&layout.claims_dir().join("0a1b").join("ann.json"), "{}", 2 * hour);
+        write(&layout.claims_dir().join("2c3d").join("bob.json"), "{}", Duration::from_secs(10));
+        // Locks and their records are dot-files too, but never garbage
+        write(&layout.camera_lock_path(), "", 5 * hour);
+        write(&root.path().join(".camera.json"), "{}", 5 * hour);
+        write(&layout.journal_dir().join(".recovery.lock"), "", 5 * hour);
+
+        // Only the entry naming a capture keeps it, whenever it was written
+        let audited = |capture: Option<&str>| {
+            let mut entry = AuditEntry::new(AuditOperation::Authentication, None, AuditOutcome::Denied);
+            entry.capture = capture.map(str::to_string);
+            entry
+        };
+        let audit = [audited(Some("authentication_audited.jpg")), audited(None)];
+        let now = SystemTime::now();
+        let report = collect_garbage(&layout, &audit, now, Some(24 * hour)).unwrap();
+
+        let mut removed: Vec<_> = report.removed.iter()
+            .map(|file| (file.kind, file.path.file_name().unwrap().to_string_lossy().into_owned()))
+            .collect();
+        removed.sort_by(|a, b| a.1.cmp(&b.1));
+        assert_eq!(removed, [
+            (Orphan::TempFile, ".ann.json.tmp".to_string()),
+            (Orphan::TempFile, ".import_x1.json".to_string()),
+            (Orphan::TempFile, ".nfc_received_x2.json".to_string()),
+            (Orphan::Claim, "ann.json".to_string()),
+            (Orphan::Export, "ann_credentials_old.json".to_string()),
+            (Orphan::Capture, "authentication_orphan.jpg".to_string()),
+            (Orphan::GeneratedCredential, "gone.json".to_string()),
+            (Orphan::TempFile, "temp_capture.jpg".to_string()),
+        ]);
+        assert!(report.removed.iter().all(|file| !file.path.exists()));
+        assert_eq!(report.reclaimed_bytes(), report.removed.iter().map(|f| f.bytes).sum::<u64>());
+        assert!(captures.join("authentication_audited.jpg").exists());
+        assert!(captures.join("registration_ann_sample1.jpg").exists());
+        assert!(!layout.claims_dir().join("0a1b").exists());
+        assert!(layout.claims_dir().join("2c3d").join("bob.json").exists());
+        assert!(layout.camera_lock_path().exists() && root.path().join(".camera.json").exists());
+        assert!(layout.journal_dir().join(".recovery.lock").exists());
+        assert!(root.path().join(".transfer_x3.json").exists());
+
+        // Nothing is left to collect, and exports stay without a retention
+        assert!(collect_garbage(&layout, &audit, now, None).unwrap().removed.is_empty());
+    }
+}
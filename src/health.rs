@@ -0,0 +1,206 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Errors kept for the next heartbeat; older ones are dropped
+const MAX_PENDING_ERRORS: usize = 50;
+
+/// State of a device, sent as heartbeat; `/healthz` serves its [`HealthProbe`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthStatus {
+    pub device_id: Option<String>,
+    pub uptime_secs: u64,
+    /// Whether the camera delivered a frame at the last attempt; `None` before the first
+    pub camera_ready: Option<bool>,
+    /// Size of the face database file
+    pub database_bytes: u64,
+    pub enrolled_users: usize,
+    pub last_successful_auth: Option<DateTime<Utc>>,
+    /// Errors since the last heartbeat, oldest first
+    pub pending_errors: Vec<String>,
+}
+
+impl HealthStatus {
+    /// False once the camera is known to be broken
+    ///
+    /// Pending errors are only reported; a single failed capture shouldn't get
+    /// the process restarted by an orchestrator.
+    pub fn is_healthy(&self) -> bool {
+        self.camera_ready != Some(false)
+    }
+}
+
+/// The part of a [`HealthStatus`] an unauthenticated probe gets
+///
+/// Pending errors carry paths, usernames and script output, and the user
+/// count is nobody's business but the fleet's; both stay in the heartbeat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthProbe {
+    pub healthy: bool,
+    pub camera_ready: Option<bool>,
+    pub uptime_secs: u64,
+}
+
+impl From<&HealthStatus> for HealthProbe {
+    fn from(status: &HealthStatus) -> Self {
+        Self { healthy: status.is_healthy(), camera_ready: status.camera_ready, uptime_secs: status.uptime_secs }
+    }
+}
+
+#[derive(Debug, Default)]
+struct HealthState {
+    camera_ready: Option<bool>,
+    last_successful_auth: Option<DateTime<Utc>>,
+    pending_errors: Vec<String>,
+}
+
+/// Collects what [`HealthStatus`] reports while a [`crate::FaceAuth`] runs
+#[derive(Debug)]
+pub struct HealthMonitor {
+    started: Instant,
+    state: Mutex<HealthState>,
+}
+
+impl Default for HealthMonitor {
+    fn default() -> Self {
+        Self { started: Instant::now(), state: Mutex::default() }
+    }
+}
+
+impl HealthMonitor {
+    fn state(&self) -> std::sync::MutexGuard<'_, HealthState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn record_success(&self) {
+        self.state().last_successful_auth = Some(Utc::now());
+    }
+
+    pub fn record_camera(&self, ready: bool) {
+        self.state().camera_ready = Some(ready);
+    }
+
+    pub fn record_error(&self, error: &anyhow::Error) {
+        let mut state = self.state();
+        if state.pending_errors.len() == MAX_PENDING_ERRORS {
+            state.pending_errors.remove(0);
+        }
+        state.pending_errors.push(format!("{}: {:#}", Utc::now().format("%Y-%m-%dT%H:%M:%SZ"), error));
+    }
+
+    /// Fill in the monitored fields of `status`
+    pub fn fill(&self, status: &mut HealthStatus) {
+        let state = self.state();
+        status.uptime_secs = self.uptime().as_secs();
+        status.camera_ready = state.camera_ready;
+        status.last_successful_auth = state.last_successful_auth;
+        status.pending_errors = state.pending_errors.clone();
+    }
+
+    /// Forget the first `count` pending errors, once they have been reported
+    pub fn clear_errors(&self, count: usize) {
+        let mut state = self.state();
+        let count = count.min(state.pending_errors.len());
+        state.pending_errors.drain(..count);
+    }
+}
+
+/// Background thread running `beat` every `interval`, e.g. publishing a
+/// [`HealthStatus`]
+///
+/// Failures are logged and retried at the next interval. Stops when dropped.
+pub struct Heartbeat {
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Heartbeat {
+    pub fn start(interval: Duration, beat: impl Fn() -> Result<()> + Send + 'static) -> Self {
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread = thread::spawn({
+            let stop = stop.clone();
+            move || loop {
+                if let Err(e) = beat() {
+                    tracing::warn!("Heartbeat failed: {:#}", e);
+                }
+                let (stopped, wake) = &*stop;
+                let stopped = stopped.lock().unwrap_or_else(PoisonError::into_inner);
+                let (stopped, _) = wake.wait_timeout_while(stopped, interval, |stopped| !*stopped)
+                    .unwrap_or_else(PoisonError::into_inner);
+                if *stopped {
+                    break;
+                }
+            }
+        });
+        Self { stop, thread: Some(thread) }
+    }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        let (stopped, wake) = &*self.stop;
+        *stopped.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        wake.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Publisher POSTing each status as JSON to `url`
+#[cfg(feature = "server")]
+pub fn http_publisher(url: impl Into<String>) -> impl Fn(&HealthStatus) -> Result<()> + Send + 'static {
+    let url = url.into();
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .http_status_as_error(false)
+        .timeout_global(Some(Duration::from_secs(10)))
+        .build()
+        .into();
+    move |status| {
+        let response = agent.post(&url).send_json(status).map_err(|e| anyhow::anyhow!("{} unreachable: {}", url, e))?;
+        if !response.status().is_success() {
+            anyhow::bail!("{} answered {}", url, response.status());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_monitor_reports_and_clears_errors() {
+        let monitor = HealthMonitor::default();
+        let mut status = HealthStatus {
+            device_id: None,
+            uptime_secs: 0,
+            camera_ready: None,
+            database_bytes: 0,
+            enrolled_users: 0,
+            last_successful_auth: None,
+            pending_errors: Vec::new(),
+        };
+        monitor.record_success();
+        for i in 0..MAX_PENDING_ERRORS + 2 {
+            monitor.record_error(&anyhow::anyhow!("capture {} failed", i));
+        }
+        monitor.fill(&mut status);
+        assert!(status.is_healthy() && status.last_successful_auth.is_some());
+        assert_eq!(status.pending_errors.len(), MAX_PENDING_ERRORS);
+        assert!(status.pending_errors[0].ends_with("capture 2 failed"));
+
+        monitor.record_camera(false);
+        monitor.clear_errors(MAX_PENDING_ERRORS - 1);
+        monitor.fill(&mut status);
+        assert!(!status.is_healthy());
+        assert_eq!(status.pending_errors.len(), 1);
+    }
+}
@@ -0,0 +1,128 @@
+use anyhow::Result;
+use serde_json::{Value, json};
+use std::collections::HashSet;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use crate::mqtt::{MqttClient, MqttOptions};
+
+/// Topic prefix Home Assistant listens on for MQTT discovery
+pub const DISCOVERY_PREFIX: &str = "homeassistant";
+
+/// Base of this crate's state topics, followed by the node id
+pub const STATE_PREFIX: &str = "face_auth";
+
+/// Home Assistant device with binary sensors for "person at door" and
+/// "recognized: <user>", announced through MQTT discovery
+///
+/// Sensors switch on with each decision and back off by themselves after
+/// `off_delay`, so no YAML is needed on the Home Assistant side. A sensor is
+/// announced for each user the first time they're recognized.
+#[derive(Debug)]
+pub struct HomeAssistant {
+    client: MqttClient,
+    node_id: String,
+    off_delay: Duration,
+    announced: Mutex<HashSet<String>>,
+}
+
+impl HomeAssistant {
+    /// Connect to the broker and announce the device as `node_id`; it shows
+    /// as unavailable when the connection drops
+    pub fn connect(options: MqttOptions, node_id: &str) -> Result<Self> {
+        let node_id = object_id(node_id);
+        let availability = format!("{}/{}/availability", STATE_PREFIX, node_id);
+        let client = MqttClient::connect(options.with_last_will(&availability, "offline"))?;
+        let assistant = Self { client, node_id, off_delay: Duration::from_secs(10), announced: Mutex::new(HashSet::new()) };
+        assistant.announce(None)?;
+        assistant.client.publish(&availability, b"online", true)?;
+        Ok(assistant)
+    }
+
+    /// How long sensors stay on after a detection (default 10s)
+    pub fn with_off_delay(mut self, off_delay: Duration) -> Result<Self> {
+        self.off_delay = off_delay;
+        self.announce(None)?;
+        Ok(self)
+    }
+
+    /// Publish an authentication: someone is at the door and, if granted,
+    /// who it is
+    pub fn publish_decision(&self, user: Option<&str>, granted: bool) -> Result<()> {
+        self.client.publish(&self.state_topic(None), b"ON", false)?;
+        if let (Some(user), true) = (user, granted) {
+            let first = self.announced.lock().unwrap_or_else(PoisonError::into_inner).insert(user.to_string());
+            if first {
+                self.announce(Some(user))?;
+            }
+            self.client.publish(&self.state_topic(Some(user)), b"ON", false)?;
+        }
+        Ok(())
+    }
+
+    fn announce(&self, user: Option<&str>) -> Result<()> {
+        let (topic, config) = discovery_config(&self.node_id, user, self.off_delay);
+        self.client.publish(&topic, config.to_string().as_bytes(), true)
+    }
+
+    fn state_topic(&self, user: Option<&str>) -> String {
+        state_topic(&self.node_id, user)
+    }
+}
+
+fn state_topic(node_id: &str, user: Option<&str>) -> String {
+    match user {
+        Some(user) => format!("{}/{}/recognized/{}", STATE_PREFIX, node_id, object_id(user)),
+        None => format!("{}/{}/person", STATE_PREFIX, node_id),
+    }
+}
+
+/// Discovery topic and config of the "person at door" sensor, or of a user's
+/// "recognized" sensor
+fn discovery_config(node_id: &str, user: Option<&str>, off_delay: Duration) -> (String, Value) {
+    let (sensor, name, device_class) = match user {
+        Some(user) => (format!("recognized_{}", object_id(user)), format!("Recognized: {}", user), "presence"),
+        None => ("person".to_string(), "Person at door".to_string(), "occupancy"),
+    };
+    let unique_id = format!("{}_{}_{}", STATE_PREFIX, node_id, sensor);
+    let config = json!({
+        "name": name,
+        "unique_id": unique_id,
+        "state_topic": state_topic(node_id, user),
+        "availability_topic": format!("{}/{}/availability", STATE_PREFIX, node_id),
+        "device_class": device_class,
+        "off_delay": off_delay.as_secs(),
+        "device": {
+            "identifiers": [format!("{}_{}", STATE_PREFIX, node_id)],
+            "name": format!("Face Auth {}", node_id),
+            "manufacturer": "face_auth",
+            "sw_version": env!("CARGO_PKG_VERSION"),
+        },
+    });
+    (format!("{}/binary_sensor/{}_{}/{}/config", DISCOVERY_PREFIX, STATE_PREFIX, node_id, sensor), config)
+}
+
+/// Topic-safe id: only letters, digits, `_` and `-`
+fn object_id(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sensors_are_discoverable() {
+        let (topic, person) = discovery_config("front_door", None, Duration::from_secs(10));
+        assert_eq!(topic, "homeassistant/binary_sensor/face_auth_front_door/person/config");
+        assert_eq!(person["state_topic"], "face_auth/front_door/person");
+        assert_eq!(person["device"]["identifiers"][0], "face_auth_front_door");
+
+        let (topic, alice) = discovery_config("front_door", Some("alice.smith"), Duration::from_secs(5));
+        assert_eq!(topic, "homeassistant/binary_sensor/face_auth_front_door/recognized_alice_smith/config");
+        assert_eq!(alice["name"], "Recognized: alice.smith");
+        assert_eq!(alice["state_topic"], "face_auth/front_door/recognized/alice_smith");
+        assert_eq!(alice["off_delay"], 5);
+        assert_eq!(alice["device"], person["device"]);
+    }
+}
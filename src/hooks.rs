@@ -0,0 +1,265 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use crate::context::AuthContext;
+#[cfg(feature = "wasm")]
+use crate::wasm::WasmModule;
+
+/// Where in an authentication a hook runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookPoint {
+    /// Before the camera is opened; a denial skips the capture
+    BeforeCapture,
+    /// After a face is matched, before access is granted; receives the result
+    AfterDecision,
+}
+
+/// A user-supplied executable or WASM plugin run at a [`HookPoint`], e.g. to
+/// check a booking system before letting someone in
+///
+/// The hook gets a JSON object with `point`, `device_id`, `timestamp`, the
+/// caller's `context` and, after a match, the `result`. An executable reads
+/// it on stdin. Exiting with 0 lets the authentication continue; any other
+/// exit code denies it, with the first line of stdout as the reason. A WASM
+/// plugin (`wasm` feature, see [`crate::wasm`]) gets it in its `hook` export
+/// and returns `{"allow": true}` or `{"allow": false, "reason": "..."}`. A hook
+/// that can't be run, times out or runs out of fuel denies too.
+#[derive(Debug, Clone)]
+pub struct Hook {
+    pub point: HookPoint,
+    /// The executable, or the file a WASM plugin was loaded from
+    pub program: PathBuf,
+    pub args: Vec<String>,
+    /// How long an executable may run; a WASM plugin is bounded by its fuel
+    pub timeout: Duration,
+    #[cfg(feature = "wasm")]
+    plugin: Option<WasmModule>,
+}
+
+/// What a WASM plugin returns
+#[cfg(feature = "wasm")]
+#[derive(Deserialize)]
+struct PluginVerdict {
+    allow: bool,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// What a hook decided
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookVerdict {
+    Allow,
+    Deny { reason: String },
+}
+
+#[derive(Serialize)]
+struct HookPayload<'a, T: Serialize> {
+    point: HookPoint,
+    device_id: Option<&'a str>,
+    timestamp: DateTime<Utc>,
+    #[serde(skip_serializing_if = "AuthContext::is_empty")]
+    context: &'a AuthContext,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<&'a T>,
+}
+
+impl Hook {
+    pub fn new(point: HookPoint, program: impl Into<PathBuf>) -> Self {
+        Self {
+            point,
+            program: program.into(),
+            args: Vec::new(),
+            timeout: Duration::from_secs(5),
+            #[cfg(feature = "wasm")]
+            plugin: None,
+        }
+    }
+
+    /// Run the WASM plugin in `path` instead of an executable
+    #[cfg(feature = "wasm")]
+    pub fn wasm(point: HookPoint, path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let module = WasmModule::load(&path)?;
+        Ok(Self::from_module(point, path, module))
+    }
+
+    /// Run a compiled plugin, `name` standing in for its file in logs and
+    /// denial reasons
+    #[cfg(feature = "wasm")]
+    pub fn from_module(point: HookPoint, name: impl Into<PathBuf>, module: WasmModule) -> Self {
+        Self { plugin: Some(module), ..Self::new(point, name) }
+    }
+
+    pub fn with_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args = args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Run the hook with `result` (after the decision) in its input
+    pub fn run<T: Serialize>(&self, device_id: Option<&str>, context: &AuthContext, result: Option<&T>) -> HookVerdict {
+        let payload = HookPayload { point: self.point, device_id, timestamp: Utc::now(), context, result };
+        match self.execute(&payload) {
+            Ok(verdict) => verdict,
+            Err(e) => {
+                tracing::warn!(hook = %self.program.display(), "Hook failed: {:#}", e);
+                HookVerdict::Deny { reason: format!("{} failed", self.name()) }
+            }
+        }
+    }
+
+    fn execute(&self, payload: &impl Serialize) -> Result<HookVerdict> {
+        #[cfg(feature = "wasm")]
+        if let Some(plugin) = &self.plugin {
+            let verdict: PluginVerdict = plugin.call_json("hook", payload)?;
+            return Ok(match verdict {
+                PluginVerdict { allow: true, .. } => HookVerdict::Allow,
+                PluginVerdict { reason, .. } => HookVerdict::Deny { reason: reason.unwrap_or_else(|| format!("Denied by {}", self.name())) },
+            });
+        }
+
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| anyhow!("Failed to start: {}", e))?;
+        let input = serde_json::to_vec(payload)?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        // A hook that exits without reading its input is fine
+        let _ = stdin.write_all(&input);
+        drop(stdin);
+        // The hook is done when it closes its output, usually by exiting;
+        // waiting for that on a channel wakes up right then, without polling
+        let mut stdout = child.stdout.take().expect("stdout is piped");
+        let (done, output) = mpsc::channel();
+        thread::spawn(move || {
+            let mut output = String::new();
+            let _ = stdout.read_to_string(&mut output);
+            let _ = done.send(output);
+        });
+        let Ok(output) = output.recv_timeout(self.timeout) else {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(anyhow!("Timed out after {:?}", self.timeout));
+        };
+        if child.wait()?.success() {
+            return Ok(HookVerdict::Allow);
+        }
+        let reason = output.lines().map(str::trim).find(|line| !line.is_empty())
+            .map_or_else(|| format!("Denied by {}", self.name()), str::to_string);
+        Ok(HookVerdict::Deny { reason })
+    }
+
+    fn name(&self) -> String {
+        self.program.file_name().unwrap_or(self.program.as_os_str()).to_string_lossy().into_owned()
+    }
+}
+
+/// Run the hooks for `point` in order, stopping at the first denial
+pub fn run_hooks<T: Serialize>(
+    hooks: &[Hook],
+    point: HookPoint,
+    device_id: Option<&str>,
+    context: &AuthContext,
+    result: Option<&T>,
+) -> HookVerdict {
+    for hook in hooks.iter().filter(|hook| hook.point == point) {
+        if let verdict @ HookVerdict::Deny { .. } = hook.run(device_id, context, result) {
+            tracing::info!(hook = %hook.program.display(), ?point, "Hook denied the authentication");
+            return verdict;
+        }
+    }
+    HookVerdict::Allow
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hooks_read_json_and_decide_by_exit_code() {
+        let booking = Hook::new(HookPoint::AfterDecision, "sh")
+            .with_args(["-c", r#"grep -q '"user":"ann"' || { echo "No booking today"; exit 1; }"#]);
+        let ann = serde_json::json!({ "user": "ann" });
+        let bob = serde_json::json!({ "user": "bob" });
+        let none = AuthContext::new();
+        assert_eq!(booking.run(Some("gate-1"), &none, Some(&ann)), HookVerdict::Allow);
+        assert_eq!(booking.run(None, &none, Some(&bob)), HookVerdict::Deny { reason: "No booking today".into() });
+
+        let hooks = [Hook::new(HookPoint::BeforeCapture, "false"), booking];
+        assert_eq!(run_hooks(&hooks, HookPoint::AfterDecision, None, &none, Some(&ann)), HookVerdict::Allow);
+        assert_eq!(run_hooks::<()>(&hooks, HookPoint::BeforeCapture, None, &none, None), HookVerdict::Deny { reason: "Denied by false".into() });
+
+        let slow = Hook::new(HookPoint::BeforeCapture, "sleep").with_args(["5"]).with_timeout(Duration::from_millis(50));
+        assert_eq!(slow.run::<()>(None, &none, None), HookVerdict::Deny { reason: "sleep failed".into() });
+        assert!(matches!(Hook::new(HookPoint::BeforeCapture, "/nonexistent/hook").run::<()>(None, &none, None), HookVerdict::Deny { .. }));
+    }
+
+    /// Denies results that mention bob, allows the rest
+    #[cfg(feature = "wasm")]
+    const BOOKING_PLUGIN: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (data (i32.const 0) "\"bob\"")
+  (data (i32.const 16) "{\"allow\":true}")
+  (data (i32.const 48) "{\"allow\":false,\"reason\":\"No booking today\"}")
+  (global $next (mut i32) (i32.const 1024))
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (global.set $next (i32.add (global.get $next) (local.get $len)))
+    (local.get $ptr))
+  (func (export "hook") (param $ptr i32) (param $len i32) (result i64)
+    (local $i i32) (local $j i32)
+    (block $done
+      (loop $outer
+        (br_if $done (i32.gt_u (i32.add (local.get $i) (i32.const 5)) (local.get $len)))
+        (local.set $j (i32.const 0))
+        (block $mismatch
+          (loop $inner
+            (if (i32.eq (local.get $j) (i32.const 5))
+              (then (return (i64.const 206158430251))))
+            (br_if $mismatch (i32.ne
+              (i32.load8_u (i32.add (local.get $ptr) (i32.add (local.get $i) (local.get $j))))
+              (i32.load8_u (local.get $j))))
+            (local.set $j (i32.add (local.get $j) (i32.const 1)))
+            (br $inner)))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $outer)))
+    (i64.const 68719476750)))
+"#;
+
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn test_wasm_hooks_decide_from_the_payload() {
+        let plugin = WasmModule::from_bytes(BOOKING_PLUGIN.as_bytes()).unwrap();
+        let booking = Hook::from_module(HookPoint::AfterDecision, "booking.wat", plugin);
+        let none = AuthContext::new();
+        assert_eq!(booking.run(None, &none, Some(&serde_json::json!({ "user": "ann" }))), HookVerdict::Allow);
+        assert_eq!(booking.run(None, &none, Some(&serde_json::json!({ "user": "bob" }))), HookVerdict::Deny { reason: "No booking today".into() });
+
+        let spinning = r#"(module (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32) (i32.const 0))
+            (func (export "hook") (param i32 i32) (result i64) (loop $forever (br $forever)) (i64.const 0)))"#;
+        let spinning = Hook::from_module(HookPoint::BeforeCapture, "spinning.wat", WasmModule::from_bytes(spinning.as_bytes()).unwrap().with_fuel(100_000));
+        assert_eq!(spinning.run::<()>(None, &none, None), HookVerdict::Deny { reason: "spinning.wat failed".into() });
+    }
+}
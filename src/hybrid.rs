@@ -0,0 +1,402 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::denial::DenialReason;
+use crate::signing::encode_hex;
+
+#[cfg(feature = "hybrid")]
+pub use client::{HybridBackend, HybridConfig, RetryPolicy};
+
+/// Path the server accepts [`VerifyRequest`]s on
+pub const VERIFY_PATH: &str = "/v1/verify";
+
+/// Path devices `POST` to for a single-use nonce when the server requires one
+pub const NONCE_PATH: &str = "/v1/nonce";
+
+/// What a device sends to the server in hybrid mode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadMode {
+    /// Only the embedding leaves the device; no image is ever transmitted
+    #[default]
+    EmbeddingOnly,
+    /// An aligned face crop, encoded again by the server's matcher
+    AlignedCrop,
+}
+
+/// Face sent for verification
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Probe {
+    Embedding { encoding: Vec<f64> },
+    /// Base64 JPEG of an aligned face chip
+    Crop { jpeg: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VerifyRequest {
+    #[serde(default)]
+    pub device_id: Option<String>,
+    pub tolerance: f64,
+    /// Quality score the device measured (0.0-1.0)
+    #[serde(default)]
+    pub quality: Option<f64>,
+    pub probe: Probe,
+    /// Server-issued nonce, required when the server protects against replays
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+}
+
+impl VerifyRequest {
+    /// Hex SHA-256 of the request without its nonce; a nonce is only accepted
+    /// in the request it was issued for
+    pub fn body_hash(&self) -> String {
+        let unsigned = Self { nonce: None, ..self.clone() };
+        let json = serde_json::to_vec(&unsigned).expect("verify requests serialize");
+        encode_hex(&Sha256::digest(&json))
+    }
+}
+
+/// Request for a nonce, `POST`ed to [`NONCE_PATH`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NonceRequest {
+    /// [`VerifyRequest::body_hash`] of the request the nonce will be sent in
+    pub body_sha256: String,
+}
+
+/// Answer to a nonce request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NonceResponse {
+    pub nonce: String,
+    /// Seconds until the nonce expires
+    pub expires_in: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VerifyResponse {
+    pub is_authenticated: bool,
+    pub user_id: Option<String>,
+    pub distance: Option<f64>,
+    pub confidence: Option<f64>,
+    pub threshold: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub denial: Option<DenialReason>,
+    /// How likely a submitted crop was manipulated, when the server runs a detector
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manipulation_score: Option<f64>,
+}
+
+#[cfg(feature = "hybrid")]
+mod client {
+    use anyhow::{Context, Result, anyhow};
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use std::fmt;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::{NonceRequest, NonceResponse, PayloadMode, Probe, VerifyRequest, VerifyResponse, NONCE_PATH, VERIFY_PATH};
+    use crate::assurance::Liveness;
+    use crate::backend::FaceBackend;
+    use crate::camera::CameraSource;
+    use crate::denial::DenialReason;
+    use crate::interop::DLIB_CHIP_SIZE;
+    use crate::kyc::DocumentFace;
+    use crate::profiles::Preprocessing;
+    use crate::registration::RegistrationOutcome;
+    use crate::standalone_python::{StandaloneAuthResult, StandalonePythonFaceAuth, WarmUpOutcome};
+    use crate::timing::TimingBreakdown;
+
+    type LivenessCheck = dyn Fn(&Path) -> Result<bool> + Send + Sync;
+
+    /// How often and how patiently requests to the server are retried
+    ///
+    /// Connection failures, timeouts, 429 and 5xx responses are retried with
+    /// exponential backoff; other errors fail immediately.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct RetryPolicy {
+        /// Tries in total, including the first
+        pub attempts: u32,
+        pub initial_backoff: Duration,
+        pub max_backoff: Duration,
+    }
+
+    impl Default for RetryPolicy {
+        fn default() -> Self {
+            Self { attempts: 3, initial_backoff: Duration::from_millis(200), max_backoff: Duration::from_secs(2) }
+        }
+    }
+
+    impl RetryPolicy {
+        fn backoff(&self, attempt: u32) -> Duration {
+            self.initial_backoff.saturating_mul(1 << attempt.min(16)).min(self.max_backoff)
+        }
+    }
+
+    /// Device-side settings of hybrid mode
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct HybridConfig {
+        /// Base URL of the verification server, e.g. `https://auth.example.com`
+        pub server_url: String,
+        pub payload: PayloadMode,
+        /// Frames scoring below this are rejected on the device (0.0-1.0)
+        pub min_quality: f64,
+        pub retry: RetryPolicy,
+        /// Timeout of a single request
+        pub timeout: Duration,
+        /// Sent with every request so the server can tell devices apart
+        pub device_id: Option<String>,
+        /// Fetch a fresh nonce from [`NONCE_PATH`] for every request, for
+        /// servers built with [`crate::Server::with_nonces`]
+        pub nonces: bool,
+        /// Sent as `Authorization: Bearer` to servers built with
+        /// [`crate::Server::with_device_token`]
+        pub token: Option<String>,
+    }
+
+    impl HybridConfig {
+        pub fn new(server_url: impl Into<String>) -> Self {
+            Self {
+                server_url: server_url.into().trim_end_matches('/').to_string(),
+                payload: PayloadMode::default(),
+                min_quality: 0.3,
+                retry: RetryPolicy::default(),
+                timeout: Duration::from_secs(10),
+                device_id: None,
+                nonces: false,
+                token: None,
+            }
+        }
+    }
+
+    /// [`FaceBackend`] that pre-filters frames on the device and has a central
+    /// server make the decision
+    ///
+    /// Detection, quality scoring and the optional liveness check run locally;
+    /// frames without a usable face never reach the network. What is sent is
+    /// set by [`HybridConfig::payload`], and chips written for
+    /// [`PayloadMode::AlignedCrop`] are deleted once sent. The `source_dir`
+    /// passed to authentication is ignored: the server matches against its
+    /// own enrolled users. Registration, export and import run locally with
+    /// the Python script.
+    #[derive(Clone)]
+    pub struct HybridBackend {
+        capture: StandalonePythonFaceAuth,
+        config: HybridConfig,
+        agent: ureq::Agent,
+        liveness: Option<Arc<LivenessCheck>>,
+    }
+
+    impl fmt::Debug for HybridBackend {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("HybridBackend")
+                .field("capture", &self.capture)
+                .field("config", &self.config)
+                .field("liveness", &self.liveness.is_some())
+                .finish()
+        }
+    }
+
+    impl HybridBackend {
+        pub fn new(capture: StandalonePythonFaceAuth, config: HybridConfig) -> Self {
+            let agent = ureq::Agent::config_builder()
+                .http_status_as_error(false)
+                .timeout_global(Some(config.timeout))
+                .build()
+                .into();
+            Self { capture, config, agent, liveness: None }
+        }
+
+        /// Reject frames for which `check` returns false before anything is sent
+        pub fn with_liveness(mut self, check: impl Fn(&Path) -> Result<bool> + Send + Sync + 'static) -> Self {
+            self.liveness = Some(Arc::new(check));
+            self
+        }
+
+        pub fn config(&self) -> &HybridConfig {
+            &self.config
+        }
+
+        /// Send a request, retrying transient failures per [`RetryPolicy`]
+        ///
+        /// With [`HybridConfig::nonces`], every attempt carries a new nonce.
+        pub fn verify(&self, request: &VerifyRequest) -> Result<VerifyResponse> {
+            let url = format!("{}{}", self.config.server_url, VERIFY_PATH);
+            let mut request = request.clone();
+            let mut attempt = 0;
+            loop {
+                let sent = match self.config.nonces {
+                    true => self.nonce(&request).and_then(|nonce| {
+                        request.nonce = Some(nonce);
+                        Ok(self.post(&url).send_json(&request)?)
+                    }),
+                    false => self.post(&url).send_json(&request).map_err(Into::into),
+                };
+                let error = match sent {
+                    Ok(mut response) if response.status().is_success() => {
+                        return response.body_mut().read_json().context("Invalid verification response");
+                    }
+                    Ok(mut response) => {
+                        let status = response.status();
+                        let body = response.body_mut().read_to_string().unwrap_or_default();
+                        let error = anyhow!("Verification server answered {}: {}", status, body.trim());
+                        if !(status.is_server_error() || status.as_u16() == 429) {
+                            return Err(error);
+                        }
+                        error
+                    }
+                    Err(e) => anyhow!("Verification server unreachable: {:#}", e),
+                };
+                attempt += 1;
+                if attempt >= self.config.retry.attempts {
+                    return Err(error.context(format!("Giving up after {} attempts", attempt)));
+                }
+                tracing::warn!("{:#}; retrying", error);
+                thread::sleep(self.config.retry.backoff(attempt - 1));
+            }
+        }
+
+        fn post(&self, url: &str) -> ureq::RequestBuilder<ureq::typestate::WithBody> {
+            let request = self.agent.post(url);
+            match &self.config.token {
+                Some(token) => request.header("Authorization", format!("Bearer {}", token)),
+                None => request,
+            }
+        }
+
+        /// A nonce for sending `request`
+        fn nonce(&self, request: &VerifyRequest) -> Result<String> {
+            let url = format!("{}{}", self.config.server_url, NONCE_PATH);
+            let mut response = self.post(&url).send_json(NonceRequest { body_sha256: request.body_hash() })?;
+            if !response.status().is_success() {
+                return Err(anyhow!("Nonce request answered {}", response.status()));
+            }
+            let nonce: NonceResponse = response.body_mut().read_json().context("Invalid nonce response")?;
+            Ok(nonce.nonce)
+        }
+
+        fn denied(&self, tolerance: f64, image: &Path, started: Instant, timings: TimingBreakdown, denial: Option<DenialReason>) -> StandaloneAuthResult {
+            StandaloneAuthResult {
+                success: true,
+                is_match: Some(false),
+                confidence: None,
+                distance: None,
+                threshold: Some(tolerance),
+                matched_user: None,
+                closest_user: None,
+                runner_up: None,
+                sample_distances: Vec::new(),
+                brightness: None,
+                image_path: Some(image.to_path_buf()),
+                processing_time_ms: Some(started.elapsed().as_millis() as u32),
+                timings,
+                liveness: None,
+                denial,
+                raw_output: String::new(),
+            }
+        }
+    }
+
+    impl FaceBackend for HybridBackend {
+        fn data_dir(&self) -> &Path {
+            self.capture.data_dir()
+        }
+
+        fn register_user(&self, username: &str, samples: u32, generated_dir: &str) -> Result<RegistrationOutcome> {
+            self.capture.register_user(username, samples, generated_dir)
+        }
+
+        fn encode_images(&self, images: &[PathBuf]) -> Result<Vec<Option<Vec<f64>>>> {
+            self.capture.encode_images(images)
+        }
+
+        fn encode_document(&self, document: &Path) -> Result<DocumentFace> {
+            self.capture.encode_document(document)
+        }
+
+        fn crop_faces(&self, image: &Path, out_dir: &Path) -> Result<Vec<PathBuf>> {
+            self.capture.crop_faces(image, out_dir)
+        }
+
+        fn set_preprocessing(&self, preprocessing: &Preprocessing) {
+            self.capture.set_preprocessing(preprocessing.clone())
+        }
+
+        fn with_camera_source(&self, source: &CameraSource) -> Result<Arc<dyn FaceBackend>> {
+            Ok(Arc::new(Self { capture: self.capture.with_camera_source(source.clone()), ..self.clone() }))
+        }
+
+        fn authenticate_user(&self, tolerance: f64, source_dir: &str) -> Result<StandaloneAuthResult> {
+            let frame = self.capture.capture_frames("auth", 1)?.into_iter().flatten().next()
+                .ok_or_else(|| anyhow!("Failed to capture a frame from the camera"))?;
+            self.authenticate_image(tolerance, source_dir, &frame)
+        }
+
+        fn authenticate_image(&self, tolerance: f64, _source_dir: &str, image: &Path) -> Result<StandaloneAuthResult> {
+            let started = Instant::now();
+            let embed = self.config.payload == PayloadMode::EmbeddingOnly;
+            let chips_dir = self.capture.data_dir().join("hybrid_chips");
+            let filtered = self.capture.prefilter(image, embed, &chips_dir, DLIB_CHIP_SIZE)?;
+            let mut timings = TimingBreakdown { detect_ms: started.elapsed().as_millis() as u64, ..Default::default() };
+
+            let quality = filtered.quality.map(|q| q.score);
+            if !filtered.face {
+                return Ok(self.denied(tolerance, image, started, timings, Some(DenialReason::NoFace)));
+            }
+            if let Some(score) = quality.filter(|&q| q < self.config.min_quality) {
+                tracing::info!(quality, min_quality = self.config.min_quality, "Frame rejected by the device pre-filter");
+                let denial = DenialReason::QualityTooLow { score, minimum: self.config.min_quality };
+                return Ok(self.denied(tolerance, image, started, timings, Some(denial)));
+            }
+            if let Some(check) = &self.liveness {
+                if !check(image)? {
+                    tracing::info!("Frame rejected by the liveness check");
+                    return Ok(self.denied(tolerance, image, started, timings, Some(DenialReason::LivenessFailed)));
+                }
+            }
+
+            let probe = match (filtered.encoding, filtered.chip) {
+                (Some(encoding), _) if embed => Probe::Embedding { encoding },
+                (_, Some(chip)) if !embed => {
+                    let jpeg = std::fs::read(&chip);
+                    let _ = std::fs::remove_file(&chip);
+                    Probe::Crop { jpeg: BASE64.encode(jpeg?) }
+                }
+                _ => return Err(anyhow!("Python pre-filter returned no {}", if embed { "encoding" } else { "chip" })),
+            };
+            let request = VerifyRequest { device_id: self.config.device_id.clone(), tolerance, quality, probe, nonce: None };
+
+            let sent = Instant::now();
+            let response = self.verify(&request)?;
+            timings.match_ms = sent.elapsed().as_millis() as u64;
+            Ok(StandaloneAuthResult {
+                is_match: Some(response.is_authenticated),
+                confidence: response.confidence,
+                distance: response.distance,
+                threshold: response.threshold.or(Some(tolerance)),
+                matched_user: response.user_id.clone().filter(|_| response.is_authenticated),
+                closest_user: response.user_id,
+                liveness: self.liveness.as_ref().map(|_| Liveness::Passive),
+                ..self.denied(tolerance, image, started, timings, response.denial)
+            })
+        }
+
+        fn warm_up(&self, camera: bool) -> Result<WarmUpOutcome> {
+            self.capture.warm_up(camera)
+        }
+
+        fn export_user(&self, username: &str, filename: &str) -> Result<bool> {
+            self.capture.export_user(username, filename)
+        }
+
+        fn import_user(&self, filename: &str) -> Result<bool> {
+            self.capture.import_user(filename)
+        }
+
+        fn check(&self) -> Result<()> {
+            self.capture.check_executable()
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use anyhow::{Context, Result, anyhow, bail};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "python-backend")]
+use crate::face_storage::{FaceDatabase, FaceSample, UserProfile};
+#[cfg(feature = "python-backend")]
+use crate::sample_id;
+#[cfg(feature = "python-backend")]
+use crate::StandalonePythonFaceAuth;
+
+/// Side length of the chips dlib's metric-learning tools are trained on
+pub const DLIB_CHIP_SIZE: u32 = 150;
+
+const IMAGE_EXTENSIONS: [&str; 6] = ["jpg", "jpeg", "png", "bmp", "pgm", "ppm"];
+
+/// Labels of an OpenCV LBPH recognizer saved with `recognizer.write()`
+///
+/// LBPH histograms can't be turned into face encodings, so only the labels
+/// and their names are read; the faces are re-encoded from the training images.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LbphModel {
+    /// Label of every training sample, in training order
+    pub labels: Vec<i32>,
+    /// Names set with `setLabelInfo`, all valid usernames
+    pub label_names: BTreeMap<i32, String>,
+}
+
+impl LbphModel {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&content).with_context(|| format!("Invalid LBPH model {}", path.display()))
+    }
+
+    /// Parse the YAML written by OpenCV's `FileStorage`
+    pub fn parse(yaml: &str) -> Result<Self> {
+        if !yaml.contains("opencv_lbphfaces") {
+            bail!("not an OpenCV LBPH model");
+        }
+        let labels_at = yaml.lines()
+            .scan(0, |offset, line| {
+                let start = *offset;
+                *offset += line.len() + 1;
+                Some((start, line))
+            })
+            .find(|(_, line)| line.trim_start().starts_with("labels:"))
+            .map(|(start, _)| start)
+            .ok_or_else(|| anyhow!("model has no labels"))?;
+        let data = &yaml[labels_at..];
+        let data = &data[data.find("data:").ok_or_else(|| anyhow!("labels have no data"))?..];
+        let open = data.find('[').ok_or_else(|| anyhow!("malformed label data"))?;
+        let close = open + data[open..].find(']').ok_or_else(|| anyhow!("malformed label data"))?;
+        let labels = data[open + 1..close]
+            .split(',')
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .map(|token| token.parse::<i32>().map_err(|_| anyhow!("invalid label '{}'", token)))
+            .collect::<Result<Vec<_>>>()?;
+
+        // Names become usernames and credential file names
+        let label_names = parse_label_info(yaml);
+        for name in label_names.values() {
+            crate::face_storage::validate_username(name).context("Invalid label name")?;
+        }
+        Ok(Self { labels, label_names })
+    }
+
+    /// Name of `label`, falling back to `label_<n>` when none was set
+    pub fn name(&self, label: i32) -> String {
+        self.label_names.get(&label).cloned().unwrap_or_else(|| format!("label_{}", label))
+    }
+
+    /// Training images of every label in the model, found under `images_dir`
+    ///
+    /// Both common dataset layouts are recognized: a directory per label
+    /// (named by number or by label name, e.g. `s1/` or `alice/`) and flat
+    /// files like `User.1.3.jpg`.
+    pub fn training_images(&self, images_dir: &Path) -> Result<BTreeMap<i32, Vec<PathBuf>>> {
+        let mut images: BTreeMap<i32, Vec<PathBuf>> = BTreeMap::new();
+        let label_of = |name: &str| -> Option<i32> {
+            let digits = name.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+            digits.parse().ok()
+                .or_else(|| self.label_names.iter().find(|(_, n)| *n == name).map(|(label, _)| *label))
+                .filter(|label| self.labels.contains(label))
+        };
+
+        let mut entries: Vec<PathBuf> = fs::read_dir(images_dir)
+            .with_context(|| format!("Failed to read {}", images_dir.display()))?
+            .flatten()
+            .map(|e| e.path())
+            .collect();
+        entries.sort();
+        for path in entries {
+            let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            if path.is_dir() {
+                if let Some(label) = label_of(&name) {
+                    images.entry(label).or_default().extend(image_files(&path)?);
+                }
+            } else if is_image(&path) {
+                // User.<label>.<n>.jpg
+                let label = name.split('.').nth(1).and_then(|part| part.parse().ok())
+                    .filter(|label| self.labels.contains(label));
+                if let Some(label) = label {
+                    images.entry(label).or_default().push(path);
+                }
+            }
+        }
+        Ok(images)
+    }
+}
+
+/// `labelsInfo` entries, in flow (`- { label: 0, value: alice }`) or block style
+fn parse_label_info(yaml: &str) -> BTreeMap<i32, String> {
+    let mut names = BTreeMap::new();
+    let mut lines = yaml.lines().skip_while(|line| !line.trim_start().starts_with("labelsInfo:"));
+    let Some(header) = lines.next() else {
+        return names;
+    };
+    let indent = header.len() - header.trim_start().len();
+
+    let (mut label, mut value) = (None, None);
+    for line in lines.take_while(|line| line.trim().is_empty() || line.len() - line.trim_start().len() > indent) {
+        for field in line.trim().trim_start_matches('-').trim().trim_matches(['{', '}']).split(',') {
+            match field.split_once(':').map(|(k, v)| (k.trim(), v.trim().trim_matches(['"', '\'']))) {
+                Some(("label", v)) => label = v.parse::<i32>().ok(),
+                Some(("value", v)) => value = Some(v.to_string()),
+                _ => {}
+            }
+        }
+        if let (Some(l), Some(v)) = (label, value.as_ref()) {
+            names.insert(l, v.clone());
+            (label, value) = (None, None);
+        }
+    }
+    names
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+fn image_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| is_image(p))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Result of [`import_lbph`]
+#[cfg(feature = "python-backend")]
+#[derive(Debug, Clone, Default)]
+pub struct LbphImport {
+    pub database: FaceDatabase,
+    /// Training images in which no face was found
+    pub unencoded: Vec<PathBuf>,
+}
+
+/// Re-encode the training images of an LBPH recognizer into a database
+///
+/// Users are named after the model's label names. Labels whose images
+/// contain no usable face are left out.
+#[cfg(feature = "python-backend")]
+pub fn import_lbph(python: &StandalonePythonFaceAuth, model: &Path, images_dir: &Path) -> Result<LbphImport> {
+    let model = LbphModel::load(model)?;
+    let timestamp = chrono::Local::now().format("%Y-%m-%dT%H:%M:%S%.6f").to_string();
+    let mut import = LbphImport::default();
+
+    for (label, images) in model.training_images(images_dir)? {
+        let name = model.name(label);
+        let encodings = python.encode_images(&images)?;
+        let mut samples = Vec::new();
+        for (image, encoding) in images.into_iter().zip(encodings) {
+            match encoding {
+                Some(encoding) => samples.push(FaceSample {
+                    encoding,
+                    timestamp: timestamp.clone(),
+                    sample_id: sample_id::generate_now(),
+                    image_path: Some(image.to_string_lossy().into_owned()),
+                    quality: None,
+                    template_version: None,
+                    quarantine: None,
+                }),
+                None => import.unencoded.push(image),
+            }
+        }
+        if samples.is_empty() {
+            tracing::warn!("No face found in the training images of label {} ({})", label, name);
+            continue;
+        }
+        import.database.users.insert(name.clone(), UserProfile {
+            user_id: name,
+            sample_count: samples.len(),
+            face_encodings: samples,
+            enrollment_date: Some(timestamp.clone()),
+            metadata: Default::default(),
+            extra: Default::default(),
+        });
+    }
+    Ok(import)
+}
+
+/// Result of [`export_dlib_chips`]
+#[cfg(feature = "python-backend")]
+#[derive(Debug, Clone, Default)]
+pub struct ChipExport {
+    pub chips: Vec<PathBuf>,
+    /// Samples whose image is missing or contains no face
+    pub skipped: Vec<String>,
+}
+
+/// Write aligned face chips of every sample image to `out_dir/<user>/`
+///
+/// Chips use dlib's 5-point alignment and padding, so the directory can be
+/// fed to dlib's metric-learning and `imglab` tooling as one class per user.
+#[cfg(feature = "python-backend")]
+pub fn export_dlib_chips(python: &StandalonePythonFaceAuth, db: &FaceDatabase, out_dir: &Path, size: u32) -> Result<ChipExport> {
+    let mut export = ChipExport::default();
+    for (user, profile) in &db.users {
+        crate::face_storage::validate_username(user)?;
+        let (images, samples): (Vec<PathBuf>, Vec<&FaceSample>) = profile.face_encodings.iter()
+            .filter_map(|s| s.image_path.as_ref().map(|p| (PathBuf::from(p), s)))
+            .filter(|(path, sample)| path.exists() || {
+                export.skipped.push(sample.sample_id.clone());
+                false
+            })
+            .unzip();
+        if images.is_empty() {
+            continue;
+        }
+        for (chip, sample) in python.face_chips(&images, &out_dir.join(user), size)?.into_iter().zip(samples) {
+            match chip {
+                Some(chip) => export.chips.push(chip),
+                None => export.skipped.push(sample.sample_id.clone()),
+            }
+        }
+    }
+    Ok(export)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODEL: &str = "%YAML:1.0
+---
+opencv_lbphfaces:
+   threshold: 1.7976931348623157e+308
+   radius: 1
+   histograms:
+      - !!opencv-matrix
+         rows: 1
+         cols: 4
+         dt: f
+         data: [ 0., 1., 0., 1. ]
+   labels: !!opencv-matrix
+      rows: 3
+      cols: 1
+      dt: i
+      data: [ 1, 1,
+          2 ]
+   labelsInfo:
+      - { label: 1, value: alice }
+";
+
+    #[test]
+    fn test_lbph_labels_and_training_images() {
+        let model = LbphModel::parse(MODEL).unwrap();
+        assert_eq!(model.labels, [1, 1, 2]);
+        assert_eq!(model.name(1), "alice");
+        assert_eq!(model.name(2), "label_2");
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("alice")).unwrap();
+        fs::write(dir.path().join("alice/a.jpg"), b"").unwrap();
+        fs::write(dir.path().join("alice/notes.txt"), b"").unwrap();
+        fs::write(dir.path().join("User.2.1.jpg"), b"").unwrap();
+        fs::write(dir.path().join("User.9.1.jpg"), b"").unwrap();
+
+        let images = model.training_images(dir.path()).unwrap();
+        assert_eq!(images[&1], [dir.path().join("alice/a.jpg")]);
+        assert_eq!(images[&2], [dir.path().join("User.2.1.jpg")]);
+        assert!(!images.contains_key(&9));
+
+        assert!(LbphModel::parse("%YAML:1.0\nfoo: 1\n").is_err());
+        assert!(LbphModel::parse(&MODEL.replace("alice", "../admin")).is_err());
+    }
+}
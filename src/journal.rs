@@ -0,0 +1,272 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::face_storage::write_atomic;
+
+const RECORD_FILE: &str = "journal.json";
+
+/// Locked by a transaction's process for as long as the transaction runs
+const OWNER_LOCK_FILE: &str = "owner.lock";
+
+/// Held exclusively by recovery, shared by transactions starting, so recovery
+/// never sees a transaction whose owner lock isn't taken yet
+const RECOVERY_LOCK_FILE: &str = ".recovery.lock";
+
+/// Tells apart transactions begun within the same microsecond
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// A file an operation may change, with a copy of how it was before
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct JournaledFile {
+    path: PathBuf,
+    /// Copy of the previous contents in the transaction's directory; `None`
+    /// when the file didn't exist and is removed on rollback
+    backup: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct JournalRecord {
+    operation: String,
+    username: Option<String>,
+    /// Process the operation runs in
+    #[serde(default)]
+    pid: u32,
+    started_at: DateTime<Utc>,
+    files: Vec<JournaledFile>,
+}
+
+/// An operation found unfinished, and rolled back, by [`Journal::recover`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecoveredOperation {
+    pub operation: String,
+    pub username: Option<String>,
+    pub started_at: DateTime<Utc>,
+    /// Files put back as they were before the operation
+    pub restored_files: usize,
+}
+
+/// Undo journal making multi-step writes, such as an enrollment rewriting
+/// the database and several credential files, all-or-nothing
+///
+/// Each [`Transaction`] copies the files it's about to change before they
+/// are touched. Committing discards the copies; a failure or drop before
+/// that puts them back, and so does [`recover`](Self::recover) on the next
+/// start when the process died in between. A transaction keeps a lock on its
+/// journal while it runs, so recovery leaves the transactions of running
+/// processes alone.
+#[derive(Debug, Clone)]
+pub struct Journal {
+    dir: PathBuf,
+}
+
+impl Journal {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn begin(&self, operation: &str, username: Option<&str>) -> Result<Transaction> {
+        let recovery = self.recovery_lock()?;
+        recovery.lock_shared()?;
+        let dir = self.dir.join(format!(
+            "{}_{}_{}",
+            Utc::now().format("%Y%m%dT%H%M%S%.6f"),
+            std::process::id(),
+            SEQUENCE.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir)?;
+        let owner = fs::File::create(dir.join(OWNER_LOCK_FILE))?;
+        owner.lock()?;
+        drop(recovery);
+        let record = JournalRecord {
+            operation: operation.to_string(),
+            username: username.map(str::to_string),
+            pid: std::process::id(),
+            started_at: Utc::now(),
+            files: Vec::new(),
+        };
+        let transaction = Transaction { dir, record, finished: false, _owner: owner };
+        transaction.persist()?;
+        Ok(transaction)
+    }
+
+    /// Roll back operations a crash left unfinished, oldest first
+    ///
+    /// Only one process recovers at a time, and only journals whose owning
+    /// process has exited are touched; its lock on the journal went with it.
+    pub fn recover(&self) -> Result<Vec<RecoveredOperation>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let recovery = self.recovery_lock()?;
+        recovery.lock()?;
+        let mut dirs: Vec<PathBuf> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_dir())
+            .collect();
+        dirs.sort();
+        let mut recovered = Vec::new();
+        for dir in dirs {
+            match fs::File::open(dir.join(OWNER_LOCK_FILE)).map(|owner| owner.try_lock()) {
+                Ok(Err(fs::TryLockError::WouldBlock)) => {
+                    tracing::debug!(journal = %dir.display(), "Skipping the journal of a running operation");
+                    continue;
+                }
+                Ok(Err(fs::TryLockError::Error(e))) => return Err(e.into()),
+                // Unlocked, or left by a crash before it was locked
+                Ok(Ok(())) | Err(_) => {}
+            }
+            let record_path = dir.join(RECORD_FILE);
+            if record_path.exists() {
+                let record: JournalRecord = serde_json::from_str(&fs::read_to_string(&record_path)?)
+                    .map_err(|e| anyhow!("Corrupt journal {}: {}", record_path.display(), e))?;
+                let restored_files = roll_back(&record)?;
+                tracing::warn!(operation = %record.operation, username = ?record.username, pid = record.pid, restored_files, "Rolled back an interrupted operation");
+                recovered.push(RecoveredOperation {
+                    operation: record.operation,
+                    username: record.username,
+                    started_at: record.started_at,
+                    restored_files,
+                });
+            }
+            fs::remove_dir_all(&dir)?;
+        }
+        Ok(recovered)
+    }
+
+    fn recovery_lock(&self) -> Result<fs::File> {
+        fs::create_dir_all(&self.dir)?;
+        Ok(fs::File::options().create(true).truncate(false).write(true).open(self.dir.join(RECOVERY_LOCK_FILE))?)
+    }
+}
+
+/// One journaled operation; rolled back when dropped without
+/// [`commit`](Self::commit)
+#[derive(Debug)]
+pub struct Transaction {
+    dir: PathBuf,
+    record: JournalRecord,
+    finished: bool,
+    /// Locked until the transaction is dropped or its process dies
+    _owner: fs::File,
+}
+
+impl Transaction {
+    /// Remember how `path` is now, before the operation changes it
+    pub fn protect(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = std::path::absolute(path.as_ref())?;
+        if self.record.files.iter().any(|file| file.path == path) {
+            return Ok(());
+        }
+        let backup = if path.exists() {
+            let backup = self.dir.join(self.record.files.len().to_string());
+            write_atomic(&backup, &fs::read(&path)?)?;
+            Some(backup)
+        } else {
+            None
+        };
+        self.record.files.push(JournaledFile { path, backup });
+        self.persist()
+    }
+
+    pub fn protect_all<P: AsRef<Path>>(&mut self, paths: impl IntoIterator<Item = P>) -> Result<()> {
+        paths.into_iter().try_for_each(|path| self.protect(path))
+    }
+
+    /// Keep the changes
+    pub fn commit(mut self) -> Result<()> {
+        self.finished = true;
+        fs::remove_dir_all(&self.dir)?;
+        Ok(())
+    }
+
+    /// Put every protected file back as it was
+    pub fn rollback(mut self) -> Result<()> {
+        self.finished = true;
+        roll_back(&self.record)?;
+        fs::remove_dir_all(&self.dir)?;
+        Ok(())
+    }
+
+    /// Leave the journal behind as a crash would
+    #[cfg(test)]
+    fn abandon(mut self) {
+        self.finished = true;
+    }
+
+    fn persist(&self) -> Result<()> {
+        write_atomic(&self.dir.join(RECORD_FILE), serde_json::to_string_pretty(&self.record)?.as_bytes())
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        match roll_back(&self.record) {
+            Ok(_) => {
+                let _ = fs::remove_dir_all(&self.dir);
+            }
+            // Left in place for the next start's recovery
+            Err(e) => tracing::error!("Failed to roll back {}: {:#}", self.record.operation, e),
+        }
+    }
+}
+
+fn roll_back(record: &JournalRecord) -> Result<usize> {
+    for file in record.files.iter().rev() {
+        match &file.backup {
+            Some(backup) => write_atomic(&file.path, &fs::read(backup)?)?,
+            None if file.path.exists() => fs::remove_file(&file.path)?,
+            None => {}
+        }
+    }
+    Ok(record.files.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unfinished_transactions_roll_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = Journal::new(dir.path().join("journal"));
+        let db = dir.path().join("db.json");
+        let credential = dir.path().join("ann.json");
+        fs::write(&db, "before").unwrap();
+
+        let mut transaction = journal.begin("registration", Some("ann")).unwrap();
+        transaction.protect_all([&db, &credential]).unwrap();
+        fs::write(&db, "after").unwrap();
+        fs::write(&credential, "new").unwrap();
+        drop(transaction);
+        assert_eq!(fs::read_to_string(&db).unwrap(), "before");
+        assert!(!credential.exists());
+
+        let mut transaction = journal.begin("registration", Some("ann")).unwrap();
+        transaction.protect(&db).unwrap();
+        fs::write(&db, "after").unwrap();
+        transaction.commit().unwrap();
+        assert_eq!(fs::read_to_string(&db).unwrap(), "after");
+
+        // A running operation is left alone
+        let mut running = journal.begin("import", None).unwrap();
+        running.protect(&db).unwrap();
+        fs::write(&db, "half written").unwrap();
+        assert!(journal.recover().unwrap().is_empty());
+        assert_eq!(fs::read_to_string(&db).unwrap(), "half written");
+
+        // A crash leaves the journal behind
+        running.abandon();
+        let recovered = journal.recover().unwrap();
+        assert_eq!(recovered.len(), 1);
+        assert_eq!((recovered[0].operation.as_str(), recovered[0].restored_files), ("import", 1));
+        assert_eq!(fs::read_to_string(&db).unwrap(), "after");
+        assert!(journal.recover().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,434 @@
+//! Guided enrollment for self-service kiosks
+//!
+//! An [`EnrollmentFlow`] walks one person through welcome, consent, one
+//! capture per pose, a quality review and a final confirmation. The kiosk UI
+//! only renders [`EnrollmentFlow::step`] and forwards button presses:
+//!
+//! ```no_run
+//! use face_auth::{EnrollmentStep, FaceAuth};
+//!
+//! # async fn kiosk(auth: FaceAuth) -> anyhow::Result<()> {
+//! let mut flow = auth.enrollment_flow("ann").min_quality(0.4).start()?;
+//! flow.on_event(|event| println!("{:?}", event));
+//! flow.begin()?;
+//! flow.give_consent()?;
+//! while let EnrollmentStep::Capture { .. } = flow.step() {
+//!     flow.capture().await?;
+//! }
+//! for pose in flow.needs_retake() {
+//!     flow.retake(pose)?;
+//!     flow.capture().await?;
+//! }
+//! let outcome = flow.confirm().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Every step is saved, so a kiosk that restarts picks the flow up again with
+//! [`FaceAuth::resume_enrollment`]. Nothing is enrolled before
+//! [`EnrollmentFlow::confirm`].
+
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::face_storage::{self, write_atomic, FaceSample, UserMetadata};
+use crate::messages::CaptureTip;
+use crate::registration::{RegistrationOutcome, SampleOutcome};
+use crate::FaceAuth;
+
+/// Head position asked for in one capture
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Pose {
+    Frontal,
+    TurnLeft,
+    TurnRight,
+    ChinUp,
+    ChinDown,
+}
+
+impl Pose {
+    /// What to tell the user before capturing this pose
+    pub fn tip(self) -> CaptureTip {
+        match self {
+            Pose::Frontal => CaptureTip::LookAtCamera,
+            _ => CaptureTip::TurnHead,
+        }
+    }
+}
+
+/// Where an [`EnrollmentFlow`] stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "step")]
+pub enum EnrollmentStep {
+    Welcome,
+    /// Waiting for the user to accept or decline biometric processing
+    Consent,
+    /// Capturing the pose at `index` of [`EnrollmentFlow::poses`]
+    Capture { index: usize },
+    /// Every pose was attempted; captures may be retaken before confirming
+    Review,
+    /// Enrolled; the flow is finished
+    Completed,
+    /// Declined or cancelled; nothing was enrolled
+    Cancelled,
+}
+
+/// One attempted pose
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PoseCapture {
+    pub pose: Pose,
+    pub outcome: SampleOutcome,
+    /// The sample enrolled on confirmation, if a face was captured
+    pub sample: Option<FaceSample>,
+}
+
+impl PoseCapture {
+    /// Stored with a quality of at least `min_quality`; samples without a
+    /// quality score pass
+    pub fn is_acceptable(&self, min_quality: f64) -> bool {
+        self.sample.is_some() && self.outcome.quality.is_none_or(|quality| quality.score >= min_quality)
+    }
+}
+
+/// Moment of an [`EnrollmentFlow`] a kiosk UI reacts to
+#[derive(Debug, Clone, PartialEq)]
+pub enum EnrollmentEvent {
+    StepChanged(EnrollmentStep),
+    PoseCaptured(PoseCapture),
+    Completed(RegistrationOutcome),
+}
+
+/// Saved state of an [`EnrollmentFlow`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnrollmentState {
+    pub id: String,
+    pub username: String,
+    pub started_at: DateTime<Utc>,
+    pub step: EnrollmentStep,
+    pub poses: Vec<Pose>,
+    /// Lowest sample quality score the review accepts
+    pub min_quality: f64,
+    #[serde(default)]
+    pub metadata: UserMetadata,
+    pub generated_dir: PathBuf,
+    /// When the user accepted biometric processing
+    pub consented_at: Option<DateTime<Utc>>,
+    /// Latest capture of each pose, in the order of `poses`
+    pub captures: Vec<Option<PoseCapture>>,
+}
+
+/// Configures an [`EnrollmentFlow`], see [`FaceAuth::enrollment_flow`]
+pub struct EnrollmentFlowBuilder {
+    auth: FaceAuth,
+    username: String,
+    poses: Vec<Pose>,
+    min_quality: f64,
+    metadata: UserMetadata,
+    generated_dir: PathBuf,
+}
+
+impl EnrollmentFlowBuilder {
+    pub(crate) fn new(auth: FaceAuth, username: &str, generated_dir: PathBuf) -> Self {
+        Self {
+            auth,
+            username: username.to_string(),
+            poses: vec![Pose::Frontal, Pose::TurnLeft, Pose::TurnRight],
+            min_quality: 0.0,
+            metadata: UserMetadata::default(),
+            generated_dir,
+        }
+    }
+
+    /// Poses to capture, one sample each (default: frontal, left, right)
+    pub fn poses(mut self, poses: impl IntoIterator<Item = Pose>) -> Self {
+        self.poses = poses.into_iter().collect();
+        self
+    }
+
+    /// Lowest [`crate::SampleQuality::score`] the review accepts (default: 0, any face)
+    pub fn min_quality(mut self, min_quality: f64) -> Self {
+        self.min_quality = min_quality;
+        self
+    }
+
+    pub fn metadata(mut self, metadata: UserMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Directory the credential is written to (default: the instance's generated directory)
+    pub fn generated_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.generated_dir = dir.into();
+        self
+    }
+
+    /// Save the flow at its welcome step
+    ///
+    /// Fails if the username is enrolled already; enrolled users refresh
+    /// their samples with [`FaceAuth::reenroll`](crate::FaceAuth::reenroll).
+    pub fn start(self) -> Result<EnrollmentFlow> {
+        face_storage::validate_username(&self.username)?;
+        self.auth.ensure_not_enrolled(&self.username)?;
+        if self.poses.is_empty() {
+            bail!("An enrollment flow needs at least one pose");
+        }
+        static FLOWS: AtomicU32 = AtomicU32::new(0);
+        let started_at = Utc::now();
+        let state = EnrollmentState {
+            id: format!("{}-{:04}", started_at.format("%Y%m%d%H%M%S%3f"), FLOWS.fetch_add(1, Ordering::Relaxed) % 10_000),
+            username: self.username,
+            started_at,
+            step: EnrollmentStep::Welcome,
+            captures: vec![None; self.poses.len()],
+            poses: self.poses,
+            min_quality: self.min_quality,
+            metadata: self.metadata,
+            generated_dir: self.generated_dir,
+            consented_at: None,
+        };
+        let flow = EnrollmentFlow { store: self.auth.enrollment_flow_store(), auth: self.auth, state, listener: None };
+        flow.store.save(&flow.state)?;
+        Ok(flow)
+    }
+}
+
+type Listener = Arc<dyn Fn(&EnrollmentEvent) + Send + Sync>;
+
+/// One person's guided enrollment, see the [module docs](self)
+///
+/// Calls out of order for the current [`EnrollmentStep`] fail without
+/// changing anything.
+pub struct EnrollmentFlow {
+    auth: FaceAuth,
+    store: FlowStore,
+    state: EnrollmentState,
+    listener: Option<Listener>,
+}
+
+impl fmt::Debug for EnrollmentFlow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EnrollmentFlow").field("state", &self.state).finish_non_exhaustive()
+    }
+}
+
+impl EnrollmentFlow {
+    pub(crate) fn resume(auth: FaceAuth, id: &str) -> Result<Self> {
+        let store = auth.enrollment_flow_store();
+        let state = store.load(id)?;
+        Ok(Self { auth, store, state, listener: None })
+    }
+
+    /// Receive [`EnrollmentEvent`]s, called on the thread driving the flow
+    pub fn on_event(&mut self, listener: impl Fn(&EnrollmentEvent) + Send + Sync + 'static) {
+        self.listener = Some(Arc::new(listener));
+    }
+
+    /// Id to resume the flow with after a restart
+    pub fn id(&self) -> &str {
+        &self.state.id
+    }
+
+    pub fn step(&self) -> EnrollmentStep {
+        self.state.step
+    }
+
+    pub fn state(&self) -> &EnrollmentState {
+        &self.state
+    }
+
+    pub fn poses(&self) -> &[Pose] {
+        &self.state.poses
+    }
+
+    /// The pose to capture next, while capturing
+    pub fn current_pose(&self) -> Option<Pose> {
+        match self.state.step {
+            EnrollmentStep::Capture { index } => Some(self.state.poses[index]),
+            _ => None,
+        }
+    }
+
+    /// Leave the welcome screen for the consent screen
+    pub fn begin(&mut self) -> Result<()> {
+        self.expect(EnrollmentStep::Welcome)?;
+        self.advance(EnrollmentStep::Consent)
+    }
+
+    /// Record the user's consent and start capturing
+    pub fn give_consent(&mut self) -> Result<()> {
+        self.expect(EnrollmentStep::Consent)?;
+        self.state.consented_at = Some(Utc::now());
+        self.advance(EnrollmentStep::Capture { index: 0 })
+    }
+
+    /// The user refused biometric processing; the flow is cancelled
+    pub fn decline_consent(&mut self) -> Result<()> {
+        self.expect(EnrollmentStep::Consent)?;
+        self.cancel()
+    }
+
+    /// Capture the current pose, moving on to the next pose without a retake
+    /// pending, or to the review after the last one
+    ///
+    /// A capture without a face is kept as attempted; it shows up in
+    /// [`EnrollmentFlow::needs_retake`].
+    pub async fn capture(&mut self) -> Result<&PoseCapture> {
+        let EnrollmentStep::Capture { index } = self.state.step else {
+            bail!("Enrollment flow is not capturing but at {:?}", self.state.step);
+        };
+        let pose = self.state.poses[index];
+        let (mut outcome, sample) = self.auth.capture_enrollment_sample(&self.state.username).await?;
+        outcome.index = index as u32 + 1;
+        let sample = sample.map(|sample| FaceSample { sample_id: self.auth.new_sample_id(), ..sample });
+        let capture = PoseCapture { pose, outcome, sample };
+        self.state.captures[index] = Some(capture.clone());
+        let next = match self.state.captures.iter().position(Option::is_none) {
+            Some(index) => EnrollmentStep::Capture { index },
+            None => EnrollmentStep::Review,
+        };
+        self.emit(&EnrollmentEvent::PoseCaptured(capture));
+        self.advance(next)?;
+        Ok(self.state.captures[index].as_ref().expect("capture was just stored"))
+    }
+
+    /// Latest capture of each attempted pose
+    pub fn captures(&self) -> impl Iterator<Item = &PoseCapture> {
+        self.state.captures.iter().flatten()
+    }
+
+    /// Indices of attempted poses without a face or below the minimum quality
+    pub fn needs_retake(&self) -> Vec<usize> {
+        self.state.captures.iter().enumerate()
+            .filter(|(_, capture)| capture.as_ref().is_some_and(|c| !c.is_acceptable(self.state.min_quality)))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Capture the pose at `index` again, from the review
+    pub fn retake(&mut self, index: usize) -> Result<()> {
+        self.expect(EnrollmentStep::Review)?;
+        if index >= self.state.poses.len() {
+            bail!("Enrollment flow has no pose {}", index);
+        }
+        self.advance(EnrollmentStep::Capture { index })
+    }
+
+    /// Enroll the reviewed captures, writing the credential to the generated
+    /// directory (and promoting it with `auto_promote`)
+    ///
+    /// Fails while [`EnrollmentFlow::needs_retake`] lists any pose, and if
+    /// another user was enrolled under the name since the flow started.
+    pub async fn confirm(&mut self) -> Result<RegistrationOutcome> {
+        self.expect(EnrollmentStep::Review)?;
+        if let Some(index) = self.needs_retake().first() {
+            bail!("Pose {} ({:?}) needs to be captured again", index + 1, self.state.poses[*index]);
+        }
+        let outcome = self.auth.enroll_captures(&self.state).await?;
+        self.state.step = EnrollmentStep::Completed;
+        self.store.remove(&self.state.id)?;
+        self.emit(&EnrollmentEvent::StepChanged(EnrollmentStep::Completed));
+        self.emit(&EnrollmentEvent::Completed(outcome.clone()));
+        Ok(outcome)
+    }
+
+    /// Stop the flow and discard its captures
+    pub fn cancel(&mut self) -> Result<()> {
+        if matches!(self.state.step, EnrollmentStep::Completed | EnrollmentStep::Cancelled) {
+            bail!("Enrollment flow already finished");
+        }
+        self.state.step = EnrollmentStep::Cancelled;
+        for capture in self.state.captures.iter_mut().filter_map(Option::take) {
+            if let Some(path) = capture.sample.and_then(|sample| sample.image_path) {
+                let _ = fs::remove_file(path);
+            }
+        }
+        self.store.remove(&self.state.id)?;
+        self.emit(&EnrollmentEvent::StepChanged(EnrollmentStep::Cancelled));
+        Ok(())
+    }
+
+    fn expect(&self, step: EnrollmentStep) -> Result<()> {
+        if self.state.step != step {
+            bail!("Enrollment flow is at {:?}, not {:?}", self.state.step, step);
+        }
+        Ok(())
+    }
+
+    fn advance(&mut self, step: EnrollmentStep) -> Result<()> {
+        self.state.step = step;
+        self.store.save(&self.state)?;
+        self.emit(&EnrollmentEvent::StepChanged(step));
+        Ok(())
+    }
+
+    fn emit(&self, event: &EnrollmentEvent) {
+        if let Some(listener) = &self.listener {
+            listener(event);
+        }
+    }
+}
+
+/// Directory of unfinished flows, one JSON file each
+#[derive(Debug, Clone)]
+pub(crate) struct FlowStore {
+    dir: PathBuf,
+}
+
+impl FlowStore {
+    pub(crate) fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Unfinished flows, oldest first
+    pub(crate) fn list(&self) -> Result<Vec<EnrollmentState>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut flows = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                match load(&path) {
+                    Ok(state) => flows.push(state),
+                    Err(e) => tracing::warn!("Skipping {}: {:#}", path.display(), e),
+                }
+            }
+        }
+        flows.sort_by(|a, b| a.started_at.cmp(&b.started_at).then_with(|| a.id.cmp(&b.id)));
+        Ok(flows)
+    }
+
+    fn load(&self, id: &str) -> Result<EnrollmentState> {
+        load(&self.path(id)?).map_err(|_| anyhow!("No unfinished enrollment flow '{}'", id))
+    }
+
+    fn save(&self, state: &EnrollmentState) -> Result<()> {
+        write_atomic(&self.path(&state.id)?, serde_json::to_string_pretty(state)?.as_bytes())
+    }
+
+    fn remove(&self, id: &str) -> Result<()> {
+        match fs::remove_file(self.path(id)?) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn path(&self, id: &str) -> Result<PathBuf> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            bail!("Invalid enrollment flow id '{}'", id);
+        }
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+}
+
+fn load(path: &Path) -> Result<EnrollmentState> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(serde_json::from_str(&content)?)
+}
@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+
+use crate::matching;
+
+/// Fraction of saturated pixels around the document portrait above which
+/// glare is blamed when no face is found
+pub const MAX_GLARE: f64 = 0.05;
+
+/// The portrait found on an identity document photo
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DocumentFace {
+    /// Encoding of the largest face, the main portrait rather than a ghost image
+    pub encoding: Option<Vec<f64>>,
+    /// Faces detected on the document
+    #[serde(default)]
+    pub faces: usize,
+    /// Fraction of saturated pixels (0.0-1.0) on the portrait, or on the
+    /// whole photo when no face was found
+    #[serde(default)]
+    pub glare: f64,
+    /// Whether glare was filled in before detection
+    #[serde(default)]
+    pub glare_removed: bool,
+}
+
+/// Maps the distance between a selfie and a document portrait to a match
+/// probability with a logistic curve
+///
+/// Document portraits are older, smaller and printed, so same-person
+/// distances run higher than between two live captures. The defaults suit
+/// the dlib encodings; refit `midpoint` and `steepness` on labeled pairs
+/// from your own document types for other models.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DocumentCalibration {
+    /// Distance scored 0.5; matches need a distance below it
+    pub midpoint: f64,
+    /// How quickly the score falls around the midpoint
+    pub steepness: f64,
+}
+
+impl Default for DocumentCalibration {
+    fn default() -> Self {
+        Self { midpoint: 0.55, steepness: 15.0 }
+    }
+}
+
+impl DocumentCalibration {
+    /// Probability (0.0-1.0) that two faces `distance` apart are the same person
+    pub fn score(&self, distance: f64) -> f64 {
+        1.0 / (1.0 + (self.steepness * (distance - self.midpoint)).exp())
+    }
+}
+
+/// Why a selfie couldn't be compared with a document
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentIssue {
+    NoSelfieFace,
+    NoDocumentFace,
+    /// No portrait was found and the document reflects too much light;
+    /// retake it at an angle
+    Glare,
+}
+
+/// Outcome of [`FaceAuth::match_document`](crate::FaceAuth::match_document)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentMatch {
+    pub is_match: bool,
+    /// Calibrated match probability (0.0-1.0)
+    pub score: f64,
+    pub distance: Option<f64>,
+    pub glare: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issue: Option<DocumentIssue>,
+}
+
+impl DocumentMatch {
+    pub fn compare(selfie: Option<&[f64]>, document: &DocumentFace, calibration: &DocumentCalibration) -> Self {
+        let issue = match (selfie, &document.encoding) {
+            (None, _) => Some(DocumentIssue::NoSelfieFace),
+            (_, None) if document.glare > MAX_GLARE => Some(DocumentIssue::Glare),
+            (_, None) => Some(DocumentIssue::NoDocumentFace),
+            _ => None,
+        };
+        let distance = selfie.zip(document.encoding.as_deref()).map(|(selfie, portrait)| matching::face_distance(selfie, portrait));
+        let score = distance.map_or(0.0, |distance| calibration.score(distance));
+        Self { is_match: score >= 0.5, score, distance, glare: document.glare, issue }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scores_are_calibrated_around_the_midpoint() {
+        let calibration = DocumentCalibration::default();
+        assert_eq!(calibration.score(0.55), 0.5);
+        assert!(calibration.score(0.35) > 0.95);
+        assert!(calibration.score(0.75) < 0.05);
+
+        let portrait = DocumentFace { encoding: Some(vec![0.0, 0.4]), faces: 2, ..Default::default() };
+        let matched = DocumentMatch::compare(Some(&[0.0, 0.0]), &portrait, &calibration);
+        assert!(matched.is_match && matched.issue.is_none());
+        assert_eq!(matched.distance, Some(0.4));
+
+        let glare = DocumentFace { glare: 0.2, ..Default::default() };
+        let retake = DocumentMatch::compare(Some(&[0.0, 0.0]), &glare, &calibration);
+        assert_eq!((retake.is_match, retake.score, retake.issue), (false, 0.0, Some(DocumentIssue::Glare)));
+        assert_eq!(DocumentMatch::compare(None, &portrait, &calibration).issue, Some(DocumentIssue::NoSelfieFace));
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::face_storage::UserProfile;
+use crate::matching::{self, MatchCandidate};
+
+/// Default number of decoded profiles kept by [`LazyDatabase`]
+pub const DEFAULT_PROFILE_CACHE: usize = 256;
+
+/// Top level of the database with user entries left undecoded
+#[derive(Deserialize)]
+struct RawDatabase {
+    #[serde(default)]
+    users: BTreeMap<String, Box<RawValue>>,
+}
+
+/// Database whose user profiles are decoded on first use
+///
+/// Opening only splits the file into per-user JSON fragments; verifying one
+/// user decodes just that profile. Recently used profiles are kept in an
+/// LRU cache.
+pub struct LazyDatabase {
+    users: BTreeMap<String, Box<RawValue>>,
+    cache: Mutex<ProfileCache>,
+}
+
+impl LazyDatabase {
+    /// Open the database, returning `None` if it has not been created yet
+    pub fn open(path: impl AsRef<Path>, cache_capacity: usize) -> Result<Option<Self>> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(path)?;
+        let raw: RawDatabase = serde_json::from_str(&content)
+            .map_err(|e| anyhow!("Invalid database {}: {}", path.display(), e))?;
+        Ok(Some(Self {
+            users: raw.users,
+            cache: Mutex::new(ProfileCache::new(cache_capacity)),
+        }))
+    }
+
+    pub fn usernames(&self) -> impl Iterator<Item = &str> {
+        self.users.keys().map(String::as_str)
+    }
+
+    pub fn contains(&self, username: &str) -> bool {
+        self.users.contains_key(username)
+    }
+
+    pub fn len(&self) -> usize {
+        self.users.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+
+    /// Decoded profile of `username`, from the cache when possible
+    pub fn profile(&self, username: &str) -> Result<Option<Arc<UserProfile>>> {
+        let Some(raw) = self.users.get(username) else {
+            return Ok(None);
+        };
+        if let Some(profile) = self.cache().get(username) {
+            return Ok(Some(profile));
+        }
+
+        let profile: Arc<UserProfile> = Arc::new(serde_json::from_str(raw.get())
+            .map_err(|e| anyhow!("Invalid profile for {}: {}", username, e))?);
+        self.cache().insert(username, profile.clone());
+        Ok(Some(profile))
+    }
+
+    /// Compare `probe` against one claimed user only (1:1 verification)
+    pub fn verify(&self, username: &str, probe: &[f64], tolerance: f64) -> Result<Option<MatchCandidate>> {
+        Ok(self.profile(username)?
+            .and_then(|profile| matching::best_match([profile.as_ref()], probe, tolerance)))
+    }
+
+    /// Number of decoded profiles currently cached
+    pub fn cached_profiles(&self) -> usize {
+        self.cache().entries.len()
+    }
+
+    fn cache(&self) -> MutexGuard<'_, ProfileCache> {
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Least-recently-used cache of decoded profiles
+struct ProfileCache {
+    capacity: usize,
+    /// Profile and the tick of its last use
+    entries: HashMap<String, (Arc<UserProfile>, u64)>,
+    tick: u64,
+}
+
+impl ProfileCache {
+    fn new(capacity: usize) -> Self {
+        Self { capacity: capacity.max(1), entries: HashMap::new(), tick: 0 }
+    }
+
+    fn get(&mut self, username: &str) -> Option<Arc<UserProfile>> {
+        self.tick += 1;
+        let entry = self.entries.get_mut(username)?;
+        entry.1 = self.tick;
+        Some(entry.0.clone())
+    }
+
+    fn insert(&mut self, username: &str, profile: Arc<UserProfile>) {
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(username) {
+            let oldest = self.entries.iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(name, _)| name.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.tick += 1;
+        self.entries.insert(username.to_string(), (profile, self.tick));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles_decoded_on_demand_and_evicted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db.json");
+        let sample = |v: f64| serde_json::json!({ "encoding": [v, v], "timestamp": "", "sample_id": "s" });
+        fs::write(&path, serde_json::json!({
+            "version": "1.0",
+            "users": {
+                "ann": { "user_id": "ann", "face_encodings": [sample(0.0)] },
+                "bob": { "user_id": "bob", "face_encodings": [sample(1.0)] },
+                "broken": { "user_id": 7 }
+            }
+        }).to_string()).unwrap();
+
+        let db = LazyDatabase::open(&path, 1).unwrap().unwrap();
+        assert_eq!(db.len(), 3);
+        assert_eq!(db.cached_profiles(), 0);
+
+        assert!(db.verify("ann", &[0.1, 0.0], 0.6).unwrap().unwrap().is_match);
+        assert!(!db.verify("bob", &[0.1, 0.0], 0.6).unwrap().unwrap().is_match);
+        assert_eq!(db.cached_profiles(), 1);
+        assert!(db.profile("nobody").unwrap().is_none());
+        assert!(db.profile("broken").is_err());
+    }
+}
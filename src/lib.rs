@@ -9,138 +9,272 @@
 //! - User data export/import
 //! - File-based access control
 //!
-//! ## Example
+//! ## Cargo features
 //!
-//! ```no_run
-//! use face_auth::{FaceAuth, FaceAuthResult};
+//! The default build contains only the matching and storage core: credential
+//! files, the database, distance matching, audit log, backups, encryption,
+//! compression and result signing helpers.
+//! Everything else is opt-in:
 //!
-//! #[tokio::main]
-//! async fn main() -> anyhow::Result<()> {
-//!     let auth = FaceAuth::new()?;
+//! - `python-backend` - [`FaceAuth`] and the bundled Python script, run as a child process
+//! - `camera` - camera capture sources beyond the Python backend's default camera
+//! - `server` - HTTP server verifying faces sent by devices in hybrid mode
+//! - `admin-ui` - web UI for admins, embedded in the `server`
+//! - `native-ml` - in-process ML models, such as custom ONNX encoders and
+//!   manipulation detectors and age estimators
+//! - `cloud-aws`, `cloud-azure` - AWS Rekognition and Azure Face backends
+//! - `hybrid` - device-side pre-filtering with verification by a `server`
+//! - `transfer` - encrypted credential transfer between devices
+//! - `nfc` - encrypted credentials on NFC tags, through PC/SC readers
+//! - `qr` - badge QR codes naming the user for 1:1 verification
+//! - `speech` - spoken feedback through the platform's `say`/`espeak`
+//! - `telemetry` - opt-in reporting of anonymous aggregate counters
+//! - `actuator` - GPIO relays, commands and URLs triggered on granted access (Linux)
+//! - `mqtt` - MQTT publishing and Home Assistant discovery
+//! - `attention` - looking-at-camera events for tracked faces, from landmarks
+//! - `secure-sketch` - experimental face-derived keys from fuzzy extractors,
+//!   and secrets released only to a live match
+//! - `webhooks` - delivering queued access events to HTTP endpoints
+//! - `notifications` - email (SMTP) and Slack alerts, configured from TOML
+//! - `self-update` - signed updates of bundled scripts, executables and models
+//! - `windows-service` - running the `server` as a Windows service
+//! - `wasm` - decision policies and hooks shipped as sandboxed WASM modules
+//! - `cli` - the `face_auth` binary and its terminal UI
+//! - `full` - all of the above
 //!
-//!     // Register a user
-//!     auth.register_user("john", 3).await?;
+//! ## API versions
 //!
-//!     // Authenticate
-//!     let result = auth.authenticate_user(0.6).await?;
-//!
-//!     if result.is_authenticated {
-//!         println!("Welcome, {}!", result.user_id.unwrap_or_default());
-//!     }
-//!
-//!     Ok(())
-//! }
-//! ```
+//! The [`v1`] module is the stable surface of the crate; see its docs for
+//! what it guarantees.
 
+#[cfg(all(feature = "actuator", target_os = "linux"))]
+pub mod actuator;
+pub mod age;
+pub mod assurance;
+#[cfg(feature = "attention")]
+pub mod attention;
+pub mod audit;
+#[cfg(feature = "python-backend")]
+mod auth;
+#[cfg(feature = "python-backend")]
+pub mod auth_flow;
+#[cfg(feature = "python-backend")]
+pub mod backend;
+pub mod backup;
+#[cfg(all(feature = "actuator", target_os = "linux"))]
+pub mod badge;
+pub mod bench;
+pub mod camera;
+#[cfg(any(feature = "cloud-aws", feature = "cloud-azure"))]
+pub mod cloud;
+pub mod clock;
+pub mod compression;
+pub mod context;
+pub mod cross_match;
+pub mod crypto;
+#[cfg(feature = "python-backend")]
+pub mod demo;
+pub mod denial;
+pub mod device;
+pub mod embeddings;
+pub mod error;
+#[cfg(feature = "python-backend")]
+pub mod explain;
+#[cfg(feature = "python-backend")]
+pub mod fake_backend;
+pub mod face_storage;
+pub mod far;
+pub mod feedback;
+#[cfg(feature = "python-backend")]
+pub mod fusion;
+pub mod gc;
+pub mod health;
+pub mod hooks;
+#[cfg(feature = "mqtt")]
+pub mod home_assistant;
+#[cfg(any(feature = "hybrid", feature = "server"))]
+pub mod hybrid;
+pub mod interop;
+pub mod journal;
+#[cfg(feature = "python-backend")]
+pub mod kiosk;
+pub mod kyc;
+pub mod lazy_database;
+pub mod manipulation;
+pub mod matching;
+pub mod messages;
+pub mod migrate;
+pub mod moderation;
+pub mod multimodal;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+#[cfg(feature = "native-ml")]
+pub mod native;
+#[cfg(feature = "nfc")]
+pub mod nfc;
+pub mod nonce;
+pub mod notify;
+pub mod outbox;
+pub mod outliers;
+pub mod platform;
+pub mod policy;
+pub mod profiles;
+pub mod provenance;
+#[cfg(feature = "qr")]
+pub mod qr;
+pub mod random;
+#[cfg(feature = "python-backend")]
+pub mod redact;
+pub mod registration;
+pub mod sample_id;
+#[cfg(feature = "python-backend")]
+pub mod replay;
+pub mod scene;
+pub mod schema;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod signing;
+#[cfg(feature = "secure-sketch")]
+pub mod sketch;
+pub mod snapshot;
+#[cfg(feature = "source-watch")]
+pub mod source_watch;
+#[cfg(feature = "python-backend")]
 pub mod standalone_python;
+#[cfg(all(feature = "server", unix))]
+pub mod systemd;
+#[cfg(feature = "telemetry")]
+pub mod telemetry;
+pub mod timing;
+pub mod tracking;
+#[cfg(feature = "transfer")]
+pub mod transfer;
+#[cfg(feature = "self-update")]
+pub mod update;
+#[cfg(feature = "python-backend")]
+pub mod v1;
+#[cfg(feature = "secure-sketch")]
+pub mod vault;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod welcome;
+#[cfg(feature = "windows-service")]
+pub mod winservice;
+#[cfg(feature = "python-backend")]
+pub mod worker_pool;
 
-use anyhow::Result;
-pub use standalone_python::{StandalonePythonFaceAuth, StandaloneAuthResult};
-
-/// Main face authentication interface
-pub struct FaceAuth {
-    python_auth: StandalonePythonFaceAuth,
-}
-
-/// Authentication result
-#[derive(Debug, Clone)]
-pub struct FaceAuthResult {
-    pub is_authenticated: bool,
-    pub user_id: Option<String>,
-    pub confidence: Option<f64>,
-    pub distance: Option<f64>,
-    pub threshold: Option<f64>,
-    pub processing_time_ms: Option<u32>,
-}
-
-impl From<StandaloneAuthResult> for FaceAuthResult {
-    fn from(result: StandaloneAuthResult) -> Self {
-        Self {
-            is_authenticated: result.is_match.unwrap_or(false),
-            user_id: result.matched_user,
-            confidence: result.confidence,
-            distance: result.distance,
-            threshold: result.threshold,
-            processing_time_ms: result.processing_time_ms,
-        }
-    }
-}
-
-impl FaceAuth {
-    /// Create a new FaceAuth instance
-    pub fn new() -> Result<Self> {
-        Ok(Self {
-            python_auth: StandalonePythonFaceAuth::new()?,
-        })
-    }
-
-    /// Register a new user with face samples
-    ///
-    /// # Arguments
-    ///
-    /// * `username` - The username to register
-    /// * `samples` - Number of face samples to capture (default: 3)
-    /// * `generated_dir` - Directory path where user data will be saved
-    ///
-    /// # Returns
-    ///
-    /// Returns `Ok(true)` if registration was successful, `Ok(false)` if it failed
-    pub async fn register_user(&self, username: &str, samples: u32, generated_dir: &str) -> Result<bool> {
-        self.python_auth.register_user(username, samples, generated_dir)
-    }
-
-    /// Authenticate a user by capturing their face
-    ///
-    /// # Arguments
-    ///
-    /// * `tolerance` - Face matching tolerance (0.0-1.0, lower = stricter)
-    /// * `source_dir` - Directory path where user data is loaded from
-    ///
-    /// # Returns
-    ///
-    /// Returns authentication result with user information
-    pub async fn authenticate_user(&self, tolerance: f64, source_dir: &str) -> Result<FaceAuthResult> {
-        let result = self.python_auth.authenticate_user(tolerance, source_dir)?;
-        Ok(result.into())
-    }
-
-    /// Export a user's face data to a file
-    ///
-    /// # Arguments
-    ///
-    /// * `username` - The username to export
-    /// * `filename` - Optional filename (auto-generated if empty)
-    pub async fn export_user(&self, username: &str, filename: &str) -> Result<bool> {
-        self.python_auth.export_user(username, filename)
-    }
-
-    /// Import a user's face data from a file
-    ///
-    /// # Arguments
-    ///
-    /// * `filename` - Path to the file to import
-    pub async fn import_user(&self, filename: &str) -> Result<bool> {
-        self.python_auth.import_user(filename)
-    }
-
-    /// List all registered users
-    pub async fn list_users(&self) -> Result<()> {
-        self.python_auth.list_users()
-    }
-
-    /// Check if the Python executable is working
-    pub async fn check_system(&self) -> Result<()> {
-        self.python_auth.check_executable()
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[tokio::test]
-    async fn test_face_auth_creation() {
-        let result = FaceAuth::new();
-        assert!(result.is_ok());
-    }
-}
+#[cfg(all(feature = "actuator", target_os = "linux"))]
+pub use actuator::{Actuator, ActuatorConfig, Output, OutputRule};
+pub use age::{AgeEstimate, AgeEstimator, MinimumAgePolicy};
+pub use assurance::{AssuranceLevel, Liveness, Session};
+#[cfg(feature = "attention")]
+pub use attention::{Attention, AttentionConfig, FaceLandmarks, HeadPose};
+pub use audit::{AuditEntry, AuditLog, AuditOperation, AuditOutcome};
+#[cfg(feature = "python-backend")]
+pub use auth::{AmbiguityPolicy, EnforcementMode, FaceAuth, FaceAuthBuilder, FaceAuthResult, FrameBlur, PrivacyMode, StorageMode, WarmUpReport};
+#[cfg(feature = "python-backend")]
+pub use auth_flow::{AuthFlow, AuthFlowConfig, AuthState};
+#[cfg(feature = "python-backend")]
+pub use backend::FaceBackend;
+pub use backup::{BackupManifest, BackupSchedule, BackupSettings};
+pub use camera::CameraSource;
+#[cfg(all(feature = "actuator", target_os = "linux"))]
+pub use badge::{Badge, WiegandFormat};
+#[cfg(any(feature = "cloud-aws", feature = "cloud-azure"))]
+pub use cloud::{CloudBackend, CloudFaceService, CloudMatch};
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use compression::Compression;
+pub use context::AuthContext;
+pub use cross_match::{CrossMatch, CrossMatchResolution};
+#[cfg(feature = "python-backend")]
+pub use demo::{DemoImages, DemoPerson, DemoProbe, DemoStep};
+pub use denial::DenialReason;
+pub use device::{DeviceContention, DeviceOperation};
+pub use embeddings::{CredentialIndex, EmbeddingIndex, EmbeddingPrecision};
+pub use error::FaceAuthError;
+#[cfg(feature = "python-backend")]
+pub use explain::{DecisionFlip, Explanation, SampleContribution};
+#[cfg(feature = "python-backend")]
+pub use fake_backend::FakeBackend;
+pub use face_storage::{DatabaseStats, FaceDatabase, FaceSample, SampleSummary, StorageLayout, UserMetadata, UserProfile, UserStats, UserSummary};
+pub use far::{FarBudget, FarMonitor, FarReport, ImpostorScores};
+pub use feedback::{Feedback, FeedbackEvent};
+#[cfg(feature = "python-backend")]
+pub use fusion::{CameraScore, FusionStrategy};
+pub use gc::{GcReport, Orphan, OrphanedFile};
+#[cfg(feature = "speech")]
+pub use feedback::SpeechFeedback;
+pub use health::{HealthMonitor, HealthProbe, HealthStatus, Heartbeat};
+pub use hooks::{Hook, HookPoint, HookVerdict};
+#[cfg(feature = "mqtt")]
+pub use home_assistant::HomeAssistant;
+#[cfg(feature = "hybrid")]
+pub use hybrid::{HybridBackend, HybridConfig, RetryPolicy};
+#[cfg(any(feature = "hybrid", feature = "server"))]
+pub use hybrid::{NonceRequest, NonceResponse, PayloadMode, Probe, VerifyRequest, VerifyResponse};
+pub use journal::{Journal, RecoveredOperation};
+#[cfg(feature = "python-backend")]
+pub use kiosk::{EnrollmentEvent, EnrollmentFlow, EnrollmentFlowBuilder, EnrollmentState, EnrollmentStep, Pose, PoseCapture};
+pub use kyc::{DocumentCalibration, DocumentFace, DocumentIssue, DocumentMatch};
+pub use lazy_database::LazyDatabase;
+pub use manipulation::ManipulationDetector;
+pub use matching::{MatchCandidate, SampleDistance};
+pub use messages::{CaptureTip, MessageCatalog, QualityIssue};
+pub use moderation::{EnrollmentQueue, EnrollmentReview, PendingEnrollment};
+pub use multimodal::{ModalityScores, ModalityWeights, MultiModalPolicy, SecondaryBiometric};
+#[cfg(feature = "mqtt")]
+pub use mqtt::{MqttClient, MqttOptions};
+#[cfg(feature = "native-ml")]
+pub use native::{EncoderConfig, NativeBackend, OnnxEncoder, OnnxAgeEstimator, OnnxManipulationDetector};
+#[cfg(feature = "nfc")]
+pub use nfc::NfcReader;
+pub use nonce::{NonceRefused, NonceStore};
+pub use notify::{Alert, AlertRules, Alerts, CallbackNotifier, Notification, Notifier};
+#[cfg(feature = "notifications")]
+pub use notify::{EmailNotifier, NotificationConfig, SlackNotifier, SmtpConfig, SmtpSecurity};
+pub use outbox::{AccessEvent, CallbackSink, EventSink, Outbox, OutboxConfig};
+#[cfg(feature = "mqtt")]
+pub use outbox::MqttSink;
+#[cfg(feature = "webhooks")]
+pub use outbox::WebhookSink;
+pub use outliers::SampleQuarantine;
+pub use platform::{CommandBiometrics, PlatformBiometrics};
+pub use policy::{DecisionPolicy, PolicyDecision, PolicyInput};
+#[cfg(feature = "wasm")]
+pub use policy::WasmPolicy;
+pub use profiles::{Preprocessing, ThresholdProfile, ThresholdProfiles};
+pub use provenance::ImageProvenance;
+#[cfg(feature = "qr")]
+pub use qr::decode_qr;
+pub use random::{OsRandom, RandomSource, SeededRandom};
+#[cfg(feature = "python-backend")]
+pub use redact::{Redaction, RedactionPolicy, RedactionStyle};
+pub use registration::{EnrollmentProgress, RegistrationOutcome, SampleOutcome, SampleQuality, SampleStatus};
+#[cfg(feature = "python-backend")]
+pub use replay::{RecordedDecision, ReplayComparison, ReplayReport, SessionRecord, SessionRecorder};
+#[cfg(feature = "server")]
+pub use server::{Server, ServerListener};
+pub use sample_id::SampleIdRewrite;
+pub use scene::{SceneGate, SceneStats};
+pub use schema::{CredentialReport, SchemaIssue};
+pub use signing::{ResultClaims, SignedResult, SignedSession};
+#[cfg(feature = "secure-sketch")]
+pub use sketch::{SecureSketch, SketchParams};
+pub use snapshot::DatabaseSnapshot;
+#[cfg(feature = "source-watch")]
+pub use source_watch::{SourceEvent, SourceWatcher};
+#[cfg(feature = "python-backend")]
+pub use standalone_python::{StandalonePythonFaceAuth, StandaloneAuthResult, WarmUpOutcome};
+#[cfg(feature = "telemetry")]
+pub use telemetry::{TelemetryConfig, TelemetryReport, TelemetryReporter};
+pub use timing::TimingBreakdown;
+pub use tracking::{TrackEvent, TrackingConfig};
+#[cfg(feature = "self-update")]
+pub use update::{UpdateOutcome, Updater};
+#[cfg(feature = "python-backend")]
+pub use v1::{AuthRequest, Frame};
+#[cfg(feature = "secure-sketch")]
+pub use vault::{SealedSecret, UnlockPolicy};
+pub use welcome::{Greeting, TimeOfDay};
+#[cfg(feature = "python-backend")]
+pub use worker_pool::{PoolStatus, WorkerFailure, WorkerPool, WorkerStatus};
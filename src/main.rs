@@ -1,254 +1,1265 @@
 use anyhow::Result;
-use std::io::{self, Write};
-use face_auth::StandalonePythonFaceAuth;
+use clap::{CommandFactory, Parser, Subcommand};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use face_auth::face_storage::{credential_path, validate_username};
+use face_auth::{interop, migrate, source_watch, DemoImages, DemoStep, DenialReason, FaceAuth, FaceAuthResult, FaceDatabase, SceneGate, SourceEvent, StandalonePythonFaceAuth, StorageLayout, TrackEvent, TrackingConfig};
+
+mod tui;
+
+/// Shown instead of a denial while nobody is enrolled
+const NO_USERS_HINT: &str = "👋 No one is enrolled yet: run `face_auth` and press r to register a user, or try `face_auth demo`";
+
+/// Log file of the terminal UI, in the working directory
+const TUI_LOG: &str = "face_auth.log";
+
+/// Face authentication system; opens the terminal UI without a subcommand
+#[derive(Parser)]
+#[command(name = "face_auth", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Measure matching, database and export/import speed on this machine
+    Bench {
+        /// Database sizes to measure, in samples
+        #[arg(long, value_delimiter = ',', default_values_t = face_auth::bench::DEFAULT_SAMPLE_COUNTS)]
+        samples: Vec<usize>,
+        /// Time spent on each operation, in milliseconds
+        #[arg(long, default_value_t = 1000)]
+        millis: u64,
+        /// Directory for temporary benchmark files
+        #[arg(long)]
+        dir: Option<PathBuf>,
+        /// Write the results as JSON, for use as a later baseline
+        #[arg(long)]
+        save: Option<PathBuf>,
+        /// Fail if any operation is slower than in this saved report
+        #[arg(long)]
+        baseline: Option<PathBuf>,
+        /// Allowed slowdown against the baseline, in percent
+        #[arg(long, default_value_t = 20.0)]
+        max_regression: f64,
+    },
+    /// Check the backend, the database and the credential files
+    #[command(visible_alias = "doctor")]
+    Diagnose {
+        #[command(flatten)]
+        dirs: StorageDirs,
+        /// Reinstall the pinned Python dependencies before checking, e.g.
+        /// after the workers failed to import them
+        #[arg(long)]
+        repair_python: bool,
+    },
+    /// Check every credential file in a directory against the schema, e.g.
+    /// before deploying it to the doors
+    Validate {
+        /// Credentials directory, such as `generated` or `source`
+        #[arg(default_value = "source")]
+        dir: PathBuf,
+    },
+    /// Enroll and authenticate synthetic faces from image files, to try the
+    /// pipeline without a camera; the drawings are only tested with the fake
+    /// backend and the real face detector may not find them
+    Demo {
+        /// Keep the photos and the demo database here instead of a temporary directory
+        #[arg(long)]
+        dir: Option<PathBuf>,
+        #[arg(long, default_value_t = 0.6)]
+        tolerance: f64,
+    },
+    /// Print a completion script, e.g. `face_auth completions bash > /etc/bash_completion.d/face_auth`
+    Completions {
+        shell: clap_complete::Shell,
+    },
+    /// Write man pages for face_auth and each of its commands
+    Man {
+        /// Directory the pages are written to
+        #[arg(long, default_value = "man")]
+        out: PathBuf,
+    },
+    /// Import encodings from a face_recognition pickle or JSON store
+    Migrate {
+        /// Store to convert (.pkl/.pickle files are loaded with Python)
+        #[arg(long = "from")]
+        from: PathBuf,
+        /// Directory holding the database
+        #[arg(long, default_value = ".")]
+        data_dir: PathBuf,
+        /// Directory credential files are written to
+        #[arg(long, default_value = "source")]
+        source_dir: PathBuf,
+        /// Replace users that are already registered
+        #[arg(long)]
+        overwrite: bool,
+    },
+    /// Re-encode the training images of an OpenCV LBPH recognizer
+    ImportLbph {
+        /// Model saved with `recognizer.write()`
+        #[arg(long)]
+        model: PathBuf,
+        /// Training images, one directory per label or `User.<label>.<n>.jpg` files
+        #[arg(long)]
+        images: PathBuf,
+        /// Directory holding the database
+        #[arg(long, default_value = ".")]
+        data_dir: PathBuf,
+        /// Directory credential files are written to
+        #[arg(long, default_value = "source")]
+        source_dir: PathBuf,
+        /// Replace users that are already registered
+        #[arg(long)]
+        overwrite: bool,
+    },
+    /// Write dlib-compatible aligned face chips of every enrolled sample
+    ExportChips {
+        /// Output directory, one subdirectory per user
+        #[arg(long)]
+        out: PathBuf,
+        /// Directory holding the database
+        #[arg(long, default_value = ".")]
+        data_dir: PathBuf,
+        /// Chip side length in pixels
+        #[arg(long, default_value_t = face_auth::interop::DLIB_CHIP_SIZE)]
+        size: u32,
+    },
+    /// Verify faces sent by devices running in hybrid mode and accept remote
+    /// enrollments (device token from FACE_AUTH_DEVICE_TOKEN, moderation token
+    /// from FACE_AUTH_ADMIN_TOKEN, submission token from FACE_AUTH_SUBMIT_TOKEN)
+    #[cfg(feature = "server")]
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: std::net::SocketAddr,
+        /// Listen on this Unix socket instead of `addr` (a socket passed by
+        /// systemd socket activation takes precedence over both)
+        #[arg(long)]
+        unix_socket: Option<PathBuf>,
+        /// Run as the named Windows service; set by `service-install`
+        #[arg(long, hide = true)]
+        windows_service: Option<String>,
+        /// Directory holding the database
+        #[arg(long, default_value = ".")]
+        data_dir: PathBuf,
+        /// Directory holding the credential files to match against
+        #[arg(long, default_value = "source")]
+        source_dir: PathBuf,
+        /// Write scheduled backups to this directory (key from FACE_AUTH_BACKUP_KEY)
+        #[arg(long)]
+        backup_dir: Option<PathBuf>,
+        /// Hours between scheduled backups
+        #[arg(long, default_value_t = 24)]
+        backup_hours: u64,
+        /// Scheduled backups to keep
+        #[arg(long, default_value_t = 7)]
+        backup_keep: usize,
+        /// POST a health heartbeat to this URL
+        #[arg(long)]
+        heartbeat_url: Option<String>,
+        /// Seconds between heartbeats
+        #[arg(long, default_value_t = 60)]
+        heartbeat_secs: u64,
+        /// JSON file of threshold profiles to pick from by scene brightness
+        #[arg(long)]
+        profiles: Option<PathBuf>,
+        /// Require a single-use nonce in verification requests, valid this many seconds
+        #[arg(long)]
+        nonce_secs: Option<u64>,
+        /// TOML file of alert rules and email/Slack channels to notify
+        #[arg(long)]
+        notifications: Option<PathBuf>,
+        /// Loosest tolerance devices can ask for
+        #[arg(long, default_value_t = face_auth::server::DEFAULT_MAX_TOLERANCE)]
+        max_tolerance: f64,
+    },
+    /// Write an encrypted backup of the database, credentials and settings
+    /// (key from FACE_AUTH_BACKUP_KEY, 64 hex digits)
+    Backup {
+        /// Backup file to write
+        #[arg(long)]
+        out: PathBuf,
+        #[command(flatten)]
+        dirs: StorageDirs,
+    },
+    /// Restore a backup written by `backup` (key from FACE_AUTH_BACKUP_KEY)
+    Restore {
+        /// Backup file to read
+        #[arg(long = "from")]
+        from: PathBuf,
+        #[command(flatten)]
+        dirs: StorageDirs,
+    },
+    /// Refresh a user's samples after verifying their face against the current ones
+    Reenroll {
+        /// User to re-enroll
+        #[arg(long)]
+        user: String,
+        /// Face matching tolerance for the verification and the new samples
+        #[arg(long, default_value_t = 0.6)]
+        tolerance: f64,
+        #[command(flatten)]
+        dirs: StorageDirs,
+    },
+    /// Authenticate whoever steps in front of the camera until interrupted,
+    /// skipping frames where nothing moved or no face is visible
+    Watch {
+        /// Face matching tolerance
+        #[arg(long, default_value_t = 0.6)]
+        tolerance: f64,
+        /// Fraction of pixels that must change between frames
+        #[arg(long, default_value_t = 0.01)]
+        min_change: f64,
+        /// Match every changed frame, not only those where a face is visible
+        #[arg(long)]
+        no_face_check: bool,
+        /// Seconds to pause after each attempt
+        #[arg(long, default_value_t = 2.0)]
+        cooldown_secs: f64,
+        /// Follow faces across frames, matching each person once and
+        /// reporting when they leave
+        #[arg(long)]
+        track: bool,
+        /// With --track, warn when someone not recognized enters within this
+        /// many seconds of a granted user
+        #[arg(long)]
+        tailgating_secs: Option<u64>,
+        /// With --track, report when people start or stop looking at the
+        /// camera (needs the attention feature)
+        #[arg(long)]
+        attention: bool,
+        /// Actuator config of outputs (relays, commands, URLs) to trigger
+        /// when access is granted
+        #[arg(long)]
+        actuator: Option<PathBuf>,
+        /// MQTT broker (`host:port`) to publish Home Assistant sensors to;
+        /// credentials come from FACE_AUTH_MQTT_USER and FACE_AUTH_MQTT_PASSWORD
+        #[arg(long)]
+        home_assistant: Option<String>,
+        /// TOML file of alert rules and email/Slack channels to notify
+        #[arg(long)]
+        notifications: Option<PathBuf>,
+        /// Read frames from this video file instead of the camera, stopping
+        /// at its end
+        #[arg(long)]
+        video: Option<PathBuf>,
+        #[command(flatten)]
+        dirs: StorageDirs,
+    },
+    /// List a user's stored samples
+    Samples {
+        #[arg(long)]
+        user: String,
+        #[command(flatten)]
+        dirs: StorageDirs,
+    },
+    /// Delete one bad sample of a user, keeping the others
+    RemoveSample {
+        #[arg(long)]
+        user: String,
+        /// Sample id shown by `samples`
+        #[arg(long)]
+        sample: String,
+        #[command(flatten)]
+        dirs: StorageDirs,
+    },
+    /// Quarantine a user's samples that don't look like the others
+    Outliers {
+        #[arg(long)]
+        user: String,
+        #[command(flatten)]
+        dirs: StorageDirs,
+    },
+    /// Return a quarantined sample to matching
+    ReleaseSample {
+        #[arg(long)]
+        user: String,
+        /// Sample id shown by `samples`
+        #[arg(long)]
+        sample: String,
+        #[command(flatten)]
+        dirs: StorageDirs,
+    },
+    /// Give samples with old `<user>_<timestamp>` ids UUIDs in the database,
+    /// credential files and exports
+    MigrateSampleIds {
+        #[command(flatten)]
+        dirs: StorageDirs,
+    },
+    /// Report pairs of enrolled users whose faces are suspiciously close
+    CrossMatches {
+        /// Distance within which samples of two users count as a match
+        #[arg(long, default_value_t = 0.6)]
+        tolerance: f64,
+        #[command(flatten)]
+        dirs: StorageDirs,
+    },
+    /// List remotely submitted enrollments awaiting approval
+    Enrollments {
+        #[command(flatten)]
+        dirs: StorageDirs,
+    },
+    /// Check a pending enrollment for quality and duplicates and enroll it
+    ApproveEnrollment {
+        /// Id shown by `enrollments`
+        #[arg(long)]
+        id: String,
+        /// Distance within which the face counts as an already enrolled user
+        #[arg(long, default_value_t = 0.6)]
+        tolerance: f64,
+        #[command(flatten)]
+        dirs: StorageDirs,
+    },
+    /// Discard a pending enrollment and its photos
+    RejectEnrollment {
+        /// Id shown by `enrollments`
+        #[arg(long)]
+        id: String,
+        #[command(flatten)]
+        dirs: StorageDirs,
+    },
+    /// Install newer bundled scripts, executables and models from a signed
+    /// channel (publisher key from FACE_AUTH_UPDATE_KEY), rolling back if the
+    /// system check fails afterwards
+    #[cfg(feature = "self-update")]
+    Update {
+        /// Manifest URL or path of the channel
+        #[arg(long)]
+        channel: String,
+        /// Name of the channel the manifest must be published on
+        #[arg(long, default_value = "stable")]
+        channel_name: String,
+        /// Directory the bundled files are installed in
+        #[arg(long, default_value = ".")]
+        install_dir: PathBuf,
+        /// Only list what would be updated
+        #[arg(long)]
+        check: bool,
+        #[command(flatten)]
+        dirs: StorageDirs,
+    },
+    /// Install `serve` as an auto-restarting Windows service (run elevated)
+    #[cfg(feature = "windows-service")]
+    ServiceInstall {
+        /// Service name, also used as the event-log source
+        #[arg(long, default_value = face_auth::winservice::DEFAULT_NAME)]
+        name: String,
+        /// Arguments for `serve`, after `--`, e.g. `-- --data-dir C:\ProgramData\face_auth`
+        #[arg(last = true)]
+        serve_args: Vec<String>,
+    },
+    /// Stop and remove the Windows service
+    #[cfg(feature = "windows-service")]
+    ServiceUninstall {
+        /// Service name
+        #[arg(long, default_value = face_auth::winservice::DEFAULT_NAME)]
+        name: String,
+    },
+    /// Send a user's credential to a device running `receive-user`
+    #[cfg(feature = "transfer")]
+    SendUser {
+        /// User to send
+        #[arg(long)]
+        user: String,
+        /// Address of the receiving device, e.g. `192.168.1.20:7070`
+        #[arg(long)]
+        to: String,
+        /// Directory holding the database
+        #[arg(long, default_value = ".")]
+        data_dir: PathBuf,
+    },
+    /// Wait for one credential from a device running `send-user` and import it
+    #[cfg(feature = "transfer")]
+    ReceiveUser {
+        /// Address to listen on
+        #[arg(long, default_value = "0.0.0.0:7070")]
+        listen: String,
+        /// Directory holding the database
+        #[arg(long, default_value = ".")]
+        data_dir: PathBuf,
+    },
+    /// Write a user's encrypted credential to an NFC tag
+    /// (key from FACE_AUTH_PROVISIONING_KEY, 64 hex digits)
+    #[cfg(feature = "nfc")]
+    NfcWrite {
+        /// User to write
+        #[arg(long)]
+        user: String,
+        /// PC/SC reader name; the first reader with a tag if unset
+        #[arg(long)]
+        reader: Option<String>,
+        /// Directory holding the database
+        #[arg(long, default_value = ".")]
+        data_dir: PathBuf,
+    },
+    /// Import the credential on an NFC tag written by `nfc-write`
+    /// (key from FACE_AUTH_PROVISIONING_KEY)
+    #[cfg(feature = "nfc")]
+    NfcRead {
+        /// PC/SC reader name; the first reader with a tag if unset
+        #[arg(long)]
+        reader: Option<String>,
+        /// Directory holding the database
+        #[arg(long, default_value = ".")]
+        data_dir: PathBuf,
+    },
+    /// Refuse the NFC tag with a serial printed by `nfc-write` on this device
+    #[cfg(feature = "nfc")]
+    NfcRevoke {
+        /// Serial of the tag
+        #[arg(long)]
+        serial: String,
+        /// Directory holding the database
+        #[arg(long, default_value = ".")]
+        data_dir: PathBuf,
+    },
+}
+
+#[derive(clap::Args)]
+struct StorageDirs {
+    /// Directory holding the database
+    #[arg(long, default_value = ".")]
+    data_dir: PathBuf,
+    /// Directory registrations are written to
+    #[arg(long, default_value = "generated")]
+    generated_dir: PathBuf,
+    /// Directory holding the credential files to authenticate against
+    #[arg(long, default_value = "source")]
+    source_dir: PathBuf,
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    loop {
-        // Show main menu
-        println!("=================================");
-        println!("  Face Authentication System");
-        println!("=================================");
-        println!();
-        println!("📷 Camera Permission Notice:");
-        println!("This app requires camera access to capture your face.");
-        println!("macOS may ask for permission - please click 'Allow'.");
-        println!();
-        println!("Please select an option:");
-        println!("1. Register - High accuracy (99%+)");
-        println!("2. Authenticate - High accuracy (99%+)");
-        println!("3. Export User - Export user credentials to file");
-        println!("4. Import User - Import user credentials from file");
-        println!("5. List Users - Show all registered users");
-        println!("6. Exit");
-        println!();
-        print!("Enter your choice (1-6): ");
-        io::stdout().flush().unwrap();
-
-        let mut input = String::new();
-        if let Err(e) = io::stdin().read_line(&mut input) {
-            println!("Error reading input: {}. Please try again.", e);
-            continue;
+    let cli = Cli::parse();
+    // Python script output is forwarded through tracing; the terminal UI owns
+    // the screen, so it logs to a file instead
+    let subscriber = tracing_subscriber::fmt().with_target(false).without_time();
+    match cli.command {
+        None => {
+            let log = std::fs::OpenOptions::new().create(true).append(true).open(TUI_LOG)?;
+            subscriber.with_ansi(false).with_writer(std::sync::Mutex::new(log)).init();
         }
-        let choice = input.trim();
-
-        match choice {
-            "1" => {
-                println!("\n--- 🐍 High-Accuracy Face Registration ---");
-                println!("This uses Python's face_recognition library for 99%+ accuracy");
-                println!("🎯 Industry-standard face detection and recognition!");
-                println!();
-
-                match StandalonePythonFaceAuth::new() {
-                    Ok(standalone_auth) => {
-                        match standalone_auth.check_executable() {
-                            Ok(_) => {
-                                print!("Enter username for registration: ");
-                                io::stdout().flush().unwrap();
-                                let mut username = String::new();
-                                if io::stdin().read_line(&mut username).is_ok() {
-                                    let username = username.trim();
-
-                                    match standalone_auth.register_user(username, 3, "generated") {
-                                        Ok(true) => {
-                                            println!("\n🎉 Standalone Python registration successful!");
-                                            println!("✅ High-accuracy face model trained with standalone executable!");
-                                            println!("📦 No Python installation was required!");
-                                        },
-                                        Ok(false) => {
-                                            println!("\n❌ Standalone Python registration failed");
-                                            println!("💡 Make sure you're positioned in front of the camera");
-                                        },
-                                        Err(e) => {
-                                            println!("\n❌ Registration error: {}", e);
-                                        }
-                                    }
-                                }
-                            },
-                            Err(e) => {
-                                println!("\n❌ Standalone executable error: {}", e);
-                                println!("💡 Make sure you've built the standalone executable first");
-                                println!("💡 Run: pyinstaller --onefile --console --add-data=\"face_auth_env/lib/python3.9/site-packages/face_recognition_models/models/*:face_recognition_models/models/\" python_face_auth_simple.py");
-                            }
-                        }
-                    },
-                    Err(e) => {
-                        println!("\n❌ Failed to initialize standalone Python: {}", e);
-                    }
-                }
+        Some(_) => subscriber.init(),
+    }
 
-                println!();
-                print!("Press ENTER to return to main menu...");
-                io::stdout().flush().unwrap();
-                let _ = io::stdin().read_line(&mut String::new());
-            },
-            "2" => {
-                println!("\n--- 🐍 High-Accuracy Face Authentication ---");
-                println!("This uses Python's face_recognition library for 99%+ accuracy");
-                println!("🎯 Industry-standard face detection and recognition!");
-                println!();
-
-                match StandalonePythonFaceAuth::new() {
-                    Ok(standalone_auth) => {
-                        match standalone_auth.check_executable() {
-                            Ok(_) => {
-                                match standalone_auth.authenticate_user(0.4, "source") {
-                                    Ok(result) => {
-                                        if result.is_match.unwrap_or(false) {
-                                            println!("\n✅ Standalone Python Authentication Successful!");
-                                            println!("🎯 Confidence: {:.1}%", result.confidence.unwrap_or(0.0) * 100.0);
-                                            println!("📏 Distance: {:.3}", result.distance.unwrap_or(0.0));
-                                            println!("👤 User: {}", result.matched_user.as_ref().unwrap_or(&"Unknown".to_string()));
-                                            println!("⚡ Processing time: {}ms", result.processing_time_ms.unwrap_or(0));
-                                            println!("📦 No Python installation was required!");
-                                            println!("🎉 Access granted with standalone executable!");
-                                        } else {
-                                            println!("\n❌ Standalone Python Authentication Failed!");
-                                            println!("🎯 Confidence: {:.1}%", result.confidence.unwrap_or(0.0) * 100.0);
-                                            println!("📏 Distance: {:.3}", result.distance.unwrap_or(0.0));
-                                            println!("🎚️  Threshold: {:.3}", result.threshold.unwrap_or(0.0));
-                                            println!("⚡ Processing time: {}ms", result.processing_time_ms.unwrap_or(0));
-                                            println!("🔒 Access denied. Please try again or register first.");
-                                        }
-                                    },
-                                    Err(e) => {
-                                        println!("\n❌ Authentication error: {}", e);
-                                    }
-                                }
-                            },
-                            Err(e) => {
-                                println!("\n❌ Standalone executable error: {}", e);
-                                println!("💡 Make sure you've built the standalone executable first");
-                            }
-                        }
-                    },
-                    Err(e) => {
-                        println!("\n❌ Failed to initialize standalone Python: {}", e);
-                    }
+    match cli.command {
+        Some(Command::Bench { samples, millis, dir, save, baseline, max_regression }) => {
+            run_bench(&samples, millis, dir, save, baseline, max_regression)
+        },
+        Some(Command::Diagnose { dirs, repair_python }) => run_diagnose(dirs, repair_python).await,
+        Some(Command::Validate { dir }) => run_validate(&dir),
+        Some(Command::Demo { dir, tolerance }) => run_demo(dir, tolerance).await,
+        Some(Command::Completions { shell }) => {
+            clap_complete::generate(shell, &mut Cli::command(), "face_auth", &mut io::stdout());
+            Ok(())
+        },
+        Some(Command::Man { out }) => run_man(&out),
+        Some(Command::Migrate { from, data_dir, source_dir, overwrite }) => {
+            run_migrate(&from, data_dir, source_dir, overwrite)
+        },
+        Some(Command::ImportLbph { model, images, data_dir, source_dir, overwrite }) => {
+            run_import_lbph(&model, &images, data_dir, source_dir, overwrite)
+        },
+        Some(Command::ExportChips { out, data_dir, size }) => run_export_chips(&out, data_dir, size),
+        #[cfg(feature = "server")]
+        Some(Command::Serve { addr, unix_socket, windows_service, data_dir, source_dir, backup_dir, backup_hours, backup_keep, heartbeat_url, heartbeat_secs, profiles, nonce_secs, notifications, max_tolerance }) => {
+            let schedule = backup_dir.map(|dir| (dir, Duration::from_secs(backup_hours * 3600), backup_keep));
+            let heartbeat = heartbeat_url.map(|url| (url, Duration::from_secs(heartbeat_secs)));
+            run_serve(addr, unix_socket, windows_service, data_dir, source_dir, schedule, heartbeat, profiles, nonce_secs.map(Duration::from_secs), notifications, max_tolerance).await
+        },
+        #[cfg(feature = "self-update")]
+        Some(Command::Update { channel, channel_name, install_dir, check, dirs }) => {
+            tokio::task::block_in_place(|| run_update(channel, &channel_name, install_dir, check, dirs))
+        },
+        #[cfg(feature = "windows-service")]
+        Some(Command::ServiceInstall { name, serve_args }) => {
+            let installer = face_auth::winservice::ServiceInstaller::new(&name, std::env::current_exe()?).with_serve_args(serve_args);
+            installer.install()?;
+            println!("✅ Installed the {} service: {}", name, installer.command_line());
+            println!("▶️  Start it with: sc.exe start {}", name);
+            Ok(())
+        },
+        #[cfg(feature = "windows-service")]
+        Some(Command::ServiceUninstall { name }) => {
+            face_auth::winservice::ServiceInstaller::uninstall(&name)?;
+            println!("🗑️  Removed the {} service", name);
+            Ok(())
+        },
+        #[cfg(feature = "transfer")]
+        Some(Command::SendUser { user, to, data_dir }) => run_send_user(&user, &to, data_dir).await,
+        #[cfg(feature = "transfer")]
+        Some(Command::ReceiveUser { listen, data_dir }) => run_receive_user(&listen, data_dir).await,
+        #[cfg(feature = "nfc")]
+        Some(Command::NfcWrite { user, reader, data_dir }) => run_nfc_write(&user, reader.as_deref(), data_dir).await,
+        #[cfg(feature = "nfc")]
+        Some(Command::NfcRead { reader, data_dir }) => run_nfc_read(reader.as_deref(), data_dir).await,
+        #[cfg(feature = "nfc")]
+        Some(Command::NfcRevoke { serial, data_dir }) => {
+            FaceAuth::builder().data_dir(data_dir).build()?.revoke_nfc_tag(&serial)?;
+            println!("✅ Revoked tag {}", serial);
+            Ok(())
+        }
+        Some(Command::Backup { out, dirs }) => run_backup(&out, dirs).await,
+        Some(Command::Restore { from, dirs }) => run_restore(&from, dirs).await,
+        Some(Command::Reenroll { user, tolerance, dirs }) => {
+            println!("📷 Look at the camera to confirm it's you, then hold still for new samples");
+            let outcome = enrollment_auth(dirs)?.reenroll(&user, tolerance).await?;
+            println!("✅ Added {} new sample(s) for {}", outcome.samples_captured, user);
+            Ok(())
+        },
+        Some(Command::Watch { tolerance, min_change, no_face_check, cooldown_secs, track, tailgating_secs, attention, actuator, home_assistant, notifications, video, dirs }) => {
+            let gate = SceneGate::default()
+                .with_min_changed_fraction(min_change)
+                .with_face_check(!no_face_check)
+                .with_cooldown(Duration::from_secs_f64(cooldown_secs));
+            #[cfg_attr(not(feature = "attention"), allow(unused_mut))]
+            let mut tracking = track.then(TrackingConfig::default);
+            if attention {
+                #[cfg(feature = "attention")]
+                if let Some(tracking) = tracking.as_mut() {
+                    tracking.attention = Some(face_auth::AttentionConfig::default());
                 }
+                #[cfg(not(feature = "attention"))]
+                anyhow::bail!("Can't report attention: built without the attention feature");
+            }
+            run_watch(tolerance, &gate, tracking, tailgating_secs.map(Duration::from_secs), actuator, home_assistant, notifications, video, dirs).await
+        },
+        Some(Command::Samples { user, dirs }) => run_samples(&user, dirs).await,
+        Some(Command::RemoveSample { user, sample, dirs }) => {
+            enrollment_auth(dirs)?.remove_sample(&user, &sample).await?;
+            println!("🗑️  Removed sample {} of {}", sample, user);
+            Ok(())
+        },
+        Some(Command::Outliers { user, dirs }) => {
+            let flagged = enrollment_auth(dirs)?.detect_outliers(&user).await?;
+            if flagged.is_empty() {
+                println!("✅ No outliers among the samples of {}", user);
+            }
+            for sample in flagged {
+                println!("⚠️  Quarantined {} for review", sample.sample_id);
+            }
+            Ok(())
+        },
+        Some(Command::ReleaseSample { user, sample, dirs }) => {
+            enrollment_auth(dirs)?.release_sample(&user, &sample).await?;
+            println!("✅ Sample {} of {} is matched again", sample, user);
+            Ok(())
+        },
+        Some(Command::MigrateSampleIds { dirs }) => {
+            let rewrites = enrollment_auth(dirs)?.migrate_sample_ids().await?;
+            if rewrites.is_empty() {
+                println!("✅ All sample ids are already UUIDs");
+            }
+            for rewrite in rewrites {
+                println!("🔁 {}: {} -> {}", rewrite.user_id, rewrite.old_id, rewrite.new_id);
+            }
+            Ok(())
+        },
+        Some(Command::CrossMatches { tolerance, dirs }) => run_cross_matches(tolerance, dirs).await,
+        Some(Command::Enrollments { dirs }) => run_enrollments(dirs).await,
+        Some(Command::ApproveEnrollment { id, tolerance, dirs }) => run_approve_enrollment(&id, tolerance, dirs).await,
+        Some(Command::RejectEnrollment { id, dirs }) => {
+            enrollment_auth(dirs)?.reject_enrollment(&id).await?;
+            println!("🗑️  Rejected enrollment {}", id);
+            Ok(())
+        },
+        None => tui::run(tui_auth()?).await,
+    }
+}
 
-                println!();
-                print!("Press ENTER to return to main menu...");
-                io::stdout().flush().unwrap();
-                let _ = io::stdin().read_line(&mut String::new());
-            },
-            "3" => {
-                println!("\n--- 📤 Export User Credentials ---");
-                println!("Export a user's face data to share with another device");
-                println!();
-
-                print!("Enter username to export: ");
-                io::stdout().flush().unwrap();
-                let mut username = String::new();
-                if io::stdin().read_line(&mut username).is_ok() {
-                    let username = username.trim();
-
-                    match StandalonePythonFaceAuth::new() {
-                        Ok(standalone_auth) => {
-                            match standalone_auth.export_user(username, "") {
-                                Ok(true) => {
-                                    println!("\n✅ User '{}' exported successfully!", username);
-                                    println!("📁 File saved in 'exported_credentials/' directory");
-                                    println!("🔄 You can copy this file to another device");
-                                    println!("🔄 Use 'Import User' on the target device to add this user");
-                                },
-                                Ok(false) => {
-                                    println!("\n❌ Export failed. User '{}' may not exist.", username);
-                                },
-                                Err(e) => {
-                                    println!("\n❌ Export error: {}", e);
-                                }
-                            }
-                        },
-                        Err(e) => {
-                            println!("\n❌ System error: {}", e);
-                        }
-                    }
-                }
+fn run_bench(
+    samples: &[usize],
+    millis: u64,
+    dir: Option<PathBuf>,
+    save: Option<PathBuf>,
+    baseline: Option<PathBuf>,
+    max_regression: f64,
+) -> Result<()> {
+    let dir = dir.unwrap_or_else(|| std::env::temp_dir().join(format!("face_auth_bench_{}", std::process::id())));
+    println!("⏱️  Benchmarking {:?} samples ({} ms per operation)...", samples, millis);
+    println!();
+
+    let report = face_auth::bench::run(samples, Duration::from_millis(millis), &dir);
+    let _ = std::fs::remove_dir_all(&dir);
+    let report = report?;
+    print!("{}", report);
+
+    if let Some(path) = save {
+        std::fs::write(&path, serde_json::to_string_pretty(&report)?)?;
+        println!("\n💾 Results saved to: {}", path.display());
+    }
+
+    if let Some(path) = baseline {
+        let baseline: face_auth::bench::BenchReport = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        let regressions = report.regressions(&baseline, max_regression / 100.0);
+        if !regressions.is_empty() {
+            println!("\n❌ Slower than {} by more than {}%:", path.display(), max_regression);
+            for regression in &regressions {
+                println!("  - {}: {:.3?} -> {:.3?}", regression.name, regression.baseline, regression.current);
+            }
+            anyhow::bail!("{} benchmark regression(s)", regressions.len());
+        }
+        println!("\n✅ No regressions against {}", path.display());
+    }
+    Ok(())
+}
+
+/// Each check runs even if an earlier one failed, so one run shows everything to fix
+async fn run_diagnose(dirs: StorageDirs, repair_python: bool) -> Result<()> {
+    let layout = StorageLayout { data_dir: dirs.data_dir, generated_dir: dirs.generated_dir, source_dir: dirs.source_dir };
+    let mut failures = 0;
+    let mut builder = FaceAuth::builder()
+        .data_dir(&layout.data_dir)
+        .generated_dir(&layout.generated_dir)
+        .source_dir(&layout.source_dir);
+    if repair_python {
+        builder = builder.python_workers(1);
+    }
+    let backend = builder.build();
+    if let (true, Ok(auth)) = (repair_python, &backend) {
+        println!("📦 Reinstalling the Python dependencies...");
+        match tokio::task::block_in_place(|| auth.repair_python_dependencies()) {
+            Ok(()) => println!("✅ Python dependencies reinstalled"),
+            Err(e) => {
+                println!("❌ Python dependencies: {:#}", e);
+                failures += 1;
+            }
+        }
+    }
+    let checked = match backend {
+        Ok(auth) => auth.check_system().await,
+        Err(e) => Err(e),
+    };
+    match checked {
+        Ok(()) => println!("✅ Backend is working"),
+        Err(e) => {
+            println!("❌ Backend: {:#}", e);
+            failures += 1;
+        }
+    }
+    match layout.stats() {
+        Ok(stats) => {
+            println!("✅ Database {}: {} user(s), {} sample(s)", layout.database_path().display(), stats.total_users, stats.total_samples);
+            if stats.enrolled_users == 0 {
+                println!("⚠️  No credential files in {} to authenticate against", layout.source_dir.display());
+            } else {
+                println!("✅ {} user(s) can authenticate", stats.enrolled_users);
+            }
+        }
+        Err(e) => {
+            println!("❌ Database: {:#}", e);
+            failures += 1;
+        }
+    }
+    if failures > 0 {
+        anyhow::bail!("{} check(s) failed", failures);
+    }
+    Ok(())
+}
 
-                println!();
-                print!("Press ENTER to return to main menu...");
-                io::stdout().flush().unwrap();
-                let _ = io::stdin().read_line(&mut String::new());
-            },
-            "4" => {
-                println!("\n--- 📥 Import User Credentials ---");
-                println!("Import a user's face data from another device");
-                println!();
-
-                print!("Enter filename to import (from exported_credentials/ or full path): ");
-                io::stdout().flush().unwrap();
-                let mut filename = String::new();
-                if io::stdin().read_line(&mut filename).is_ok() {
-                    let filename = filename.trim();
-
-                    match StandalonePythonFaceAuth::new() {
-                        Ok(standalone_auth) => {
-                            match standalone_auth.import_user(filename) {
-                                Ok(true) => {
-                                    println!("\n✅ User imported successfully from '{}'", filename);
-                                    println!("👤 User is now available for authentication");
-                                },
-                                Ok(false) => {
-                                    println!("\n❌ Import failed. Check if file exists and is valid.");
-                                },
-                                Err(e) => {
-                                    println!("\n❌ Import error: {}", e);
-                                }
-                            }
-                        },
-                        Err(e) => {
-                            println!("\n❌ System error: {}", e);
-                        }
-                    }
+fn run_validate(dir: &Path) -> Result<()> {
+    let reports = face_auth::schema::check_credentials_dir(dir)?;
+    if reports.is_empty() {
+        println!("⚠️  No credential files in {}", dir.display());
+    }
+    let mut invalid = 0;
+    for report in &reports {
+        let name = report.path.file_name().unwrap_or_default().to_string_lossy();
+        if report.is_valid() {
+            println!("✅ {}", name);
+        } else {
+            println!("❌ {}", name);
+            invalid += 1;
+        }
+        for error in &report.errors {
+            println!("   ❌ {}", error);
+        }
+        for warning in &report.warnings {
+            println!("   ⚠️  {}", warning);
+        }
+    }
+    if invalid > 0 {
+        anyhow::bail!("{} of {} credential file(s) are invalid", invalid, reports.len());
+    }
+    Ok(())
+}
+
+async fn run_demo(dir: Option<PathBuf>, tolerance: f64) -> Result<()> {
+    if let Some(dir) = dir {
+        return demo_in(&dir, tolerance).await;
+    }
+    // Removed when dropped, whether or not the demo passed
+    let root = tempfile::Builder::new().prefix("face_auth_demo_").tempdir()?;
+    demo_in(root.path(), tolerance).await
+}
+
+async fn demo_in(root: &Path, tolerance: f64) -> Result<()> {
+    println!("🎭 Generating demo photos in {}...", root.display());
+    let images = DemoImages::generate(&root.join("photos"))?;
+    let auth = FaceAuth::builder()
+        .data_dir(root.join("data"))
+        .generated_dir(root.join("generated"))
+        .source_dir(root.join("source"))
+        .auto_promote(true)
+        .build()?;
+    let steps = face_auth::demo::run(&auth, &images, tolerance, |step| {
+        let mark = if step.passed() { "✅" } else { "❌" };
+        match step {
+            DemoStep::Enrolled { user, photos, usable } => println!("{} Enrolled {} from {}/{} photo(s)", mark, user, usable, photos),
+            DemoStep::Authenticated { probe, result } => {
+                let name = probe.image.file_name().unwrap_or_default().to_string_lossy();
+                let expected = probe.expected.as_deref().unwrap_or("nobody (stranger)");
+                match (&result.user_id, result.is_authenticated) {
+                    (Some(user), true) => println!("{} {}: granted as {}, expected {}", mark, name, user, expected),
+                    _ => println!("{} {}: denied, expected {}", mark, name, expected),
                 }
+            }
+        }
+    }).await?;
+    let unusable = steps.iter().any(|step| matches!(step, DemoStep::Enrolled { usable: 0, .. }));
+    let failed = steps.iter().filter(|step| !step.passed()).count();
+    if unusable {
+        anyhow::bail!(
+            "{} demo step(s) failed: no face was found in the drawn photos. They are only tested with the fake backend, \
+             so this doesn't mean the installation is broken; run `face_auth doctor` to check it with the camera",
+            failed
+        );
+    }
+    if failed > 0 {
+        anyhow::bail!("{} demo step(s) failed; run `face_auth doctor` to check the backend", failed);
+    }
+    println!("🎉 Demo passed: this installation enrolls and recognizes the drawn faces");
+    Ok(())
+}
+
+/// Credentials a sync process changed while `serve` or `watch` runs
+fn print_source_event(event: &SourceEvent) {
+    match event {
+        SourceEvent::UserAdded { user_id } => println!("➕ {} can now authenticate", user_id),
+        SourceEvent::UserRemoved { user_id } => println!("➖ {} was removed", user_id),
+        SourceEvent::UserUpdated { user_id } => println!("🔄 {} was re-enrolled", user_id),
+    }
+}
+
+/// Write a page for the binary and one per command, named like `face_auth-serve.1`
+fn run_man(out: &Path) -> Result<()> {
+    std::fs::create_dir_all(out)?;
+    clap_mangen::generate_to(Cli::command(), out)?;
+    println!("✅ Man pages written to {}", out.display());
+    Ok(())
+}
+
+fn run_migrate(from: &Path, data_dir: PathBuf, source_dir: PathBuf, overwrite: bool) -> Result<()> {
+    println!("📦 Migrating encodings from {}...", from.display());
+    let migrated = if migrate::is_pickle(from) {
+        let python = StandalonePythonFaceAuth::with_data_dir(&data_dir)?;
+        migrate::load_pickle(&python, from)?
+    } else {
+        migrate::load_json(from)?
+    };
+    store_users(migrated, data_dir, source_dir, overwrite)
+}
+
+fn run_import_lbph(model: &Path, images: &Path, data_dir: PathBuf, source_dir: PathBuf, overwrite: bool) -> Result<()> {
+    println!("📦 Re-encoding LBPH training images from {}...", images.display());
+    let python = StandalonePythonFaceAuth::with_data_dir(&data_dir)?;
+    let import = interop::import_lbph(&python, model, images)?;
+    for image in &import.unencoded {
+        println!("⚠️  No face found in {}", image.display());
+    }
+    store_users(import.database, data_dir, source_dir, overwrite)
+}
+
+fn run_export_chips(out: &Path, data_dir: PathBuf, size: u32) -> Result<()> {
+    let python = StandalonePythonFaceAuth::with_data_dir(&data_dir)?;
+    let db_path = StorageLayout { generated_dir: data_dir.join("generated"), source_dir: data_dir.join("source"), data_dir }.database_path();
+    let db = FaceDatabase::load(&db_path)?.ok_or_else(|| anyhow::anyhow!("No database at {}", db_path.display()))?;
+
+    println!("✂️  Writing {}x{} face chips to {}...", size, size, out.display());
+    let export = interop::export_dlib_chips(&python, &db, out, size)?;
+    for sample in &export.skipped {
+        println!("⚠️  Skipped sample {} (image missing or no face found)", sample);
+    }
+    println!("✅ Wrote {} chip(s)", export.chips.len());
+    Ok(())
+}
+
+#[cfg(feature = "server")]
+#[allow(clippy::too_many_arguments)]
+async fn run_serve(
+    addr: std::net::SocketAddr,
+    unix_socket: Option<PathBuf>,
+    windows_service: Option<String>,
+    data_dir: PathBuf,
+    source_dir: PathBuf,
+    backups: Option<(PathBuf, Duration, usize)>,
+    heartbeat: Option<(String, Duration)>,
+    profiles: Option<PathBuf>,
+    nonce_ttl: Option<Duration>,
+    notifications: Option<PathBuf>,
+    max_tolerance: f64,
+) -> Result<()> {
+    let mut builder = FaceAuth::builder().data_dir(&data_dir).source_dir(&source_dir);
+    if let Some(path) = notifications {
+        builder = with_notifications(builder, &path)?;
+    }
+    if backups.is_some() {
+        builder = builder.backup_key(backup_key_from_env()?);
+    }
+    if let Some(path) = profiles {
+        let profiles = face_auth::ThresholdProfiles::load(&path)?;
+        println!("🎚️  Threshold profiles: {}", profiles.profiles.iter().map(|p| p.name.as_str()).collect::<Vec<_>>().join(", "));
+        builder = builder.threshold_profiles(profiles);
+    }
+    let auth = builder.build()?;
+    let _schedule = match backups {
+        Some((dir, interval, keep)) => {
+            println!("💾 Backing up to {} every {}h, keeping {}", dir.display(), interval.as_secs() / 3600, keep);
+            Some(auth.schedule_backups(dir, interval, keep)?)
+        }
+        None => None,
+    };
+    let _heartbeat = heartbeat.map(|(url, interval)| {
+        println!("💓 Sending heartbeats to {} every {}s", url, interval.as_secs());
+        auth.start_heartbeat(interval, face_auth::health::http_publisher(url))
+    });
+    if face_auth::face_storage::load_credentials_dir(&source_dir)?.is_empty() {
+        println!("👋 No one is enrolled in {} yet: approve submitted enrollments or register users with `face_auth`", source_dir.display());
+    }
+    let _source_watch = auth.watch_source_dir(source_watch::DEFAULT_DEBOUNCE, print_source_event)?;
+    let mut server = face_auth::Server::new(auth, source_dir).with_max_tolerance(max_tolerance);
+    match std::env::var("FACE_AUTH_DEVICE_TOKEN") {
+        Ok(token) => server = server.with_device_token(token),
+        Err(_) => println!("⚠️  FACE_AUTH_DEVICE_TOKEN is not set: devices can't verify faces"),
+    }
+    if let Ok(token) = std::env::var("FACE_AUTH_ADMIN_TOKEN") {
+        server = server.with_admin_token(token);
+        #[cfg(feature = "admin-ui")]
+        println!("🖥️  Admin UI at http://{}{}", addr, face_auth::server::ADMIN_PATH);
+    }
+    if let Ok(token) = std::env::var("FACE_AUTH_SUBMIT_TOKEN") {
+        server = server.with_submit_token(token);
+    }
+    if let Some(ttl) = nonce_ttl {
+        println!("🔁 Verification requests need a nonce from {}, valid {}s", face_auth::hybrid::NONCE_PATH, ttl.as_secs());
+        server = server.with_nonces(ttl);
+    }
+    let listener = server_listener(addr, unix_socket).await?;
+    if let Some(name) = windows_service {
+        return run_windows_service(name, server, listener).await;
+    }
+    server.serve_on(listener, shutdown_signal()).await?;
+    println!("👋 Stopped cleanly");
+    Ok(())
+}
 
-                println!();
-                print!("Press ENTER to return to main menu...");
-                io::stdout().flush().unwrap();
-                let _ = io::stdin().read_line(&mut String::new());
-            },
-            "5" => {
-                println!("\n--- 👥 Registered Users ---");
-                println!();
-
-                match StandalonePythonFaceAuth::new() {
-                    Ok(standalone_auth) => {
-                        match standalone_auth.list_users() {
-                            Ok(_) => {
-                                // Success message already printed by Python script
-                            },
-                            Err(e) => {
-                                println!("❌ Error listing users: {}", e);
-                            }
-                        }
-                    },
-                    Err(e) => {
-                        println!("❌ System error: {}", e);
-                    }
+#[cfg(feature = "server")]
+async fn server_listener(addr: std::net::SocketAddr, unix_socket: Option<PathBuf>) -> Result<face_auth::ServerListener> {
+    use face_auth::ServerListener;
+
+    #[cfg(unix)]
+    if let Some(listener) = ServerListener::from_systemd()? {
+        println!("🌐 Verification server listening on the socket passed by systemd (health at {})", face_auth::server::HEALTH_PATH);
+        return Ok(listener);
+    }
+    if let Some(path) = unix_socket {
+        #[cfg(unix)]
+        {
+            println!("🌐 Verification server listening on {} (health at {})", path.display(), face_auth::server::HEALTH_PATH);
+            return ServerListener::bind_unix(&path);
+        }
+        #[cfg(not(unix))]
+        anyhow::bail!("Unix sockets are not supported on this platform: {}", path.display());
+    }
+    println!("🌐 Verification server listening on {} (health at {})", addr, face_auth::server::HEALTH_PATH);
+    ServerListener::bind(addr).await
+}
+
+/// Hand the server to the Windows service control manager until it stops
+#[cfg(feature = "windows-service")]
+async fn run_windows_service(name: String, server: face_auth::Server, listener: face_auth::ServerListener) -> Result<()> {
+    let runtime = tokio::runtime::Handle::current();
+    tokio::task::spawn_blocking(move || {
+        face_auth::winservice::run(&name, move |stop| runtime.block_on(server.serve_on(listener, stop.requested())))
+    }).await?
+}
+
+#[cfg(all(feature = "server", not(feature = "windows-service")))]
+async fn run_windows_service(name: String, _server: face_auth::Server, _listener: face_auth::ServerListener) -> Result<()> {
+    anyhow::bail!("Cannot run as the {} service: built without the windows-service feature", name)
+}
+
+/// Completes on Ctrl+C or, on Unix, SIGTERM as sent by `systemctl stop`
+#[cfg(feature = "server")]
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(terminate) => terminate,
+            Err(e) => {
+                eprintln!("⚠️  Cannot handle SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+                return;
+            }
+        };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+    println!("🛑 Shutting down, finishing requests in progress...");
+}
+
+#[cfg(feature = "transfer")]
+async fn run_send_user(user: &str, to: &str, data_dir: PathBuf) -> Result<()> {
+    let auth = FaceAuth::builder().data_dir(data_dir).build()?;
+    let passphrase = read_passphrase()?;
+    println!("📤 Sending '{}' to {}...", user, to);
+    auth.send_user(user, to, &passphrase).await?;
+    println!("✅ '{}' was imported on the other device", user);
+    Ok(())
+}
+
+#[cfg(feature = "transfer")]
+async fn run_receive_user(listen: &str, data_dir: PathBuf) -> Result<()> {
+    let auth = FaceAuth::builder().data_dir(data_dir).build()?;
+    let passphrase = read_passphrase()?;
+    println!("📥 Waiting for a credential on {}...", listen);
+    let user = auth.receive_user(listen, &passphrase).await?;
+    println!("✅ Imported '{}'", user);
+    Ok(())
+}
+
+#[cfg(feature = "nfc")]
+async fn run_nfc_write(user: &str, reader: Option<&str>, data_dir: PathBuf) -> Result<()> {
+    let auth = FaceAuth::builder().data_dir(data_dir).provisioning_key(key_from_env("FACE_AUTH_PROVISIONING_KEY")?).build()?;
+    println!("📝 Writing '{}', hold the tag on the reader...", user);
+    let tag = auth.write_nfc_tag(user, reader).await?;
+    println!("✅ Wrote {} samples in {} bytes, serial {}, expires {}", tag.samples, tag.data.len(), tag.serial, tag.expires_at.format("%Y-%m-%d"));
+    Ok(())
+}
+
+#[cfg(feature = "nfc")]
+async fn run_nfc_read(reader: Option<&str>, data_dir: PathBuf) -> Result<()> {
+    let auth = FaceAuth::builder().data_dir(data_dir).provisioning_key(key_from_env("FACE_AUTH_PROVISIONING_KEY")?).build()?;
+    println!("📥 Reading the tag...");
+    let user = auth.read_nfc_tag(reader).await?;
+    println!("✅ Imported '{}'", user);
+    Ok(())
+}
+
+/// Passphrase shared by both devices of a transfer, typed on each of them
+#[cfg(feature = "transfer")]
+fn read_passphrase() -> Result<String> {
+    use std::io::Write;
+
+    print!("🔑 Transfer passphrase: ");
+    io::stdout().flush()?;
+    let mut passphrase = String::new();
+    io::stdin().read_line(&mut passphrase)?;
+    let passphrase = passphrase.trim().to_string();
+    if passphrase.is_empty() {
+        return Err(anyhow::anyhow!("The passphrase must not be empty"));
+    }
+    Ok(passphrase)
+}
+
+fn storage_auth(dirs: StorageDirs) -> Result<FaceAuth> {
+    FaceAuth::builder()
+        .data_dir(dirs.data_dir)
+        .generated_dir(dirs.generated_dir)
+        .source_dir(dirs.source_dir)
+        .backup_key(backup_key_from_env()?)
+        .build()
+}
+
+async fn run_backup(out: &Path, dirs: StorageDirs) -> Result<()> {
+    let manifest = storage_auth(dirs)?.backup(out).await?;
+    println!("✅ Backed up {} file(s) to {}", manifest.files.len(), out.display());
+    Ok(())
+}
+
+async fn run_restore(from: &Path, dirs: StorageDirs) -> Result<()> {
+    let manifest = storage_auth(dirs)?.restore(from).await?;
+    println!("✅ Restored {} file(s) from the backup of {}", manifest.files.len(), manifest.created_at.format("%Y-%m-%d %H:%M UTC"));
+    if let Some(device_id) = &manifest.settings.device_id {
+        println!("ℹ️  The backup was taken on device '{}'", device_id);
+    }
+    Ok(())
+}
+
+/// Instance that promotes approved enrollments into the source directory
+fn enrollment_auth(dirs: StorageDirs) -> Result<FaceAuth> {
+    FaceAuth::builder()
+        .data_dir(dirs.data_dir)
+        .generated_dir(dirs.generated_dir)
+        .source_dir(dirs.source_dir)
+        .auto_promote(true)
+        .build()
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_watch(
+    tolerance: f64,
+    gate: &SceneGate,
+    tracking: Option<TrackingConfig>,
+    tailgating: Option<Duration>,
+    actuator: Option<PathBuf>,
+    home_assistant: Option<String>,
+    notifications: Option<PathBuf>,
+    video: Option<PathBuf>,
+    dirs: StorageDirs,
+) -> Result<()> {
+    let source_dir = dirs.source_dir.to_string_lossy().into_owned();
+    let mut builder = FaceAuth::builder()
+        .data_dir(dirs.data_dir)
+        .generated_dir(dirs.generated_dir)
+        .source_dir(dirs.source_dir);
+    let watching = match &video {
+        Some(path) => format!("🎞️  Watching {}", path.display()),
+        None => "👀 Watching the camera, press Ctrl+C to stop".to_string(),
+    };
+    if let Some(path) = video {
+        builder = builder.camera_source(face_auth::CameraSource::VideoFile(path));
+    }
+    if let Some(window) = tailgating {
+        builder = builder.tailgating_window(window);
+    }
+    if let Some(path) = actuator {
+        #[cfg(all(feature = "actuator", target_os = "linux"))]
+        {
+            let config = face_auth::ActuatorConfig::load(&path)?;
+            println!("🔌 Outputs: {}", config.outputs.keys().map(String::as_str).collect::<Vec<_>>().join(", "));
+            builder = builder.actuator(face_auth::Actuator::new(config)?);
+        }
+        #[cfg(not(all(feature = "actuator", target_os = "linux")))]
+        anyhow::bail!("Can't use {}: built without the actuator feature (Linux only)", path.display());
+    }
+    if let Some(broker) = home_assistant {
+        #[cfg(feature = "mqtt")]
+        {
+            let mut options = face_auth::MqttOptions::new(&broker, "face_auth");
+            if let (Ok(user), Ok(password)) = (std::env::var("FACE_AUTH_MQTT_USER"), std::env::var("FACE_AUTH_MQTT_PASSWORD")) {
+                options = options.with_credentials(user, password);
+            }
+            builder = builder.home_assistant(face_auth::HomeAssistant::connect(options, "face_auth")?);
+            println!("🏠 Publishing to Home Assistant via {}", broker);
+        }
+        #[cfg(not(feature = "mqtt"))]
+        anyhow::bail!("Can't publish to {}: built without the mqtt feature", broker);
+    }
+    if let Some(path) = notifications {
+        builder = with_notifications(builder, &path)?;
+    }
+    let auth = builder.build()?;
+    let _source_watch = auth.watch_source_dir(source_watch::DEFAULT_DEBOUNCE, print_source_event)?;
+    let report = |result: &FaceAuthResult| match (&result.user_id, result.is_authenticated) {
+        (Some(user), true) => format!("✅ Welcome, {}", user),
+        _ if result.denial == Some(DenialReason::NoUsersEnrolled) => NO_USERS_HINT.to_string(),
+        _ => format!("❌ {}", result.denial.as_ref().map_or_else(|| "Not recognized".to_string(), |d| auth.messages().denial(d))),
+    };
+    println!("{}", watching);
+    if let Some(tracking) = tracking {
+        auth.track_faces(tolerance, &source_dir, gate, &tracking, |event| {
+            match event {
+                TrackEvent::Entered { track_id, result } => println!("➡️  #{} {}", track_id, report(&result)),
+                TrackEvent::Exited { track_id, user_id, .. } => println!("⬅️  #{} {} left", track_id, user_id.as_deref().unwrap_or("unknown")),
+                TrackEvent::TailgatingSuspected { track_id, followed_user, after, .. } => {
+                    println!("🚨 #{} followed {} through {:.1}s after access was granted", track_id, followed_user, after.as_secs_f64());
                 }
+                #[cfg(feature = "attention")]
+                TrackEvent::AttentionChanged { track_id, attention, .. } => match attention {
+                    face_auth::Attention::LookingAtCamera => println!("👁️  #{} is looking at the camera", track_id),
+                    face_auth::Attention::LookingAway => println!("↪️  #{} looked away", track_id),
+                },
+            }
+            true
+        }).await?;
+    } else {
+        auth.watch(tolerance, &source_dir, gate, |result| {
+            println!("{}", report(&result));
+            true
+        }).await?;
+    }
+    Ok(())
+}
+
+fn with_notifications(builder: face_auth::FaceAuthBuilder, path: &Path) -> Result<face_auth::FaceAuthBuilder> {
+    #[cfg(feature = "notifications")]
+    {
+        let config = face_auth::NotificationConfig::load(path)?;
+        let channels: Vec<&str> = config.email.iter().map(|c| c.name.as_str()).chain(config.slack.iter().map(|c| c.name.as_str())).collect();
+        println!("🔔 Sending alerts to {}", channels.join(", "));
+        builder.notification_config(&config)
+    }
+    #[cfg(not(feature = "notifications"))]
+    {
+        let _ = builder;
+        anyhow::bail!("Can't use {}: built without the notifications feature", path.display())
+    }
+}
+
+async fn run_samples(user: &str, dirs: StorageDirs) -> Result<()> {
+    for sample in enrollment_auth(dirs)?.list_samples(user).await? {
+        println!(
+            "🧬 {}  captured {}, quality {}, model {}",
+            sample.sample_id,
+            sample.captured_at.map_or_else(|| "unknown".to_string(), |at| at.format("%Y-%m-%d %H:%M UTC").to_string()),
+            sample.quality.map_or_else(|| "n/a".to_string(), |q| format!("{:.2}", q)),
+            sample.model_version,
+        );
+        if let Some(image) = &sample.image_path {
+            println!("   📷 {}", image);
+        }
+        if let Some(quarantine) = &sample.quarantine {
+            println!("   ⚠️  Quarantined {}: distance {:.3} above {:.3}", quarantine.flagged_at.format("%Y-%m-%d %H:%M UTC"), quarantine.distance, quarantine.threshold);
+        }
+    }
+    Ok(())
+}
+
+async fn run_cross_matches(tolerance: f64, dirs: StorageDirs) -> Result<()> {
+    let matches = enrollment_auth(dirs)?.audit_cross_matches(tolerance).await?;
+    if matches.is_empty() {
+        println!("✅ No users within {:.2} of each other", tolerance);
+    }
+    for found in matches {
+        println!(
+            "👥 {} ↔ {}  closest {:.3} ({} / {}), averages {:.3} apart",
+            found.first_user, found.second_user, found.closest_distance, found.first_sample, found.second_sample, found.centroid_distance,
+        );
+        println!("   💡 {}", found.suggestion());
+    }
+    Ok(())
+}
+
+async fn run_enrollments(dirs: StorageDirs) -> Result<()> {
+    let pending = enrollment_auth(dirs)?.pending_enrollments().await?;
+    if pending.is_empty() {
+        println!("📭 No pending enrollments");
+    }
+    for enrollment in pending {
+        println!(
+            "📝 {}  {} ({} photo(s), submitted {} by {})",
+            enrollment.id,
+            enrollment.username,
+            enrollment.photos.len(),
+            enrollment.submitted_at.format("%Y-%m-%d %H:%M UTC"),
+            enrollment.submitted_by.as_deref().unwrap_or("unknown"),
+        );
+        if let Some(problem) = enrollment.review.as_ref().and_then(|r| r.problem()) {
+            println!("   ⚠️  {}", problem);
+        }
+    }
+    Ok(())
+}
 
-                println!();
-                print!("Press ENTER to return to main menu...");
-                io::stdout().flush().unwrap();
-                let _ = io::stdin().read_line(&mut String::new());
-            },
-            "6" => {
-                println!("\nThank you for using Face Authentication System!");
-                println!("Goodbye! 👋");
-                break;
-            },
-            _ => {
-                println!("\n❌ Invalid choice. Please select 1-6.");
-                println!();
-                print!("Press ENTER to continue...");
-                io::stdout().flush().unwrap();
-                let _ = io::stdin().read_line(&mut String::new());
+async fn run_approve_enrollment(id: &str, tolerance: f64, dirs: StorageDirs) -> Result<()> {
+    let generated_dir = dirs.generated_dir.to_string_lossy().into_owned();
+    let outcome = enrollment_auth(dirs)?.approve_enrollment(id, tolerance, &generated_dir).await?;
+    println!("✅ Enrolled {} with {} of {} photo(s)", outcome.username, outcome.samples_captured, outcome.samples_requested);
+    if let Some(promoted) = &outcome.promoted_file {
+        println!("📁 Credential: {}", promoted.display());
+    }
+    Ok(())
+}
+
+/// Backup key from `FACE_AUTH_BACKUP_KEY`, as 64 hex digits
+fn backup_key_from_env() -> Result<[u8; face_auth::crypto::KEY_LEN]> {
+    key_from_env("FACE_AUTH_BACKUP_KEY")
+}
+
+fn key_from_env<const N: usize>(var: &str) -> Result<[u8; N]> {
+    let hex = std::env::var(var).map_err(|_| anyhow::anyhow!("{} is not set", var))?;
+    let mut key = [0u8; N];
+    if hex.len() != key.len() * 2 {
+        return Err(anyhow::anyhow!("{} must be {} hex digits", var, key.len() * 2));
+    }
+    for (byte, pair) in key.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair)?, 16)?;
+    }
+    Ok(key)
+}
+
+#[cfg(feature = "self-update")]
+fn run_update(channel: String, channel_name: &str, install_dir: PathBuf, check: bool, dirs: StorageDirs) -> Result<()> {
+    use face_auth::UpdateOutcome;
+
+    let public_key: [u8; 32] = key_from_env("FACE_AUTH_UPDATE_KEY")?;
+    let updater = face_auth::Updater::new(channel_name, &channel, &public_key, install_dir);
+    println!("🔍 Checking {}...", channel);
+    if check {
+        let newer = updater.check()?;
+        if newer.is_empty() {
+            println!("✅ Up to date");
+        }
+        for artifact in newer {
+            println!("⬆️  {} {} -> {}", artifact.name, artifact.version, artifact.path.display());
+        }
+        return Ok(());
+    }
+    let health_check = || {
+        let auth = FaceAuth::builder().data_dir(dirs.data_dir).generated_dir(dirs.generated_dir).source_dir(dirs.source_dir).build()?;
+        tokio::runtime::Handle::current().block_on(auth.check_system())
+    };
+    match updater.update(health_check)? {
+        UpdateOutcome::UpToDate => println!("✅ Up to date"),
+        UpdateOutcome::Installed(artifacts) => {
+            for artifact in artifacts {
+                println!("✅ Installed {} {}", artifact.name, artifact.version);
             }
         }
+        UpdateOutcome::RolledBack { attempted, reason } => {
+            let names = attempted.iter().map(|a| format!("{} {}", a.name, a.version)).collect::<Vec<_>>().join(", ");
+            anyhow::bail!("Rolled back {} after the system check failed: {}", names, reason);
+        }
+    }
+    Ok(())
+}
 
-        println!(); // Add some spacing before showing menu again
+/// Add converted users to the database and write their credential files
+fn store_users(migrated: FaceDatabase, data_dir: PathBuf, source_dir: PathBuf, overwrite: bool) -> Result<()> {
+    let layout = StorageLayout { generated_dir: data_dir.join("generated"), data_dir, source_dir };
+    // Names become file names; a bad one rejects the import before anything is written
+    for name in migrated.users.keys() {
+        validate_username(name)?;
+    }
+    let journal = face_auth::Journal::new(layout.journal_dir());
+    for recovered in journal.recover()? {
+        println!("♻️  Rolled back an interrupted {} from {}", recovered.operation, recovered.started_at.format("%Y-%m-%d %H:%M:%S"));
     }
+    // All users are imported or none, even if this process is killed part way
+    let mut transaction = journal.begin("migration", None)?;
+    transaction.protect(layout.database_path())?;
+    let mut db = FaceDatabase::load(layout.database_path())?.unwrap_or_default();
+    let mut imported = 0;
+    for (name, profile) in migrated.users {
+        if db.users.contains_key(&name) && !overwrite {
+            println!("⚠️  Skipping '{}': already registered (use --overwrite to replace)", name);
+            continue;
+        }
+        transaction.protect(credential_path(&layout.source_dir, &name))?;
+        profile.save(credential_path(&layout.source_dir, &name))?;
+        println!("✅ {} ({} samples)", name, profile.face_encodings.len());
+        db.users.insert(name, profile);
+        imported += 1;
+    }
+    db.version.get_or_insert_with(|| "1.0".to_string());
+    db.save(layout.database_path())?;
+    transaction.commit()?;
 
+    println!();
+    println!("🎉 Imported {} user(s) into {}", imported, layout.database_path().display());
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Instance behind the terminal UI, keeping its data in the working directory;
+/// registrations are promoted so they can authenticate right away
+fn tui_auth() -> Result<FaceAuth> {
+    FaceAuth::builder().auto_promote(true).build()
+}
@@ -0,0 +1,15 @@
+use anyhow::Result;
+use std::fmt;
+use std::path::Path;
+
+/// Scores how likely an image was digitally manipulated, e.g. a face swap,
+/// a GAN-generated face or a screen replay
+///
+/// Run on a crop of each face in uploaded probes before matching, see
+/// [`FaceAuthBuilder::manipulation_detector`](crate::FaceAuthBuilder::manipulation_detector).
+/// With `native-ml`, [`OnnxManipulationDetector`](crate::native::OnnxManipulationDetector)
+/// runs a detection model in-process.
+pub trait ManipulationDetector: fmt::Debug + Send + Sync {
+    /// Score from 0.0 (genuine) to 1.0 (certainly manipulated)
+    fn score(&self, image: &Path) -> Result<f64>;
+}
@@ -0,0 +1,224 @@
+use serde::{Deserialize, Serialize};
+use wide::{f32x8, f64x4};
+
+use crate::face_storage::UserProfile;
+
+/// Default tolerance of the face_recognition model
+pub const DEFAULT_TOLERANCE: f64 = 0.6;
+
+/// Euclidean distance between two encodings, as computed by face_recognition
+pub fn face_distance(a: &[f64], b: &[f64]) -> f64 {
+    debug_assert_eq!(a.len(), b.len());
+    let (a_chunks, a_rest) = a.as_chunks::<4>();
+    let (b_chunks, b_rest) = b.as_chunks::<4>();
+    let mut sum = f64x4::ZERO;
+    for (x, y) in a_chunks.iter().zip(b_chunks) {
+        let d = f64x4::from(*x) - f64x4::from(*y);
+        sum = d.mul_add(d, sum);
+    }
+    let rest: f64 = a_rest.iter().zip(b_rest).map(|(x, y)| (x - y) * (x - y)).sum();
+    (sum.reduce_add() + rest).sqrt()
+}
+
+// Distance kernels for the embedding index. Slices must have equal lengths;
+// eight lanes are processed at a time with a scalar tail.
+
+/// Squared Euclidean distance
+pub fn squared_euclidean_f32(a: &[f32], b: &[f32]) -> f32 {
+    debug_assert_eq!(a.len(), b.len());
+    let (a_chunks, a_rest) = a.as_chunks::<8>();
+    let (b_chunks, b_rest) = b.as_chunks::<8>();
+    let mut sum = f32x8::ZERO;
+    for (x, y) in a_chunks.iter().zip(b_chunks) {
+        let d = f32x8::from(*x) - f32x8::from(*y);
+        sum = d.mul_add(d, sum);
+    }
+    let rest: f32 = a_rest.iter().zip(b_rest).map(|(x, y)| (x - y) * (x - y)).sum();
+    sum.reduce_add() + rest
+}
+
+/// Squared Euclidean distance between an int8-quantized vector (`q * scale`) and `probe`
+pub fn squared_euclidean_i8(quantized: &[i8], scale: f32, probe: &[f32]) -> f32 {
+    debug_assert_eq!(quantized.len(), probe.len());
+    let (q_chunks, q_rest) = quantized.as_chunks::<8>();
+    let (p_chunks, p_rest) = probe.as_chunks::<8>();
+    let scale_lanes = f32x8::splat(scale);
+    let mut sum = f32x8::ZERO;
+    for (q, p) in q_chunks.iter().zip(p_chunks) {
+        let d = f32x8::from(q.map(f32::from)).mul_sub(scale_lanes, f32x8::from(*p));
+        sum = d.mul_add(d, sum);
+    }
+    let rest: f32 = q_rest.iter().zip(p_rest)
+        .map(|(&q, p)| {
+            let d = f32::from(q) * scale - p;
+            d * d
+        })
+        .sum();
+    sum.reduce_add() + rest
+}
+
+/// Dot product
+pub fn dot_f32(a: &[f32], b: &[f32]) -> f32 {
+    debug_assert_eq!(a.len(), b.len());
+    let (a_chunks, a_rest) = a.as_chunks::<8>();
+    let (b_chunks, b_rest) = b.as_chunks::<8>();
+    let mut sum = f32x8::ZERO;
+    for (x, y) in a_chunks.iter().zip(b_chunks) {
+        sum = f32x8::from(*x).mul_add(f32x8::from(*y), sum);
+    }
+    let rest: f32 = a_rest.iter().zip(b_rest).map(|(x, y)| x * y).sum();
+    sum.reduce_add() + rest
+}
+
+/// Cosine distance (1 - cosine similarity); 1.0 when either vector is zero
+pub fn cosine_distance_f32(a: &[f32], b: &[f32]) -> f32 {
+    let norms = (dot_f32(a, a) * dot_f32(b, b)).sqrt();
+    if norms == 0.0 {
+        return 1.0;
+    }
+    1.0 - dot_f32(a, b) / norms
+}
+
+/// Average of `encodings`; `None` if there are none or their dimensions differ
+pub fn centroid<'a>(encodings: impl IntoIterator<Item = &'a [f64]>) -> Option<Vec<f64>> {
+    let mut encodings = encodings.into_iter();
+    let mut sum = encodings.next()?.to_vec();
+    let mut count = 1;
+    for encoding in encodings {
+        if encoding.len() != sum.len() {
+            return None;
+        }
+        sum.iter_mut().zip(encoding).for_each(|(s, v)| *s += v);
+        count += 1;
+    }
+    Some(sum.into_iter().map(|s| s / count as f64).collect())
+}
+
+/// Closest enrolled user for a probe encoding
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct MatchCandidate {
+    pub user_id: String,
+    /// Smallest distance to any of the user's samples
+    pub distance: f64,
+    /// Whether `distance` is within the tolerance
+    pub is_match: bool,
+}
+
+impl MatchCandidate {
+    pub fn confidence(&self) -> f64 {
+        (1.0 - self.distance).max(0.0)
+    }
+}
+
+/// Distance of one enrolled sample to a probe
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SampleDistance {
+    pub sample_id: String,
+    pub distance: f64,
+}
+
+/// Distance of `probe` to each of `profile`'s samples that take part in
+/// matching, closest first
+pub fn sample_distances(profile: &UserProfile, probe: &[f64]) -> Vec<SampleDistance> {
+    let mut distances: Vec<SampleDistance> = profile.face_encodings.iter()
+        .filter(|sample| sample.encoding.len() == probe.len() && !sample.is_quarantined())
+        .map(|sample| SampleDistance { sample_id: sample.sample_id.clone(), distance: face_distance(&sample.encoding, probe) })
+        .collect();
+    distances.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+    distances
+}
+
+/// Compare `probe` against every sample of every profile, like the Python
+/// script's auth mode, and return the closest user
+///
+/// Quarantined samples and those whose dimensions differ from the probe are ignored.
+pub fn best_match<'a>(
+    profiles: impl IntoIterator<Item = &'a UserProfile>,
+    probe: &[f64],
+    tolerance: f64,
+) -> Option<MatchCandidate> {
+    profiles
+        .into_iter()
+        .filter_map(|profile| {
+            profile.face_encodings.iter()
+                .filter(|sample| sample.encoding.len() == probe.len() && !sample.is_quarantined())
+                .map(|sample| face_distance(&sample.encoding, probe))
+                .min_by(f64::total_cmp)
+                .map(|distance| (profile, distance))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(profile, distance)| MatchCandidate {
+            user_id: profile.user_id.clone(),
+            distance,
+            is_match: distance <= tolerance,
+        })
+}
+
+/// Closest sample distance of every profile to `probe`, closest user first
+///
+/// Used where the gap to the runner-up matters, see [`best_match`] otherwise.
+pub fn ranked_matches<'a>(
+    profiles: impl IntoIterator<Item = &'a UserProfile>,
+    probe: &[f64],
+    tolerance: f64,
+) -> Vec<MatchCandidate> {
+    let mut ranked: Vec<MatchCandidate> = profiles
+        .into_iter()
+        .filter_map(|profile| best_match([profile], probe, tolerance))
+        .collect();
+    ranked.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::face_storage::FaceSample;
+
+    fn profile(user_id: &str, encodings: &[[f64; 2]]) -> UserProfile {
+        let mut profile: UserProfile = serde_json::from_value(serde_json::json!({ "user_id": user_id, "face_encodings": [] })).unwrap();
+        profile.face_encodings = encodings.iter().enumerate().map(|(i, e)| FaceSample {
+            encoding: e.to_vec(),
+            timestamp: String::new(),
+            image_path: None,
+            sample_id: format!("{}_{}", user_id, i),
+            quality: None,
+            template_version: None,
+            quarantine: None,
+        }).collect();
+        profile
+    }
+
+    #[test]
+    fn test_kernels_match_scalar_reference() {
+        let a: Vec<f32> = (0..131).map(|i| (i as f32 * 0.3).sin()).collect();
+        let b: Vec<f32> = (0..131).map(|i| (i as f32 * 0.7).cos()).collect();
+        let scalar: f32 = a.iter().zip(&b).map(|(x, y)| (x - y) * (x - y)).sum();
+        assert!((squared_euclidean_f32(&a, &b) - scalar).abs() < 1e-3);
+
+        let a64: Vec<f64> = a.iter().map(|&x| x.into()).collect();
+        let b64: Vec<f64> = b.iter().map(|&x| x.into()).collect();
+        assert!((face_distance(&a64, &b64) - f64::from(scalar).sqrt()).abs() < 1e-3);
+
+        let q: Vec<i8> = a.iter().map(|x| (x * 127.0).round() as i8).collect();
+        let dequantized: Vec<f32> = q.iter().map(|&v| f32::from(v) / 127.0).collect();
+        assert!((squared_euclidean_i8(&q, 1.0 / 127.0, &b) - squared_euclidean_f32(&dequantized, &b)).abs() < 1e-3);
+
+        assert!(cosine_distance_f32(&a, &a).abs() < 1e-5);
+        assert_eq!(cosine_distance_f32(&a, &[0.0; 131]), 1.0);
+    }
+
+    #[test]
+    fn test_best_match_uses_closest_sample() {
+        let profiles = [profile("ann", &[[1.0, 1.0], [0.1, 0.0]]), profile("bob", &[[0.3, 0.0]])];
+
+        let candidate = best_match(&profiles, &[0.0, 0.0], DEFAULT_TOLERANCE).unwrap();
+        assert_eq!(candidate.user_id, "ann");
+        assert!((candidate.distance - 0.1).abs() < 1e-9);
+        assert!(candidate.is_match);
+
+        assert!(!best_match(&profiles, &[5.0, 5.0], DEFAULT_TOLERANCE).unwrap().is_match);
+        assert!(best_match(&profiles, &[0.0, 0.0, 0.0], DEFAULT_TOLERANCE).is_none());
+    }
+}
@@ -0,0 +1,280 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::denial::DenialReason;
+use crate::registration::{SampleQuality, SampleStatus};
+
+/// Catalogs shipped with the library, by language
+const BUILTIN: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.json")),
+    ("de", include_str!("../locales/de.json")),
+    ("es", include_str!("../locales/es.json")),
+    ("fr", include_str!("../locales/fr.json")),
+];
+
+/// Sharpness at which the quality score stops improving
+pub const SHARP_ENOUGH: f64 = 100.0;
+
+/// Face ratio at which the quality score stops improving
+pub const LARGE_ENOUGH: f64 = 0.1;
+
+/// Problem with a captured face that the user can fix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QualityIssue {
+    Blurry,
+    FaceTooSmall,
+}
+
+impl QualityIssue {
+    pub fn key(self) -> &'static str {
+        match self {
+            QualityIssue::Blurry => "quality.blurry",
+            QualityIssue::FaceTooSmall => "quality.face_too_small",
+        }
+    }
+
+    /// What the user should do about it
+    pub fn tip(self) -> CaptureTip {
+        match self {
+            QualityIssue::Blurry => CaptureTip::HoldStill,
+            QualityIssue::FaceTooSmall => CaptureTip::MoveCloser,
+        }
+    }
+}
+
+impl SampleQuality {
+    /// Issues that lowered the score
+    pub fn issues(&self) -> Vec<QualityIssue> {
+        let mut issues = Vec::new();
+        if self.sharpness < SHARP_ENOUGH {
+            issues.push(QualityIssue::Blurry);
+        }
+        if self.face_ratio < LARGE_ENOUGH {
+            issues.push(QualityIssue::FaceTooSmall);
+        }
+        issues
+    }
+}
+
+/// Guidance shown while capturing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureTip {
+    LookAtCamera,
+    HoldStill,
+    MoveCloser,
+    TryAgain,
+    /// Vary the pose between registration samples
+    TurnHead,
+    /// Nobody can be recognized before someone is registered
+    RegisterFirst,
+}
+
+impl CaptureTip {
+    pub fn key(self) -> &'static str {
+        match self {
+            CaptureTip::LookAtCamera => "tip.look_at_camera",
+            CaptureTip::HoldStill => "tip.hold_still",
+            CaptureTip::MoveCloser => "tip.move_closer",
+            CaptureTip::TryAgain => "tip.try_again",
+            CaptureTip::TurnHead => "tip.turn_head",
+            CaptureTip::RegisterFirst => "tip.register_first",
+        }
+    }
+}
+
+impl SampleStatus {
+    /// What the user should do differently for the next sample
+    pub fn tip(self) -> Option<CaptureTip> {
+        match self {
+            SampleStatus::Stored => None,
+            SampleStatus::CaptureFailed => Some(CaptureTip::TryAgain),
+            SampleStatus::NoFace => Some(CaptureTip::LookAtCamera),
+            SampleStatus::Duplicate => Some(CaptureTip::TurnHead),
+        }
+    }
+}
+
+impl DenialReason {
+    /// Catalog key of the message; `denial.` followed by the code
+    pub fn key(&self) -> &'static str {
+        match self {
+            DenialReason::NoFace => "denial.no_face",
+            DenialReason::NoUsersEnrolled => "denial.no_users_enrolled",
+            DenialReason::ClaimNotVerified => "denial.claim_not_verified",
+            DenialReason::BelowThreshold { .. } => "denial.below_threshold",
+            DenialReason::LivenessFailed => "denial.liveness_failed",
+            DenialReason::LockedOut { until: None } => "denial.locked_out",
+            DenialReason::LockedOut { until: Some(_) } => "denial.locked_out_until",
+            DenialReason::OutsideSchedule => "denial.outside_schedule",
+            DenialReason::WatchlistHit { .. } => "denial.watchlist_hit",
+            DenialReason::QualityTooLow { .. } => "denial.quality_too_low",
+            DenialReason::ShadowMode => "denial.shadow_mode",
+            DenialReason::Ambiguous { .. } => "denial.ambiguous",
+            DenialReason::HookDenied { .. } => "denial.hook_denied",
+            DenialReason::PolicyDenied { .. } => "denial.policy_denied",
+            DenialReason::StepUpRequired { .. } => "denial.step_up_required",
+            DenialReason::ImageTooOld { .. } => "denial.image_too_old",
+            DenialReason::ManipulationSuspected { .. } => "denial.manipulation_suspected",
+            DenialReason::PlatformRefused { .. } => "denial.platform_refused",
+            DenialReason::DeviceBusy { eta: None, .. } => "denial.device_busy",
+            DenialReason::DeviceBusy { eta: Some(_), .. } => "denial.device_busy_until",
+        }
+    }
+
+    /// What the user can do to get in, if anything
+    pub fn tip(&self) -> Option<CaptureTip> {
+        match self {
+            DenialReason::NoFace => Some(CaptureTip::LookAtCamera),
+            DenialReason::NoUsersEnrolled => Some(CaptureTip::RegisterFirst),
+            DenialReason::QualityTooLow { .. } => Some(CaptureTip::HoldStill),
+            DenialReason::BelowThreshold { .. } | DenialReason::ClaimNotVerified | DenialReason::LivenessFailed | DenialReason::DeviceBusy { .. } => {
+                Some(CaptureTip::TryAgain)
+            }
+            _ => None,
+        }
+    }
+
+    fn arguments(&self) -> Vec<(&'static str, String)> {
+        match self {
+            DenialReason::BelowThreshold { distance, threshold } => {
+                vec![("distance", format!("{:.3}", distance)), ("threshold", format!("{:.3}", threshold))]
+            }
+            DenialReason::LockedOut { until: Some(until) } => vec![("until", until.format("%Y-%m-%d %H:%M UTC").to_string())],
+            DenialReason::DeviceBusy { eta: Some(eta), .. } => vec![("eta", eta.format("%H:%M:%S UTC").to_string())],
+            DenialReason::ImageTooOld { captured_at } => vec![("captured_at", captured_at.format("%Y-%m-%d %H:%M UTC").to_string())],
+            DenialReason::WatchlistHit { entry } => vec![("entry", entry.clone())],
+            DenialReason::PlatformRefused { platform } => vec![("platform", platform.clone())],
+            DenialReason::HookDenied { reason } | DenialReason::PolicyDenied { reason } | DenialReason::StepUpRequired { reason } => {
+                vec![("reason", reason.clone())]
+            }
+            DenialReason::Ambiguous { distance, runner_up_distance } => {
+                vec![("distance", format!("{:.3}", distance)), ("runner_up_distance", format!("{:.3}", runner_up_distance))]
+            }
+            DenialReason::ManipulationSuspected { score, maximum } => {
+                vec![("score", format!("{:.2}", score)), ("maximum", format!("{:.2}", maximum))]
+            }
+            DenialReason::QualityTooLow { score, minimum } => {
+                vec![("score", format!("{:.2}", score)), ("minimum", format!("{:.2}", minimum))]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// User-facing strings of one locale
+///
+/// Keys missing from a catalog fall back to English. Messages may contain
+/// `{name}` placeholders, filled in from the reason they describe.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageCatalog {
+    locale: String,
+    messages: HashMap<String, String>,
+}
+
+impl MessageCatalog {
+    pub fn english() -> Self {
+        Self::builtin("en")
+    }
+
+    /// Shipped catalog for `locale`, matched on its language (`de-CH` uses `de`);
+    /// English if there is none
+    pub fn builtin(locale: &str) -> Self {
+        let language = locale.split(['-', '_']).next().unwrap_or_default().to_ascii_lowercase();
+        let english = parse(BUILTIN[0].1).expect("built-in English catalog is valid");
+        let mut messages = english.clone();
+        if let Some((_, json)) = BUILTIN.iter().find(|(lang, _)| *lang == language) {
+            messages.extend(parse(json).expect("built-in catalogs are valid"));
+        }
+        Self { locale: locale.to_string(), messages }
+    }
+
+    /// Catalog for `locale` from a JSON object of key to message, layered over
+    /// the built-in one
+    ///
+    /// Unknown keys are rejected so typos don't silently fall back to English.
+    pub fn from_json(locale: &str, json: &str) -> Result<Self> {
+        let mut catalog = Self::builtin(locale);
+        for (key, message) in parse(json)? {
+            if !catalog.messages.contains_key(&key) {
+                return Err(anyhow!("Unknown message key '{}'", key));
+            }
+            catalog.messages.insert(key, message);
+        }
+        Ok(catalog)
+    }
+
+    pub fn load(locale: &str, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        Self::from_json(locale, &json).map_err(|e| anyhow!("Invalid message catalog {}: {}", path.display(), e))
+    }
+
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    /// Message for `key` with its placeholders filled in; the key itself if unknown
+    pub fn get(&self, key: &str, arguments: &[(&str, String)]) -> String {
+        let Some(message) = self.messages.get(key) else {
+            return key.to_string();
+        };
+        arguments.iter().fold(message.clone(), |message, (name, value)| message.replace(&format!("{{{}}}", name), value))
+    }
+
+    pub fn denial(&self, reason: &DenialReason) -> String {
+        self.get(reason.key(), &reason.arguments())
+    }
+
+    pub fn quality(&self, issue: QualityIssue) -> String {
+        self.get(issue.key(), &[])
+    }
+
+    pub fn tip(&self, tip: CaptureTip) -> String {
+        self.get(tip.key(), &[])
+    }
+}
+
+impl Default for MessageCatalog {
+    fn default() -> Self {
+        Self::english()
+    }
+}
+
+/// Shared English catalog, used for log messages
+pub(crate) fn english() -> &'static MessageCatalog {
+    static ENGLISH: OnceLock<MessageCatalog> = OnceLock::new();
+    ENGLISH.get_or_init(MessageCatalog::english)
+}
+
+fn parse(json: &str) -> Result<HashMap<String, String>> {
+    Ok(serde_json::from_str(json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalogs_localize_with_english_fallback() {
+        let english = MessageCatalog::english();
+        for (locale, json) in BUILTIN {
+            let keys = parse(json).unwrap();
+            assert_eq!(keys.len(), english.messages.len(), "catalog '{}' is incomplete", locale);
+        }
+
+        let german = MessageCatalog::builtin("de-CH");
+        let reason = DenialReason::BelowThreshold { distance: 0.71, threshold: 0.6 };
+        assert_eq!(german.denial(&reason), "Gesicht nicht erkannt (Abstand 0.710, Schwelle 0.600)");
+        assert_eq!(MessageCatalog::builtin("ja").tip(CaptureTip::MoveCloser), "Move closer to the camera");
+
+        let custom = MessageCatalog::from_json("nl", r#"{"denial.no_face": "Geen gezicht gevonden"}"#).unwrap();
+        assert_eq!(custom.denial(&DenialReason::NoFace), "Geen gezicht gevonden");
+        assert_eq!(custom.quality(QualityIssue::Blurry), "The image is blurry");
+        assert!(MessageCatalog::from_json("nl", r#"{"denial.no_fcae": "typo"}"#).is_err());
+    }
+}
@@ -0,0 +1,205 @@
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::Local;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::face_storage::{self, FaceDatabase, FaceSample, UserProfile};
+use crate::sample_id;
+
+/// Convert an encoding store written by a `face_recognition` script
+///
+/// Recognized layouts:
+/// - `{"encodings": [...], "names": [...]}`, the parallel lists most tutorials pickle
+/// - `{"<name>": <encoding or list of encodings>, ...}`
+/// - `[{"name": ..., "encoding": ...}, ...]` (also `encodings`, `user` or `user_id`)
+/// - a `FaceDatabase` of this crate, which is returned unchanged
+///
+/// Names are kept as usernames and every encoding becomes one sample. A
+/// name that can't be a credential file name, such as `../admin`, fails the
+/// whole conversion.
+pub fn from_value(value: Value) -> Result<FaceDatabase> {
+    let mut samples: BTreeMap<String, Vec<Vec<f64>>> = BTreeMap::new();
+    match value {
+        Value::Object(map) if map.contains_key("users") => {
+            let db = FaceDatabase::from_json(&Value::Object(map).to_string())?;
+            for name in db.users.keys() {
+                face_storage::validate_username(name).context("Cannot migrate")?;
+            }
+            return Ok(db);
+        }
+        Value::Object(mut map) if map.contains_key("encodings") && map.contains_key("names") => {
+            let names = map.remove("names").unwrap_or_default();
+            let encodings = map.remove("encodings").unwrap_or_default();
+            let (Value::Array(names), Value::Array(encodings)) = (names, encodings) else {
+                bail!("'names' and 'encodings' must be lists");
+            };
+            if names.len() != encodings.len() {
+                bail!("{} names but {} encodings", names.len(), encodings.len());
+            }
+            for (name, encoding) in names.into_iter().zip(encodings) {
+                let name = name_of(&name)?;
+                samples.entry(name).or_default().push(encoding_of(&encoding)?);
+            }
+        }
+        Value::Object(map) => {
+            for (name, encodings) in map {
+                samples.entry(name).or_default().extend(encodings_of(&encodings)?);
+            }
+        }
+        Value::Array(records) => {
+            for record in records {
+                let name = ["name", "user", "user_id"].iter()
+                    .find_map(|key| record.get(key))
+                    .ok_or_else(|| anyhow!("Record without a name: {}", record))?;
+                let encodings = ["encodings", "encoding"].iter()
+                    .find_map(|key| record.get(key))
+                    .ok_or_else(|| anyhow!("Record without an encoding: {}", record))?;
+                samples.entry(name_of(name)?).or_default().extend(encodings_of(encodings)?);
+            }
+        }
+        other => bail!("Unrecognized encoding store: expected an object or a list, found {}", kind(&other)),
+    }
+
+    let now = Local::now().format("%Y-%m-%dT%H:%M:%S%.6f").to_string();
+    let mut db = FaceDatabase {
+        version: Some("1.0".to_string()),
+        created: Some(now.clone()),
+        ..Default::default()
+    };
+    for (name, encodings) in samples {
+        face_storage::validate_username(&name).context("Cannot migrate")?;
+        let face_encodings: Vec<FaceSample> = encodings.into_iter().map(|encoding| FaceSample {
+            encoding,
+            timestamp: now.clone(),
+            image_path: None,
+            sample_id: sample_id::generate_now(),
+            quality: None,
+            template_version: None,
+            quarantine: None,
+        }).collect();
+        let profile = UserProfile {
+            user_id: name.clone(),
+            sample_count: face_encodings.len(),
+            face_encodings,
+            enrollment_date: Some(now.clone()),
+            metadata: Default::default(),
+            extra: Default::default(),
+        };
+        profile.validate().with_context(|| format!("Cannot migrate '{}'", name))?;
+        db.users.insert(name, profile);
+    }
+    Ok(db)
+}
+
+/// Read a JSON encoding store, see [`from_value`]
+pub fn load_json(path: impl AsRef<Path>) -> Result<FaceDatabase> {
+    let path = path.as_ref();
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let value = serde_json::from_str(&content)
+        .with_context(|| format!("Invalid JSON in {}", path.display()))?;
+    from_value(value).with_context(|| format!("Failed to migrate {}", path.display()))
+}
+
+/// Read a pickled encoding store, see [`from_value`]
+///
+/// The pickle is loaded by the backend's Python interpreter, which also
+/// turns numpy arrays into lists. Unpickling runs arbitrary code, so only
+/// migrate files you created yourself.
+#[cfg(feature = "python-backend")]
+pub fn load_pickle(python: &crate::StandalonePythonFaceAuth, path: impl AsRef<Path>) -> Result<FaceDatabase> {
+    let path = path.as_ref();
+    let value = python.load_pickle(path)?;
+    from_value(value).with_context(|| format!("Failed to migrate {}", path.display()))
+}
+
+/// Whether `path` looks like a pickle rather than JSON
+pub fn is_pickle(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "pkl" || ext == "pickle" || ext == "p")
+}
+
+fn name_of(value: &Value) -> Result<String> {
+    match value {
+        Value::String(name) => Ok(name.clone()),
+        Value::Number(number) => Ok(number.to_string()),
+        other => bail!("Expected a name, found {}", kind(other)),
+    }
+}
+
+fn encoding_of(value: &Value) -> Result<Vec<f64>> {
+    let Value::Array(values) = value else {
+        bail!("Expected an encoding, found {}", kind(value));
+    };
+    values.iter()
+        .map(|v| v.as_f64().ok_or_else(|| anyhow!("Expected a number in encoding, found {}", kind(v))))
+        .collect()
+}
+
+/// One encoding or a list of encodings
+fn encodings_of(value: &Value) -> Result<Vec<Vec<f64>>> {
+    match value {
+        Value::Array(items) if items.iter().all(Value::is_array) => items.iter().map(encoding_of).collect(),
+        _ => Ok(vec![encoding_of(value)?]),
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "a list",
+        Value::Object(_) => "an object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::face_storage::ENCODING_DIMENSIONS;
+    use serde_json::json;
+
+    #[test]
+    fn test_recognized_layouts_preserve_names_and_counts() {
+        let face = |v: f64| vec![v; ENCODING_DIMENSIONS];
+        let layouts = [
+            json!({ "encodings": [face(0.1), face(0.2), face(0.3)], "names": ["Ann Lee", "Ann Lee", "bob"] }),
+            json!({ "Ann Lee": [face(0.1), face(0.2)], "bob": face(0.3) }),
+            json!([{ "name": "Ann Lee", "encodings": [face(0.1), face(0.2)] }, { "user": "bob", "encoding": face(0.3) }]),
+        ];
+        for layout in layouts {
+            let db = from_value(layout).unwrap();
+            assert_eq!(db.users["Ann Lee"].sample_count, 2);
+            assert_eq!(db.users["bob"].face_encodings.len(), 1);
+            assert_eq!(db.users["bob"].face_encodings[0].encoding, face(0.3));
+        }
+
+        assert!(from_value(json!({ "encodings": [face(0.1)], "names": [] })).is_err());
+        assert!(from_value(json!({ "ann": [0.1, 0.2] })).is_err());
+        assert!(from_value(json!("ann")).is_err());
+        assert!(from_value(json!({ "../admin": face(0.1) })).is_err());
+        assert!(from_value(json!([{ "name": "a/b", "encoding": face(0.1) }])).is_err());
+    }
+
+    #[cfg(feature = "python-backend")]
+    #[test]
+    fn test_pickle_is_converted_with_python() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("known_faces.pkl");
+        let status = std::process::Command::new("python3")
+            .arg("-c")
+            .arg("import pickle, sys; pickle.dump({'names': ['ann'], 'encodings': [tuple([0.1] * 128)]}, open(sys.argv[1], 'wb'))")
+            .arg(&path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let python = crate::StandalonePythonFaceAuth::from_parts("python3", "unused.py", dir.path());
+        let db = load_pickle(&python, &path).unwrap();
+        assert_eq!(db.users["ann"].face_encodings[0].encoding, vec![0.1; ENCODING_DIMENSIONS]);
+        assert!(is_pickle(&path));
+    }
+}
@@ -0,0 +1,199 @@
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::error::FaceAuthError;
+use crate::face_storage::{self, write_atomic, UserMetadata};
+use crate::matching::MatchCandidate;
+use crate::provenance;
+
+/// Largest photo accepted for a remote enrollment
+pub const MAX_PHOTO_BYTES: usize = 10 * 1024 * 1024;
+
+const REQUEST_FILE: &str = "request.json";
+
+/// Enrollment photos submitted remotely, waiting for an admin's decision
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingEnrollment {
+    pub id: String,
+    pub username: String,
+    pub submitted_at: DateTime<Utc>,
+    /// Who submitted the photos, e.g. the HR portal's account
+    pub submitted_by: Option<String>,
+    #[serde(default)]
+    pub metadata: UserMetadata,
+    /// Photo file names inside the enrollment's directory
+    pub photos: Vec<String>,
+    /// Result of the last quality and duplication check
+    pub review: Option<EnrollmentReview>,
+}
+
+/// Quality and duplication check of a [`PendingEnrollment`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnrollmentReview {
+    pub reviewed_at: DateTime<Utc>,
+    /// Photos in which a face was found
+    pub usable_photos: usize,
+    /// Photos without a face
+    pub rejected_photos: Vec<String>,
+    /// Another enrolled user the face already matches
+    pub duplicate_of: Option<MatchCandidate>,
+    /// Someone is already enrolled under the submitted username; approving
+    /// would replace their enrollment
+    #[serde(default)]
+    pub name_taken: bool,
+}
+
+impl EnrollmentReview {
+    /// At least one usable photo, no match with another user and a free username
+    pub fn passed(&self) -> bool {
+        self.usable_photos > 0 && self.duplicate_of.is_none() && !self.name_taken
+    }
+
+    /// Why the check failed, if it did
+    pub fn problem(&self) -> Option<String> {
+        if self.name_taken {
+            Some("Username is already enrolled".to_string())
+        } else if let Some(duplicate) = &self.duplicate_of {
+            Some(format!("Face already enrolled as '{}' (distance {:.3})", duplicate.user_id, duplicate.distance))
+        } else if self.usable_photos == 0 {
+            Some("No face found in any photo".to_string())
+        } else {
+            None
+        }
+    }
+}
+
+/// Directory of pending enrollments, one subdirectory per submission
+#[derive(Debug, Clone)]
+pub struct EnrollmentQueue {
+    dir: PathBuf,
+}
+
+impl EnrollmentQueue {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Store `photos` (JPEG or PNG bytes) for `username` as pending
+    pub fn submit(&self, username: &str, photos: &[Vec<u8>], submitted_by: Option<String>, metadata: UserMetadata) -> Result<PendingEnrollment> {
+        face_storage::validate_username(username)?;
+        if photos.is_empty() {
+            return Err(invalid("An enrollment needs at least one photo".to_string()));
+        }
+        if let Some(index) = photos.iter().position(|p| p.len() > MAX_PHOTO_BYTES) {
+            return Err(invalid(format!("Photo {} is larger than {} bytes", index + 1, MAX_PHOTO_BYTES)));
+        }
+
+        static SUBMISSIONS: AtomicU32 = AtomicU32::new(0);
+        let submitted_at = Utc::now();
+        let id = format!("{}-{:04}", submitted_at.format("%Y%m%d%H%M%S%3f"), SUBMISSIONS.fetch_add(1, Ordering::Relaxed) % 10_000);
+        let dir = self.dir.join(&id);
+        let mut names = Vec::with_capacity(photos.len());
+        for (index, photo) in photos.iter().enumerate() {
+            let name = format!("photo_{}.jpg", index + 1);
+            // Submitted photos often carry the phone's GPS position
+            let scrubbed = provenance::scrub_metadata(photo)
+                .map_err(|_| invalid(format!("Photo {} is not a readable JPEG or PNG", index + 1)))?;
+            write_atomic(&dir.join(&name), &scrubbed.bytes)?;
+            names.push(name);
+        }
+        let pending = PendingEnrollment {
+            id,
+            username: username.to_string(),
+            submitted_at,
+            submitted_by,
+            metadata,
+            photos: names,
+            review: None,
+        };
+        self.save(&pending)?;
+        Ok(pending)
+    }
+
+    /// Pending enrollments, oldest first
+    pub fn list(&self) -> Result<Vec<PendingEnrollment>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut pending = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path().join(REQUEST_FILE);
+            if path.is_file() {
+                match load(&path) {
+                    Ok(enrollment) => pending.push(enrollment),
+                    Err(e) => tracing::warn!("Skipping {}: {:#}", path.display(), e),
+                }
+            }
+        }
+        pending.sort_by(|a, b| a.submitted_at.cmp(&b.submitted_at).then_with(|| a.id.cmp(&b.id)));
+        Ok(pending)
+    }
+
+    pub fn get(&self, id: &str) -> Result<PendingEnrollment> {
+        self.request_path(id).and_then(|path| load(&path)).map_err(|_| not_pending(id))
+    }
+
+    pub fn photo_paths(&self, pending: &PendingEnrollment) -> Vec<PathBuf> {
+        pending.photos.iter().map(|name| self.dir.join(&pending.id).join(name)).collect()
+    }
+
+    pub fn save(&self, pending: &PendingEnrollment) -> Result<()> {
+        write_atomic(&self.request_path(&pending.id)?, serde_json::to_string_pretty(pending)?.as_bytes())
+    }
+
+    /// Delete a pending enrollment and its photos
+    pub fn remove(&self, id: &str) -> Result<()> {
+        let request = self.request_path(id).map_err(|_| not_pending(id))?;
+        let dir = request.parent().expect("request file is inside the enrollment directory");
+        fs::remove_dir_all(dir).map_err(|_| not_pending(id))
+    }
+
+    fn request_path(&self, id: &str) -> Result<PathBuf> {
+        // Ids come from HTTP requests; keep them inside the queue directory
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            bail!("Invalid enrollment id '{}'", id);
+        }
+        Ok(self.dir.join(id).join(REQUEST_FILE))
+    }
+}
+
+fn invalid(reason: String) -> anyhow::Error {
+    FaceAuthError::InvalidEnrollment { reason }.into()
+}
+
+fn not_pending(id: &str) -> anyhow::Error {
+    FaceAuthError::EnrollmentNotPending { id: id.to_string() }.into()
+}
+
+fn load(path: &Path) -> Result<PendingEnrollment> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_stores_lists_and_removes_submissions() {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = EnrollmentQueue::new(dir.path());
+        assert!(queue.submit("ann", &[], None, UserMetadata::default()).is_err());
+        assert!(queue.submit("../ann", &[b"jpeg".to_vec()], None, UserMetadata::default()).is_err());
+
+        let first = queue.submit("ann", &[b"one".to_vec(), b"two".to_vec()], Some("hr-portal".into()), UserMetadata::default()).unwrap();
+        let second = queue.submit("bob", &[b"three".to_vec()], None, UserMetadata::default()).unwrap();
+        let ids: Vec<_> = queue.list().unwrap().into_iter().map(|p| p.id).collect();
+        assert_eq!(ids, [first.id.clone(), second.id.clone()]);
+        assert_eq!(fs::read(&queue.photo_paths(&first)[1]).unwrap(), b"two");
+        assert!(queue.get("../../etc").is_err());
+
+        queue.remove(&first.id).unwrap();
+        assert!(queue.get(&first.id).is_err());
+        assert_eq!(queue.list().unwrap(), [second]);
+    }
+}
@@ -0,0 +1,267 @@
+use anyhow::{Result, anyhow, bail};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+use std::thread;
+use std::time::Duration;
+
+/// Broker connection settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct MqttOptions {
+    /// `host:port`, e.g. `homeassistant.local:1883`
+    pub broker: String,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub keep_alive: Duration,
+    /// Retained message the broker publishes if the connection drops
+    pub last_will: Option<(String, String)>,
+}
+
+impl MqttOptions {
+    pub fn new(broker: impl Into<String>, client_id: impl Into<String>) -> Self {
+        Self {
+            broker: broker.into(),
+            client_id: client_id.into(),
+            username: None,
+            password: None,
+            keep_alive: Duration::from_secs(60),
+            last_will: None,
+        }
+    }
+
+    pub fn with_credentials(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.username = Some(username.into());
+        self.password = Some(password.into());
+        self
+    }
+
+    pub fn with_last_will(mut self, topic: impl Into<String>, payload: impl Into<String>) -> Self {
+        self.last_will = Some((topic.into(), payload.into()));
+        self
+    }
+}
+
+/// Minimal MQTT 3.1.1 publisher (QoS 0 and 1, plain TCP)
+///
+/// Enough to report state to a broker without pulling in an async client. A
+/// background thread sends keep-alive pings; a publish that fails reconnects
+/// once and retries.
+#[derive(Debug, Clone)]
+pub struct MqttClient {
+    options: MqttOptions,
+    stream: Arc<Mutex<TcpStream>>,
+    packet_id: Arc<AtomicU16>,
+}
+
+impl MqttClient {
+    pub fn connect(options: MqttOptions) -> Result<Self> {
+        let stream = Arc::new(Mutex::new(open(&options)?));
+        let pinger = Arc::downgrade(&stream);
+        let interval = options.keep_alive / 2;
+        if !interval.is_zero() {
+            thread::spawn(move || keep_alive(pinger, interval));
+        }
+        Ok(Self { options, stream, packet_id: Arc::new(AtomicU16::new(1)) })
+    }
+
+    /// Publish at QoS 0: done once written to the connection
+    pub fn publish(&self, topic: &str, payload: &[u8], retain: bool) -> Result<()> {
+        let mut body = encode_str(topic);
+        body.extend_from_slice(payload);
+        let packet = packet(0x30 | retain as u8, &body);
+
+        let mut stream = self.stream.lock().unwrap_or_else(PoisonError::into_inner);
+        if stream.write_all(&packet).is_err() {
+            *stream = open(&self.options)?;
+            stream.write_all(&packet).map_err(|e| anyhow!("Failed to publish to {}: {}", self.options.broker, e))?;
+        }
+        Ok(())
+    }
+
+    /// Publish at QoS 1: done only once the broker acknowledged the message
+    /// with a PUBACK. Without one the message is sent again on a fresh
+    /// connection, so the broker may see it twice.
+    pub fn publish_acked(&self, topic: &str, payload: &[u8], retain: bool) -> Result<()> {
+        let id = match self.packet_id.fetch_add(1, Ordering::Relaxed) {
+            0 => self.packet_id.fetch_add(1, Ordering::Relaxed),
+            id => id,
+        };
+        let mut body = encode_str(topic);
+        body.extend(id.to_be_bytes());
+        body.extend_from_slice(payload);
+        let packet = packet(0x32 | retain as u8, &body);
+
+        let mut stream = self.stream.lock().unwrap_or_else(PoisonError::into_inner);
+        if send_acked(&mut stream, &packet, id).is_err() {
+            *stream = open(&self.options)?;
+            // DUP flag: the broker may have received the first attempt
+            let mut packet = packet;
+            packet[0] |= 0x08;
+            send_acked(&mut stream, &packet, id).map_err(|e| anyhow!("Failed to publish to {}: {}", self.options.broker, e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Write a QoS 1 `packet` and wait for the broker's PUBACK of `id`
+fn send_acked(stream: &mut TcpStream, packet: &[u8], id: u16) -> Result<()> {
+    stream.write_all(packet)?;
+    let mut puback = [0u8; 4];
+    stream.read_exact(&mut puback)?;
+    if puback[..2] != [0x40, 0x02] || puback[2..] != id.to_be_bytes() {
+        bail!("Expected PUBACK for packet {}, got {:02x?}", id, puback);
+    }
+    Ok(())
+}
+
+impl Drop for MqttClient {
+    fn drop(&mut self) {
+        // Only the last clone disconnects; the pinger holds a weak reference
+        if Arc::strong_count(&self.stream) == 1 {
+            let _ = self.stream.lock().unwrap_or_else(PoisonError::into_inner).write_all(&[0xE0, 0x00]);
+        }
+    }
+}
+
+fn open(options: &MqttOptions) -> Result<TcpStream> {
+    let mut stream = TcpStream::connect(&options.broker).map_err(|e| anyhow!("{} unreachable: {}", options.broker, e))?;
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    stream.write_all(&connect_packet(options))?;
+
+    let mut connack = [0u8; 4];
+    stream.read_exact(&mut connack).map_err(|e| anyhow!("No answer from {}: {}", options.broker, e))?;
+    if connack[0] != 0x20 {
+        bail!("{} is not an MQTT broker", options.broker);
+    }
+    if connack[3] != 0 {
+        bail!("{} refused the connection (code {})", options.broker, connack[3]);
+    }
+    Ok(stream)
+}
+
+fn keep_alive(stream: Weak<Mutex<TcpStream>>, interval: Duration) {
+    loop {
+        thread::sleep(interval);
+        let Some(stream) = stream.upgrade() else { return };
+        let mut stream = stream.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut response = [0u8; 2];
+        if let Err(e) = stream.write_all(&[0xC0, 0x00]).and_then(|_| stream.read_exact(&mut response)) {
+            tracing::warn!("MQTT keep-alive failed: {}", e);
+        }
+    }
+}
+
+fn connect_packet(options: &MqttOptions) -> Vec<u8> {
+    let mut flags = 0x02;
+    let mut payload = encode_str(&options.client_id);
+    if let Some((topic, message)) = &options.last_will {
+        flags |= 0x04 | 0x20;
+        payload.extend(encode_str(topic));
+        payload.extend(encode_str(message));
+    }
+    if let Some(username) = &options.username {
+        flags |= 0x80;
+        payload.extend(encode_str(username));
+    }
+    if let Some(password) = &options.password {
+        flags |= 0x40;
+        payload.extend(encode_str(password));
+    }
+    let keep_alive = options.keep_alive.as_secs().min(u16::MAX as u64) as u16;
+    let mut body = encode_str("MQTT");
+    body.extend([0x04, flags]);
+    body.extend(keep_alive.to_be_bytes());
+    body.extend(payload);
+    packet(0x10, &body)
+}
+
+fn encode_str(s: &str) -> Vec<u8> {
+    let mut encoded = (s.len() as u16).to_be_bytes().to_vec();
+    encoded.extend_from_slice(s.as_bytes());
+    encoded
+}
+
+/// Fixed header with the variable-length remaining length, then `body`
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    let mut remaining = body.len();
+    loop {
+        let byte = (remaining % 128) as u8;
+        remaining /= 128;
+        packet.push(if remaining > 0 { byte | 0x80 } else { byte });
+        if remaining == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_client_connects_and_publishes() {
+        assert_eq!(&packet(0x30, &[0; 200])[..3], [0x30, 0xC8, 0x01]);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let broker = listener.local_addr().unwrap().to_string();
+        let received = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut header = [0u8; 2];
+            stream.read_exact(&mut header).unwrap();
+            let mut connect = vec![0u8; header[1] as usize];
+            stream.read_exact(&mut connect).unwrap();
+            stream.write_all(&[0x20, 0x02, 0x00, 0x00]).unwrap();
+            let mut rest = Vec::new();
+            stream.read_to_end(&mut rest).unwrap();
+            (connect, rest)
+        });
+
+        let options = MqttOptions::new(&broker, "door").with_last_will("door/status", "offline");
+        let client = MqttClient::connect(options).unwrap();
+        client.publish("door/person", b"ON", true).unwrap();
+        drop(client);
+
+        let (connect, rest) = received.join().unwrap();
+        assert_eq!(&connect[..8], [0, 4, b'M', b'Q', b'T', b'T', 4, 0x26]);
+        assert!(connect.ends_with(b"offline"));
+        assert_eq!(rest, [&[0x31, 15, 0, 11][..], b"door/person", b"ON", &[0xE0, 0x00]].concat());
+    }
+
+    #[test]
+    fn test_acked_publish_waits_for_the_puback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let broker = listener.local_addr().unwrap().to_string();
+        let accept = |listener: &TcpListener| {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut header = [0u8; 2];
+            stream.read_exact(&mut header).unwrap();
+            stream.read_exact(&mut vec![0u8; header[1] as usize]).unwrap();
+            stream.write_all(&[0x20, 0x02, 0x00, 0x00]).unwrap();
+            stream
+        };
+        let broker_thread = thread::spawn(move || {
+            let mut stream = accept(&listener);
+            let mut publish = [0u8; 19];
+            stream.read_exact(&mut publish).unwrap();
+            stream.write_all(&[0x40, 0x02, publish[15], publish[16]]).unwrap();
+
+            // Swallow the second publish without acknowledging it, then go away
+            stream.read_exact(&mut publish).unwrap();
+            drop(listener);
+            publish
+        });
+
+        let options = MqttOptions::new(&broker, "door").with_last_will("door/status", "offline");
+        let client = MqttClient::connect(MqttOptions { keep_alive: Duration::ZERO, ..options }).unwrap();
+        client.publish_acked("door/events", b"{}", false).unwrap();
+        assert!(client.publish_acked("door/events", b"{}", false).is_err());
+        let publish = broker_thread.join().unwrap();
+        assert_eq!(publish, [&[0x32, 17, 0, 11][..], b"door/events", &[0, 2], b"{}"].concat()[..]);
+    }
+}
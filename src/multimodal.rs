@@ -0,0 +1,146 @@
+//! Fusing the face with another biometric
+//!
+//! Deployments that already verify another modality, e.g. a call center
+//! scoring the caller's voice, can have that score count towards the
+//! decision. A [`SecondaryBiometric`] scores the user the face matched, both
+//! scores reach the decision policy in [`PolicyInput::modalities`], and a
+//! [`MultiModalPolicy`] grants only when their weighted sum is high enough:
+//! a strong voice match can carry a borderline face and the other way round.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::context::AuthContext;
+use crate::policy::{DecisionPolicy, PolicyDecision, PolicyInput};
+
+/// Another modality's verifier, e.g. a voice-verification engine
+///
+/// Configured with [`FaceAuthBuilder::secondary_biometric`](crate::FaceAuthBuilder::secondary_biometric)
+/// and asked after every face match. The attempt's [`AuthContext`] tells it
+/// which sample to score, e.g. the call the voice was recorded on.
+pub trait SecondaryBiometric: fmt::Debug + Send + Sync {
+    /// Name shown in results and denial reasons, e.g. "voice"
+    fn name(&self) -> &str;
+
+    /// How well this attempt's sample matches `user_id` (0.0-1.0), `None`
+    /// when there is no sample for it
+    fn score(&self, user_id: &str, context: &AuthContext) -> Result<Option<f64>>;
+}
+
+/// Scores of one matched attempt, each 0.0-1.0
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModalityScores {
+    /// The [`SecondaryBiometric::name`]
+    pub modality: String,
+    /// The face distance as a score, see [`face_score`]
+    pub face: f64,
+    /// `None` when the other modality had no sample or failed
+    pub secondary: Option<f64>,
+}
+
+/// The face distance as a score: 1.0 for identical faces, 0.0 at the
+/// tolerance
+pub fn face_score(distance: f64, tolerance: f64) -> f64 {
+    (1.0 - distance / tolerance).clamp(0.0, 1.0)
+}
+
+/// How much each modality counts, and the combined score a grant needs
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModalityWeights {
+    pub face: f64,
+    pub secondary: f64,
+    /// Weighted mean of the scores needed to grant (0.0-1.0)
+    pub threshold: f64,
+}
+
+impl Default for ModalityWeights {
+    fn default() -> Self {
+        Self { face: 0.5, secondary: 0.5, threshold: 0.5 }
+    }
+}
+
+impl ModalityWeights {
+    pub fn with_face(mut self, weight: f64) -> Self {
+        self.face = weight;
+        self
+    }
+
+    pub fn with_secondary(mut self, weight: f64) -> Self {
+        self.secondary = weight;
+        self
+    }
+
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Weighted mean of the two scores
+    pub fn fuse(&self, face: f64, secondary: f64) -> f64 {
+        let total = self.face + self.secondary;
+        if total <= 0.0 {
+            return 0.0;
+        }
+        (face * self.face + secondary * self.secondary) / total
+    }
+}
+
+/// Grants a face match only when its score fused with the
+/// [`SecondaryBiometric`]'s reaches the threshold
+///
+/// An attempt without a secondary sample asks for a second factor instead.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MultiModalPolicy {
+    pub weights: ModalityWeights,
+}
+
+impl MultiModalPolicy {
+    pub fn new(weights: ModalityWeights) -> Self {
+        Self { weights }
+    }
+}
+
+impl DecisionPolicy for MultiModalPolicy {
+    fn decide(&self, input: &PolicyInput) -> Result<PolicyDecision> {
+        let Some(scores) = &input.modalities else {
+            return Ok(PolicyDecision::Deny { reason: "No secondary biometric is configured".into() });
+        };
+        let Some(secondary) = scores.secondary else {
+            return Ok(PolicyDecision::StepUp { reason: format!("No {} sample", scores.modality) });
+        };
+        let fused = self.weights.fuse(scores.face, secondary);
+        Ok(match fused >= self.weights.threshold {
+            true => PolicyDecision::Accept,
+            false => PolicyDecision::Deny {
+                reason: format!("Face and {} score {:.2}, below {:.2}", scores.modality, fused, self.weights.threshold),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weighted_scores_decide() {
+        assert_eq!(face_score(0.0, 0.6), 1.0);
+        assert!((face_score(0.45, 0.6) - 0.25).abs() < 1e-9);
+        assert_eq!(face_score(0.7, 0.6), 0.0);
+
+        let input = |secondary| PolicyInput {
+            modalities: Some(ModalityScores { modality: "voice".into(), face: 0.25, secondary }),
+            ..PolicyInput::default()
+        };
+        // A strong voice match carries a borderline face unless the face counts more
+        let policy = MultiModalPolicy::default();
+        assert_eq!(policy.decide(&input(Some(0.9))).unwrap(), PolicyDecision::Accept);
+        assert!(matches!(policy.decide(&input(Some(0.5))).unwrap(), PolicyDecision::Deny { .. }));
+        let face_heavy = MultiModalPolicy::new(ModalityWeights::default().with_face(3.0).with_secondary(1.0));
+        assert!(matches!(face_heavy.decide(&input(Some(0.9))).unwrap(), PolicyDecision::Deny { .. }));
+
+        assert!(matches!(policy.decide(&input(None)).unwrap(), PolicyDecision::StepUp { .. }));
+        assert!(matches!(policy.decide(&PolicyInput::default()).unwrap(), PolicyDecision::Deny { .. }));
+    }
+}
@@ -0,0 +1,502 @@
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tract_onnx::prelude::*;
+
+use crate::age::{AgeEstimate, AgeEstimator};
+use crate::denial::DenialReason;
+use crate::face_storage::{self, FaceSample, UserProfile};
+use crate::manipulation::ManipulationDetector;
+use crate::matching::{self, MatchCandidate};
+use crate::sample_id;
+
+/// Memory layout of the model's image input
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputLayout {
+    /// `[1, 3, height, width]`, used by most PyTorch exports
+    #[default]
+    Nchw,
+    /// `[1, height, width, 3]`, used by most TensorFlow exports
+    Nhwc,
+}
+
+/// Description of a custom ONNX embedding model such as ArcFace or MobileFaceNet
+///
+/// ```json
+/// { "name": "arcface", "model_path": "arcface_r100.onnx", "input_size": [112, 112], "embedding_dim": 512 }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncoderConfig {
+    /// Short name recorded in the template version
+    pub name: String,
+    /// Path to the `.onnx` file, relative to the config file when loaded with [`EncoderConfig::load`]
+    pub model_path: PathBuf,
+    /// Width and height of the aligned face crop the model expects
+    pub input_size: [u32; 2],
+    /// Length of the embedding the model outputs
+    pub embedding_dim: usize,
+    /// Subtracted from every 0-255 channel value
+    #[serde(default = "default_mean")]
+    pub mean: f32,
+    /// Channel values are divided by this after subtracting `mean`
+    #[serde(default = "default_std")]
+    pub std: f32,
+    #[serde(default)]
+    pub layout: InputLayout,
+    /// Scale embeddings to unit length, which ArcFace-style models expect
+    #[serde(default = "default_normalize")]
+    pub normalize: bool,
+}
+
+fn default_mean() -> f32 {
+    127.5
+}
+
+fn default_std() -> f32 {
+    127.5
+}
+
+fn default_normalize() -> bool {
+    true
+}
+
+impl EncoderConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read encoder config {}", path.display()))?;
+        let mut config: Self = serde_json::from_str(&content)
+            .with_context(|| format!("Invalid encoder config {}", path.display()))?;
+        if config.model_path.is_relative() {
+            if let Some(dir) = path.parent() {
+                config.model_path = dir.join(&config.model_path);
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// An ONNX embedding model run in-process with tract
+pub struct OnnxEncoder {
+    config: EncoderConfig,
+    template_version: String,
+    model: Arc<TypedRunnableModel>,
+}
+
+impl std::fmt::Debug for OnnxEncoder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OnnxEncoder")
+            .field("config", &self.config)
+            .field("template_version", &self.template_version)
+            .finish_non_exhaustive()
+    }
+}
+
+impl OnnxEncoder {
+    /// Load and optimize the model, checking that it outputs `embedding_dim` values
+    pub fn load(config: EncoderConfig) -> Result<Self> {
+        let [width, height] = config.input_size;
+        if width == 0 || height == 0 || config.embedding_dim == 0 {
+            bail!("Encoder '{}' needs a non-zero input size and embedding dimension", config.name);
+        }
+        let bytes = fs::read(&config.model_path)
+            .with_context(|| format!("Failed to read model {}", config.model_path.display()))?;
+        let digest = Sha256::digest(&bytes);
+        let hash: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+        let template_version = format!("onnx-{}-sha256:{}", config.name, hash);
+
+        let shape = match config.layout {
+            InputLayout::Nchw => [1, 3, height as usize, width as usize],
+            InputLayout::Nhwc => [1, height as usize, width as usize, 3],
+        };
+        let model = tract_onnx::onnx()
+            .model_for_read(&mut bytes.as_slice())
+            .and_then(|model| model.with_input_fact(0, f32::fact(shape).into()))
+            .and_then(|model| model.into_optimized())
+            .and_then(|model| model.into_runnable())
+            .map_err(|e| anyhow!("Failed to load model {}: {}", config.model_path.display(), e))?;
+
+        let encoder = Self { config, template_version, model };
+        let produced = encoder.run(Tensor::zero::<f32>(&shape).map_err(|e| anyhow!("{}", e))?)?.len();
+        if produced != encoder.config.embedding_dim {
+            bail!(
+                "Model {} outputs {} values but the config declares embedding_dim {}",
+                encoder.config.model_path.display(), produced, encoder.config.embedding_dim
+            );
+        }
+        Ok(encoder)
+    }
+
+    pub fn config(&self) -> &EncoderConfig {
+        &self.config
+    }
+
+    /// `onnx-<name>-sha256:<model hash>`, stored on every sample this encoder produces
+    pub fn template_version(&self) -> &str {
+        &self.template_version
+    }
+
+    /// Embedding of an image that is already an aligned face crop
+    pub fn encode_image(&self, path: &Path) -> Result<Vec<f64>> {
+        let [width, height] = self.config.input_size;
+        let image = image::open(path)
+            .with_context(|| format!("Failed to open image {}", path.display()))?
+            .to_rgb8();
+        let image = image::imageops::resize(&image, width, height, image::imageops::FilterType::Triangle);
+
+        let (w, h) = (width as usize, height as usize);
+        let value = |x: usize, y: usize, c: usize| {
+            (image.get_pixel(x as u32, y as u32)[c] as f32 - self.config.mean) / self.config.std
+        };
+        let input: Tensor = match self.config.layout {
+            InputLayout::Nchw => tract_ndarray::Array4::from_shape_fn((1, 3, h, w), |(_, c, y, x)| value(x, y, c)).into(),
+            InputLayout::Nhwc => tract_ndarray::Array4::from_shape_fn((1, h, w, 3), |(_, y, x, c)| value(x, y, c)).into(),
+        };
+        self.run(input)
+    }
+
+    fn run(&self, input: Tensor) -> Result<Vec<f64>> {
+        let outputs = self.model.run(tvec!(input.into()))
+            .map_err(|e| anyhow!("Inference failed: {}", e))?;
+        let output = outputs.first().ok_or_else(|| anyhow!("Model produced no output"))?;
+        let mut embedding: Vec<f64> = output.to_plain_array_view::<f32>()
+            .map_err(|e| anyhow!("Unexpected model output: {}", e))?
+            .iter()
+            .map(|&v| f64::from(v))
+            .collect();
+        if self.config.normalize {
+            let norm = embedding.iter().map(|v| v * v).sum::<f64>().sqrt();
+            if norm > 0.0 {
+                embedding.iter_mut().for_each(|v| *v /= norm);
+            }
+        }
+        Ok(embedding)
+    }
+}
+
+/// Enrollment and matching with a custom ONNX encoder instead of the Python script
+///
+/// Templates are tagged with the encoder's [`OnnxEncoder::template_version`];
+/// embeddings from different models are not comparable, so templates of any
+/// other model are refused rather than silently mismatched.
+#[derive(Debug)]
+pub struct NativeBackend {
+    encoder: OnnxEncoder,
+}
+
+impl NativeBackend {
+    pub fn new(encoder: OnnxEncoder) -> Self {
+        Self { encoder }
+    }
+
+    /// Backend for the encoder described by a JSON [`EncoderConfig`] file
+    pub fn from_config(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(OnnxEncoder::load(EncoderConfig::load(path)?)?))
+    }
+
+    pub fn encoder(&self) -> &OnnxEncoder {
+        &self.encoder
+    }
+
+    pub fn template_version(&self) -> &str {
+        self.encoder.template_version()
+    }
+
+    /// Profile of `username` with one sample per face crop
+    pub fn enroll(&self, username: &str, images: &[PathBuf]) -> Result<UserProfile> {
+        face_storage::validate_username(username)?;
+        if images.is_empty() {
+            bail!("No images to enroll '{}' from", username);
+        }
+        let now = Local::now().format("%Y-%m-%dT%H:%M:%S%.6f").to_string();
+        let face_encodings = images.iter()
+            .map(|image| Ok(FaceSample {
+                encoding: self.encoder.encode_image(image)?,
+                timestamp: now.clone(),
+                image_path: Some(image.to_string_lossy().into_owned()),
+                sample_id: sample_id::generate_now(),
+                quality: None,
+                template_version: Some(self.template_version().to_string()),
+                quarantine: None,
+            }))
+            .collect::<Result<Vec<_>>>()?;
+        let profile = UserProfile {
+            user_id: username.to_string(),
+            sample_count: face_encodings.len(),
+            face_encodings,
+            enrollment_date: Some(now),
+            metadata: Default::default(),
+            extra: Default::default(),
+        };
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    /// Check that every template of `profile` was produced by this encoder
+    pub fn validate_templates(&self, profile: &UserProfile) -> Result<()> {
+        for sample in &profile.face_encodings {
+            let version = sample.template_version.as_deref();
+            if version != Some(self.template_version()) {
+                bail!(
+                    "Sample '{}' of user '{}' was encoded by {}, not {}; re-enroll the user with this model",
+                    sample.sample_id, profile.user_id, version.unwrap_or("the dlib model"), self.template_version()
+                );
+            }
+            if sample.encoding.len() != self.encoder.config.embedding_dim {
+                bail!(
+                    "Sample '{}' of user '{}' has {} dimensions, the model outputs {}",
+                    sample.sample_id, profile.user_id, sample.encoding.len(), self.encoder.config.embedding_dim
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Closest enrolled user to the face crop in `image`, or
+    /// [`DenialReason::NoUsersEnrolled`] if `source_dir` holds no credentials
+    ///
+    /// Users enrolled with another model are skipped with a warning; it is
+    /// an error if no user in `source_dir` has templates of this model.
+    pub fn authenticate_image(&self, tolerance: f64, source_dir: &Path, image: &Path) -> Result<std::result::Result<MatchCandidate, DenialReason>> {
+        let profiles = face_storage::load_credentials_dir(source_dir)?;
+        if profiles.is_empty() {
+            return Ok(Err(DenialReason::NoUsersEnrolled));
+        }
+        let compatible: Vec<&UserProfile> = profiles.iter()
+            .filter(|profile| match self.validate_templates(profile) {
+                Ok(()) => true,
+                Err(e) => {
+                    tracing::warn!("Skipping '{}': {:#}", profile.user_id, e);
+                    false
+                }
+            })
+            .collect();
+        if compatible.is_empty() {
+            bail!("No user in {} is enrolled with {}", source_dir.display(), self.template_version());
+        }
+        let probe = self.encoder.encode_image(image)?;
+        // Users whose samples are all quarantined can't be matched either
+        Ok(matching::best_match(compatible, &probe, tolerance).ok_or(DenialReason::NoUsersEnrolled))
+    }
+}
+
+/// Deepfake and GAN-artifact detection with an ONNX classifier
+///
+/// The model is described by an [`EncoderConfig`] whose `embedding_dim` is
+/// 1, a manipulation probability, or 2, `[genuine, manipulated]` logits that
+/// are turned into a probability with softmax.
+#[derive(Debug)]
+pub struct OnnxManipulationDetector {
+    model: OnnxEncoder,
+}
+
+impl OnnxManipulationDetector {
+    pub fn load(mut config: EncoderConfig) -> Result<Self> {
+        if !matches!(config.embedding_dim, 1 | 2) {
+            bail!("Manipulation model '{}' must output 1 or 2 values, not {}", config.name, config.embedding_dim);
+        }
+        config.normalize = false;
+        Ok(Self { model: OnnxEncoder::load(config)? })
+    }
+
+    /// Detector for the model described by a JSON [`EncoderConfig`] file
+    pub fn from_config(path: impl AsRef<Path>) -> Result<Self> {
+        Self::load(EncoderConfig::load(path)?)
+    }
+}
+
+impl ManipulationDetector for OnnxManipulationDetector {
+    fn score(&self, image: &Path) -> Result<f64> {
+        let score = match self.model.encode_image(image)?.as_slice() {
+            &[probability] => probability,
+            &[genuine, manipulated] => 1.0 / (1.0 + (genuine - manipulated).exp()),
+            other => bail!("Manipulation model produced {} values", other.len()),
+        };
+        Ok(score.clamp(0.0, 1.0))
+    }
+}
+
+/// How far the outputs of a softmax may sum from 1 in single precision
+const PROBABILITY_SUM_TOLERANCE: f64 = 1e-3;
+
+/// [`AgeEstimator`] running an ONNX model that outputs probabilities over
+/// ages 0, 1, 2, ... years, e.g. a DEX-style network with 101 outputs
+#[derive(Debug)]
+pub struct OnnxAgeEstimator {
+    model: OnnxEncoder,
+    confidence: f64,
+}
+
+impl OnnxAgeEstimator {
+    /// Estimator reporting intervals that hold `confidence` (e.g. 0.9) of
+    /// the model's probability
+    pub fn load(mut config: EncoderConfig, confidence: f64) -> Result<Self> {
+        if config.embedding_dim < 2 {
+            bail!("Age model '{}' must output a probability per year of age", config.name);
+        }
+        if !(0.0..1.0).contains(&confidence) {
+            bail!("Confidence must be between 0 and 1, not {}", confidence);
+        }
+        config.normalize = false;
+        Ok(Self { model: OnnxEncoder::load(config)?, confidence })
+    }
+
+    /// Estimator for the model described by a JSON [`EncoderConfig`] file
+    pub fn from_config(path: impl AsRef<Path>, confidence: f64) -> Result<Self> {
+        Self::load(EncoderConfig::load(path)?, confidence)
+    }
+}
+
+impl AgeEstimator for OnnxAgeEstimator {
+    fn estimate(&self, image: &Path) -> Result<AgeEstimate> {
+        let outputs = self.model.encode_image(image)?;
+        // Models end in logits or in a softmax; either way normalize to probabilities.
+        // Outputs that merely lie in 0..=1 may still be logits, a softmax also sums to 1
+        let is_distribution = outputs.iter().all(|&p| (0.0..=1.0).contains(&p))
+            && (outputs.iter().sum::<f64>() - 1.0).abs() < PROBABILITY_SUM_TOLERANCE;
+        let probabilities: Vec<f64> = if is_distribution {
+            outputs
+        } else {
+            let max = outputs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            outputs.iter().map(|logit| (logit - max).exp()).collect()
+        };
+        AgeEstimate::from_distribution(&probabilities, self.confidence)
+            .ok_or_else(|| anyhow!("Age model produced no usable probabilities"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn varint(mut value: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        while value >= 0x80 {
+            out.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+        out
+    }
+
+    /// Protobuf length-delimited field
+    fn field(number: u64, payload: &[u8]) -> Vec<u8> {
+        [varint(number << 3 | 2), varint(payload.len() as u64), payload.to_vec()].concat()
+    }
+
+    fn int_field(number: u64, value: u64) -> Vec<u8> {
+        [varint(number << 3), varint(value)].concat()
+    }
+
+    /// ONNX model averaging each channel of the image: a 3-dimensional "embedding"
+    fn channel_mean_model() -> Vec<u8> {
+        let node = |input: &str, output: &str, op: &str| {
+            field(1, &[field(1, input.as_bytes()), field(2, output.as_bytes()), field(4, op.as_bytes())].concat())
+        };
+        // ValueInfoProto { name, type: TypeProto { tensor_type: { elem_type: FLOAT } } }
+        let float_value = |number: u64, name: &str| {
+            field(number, &[field(1, name.as_bytes()), field(2, &field(1, &int_field(1, 1)))].concat())
+        };
+        let graph = [
+            node("x", "pooled", "GlobalAveragePool"),
+            node("pooled", "y", "Flatten"),
+            field(2, b"channel_mean"),
+            float_value(11, "x"),
+            float_value(12, "y"),
+        ].concat();
+        [int_field(1, 8), field(7, &graph), field(8, &int_field(2, 13))].concat()
+    }
+
+    /// ONNX model averaging the whole image into a single value
+    fn image_mean_model() -> Vec<u8> {
+        let node = field(1, &[field(1, b"x"), field(2, b"y"), field(4, b"ReduceMean")].concat());
+        let float_value = |number: u64, name: &str| {
+            field(number, &[field(1, name.as_bytes()), field(2, &field(1, &int_field(1, 1)))].concat())
+        };
+        let graph = [node, field(2, b"image_mean"), float_value(11, "x"), float_value(12, "y")].concat();
+        [int_field(1, 8), field(7, &graph), field(8, &int_field(2, 13))].concat()
+    }
+
+    #[test]
+    fn test_manipulation_score_is_a_probability() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("mean.onnx"), image_mean_model()).unwrap();
+        let config_path = dir.path().join("detector.json");
+        let config = |dim: usize| format!(r#"{{ "name": "mean", "model_path": "mean.onnx", "input_size": [4, 4], "embedding_dim": {} }}"#, dim);
+        fs::write(&config_path, config(3)).unwrap();
+        assert!(OnnxManipulationDetector::from_config(&config_path).is_err());
+
+        fs::write(&config_path, config(1)).unwrap();
+        let detector = OnnxManipulationDetector::from_config(&config_path).unwrap();
+        let image = |name: &str, value: u8| {
+            let path = dir.path().join(name);
+            image::RgbImage::from_pixel(8, 8, image::Rgb([value; 3])).save(&path).unwrap();
+            path
+        };
+        assert_eq!(detector.score(&image("white.png", 255)).unwrap(), 1.0);
+        assert_eq!(detector.score(&image("black.png", 0)).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_age_is_estimated_from_per_year_probabilities() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("mean.onnx"), channel_mean_model()).unwrap();
+        let config_path = dir.path().join("age.json");
+        let config = |dim: usize| {
+            format!(r#"{{ "name": "mean", "model_path": "mean.onnx", "input_size": [4, 4], "embedding_dim": {}, "mean": 0.0, "std": 255.0 }}"#, dim)
+        };
+        fs::write(&config_path, config(1)).unwrap();
+        assert!(OnnxAgeEstimator::from_config(&config_path, 0.9).is_err());
+
+        // Three channels: probabilities of being 0, 1 and 2 years old
+        fs::write(&config_path, config(3)).unwrap();
+        let estimator = OnnxAgeEstimator::from_config(&config_path, 0.9).unwrap();
+        let path = dir.path().join("blue.png");
+        image::RgbImage::from_pixel(8, 8, image::Rgb([0, 0, 255])).save(&path).unwrap();
+        let estimate = estimator.estimate(&path).unwrap();
+        assert_eq!((estimate.years, estimate.low, estimate.high, estimate.confidence), (2.0, 2.0, 2.0, 0.9));
+
+        // Outputs within 0..=1 that don't sum to 1 are logits
+        let path = dir.path().join("yellow.png");
+        image::RgbImage::from_pixel(8, 8, image::Rgb([255, 255, 0])).save(&path).unwrap();
+        let e = std::f64::consts::E;
+        let expected = (e + 2.0) / (2.0 * e + 1.0);
+        assert!((estimator.estimate(&path).unwrap().years - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_custom_model_is_validated_and_versioned() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("mean.onnx"), channel_mean_model()).unwrap();
+        let config_path = dir.path().join("mean.json");
+        let config = |dim: usize| format!(r#"{{ "name": "mean", "model_path": "mean.onnx", "input_size": [4, 4], "embedding_dim": {} }}"#, dim);
+        fs::write(&config_path, config(4)).unwrap();
+        let err = NativeBackend::from_config(&config_path).unwrap_err();
+        assert!(err.to_string().contains("outputs 3 values"), "{}", err);
+
+        fs::write(&config_path, config(3)).unwrap();
+        let backend = NativeBackend::from_config(&config_path).unwrap();
+        assert!(backend.template_version().starts_with("onnx-mean-sha256:"));
+
+        let crop = dir.path().join("red.png");
+        image::RgbImage::from_pixel(8, 8, image::Rgb([255, 0, 0])).save(&crop).unwrap();
+        let profile = backend.enroll("ann", std::slice::from_ref(&crop)).unwrap();
+        assert_eq!(profile.face_encodings[0].template_version.as_deref(), Some(backend.template_version()));
+        assert_eq!(profile.face_encodings[0].encoding.len(), 3);
+        backend.validate_templates(&profile).unwrap();
+
+        let source = dir.path().join("source");
+        profile.save(face_storage::credential_path(&source, "ann")).unwrap();
+        let candidate = backend.authenticate_image(0.1, &source, &crop).unwrap().unwrap();
+        assert!(candidate.is_match && candidate.user_id == "ann");
+
+        let mut dlib = profile.clone();
+        dlib.face_encodings.iter_mut().for_each(|s| s.template_version = None);
+        let err = backend.validate_templates(&dlib).unwrap_err();
+        assert!(err.to_string().contains("re-enroll"), "{}", err);
+    }
+}
@@ -0,0 +1,434 @@
+//! Credentials on NFC tags
+//!
+//! A kiosk writes a user's face templates to an NFC Forum Type 2 tag
+//! (NTAG21x and the like) through a PC/SC reader, and another kiosk reads them
+//! back and imports them, for front desks that hand out badges instead of
+//! enrolling at every door.
+//!
+//! Tags are small, so a tag holds a compact binary [`TagCredential`]: the
+//! username, a serial, issue and expiry times and up to [`MAX_SAMPLES`]
+//! encodings quantized to a byte per dimension. It is encrypted and
+//! authenticated with ChaCha20-Poly1305 under a provisioning key shared by the
+//! kiosks, so a tag written elsewhere or edited is refused and a lost badge
+//! gives nothing away. Kiosks refuse expired tags and tags whose serial was
+//! revoked. On the tag it is one NDEF record of type [`MIME_TYPE`], which
+//! phones and other readers show as an opaque file.
+//!
+//! See [`FaceAuth::write_nfc_tag`](crate::FaceAuth::write_nfc_tag) and
+//! [`FaceAuth::read_nfc_tag`](crate::FaceAuth::read_nfc_tag).
+
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Utc};
+use pcsc::{Card, Protocols, Scope, ShareMode};
+use std::time::Duration;
+
+use crate::crypto::{self, KEY_LEN};
+
+/// MIME type of the NDEF record holding a credential
+pub const MIME_TYPE: &str = "application/vnd.face-auth.credential";
+
+/// User memory of the common tags, in bytes
+pub const NTAG213_CAPACITY: usize = 144;
+pub const NTAG215_CAPACITY: usize = 504;
+pub const NTAG216_CAPACITY: usize = 888;
+
+/// Samples a tag holds at most; five 128-dimension samples fit an NTAG216
+pub const MAX_SAMPLES: usize = 5;
+
+/// How long a written tag is accepted by default
+pub const DEFAULT_TAG_LIFETIME: Duration = Duration::from_secs(30 * 24 * 3600);
+
+/// What [`FaceAuth::write_nfc_tag`](crate::FaceAuth::write_nfc_tag) wrote
+#[derive(Debug, Clone, PartialEq)]
+pub struct NfcTag {
+    /// The tag's data area from its first page
+    pub data: Vec<u8>,
+    pub serial: String,
+    pub expires_at: DateTime<Utc>,
+    /// Samples that fit on the tag, most recent first
+    pub samples: usize,
+}
+
+/// Authenticated with the credential, so a tag only opens as a credential of
+/// this format
+const DOMAIN: &[u8] = b"face_auth.nfc.credential.v2";
+
+const FORMAT_VERSION: u8 = 2;
+const SERIAL_LEN: usize = 8;
+
+/// First page of a Type 2 tag's data area, after the UID, lock bytes and
+/// capability container
+const FIRST_DATA_PAGE: usize = 4;
+const PAGE_LEN: usize = 4;
+
+const NDEF_TLV: u8 = 0x03;
+const TERMINATOR_TLV: u8 = 0xFE;
+const TNF_MIME: u8 = 0x02;
+
+/// What a tag carries
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagCredential {
+    pub user_id: String,
+    /// Random hex id the tag can be revoked by, see
+    /// [`FaceAuth::revoke_nfc_tag`](crate::FaceAuth::revoke_nfc_tag)
+    pub serial: String,
+    pub issued_at: DateTime<Utc>,
+    /// Kiosks refuse the tag from then on
+    pub expires_at: DateTime<Utc>,
+    pub samples: Vec<TagSample>,
+}
+
+/// One face template on a tag
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagSample {
+    /// Read back within half a quantization step of what was written
+    pub encoding: Vec<f64>,
+    /// Model that produced the encoding, see
+    /// [`FaceSample::template_version`](crate::face_storage::FaceSample::template_version)
+    pub template_version: Option<String>,
+}
+
+impl TagCredential {
+    /// The credential in its binary form, each encoding as a scale and one
+    /// signed byte per dimension
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let serial = parse_serial(&self.serial)?;
+        let mut bytes = vec![FORMAT_VERSION];
+        bytes.extend_from_slice(&serial);
+        bytes.extend_from_slice(&self.issued_at.timestamp().to_be_bytes());
+        bytes.extend_from_slice(&self.expires_at.timestamp().to_be_bytes());
+        push_short_str(&mut bytes, &self.user_id)?;
+        bytes.push(u8::try_from(self.samples.len()).map_err(|_| anyhow!("Too many samples for a tag"))?);
+        for sample in &self.samples {
+            push_short_str(&mut bytes, sample.template_version.as_deref().unwrap_or_default())?;
+            let dimensions = u16::try_from(sample.encoding.len()).map_err(|_| anyhow!("The encoding is too long for a tag"))?;
+            bytes.extend_from_slice(&dimensions.to_be_bytes());
+            let largest = sample.encoding.iter().fold(0.0f64, |largest, v| largest.max(v.abs()));
+            if !largest.is_finite() {
+                bail!("The encoding is not finite");
+            }
+            let scale = (largest / i8::MAX as f64) as f32;
+            bytes.extend_from_slice(&scale.to_be_bytes());
+            bytes.extend(sample.encoding.iter().map(|v| if scale > 0.0 {
+                (v / scale as f64).round().clamp(-127.0, 127.0) as i8 as u8
+            } else {
+                0
+            }));
+        }
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Bytes(bytes);
+        let version = reader.byte()?;
+        if version != FORMAT_VERSION {
+            bail!("Unsupported tag format {}", version);
+        }
+        let serial = reader.take(SERIAL_LEN)?.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let timestamp = |seconds: i64| DateTime::from_timestamp(seconds, 0).ok_or_else(|| anyhow!("Invalid time on the tag"));
+        let issued_at = timestamp(i64::from_be_bytes(reader.array()?))?;
+        let expires_at = timestamp(i64::from_be_bytes(reader.array()?))?;
+        let user_id = reader.short_str()?;
+        let count = reader.byte()?;
+        let mut samples = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let template_version = Some(reader.short_str()?).filter(|version| !version.is_empty());
+            let dimensions = u16::from_be_bytes(reader.array()?) as usize;
+            let scale = f32::from_be_bytes(reader.array()?) as f64;
+            let encoding = reader.take(dimensions)?.iter().map(|&q| q as i8 as f64 * scale).collect();
+            samples.push(TagSample { encoding, template_version });
+        }
+        if !reader.0.is_empty() {
+            bail!("Unexpected data after the credential");
+        }
+        Ok(Self { user_id, serial, issued_at, expires_at, samples })
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        now >= self.expires_at
+    }
+}
+
+/// Encrypt a credential with `key`
+pub fn seal(credential: &TagCredential, key: &[u8; KEY_LEN]) -> Result<Vec<u8>> {
+    crypto::seal_bound(key, &credential.to_bytes()?, DOMAIN)
+}
+
+/// The credential in [`seal`]ed bytes, if they were sealed with `key`
+pub fn unseal(sealed: &[u8], key: &[u8; KEY_LEN]) -> Result<TagCredential> {
+    let bytes = crypto::open_bound(key, sealed, DOMAIN)
+        .map_err(|_| anyhow!("The credential was not written with this provisioning key"))?;
+    TagCredential::from_bytes(&bytes)
+}
+
+/// The NDEF message of `credential` sealed with `key`, with as many of its
+/// samples as fit `capacity` bytes, and the number of samples kept
+///
+/// Fails if not even one sample fits.
+pub fn tag_data(credential: &TagCredential, key: &[u8; KEY_LEN], capacity: usize) -> Result<(Vec<u8>, usize)> {
+    let mut credential = credential.clone();
+    credential.samples.truncate(MAX_SAMPLES);
+    while !credential.samples.is_empty() {
+        let data = ndef_message(&seal(&credential, key)?);
+        if data.len() <= capacity {
+            return Ok((data, credential.samples.len()));
+        }
+        credential.samples.pop();
+    }
+    bail!("No sample of '{}' fits on a tag of {} bytes", credential.user_id, capacity)
+}
+
+fn parse_serial(serial: &str) -> Result<[u8; SERIAL_LEN]> {
+    let mut bytes = [0u8; SERIAL_LEN];
+    if serial.len() != SERIAL_LEN * 2 || !serial.is_ascii() {
+        bail!("Invalid tag serial '{}'", serial);
+    }
+    for (byte, pair) in bytes.iter_mut().zip(serial.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair)?, 16).with_context(|| format!("Invalid tag serial '{}'", serial))?;
+    }
+    Ok(bytes)
+}
+
+fn push_short_str(bytes: &mut Vec<u8>, s: &str) -> Result<()> {
+    bytes.push(u8::try_from(s.len()).map_err(|_| anyhow!("'{}' is too long for a tag", s))?);
+    bytes.extend_from_slice(s.as_bytes());
+    Ok(())
+}
+
+/// `payload` as a single-record NDEF message in a TLV, the way a Type 2 tag
+/// stores it from its first data page
+pub fn ndef_message(payload: &[u8]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(payload.len() + MIME_TYPE.len() + 6);
+    // Message begin and end, short record when the length fits a byte
+    let short = payload.len() < 256;
+    record.push(0x80 | 0x40 | if short { 0x10 } else { 0 } | TNF_MIME);
+    record.push(MIME_TYPE.len() as u8);
+    if short {
+        record.push(payload.len() as u8);
+    } else {
+        record.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    }
+    record.extend_from_slice(MIME_TYPE.as_bytes());
+    record.extend_from_slice(payload);
+
+    let mut message = vec![NDEF_TLV];
+    if record.len() < 0xFF {
+        message.push(record.len() as u8);
+    } else {
+        message.push(0xFF);
+        message.extend_from_slice(&(record.len() as u16).to_be_bytes());
+    }
+    message.extend_from_slice(&record);
+    message.push(TERMINATOR_TLV);
+    message
+}
+
+/// Payload of the [`MIME_TYPE`] record in a tag's data area
+pub fn parse_ndef(data: &[u8]) -> Result<Vec<u8>> {
+    let mut reader = Bytes(data);
+    loop {
+        match reader.byte()? {
+            0x00 => continue,
+            TERMINATOR_TLV => bail!("The tag holds no face_auth credential"),
+            tlv => {
+                let len = match reader.byte()? {
+                    0xFF => u16::from_be_bytes(reader.array()?) as usize,
+                    len => len as usize,
+                };
+                let value = reader.take(len)?;
+                if tlv == NDEF_TLV {
+                    if let Some(payload) = credential_record(value)? {
+                        return Ok(payload);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn credential_record(message: &[u8]) -> Result<Option<Vec<u8>>> {
+    let mut reader = Bytes(message);
+    while !reader.0.is_empty() {
+        let header = reader.byte()?;
+        let type_len = reader.byte()? as usize;
+        let payload_len = match header & 0x10 {
+            0 => u32::from_be_bytes(reader.array()?) as usize,
+            _ => reader.byte()? as usize,
+        };
+        let id_len = match header & 0x08 {
+            0 => 0,
+            _ => reader.byte()? as usize,
+        };
+        let record_type = reader.take(type_len)?;
+        reader.take(id_len)?;
+        let payload = reader.take(payload_len)?;
+        if header & 0x07 == TNF_MIME && record_type == MIME_TYPE.as_bytes() {
+            return Ok(Some(payload.to_vec()));
+        }
+    }
+    Ok(None)
+}
+
+struct Bytes<'a>(&'a [u8]);
+
+impl<'a> Bytes<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let (taken, rest) = self.0.split_at_checked(len).ok_or_else(|| anyhow!("The data on the tag is truncated"))?;
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("took N bytes"))
+    }
+
+    /// A string prefixed with its length in a byte
+    fn short_str(&mut self) -> Result<String> {
+        let len = self.byte()? as usize;
+        Ok(std::str::from_utf8(self.take(len)?)?.to_string())
+    }
+}
+
+/// A tag on a PC/SC reader, such as an ACR122U
+pub struct NfcReader {
+    card: Card,
+}
+
+impl NfcReader {
+    /// Connect to the tag on the reader named `reader`, or on the first
+    /// reader that has one
+    pub fn connect(reader: Option<&str>) -> Result<Self> {
+        let context = pcsc::Context::establish(Scope::User).context("PC/SC is not available")?;
+        let mut names = vec![0; context.list_readers_len()?];
+        for name in context.list_readers(&mut names)? {
+            if reader.is_some_and(|reader| name.to_string_lossy() != reader) {
+                continue;
+            }
+            match context.connect(name, ShareMode::Shared, Protocols::ANY) {
+                Ok(card) => return Ok(Self { card }),
+                Err(pcsc::Error::NoSmartcard | pcsc::Error::RemovedCard) => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Err(anyhow!("No NFC tag on {}", reader.unwrap_or("any reader")))
+    }
+
+    fn transmit(&self, apdu: &[u8]) -> Result<Vec<u8>> {
+        let mut buffer = [0; pcsc::MAX_BUFFER_SIZE];
+        let response = self.card.transmit(apdu, &mut buffer)?;
+        match response.split_last_chunk::<2>() {
+            Some((data, [0x90, 0x00])) => Ok(data.to_vec()),
+            Some((_, [sw1, sw2])) => Err(anyhow!("The tag refused the command ({:02X}{:02X})", sw1, sw2)),
+            None => Err(anyhow!("The reader sent a short response")),
+        }
+    }
+
+    /// Four pages starting at `page`
+    fn read_pages(&self, page: usize) -> Result<Vec<u8>> {
+        self.transmit(&[0xFF, 0xB0, 0x00, u8::try_from(page)?, 16])
+    }
+
+    /// Size of the tag's data area, from its capability container
+    pub fn capacity(&self) -> Result<usize> {
+        let container = self.read_pages(3)?;
+        if container.first() != Some(&0xE1) {
+            bail!("The tag is not NDEF formatted");
+        }
+        Ok(container[2] as usize * 8)
+    }
+
+    /// Write `data` from the first data page, e.g. an [`ndef_message`]
+    pub fn write(&self, data: &[u8]) -> Result<()> {
+        let capacity = self.capacity()?;
+        if data.len() > capacity {
+            bail!("{} bytes don't fit on the tag, which holds {}", data.len(), capacity);
+        }
+        for (i, chunk) in data.chunks(PAGE_LEN).enumerate() {
+            let mut apdu = vec![0xFF, 0xD6, 0x00, u8::try_from(FIRST_DATA_PAGE + i)?, PAGE_LEN as u8];
+            apdu.extend_from_slice(chunk);
+            apdu.resize(5 + PAGE_LEN, 0);
+            self.transmit(&apdu)?;
+        }
+        Ok(())
+    }
+
+    /// The whole data area
+    pub fn read(&self) -> Result<Vec<u8>> {
+        let capacity = self.capacity()?;
+        let mut data = Vec::with_capacity(capacity + 16);
+        while data.len() < capacity {
+            data.extend(self.read_pages(FIRST_DATA_PAGE + data.len() / PAGE_LEN)?);
+        }
+        data.truncate(capacity);
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credential(samples: usize) -> TagCredential {
+        let issued_at = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        TagCredential {
+            user_id: "ann".to_string(),
+            serial: "0123456789abcdef".to_string(),
+            issued_at,
+            expires_at: issued_at + chrono::Duration::days(30),
+            samples: (0..samples).map(|i| TagSample {
+                encoding: (0..128).map(|d| ((i * 128 + d) as f64 * 0.37).sin() * 0.3).collect(),
+                template_version: None,
+            }).collect(),
+        }
+    }
+
+    #[test]
+    fn test_sealed_credential_round_trips_through_ndef() {
+        let key = [7u8; KEY_LEN];
+        let written = credential(3);
+        let sealed = seal(&written, &key).unwrap();
+
+        // Padded like a tag's data area after the message
+        let mut data = ndef_message(&sealed);
+        data.resize(data.len() + 40, 0);
+        let payload = parse_ndef(&data).unwrap();
+        let read = unseal(&payload, &key).unwrap();
+        assert_eq!((&read.user_id, &read.serial, read.issued_at, read.expires_at), (&written.user_id, &written.serial, written.issued_at, written.expires_at));
+        for (read, written) in read.samples.iter().zip(&written.samples) {
+            let error = read.encoding.iter().zip(&written.encoding).fold(0.0f64, |error, (a, b)| error.max((a - b).abs()));
+            assert!(error <= 0.3 / 127.0 / 2.0 + 1e-6, "quantization error {}", error);
+        }
+        assert!(read.is_expired(written.expires_at) && !read.is_expired(written.issued_at));
+        assert!(unseal(&payload, &[8u8; KEY_LEN]).is_err());
+
+        let mut edited = payload.clone();
+        *edited.last_mut().unwrap() ^= 1;
+        assert!(unseal(&edited, &key).is_err());
+
+        // Long records and three-byte TLV lengths
+        let long = vec![1u8; 600];
+        assert_eq!(parse_ndef(&ndef_message(&long)).unwrap(), long);
+        assert!(parse_ndef(&[NDEF_TLV, 0, TERMINATOR_TLV]).is_err());
+        assert!(parse_ndef(&ndef_message(&long)[..100]).is_err());
+    }
+
+    #[test]
+    fn test_credentials_fit_real_tags() {
+        let key = [7u8; KEY_LEN];
+        let (data, samples) = tag_data(&credential(8), &key, NTAG216_CAPACITY).unwrap();
+        assert_eq!(samples, MAX_SAMPLES);
+        assert!(data.len() <= NTAG216_CAPACITY, "{} bytes", data.len());
+        assert_eq!(unseal(&parse_ndef(&data).unwrap(), &key).unwrap().samples.len(), MAX_SAMPLES);
+
+        let (data, samples) = tag_data(&credential(8), &key, NTAG215_CAPACITY).unwrap();
+        assert_eq!(samples, 2);
+        assert!(data.len() <= NTAG215_CAPACITY);
+
+        assert!(tag_data(&credential(8), &key, NTAG213_CAPACITY).is_err());
+        assert!(tag_data(&credential(0), &key, NTAG216_CAPACITY).is_err());
+    }
+}
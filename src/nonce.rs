@@ -0,0 +1,197 @@
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use crate::clock::{Clock, SystemClock};
+use crate::random::{OsRandom, RandomSource};
+
+/// Outstanding nonces a store holds at most, so clients can't exhaust memory
+/// by requesting nonces they never use
+pub const MAX_OUTSTANDING: usize = 10_000;
+
+/// Nonces one client may be issued per minute by default
+pub const DEFAULT_CLIENT_LIMIT: u32 = 30;
+
+const RATE_WINDOW: chrono::Duration = chrono::Duration::minutes(1);
+
+/// Why no nonce was issued
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonceRefused {
+    /// The client asked for more than its limit this minute
+    RateLimited,
+    /// [`MAX_OUTSTANDING`] unexpired nonces are outstanding
+    TooManyOutstanding,
+}
+
+impl std::fmt::Display for NonceRefused {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RateLimited => write!(f, "Too many nonce requests, try again later"),
+            Self::TooManyOutstanding => write!(f, "Too many outstanding nonces"),
+        }
+    }
+}
+
+impl std::error::Error for NonceRefused {}
+
+#[derive(Debug)]
+struct Issued {
+    expires: DateTime<Utc>,
+    /// Hash of the request body the nonce may be used in
+    body_hash: String,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    issued: HashMap<String, Issued>,
+    /// Start of each client's current rate window and nonces issued in it
+    clients: HashMap<String, (DateTime<Utc>, u32)>,
+}
+
+/// Single-use challenges a server hands out before accepting a verification
+///
+/// A client asks for a nonce for the hash of the request it is about to send
+/// and embeds the nonce in that request. The nonce is consumed on first use,
+/// is only accepted with the same request body and expires after `ttl`, so a
+/// captured request can't be sent again and a captured nonce can't carry
+/// another face. Each client gets a limited number of nonces per minute.
+#[derive(Debug)]
+pub struct NonceStore {
+    ttl: Duration,
+    client_limit: u32,
+    clock: Arc<dyn Clock>,
+    random: Arc<dyn RandomSource>,
+    state: Mutex<State>,
+}
+
+impl NonceStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            client_limit: DEFAULT_CLIENT_LIMIT,
+            clock: Arc::new(SystemClock),
+            random: Arc::new(OsRandom),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Time issued nonces expire by
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Where nonces are drawn from
+    pub fn with_random(mut self, random: Arc<dyn RandomSource>) -> Self {
+        self.random = random;
+        self
+    }
+
+    /// Issue each client at most `per_minute` nonces a minute
+    pub fn with_client_limit(mut self, per_minute: u32) -> Self {
+        self.client_limit = per_minute;
+        self
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// A fresh random nonce for `client`, e.g. its address, valid for the
+    /// request body hashing to `body_hash` until redeemed or expired
+    pub fn issue(&self, client: &str, body_hash: &str) -> Result<String, NonceRefused> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = self.clock.now();
+        state.clients.retain(|_, (started, _)| now - *started < RATE_WINDOW);
+        let (_, count) = state.clients.entry(client.to_string()).or_insert((now, 0));
+        if *count >= self.client_limit {
+            return Err(NonceRefused::RateLimited);
+        }
+        if state.issued.len() >= MAX_OUTSTANDING {
+            state.issued.retain(|_, issued| issued.expires > now);
+            if state.issued.len() >= MAX_OUTSTANDING {
+                return Err(NonceRefused::TooManyOutstanding);
+            }
+        }
+        if let Some((_, count)) = state.clients.get_mut(client) {
+            *count += 1;
+        }
+
+        let nonce = self.random.hex(16);
+        let ttl = chrono::Duration::from_std(self.ttl).unwrap_or(chrono::Duration::MAX);
+        let expires = now.checked_add_signed(ttl).unwrap_or(DateTime::<Utc>::MAX_UTC);
+        state.issued.insert(nonce.clone(), Issued { expires, body_hash: body_hash.to_string() });
+        Ok(nonce)
+    }
+
+    /// Consume `nonce`; false if it was never issued, already used, expired
+    /// or issued for another body
+    pub fn redeem(&self, nonce: &str, body_hash: &str) -> bool {
+        let issued = self.state.lock().unwrap_or_else(PoisonError::into_inner).issued.remove(nonce);
+        issued.is_some_and(|issued| issued.expires > self.clock.now() && issued.body_hash == body_hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::random::SeededRandom;
+
+    #[test]
+    fn test_nonces_are_single_use_and_expire() {
+        let store = NonceStore::new(Duration::from_secs(60));
+        let nonce = store.issue("door-1", "body").unwrap();
+        assert_eq!(nonce.len(), 32);
+        assert_ne!(store.issue("door-1", "body").unwrap(), nonce);
+        assert!(store.redeem(&nonce, "body"));
+        assert!(!store.redeem(&nonce, "body"));
+        assert!(!store.redeem("0123456789abcdef0123456789abcdef", "body"));
+
+        // Bound to the body it was issued for, and spent by a mismatch
+        let nonce = store.issue("door-1", "body").unwrap();
+        assert!(!store.redeem(&nonce, "other body"));
+        assert!(!store.redeem(&nonce, "body"));
+
+        let expired = NonceStore::new(Duration::ZERO);
+        let nonce = expired.issue("door-1", "body").unwrap();
+        assert!(!expired.redeem(&nonce, "body"));
+    }
+
+    #[test]
+    fn test_nonces_follow_the_plugged_in_clock_and_random_source() {
+        let clock = ManualClock::new(Utc::now());
+        let store = |seed| NonceStore::new(Duration::from_secs(60))
+            .with_clock(Arc::new(clock.clone()))
+            .with_random(Arc::new(SeededRandom::new(seed)));
+        let (first, second) = (store(1), store(1));
+        let nonce = first.issue("door-1", "body").unwrap();
+        assert_eq!(second.issue("door-1", "body").unwrap(), nonce);
+
+        clock.advance(Duration::from_secs(59));
+        assert!(second.redeem(&nonce, "body"));
+        clock.advance(Duration::from_secs(1));
+        assert!(!first.redeem(&nonce, "body"));
+    }
+
+    #[test]
+    fn test_clients_are_rate_limited_and_expired_nonces_make_room() {
+        let clock = ManualClock::new(Utc::now());
+        let store = NonceStore::new(Duration::from_secs(10)).with_clock(Arc::new(clock.clone())).with_client_limit(2);
+        store.issue("door-1", "body").unwrap();
+        store.issue("door-1", "body").unwrap();
+        assert_eq!(store.issue("door-1", "body"), Err(NonceRefused::RateLimited));
+        store.issue("door-2", "body").unwrap();
+        clock.advance(Duration::from_secs(60));
+        store.issue("door-1", "body").unwrap();
+
+        let store = NonceStore::new(Duration::from_secs(10)).with_clock(Arc::new(clock.clone())).with_client_limit(u32::MAX);
+        for _ in 0..MAX_OUTSTANDING {
+            store.issue("door-1", "body").unwrap();
+        }
+        assert_eq!(store.issue("door-1", "body"), Err(NonceRefused::TooManyOutstanding));
+        clock.advance(Duration::from_secs(10));
+        store.issue("door-1", "body").unwrap();
+    }
+}
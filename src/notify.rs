@@ -0,0 +1,581 @@
+use anyhow::Result;
+#[cfg(feature = "notifications")]
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::denial::DenialReason;
+
+#[cfg(feature = "notifications")]
+mod smtp;
+#[cfg(feature = "notifications")]
+pub use smtp::{EmailNotifier, SmtpConfig, SmtpSecurity};
+
+/// Something operators should look at
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Alert {
+    /// At least [`AlertRules::failed_attempts`] denials within the window
+    ///
+    /// Names no user: alerts go to channels such as chat rooms that
+    /// shouldn't learn who is enrolled or who was tried.
+    RepeatedFailures { failures: usize, window_secs: u64 },
+    /// A face matched a watchlist entry
+    WatchlistHit { entry: String },
+    /// The camera stopped delivering frames
+    CameraOffline,
+    /// The disk holding the data directory is filling up
+    StorageNearlyFull { path: PathBuf, used_percent: f64 },
+    /// Expected false accepts of the period exceed the
+    /// [`FarBudget`](crate::far::FarBudget)
+    FalseAcceptBudget {
+        projected: f64,
+        budget: f64,
+        tolerance: f64,
+        recommended_tolerance: Option<f64>,
+    },
+}
+
+impl Alert {
+    /// Alerts with the same key share a cooldown
+    fn key(&self) -> String {
+        match self {
+            Alert::RepeatedFailures { .. } => "repeated_failures".into(),
+            Alert::WatchlistHit { entry } => format!("watchlist_hit:{}", entry),
+            Alert::CameraOffline => "camera_offline".into(),
+            Alert::StorageNearlyFull { .. } => "storage_nearly_full".into(),
+            Alert::FalseAcceptBudget { .. } => "false_accept_budget".into(),
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        match self {
+            Alert::RepeatedFailures { failures, window_secs, .. } => {
+                format!("{} failed authentication attempts within {}s", failures, window_secs)
+            }
+            Alert::WatchlistHit { entry } => format!("Watchlist hit: {}", entry),
+            Alert::CameraOffline => "Camera offline".into(),
+            Alert::StorageNearlyFull { used_percent, .. } => format!("Storage {:.0}% full", used_percent),
+            Alert::FalseAcceptBudget { projected, budget, .. } => {
+                format!("{:.2} false accepts expected this period, budget {:.2}", projected, budget)
+            }
+        }
+    }
+}
+
+/// An [`Alert`] as sent to notifiers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Notification {
+    pub alert: Alert,
+    pub device_id: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Notification {
+    pub fn subject(&self) -> String {
+        format!("[face_auth {}] {}", self.device_id.as_deref().unwrap_or("device"), self.alert.summary())
+    }
+
+    /// Plain-text body for email and chat messages
+    pub fn text(&self) -> String {
+        let mut text = format!("{}\n\nDevice: {}\nTime: {}\n", self.alert.summary(), self.device_id.as_deref().unwrap_or("-"), self.timestamp.to_rfc3339());
+        match &self.alert {
+            Alert::StorageNearlyFull { path, .. } => text.push_str(&format!("Path: {}\n", path.display())),
+            Alert::FalseAcceptBudget { tolerance, recommended_tolerance, .. } => {
+                text.push_str(&format!("Tolerance: {:.3}\n", tolerance));
+                if let Some(recommended) = recommended_tolerance {
+                    text.push_str(&format!("Recommended tolerance: {:.3}\n", recommended));
+                }
+            }
+            _ => {}
+        }
+        text
+    }
+}
+
+/// A channel operational alerts are sent through
+pub trait Notifier: fmt::Debug + Send + Sync {
+    fn name(&self) -> &str;
+
+    fn send(&self, notification: &Notification) -> Result<()>;
+}
+
+/// Notifier calling a closure, e.g. to page through an in-house system
+pub struct CallbackNotifier<F> {
+    name: String,
+    send: F,
+}
+
+impl<F> CallbackNotifier<F>
+where
+    F: Fn(&Notification) -> Result<()> + Send + Sync,
+{
+    pub fn new(name: impl Into<String>, send: F) -> Self {
+        Self { name: name.into(), send }
+    }
+}
+
+impl<F> fmt::Debug for CallbackNotifier<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallbackNotifier").field("name", &self.name).finish_non_exhaustive()
+    }
+}
+
+impl<F> Notifier for CallbackNotifier<F>
+where
+    F: Fn(&Notification) -> Result<()> + Send + Sync,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn send(&self, notification: &Notification) -> Result<()> {
+        (self.send)(notification)
+    }
+}
+
+/// Notifier posting to a Slack incoming webhook
+#[cfg(feature = "notifications")]
+#[derive(Debug)]
+pub struct SlackNotifier {
+    name: String,
+    webhook_url: String,
+    agent: ureq::Agent,
+}
+
+#[cfg(feature = "notifications")]
+impl SlackNotifier {
+    pub fn new(name: impl Into<String>, webhook_url: impl Into<String>) -> Self {
+        let agent = ureq::Agent::config_builder()
+            .http_status_as_error(false)
+            .timeout_global(Some(Duration::from_secs(10)))
+            .build()
+            .into();
+        Self { name: name.into(), webhook_url: webhook_url.into(), agent }
+    }
+}
+
+#[cfg(feature = "notifications")]
+impl Notifier for SlackNotifier {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn send(&self, notification: &Notification) -> Result<()> {
+        let text = format!("*{}*\n{}", notification.subject(), notification.text());
+        let response = self.agent.post(&self.webhook_url)
+            .send_json(serde_json::json!({ "text": text }))
+            .map_err(|e| anyhow!("Slack webhook {} unreachable: {}", self.name, e))?;
+        if !response.status().is_success() {
+            return Err(anyhow!("Slack webhook {} answered {}", self.name, response.status()));
+        }
+        Ok(())
+    }
+}
+
+/// When alerts are raised
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct AlertRules {
+    /// Denials within `failed_window` that raise [`Alert::RepeatedFailures`]
+    pub failed_attempts: usize,
+    pub failed_window: Duration,
+    /// Disk usage raising [`Alert::StorageNearlyFull`]
+    pub storage_warning_percent: f64,
+    pub storage_check_interval: Duration,
+    /// Minimum time between two alerts of the same kind
+    pub cooldown: Duration,
+}
+
+impl Default for AlertRules {
+    fn default() -> Self {
+        Self {
+            failed_attempts: 5,
+            failed_window: Duration::from_secs(5 * 60),
+            storage_warning_percent: 90.0,
+            storage_check_interval: Duration::from_secs(5 * 60),
+            cooldown: Duration::from_secs(15 * 60),
+        }
+    }
+}
+
+impl AlertRules {
+    pub fn with_failed_attempts(mut self, attempts: usize, window: Duration) -> Self {
+        self.failed_attempts = attempts;
+        self.failed_window = window;
+        self
+    }
+
+    pub fn with_storage_warning(mut self, percent: f64, check_interval: Duration) -> Self {
+        self.storage_warning_percent = percent;
+        self.storage_check_interval = check_interval;
+        self
+    }
+
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+struct AlertState {
+    failures: VecDeque<Instant>,
+    camera_ready: Option<bool>,
+    last_sent: HashMap<String, Instant>,
+}
+
+impl AlertState {
+    /// Whether `alert` is out of its cooldown, marking it sent if so
+    fn admit(&mut self, alert: &Alert, cooldown: Duration) -> bool {
+        let now = Instant::now();
+        let key = alert.key();
+        if self.last_sent.get(&key).is_some_and(|sent| now.duration_since(*sent) < cooldown) {
+            return false;
+        }
+        self.last_sent.insert(key, now);
+        true
+    }
+}
+
+/// Watches decisions, the camera and the disk, and sends [`Alert`]s to the
+/// configured notifiers
+///
+/// Notifications are sent from a background thread so a slow mail server
+/// doesn't delay decisions; failures are logged. The thread stops when this
+/// is dropped.
+#[derive(Debug)]
+pub struct Alerts {
+    rules: AlertRules,
+    device_id: Option<String>,
+    state: Arc<Mutex<AlertState>>,
+    sender: Option<mpsc::Sender<Notification>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Alerts {
+    /// Start watching, checking the disk holding `storage_dir`
+    pub fn start(notifiers: Vec<Arc<dyn Notifier>>, rules: AlertRules, device_id: Option<String>, storage_dir: impl Into<PathBuf>) -> Self {
+        let (sender, receiver) = mpsc::channel::<Notification>();
+        let state = Arc::new(Mutex::new(AlertState::default()));
+        let storage_dir = storage_dir.into();
+        let worker = {
+            let (rules, device_id, state) = (rules.clone(), device_id.clone(), state.clone());
+            thread::spawn(move || loop {
+                let notification = match receiver.recv_timeout(rules.storage_check_interval) {
+                    Ok(notification) => notification,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                    Err(RecvTimeoutError::Timeout) => {
+                        let Some(used_percent) = storage_used_percent(&storage_dir).filter(|used| *used >= rules.storage_warning_percent) else {
+                            continue;
+                        };
+                        let alert = Alert::StorageNearlyFull { path: storage_dir.clone(), used_percent };
+                        if !state.lock().unwrap_or_else(PoisonError::into_inner).admit(&alert, rules.cooldown) {
+                            continue;
+                        }
+                        Notification { alert, device_id: device_id.clone(), timestamp: Utc::now() }
+                    }
+                };
+                for notifier in &notifiers {
+                    match notifier.send(&notification) {
+                        Ok(()) => tracing::info!(notifier = notifier.name(), alert = %notification.alert.summary(), "Sent alert"),
+                        Err(e) => tracing::warn!(notifier = notifier.name(), "Failed to send alert: {:#}", e),
+                    }
+                }
+            })
+        };
+        Self { rules, device_id, state, sender: Some(sender), worker: Some(worker) }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, AlertState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Send `alert` unless one of its kind was sent within the cooldown
+    pub fn raise(&self, alert: Alert) {
+        if !self.state().admit(&alert, self.rules.cooldown) {
+            tracing::debug!(alert = %alert.summary(), "Alert suppressed by cooldown");
+            return;
+        }
+        let notification = Notification { alert, device_id: self.device_id.clone(), timestamp: Utc::now() };
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(notification);
+        }
+    }
+
+    /// Count a decision towards [`Alert::RepeatedFailures`] and raise
+    /// [`Alert::WatchlistHit`] for watchlist denials
+    pub fn record_attempt(&self, granted: bool, denial: Option<&DenialReason>) {
+        if let Some(DenialReason::WatchlistHit { entry }) = denial {
+            self.raise(Alert::WatchlistHit { entry: entry.clone() });
+        }
+        if granted || self.rules.failed_attempts == 0 {
+            return;
+        }
+        let failures = {
+            let mut state = self.state();
+            let now = Instant::now();
+            state.failures.push_back(now);
+            while state.failures.front().is_some_and(|failed| now.duration_since(*failed) > self.rules.failed_window) {
+                state.failures.pop_front();
+            }
+            let failures = state.failures.len();
+            if failures >= self.rules.failed_attempts {
+                state.failures.clear();
+            }
+            failures
+        };
+        if failures >= self.rules.failed_attempts {
+            self.raise(Alert::RepeatedFailures { failures, window_secs: self.rules.failed_window.as_secs() });
+        }
+    }
+
+    /// Raise [`Alert::CameraOffline`] when a working camera stops delivering frames
+    pub fn record_camera(&self, ready: bool) {
+        let previous = self.state().camera_ready.replace(ready);
+        if !ready && previous != Some(false) {
+            self.raise(Alert::CameraOffline);
+        }
+    }
+}
+
+impl Drop for Alerts {
+    fn drop(&mut self) {
+        self.sender = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Used share of the file system holding `path`, in percent
+#[cfg(unix)]
+pub fn storage_used_percent(path: &Path) -> Option<f64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stats` is a valid, writable statvfs
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stats) } != 0 || stats.f_blocks == 0 {
+        return None;
+    }
+    let (total, available) = (stats.f_blocks as f64, stats.f_bavail as f64);
+    Some(100.0 * (total - available) / total)
+}
+
+/// Used share of the file system holding `path`, in percent; not available
+/// on this platform
+#[cfg(not(unix))]
+pub fn storage_used_percent(_path: &Path) -> Option<f64> {
+    None
+}
+
+/// Notification channels and alert rules from a TOML file:
+///
+/// ```toml
+/// [alerts]
+/// failed_attempts = 5
+/// failed_window_secs = 300
+/// storage_warning_percent = 90
+/// cooldown_secs = 900
+///
+/// [[email]]
+/// name = "ops-mail"
+/// host = "smtp.example.com"
+/// port = 587
+/// security = "starttls"
+/// username = "alerts@example.com"
+/// password_env = "FACE_AUTH_SMTP_PASSWORD"
+/// from = "alerts@example.com"
+/// to = ["ops@example.com"]
+///
+/// [[slack]]
+/// name = "ops-channel"
+/// webhook_url = "https://hooks.slack.com/services/..."
+/// ```
+#[cfg(feature = "notifications")]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationConfig {
+    #[serde(default)]
+    pub alerts: AlertRulesConfig,
+    #[serde(default)]
+    pub email: Vec<EmailChannel>,
+    #[serde(default)]
+    pub slack: Vec<SlackChannel>,
+}
+
+/// `[alerts]` table; unset values keep the [`AlertRules`] defaults
+#[cfg(feature = "notifications")]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertRulesConfig {
+    pub failed_attempts: Option<usize>,
+    pub failed_window_secs: Option<u64>,
+    pub storage_warning_percent: Option<f64>,
+    pub storage_check_secs: Option<u64>,
+    pub cooldown_secs: Option<u64>,
+}
+
+#[cfg(feature = "notifications")]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmailChannel {
+    pub name: String,
+    pub host: String,
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    /// Environment variable holding the password, so it stays out of the file
+    pub password_env: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+#[cfg(feature = "notifications")]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SlackChannel {
+    pub name: String,
+    pub webhook_url: String,
+}
+
+#[cfg(feature = "notifications")]
+impl NotificationConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        Self::from_toml(&text).map_err(|e| anyhow!("Invalid notification config {}: {}", path.display(), e))
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    pub fn rules(&self) -> AlertRules {
+        let defaults = AlertRules::default();
+        let alerts = &self.alerts;
+        AlertRules {
+            failed_attempts: alerts.failed_attempts.unwrap_or(defaults.failed_attempts),
+            failed_window: alerts.failed_window_secs.map_or(defaults.failed_window, Duration::from_secs),
+            storage_warning_percent: alerts.storage_warning_percent.unwrap_or(defaults.storage_warning_percent),
+            storage_check_interval: alerts.storage_check_secs.map_or(defaults.storage_check_interval, Duration::from_secs),
+            cooldown: alerts.cooldown_secs.map_or(defaults.cooldown, Duration::from_secs),
+        }
+    }
+
+    /// The configured channels; fails if a password variable isn't set or
+    /// a channel would send its password unencrypted
+    pub fn notifiers(&self) -> Result<Vec<Arc<dyn Notifier>>> {
+        let mut notifiers: Vec<Arc<dyn Notifier>> = Vec::new();
+        for channel in &self.email {
+            if channel.username.is_some() && channel.security == SmtpSecurity::None {
+                return Err(anyhow!("Email channel {} logs in, which needs security \"starttls\" or \"tls\"", channel.name));
+            }
+            let password = channel.password_env.as_deref()
+                .map(|var| std::env::var(var).map_err(|_| anyhow!("{} is not set for email channel {}", var, channel.name)))
+                .transpose()?;
+            let config = SmtpConfig {
+                host: channel.host.clone(),
+                port: channel.port.unwrap_or_else(|| channel.security.default_port()),
+                security: channel.security,
+                credentials: channel.username.clone().zip(password),
+                from: channel.from.clone(),
+                to: channel.to.clone(),
+                timeout: Duration::from_secs(30),
+            };
+            notifiers.push(Arc::new(EmailNotifier::new(&channel.name, config)));
+        }
+        for channel in &self.slack {
+            notifiers.push(Arc::new(SlackNotifier::new(&channel.name, &channel.webhook_url)));
+        }
+        Ok(notifiers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alerts_are_raised_once_per_cooldown() {
+        let (sent, received) = mpsc::channel();
+        let sent = Mutex::new(sent);
+        let notifier = CallbackNotifier::new("test", move |notification: &Notification| {
+            sent.lock().unwrap().send(notification.alert.clone())?;
+            Ok(())
+        });
+        let rules = AlertRules { failed_attempts: 3, ..AlertRules::default() };
+        let dir = tempfile::tempdir().unwrap();
+        let alerts = Alerts::start(vec![Arc::new(notifier)], rules, Some("door-1".into()), dir.path());
+
+        alerts.record_attempt(false, None);
+        alerts.record_attempt(true, None);
+        alerts.record_attempt(false, None);
+        alerts.record_attempt(false, None);
+        // Within the cooldown
+        for _ in 0..3 {
+            alerts.record_attempt(false, None);
+        }
+        alerts.record_attempt(false, Some(&DenialReason::WatchlistHit { entry: "banned".into() }));
+        alerts.record_camera(true);
+        alerts.record_camera(false);
+        alerts.record_camera(false);
+        drop(alerts);
+
+        let alerts: Vec<Alert> = received.try_iter().collect();
+        assert_eq!(alerts, [
+            Alert::RepeatedFailures { failures: 3, window_secs: 300 },
+            Alert::WatchlistHit { entry: "banned".into() },
+            Alert::CameraOffline,
+        ]);
+        assert!(storage_used_percent(dir.path()).is_none_or(|used| (0.0..=100.0).contains(&used)));
+    }
+
+    #[cfg(feature = "notifications")]
+    #[test]
+    fn test_config_is_read_from_toml() {
+        let config = NotificationConfig::from_toml(r#"
+            [alerts]
+            failed_attempts = 3
+            cooldown_secs = 60
+
+            [[email]]
+            name = "ops-mail"
+            host = "smtp.example.com"
+            security = "tls"
+            from = "alerts@example.com"
+            to = ["ops@example.com"]
+
+            [[slack]]
+            name = "ops-channel"
+            webhook_url = "https://hooks.slack.com/services/T/B/X"
+        "#).unwrap();
+        let rules = config.rules();
+        assert_eq!((rules.failed_attempts, rules.cooldown), (3, Duration::from_secs(60)));
+        assert_eq!(rules.failed_window, AlertRules::default().failed_window);
+        assert_eq!(config.email[0].security, SmtpSecurity::Tls);
+        let names: Vec<String> = config.notifiers().unwrap().iter().map(|n| n.name().to_string()).collect();
+        assert_eq!(names, ["ops-mail", "ops-channel"]);
+        assert!(NotificationConfig::from_toml("[[pager]]\nname = \"x\"").is_err());
+
+        let plain = NotificationConfig::from_toml(r#"
+            [[email]]
+            name = "relay"
+            host = "localhost"
+            security = "none"
+            username = "alerts"
+            from = "alerts@example.com"
+            to = ["ops@example.com"]
+        "#).unwrap();
+        assert!(plain.notifiers().is_err());
+    }
+}
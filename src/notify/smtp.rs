@@ -0,0 +1,238 @@
+use anyhow::{Result, anyhow, bail};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::Deserialize;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{Notification, Notifier};
+
+/// How the connection to the mail server is protected
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpSecurity {
+    /// Plain connection upgraded with `STARTTLS`, usually port 587
+    #[default]
+    StartTls,
+    /// TLS from the start, usually port 465
+    Tls,
+    /// No encryption, only for a relay on the local host or network
+    None,
+}
+
+impl SmtpSecurity {
+    pub fn default_port(self) -> u16 {
+        match self {
+            SmtpSecurity::StartTls => 587,
+            SmtpSecurity::Tls => 465,
+            SmtpSecurity::None => 25,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    /// Username and password for `AUTH PLAIN`, only sent over TLS
+    pub credentials: Option<(String, String)>,
+    pub from: String,
+    pub to: Vec<String>,
+    pub timeout: Duration,
+}
+
+/// Notifier mailing alerts through an SMTP server
+#[derive(Debug)]
+pub struct EmailNotifier {
+    name: String,
+    config: SmtpConfig,
+}
+
+impl EmailNotifier {
+    pub fn new(name: impl Into<String>, config: SmtpConfig) -> Self {
+        Self { name: name.into(), config }
+    }
+}
+
+impl Notifier for EmailNotifier {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn send(&self, notification: &Notification) -> Result<()> {
+        send_mail(&self.config, &notification.subject(), &notification.text())
+            .map_err(|e| anyhow!("Mail via {}:{} failed: {:#}", self.config.host, self.config.port, e))
+    }
+}
+
+trait Stream: Read + Write {}
+
+impl<S: Read + Write> Stream for S {}
+
+/// One SMTP session over a plain or TLS stream
+struct Session {
+    stream: Box<dyn Stream>,
+}
+
+impl Session {
+    /// Read a possibly multi-line reply, failing unless its code is `expected`
+    fn expect(&mut self, expected: u16) -> Result<String> {
+        let mut reply = String::new();
+        loop {
+            let mut line = Vec::new();
+            let mut byte = [0u8; 1];
+            while line.last() != Some(&b'\n') {
+                if self.stream.read(&mut byte)? == 0 {
+                    bail!("Connection closed by the server");
+                }
+                line.push(byte[0]);
+            }
+            let line = String::from_utf8_lossy(&line).trim_end().to_string();
+            reply.push_str(&line);
+            reply.push('\n');
+            if line.len() < 4 || line.as_bytes()[3] != b'-' {
+                let code: u16 = line.get(..3).and_then(|code| code.parse().ok()).ok_or_else(|| anyhow!("Malformed reply: {}", line))?;
+                if code != expected {
+                    bail!("Server answered {}", reply.trim_end());
+                }
+                return Ok(reply);
+            }
+        }
+    }
+
+    fn command(&mut self, command: &str, expected: u16) -> Result<String> {
+        self.stream.write_all(command.as_bytes())?;
+        self.stream.write_all(b"\r\n")?;
+        self.stream.flush()?;
+        self.expect(expected)
+    }
+}
+
+fn tls(host: &str, stream: TcpStream) -> Result<Box<dyn Stream>> {
+    let roots = rustls::RootCertStore { roots: webpki_roots::TLS_SERVER_ROOTS.into() };
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let server_name = rustls::pki_types::ServerName::try_from(host.to_string())?;
+    let connection = rustls::ClientConnection::new(Arc::new(config), server_name)?;
+    Ok(Box::new(rustls::StreamOwned::new(connection, stream)))
+}
+
+fn send_mail(config: &SmtpConfig, subject: &str, body: &str) -> Result<()> {
+    if config.credentials.is_some() && config.security == SmtpSecurity::None {
+        bail!("Refusing to send the SMTP password over an unencrypted connection");
+    }
+    let addr = (config.host.as_str(), config.port).to_socket_addrs()?.next().ok_or_else(|| anyhow!("Unknown host"))?;
+    let tcp = TcpStream::connect_timeout(&addr, config.timeout)?;
+    tcp.set_read_timeout(Some(config.timeout))?;
+    tcp.set_write_timeout(Some(config.timeout))?;
+    let stream = match config.security {
+        SmtpSecurity::Tls => tls(&config.host, tcp.try_clone()?)?,
+        SmtpSecurity::StartTls | SmtpSecurity::None => Box::new(tcp.try_clone()?),
+    };
+    let mut session = Session { stream };
+    session.expect(220)?;
+    let ehlo = format!("EHLO {}", hostname());
+    session.command(&ehlo, 250)?;
+    if config.security == SmtpSecurity::StartTls {
+        session.command("STARTTLS", 220)?;
+        session.stream = tls(&config.host, tcp)?;
+        session.command(&ehlo, 250)?;
+    }
+    if let Some((username, password)) = &config.credentials {
+        let token = BASE64.encode(format!("\0{}\0{}", username, password));
+        session.command(&format!("AUTH PLAIN {}", token), 235)?;
+    }
+    session.command(&format!("MAIL FROM:<{}>", config.from), 250)?;
+    for recipient in &config.to {
+        session.command(&format!("RCPT TO:<{}>", recipient), 250)?;
+    }
+    session.command("DATA", 354)?;
+    session.command(&format!("{}\r\n.", message(config, subject, body)), 250)?;
+    session.command("QUIT", 221)?;
+    Ok(())
+}
+
+/// The message with headers, CRLF line endings and dot-stuffed lines
+fn message(config: &SmtpConfig, subject: &str, body: &str) -> String {
+    let headers = [
+        format!("From: <{}>", config.from),
+        format!("To: {}", config.to.iter().map(|to| format!("<{}>", to)).collect::<Vec<_>>().join(", ")),
+        format!("Subject: =?utf-8?B?{}?=", BASE64.encode(subject)),
+        format!("Date: {}", chrono::Utc::now().to_rfc2822()),
+        "MIME-Version: 1.0".to_string(),
+        "Content-Type: text/plain; charset=utf-8".to_string(),
+        "Content-Transfer-Encoding: 8bit".to_string(),
+    ];
+    let body = body.lines().map(|line| if line.starts_with('.') { format!(".{}", line) } else { line.to_string() });
+    headers.into_iter().chain([String::new()]).chain(body).collect::<Vec<_>>().join("\r\n")
+}
+
+fn hostname() -> String {
+    std::env::var("HOSTNAME").ok().filter(|name| !name.is_empty()).unwrap_or_else(|| "localhost".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::Alert;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_mail_is_sent_through_smtp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut lines = BufReader::new(stream).lines().map(Result::unwrap);
+            let mut transcript = Vec::new();
+            writer.write_all(b"220 test ESMTP\r\n").unwrap();
+            while let Some(line) = lines.next() {
+                let reply: &[u8] = match line.as_str() {
+                    l if l.starts_with("EHLO") => b"250-test\r\n250 AUTH PLAIN\r\n",
+                    l if l.starts_with("AUTH") => b"235 ok\r\n",
+                    "DATA" => {
+                        writer.write_all(b"354 go on\r\n").unwrap();
+                        transcript.extend(lines.by_ref().take_while(|line| line != "."));
+                        b"250 queued\r\n"
+                    }
+                    "QUIT" => {
+                        writer.write_all(b"221 bye\r\n").unwrap();
+                        break;
+                    }
+                    _ => b"250 ok\r\n",
+                };
+                transcript.push(line);
+                writer.write_all(reply).unwrap();
+            }
+            transcript
+        });
+
+        let config = SmtpConfig {
+            host: "127.0.0.1".into(),
+            port,
+            security: SmtpSecurity::None,
+            credentials: None,
+            from: "alerts@example.com".into(),
+            to: vec!["ops@example.com".into(), "oncall@example.com".into()],
+            timeout: Duration::from_secs(5),
+        };
+        let notification = Notification { alert: Alert::CameraOffline, device_id: Some("door-1".into()), timestamp: chrono::Utc::now() };
+        // A password never goes over the plain connection
+        let login = SmtpConfig { credentials: Some(("alerts".into(), "secret".into())), ..config.clone() };
+        assert!(EmailNotifier::new("ops-mail", login).send(&notification).is_err());
+        EmailNotifier::new("ops-mail", config).send(&notification).unwrap();
+
+        let transcript = server.join().unwrap();
+        assert!(!transcript.iter().any(|line| line.starts_with("AUTH")));
+        assert!(transcript.contains(&"RCPT TO:<oncall@example.com>".to_string()));
+        assert!(transcript.contains(&"To: <ops@example.com>, <oncall@example.com>".to_string()));
+        assert!(transcript.contains(&"Device: door-1".to_string()));
+    }
+}
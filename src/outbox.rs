@@ -0,0 +1,435 @@
+use anyhow::{Result, anyhow};
+use chacha20poly1305::aead::OsRng;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::assurance::AssuranceLevel;
+use crate::denial::DenialReason;
+use crate::face_storage::write_atomic;
+use crate::signing::{SignedResult, encode_hex};
+
+/// Tells apart events queued within the same nanosecond
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// An access decision as sent to webhooks, MQTT or a fleet server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccessEvent {
+    /// Random id; receivers should ignore ids they've seen, since delivery
+    /// is at least once
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    pub device_id: Option<String>,
+    pub user_id: Option<String>,
+    pub granted: bool,
+    pub distance: Option<f64>,
+    pub denial: Option<DenialReason>,
+    pub assurance: Option<AssuranceLevel>,
+    /// The signed result, for receivers holding the device key
+    pub signed: Option<SignedResult>,
+}
+
+impl AccessEvent {
+    pub fn new(device_id: Option<String>, user_id: Option<String>, granted: bool) -> Self {
+        let mut id = [0u8; 16];
+        OsRng.fill_bytes(&mut id);
+        Self {
+            id: encode_hex(&id),
+            timestamp: Utc::now(),
+            device_id,
+            user_id,
+            granted,
+            distance: None,
+            denial: None,
+            assurance: None,
+            signed: None,
+        }
+    }
+}
+
+/// Somewhere access events are delivered to
+pub trait EventSink: fmt::Debug + Send + Sync {
+    /// Names the sink's queue directory, so must be usable as a file name
+    fn name(&self) -> &str;
+
+    /// Deliver one event; an error keeps it queued for a retry
+    fn deliver(&self, event: &AccessEvent) -> Result<()>;
+}
+
+/// Sink calling a closure, e.g. to forward events over a custom transport
+pub struct CallbackSink<F> {
+    name: String,
+    deliver: F,
+}
+
+impl<F> CallbackSink<F>
+where
+    F: Fn(&AccessEvent) -> Result<()> + Send + Sync,
+{
+    pub fn new(name: impl Into<String>, deliver: F) -> Self {
+        Self { name: name.into(), deliver }
+    }
+}
+
+impl<F> fmt::Debug for CallbackSink<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallbackSink").field("name", &self.name).finish_non_exhaustive()
+    }
+}
+
+impl<F> EventSink for CallbackSink<F>
+where
+    F: Fn(&AccessEvent) -> Result<()> + Send + Sync,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn deliver(&self, event: &AccessEvent) -> Result<()> {
+        (self.deliver)(event)
+    }
+}
+
+/// Sink POSTing each event as JSON; any 2xx answer counts as delivered
+#[cfg(feature = "webhooks")]
+#[derive(Debug)]
+pub struct WebhookSink {
+    name: String,
+    url: String,
+    agent: ureq::Agent,
+}
+
+#[cfg(feature = "webhooks")]
+impl WebhookSink {
+    pub fn new(name: impl Into<String>, url: impl Into<String>) -> Self {
+        let agent = ureq::Agent::config_builder()
+            .http_status_as_error(false)
+            .timeout_global(Some(Duration::from_secs(10)))
+            .build()
+            .into();
+        Self { name: name.into(), url: url.into(), agent }
+    }
+}
+
+#[cfg(feature = "webhooks")]
+impl EventSink for WebhookSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn deliver(&self, event: &AccessEvent) -> Result<()> {
+        let response = self.agent.post(&self.url).send_json(event).map_err(|e| anyhow!("{} unreachable: {}", self.url, e))?;
+        if !response.status().is_success() {
+            return Err(anyhow!("{} answered {}", self.url, response.status()));
+        }
+        Ok(())
+    }
+}
+
+/// Sink publishing each event as JSON to an MQTT topic, connecting lazily
+/// so a broker that's down at start doesn't stop the device
+///
+/// Publishes at QoS 1, so an event counts as delivered only once the broker
+/// acknowledged it.
+#[cfg(feature = "mqtt")]
+#[derive(Debug)]
+pub struct MqttSink {
+    name: String,
+    options: crate::mqtt::MqttOptions,
+    topic: String,
+    client: Mutex<Option<crate::mqtt::MqttClient>>,
+}
+
+#[cfg(feature = "mqtt")]
+impl MqttSink {
+    pub fn new(name: impl Into<String>, options: crate::mqtt::MqttOptions, topic: impl Into<String>) -> Self {
+        Self { name: name.into(), options, topic: topic.into(), client: Mutex::new(None) }
+    }
+}
+
+#[cfg(feature = "mqtt")]
+impl EventSink for MqttSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn deliver(&self, event: &AccessEvent) -> Result<()> {
+        let mut client = self.client.lock().unwrap_or_else(PoisonError::into_inner);
+        if client.is_none() {
+            *client = Some(crate::mqtt::MqttClient::connect(self.options.clone())?);
+        }
+        let result = client.as_ref().expect("connected above").publish_acked(&self.topic, &serde_json::to_vec(event)?, false);
+        if result.is_err() {
+            *client = None;
+        }
+        result
+    }
+}
+
+/// Retry timing and disk bound of an [`Outbox`]
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct OutboxConfig {
+    /// Wait after the first failed delivery, doubled on each further failure
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Events kept per sink while it's unreachable; the oldest are dropped
+    /// beyond this
+    pub max_pending: usize,
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        Self { initial_backoff: Duration::from_secs(1), max_backoff: Duration::from_secs(300), max_pending: 10_000 }
+    }
+}
+
+impl OutboxConfig {
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    pub fn with_max_pending(mut self, max_pending: usize) -> Self {
+        self.max_pending = max_pending;
+        self
+    }
+}
+
+/// A sink's queue directory and how many events it holds, counted as they
+/// come and go instead of listing the directory
+#[derive(Debug)]
+struct Queue {
+    dir: PathBuf,
+    len: AtomicUsize,
+}
+
+impl Queue {
+    fn open(dir: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&dir)?;
+        let len = AtomicUsize::new(queued(&dir)?.len());
+        Ok(Self { dir, len })
+    }
+
+    /// Remove a queued event; one already removed by the other side of the
+    /// queue isn't an error
+    fn remove(&self, path: &Path) -> Result<()> {
+        match fs::remove_file(path) {
+            Ok(()) => {
+                let _ = self.len.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |len| len.checked_sub(1));
+                Ok(())
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+struct Route {
+    sink: Arc<dyn EventSink>,
+    queue: Arc<Queue>,
+    backoff: Option<Duration>,
+    next_attempt: Instant,
+}
+
+#[derive(Default)]
+struct State {
+    stopped: bool,
+    /// Set by enqueue so the sender doesn't sleep through a new event
+    woken: bool,
+}
+
+/// Queue of outgoing access events kept on disk until each sink has taken
+/// them, so none are lost while the network is down
+///
+/// Every sink has its own queue directory and backoff, so one unreachable
+/// sink doesn't hold up the others. Events are delivered oldest first and
+/// removed only after delivery succeeded: at least once, never lost to a
+/// crash or restart. A background thread sends; it stops when dropped.
+pub struct Outbox {
+    queues: Vec<Arc<Queue>>,
+    max_pending: usize,
+    state: Arc<(Mutex<State>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Outbox {
+    pub fn start(dir: impl AsRef<Path>, sinks: Vec<Arc<dyn EventSink>>, config: OutboxConfig) -> Result<Self> {
+        let mut routes = Vec::new();
+        for sink in sinks {
+            crate::face_storage::validate_username(sink.name()).map_err(|_| anyhow!("Invalid sink name: '{}'", sink.name()))?;
+            let queue = Arc::new(Queue::open(dir.as_ref().join(sink.name()))?);
+            routes.push(Route { sink, queue, backoff: None, next_attempt: Instant::now() });
+        }
+        let queues = routes.iter().map(|route| route.queue.clone()).collect();
+        let max_pending = config.max_pending;
+        let state = Arc::new((Mutex::new(State::default()), Condvar::new()));
+        let thread = thread::spawn({
+            let state = state.clone();
+            move || send_loop(routes, &config, &state)
+        });
+        Ok(Self { queues, max_pending, state, thread: Some(thread) })
+    }
+
+    /// Persist `event` for every sink, then wake the sender
+    pub fn enqueue(&self, event: &AccessEvent) -> Result<()> {
+        let name = format!(
+            "{}_{:06}.json",
+            event.timestamp.format("%Y%m%dT%H%M%S%.9f"),
+            SEQUENCE.fetch_add(1, Ordering::Relaxed) % 1_000_000
+        );
+        let contents = serde_json::to_vec(event)?;
+        for queue in &self.queues {
+            write_atomic(&queue.dir.join(&name), &contents)?;
+            if queue.len.fetch_add(1, Ordering::SeqCst) < self.max_pending {
+                continue;
+            }
+            // Only a full queue is listed, to find its oldest events
+            let pending = queued(&queue.dir)?;
+            let excess = pending.len().saturating_sub(self.max_pending);
+            for path in &pending[..excess] {
+                tracing::warn!("Outbox full, dropping {}", path.display());
+                queue.remove(path)?;
+            }
+        }
+        let (state, wake) = &*self.state;
+        state.lock().unwrap_or_else(PoisonError::into_inner).woken = true;
+        wake.notify_all();
+        Ok(())
+    }
+
+    /// Events not yet delivered, summed over all sinks
+    pub fn pending(&self) -> Result<usize> {
+        Ok(self.queues.iter().map(|queue| queue.len.load(Ordering::SeqCst)).sum())
+    }
+}
+
+impl fmt::Debug for Outbox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Outbox").field("queues", &self.queues).finish_non_exhaustive()
+    }
+}
+
+impl Drop for Outbox {
+    fn drop(&mut self) {
+        let (state, wake) = &*self.state;
+        state.lock().unwrap_or_else(PoisonError::into_inner).stopped = true;
+        wake.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn send_loop(mut routes: Vec<Route>, config: &OutboxConfig, state: &(Mutex<State>, Condvar)) {
+    let (state, wake) = state;
+    loop {
+        for route in routes.iter_mut().filter(|route| route.next_attempt <= Instant::now()) {
+            match drain(route, state) {
+                Ok(()) => route.backoff = None,
+                Err(e) => {
+                    let backoff = route.backoff.map_or(config.initial_backoff, |backoff| (backoff * 2).min(config.max_backoff));
+                    tracing::warn!(sink = route.sink.name(), retry_in = ?backoff, "Failed to deliver event: {:#}", e);
+                    route.backoff = Some(backoff);
+                    route.next_attempt = Instant::now() + backoff;
+                }
+            }
+        }
+        let now = Instant::now();
+        let timeout = routes.iter()
+            .filter(|route| route.backoff.is_some())
+            .map(|route| route.next_attempt.saturating_duration_since(now))
+            .min()
+            .unwrap_or(config.max_backoff);
+        let guard = state.lock().unwrap_or_else(PoisonError::into_inner);
+        let (mut guard, _) = wake.wait_timeout_while(guard, timeout, |state| !state.stopped && !state.woken)
+            .unwrap_or_else(PoisonError::into_inner);
+        if guard.stopped {
+            break;
+        }
+        guard.woken = false;
+    }
+}
+
+/// Deliver a sink's queued events oldest first, stopping at the first failure
+fn drain(route: &Route, state: &Mutex<State>) -> Result<()> {
+    for path in queued(&route.queue.dir)? {
+        if state.lock().unwrap_or_else(PoisonError::into_inner).stopped {
+            break;
+        }
+        let contents = match fs::read(&path) {
+            Ok(contents) => contents,
+            // Dropped by a full queue since it was listed
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let event: AccessEvent = match serde_json::from_slice(&contents) {
+            Ok(event) => event,
+            Err(e) => {
+                tracing::warn!("Dropping unreadable event {}: {}", path.display(), e);
+                route.queue.remove(&path)?;
+                continue;
+            }
+        };
+        route.sink.deliver(&event)?;
+        route.queue.remove(&path)?;
+    }
+    Ok(())
+}
+
+/// Queued event files, oldest first
+fn queued(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter(|path| !path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.')))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_survive_outages_and_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = OutboxConfig { initial_backoff: Duration::from_millis(10), max_backoff: Duration::from_millis(40), max_pending: 3 };
+        let online = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+        let sink = |online: Arc<std::sync::atomic::AtomicBool>, delivered: Arc<Mutex<Vec<String>>>| -> Arc<dyn EventSink> {
+            Arc::new(CallbackSink::new("fleet", move |event: &AccessEvent| {
+                if !online.load(Ordering::SeqCst) {
+                    anyhow::bail!("network down");
+                }
+                delivered.lock().unwrap().push(event.user_id.clone().unwrap());
+                Ok(())
+            }))
+        };
+
+        let outbox = Outbox::start(dir.path(), vec![sink(online.clone(), delivered.clone())], config.clone()).unwrap();
+        for user in ["ann", "bob", "cat", "dan"] {
+            outbox.enqueue(&AccessEvent::new(None, Some(user.to_string()), true)).unwrap();
+        }
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(outbox.pending().unwrap(), 3, "the oldest was dropped");
+        drop(outbox);
+
+        online.store(true, Ordering::SeqCst);
+        let outbox = Outbox::start(dir.path(), vec![sink(online, delivered.clone())], config).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while outbox.pending().unwrap() > 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(*delivered.lock().unwrap(), ["bob", "cat", "dan"]);
+    }
+}
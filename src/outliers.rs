@@ -0,0 +1,157 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::face_storage::{FaceDatabase, SampleSummary, UserProfile};
+use crate::matching::face_distance;
+
+/// Samples needed before outliers can be told apart from normal variation
+pub const MIN_SAMPLES_FOR_OUTLIERS: usize = 3;
+
+/// Median absolute deviations above the median distance at which a sample is flagged
+pub const OUTLIER_DEVIATIONS: f64 = 3.0;
+
+/// Distance to the centroid below which a sample is never flagged, however
+/// tight the other samples are
+pub const MIN_OUTLIER_DISTANCE: f64 = 0.4;
+
+/// Scales the median absolute deviation to a standard deviation for normal data
+const MAD_SCALE: f64 = 1.4826;
+
+/// Why a sample was taken out of matching
+///
+/// Quarantined samples stay stored until an admin releases or removes them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SampleQuarantine {
+    pub flagged_at: DateTime<Utc>,
+    /// Distance to the centroid of the user's other samples
+    pub distance: f64,
+    /// Distance above which samples of this user were flagged
+    pub threshold: f64,
+}
+
+impl UserProfile {
+    /// Quarantine samples far from the centroid of the user's other samples,
+    /// e.g. a mislabelled capture or one with someone else in frame
+    ///
+    /// Each sample is compared against the centroid of the remaining active
+    /// samples, so an outlier doesn't pull the centroid towards itself. A
+    /// sample is flagged when its distance exceeds both the median by
+    /// [`OUTLIER_DEVIATIONS`] scaled median absolute deviations and
+    /// [`MIN_OUTLIER_DISTANCE`]. Remote and already quarantined samples are
+    /// skipped. Returns the newly quarantined samples.
+    pub fn detect_outliers(&mut self) -> Vec<SampleSummary> {
+        let dimensions = self.face_encodings.iter()
+            .find(|s| !s.is_remote() && !s.is_quarantined())
+            .map_or(0, |s| s.encoding.len());
+        let active: Vec<usize> = (0..self.face_encodings.len())
+            .filter(|&i| {
+                let sample = &self.face_encodings[i];
+                !sample.is_remote() && !sample.is_quarantined() && sample.encoding.len() == dimensions
+            })
+            .collect();
+        if active.len() < MIN_SAMPLES_FOR_OUTLIERS {
+            return Vec::new();
+        }
+
+        let sum = active.iter().fold(vec![0.0; dimensions], |mut sum, &i| {
+            sum.iter_mut().zip(&self.face_encodings[i].encoding).for_each(|(s, v)| *s += v);
+            sum
+        });
+        let others = (active.len() - 1) as f64;
+        let distances: Vec<f64> = active.iter()
+            .map(|&i| {
+                let encoding = &self.face_encodings[i].encoding;
+                let centroid: Vec<f64> = sum.iter().zip(encoding).map(|(s, v)| (s - v) / others).collect();
+                face_distance(encoding, &centroid)
+            })
+            .collect();
+
+        let median_distance = median(distances.clone());
+        let deviation = median(distances.iter().map(|d| (d - median_distance).abs()).collect());
+        let threshold = (median_distance + OUTLIER_DEVIATIONS * MAD_SCALE * deviation).max(MIN_OUTLIER_DISTANCE);
+
+        let flagged_at = Utc::now();
+        let mut flagged = Vec::new();
+        for (&i, &distance) in active.iter().zip(&distances) {
+            if distance > threshold {
+                let sample = &mut self.face_encodings[i];
+                sample.quarantine = Some(SampleQuarantine { flagged_at, distance, threshold });
+                tracing::warn!(user = %self.user_id, sample = %sample.sample_id, distance, threshold, "Quarantined outlier sample");
+                flagged.push(SampleSummary::from(&*sample));
+            }
+        }
+        flagged
+    }
+
+    /// Return a quarantined sample to matching after review
+    pub fn release_sample(&mut self, sample_id: &str) -> Result<()> {
+        let sample = self.face_encodings.iter_mut().find(|s| s.sample_id == sample_id)
+            .ok_or_else(|| anyhow!("User '{}' has no sample '{}'", self.user_id, sample_id))?;
+        if sample.quarantine.take().is_none() {
+            return Err(anyhow!("Sample '{}' of user '{}' is not quarantined", sample_id, self.user_id));
+        }
+        Ok(())
+    }
+}
+
+impl FaceDatabase {
+    /// Run [`UserProfile::detect_outliers`] on `username`'s database entry
+    pub fn detect_outliers(&mut self, username: &str) -> Result<Vec<SampleSummary>> {
+        self.users.get_mut(username)
+            .map(UserProfile::detect_outliers)
+            .ok_or_else(|| anyhow!("User '{}' not found", username))
+    }
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::face_storage::FaceSample;
+    use crate::matching;
+
+    fn profile(samples: &[Vec<f64>]) -> UserProfile {
+        let mut profile: UserProfile = serde_json::from_value(serde_json::json!({ "user_id": "ann", "face_encodings": [] })).unwrap();
+        profile.face_encodings = samples.iter().enumerate().map(|(i, encoding)| FaceSample {
+            encoding: encoding.clone(),
+            timestamp: String::new(),
+            image_path: None,
+            sample_id: format!("ann_{}", i + 1),
+            quality: None,
+            template_version: None,
+            quarantine: None,
+        }).collect();
+        profile
+    }
+
+    #[test]
+    fn test_foreign_sample_is_quarantined_and_released() {
+        let face = |offset: f64, scale: f64| (0..8).map(|i| offset + scale * (i as f64 * 0.7).sin()).collect::<Vec<_>>();
+        let mut genuine = profile(&[face(0.0, 0.1), face(0.02, 0.1), face(0.0, 0.12), face(0.01, 0.11)]);
+        assert!(genuine.detect_outliers().is_empty());
+
+        let mut mixed = profile(&[face(0.0, 0.1), face(0.02, 0.1), face(0.0, 0.12), face(0.4, -0.2)]);
+        let flagged = mixed.detect_outliers();
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].sample_id, "ann_4");
+        assert!(mixed.detect_outliers().is_empty(), "flagged samples aren't checked again");
+
+        let probe = face(0.4, -0.2);
+        let distance = matching::best_match([&mixed], &probe, 0.6).unwrap().distance;
+        assert!(distance > 0.4, "quarantined samples don't match");
+
+        mixed.release_sample("ann_4").unwrap();
+        assert!(mixed.release_sample("ann_4").is_err());
+        assert_eq!(matching::best_match([&mixed], &probe, 0.6).unwrap().distance, 0.0);
+    }
+}
@@ -0,0 +1,160 @@
+use anyhow::{Result, anyhow, bail};
+use std::fmt;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Environment variable the prompt's reason is passed to commands in
+pub const REASON_VAR: &str = "FACE_AUTH_REASON";
+
+/// Asks Windows Hello through WinRT's `UserConsentVerifier`
+const WINDOWS_HELLO_SCRIPT: &str = r#"
+Add-Type -AssemblyName System.Runtime.WindowsRuntime
+$null = [Windows.Security.Credentials.UI.UserConsentVerifier, Windows.Security.Credentials.UI, ContentType = WindowsRuntime]
+$asTask = [System.WindowsRuntimeSystemExtensions].GetMethods() | Where-Object {
+    $_.Name -eq 'AsTask' -and $_.GetParameters().Count -eq 1 -and $_.GetParameters()[0].ParameterType.Name -eq 'IAsyncOperation`1'
+} | Select-Object -First 1
+function Await($operation, [Type]$type) {
+    $task = $asTask.MakeGenericMethod($type).Invoke($null, @($operation))
+    $null = $task.Wait(-1)
+    $task.Result
+}
+$verifier = [Windows.Security.Credentials.UI.UserConsentVerifier]
+if ((Await $verifier::CheckAvailabilityAsync() ([Windows.Security.Credentials.UI.UserConsentVerifierAvailability])) -ne 'Available') { exit 2 }
+$result = Await $verifier::RequestVerificationAsync($env:FACE_AUTH_REASON) ([Windows.Security.Credentials.UI.UserConsentVerificationResult])
+if ($result -eq 'Verified') { exit 0 } else { exit 1 }
+"#;
+
+/// Asks Touch ID or Face ID through macOS LocalAuthentication
+const LOCAL_AUTHENTICATION_SCRIPT: &str = r#"
+ObjC.import('LocalAuthentication');
+ObjC.import('stdlib');
+function run() {
+    const context = $.LAContext.alloc.init;
+    const biometrics = 1; // LAPolicyDeviceOwnerAuthenticationWithBiometrics
+    if (!context.canEvaluatePolicyError(biometrics, null)) { $.exit(2); }
+    const reason = $.NSProcessInfo.processInfo.environment.objectForKey('FACE_AUTH_REASON').js;
+    let done = false, verified = false;
+    context.evaluatePolicyLocalizedReasonReply(biometrics, reason, (success, error) => { verified = success; done = true; });
+    while (!done) { $.NSRunLoop.currentRunLoop.runUntilDate($.NSDate.dateWithTimeIntervalSinceNow(0.1)); }
+    $.exit(verified ? 0 : 1);
+}
+"#;
+
+/// The operating system's own biometric prompt, e.g. Windows Hello or Touch
+/// ID, used when the camera or face model is unavailable
+///
+/// Configured with [`FaceAuthBuilder::platform_fallback`](crate::FaceAuthBuilder::platform_fallback).
+/// Platforms verify the signed-in account rather than telling users apart,
+/// so a fallback vouches for one configured user.
+pub trait PlatformBiometrics: fmt::Debug + Send + Sync {
+    /// Name shown in results and logs, e.g. "Windows Hello"
+    fn name(&self) -> &str;
+
+    /// Prompt the user with `reason`; `Ok(false)` when they failed or
+    /// cancelled, an error when the platform can't verify anyone
+    fn verify(&self, reason: &str) -> Result<bool>;
+}
+
+/// Platform biometrics behind an external command
+///
+/// The reason is passed in [`REASON_VAR`]. Exiting with 0 means verified, 1
+/// means refused; any other exit code, or running past the timeout, means
+/// the platform is unavailable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandBiometrics {
+    pub name: String,
+    pub program: PathBuf,
+    pub args: Vec<String>,
+    pub timeout: Duration,
+}
+
+impl CommandBiometrics {
+    pub fn new(name: impl Into<String>, program: impl Into<PathBuf>) -> Self {
+        Self { name: name.into(), program: program.into(), args: Vec::new(), timeout: Duration::from_secs(60) }
+    }
+
+    pub fn with_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args = args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Windows Hello, via PowerShell
+    pub fn windows_hello() -> Self {
+        Self::new("Windows Hello", "powershell").with_args(["-NoProfile", "-NonInteractive", "-Command", WINDOWS_HELLO_SCRIPT])
+    }
+
+    /// Touch ID or Face ID on macOS, via `osascript`
+    pub fn local_authentication() -> Self {
+        Self::new("Touch ID", "osascript").with_args(["-l", "JavaScript", "-e", LOCAL_AUTHENTICATION_SCRIPT])
+    }
+
+    /// The prompt of the platform this was built for, if it has one
+    pub fn for_current_platform() -> Option<Self> {
+        if cfg!(target_os = "windows") {
+            Some(Self::windows_hello())
+        } else if cfg!(target_os = "macos") {
+            Some(Self::local_authentication())
+        } else {
+            None
+        }
+    }
+}
+
+impl PlatformBiometrics for CommandBiometrics {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn verify(&self, reason: &str) -> Result<bool> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .env(REASON_VAR, reason)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| anyhow!("Failed to start {}: {}", self.name, e))?;
+        let started = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if started.elapsed() > self.timeout {
+                let _ = child.kill();
+                let _ = child.wait();
+                bail!("{} timed out after {:?}", self.name, self.timeout);
+            }
+            thread::sleep(Duration::from_millis(50));
+        };
+        match status.code() {
+            Some(0) => Ok(true),
+            Some(1) => Ok(false),
+            code => bail!("{} is unavailable (exit code {:?})", self.name, code),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_exit_codes_map_to_verdicts() {
+        let command = |script: &str| CommandBiometrics::new("Test", "sh").with_args(["-c", script]);
+        assert!(command(r#"test "$FACE_AUTH_REASON" = "Unlock""#).verify("Unlock").unwrap());
+        assert!(!command("exit 1").verify("Unlock").unwrap());
+        assert!(command("exit 2").verify("Unlock").is_err());
+        assert!(command("sleep 5").with_timeout(Duration::from_millis(100)).verify("Unlock").is_err());
+    }
+}
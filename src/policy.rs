@@ -0,0 +1,175 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+#[cfg(feature = "wasm")]
+use std::path::Path;
+
+use crate::age::AgeEstimate;
+use crate::context::AuthContext;
+use crate::matching::MatchCandidate;
+use crate::multimodal::ModalityScores;
+#[cfg(feature = "wasm")]
+use crate::wasm::WasmModule;
+
+/// What a [`DecisionPolicy`] sees of a matched face
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PolicyInput {
+    /// Closest enrolled users, closest first; the first one matched
+    pub candidates: Vec<MatchCandidate>,
+    /// Frame quality (0.0-1.0), when the backend measured it
+    pub quality: Option<f64>,
+    /// Liveness check outcome, when one ran
+    pub liveness: Option<bool>,
+    pub device_id: Option<String>,
+    /// Caller-supplied context, e.g. the door or resource requested
+    #[serde(default)]
+    pub context: AuthContext,
+    /// Apparent age of the face, when an age estimator is configured
+    #[serde(default)]
+    pub age: Option<AgeEstimate>,
+    /// Apparent age of every face in the frame, largest first
+    #[serde(default)]
+    pub ages: Vec<AgeEstimate>,
+    /// Face and secondary biometric scores, when a
+    /// [`SecondaryBiometric`](crate::multimodal::SecondaryBiometric) is configured
+    #[serde(default)]
+    pub modalities: Option<ModalityScores>,
+}
+
+/// A policy's verdict on a matched face
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum PolicyDecision {
+    Accept,
+    Deny { reason: String },
+    /// Grant only after a second factor such as a badge or PIN
+    StepUp { reason: String },
+}
+
+/// Decision logic run on every match before access is granted
+///
+/// Policies can only tighten the face decision: they run after a match and
+/// may deny it or require a second factor, never grant a face that didn't
+/// match. Errors deny access.
+///
+/// Inputs and decisions serialize to JSON (`{"decision": "step_up", "reason":
+/// ...}`), which is what a [`WasmPolicy`] module exchanges with the host.
+pub trait DecisionPolicy: fmt::Debug + Send + Sync {
+    fn decide(&self, input: &PolicyInput) -> Result<PolicyDecision>;
+}
+
+/// Decision policy shipped as a sandboxed WASM module, e.g. proprietary
+/// logic deployed to kiosks
+///
+/// The module exports `decide`, which gets the [`PolicyInput`] and returns
+/// the [`PolicyDecision`] as JSON, see [`crate::wasm`] for the calling
+/// convention and sandbox. A module that traps, runs out of fuel or returns
+/// anything but a decision denies access.
+#[cfg(feature = "wasm")]
+#[derive(Debug, Clone)]
+pub struct WasmPolicy {
+    module: WasmModule,
+}
+
+#[cfg(feature = "wasm")]
+impl WasmPolicy {
+    /// Compile the policy module in `path`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        WasmModule::load(path).map(Self::new)
+    }
+
+    /// Policy of an already compiled module, e.g. one with its own limits
+    pub fn new(module: WasmModule) -> Self {
+        Self { module }
+    }
+}
+
+#[cfg(feature = "wasm")]
+impl DecisionPolicy for WasmPolicy {
+    fn decide(&self, input: &PolicyInput) -> Result<PolicyDecision> {
+        self.module.call_json("decide", input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decisions_round_trip_as_json() {
+        let step_up = PolicyDecision::StepUp { reason: "High-value transaction".into() };
+        let json = serde_json::to_value(&step_up).unwrap();
+        assert_eq!(json, serde_json::json!({"decision": "step_up", "reason": "High-value transaction"}));
+        assert_eq!(serde_json::from_str::<PolicyDecision>(r#"{"decision": "accept"}"#).unwrap(), PolicyDecision::Accept);
+
+        let input: PolicyInput = serde_json::from_str(
+            r#"{"candidates": [{"user_id": "ann", "distance": 0.3, "is_match": true}], "quality": null, "liveness": true, "device_id": null}"#,
+        ).unwrap();
+        assert_eq!(input.candidates[0].user_id, "ann");
+        assert!(input.context.is_empty());
+        assert!(input.age.is_none());
+        assert!(input.modalities.is_none());
+    }
+
+    /// Denies when the input mentions "vault", accepts otherwise
+    #[cfg(feature = "wasm")]
+    const VAULT_POLICY: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (data (i32.const 0) "vault")
+  (data (i32.const 16) "{\"decision\":\"accept\"}")
+  (data (i32.const 48) "{\"decision\":\"deny\",\"reason\":\"No vault access\"}")
+  (global $next (mut i32) (i32.const 1024))
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (global.set $next (i32.add (global.get $next) (local.get $len)))
+    (local.get $ptr))
+  (func (export "decide") (param $ptr i32) (param $len i32) (result i64)
+    (local $i i32) (local $j i32)
+    (block $done
+      (loop $outer
+        (br_if $done (i32.gt_u (i32.add (local.get $i) (i32.const 5)) (local.get $len)))
+        (local.set $j (i32.const 0))
+        (block $mismatch
+          (loop $inner
+            (if (i32.eq (local.get $j) (i32.const 5))
+              (then (return (i64.const 206158430254))))
+            (br_if $mismatch (i32.ne
+              (i32.load8_u (i32.add (local.get $ptr) (i32.add (local.get $i) (local.get $j))))
+              (i32.load8_u (local.get $j))))
+            (local.set $j (i32.add (local.get $j) (i32.const 1)))
+            (br $inner)))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $outer)))
+    (i64.const 68719476757)))
+"#;
+
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn test_wasm_policies_decide_in_a_sandbox() {
+        let policy = WasmPolicy::new(WasmModule::from_bytes(VAULT_POLICY.as_bytes()).unwrap());
+        let input = |resource: &str| PolicyInput {
+            candidates: vec![MatchCandidate { user_id: "ann".into(), distance: 0.3, is_match: true }],
+            context: AuthContext::new().with("resource", resource),
+            ..PolicyInput::default()
+        };
+        assert_eq!(policy.decide(&input("lobby")).unwrap(), PolicyDecision::Accept);
+        assert_eq!(policy.decide(&input("vault")).unwrap(), PolicyDecision::Deny { reason: "No vault access".into() });
+
+        // Endless loops run out of fuel, and modules can't import anything
+        let spinning = r#"(module (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32) (i32.const 0))
+            (func (export "decide") (param i32 i32) (result i64) (loop $forever (br $forever)) (i64.const 0)))"#;
+        let spinning = WasmPolicy::new(WasmModule::from_bytes(spinning.as_bytes()).unwrap().with_fuel(100_000));
+        assert!(spinning.decide(&input("lobby")).is_err());
+        let importing = r#"(module (import "env" "open" (func)) (memory (export "memory") 1))"#;
+        assert!(WasmModule::from_bytes(importing.as_bytes()).is_err());
+
+        // Output outside memory or not a decision is an error
+        let garbage = r#"(module (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32) (i32.const 0))
+            (func (export "decide") (param i32 i32) (result i64) (i64.const 0x0000ffff00000010)))"#;
+        assert!(WasmPolicy::new(WasmModule::from_bytes(garbage.as_bytes()).unwrap()).decide(&input("lobby")).is_err());
+    }
+}
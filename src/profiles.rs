@@ -0,0 +1,212 @@
+use anyhow::{Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+#[cfg(feature = "python-backend")]
+use std::sync::{Mutex, PoisonError};
+
+/// Adjustments applied to a frame before face detection
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Preprocessing {
+    /// Local histogram equalization of the luminance, for backlit or uneven light
+    #[serde(default)]
+    pub equalize: bool,
+    /// Gamma correction; below 1.0 brightens, above 1.0 darkens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gamma: Option<f64>,
+}
+
+impl Preprocessing {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Tolerance and preprocessing for one environment, e.g. `indoor-kiosk`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThresholdProfile {
+    pub name: String,
+    pub tolerance: f64,
+    /// Mean gray level of the frame (0-255) from which the profile is picked
+    /// automatically
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_brightness: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_brightness: Option<f64>,
+    #[serde(default, skip_serializing_if = "Preprocessing::is_empty")]
+    pub preprocessing: Preprocessing,
+}
+
+impl ThresholdProfile {
+    pub fn new(name: impl Into<String>, tolerance: f64) -> Self {
+        Self { name: name.into(), tolerance, min_brightness: None, max_brightness: None, preprocessing: Preprocessing::default() }
+    }
+
+    /// Pick this profile automatically for frames with a brightness in `min..=max`
+    pub fn with_brightness(mut self, min: f64, max: f64) -> Self {
+        self.min_brightness = Some(min);
+        self.max_brightness = Some(max);
+        self
+    }
+
+    pub fn with_preprocessing(mut self, preprocessing: Preprocessing) -> Self {
+        self.preprocessing = preprocessing;
+        self
+    }
+
+    /// Whether the profile has a brightness range that contains `brightness`
+    fn covers(&self, brightness: f64) -> bool {
+        (self.min_brightness.is_some() || self.max_brightness.is_some())
+            && self.min_brightness.is_none_or(|min| brightness >= min)
+            && self.max_brightness.is_none_or(|max| brightness <= max)
+    }
+}
+
+/// Named threshold profiles as stored in a config file
+///
+/// ```json
+/// {
+///   "default": "indoor-kiosk",
+///   "profiles": [
+///     { "name": "indoor-kiosk", "tolerance": 0.6, "max_brightness": 170 },
+///     { "name": "outdoor-gate-backlit", "tolerance": 0.5, "min_brightness": 170,
+///       "preprocessing": { "equalize": true, "gamma": 1.4 } }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThresholdProfiles {
+    /// Used when no profile covers the measured brightness
+    pub default: String,
+    pub profiles: Vec<ThresholdProfile>,
+}
+
+impl ThresholdProfiles {
+    pub fn new(default: ThresholdProfile) -> Self {
+        Self { default: default.name.clone(), profiles: vec![default] }
+    }
+
+    pub fn with_profile(mut self, profile: ThresholdProfile) -> Self {
+        self.profiles.push(profile);
+        self
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        let profiles: Self = serde_json::from_str(&json).map_err(|e| anyhow!("Invalid profiles {}: {}", path.display(), e))?;
+        profiles.validate().map_err(|e| anyhow!("Invalid profiles {}: {}", path.display(), e))?;
+        Ok(profiles)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for (index, profile) in self.profiles.iter().enumerate() {
+            if self.profiles[..index].iter().any(|p| p.name == profile.name) {
+                bail!("Profile '{}' is defined twice", profile.name);
+            }
+            if !(profile.tolerance.is_finite() && profile.tolerance > 0.0) {
+                bail!("Profile '{}' has an invalid tolerance {}", profile.name, profile.tolerance);
+            }
+            if profile.preprocessing.gamma.is_some_and(|g| !(g.is_finite() && g > 0.0)) {
+                bail!("Profile '{}' has an invalid gamma", profile.name);
+            }
+        }
+        self.get(&self.default).map(|_| ())
+    }
+
+    pub fn get(&self, name: &str) -> Result<&ThresholdProfile> {
+        self.profiles.iter().find(|p| p.name == name).ok_or_else(|| anyhow!("Unknown threshold profile '{}'", name))
+    }
+
+    /// First profile whose brightness range contains `brightness`, else the default
+    pub fn for_brightness(&self, brightness: Option<f64>) -> &ThresholdProfile {
+        brightness
+            .and_then(|b| self.profiles.iter().find(|p| p.covers(b)))
+            .unwrap_or_else(|| self.get(&self.default).expect("validated default profile"))
+    }
+}
+
+/// [`ThresholdProfiles`] with the runtime choice between a fixed profile and
+/// automatic selection by brightness
+#[cfg(feature = "python-backend")]
+#[derive(Debug)]
+pub(crate) struct ProfileSwitcher {
+    profiles: ThresholdProfiles,
+    fixed: Mutex<Option<String>>,
+    last_brightness: Mutex<Option<f64>>,
+}
+
+#[cfg(feature = "python-backend")]
+impl ProfileSwitcher {
+    pub(crate) fn new(profiles: ThresholdProfiles) -> Result<Self> {
+        profiles.validate()?;
+        Ok(Self { profiles, fixed: Mutex::new(None), last_brightness: Mutex::new(None) })
+    }
+
+    /// Use the named profile for every frame, or select automatically again with `None`
+    pub(crate) fn set_fixed(&self, name: Option<&str>) -> Result<()> {
+        if let Some(name) = name {
+            self.profiles.get(name)?;
+        }
+        *self.fixed.lock().unwrap_or_else(PoisonError::into_inner) = name.map(str::to_string);
+        Ok(())
+    }
+
+    fn fixed(&self) -> Option<&ThresholdProfile> {
+        let fixed = self.fixed.lock().unwrap_or_else(PoisonError::into_inner);
+        fixed.as_deref().and_then(|name| self.profiles.get(name).ok())
+    }
+
+    /// Profile for a frame not yet captured, going by the last measured brightness
+    pub(crate) fn for_next_frame(&self) -> &ThresholdProfile {
+        self.fixed().unwrap_or_else(|| {
+            self.profiles.for_brightness(*self.last_brightness.lock().unwrap_or_else(PoisonError::into_inner))
+        })
+    }
+
+    /// Profile for a captured frame, remembering its brightness for the next one
+    pub(crate) fn for_frame(&self, brightness: Option<f64>) -> &ThresholdProfile {
+        if brightness.is_some() {
+            *self.last_brightness.lock().unwrap_or_else(PoisonError::into_inner) = brightness;
+        }
+        match self.fixed() {
+            Some(profile) => profile,
+            None if brightness.is_some() => self.profiles.for_brightness(brightness),
+            None => self.for_next_frame(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "python-backend"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles_switch_by_brightness_and_name() {
+        let json = r#"{
+            "default": "indoor-kiosk",
+            "profiles": [
+                { "name": "indoor-kiosk", "tolerance": 0.6, "max_brightness": 170 },
+                { "name": "outdoor-gate-backlit", "tolerance": 0.5, "min_brightness": 170,
+                  "preprocessing": { "equalize": true, "gamma": 1.4 } },
+                { "name": "night", "tolerance": 0.55 }
+            ]
+        }"#;
+        let profiles: ThresholdProfiles = serde_json::from_str(json).unwrap();
+        let switcher = ProfileSwitcher::new(profiles.clone()).unwrap();
+        assert_eq!(switcher.for_next_frame().name, "indoor-kiosk");
+        assert_eq!(switcher.for_frame(Some(210.0)).name, "outdoor-gate-backlit");
+        assert!(switcher.for_next_frame().preprocessing.equalize, "the next frame follows the last brightness");
+        assert_eq!(switcher.for_frame(None).name, "outdoor-gate-backlit");
+        assert_eq!(switcher.for_frame(Some(90.0)).name, "indoor-kiosk");
+
+        switcher.set_fixed(Some("night")).unwrap();
+        assert_eq!(switcher.for_frame(Some(210.0)).name, "night");
+        assert!(switcher.set_fixed(Some("beach")).is_err());
+        switcher.set_fixed(None).unwrap();
+        assert_eq!(switcher.for_frame(Some(210.0)).name, "outdoor-gate-backlit");
+
+        let mut invalid = profiles;
+        invalid.default = "lobby".into();
+        assert!(ProfileSwitcher::new(invalid).is_err());
+    }
+}
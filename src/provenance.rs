@@ -0,0 +1,246 @@
+use anyhow::{Result, bail};
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Where an uploaded image came from, recorded in the audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageProvenance {
+    /// Who or what supplied the image, e.g. a file path or a device id
+    pub source: String,
+    /// SHA-256 of the image as received, before scrubbing
+    pub sha256: String,
+    pub received_at: DateTime<Utc>,
+    /// When the camera says it took the picture, from EXIF
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub captured_at: Option<DateTime<Utc>>,
+    /// Whether EXIF, GPS or other metadata was removed
+    pub metadata_stripped: bool,
+}
+
+impl ImageProvenance {
+    pub fn new(source: impl Into<String>, received: &[u8], scrubbed: &ScrubbedImage) -> Self {
+        Self {
+            source: source.into(),
+            sha256: Sha256::digest(received).iter().map(|byte| format!("{:02x}", byte)).collect(),
+            received_at: Utc::now(),
+            captured_at: scrubbed.captured_at,
+            metadata_stripped: scrubbed.stripped,
+        }
+    }
+}
+
+/// An image with its metadata removed
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScrubbedImage {
+    pub bytes: Vec<u8>,
+    /// Capture time read from the EXIF data before it was removed
+    pub captured_at: Option<DateTime<Utc>>,
+    pub stripped: bool,
+    /// File extension matching the format, e.g. `jpg`
+    pub extension: &'static str,
+}
+
+/// Remove EXIF, GPS, XMP, IPTC, comments and text chunks from a JPEG or PNG
+///
+/// Pixel data and color profiles are kept as they are. Other formats are
+/// returned unchanged.
+pub fn scrub_metadata(image: &[u8]) -> Result<ScrubbedImage> {
+    if image.starts_with(&[0xFF, 0xD8]) {
+        scrub_jpeg(image)
+    } else if image.starts_with(PNG_SIGNATURE) {
+        scrub_png(image)
+    } else {
+        Ok(ScrubbedImage { bytes: image.to_vec(), captured_at: None, stripped: false, extension: "img" })
+    }
+}
+
+fn scrub_jpeg(image: &[u8]) -> Result<ScrubbedImage> {
+    let mut scrubbed = ScrubbedImage { bytes: image[..2].to_vec(), captured_at: None, stripped: false, extension: "jpg" };
+    let mut position = 2;
+    while position < image.len() {
+        if image[position] != 0xFF || position + 1 >= image.len() {
+            bail!("Corrupt JPEG: expected a marker at byte {}", position);
+        }
+        let marker = image[position + 1];
+        // Fill bytes, restart markers and other markers without a length
+        if marker == 0xFF {
+            position += 1;
+            continue;
+        }
+        if matches!(marker, 0x01 | 0xD0..=0xD7) {
+            scrubbed.bytes.extend_from_slice(&image[position..position + 2]);
+            position += 2;
+            continue;
+        }
+        let Some(&[high, low]) = image.get(position + 2..position + 4) else {
+            bail!("Corrupt JPEG: truncated segment at byte {}", position);
+        };
+        let length = u16::from_be_bytes([high, low]) as usize;
+        let end = position + 2 + length;
+        if length < 2 || end > image.len() {
+            bail!("Corrupt JPEG: segment at byte {} runs past the end", position);
+        }
+        // Start of scan: the entropy-coded image data follows, copy it all
+        if marker == 0xDA {
+            scrubbed.bytes.extend_from_slice(&image[position..]);
+            break;
+        }
+        let segment = &image[position..end];
+        // APP0 (JFIF), APP2 (ICC profile) and APP14 (Adobe color transform) are needed to decode
+        let metadata = matches!(marker, 0xE1 | 0xE3..=0xED | 0xEF | 0xFE);
+        if marker == 0xE1 && segment[4..].starts_with(b"Exif\0\0") {
+            scrubbed.captured_at = scrubbed.captured_at.or_else(|| exif_capture_time(&segment[10..]));
+        }
+        if metadata {
+            scrubbed.stripped = true;
+        } else {
+            scrubbed.bytes.extend_from_slice(segment);
+        }
+        position = end;
+    }
+    Ok(scrubbed)
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+fn scrub_png(image: &[u8]) -> Result<ScrubbedImage> {
+    let mut scrubbed = ScrubbedImage { bytes: PNG_SIGNATURE.to_vec(), captured_at: None, stripped: false, extension: "png" };
+    let mut position = PNG_SIGNATURE.len();
+    while position < image.len() {
+        let Some(header) = image.get(position..position + 8) else {
+            bail!("Corrupt PNG: truncated chunk at byte {}", position);
+        };
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let end = position + 12 + length;
+        if end > image.len() {
+            bail!("Corrupt PNG: chunk at byte {} runs past the end", position);
+        }
+        let kind = &header[4..8];
+        if kind == b"eXIf" {
+            scrubbed.captured_at = exif_capture_time(&image[position + 8..end - 4]);
+        }
+        if matches!(kind, b"eXIf" | b"tEXt" | b"zTXt" | b"iTXt" | b"tIME") {
+            scrubbed.stripped = true;
+        } else {
+            scrubbed.bytes.extend_from_slice(&image[position..end]);
+        }
+        position = end;
+    }
+    Ok(scrubbed)
+}
+
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_OFFSET_TIME_ORIGINAL: u16 = 0x9011;
+
+/// Capture time in a TIFF-structured EXIF block
+///
+/// Prefers `DateTimeOriginal` over the file's `DateTime`. EXIF times carry
+/// no zone unless `OffsetTimeOriginal` is present; they're read as this
+/// machine's local time otherwise.
+fn exif_capture_time(tiff: &[u8]) -> Option<DateTime<Utc>> {
+    let big_endian = match tiff.get(..4)? {
+        b"MM\0*" => true,
+        b"II*\0" => false,
+        _ => return None,
+    };
+    let u16_at = |offset: usize| -> Option<u16> {
+        let bytes = [*tiff.get(offset)?, *tiff.get(offset + 1)?];
+        Some(if big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    };
+    let u32_at = |offset: usize| -> Option<u32> {
+        let bytes: [u8; 4] = tiff.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    };
+    // Offset of the entry of `tag` in the IFD at `ifd`
+    let find = |ifd: usize, tag: u16| -> Option<usize> {
+        let count = u16_at(ifd)? as usize;
+        (0..count).map(|i| ifd + 2 + i * 12).find(|&entry| u16_at(entry) == Some(tag))
+    };
+    // ASCII values up to 4 bytes are stored in the entry itself
+    let text = |ifd: usize, tag: u16| -> Option<String> {
+        let entry = find(ifd, tag)?;
+        if u16_at(entry + 2)? != 2 {
+            return None;
+        }
+        let count = u32_at(entry + 4)? as usize;
+        let start = if count <= 4 { entry + 8 } else { u32_at(entry + 8)? as usize };
+        let raw = tiff.get(start..start.checked_add(count)?)?;
+        Some(String::from_utf8_lossy(raw).trim_end_matches('\0').trim().to_string())
+    };
+
+    let ifd0 = u32_at(4)? as usize;
+    let exif_ifd = find(ifd0, TAG_EXIF_IFD).and_then(|entry| u32_at(entry + 8)).map(|offset| offset as usize);
+    let (taken, offset) = match exif_ifd.and_then(|ifd| Some((text(ifd, TAG_DATE_TIME_ORIGINAL)?, ifd))) {
+        Some((taken, ifd)) => (taken, text(ifd, TAG_OFFSET_TIME_ORIGINAL)),
+        None => (text(ifd0, TAG_DATE_TIME)?, None),
+    };
+    let taken = NaiveDateTime::parse_from_str(&taken, "%Y:%m:%d %H:%M:%S").ok()?;
+    let local = match offset.and_then(|offset| offset.parse::<FixedOffset>().ok()) {
+        Some(offset) => offset.from_local_datetime(&taken).single()?.with_timezone(&Utc),
+        None => Local.from_local_datetime(&taken).earliest()?.with_timezone(&Utc),
+    };
+    Some(local)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Little-endian EXIF block with an Exif IFD holding DateTimeOriginal and OffsetTimeOriginal
+    fn exif_block(taken: &str, offset: &str) -> Vec<u8> {
+        let mut tiff = b"II*\0".to_vec();
+        tiff.extend_from_slice(&8u32.to_le_bytes());
+        // IFD0 at 8: one entry pointing at the Exif IFD at 26
+        tiff.extend_from_slice(&1u16.to_le_bytes());
+        tiff.extend_from_slice(&TAG_EXIF_IFD.to_le_bytes());
+        tiff.extend_from_slice(&4u16.to_le_bytes());
+        tiff.extend_from_slice(&1u32.to_le_bytes());
+        tiff.extend_from_slice(&26u32.to_le_bytes());
+        tiff.extend_from_slice(&0u32.to_le_bytes());
+        // Exif IFD at 26: two ASCII entries, strings after it at 56
+        tiff.extend_from_slice(&2u16.to_le_bytes());
+        let offset_at = 56 + taken.len() as u32 + 1;
+        for (tag, text, at) in [(TAG_DATE_TIME_ORIGINAL, taken, 56u32), (TAG_OFFSET_TIME_ORIGINAL, offset, offset_at)] {
+            tiff.extend_from_slice(&tag.to_le_bytes());
+            tiff.extend_from_slice(&2u16.to_le_bytes());
+            tiff.extend_from_slice(&(text.len() as u32 + 1).to_le_bytes());
+            tiff.extend_from_slice(&at.to_le_bytes());
+        }
+        tiff.extend_from_slice(&0u32.to_le_bytes());
+        for text in [taken, offset] {
+            tiff.extend_from_slice(text.as_bytes());
+            tiff.push(0);
+        }
+        tiff
+    }
+
+    #[test]
+    fn test_exif_is_stripped_and_capture_time_kept() {
+        let exif = [b"Exif\0\0".as_slice(), &exif_block("2024:03:01 09:30:00", "+02:00")].concat();
+        let segment = |marker: u8, data: &[u8]| [&[0xFF, marker][..], &(data.len() as u16 + 2).to_be_bytes(), data].concat();
+        let jpeg = [
+            &[0xFF, 0xD8][..],
+            &segment(0xE0, b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0"),
+            &segment(0xE1, &exif),
+            &segment(0xFE, b"shot at 52.52N 13.40E"),
+            &segment(0xDA, &[1, 2, 3]),
+            &[0x12, 0x34, 0xFF, 0xD9],
+        ].concat();
+
+        let scrubbed = scrub_metadata(&jpeg).unwrap();
+        assert!(scrubbed.stripped);
+        assert_eq!(scrubbed.extension, "jpg");
+        assert_eq!(scrubbed.captured_at, Some(Utc.with_ymd_and_hms(2024, 3, 1, 7, 30, 0).unwrap()));
+        let expected = [&[0xFF, 0xD8][..], &segment(0xE0, b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0"), &segment(0xDA, &[1, 2, 3]), &[0x12, 0x34, 0xFF, 0xD9]].concat();
+        assert_eq!(scrubbed.bytes, expected);
+        assert!(!scrub_metadata(&expected).unwrap().stripped);
+        assert!(scrub_metadata(&jpeg[..30]).is_err());
+
+        let provenance = ImageProvenance::new("kiosk-3", &jpeg, &scrubbed);
+        assert_eq!(provenance.sha256.len(), 64);
+        assert_eq!(provenance.captured_at, scrubbed.captured_at);
+    }
+}
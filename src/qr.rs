@@ -0,0 +1,57 @@
+//! Badge QR codes
+//!
+//! At large sites, matching a face against everyone enrolled is slow and
+//! compares it with people who have nothing to do with the attempt. A kiosk
+//! can instead read who the user claims to be from the QR code on their badge
+//! and verify the face against that one user, see
+//! [`FaceAuth::verify_badge`](crate::FaceAuth::verify_badge). The QR code
+//! holds the username as plain text.
+
+use anyhow::{Context, Result};
+
+/// Text of the first readable QR code in an encoded image (JPEG or PNG),
+/// `None` if there is none
+pub fn decode_qr(image: &[u8]) -> Result<Option<String>> {
+    let image = image::load_from_memory(image).context("Failed to decode the badge image")?.to_luma8();
+    let mut prepared = rqrr::PreparedImage::prepare_from_greyscale(image.width() as usize, image.height() as usize, |x, y| {
+        image.get_pixel(x as u32, y as u32).0[0]
+    });
+    Ok(prepared.detect_grids().into_iter().find_map(|grid| match grid.decode() {
+        Ok((_, content)) => Some(content.trim().to_string()),
+        Err(e) => {
+            tracing::debug!("Skipping unreadable QR code: {}", e);
+            None
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// PNG of `image`
+    fn png(image: image::GrayImage) -> Vec<u8> {
+        let mut png = Vec::new();
+        image.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png).unwrap();
+        png
+    }
+
+    /// A QR code holding `content`, as printed on a badge
+    fn badge(content: &str) -> image::GrayImage {
+        let code = qrcode::QrCode::new(content).unwrap();
+        let (modules, scale, quiet) = (code.width() as u32, 6, 4);
+        let side = (modules + 2 * quiet) * scale;
+        image::GrayImage::from_fn(side, side, |x, y| {
+            let (x, y) = ((x / scale).checked_sub(quiet), (y / scale).checked_sub(quiet));
+            let dark = matches!((x, y), (Some(x), Some(y)) if x < modules && y < modules && code[(x as usize, y as usize)] == qrcode::Color::Dark);
+            image::Luma([if dark { 0 } else { 255 }])
+        })
+    }
+
+    #[test]
+    fn test_decode_badge() {
+        assert_eq!(decode_qr(&png(badge("ann"))).unwrap().as_deref(), Some("ann"));
+        assert!(decode_qr(&png(image::GrayImage::from_pixel(64, 64, image::Luma([255])))).unwrap().is_none());
+        assert!(decode_qr(b"not an image").is_err());
+    }
+}
@@ -0,0 +1,76 @@
+//! Where non-secret randomness comes from
+//!
+//! Sample id suffixes and server nonces draw from a [`RandomSource`]: the
+//! operating system's generator by default, or a [`SeededRandom`] that repeats
+//! the same sequence, set with
+//! [`FaceAuthBuilder::random`](crate::FaceAuthBuilder::random) for
+//! deterministic tests. Keys, salts and sketches always use the operating
+//! system's generator.
+
+use chacha20poly1305::aead::OsRng;
+use chacha20poly1305::aead::rand_core::RngCore;
+use std::fmt;
+use std::sync::{Mutex, PoisonError};
+
+/// Source of random bytes
+pub trait RandomSource: fmt::Debug + Send + Sync {
+    fn fill_bytes(&self, dest: &mut [u8]);
+
+    /// `len` random bytes as lowercase hex
+    fn hex(&self, len: usize) -> String {
+        let mut bytes = vec![0u8; len];
+        self.fill_bytes(&mut bytes);
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+/// The operating system's generator
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OsRandom;
+
+impl RandomSource for OsRandom {
+    fn fill_bytes(&self, dest: &mut [u8]) {
+        OsRng.fill_bytes(dest);
+    }
+}
+
+/// A repeatable sequence for tests (SplitMix64); never use it for secrets
+#[derive(Debug)]
+pub struct SeededRandom {
+    state: Mutex<u64>,
+}
+
+impl SeededRandom {
+    pub fn new(seed: u64) -> Self {
+        Self { state: Mutex::new(seed) }
+    }
+}
+
+impl RandomSource for SeededRandom {
+    fn fill_bytes(&self, dest: &mut [u8]) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        for chunk in dest.chunks_mut(8) {
+            *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = *state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^= z >> 31;
+            chunk.copy_from_slice(&z.to_le_bytes()[..chunk.len()]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_sources_repeat_their_sequence() {
+        let (first, second) = (SeededRandom::new(7), SeededRandom::new(7));
+        let hex = first.hex(12);
+        assert_eq!(hex.len(), 24);
+        assert_eq!(second.hex(12), hex);
+        assert_ne!(first.hex(12), hex);
+        assert_ne!(SeededRandom::new(8).hex(12), hex);
+    }
+}
@@ -0,0 +1,162 @@
+//! Blurring or pixelating faces in arbitrary images
+//!
+//! The detection behind [`crate::FrameBlur`], usable on any JPEG or PNG, e.g.
+//! to anonymize photos before sharing them:
+//!
+//! ```no_run
+//! use face_auth::redact::{self, RedactionPolicy, RedactionStyle};
+//!
+//! let photo = std::fs::read("lobby.jpg")?;
+//! let policy = RedactionPolicy::all_faces().with_style(RedactionStyle::Pixelate { block: 16 });
+//! let redacted = redact::redact_faces(&photo, &policy)?;
+//! std::fs::write("lobby-redacted.jpg", &redacted.image)?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::FaceBackend;
+use crate::standalone_python::StandalonePythonFaceAuth;
+
+/// How faces are made unrecognizable
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RedactionStyle {
+    /// Gaussian blur scaled to the face size
+    #[default]
+    Blur,
+    /// Blocks of `block` pixels of the face's average color
+    Pixelate { block: u32 },
+}
+
+/// Which faces to redact and how
+#[derive(Debug, Clone, PartialEq)]
+pub struct RedactionPolicy {
+    /// Leave the largest face, the person in front of the camera, untouched
+    pub keep_largest: bool,
+    pub style: RedactionStyle,
+    /// Share of the face height added on every side of its box
+    pub padding: f64,
+}
+
+impl Default for RedactionPolicy {
+    fn default() -> Self {
+        Self { keep_largest: false, style: RedactionStyle::Blur, padding: 0.25 }
+    }
+}
+
+impl RedactionPolicy {
+    /// Redact every face found
+    pub fn all_faces() -> Self {
+        Self::default()
+    }
+
+    /// Redact every face but the largest one
+    pub fn bystanders() -> Self {
+        Self { keep_largest: true, ..Self::default() }
+    }
+
+    pub fn with_style(mut self, style: RedactionStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn with_padding(mut self, padding: f64) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        if !(0.0..=2.0).contains(&self.padding) {
+            bail!("Redaction padding must be between 0 and 2, got {}", self.padding);
+        }
+        if let RedactionStyle::Pixelate { block: 0 } = self.style {
+            bail!("Pixelation block size must be at least one pixel");
+        }
+        Ok(())
+    }
+}
+
+/// Pixel box of a detected face, before padding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "[u32; 4]", into = "[u32; 4]")]
+pub struct FaceBox {
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    pub left: u32,
+}
+
+impl From<[u32; 4]> for FaceBox {
+    fn from([top, right, bottom, left]: [u32; 4]) -> Self {
+        Self { top, right, bottom, left }
+    }
+}
+
+impl From<FaceBox> for [u32; 4] {
+    fn from(face: FaceBox) -> Self {
+        [face.top, face.right, face.bottom, face.left]
+    }
+}
+
+/// A face found in the image and whether the policy redacted it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DetectedFace {
+    #[serde(rename = "box")]
+    pub bounds: FaceBox,
+    pub redacted: bool,
+}
+
+/// The image with faces redacted, in the format it was given in
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redaction {
+    pub image: Vec<u8>,
+    pub faces: Vec<DetectedFace>,
+}
+
+impl Redaction {
+    /// Number of faces blurred or pixelated
+    pub fn redacted(&self) -> usize {
+        self.faces.iter().filter(|face| face.redacted).count()
+    }
+}
+
+/// Detect and redact faces in a JPEG or PNG with the bundled Python script
+///
+/// Other formats are re-encoded as JPEG. The image is passed on stdin and
+/// the result read back from the script's output, never written to disk.
+pub fn redact_faces(image: &[u8], policy: &RedactionPolicy) -> Result<Redaction> {
+    redact_faces_with(&StandalonePythonFaceAuth::new()?, image, policy)
+}
+
+/// Like [`redact_faces`], detecting faces with `backend`
+pub fn redact_faces_with(backend: &dyn FaceBackend, image: &[u8], policy: &RedactionPolicy) -> Result<Redaction> {
+    policy.validate()?;
+    backend.redact_faces(image, policy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FakeBackend;
+
+    #[test]
+    fn test_policy_decides_which_faces_are_redacted() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = FakeBackend::new(dir.path()).unwrap().with_frame("alice", vec![0.1; 128]).with_frame("empty", vec![]);
+
+        let everyone = redact_faces_with(&backend, b"alice", &RedactionPolicy::all_faces()).unwrap();
+        assert_eq!(everyone.redacted(), 1);
+        assert_eq!(everyone.image, b"alice\nredacted: blur");
+
+        let bystanders = redact_faces_with(&backend, b"alice", &RedactionPolicy::bystanders()).unwrap();
+        assert_eq!((bystanders.faces.len(), bystanders.redacted()), (1, 0));
+        assert_eq!(bystanders.image, b"alice");
+
+        let empty = redact_faces_with(&backend, b"empty", &RedactionPolicy::all_faces()).unwrap();
+        assert!(empty.faces.is_empty());
+
+        let invalid = RedactionPolicy::all_faces().with_style(RedactionStyle::Pixelate { block: 0 });
+        assert!(redact_faces_with(&backend, b"alice", &invalid).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::face_storage::{write_atomic, FaceSample};
+
+/// Samples closer than this to the previous stored sample are treated as the
+/// same frame captured again (mirrored by the Python script)
+pub const DUPLICATE_SAMPLE_EPSILON: f64 = 0.04;
+
+/// Extra captures per sample when the frame repeats the previous sample
+pub const DUPLICATE_SAMPLE_RETRIES: u32 = 2;
+
+/// Interrupted registrations last updated longer ago start over, as someone
+/// else may be in front of the camera by then
+pub const PROGRESS_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// What happened to one requested registration sample
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleStatus {
+    /// Face detected, encoded and stored
+    Stored,
+    /// The camera did not deliver a frame
+    CaptureFailed,
+    /// A frame was captured but no usable face was found
+    NoFace,
+    /// Every capture nearly repeated the previous sample, so none was stored
+    Duplicate,
+}
+
+/// Quality assessment of a stored sample
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SampleQuality {
+    /// Combined score (0.0-1.0, higher is better)
+    pub score: f64,
+    /// Variance of the Laplacian over the face region
+    pub sharpness: f64,
+    /// Fraction of the frame covered by the face
+    pub face_ratio: f64,
+}
+
+/// Per-sample registration report
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct SampleOutcome {
+    /// 1-based sample number
+    pub index: u32,
+    pub status: SampleStatus,
+    pub quality: Option<SampleQuality>,
+}
+
+/// Result of a registration attempt
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct RegistrationOutcome {
+    pub username: String,
+    pub samples_requested: u32,
+    pub samples_captured: u32,
+    pub samples: Vec<SampleOutcome>,
+    /// Credential file written to the generated directory
+    pub generated_file: Option<PathBuf>,
+    /// All requested samples were stored and the credential file was written
+    pub fully_enrolled: bool,
+    /// Thumbnail of the best sample, encrypted if a thumbnail key is configured
+    #[serde(default)]
+    pub thumbnail_file: Option<PathBuf>,
+    /// Credential copied into the source directory by auto-promotion
+    #[serde(default)]
+    pub promoted_file: Option<PathBuf>,
+}
+
+/// Whether `encoding` nearly repeats one of the `kept` samples, the
+/// duplicate check of the registration quality gate
+#[cfg(feature = "python-backend")]
+pub(crate) fn repeats_sample(kept: &[FaceSample], encoding: &[f64]) -> bool {
+    kept.iter().any(|sample| crate::matching::face_distance(&sample.encoding, encoding) < DUPLICATE_SAMPLE_EPSILON)
+}
+
+impl RegistrationOutcome {
+    /// Whether at least one sample was stored for the user
+    pub fn is_registered(&self) -> bool {
+        self.samples_captured > 0 && self.generated_file.is_some()
+    }
+}
+
+/// Samples an unfinished registration has stored so far
+///
+/// Backends record it after every sample in
+/// [`StorageLayout::registration_progress_dir`](crate::StorageLayout::registration_progress_dir)
+/// and remove it once the registration ends. If the process dies in
+/// between, the next registration of the user with the same number of
+/// samples within [`PROGRESS_TTL`] continues after the last recorded one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct EnrollmentProgress {
+    pub username: String,
+    pub samples_requested: u32,
+    /// Outcomes of the samples attempted so far, in order
+    pub samples: Vec<SampleOutcome>,
+    /// Encodings of the stored samples
+    pub face_encodings: Vec<FaceSample>,
+    pub updated_at: String,
+}
+
+impl EnrollmentProgress {
+    /// Progress in `path`, `None` if no registration was interrupted
+    pub fn load(path: impl AsRef<Path>) -> Result<Option<Self>> {
+        let path = path.as_ref();
+        match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map(Some)
+                .with_context(|| format!("Invalid registration progress {}", path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        write_atomic(path.as_ref(), serde_json::to_string(self)?.as_bytes())
+    }
+
+    /// 1-based number of the next sample to capture
+    pub fn next_index(&self) -> u32 {
+        self.samples.len() as u32 + 1
+    }
+
+    /// Whether a registration of `samples` samples continues from here;
+    /// progress of a different size is started over
+    pub fn resumes(&self, samples: u32) -> bool {
+        self.samples_requested == samples
+    }
+
+    /// When the progress was last recorded, from the local time in
+    /// `updated_at`; `None` if it doesn't parse
+    pub fn last_update(&self) -> Option<DateTime<Utc>> {
+        if let Ok(time) = DateTime::parse_from_rfc3339(&self.updated_at) {
+            return Some(time.with_timezone(&Utc));
+        }
+        let naive = NaiveDateTime::parse_from_str(&self.updated_at, "%Y-%m-%dT%H:%M:%S%.f").ok()?;
+        Local.from_local_datetime(&naive).earliest().map(|time| time.with_timezone(&Utc))
+    }
+}
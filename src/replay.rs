@@ -0,0 +1,256 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use crate::backend::FaceBackend;
+use crate::compression::{self, Compression};
+use crate::standalone_python::StandaloneAuthResult;
+
+/// Decision made on one frame
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedDecision {
+    pub is_match: bool,
+    pub matched_user: Option<String>,
+    pub closest_user: Option<String>,
+    pub distance: Option<f64>,
+    pub threshold: Option<f64>,
+}
+
+impl From<&StandaloneAuthResult> for RecordedDecision {
+    fn from(result: &StandaloneAuthResult) -> Self {
+        Self {
+            is_match: result.is_match.unwrap_or(false),
+            matched_user: result.matched_user.clone(),
+            closest_user: result.closest_user.clone(),
+            distance: result.distance,
+            threshold: result.threshold,
+        }
+    }
+}
+
+/// A recorded live authentication: the raw frame and the decision made on it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    /// Frame file, relative to the recorder's frames directory
+    pub frame: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+    pub decision: RecordedDecision,
+}
+
+/// Stores frames and decisions of live sessions
+///
+/// Records are appended as JSON lines to `sessions.jsonl`; frames are
+/// copied into `frames/` so captures can be cleaned up independently.
+#[derive(Debug, Clone)]
+pub struct SessionRecorder {
+    dir: PathBuf,
+    compression: Compression,
+}
+
+impl SessionRecorder {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into(), compression: Compression::None }
+    }
+
+    /// Store new frames compressed, with a `.zst` suffix, unless they are JPEG
+    /// or PNG images already; frames already recorded are read either way
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn log_path(&self) -> PathBuf {
+        self.dir.join("sessions.jsonl")
+    }
+
+    pub fn frames_dir(&self) -> PathBuf {
+        self.dir.join("frames")
+    }
+
+    /// Absolute path of a record's frame
+    pub fn frame_path(&self, record: &SessionRecord) -> PathBuf {
+        self.frames_dir().join(&record.frame)
+    }
+
+    /// Contents of a record's frame, decompressed if it was stored compressed
+    pub fn frame(&self, record: &SessionRecord) -> Result<Vec<u8>> {
+        let path = self.frame_path(record);
+        let bytes = fs::read(&path).with_context(|| format!("Failed to read frame {}", path.display()))?;
+        Ok(compression::decompress(&bytes)?.into_owned())
+    }
+
+    /// Copy `frame` into the recording and append its decision
+    pub fn record(&self, frame: &Path, decision: RecordedDecision, device_id: Option<String>) -> Result<SessionRecord> {
+        let timestamp = Utc::now();
+        let id = timestamp.format("%Y%m%dT%H%M%S%.6f").to_string();
+        let extension = frame.extension().and_then(|e| e.to_str()).unwrap_or("jpg");
+        let bytes = fs::read(frame).with_context(|| format!("Failed to record frame {}", frame.display()))?;
+        let compression = match compression::is_compressed_image(&bytes) {
+            true => Compression::None,
+            false => self.compression,
+        };
+        let mut frame_name = format!("{}.{}", id, extension);
+        if compression.is_enabled() {
+            frame_name = format!("{}.{}", frame_name, compression::EXTENSION);
+        }
+        let frame_name = PathBuf::from(frame_name);
+
+        fs::create_dir_all(self.frames_dir())?;
+        fs::write(self.frames_dir().join(&frame_name), compression.compress(&bytes)?)?;
+
+        let record = SessionRecord { id, timestamp, frame: frame_name, device_id, decision };
+        let mut log = OpenOptions::new().create(true).append(true).open(self.log_path())?;
+        writeln!(log, "{}", serde_json::to_string(&record)?)?;
+        Ok(record)
+    }
+
+    /// All records in order; unreadable lines are skipped with a warning
+    pub fn records(&self) -> Result<Vec<SessionRecord>> {
+        let file = match fs::File::open(self.log_path()) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut records = Vec::new();
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(record) => records.push(record),
+                Err(e) => tracing::warn!("Skipping session record line {}: {}", number + 1, e),
+            }
+        }
+        Ok(records)
+    }
+}
+
+/// Original and replayed decision for one recorded frame
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayComparison {
+    pub record: SessionRecord,
+    pub replayed: RecordedDecision,
+}
+
+impl ReplayComparison {
+    /// Whether the new configuration grants a different user, or grants where
+    /// the original denied (or the other way round)
+    pub fn changed(&self) -> bool {
+        let original = &self.record.decision;
+        original.is_match != self.replayed.is_match || original.matched_user != self.replayed.matched_user
+    }
+}
+
+/// Side-by-side decisions of a replay run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReplayReport {
+    pub comparisons: Vec<ReplayComparison>,
+}
+
+impl ReplayReport {
+    /// Frames whose decision differs under the new configuration
+    pub fn changed(&self) -> impl Iterator<Item = &ReplayComparison> {
+        self.comparisons.iter().filter(|c| c.changed())
+    }
+
+    /// Frames denied originally and granted after the replay
+    pub fn newly_granted(&self) -> usize {
+        self.changed().filter(|c| c.replayed.is_match && !c.record.decision.is_match).count()
+    }
+
+    /// Frames granted originally and denied after the replay
+    pub fn newly_denied(&self) -> usize {
+        self.changed().filter(|c| !c.replayed.is_match && c.record.decision.is_match).count()
+    }
+}
+
+/// Run every recorded frame through `backend` with a new tolerance and
+/// enrolled set, without re-capturing anyone
+///
+/// Pass a backend using a different script to compare model versions.
+pub fn replay(
+    backend: &dyn FaceBackend,
+    recorder: &SessionRecorder,
+    tolerance: f64,
+    source_dir: &str,
+) -> Result<ReplayReport> {
+    let mut report = ReplayReport::default();
+    for record in recorder.records()? {
+        let path = recorder.frame_path(&record);
+        let result = if path.extension().is_some_and(|e| e == compression::EXTENSION) {
+            // The backend reads image files, so it gets the frame as recorded
+            let unpacked = recorder.dir().join(format!(".replay_{}", path.file_stem().unwrap_or_default().to_string_lossy()));
+            fs::write(&unpacked, recorder.frame(&record)?)?;
+            let result = backend.authenticate_image(tolerance, source_dir, &unpacked);
+            let _ = fs::remove_file(&unpacked);
+            result?
+        } else {
+            backend.authenticate_image(tolerance, source_dir, &path)?
+        };
+        report.comparisons.push(ReplayComparison {
+            replayed: RecordedDecision::from(&result),
+            record,
+        });
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decision(user: Option<&str>) -> RecordedDecision {
+        RecordedDecision {
+            is_match: user.is_some(),
+            matched_user: user.map(str::to_string),
+            closest_user: Some("ann".to_string()),
+            distance: Some(0.5),
+            threshold: Some(0.6),
+        }
+    }
+
+    #[test]
+    fn test_record_and_compare() {
+        let dir = tempfile::tempdir().unwrap();
+        let frame = dir.path().join("capture.jpg");
+        fs::write(&frame, b"jpeg").unwrap();
+
+        let recorder = SessionRecorder::new(dir.path().join("recordings"));
+        recorder.record(&frame, decision(Some("ann")), Some("door-1".into())).unwrap();
+
+        let records = recorder.records().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(fs::read(recorder.frame_path(&records[0])).unwrap(), b"jpeg");
+
+        let report = ReplayReport {
+            comparisons: vec![ReplayComparison { record: records[0].clone(), replayed: decision(None) }],
+        };
+        assert_eq!(report.changed().count(), 1);
+        assert_eq!(report.newly_denied(), 1);
+        assert_eq!(report.newly_granted(), 0);
+
+        let compressed = SessionRecorder::new(dir.path().join("compressed")).with_compression(Compression::zstd_default());
+        let record = compressed.record(&frame, decision(None), None).unwrap();
+        assert_eq!(record.frame.extension().unwrap(), "zst");
+        assert_eq!(compressed.frame(&record).unwrap(), b"jpeg");
+
+        // Real JPEGs are kept as they are
+        let jpeg = dir.path().join("real.jpg");
+        fs::write(&jpeg, [0xff, 0xd8, 0xff, 0xe0, 0, 0x10]).unwrap();
+        let record = compressed.record(&jpeg, decision(None), None).unwrap();
+        assert_eq!(record.frame.extension().unwrap(), "jpg");
+        assert_eq!(fs::read(compressed.frame_path(&record)).unwrap(), [0xff, 0xd8, 0xff, 0xe0, 0, 0x10]);
+    }
+}
@@ -0,0 +1,151 @@
+//! Sample ids
+//!
+//! Samples are named by UUIDv7s: a millisecond timestamp followed by random
+//! bits. They sort by capture time and stay unique across devices and clock
+//! resets, so credentials merged from several kiosks never share an id.
+//! Earlier versions named samples `<user>_<timestamp>`, which samples taken
+//! in the same instant could share;
+//! [`StorageLayout::migrate_sample_ids`](crate::StorageLayout::migrate_sample_ids)
+//! renames those.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use uuid::{Builder, Uuid};
+
+use crate::face_storage::{self, UserProfile};
+use crate::random::{OsRandom, RandomSource};
+
+/// A fresh id for a sample captured at `at`
+pub fn generate(at: DateTime<Utc>, random: &dyn RandomSource) -> String {
+    let mut bytes = [0u8; 10];
+    random.fill_bytes(&mut bytes);
+    let millis = u64::try_from(at.timestamp_millis()).unwrap_or(0);
+    Builder::from_unix_timestamp_millis(millis, &bytes).into_uuid().to_string()
+}
+
+/// A fresh id for a sample captured now
+pub fn generate_now() -> String {
+    generate(Utc::now(), &OsRandom)
+}
+
+/// Whether `id` predates UUIDv7 sample ids
+pub fn is_legacy(id: &str) -> bool {
+    !Uuid::try_parse(id).is_ok_and(|id| id.get_version_num() == 7)
+}
+
+/// A legacy sample id replaced by [`StorageLayout::migrate_sample_ids`](crate::StorageLayout::migrate_sample_ids)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SampleIdRewrite {
+    pub user_id: String,
+    pub old_id: String,
+    pub new_id: String,
+}
+
+/// Renames legacy samples, giving every copy of a sample the same new id
+pub(crate) struct Rewriter<'a> {
+    now: DateTime<Utc>,
+    random: &'a dyn RandomSource,
+    /// New id by user, old id and how many samples of the profile had that
+    /// old id before, so duplicates get apart
+    assigned: HashMap<(String, String, usize), String>,
+    rewrites: Vec<SampleIdRewrite>,
+}
+
+impl<'a> Rewriter<'a> {
+    pub(crate) fn new(now: DateTime<Utc>, random: &'a dyn RandomSource) -> Self {
+        Self { now, random, assigned: HashMap::new(), rewrites: Vec::new() }
+    }
+
+    /// Rename the legacy samples of `profile`; false if it had none
+    ///
+    /// Samples held by a cloud service keep the service's face id.
+    pub(crate) fn rewrite(&mut self, profile: &mut UserProfile) -> bool {
+        let mut occurrences: HashMap<String, usize> = HashMap::new();
+        let mut changed = false;
+        for sample in &mut profile.face_encodings {
+            if sample.is_remote() || !is_legacy(&sample.sample_id) {
+                continue;
+            }
+            let occurrence = occurrences.entry(sample.sample_id.clone()).or_default();
+            let key = (profile.user_id.clone(), sample.sample_id.clone(), *occurrence);
+            *occurrence += 1;
+            let new_id = self.assigned.entry(key).or_insert_with(|| {
+                // Keep the capture time, so renamed ids still sort by it
+                let at = face_storage::parse_python_timestamp(&sample.timestamp).unwrap_or(self.now);
+                let new_id = generate(at, self.random);
+                self.rewrites.push(SampleIdRewrite {
+                    user_id: profile.user_id.clone(),
+                    old_id: sample.sample_id.clone(),
+                    new_id: new_id.clone(),
+                });
+                new_id
+            });
+            sample.sample_id = new_id.clone();
+            changed = true;
+        }
+        changed
+    }
+
+    pub(crate) fn finish(self) -> Vec<SampleIdRewrite> {
+        self.rewrites
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::face_storage::FaceSample;
+    use crate::random::SeededRandom;
+
+    fn profile(ids: &[&str]) -> UserProfile {
+        UserProfile {
+            user_id: "ann".into(),
+            face_encodings: ids.iter().map(|id| FaceSample {
+                encoding: vec![0.0; 128],
+                timestamp: "2024-05-01T08:00:00Z".into(),
+                image_path: None,
+                sample_id: id.to_string(),
+                quality: None,
+                template_version: None,
+                quarantine: None,
+            }).collect(),
+            sample_count: ids.len(),
+            enrollment_date: None,
+            metadata: Default::default(),
+            extra: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_ids_are_uuid_v7_in_capture_order() {
+        let random = SeededRandom::new(1);
+        let at = DateTime::parse_from_rfc3339("2024-05-01T08:00:00Z").unwrap().with_timezone(&Utc);
+        let first = generate(at, &random);
+        let second = generate(at + chrono::Duration::milliseconds(1), &random);
+        assert!(!is_legacy(&first));
+        assert!(first < second);
+        assert_ne!(generate(at, &random), first);
+        assert!(is_legacy("ann_20240501_080000"));
+        assert!(is_legacy("67e55044-10b1-426f-9247-bb680e5fe0c8"), "a v4 UUID");
+    }
+
+    #[test]
+    fn test_copies_of_a_sample_get_the_same_new_id() {
+        let random = SeededRandom::new(1);
+        let mut rewriter = Rewriter::new(Utc::now(), &random);
+        let fresh = generate_now();
+        let (mut database, mut credential) = (profile(&["ann_1", "ann_1", &fresh]), profile(&["ann_1", "ann_1"]));
+        assert!(rewriter.rewrite(&mut database));
+        assert!(rewriter.rewrite(&mut credential));
+        assert!(!rewriter.rewrite(&mut database.clone()));
+
+        let ids: Vec<&str> = database.face_encodings.iter().map(|s| s.sample_id.as_str()).collect();
+        assert_ne!(ids[0], ids[1], "a duplicated legacy id becomes two ids");
+        assert_eq!(ids[2], fresh);
+        assert!(credential.face_encodings.iter().map(|s| s.sample_id.as_str()).eq(ids[..2].iter().copied()));
+        let rewrites = rewriter.finish();
+        assert_eq!(rewrites.len(), 2);
+        assert_eq!((rewrites[0].old_id.as_str(), rewrites[0].new_id.as_str()), ("ann_1", ids[0]));
+    }
+}
@@ -0,0 +1,93 @@
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Cheap checks every camera frame must pass in continuous mode before the
+/// expensive encode-and-match path runs
+///
+/// Frames are downscaled to grayscale and compared with the previous one;
+/// still scenes are skipped, then frames without a face found by a fast
+/// cascade detector. On an always-on door device most frames show an empty
+/// hallway, so most of them never reach the encoder.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneGate {
+    /// Fraction of pixels (0.0-1.0) that must differ from the previous frame
+    pub min_changed_fraction: f64,
+    /// Gray level difference (0-255) at which a pixel counts as changed
+    pub pixel_threshold: u8,
+    /// Also require a face from the cascade detector
+    pub face_check: bool,
+    /// Frames are only read, not checked, for this long after an attempt
+    pub cooldown: Duration,
+}
+
+impl Default for SceneGate {
+    fn default() -> Self {
+        Self { min_changed_fraction: 0.01, pixel_threshold: 25, face_check: true, cooldown: Duration::from_secs(2) }
+    }
+}
+
+impl SceneGate {
+    pub fn with_min_changed_fraction(mut self, fraction: f64) -> Self {
+        self.min_changed_fraction = fraction;
+        self
+    }
+
+    pub fn with_face_check(mut self, face_check: bool) -> Self {
+        self.face_check = face_check;
+        self
+    }
+
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..=1.0).contains(&self.min_changed_fraction) {
+            bail!("Changed fraction {} is not between 0 and 1", self.min_changed_fraction);
+        }
+        Ok(())
+    }
+}
+
+/// Frames seen in continuous mode and what happened to them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SceneStats {
+    pub frames: u64,
+    /// Skipped because nothing moved
+    pub skipped_still: u64,
+    /// Skipped because no face was visible
+    pub skipped_empty: u64,
+    /// Showing only faces already identified, when tracking
+    #[serde(default)]
+    pub tracked: u64,
+    /// Encoded and matched
+    pub attempts: u64,
+}
+
+impl SceneStats {
+    /// Share of checked frames that never reached the encoder
+    pub fn skipped_fraction(&self) -> f64 {
+        let skipped = self.skipped_still + self.skipped_empty + self.tracked;
+        if skipped + self.attempts == 0 {
+            return 0.0;
+        }
+        skipped as f64 / (skipped + self.attempts) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scene_stats_parse_and_count_skips() {
+        let stats: SceneStats = serde_json::from_str(r#"{"frames": 120, "skipped_still": 100, "skipped_empty": 18, "attempts": 2}"#).unwrap();
+        assert!((stats.skipped_fraction() - 118.0 / 120.0).abs() < 1e-9);
+        assert_eq!(SceneStats::default().skipped_fraction(), 0.0);
+
+        assert!(SceneGate::default().validate().is_ok());
+        assert!(SceneGate::default().with_min_changed_fraction(1.5).validate().is_err());
+    }
+}
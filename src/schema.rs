@@ -0,0 +1,305 @@
+//! Credential file schema
+//!
+//! The per-user JSON files written to `generated/` and read from `source/`
+//! are [`UserProfile`]s. A file that doesn't fit the schema is skipped when
+//! the source directory is loaded, which shows up as nothing more than a user
+//! who can't authenticate. The checks here say what is wrong and where, as a
+//! path into the file such as `face_encodings[2].encoding[17]`: type errors
+//! also carry the line and column. [`check_credentials_dir`] checks a whole
+//! directory before it is deployed, see `face_auth validate`.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::face_storage::{self, ENCODING_DIMENSIONS, UserProfile};
+
+/// Fields of a sample; others are dropped when the file is loaded
+const SAMPLE_FIELDS: &[&str] = &["encoding", "timestamp", "image_path", "sample_id", "quality", "template_version", "quarantine"];
+
+/// Fields of the metadata; others are dropped when the file is loaded
+const METADATA_FIELDS: &[&str] = &["display_name", "external_id", "email", "badge_id", "custom"];
+
+/// One problem in a credential file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaIssue {
+    /// Path of the offending value, e.g. `face_encodings[2].encoding`; `.`
+    /// for the file as a whole
+    pub location: String,
+    pub message: String,
+}
+
+impl SchemaIssue {
+    fn new(location: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { location: location.into(), message: message.into() }
+    }
+}
+
+impl fmt::Display for SchemaIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location.as_str() {
+            "." => write!(f, "{}", self.message),
+            location => write!(f, "at {}: {}", location, self.message),
+        }
+    }
+}
+
+/// Outcome of checking one credential file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CredentialReport {
+    pub path: PathBuf,
+    /// `None` when the file couldn't be parsed
+    pub user_id: Option<String>,
+    /// Problems that keep the file from being used for authentication
+    pub errors: Vec<SchemaIssue>,
+    /// Problems the file is used despite, e.g. unknown fields
+    pub warnings: Vec<SchemaIssue>,
+}
+
+impl CredentialReport {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Parse a credential file's contents, locating type errors
+pub fn parse_credential(json: &str) -> Result<UserProfile, SchemaIssue> {
+    let deserializer = &mut serde_json::Deserializer::from_str(json);
+    serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        // Syntax errors end in a `?` segment, for the key that couldn't be read
+        let path = e.path().to_string();
+        let location = match path.trim_end_matches('?').trim_end_matches('.') {
+            "" => ".",
+            location => location,
+        };
+        SchemaIssue::new(location, e.inner().to_string())
+    })
+}
+
+/// Problems that keep `profile` from being used for authentication
+pub fn check_profile(profile: &UserProfile) -> Vec<SchemaIssue> {
+    let mut issues = Vec::new();
+    if let Err(e) = face_storage::validate_username(&profile.user_id) {
+        issues.push(SchemaIssue::new("user_id", e.to_string()));
+    }
+    if profile.face_encodings.is_empty() {
+        issues.push(SchemaIssue::new("face_encodings", "no samples"));
+    }
+
+    // Custom models may use any size, but all of a model's samples must agree
+    let mut dimensions: HashMap<Option<&str>, usize> = HashMap::new();
+    for (i, sample) in profile.face_encodings.iter().enumerate() {
+        let at = |field: &str| format!("face_encodings[{}].{}", i, field);
+        if sample.is_remote() {
+            if !sample.encoding.is_empty() {
+                issues.push(SchemaIssue::new(at("encoding"), format!("remote sample '{}' has a local encoding", sample.sample_id)));
+            }
+            continue;
+        }
+        let version = sample.template_version.as_deref();
+        let expected = match version {
+            None => ENCODING_DIMENSIONS,
+            Some(_) => *dimensions.entry(version).or_insert(sample.encoding.len()),
+        };
+        if sample.encoding.is_empty() {
+            issues.push(SchemaIssue::new(at("encoding"), format!("sample '{}' has no encoding", sample.sample_id)));
+        } else if sample.encoding.len() != expected {
+            issues.push(SchemaIssue::new(at("encoding"), format!(
+                "sample '{}' has {} dimensions, expected {}", sample.sample_id, sample.encoding.len(), expected
+            )));
+        }
+        if let Some(j) = sample.encoding.iter().position(|v| !v.is_finite()) {
+            issues.push(SchemaIssue::new(format!("face_encodings[{}].encoding[{}]", i, j), format!("sample '{}' contains non-finite values", sample.sample_id)));
+        }
+    }
+    issues
+}
+
+/// Problems `profile` is used for authentication despite
+///
+/// Files written by older versions may have empty or repeated sample ids,
+/// which `face_auth migrate-sample-ids` replaces, and quality scores of other
+/// scales; matching needs neither.
+pub fn profile_warnings(profile: &UserProfile) -> Vec<SchemaIssue> {
+    let mut issues = Vec::new();
+    let mut sample_ids: HashMap<&str, usize> = HashMap::new();
+    for (i, sample) in profile.face_encodings.iter().enumerate() {
+        let at = |field: &str| format!("face_encodings[{}].{}", i, field);
+        if sample.sample_id.is_empty() {
+            issues.push(SchemaIssue::new(at("sample_id"), "empty sample id, run `face_auth migrate-sample-ids`"));
+        } else if let Some(first) = sample_ids.insert(&sample.sample_id, i) {
+            issues.push(SchemaIssue::new(at("sample_id"), format!(
+                "sample id '{}' is also used by face_encodings[{}], run `face_auth migrate-sample-ids`", sample.sample_id, first
+            )));
+        }
+        if sample.quality.is_some_and(|q| !(0.0..=1.0).contains(&q)) {
+            issues.push(SchemaIssue::new(at("quality"), "quality outside 0.0-1.0"));
+        }
+        if face_storage::parse_python_timestamp(&sample.timestamp).is_none() {
+            issues.push(SchemaIssue::new(at("timestamp"), format!("'{}' isn't a timestamp", sample.timestamp)));
+        }
+    }
+    issues
+}
+
+/// Check one credential file
+pub fn check_credential(path: &Path) -> CredentialReport {
+    let mut report = CredentialReport { path: path.to_path_buf(), user_id: None, errors: Vec::new(), warnings: Vec::new() };
+    let json = match fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(e) => {
+            report.errors.push(SchemaIssue::new(".", format!("can't be read: {}", e)));
+            return report;
+        }
+    };
+    let profile = match parse_credential(&json) {
+        Ok(profile) => profile,
+        Err(issue) => {
+            report.errors.push(issue);
+            return report;
+        }
+    };
+
+    report.errors = check_profile(&profile);
+    if let Ok(raw) = serde_json::from_str::<serde_json::Value>(&json) {
+        report.warnings = unknown_fields(&profile, &raw);
+    }
+    if path.file_stem().is_some_and(|stem| *stem != *profile.user_id) {
+        report.warnings.push(SchemaIssue::new("user_id", format!(
+            "file isn't named {}.json, so renaming or deleting the user won't find it", profile.user_id
+        )));
+    }
+    if profile.sample_count != profile.face_encodings.len() {
+        report.warnings.push(SchemaIssue::new("sample_count", format!(
+            "{} doesn't match the {} sample(s)", profile.sample_count, profile.face_encodings.len()
+        )));
+    }
+    report.warnings.extend(profile_warnings(&profile));
+    report.user_id = Some(profile.user_id);
+    report
+}
+
+/// Check every `*.json` credential file in `dir`
+///
+/// On top of the per-file checks, a user with more than one file is an error
+/// in every file after the first.
+pub fn check_credentials_dir(dir: &Path) -> Result<Vec<CredentialReport>> {
+    if !dir.is_dir() {
+        anyhow::bail!("{} is not a directory", dir.display());
+    }
+    let mut reports: Vec<CredentialReport> = face_storage::credential_files(dir)?.iter().map(|path| check_credential(path)).collect();
+    let mut seen: HashMap<String, PathBuf> = HashMap::new();
+    for report in &mut reports {
+        let Some(user_id) = &report.user_id else { continue };
+        if let Some(first) = seen.get(user_id) {
+            report.errors.push(SchemaIssue::new("user_id", format!("user '{}' is already defined in {}", user_id, first.display())));
+        } else {
+            seen.insert(user_id.clone(), report.path.clone());
+        }
+    }
+    Ok(reports)
+}
+
+/// Fields of `raw` the schema doesn't know
+fn unknown_fields(profile: &UserProfile, raw: &serde_json::Value) -> Vec<SchemaIssue> {
+    let mut issues: Vec<SchemaIssue> = profile.extra.keys()
+        .map(|key| SchemaIssue::new(key.as_str(), "unknown field, kept when the file is rewritten"))
+        .collect();
+    let dropped = |object: Option<&serde_json::Map<String, serde_json::Value>>, known: &[&str], at: &str| -> Vec<SchemaIssue> {
+        let known: HashSet<&str> = known.iter().copied().collect();
+        object.into_iter().flatten()
+            .filter(|(key, _)| !known.contains(key.as_str()))
+            .map(|(key, _)| SchemaIssue::new(format!("{}.{}", at, key), "unknown field, ignored"))
+            .collect()
+    };
+    if let Some(samples) = raw.get("face_encodings").and_then(|s| s.as_array()) {
+        for (i, sample) in samples.iter().enumerate() {
+            issues.extend(dropped(sample.as_object(), SAMPLE_FIELDS, &format!("face_encodings[{}]", i)));
+        }
+    }
+    issues.extend(dropped(raw.get("metadata").and_then(|m| m.as_object()), METADATA_FIELDS, "metadata"));
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::face_storage::{FaceSample, UserMetadata};
+    use crate::outliers::SampleQuarantine;
+
+    fn credential(encodings: serde_json::Value) -> String {
+        serde_json::json!({
+            "user_id": "ann",
+            "face_encodings": encodings,
+            "sample_count": 1,
+            "badge_color": "red",
+        }).to_string()
+    }
+
+    fn sample(encoding: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({ "encoding": encoding, "timestamp": "2024-03-01T09:00:00", "sample_id": "s1", "pose": "left" })
+    }
+
+    #[test]
+    fn test_issues_are_located() {
+        let mut encoding = vec![serde_json::json!(0.1); ENCODING_DIMENSIONS];
+        encoding[17] = serde_json::json!("0.1");
+        let issue = parse_credential(&credential(serde_json::json!([sample(encoding.into())]))).unwrap_err();
+        assert_eq!(issue.location, "face_encodings[0].encoding[17]");
+        assert!(issue.message.contains("expected f64") && issue.message.contains("line 1"), "{}", issue);
+        assert_eq!(parse_credential("{").unwrap_err().location, ".");
+
+        let short = sample(vec![0.1; ENCODING_DIMENSIONS - 1].into());
+        let profile = parse_credential(&credential(serde_json::json!([short.clone(), short]))).unwrap();
+        let locations: Vec<_> = check_profile(&profile).into_iter().map(|i| i.location).collect();
+        assert_eq!(locations, ["face_encodings[0].encoding", "face_encodings[1].encoding"]);
+        // Legacy sample ids and quality scales are warned about, not refused
+        let mut legacy = sample(vec![0.1; ENCODING_DIMENSIONS].into());
+        legacy["quality"] = serde_json::json!(42.0);
+        let profile = parse_credential(&credential(serde_json::json!([legacy.clone(), legacy]))).unwrap();
+        assert!(check_profile(&profile).is_empty());
+        assert!(profile.validate().is_ok());
+        let locations: Vec<_> = profile_warnings(&profile).into_iter().map(|i| i.location).collect();
+        assert_eq!(locations, ["face_encodings[0].quality", "face_encodings[1].sample_id", "face_encodings[1].quality"]);
+    }
+
+    #[test]
+    fn test_directory_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let valid = credential(serde_json::json!([sample(vec![0.1; ENCODING_DIMENSIONS].into())]));
+        fs::write(dir.path().join("ann.json"), &valid).unwrap();
+        fs::write(dir.path().join("ann_copy.json"), &valid).unwrap();
+        fs::write(dir.path().join("broken.json"), "{\"user_id\": \"bob\", \"face_encodings\": 3}").unwrap();
+
+        let reports = check_credentials_dir(dir.path()).unwrap();
+        assert!(reports[0].is_valid());
+        let warnings: Vec<_> = reports[0].warnings.iter().map(|w| w.location.as_str()).collect();
+        assert_eq!(warnings, ["badge_color", "face_encodings[0].pose"]);
+        assert_eq!(reports[1].errors[0].location, "user_id");
+        assert_eq!(reports[2].errors[0].location, "face_encodings");
+        assert_eq!(reports[2].user_id, None);
+        assert!(check_credentials_dir(&dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_known_fields_cover_the_schema() {
+        let sample = FaceSample {
+            encoding: vec![0.1],
+            timestamp: "2024-03-01T09:00:00".into(),
+            image_path: Some("a.jpg".into()),
+            sample_id: "s1".into(),
+            quality: Some(0.9),
+            template_version: Some("v".into()),
+            quarantine: Some(SampleQuarantine { flagged_at: chrono::Utc::now(), distance: 0.7, threshold: 0.5 }),
+        };
+        let metadata = UserMetadata { display_name: Some("Ann".into()), external_id: Some("1".into()), email: Some("a@example.com".into()), badge_id: Some("b".into()), custom: HashMap::from([("k".into(), "v".into())]) };
+        for (value, known) in [(serde_json::to_value(sample).unwrap(), SAMPLE_FIELDS), (serde_json::to_value(metadata).unwrap(), METADATA_FIELDS)] {
+            for key in value.as_object().unwrap().keys() {
+                assert!(known.contains(&key.as_str()), "{} is missing", key);
+            }
+        }
+    }
+}
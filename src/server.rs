@@ -0,0 +1,549 @@
+use anyhow::Result;
+use axum::extract::{Path as UrlPath, State};
+use axum::body::Bytes;
+use axum::extract::ConnectInfo;
+use axum::http::{Extensions, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::future::Future;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::FaceAuthError;
+use crate::face_storage::UserMetadata;
+use crate::health::HealthProbe;
+use crate::hybrid::{NonceRequest, NonceResponse, Probe, VerifyRequest, VerifyResponse, NONCE_PATH, VERIFY_PATH};
+use crate::moderation::PendingEnrollment;
+use crate::nonce::{NonceRefused, NonceStore};
+use crate::registration::RegistrationOutcome;
+#[cfg(unix)]
+use crate::systemd;
+use crate::v1::AuthRequest;
+use crate::FaceAuth;
+
+#[cfg(feature = "admin-ui")]
+mod admin;
+#[cfg(feature = "admin-ui")]
+pub use admin::ADMIN_PATH;
+
+/// Liveness/readiness probe: 200 with the [`crate::HealthProbe`] while healthy, 503 otherwise
+pub const HEALTH_PATH: &str = "/healthz";
+
+/// Remote enrollment: `POST` submits photos, `GET` lists pending submissions,
+/// `POST {ENROLLMENTS_PATH}/{id}/approve` and `.../reject` moderate them
+pub const ENROLLMENTS_PATH: &str = "/enrollments";
+
+/// HTTP front end of a [`FaceAuth`] instance
+///
+/// Serves the verification endpoint devices in hybrid mode talk to,
+/// [`HEALTH_PATH`] for orchestration probes and the [`ENROLLMENTS_PATH`]
+/// moderation queue, plus the admin web UI with the `admin-ui` feature.
+///
+/// Moderating enrollments requires the admin token as `Authorization: Bearer`
+/// and is disabled without one; likewise submitting enrollments requires the
+/// submit token, and verification and nonces the device token. Devices can
+/// ask for a tolerance up to the server's maximum, never a looser one.
+#[derive(Clone)]
+pub struct Server {
+    auth: FaceAuth,
+    source_dir: String,
+    generated_dir: String,
+    admin_token: Option<String>,
+    submit_token: Option<String>,
+    device_token: Option<String>,
+    max_tolerance: f64,
+    nonces: Option<Arc<NonceStore>>,
+}
+
+/// Body of an enrollment submission
+#[derive(Debug, Deserialize)]
+struct SubmitEnrollment {
+    username: String,
+    /// Base64-encoded JPEG or PNG photos
+    photos: Vec<String>,
+    #[serde(default)]
+    submitted_by: Option<String>,
+    #[serde(default)]
+    metadata: UserMetadata,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApproveEnrollment {
+    #[serde(default = "default_tolerance")]
+    tolerance: f64,
+}
+
+fn default_tolerance() -> f64 {
+    0.6
+}
+
+/// Tolerance devices can ask for unless [`Server::with_max_tolerance`] says otherwise
+pub const DEFAULT_MAX_TOLERANCE: f64 = 0.6;
+
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+/// Details stay in the server's log; callers only learn that something failed
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        tracing::error!("Request failed: {:#}", err);
+        ApiError(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".into())
+    }
+}
+
+impl Server {
+    /// Server matching against the credentials in `source_dir`
+    pub fn new(auth: FaceAuth, source_dir: impl AsRef<Path>) -> Self {
+        let source_dir = source_dir.as_ref().to_string_lossy().into_owned();
+        Self {
+            auth,
+            generated_dir: source_dir.clone(),
+            source_dir,
+            admin_token: None,
+            submit_token: None,
+            device_token: None,
+            max_tolerance: DEFAULT_MAX_TOLERANCE,
+            nonces: None,
+        }
+    }
+
+    /// Token admins moderate enrollments with
+    pub fn with_admin_token(mut self, token: impl Into<String>) -> Self {
+        self.admin_token = Some(token.into());
+        self
+    }
+
+    /// Token enrollments are submitted with; submissions are disabled without one
+    pub fn with_submit_token(mut self, token: impl Into<String>) -> Self {
+        self.submit_token = Some(token.into());
+        self
+    }
+
+    /// Token devices verify faces and fetch nonces with; both are disabled without one
+    pub fn with_device_token(mut self, token: impl Into<String>) -> Self {
+        self.device_token = Some(token.into());
+        self
+    }
+
+    /// Loosest tolerance a device can ask for; looser requests are clamped to it
+    pub fn with_max_tolerance(mut self, tolerance: f64) -> Self {
+        self.max_tolerance = tolerance;
+        self
+    }
+
+    /// Reject verifications without a nonce from [`NONCE_PATH`], issued at
+    /// most `ttl` ago for the same request body and not used before, so
+    /// captured requests can't be replayed; each client address gets
+    /// [`crate::nonce::DEFAULT_CLIENT_LIMIT`] nonces a minute
+    pub fn with_nonces(mut self, ttl: Duration) -> Self {
+        self.nonces = Some(Arc::new(NonceStore::new(ttl).with_clock(self.auth.clock()).with_random(self.auth.random())));
+        self
+    }
+
+    /// Where approved enrollments' credentials are written; defaults to the
+    /// source directory, so they can authenticate right away
+    pub fn with_generated_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.generated_dir = dir.as_ref().to_string_lossy().into_owned();
+        self
+    }
+
+    pub fn router(&self) -> Router {
+        let router = Router::new()
+            .route(VERIFY_PATH, post(verify))
+            .route(NONCE_PATH, post(issue_nonce))
+            .route(HEALTH_PATH, get(healthz))
+            .route(ENROLLMENTS_PATH, post(submit_enrollment).get(pending_enrollments))
+            .route(&format!("{}/{{id}}/approve", ENROLLMENTS_PATH), post(approve_enrollment))
+            .route(&format!("{}/{{id}}/reject", ENROLLMENTS_PATH), post(reject_enrollment));
+        #[cfg(feature = "admin-ui")]
+        let router = router.merge(admin::routes());
+        router.with_state(Arc::new(self.clone()))
+    }
+
+    /// Listen on `addr` until the process is stopped
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        self.serve_on(ServerListener::bind(addr).await?, std::future::pending()).await
+    }
+
+    /// Accept connections on `listener` until `shutdown` completes, then
+    /// finish requests in progress and shut the [`FaceAuth`] instance down
+    ///
+    /// Under systemd, readiness is reported once listening, the watchdog is
+    /// pinged while the runtime is responsive and stopping is reported on
+    /// shutdown.
+    pub async fn serve_on(self, listener: ServerListener, shutdown: impl Future<Output = ()> + Send + 'static) -> Result<()> {
+        let router = self.router();
+        #[cfg(unix)]
+        let watchdog = systemd::watchdog_interval().map(|interval| {
+            tokio::spawn(async move {
+                let mut ticks = tokio::time::interval(interval / 2);
+                loop {
+                    ticks.tick().await;
+                    if let Err(e) = systemd::notify("WATCHDOG=1") {
+                        tracing::warn!("Watchdog ping failed: {:#}", e);
+                    }
+                }
+            })
+        });
+        #[cfg(unix)]
+        systemd::notify("READY=1")?;
+        match listener {
+            ServerListener::Tcp(listener) => {
+                tracing::info!("Listening on {}", listener.local_addr()?);
+                // Client addresses rate-limit nonces
+                let service = router.into_make_service_with_connect_info::<SocketAddr>();
+                axum::serve(listener, service).with_graceful_shutdown(shutdown).await?;
+            }
+            #[cfg(unix)]
+            ServerListener::Unix(listener) => {
+                tracing::info!("Listening on {:?}", listener.local_addr()?);
+                axum::serve(listener, router).with_graceful_shutdown(shutdown).await?;
+            }
+        }
+
+        #[cfg(unix)]
+        {
+            systemd::notify("STOPPING=1")?;
+            if let Some(watchdog) = watchdog {
+                watchdog.abort();
+            }
+        }
+        self.auth.shutdown().await
+    }
+}
+
+/// Socket a [`Server`] accepts connections on
+#[derive(Debug)]
+pub enum ServerListener {
+    Tcp(tokio::net::TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+}
+
+impl ServerListener {
+    pub async fn bind(addr: SocketAddr) -> Result<Self> {
+        Ok(Self::Tcp(tokio::net::TcpListener::bind(addr).await?))
+    }
+
+    /// Listen on a Unix socket at `path`, replacing a stale socket left by a
+    /// previous run
+    #[cfg(unix)]
+    pub fn bind_unix(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if std::fs::symlink_metadata(path).is_ok_and(|meta| std::os::unix::fs::FileTypeExt::is_socket(&meta.file_type())) {
+            std::fs::remove_file(path)?;
+        }
+        Ok(Self::Unix(tokio::net::UnixListener::bind(path)?))
+    }
+
+    /// The first socket passed by a systemd `.socket` unit, if any
+    #[cfg(unix)]
+    pub fn from_systemd() -> Result<Option<Self>> {
+        let Some(fd) = systemd::listen_fds().into_iter().next() else {
+            return Ok(None);
+        };
+        let tcp = std::net::TcpListener::from(fd);
+        // Only inet sockets have an inet address
+        let listener = if tcp.local_addr().is_ok() {
+            tcp.set_nonblocking(true)?;
+            Self::Tcp(tokio::net::TcpListener::from_std(tcp)?)
+        } else {
+            let unix = std::os::unix::net::UnixListener::from(std::os::fd::OwnedFd::from(tcp));
+            unix.set_nonblocking(true)?;
+            Self::Unix(tokio::net::UnixListener::from_std(unix)?)
+        };
+        Ok(Some(listener))
+    }
+}
+
+async fn issue_nonce(State(server): State<Arc<Server>>, extensions: Extensions, headers: HeaderMap, body: Bytes) -> Result<Json<NonceResponse>, ApiError> {
+    authorize(&headers, server.device_token.as_deref(), "device")?;
+    let Some(nonces) = &server.nonces else {
+        return Err(ApiError(StatusCode::NOT_FOUND, "Nonces are not enabled".into()));
+    };
+    let request: NonceRequest = serde_json::from_slice(&body)
+        .map_err(|e| ApiError(StatusCode::BAD_REQUEST, format!("Invalid nonce request: {}", e)))?;
+    // Unix socket clients are all local and share one limit
+    let client = extensions.get::<ConnectInfo<SocketAddr>>().map_or("local".to_string(), |info| info.0.ip().to_string());
+    let nonce = nonces.issue(&client, &request.body_sha256).map_err(|e| {
+        tracing::warn!(client, "Refused a nonce: {}", e);
+        let status = match e {
+            NonceRefused::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            NonceRefused::TooManyOutstanding => StatusCode::SERVICE_UNAVAILABLE,
+        };
+        ApiError(status, e.to_string())
+    })?;
+    Ok(Json(NonceResponse { nonce, expires_in: nonces.ttl().as_secs() }))
+}
+
+async fn verify(State(server): State<Arc<Server>>, headers: HeaderMap, Json(request): Json<VerifyRequest>) -> Result<Json<VerifyResponse>, ApiError> {
+    authorize(&headers, server.device_token.as_deref(), "device")?;
+    let device = request.device_id.as_deref().unwrap_or("-");
+    let tolerance = request.tolerance.min(server.max_tolerance);
+    if tolerance < request.tolerance {
+        tracing::warn!(device, requested = request.tolerance, tolerance, "Clamped the requested tolerance to the server's maximum");
+    }
+    if let Some(nonces) = &server.nonces {
+        if !request.nonce.as_deref().is_some_and(|nonce| nonces.redeem(nonce, &request.body_hash())) {
+            tracing::warn!(device, "Rejected verification with a missing, used, expired or mismatched nonce");
+            return Err(ApiError(StatusCode::UNAUTHORIZED, "Missing, used, expired or mismatched nonce".into()));
+        }
+    }
+    let result = match request.probe {
+        Probe::Embedding { encoding } => {
+            tracing::info!(device, quality = request.quality, "Verifying embedding");
+            let server = server.clone();
+            blocking(async move { server.auth.authenticate_embedding(tolerance, &server.source_dir, &encoding).await }).await?
+        }
+        Probe::Crop { jpeg } => {
+            tracing::info!(device, quality = request.quality, "Verifying face crop");
+            let jpeg = BASE64.decode(jpeg).map_err(|e| ApiError(StatusCode::BAD_REQUEST, format!("Invalid crop: {}", e)))?;
+            let request = AuthRequest::upload(tolerance, jpeg, device).with_source_dir(&server.source_dir);
+            let server = server.clone();
+            blocking(async move { server.auth.authenticate(&request).await }).await?
+        }
+    };
+    Ok(Json(VerifyResponse {
+        is_authenticated: result.is_authenticated,
+        user_id: result.user_id,
+        distance: result.distance,
+        confidence: result.confidence,
+        threshold: result.threshold,
+        denial: result.denial,
+        manipulation_score: result.manipulation_score,
+    }))
+}
+
+/// Run `work` on a blocking thread
+///
+/// Matching waits for the Python script or a free pooled worker; on the
+/// runtime's threads a few concurrent doors would starve the health probe,
+/// the watchdog ping and every other route.
+async fn blocking<T: Send + 'static>(work: impl Future<Output = Result<T>> + Send + 'static) -> Result<T> {
+    let runtime = tokio::runtime::Handle::current();
+    tokio::task::spawn_blocking(move || runtime.block_on(work)).await?
+}
+
+async fn healthz(State(server): State<Arc<Server>>) -> Result<Response, ApiError> {
+    let probe = HealthProbe::from(&server.auth.health().await?);
+    let code = if probe.healthy { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    Ok((code, Json(probe)).into_response())
+}
+
+/// Compare the bearer token against `expected` in constant time
+fn authorize(headers: &HeaderMap, expected: Option<&str>, role: &str) -> Result<(), ApiError> {
+    let Some(expected) = expected else {
+        return Err(ApiError(StatusCode::FORBIDDEN, format!("No {} token configured", role)));
+    };
+    let given = headers.get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    let equal = given.len() == expected.len() && given.bytes().zip(expected.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0;
+    if equal { Ok(()) } else { Err(ApiError(StatusCode::UNAUTHORIZED, format!("Invalid {} token", role))) }
+}
+
+async fn submit_enrollment(
+    State(server): State<Arc<Server>>,
+    headers: HeaderMap,
+    Json(request): Json<SubmitEnrollment>,
+) -> Result<(StatusCode, Json<PendingEnrollment>), ApiError> {
+    authorize(&headers, server.submit_token.as_deref(), "submit")?;
+    let photos = request.photos.iter()
+        .map(|photo| BASE64.decode(photo))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| ApiError(StatusCode::BAD_REQUEST, format!("Invalid photo: {}", e)))?;
+    let pending = server.auth.submit_enrollment(&request.username, &photos, request.submitted_by, request.metadata).await
+        .map_err(|e| match e.downcast_ref::<FaceAuthError>() {
+            Some(invalid @ (FaceAuthError::InvalidUsername { .. }
+            | FaceAuthError::InvalidEnrollment { .. }
+            | FaceAuthError::ReadOnly { .. }
+            | FaceAuthError::PrivacyStrict { .. })) => ApiError(StatusCode::BAD_REQUEST, invalid.to_string()),
+            _ => ApiError::from(e),
+        })?;
+    Ok((StatusCode::CREATED, Json(pending)))
+}
+
+async fn pending_enrollments(State(server): State<Arc<Server>>, headers: HeaderMap) -> Result<Json<Vec<PendingEnrollment>>, ApiError> {
+    authorize(&headers, server.admin_token.as_deref(), "admin")?;
+    Ok(Json(server.auth.pending_enrollments().await?))
+}
+
+async fn approve_enrollment(
+    State(server): State<Arc<Server>>,
+    UrlPath(id): UrlPath<String>,
+    headers: HeaderMap,
+    request: Option<Json<ApproveEnrollment>>,
+) -> Result<Json<RegistrationOutcome>, ApiError> {
+    authorize(&headers, server.admin_token.as_deref(), "admin")?;
+    let tolerance = request.map_or_else(default_tolerance, |Json(r)| r.tolerance);
+    let outcome = server.auth.approve_enrollment(&id, tolerance, &server.generated_dir).await
+        .map_err(|e| match e.downcast_ref::<FaceAuthError>() {
+            // Approved or rejected by another admin meanwhile, or refused by its review
+            Some(decided @ (FaceAuthError::EnrollmentNotPending { .. } | FaceAuthError::EnrollmentReviewFailed { .. })) => {
+                ApiError(StatusCode::CONFLICT, decided.to_string())
+            }
+            _ => ApiError::from(e),
+        })?;
+    Ok(Json(outcome))
+}
+
+async fn reject_enrollment(State(server): State<Arc<Server>>, UrlPath(id): UrlPath<String>, headers: HeaderMap) -> Result<StatusCode, ApiError> {
+    authorize(&headers, server.admin_token.as_deref(), "admin")?;
+    server.auth.reject_enrollment(&id).await.map_err(|e| match e.downcast_ref::<FaceAuthError>() {
+        Some(missing @ FaceAuthError::EnrollmentNotPending { .. }) => ApiError(StatusCode::NOT_FOUND, missing.to_string()),
+        _ => ApiError::from(e),
+    })?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(all(test, feature = "hybrid"))]
+mod tests {
+    use super::*;
+    use crate::hybrid::{HybridBackend, HybridConfig, RetryPolicy};
+    use crate::{FakeBackend, StandalonePythonFaceAuth};
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    const FIXTURES: &str = "tests/fixtures/faces.json";
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_hybrid_device_is_verified_by_server() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = FakeBackend::from_fixtures(dir.path().join("data"), FIXTURES).unwrap();
+        let source = dir.path().join("source");
+        let auth = FaceAuth::builder().backend(backend.clone()).source_dir(&source).auto_promote(true).build().unwrap();
+        backend.queue_frames(["ann_1", "ann_2"]);
+        let generated = dir.path().join("generated");
+        auth.register_user("ann", 2, generated.to_str().unwrap()).await.unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let router = Server::new(auth, &source).with_device_token("door-secret").with_nonces(Duration::from_secs(60)).router();
+        tokio::spawn(async move { axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>()).await });
+
+        let fixtures: HashMap<String, Vec<f64>> = serde_json::from_str(&std::fs::read_to_string(FIXTURES).unwrap()).unwrap();
+        let python = StandalonePythonFaceAuth::from_parts("python3", "unused.py", dir.path());
+        let config = HybridConfig { token: Some("door-secret".to_string()), ..HybridConfig::new(&url) };
+        let device = HybridBackend::new(python.clone(), HybridConfig { nonces: true, ..config.clone() });
+        let without_nonces = HybridBackend::new(python.clone(), config.clone());
+        let without_token = HybridBackend::new(python.clone(), HybridConfig { nonces: true, token: Some("guess".to_string()), ..config });
+        let offline = HybridBackend::new(python, HybridConfig {
+            retry: RetryPolicy { attempts: 2, initial_backoff: Duration::from_millis(1), max_backoff: Duration::from_millis(1) },
+            ..HybridConfig::new("http://127.0.0.1:9")
+        });
+
+        let health_url = format!("{}{}", url, HEALTH_PATH);
+        let (nonce_url, verify_url) = (format!("{}{}", url, NONCE_PATH), format!("{}{}", url, VERIFY_PATH));
+        let responses = tokio::task::spawn_blocking(move || {
+            let request = |frame: &str| VerifyRequest {
+                device_id: Some("door-1".to_string()),
+                tolerance: 0.6,
+                quality: None,
+                probe: Probe::Embedding { encoding: fixtures[frame].clone() },
+                nonce: None,
+            };
+            let offline_error = offline.verify(&request("ann_probe")).unwrap_err();
+            assert!(format!("{:#}", without_nonces.verify(&request("ann_probe")).unwrap_err()).contains("401"));
+            assert!(format!("{:#}", without_token.verify(&request("ann_probe")).unwrap_err()).contains("401"));
+            let agent: ureq::Agent = ureq::Agent::config_builder().http_status_as_error(false).build().into();
+            assert_eq!(agent.post(&nonce_url).send_empty().unwrap().status(), 401);
+            let nonce = |request: &VerifyRequest| -> NonceResponse {
+                ureq::post(&nonce_url).header("Authorization", "Bearer door-secret")
+                    .send_json(NonceRequest { body_sha256: request.body_hash() }).unwrap().body_mut().read_json().unwrap()
+            };
+            let captured = VerifyRequest { nonce: Some(nonce(&request("ann_probe")).nonce), ..request("ann_probe") };
+            let send = |request: &VerifyRequest| agent.post(&verify_url).header("Authorization", "Bearer door-secret").send_json(request).unwrap().status();
+            assert_eq!(send(&captured), 200);
+            assert_eq!(send(&captured), 401);
+            // A nonce only vouches for the face it was issued for
+            let swapped = VerifyRequest { nonce: Some(nonce(&request("bob_probe")).nonce), ..request("ann_probe") };
+            assert_eq!(send(&swapped), 401);
+            let granted = device.verify(&request("ann_probe")).unwrap();
+            let health: serde_json::Value = ureq::get(&health_url).call().unwrap().body_mut().read_json().unwrap();
+            assert_eq!(health["healthy"], true);
+            // Users and errors are only for the authenticated heartbeat
+            assert!(health.get("pending_errors").is_none() && health.get("enrolled_users").is_none(), "{}", health);
+            // Asking for a looser tolerance gets the server's maximum
+            let loose = VerifyRequest { tolerance: 10.0, ..request("stranger_probe") };
+            (granted, device.verify(&loose).unwrap(), offline_error)
+        }).await.unwrap();
+
+        assert!(responses.0.is_authenticated);
+        assert_eq!(responses.0.user_id.as_deref(), Some("ann"));
+        assert!(!responses.1.is_authenticated);
+        assert_eq!(responses.1.threshold, Some(DEFAULT_MAX_TOLERANCE));
+        assert!(format!("{:#}", responses.2).contains("after 2 attempts"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_enrollment_errors_name_no_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = FakeBackend::from_fixtures(dir.path().join("data"), FIXTURES).unwrap();
+        let auth = FaceAuth::builder().backend(backend).build().unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}{}", listener.local_addr().unwrap(), ENROLLMENTS_PATH);
+        let router = Server::new(auth, dir.path()).with_admin_token("admin").with_submit_token("portal").router();
+        tokio::spawn(async move { axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>()).await });
+
+        let data_dir = dir.path().to_string_lossy().into_owned();
+        tokio::task::spawn_blocking(move || {
+            let agent: ureq::Agent = ureq::Agent::config_builder().http_status_as_error(false).build().into();
+            let reply = |mut response: ureq::http::Response<ureq::Body>| (response.status().as_u16(), response.body_mut().read_to_string().unwrap());
+            let submit = |username: &str, photos: Vec<String>| {
+                let request = serde_json::json!({ "username": username, "photos": photos });
+                reply(agent.post(&url).header("Authorization", "Bearer portal").send_json(request).unwrap())
+            };
+
+            let (status, body) = submit("../ann", vec![BASE64.encode("ann_1")]);
+            assert_eq!((status, body.contains("Invalid username")), (400, true), "{}", body);
+            let (status, body) = submit("ann", Vec::new());
+            assert_eq!((status, body.contains("at least one photo")), (400, true), "{}", body);
+            let (status, body) = reply(agent.post(format!("{}/20200101000000000-0000/reject", url)).header("Authorization", "Bearer admin").send_empty().unwrap());
+            assert_eq!((status, body.contains(&data_dir)), (404, false), "{}", body);
+
+            let (status, body) = submit("ann", vec![BASE64.encode("ann_1")]);
+            assert_eq!(status, 201, "{}", body);
+            let id = serde_json::from_str::<PendingEnrollment>(&body).unwrap().id;
+            let approve = || reply(agent.post(format!("{}/{}/approve", url, id)).header("Authorization", "Bearer admin").send_empty().unwrap());
+            assert_eq!(approve().0, 200);
+            let (status, body) = approve();
+            assert_eq!((status, body.contains("No pending enrollment")), (409, true), "{}", body);
+        }).await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket_serves_until_shutdown() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let dir = tempfile::tempdir().unwrap();
+        let backend = FakeBackend::from_fixtures(dir.path().join("data"), FIXTURES).unwrap();
+        let auth = FaceAuth::builder().backend(backend).build().unwrap();
+        let socket = dir.path().join("face_auth.sock");
+        std::os::unix::net::UnixListener::bind(&socket).unwrap();
+        let listener = ServerListener::bind_unix(&socket).unwrap();
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let serving = tokio::spawn(Server::new(auth, dir.path()).serve_on(listener, async { let _ = stopped.await; }));
+
+        let mut stream = tokio::net::UnixStream::connect(&socket).await.unwrap();
+        stream.write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", HEALTH_PATH).as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 "), "{}", response);
+
+        stop.send(()).unwrap();
+        serving.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,158 @@
+//! Embedded admin web UI served under [`ADMIN_PATH`]
+//!
+//! The page itself is public; it asks for the admin token and sends it as
+//! `Authorization: Bearer` with every API call, which are checked like the
+//! moderation endpoints.
+
+use axum::extract::{Query, State};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{Html, IntoResponse};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use super::{authorize, blocking, default_tolerance, ApiError, Server};
+use crate::audit::AuditEntry;
+use crate::face_storage::{SampleSummary, UserSummary};
+use crate::v1::AuthRequest;
+use crate::FaceAuthResult;
+
+/// Where the admin UI is served
+pub const ADMIN_PATH: &str = "/admin";
+
+const INDEX_HTML: &str = include_str!("admin/index.html");
+const APP_JS: &str = include_str!("admin/app.js");
+const STYLE_CSS: &str = include_str!("admin/style.css");
+
+/// Source recorded in the audit log for live tests
+const TEST_SOURCE: &str = "admin-ui";
+
+/// A user with their samples, for judging enrollment quality
+#[derive(Debug, Serialize)]
+struct UserQuality {
+    #[serde(flatten)]
+    summary: UserSummary,
+    /// Average quality of the samples that report one
+    average_quality: Option<f64>,
+    samples: Vec<SampleSummary>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AuditQuery {
+    #[serde(default = "default_limit")]
+    limit: usize,
+    #[serde(default)]
+    user: Option<String>,
+}
+
+fn default_limit() -> usize {
+    200
+}
+
+#[derive(Debug, Deserialize)]
+struct TestAuthentication {
+    /// Base64-encoded JPEG or PNG photo
+    photo: String,
+    #[serde(default = "default_tolerance")]
+    tolerance: f64,
+}
+
+pub(super) fn routes() -> Router<Arc<Server>> {
+    Router::new()
+        .route(ADMIN_PATH, get(|| async { Html(INDEX_HTML) }))
+        .route(&format!("{}/app.js", ADMIN_PATH), get(|| async { ([(CONTENT_TYPE, "text/javascript")], APP_JS) }))
+        .route(&format!("{}/style.css", ADMIN_PATH), get(|| async { ([(CONTENT_TYPE, "text/css")], STYLE_CSS) }))
+        .route(&format!("{}/api/users", ADMIN_PATH), get(users))
+        .route(&format!("{}/api/audit", ADMIN_PATH), get(audit))
+        .route(&format!("{}/api/authenticate", ADMIN_PATH), post(test_authentication))
+}
+
+async fn users(State(server): State<Arc<Server>>, headers: HeaderMap) -> Result<Json<Vec<UserQuality>>, ApiError> {
+    authorize(&headers, server.admin_token.as_deref(), "admin")?;
+    let mut users = Vec::new();
+    for summary in server.auth.list_users().await? {
+        let samples = server.auth.list_samples(&summary.username).await?;
+        let qualities: Vec<f64> = samples.iter().filter_map(|sample| sample.quality).collect();
+        let average_quality = (!qualities.is_empty()).then(|| qualities.iter().sum::<f64>() / qualities.len() as f64);
+        users.push(UserQuality { summary, average_quality, samples });
+    }
+    Ok(Json(users))
+}
+
+/// Latest entries first, optionally only those of one user
+async fn audit(
+    State(server): State<Arc<Server>>,
+    headers: HeaderMap,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditEntry>>, ApiError> {
+    authorize(&headers, server.admin_token.as_deref(), "admin")?;
+    let entries = server.auth.audit_entries().await?.into_iter()
+        .rev()
+        .filter(|entry| query.user.is_none() || entry.user == query.user)
+        .take(query.limit)
+        .collect();
+    Ok(Json(entries))
+}
+
+/// Match an uploaded photo like a device would, e.g. a webcam snapshot taken
+/// by the browser, without opening a door or counting an attempt
+async fn test_authentication(
+    State(server): State<Arc<Server>>,
+    headers: HeaderMap,
+    Json(request): Json<TestAuthentication>,
+) -> Result<impl IntoResponse, ApiError> {
+    authorize(&headers, server.admin_token.as_deref(), "admin")?;
+    let photo = BASE64.decode(&request.photo).map_err(|e| ApiError(StatusCode::BAD_REQUEST, format!("Invalid photo: {}", e)))?;
+    let request = AuthRequest::upload(request.tolerance, photo, TEST_SOURCE).with_source_dir(&server.source_dir);
+    let result: FaceAuthResult = blocking(async move { server.auth.test_match(&request).await }).await?;
+    Ok(Json(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FaceAuth, FakeBackend};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_admin_ui_lists_users_and_tests_authentication() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = FakeBackend::from_fixtures(dir.path().join("data"), "tests/fixtures/faces.json").unwrap();
+        let source = dir.path().join("source");
+        let auth = FaceAuth::builder().backend(backend.clone()).source_dir(&source).build().unwrap();
+        backend.queue_frames(["ann_1", "ann_2"]);
+        auth.register_user("ann", 2, source.to_str().unwrap()).await.unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}{}", listener.local_addr().unwrap(), ADMIN_PATH);
+        let router = Server::new(auth, &source).with_admin_token("secret").router();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let (page, unauthorized, users, result, audit) = tokio::task::spawn_blocking(move || {
+            let agent: ureq::Agent = ureq::Agent::config_builder().http_status_as_error(false).build().into();
+            let page = agent.get(&url).call().unwrap().body_mut().read_to_string().unwrap();
+            let unauthorized = agent.get(format!("{}/api/users", url)).call().unwrap().status();
+            let users: serde_json::Value = agent.get(format!("{}/api/users", url))
+                .header("Authorization", "Bearer secret").call().unwrap().body_mut().read_json().unwrap();
+            let result: serde_json::Value = agent.post(format!("{}/api/authenticate", url))
+                .header("Authorization", "Bearer secret")
+                .send_json(serde_json::json!({ "photo": BASE64.encode("ann_probe") })).unwrap()
+                .body_mut().read_json().unwrap();
+            let audit: Vec<AuditEntry> = agent.get(format!("{}/api/audit?limit=1", url))
+                .header("Authorization", "Bearer secret").call().unwrap().body_mut().read_json().unwrap();
+            (page, unauthorized, users, result, audit)
+        }).await.unwrap();
+
+        assert!(page.contains("app.js"));
+        assert_eq!(unauthorized, 401);
+        assert_eq!(users[0]["username"], "ann");
+        assert_eq!(users[0]["samples"].as_array().unwrap().len(), 2);
+        assert_eq!(result["user_id"], "ann");
+        assert_eq!(audit.len(), 1);
+        assert_eq!(audit[0].operation, crate::AuditOperation::TestMatch);
+        assert_eq!(audit[0].provenance.as_ref().map(|p| p.source.as_str()), Some(TEST_SOURCE));
+    }
+}
@@ -0,0 +1,192 @@
+"use strict";
+
+const state = { token: sessionStorage.getItem("face_auth_admin_token") || "" };
+
+const $ = (selector) => document.querySelector(selector);
+
+function status(message) {
+  $("#status").textContent = message || "";
+}
+
+function cell(row, value, className) {
+  const td = row.insertCell();
+  td.textContent = value === null || value === undefined ? "-" : value;
+  if (className) td.className = className;
+  return td;
+}
+
+function formatNumber(value) {
+  return typeof value === "number" ? value.toFixed(3) : null;
+}
+
+async function api(path, options = {}) {
+  const response = await fetch(path, {
+    ...options,
+    headers: { "Authorization": `Bearer ${state.token}`, "Content-Type": "application/json", ...(options.headers || {}) },
+  });
+  if (response.status === 204) return null;
+  const body = await response.json().catch(() => null);
+  if (!response.ok) throw new Error((body && body.error) || `${response.status} ${response.statusText}`);
+  return body;
+}
+
+const NO_USERS_HINT = "No one is enrolled yet: approve a pending enrollment or register a user with the face_auth CLI.";
+
+async function loadUsers() {
+  const users = await api("/admin/api/users");
+  const tbody = $("#users tbody");
+  tbody.replaceChildren();
+  if (users.length === 0) {
+    const td = tbody.insertRow().insertCell();
+    td.colSpan = 5;
+    td.textContent = NO_USERS_HINT;
+  }
+  for (const user of users) {
+    const row = tbody.insertRow();
+    cell(row, user.username);
+    cell(row, user.sample_count);
+    cell(row, formatNumber(user.average_quality));
+    cell(row, user.enrollment_date);
+    cell(row, user.samples.filter((sample) => sample.quarantine).length);
+  }
+}
+
+async function loadEnrollments() {
+  const pending = await api("/enrollments");
+  const tbody = $("#enrollments tbody");
+  tbody.replaceChildren();
+  for (const enrollment of pending) {
+    const row = tbody.insertRow();
+    cell(row, enrollment.username);
+    cell(row, new Date(enrollment.submitted_at).toLocaleString());
+    cell(row, enrollment.submitted_by);
+    cell(row, enrollment.photos.length);
+    const review = enrollment.review;
+    if (!review) {
+      cell(row, "not reviewed");
+    } else if (review.duplicate_of) {
+      cell(row, `matches ${review.duplicate_of.user_id}`, "failed");
+    } else {
+      cell(row, `${review.usable_photos} usable, ${review.rejected_photos.length} rejected`, review.usable_photos > 0 ? "passed" : "failed");
+    }
+    const actions = row.insertCell();
+    for (const [label, action] of [["Approve", "approve"], ["Reject", "reject"]]) {
+      const button = document.createElement("button");
+      button.textContent = label;
+      button.addEventListener("click", () => moderate(enrollment.id, action));
+      actions.append(button);
+    }
+  }
+}
+
+async function moderate(id, action) {
+  const body = action === "approve" ? JSON.stringify({ tolerance: Number($("#approve-tolerance").value) }) : undefined;
+  try {
+    await api(`/enrollments/${encodeURIComponent(id)}/${action}`, { method: "POST", body });
+    status(`Enrollment ${action === "approve" ? "approved" : "rejected"}`);
+    await loadEnrollments();
+  } catch (error) {
+    status(error.message);
+  }
+}
+
+async function loadAudit() {
+  const params = new URLSearchParams({ limit: $("#audit-limit").value });
+  const user = $("#audit-user").value.trim();
+  if (user) params.set("user", user);
+  const entries = await api(`/admin/api/audit?${params}`);
+  const tbody = $("#audit tbody");
+  tbody.replaceChildren();
+  for (const entry of entries) {
+    const row = tbody.insertRow();
+    cell(row, new Date(entry.timestamp).toLocaleString());
+    cell(row, entry.operation);
+    cell(row, entry.user);
+    cell(row, entry.outcome, entry.outcome);
+    cell(row, formatNumber(entry.distance));
+    const details = [entry.denial && entry.denial.code, entry.device_id, entry.provenance && entry.provenance.source];
+    cell(row, details.filter(Boolean).join(", ") || null);
+  }
+}
+
+async function authenticate(blob) {
+  const photo = await new Promise((resolve, reject) => {
+    const reader = new FileReader();
+    reader.onload = () => resolve(reader.result.split(",")[1]);
+    reader.onerror = () => reject(reader.error);
+    reader.readAsDataURL(blob);
+  });
+  const result = await api("/admin/api/authenticate", {
+    method: "POST",
+    body: JSON.stringify({ photo, tolerance: Number($("#test-tolerance").value) }),
+  });
+  const output = $("#result");
+  output.className = result.is_authenticated ? "granted" : "denied";
+  output.textContent = JSON.stringify(result, null, 2);
+  if (result.denial && result.denial.code === "no_users_enrolled") status(NO_USERS_HINT);
+}
+
+async function startCamera() {
+  const video = $("#camera");
+  video.srcObject = await navigator.mediaDevices.getUserMedia({ video: true });
+  $("#capture").disabled = false;
+}
+
+function capture() {
+  const video = $("#camera");
+  const canvas = $("#snapshot");
+  canvas.width = video.videoWidth;
+  canvas.height = video.videoHeight;
+  canvas.getContext("2d").drawImage(video, 0, 0);
+  return new Promise((resolve) => canvas.toBlob(resolve, "image/jpeg", 0.92));
+}
+
+const loaders = { users: loadUsers, enrollments: loadEnrollments, audit: loadAudit, test: async () => {} };
+
+async function show(tab) {
+  for (const button of document.querySelectorAll("nav button")) {
+    button.classList.toggle("active", button.dataset.tab === tab);
+  }
+  for (const section of document.querySelectorAll("main section")) {
+    section.hidden = section.id !== tab;
+  }
+  status();
+  if (!state.token) {
+    status("Sign in with the admin token");
+    return;
+  }
+  try {
+    await loaders[tab]();
+  } catch (error) {
+    status(error.message);
+  }
+}
+
+const guarded = (action) => async (event) => {
+  if (event) event.preventDefault();
+  status();
+  try {
+    await action();
+  } catch (error) {
+    status(error.message);
+  }
+};
+
+$("#login").addEventListener("submit", (event) => {
+  event.preventDefault();
+  state.token = $("#token").value;
+  sessionStorage.setItem("face_auth_admin_token", state.token);
+  show(document.querySelector("nav button.active").dataset.tab);
+});
+for (const button of document.querySelectorAll("nav button")) {
+  button.addEventListener("click", () => show(button.dataset.tab));
+}
+$("#audit-filter").addEventListener("submit", guarded(loadAudit));
+$("#start-camera").addEventListener("click", guarded(startCamera));
+$("#capture").addEventListener("click", guarded(async () => authenticate(await capture())));
+$("#photo").addEventListener("change", guarded(async () => {
+  const file = $("#photo").files[0];
+  if (file) await authenticate(file);
+}));
+
+show("users");
@@ -0,0 +1,189 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, KeyInit, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::assurance::{AssuranceLevel, Session};
+use crate::crypto::KEY_LEN;
+use crate::error::FaceAuthError;
+
+/// Prefix of the signed bytes, so a signature can't be replayed as anything
+/// other than an authentication result
+const DOMAIN: &[u8] = b"face_auth.result.v1\0";
+
+/// Prefix of signed sessions, so results and sessions can't stand in for
+/// each other
+const SESSION_DOMAIN: &[u8] = b"face_auth.session.v1\0";
+
+/// What a result signature vouches for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResultClaims {
+    pub user_id: Option<String>,
+    pub authenticated: bool,
+    pub timestamp: DateTime<Utc>,
+    pub device_id: Option<String>,
+    pub assurance: Option<AssuranceLevel>,
+}
+
+/// Claims about an authentication with an HMAC-SHA256 tag made with the
+/// device key
+///
+/// Sent alongside results to a backend that holds each device's key, so it
+/// can tell results of genuine devices from ones forged by a tampered kiosk
+/// or anyone on the network. Serializes as the flat claims plus a hex
+/// `signature`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignedResult {
+    #[serde(flatten)]
+    pub claims: ResultClaims,
+    pub signature: String,
+}
+
+impl ResultClaims {
+    pub fn sign(self, key: &[u8; KEY_LEN]) -> Result<SignedResult> {
+        let tag = mac(key, DOMAIN, &self)?.finalize().into_bytes();
+        let signature = encode_hex(&tag);
+        Ok(SignedResult { claims: self, signature })
+    }
+}
+
+impl SignedResult {
+    /// Check the signature against `key`, returning the claims it covers
+    ///
+    /// Fails with [`FaceAuthError::InvalidSignature`] when the result was
+    /// altered or signed with another key.
+    pub fn verify(&self, key: &[u8; KEY_LEN]) -> Result<&ResultClaims> {
+        let invalid = || anyhow::Error::from(FaceAuthError::InvalidSignature { device_id: self.claims.device_id.clone() });
+        let tag = decode_hex(&self.signature).ok_or_else(invalid)?;
+        mac(key, DOMAIN, &self.claims)?.verify_slice(&tag).map_err(|_| invalid())?;
+        Ok(&self.claims)
+    }
+}
+
+/// A [`Session`] with an HMAC-SHA256 tag made with the device key
+///
+/// Sessions are kept by the application between actions, e.g. in a cookie or
+/// a cache. The signature stops a stored session from being altered to
+/// extend it or raise its assurance level. Serializes as the flat session
+/// plus a hex `signature`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignedSession {
+    #[serde(flatten)]
+    pub session: Session,
+    pub signature: String,
+}
+
+impl Session {
+    pub fn sign(self, key: &[u8; KEY_LEN]) -> Result<SignedSession> {
+        let tag = mac(key, SESSION_DOMAIN, &self)?.finalize().into_bytes();
+        let signature = encode_hex(&tag);
+        Ok(SignedSession { session: self, signature })
+    }
+}
+
+impl SignedSession {
+    /// Check the signature against `key`, returning the session it covers
+    ///
+    /// Fails with [`FaceAuthError::InvalidSignature`] when the session was
+    /// altered or signed with another key.
+    pub fn verify(&self, key: &[u8; KEY_LEN]) -> Result<&Session> {
+        let invalid = || anyhow::Error::from(FaceAuthError::InvalidSignature { device_id: None });
+        let tag = decode_hex(&self.signature).ok_or_else(invalid)?;
+        mac(key, SESSION_DOMAIN, &self.session)?.verify_slice(&tag).map_err(|_| invalid())?;
+        Ok(&self.session)
+    }
+
+    /// Verify the session, then check it still holds and reached `level`,
+    /// see [`Session::require_at`]
+    pub fn require_at(&self, key: &[u8; KEY_LEN], level: AssuranceLevel, now: DateTime<Utc>) -> Result<()> {
+        self.verify(key)?.require_at(level, now)
+    }
+}
+
+/// Verify a signed result received as JSON, looking up the key of the
+/// device that claims to have produced it
+pub fn verify_result<F>(json: &str, key_for_device: F) -> Result<ResultClaims>
+where
+    F: FnOnce(Option<&str>) -> Option<[u8; KEY_LEN]>,
+{
+    let signed: SignedResult = serde_json::from_str(json)?;
+    let key = key_for_device(signed.claims.device_id.as_deref())
+        .ok_or_else(|| anyhow!("No key for device {:?}", signed.claims.device_id))?;
+    signed.verify(&key)?;
+    Ok(signed.claims)
+}
+
+fn mac(key: &[u8; KEY_LEN], domain: &[u8], claims: &impl Serialize) -> Result<Hmac<Sha256>> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(domain);
+    mac.update(&serde_json::to_vec(claims)?);
+    Ok(mac)
+}
+
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub(crate) fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signed_results_verify_only_unaltered_with_the_device_key() {
+        let claims = ResultClaims {
+            user_id: Some("ann".into()),
+            authenticated: true,
+            timestamp: Utc::now(),
+            device_id: Some("kiosk-7".into()),
+            assurance: Some(AssuranceLevel::L2),
+        };
+        let key = [4; KEY_LEN];
+        let signed = claims.clone().sign(&key).unwrap();
+        assert_eq!(signed.signature.len(), 64);
+        assert_eq!(signed.verify(&key).unwrap(), &claims);
+
+        let json = serde_json::to_string(&signed).unwrap();
+        assert_eq!(verify_result(&json, |device| (device == Some("kiosk-7")).then_some(key)).unwrap(), claims);
+        assert!(verify_result(&json, |_| None).is_err());
+
+        let error = signed.verify(&[5; KEY_LEN]).unwrap_err();
+        assert!(matches!(error.downcast_ref::<FaceAuthError>(), Some(FaceAuthError::InvalidSignature { .. })));
+        let mut forged = signed.clone();
+        forged.claims.assurance = Some(AssuranceLevel::L3);
+        assert!(forged.verify(&key).is_err());
+        forged.signature = "zz".into();
+        assert!(forged.verify(&key).is_err());
+    }
+
+    #[test]
+    fn test_signed_sessions_reject_raised_levels_and_extended_expiry() {
+        let key = [4; KEY_LEN];
+        let now = Utc::now();
+        let signed = Session::starting_at("ann", AssuranceLevel::L2, std::time::Duration::from_secs(60), now).sign(&key).unwrap();
+        assert!(signed.require_at(&key, AssuranceLevel::L2, now).is_ok());
+        let json = serde_json::to_string(&signed).unwrap();
+        assert_eq!(serde_json::from_str::<SignedSession>(&json).unwrap(), signed);
+
+        let mut raised = signed.clone();
+        raised.session.assurance = AssuranceLevel::L3;
+        let error = raised.require_at(&key, AssuranceLevel::L3, now).unwrap_err();
+        assert!(matches!(error.downcast_ref::<FaceAuthError>(), Some(FaceAuthError::InvalidSignature { .. })));
+        let mut extended = signed.clone();
+        extended.session.expires_at += chrono::Duration::days(1);
+        assert!(extended.verify(&key).is_err());
+        assert!(signed.verify(&[5; KEY_LEN]).is_err());
+
+        // A result signature isn't a session signature
+        let claims = ResultClaims { user_id: Some("ann".into()), authenticated: true, timestamp: now, device_id: None, assurance: None };
+        let result = claims.sign(&key).unwrap();
+        assert!(SignedSession { signature: result.signature, ..signed }.verify(&key).is_err());
+    }
+}
@@ -0,0 +1,244 @@
+//! Experimental fuzzy extractor over face embeddings
+//!
+//! Enrollment derives a random key and public helper data; only an embedding
+//! close to the enrolled one reproduces the key from the helper data, so keys
+//! can be bound to a face without storing the embedding.
+//!
+//! The construction is a code-offset secure sketch: the embedding is reduced
+//! to bits by random sign projections, of which the most stable are kept, and
+//! the key is encoded with a repetition code. It has not been reviewed and
+//! the helper data leaks some information about the face; treat it as
+//! research, not as a replacement for a passphrase.
+
+use anyhow::{Result, anyhow, bail};
+use chacha20poly1305::aead::OsRng;
+use chacha20poly1305::aead::rand_core::RngCore;
+use hmac::{Hmac, KeyInit, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::crypto::KEY_LEN;
+use crate::signing::{decode_hex, encode_hex};
+
+/// Prefixes of the hashed bytes, so neither hash can stand in for the other
+const PROJECTION_DOMAIN: &[u8] = b"face_auth sketch projection v1";
+const KEY_DOMAIN: &[u8] = b"face_auth sketch key v1";
+const CHECK_DOMAIN: &[u8] = b"face_auth sketch check v1";
+
+/// Candidate projections per kept bit; only the most stable are used
+const CANDIDATES_PER_BIT: usize = 4;
+
+/// Strength of a sketch against noise between captures
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SketchParams {
+    /// Random bits the key is derived from
+    pub key_bits: usize,
+    /// Projected bits voting on each key bit; odd, higher tolerates more noise
+    /// but also lets closer lookalikes through
+    pub repetition: usize,
+}
+
+impl Default for SketchParams {
+    fn default() -> Self {
+        Self { key_bits: 128, repetition: 7 }
+    }
+}
+
+impl SketchParams {
+    pub fn validate(&self) -> Result<()> {
+        if self.key_bits == 0 || !self.key_bits.is_multiple_of(8) {
+            bail!("Sketch key bits must be a positive multiple of 8");
+        }
+        if self.repetition.is_multiple_of(2) {
+            bail!("Sketch repetition must be odd");
+        }
+        Ok(())
+    }
+}
+
+/// Public helper data of a face-derived key; reveals neither the key nor the
+/// embedding by itself
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecureSketch {
+    pub params: SketchParams,
+    /// Length of the embeddings the sketch was made from
+    pub dimensions: usize,
+    /// Hex seed of the projections
+    pub seed: String,
+    /// Projections kept, grouped by the key bit they vote on
+    pub positions: Vec<u32>,
+    /// Hex of the projected bits XORed with the encoded key bits
+    pub offset: String,
+    /// Hex hash telling a reproduced key from a wrong one
+    pub check: String,
+}
+
+impl SecureSketch {
+    /// Derive a fresh key from `embedding`, with the helper data that
+    /// reproduces it
+    pub fn enroll(embedding: &[f64], params: SketchParams) -> Result<([u8; KEY_LEN], Self)> {
+        params.validate()?;
+        if embedding.is_empty() || embedding.iter().any(|v| !v.is_finite()) {
+            bail!("Embedding must be non-empty and finite");
+        }
+        let mut seed = [0u8; 32];
+        OsRng.fill_bytes(&mut seed);
+        let mut secret = vec![0u8; params.key_bits / 8];
+        OsRng.fill_bytes(&mut secret);
+
+        let kept = params.key_bits * params.repetition;
+        let projections = project(&seed, embedding, kept * CANDIDATES_PER_BIT);
+        let mut stable: Vec<usize> = (0..projections.len()).collect();
+        stable.sort_by(|&a, &b| projections[b].abs().total_cmp(&projections[a].abs()));
+        let mut positions: Vec<u32> = stable[..kept].iter().map(|&i| i as u32).collect();
+        // Spread the stable bits over key bits so none gets only the weakest ones
+        shuffle(&mut positions, &seed);
+
+        let mut offset = vec![0u8; kept.div_ceil(8)];
+        for (i, &position) in positions.iter().enumerate() {
+            let bit = (projections[position as usize] > 0.0) ^ get_bit(&secret, i / params.repetition);
+            set_bit(&mut offset, i, bit);
+        }
+        let key = derive_key(&seed, &secret);
+        let sketch = Self {
+            params,
+            dimensions: embedding.len(),
+            seed: encode_hex(&seed),
+            positions,
+            offset: encode_hex(&offset),
+            check: encode_hex(&check_hash(&seed, &key)),
+        };
+        Ok((key, sketch))
+    }
+
+    /// The enrolled key, if `embedding` is close enough to the enrolled one
+    pub fn reproduce(&self, embedding: &[f64]) -> Result<Option<[u8; KEY_LEN]>> {
+        if embedding.len() != self.dimensions {
+            bail!("Sketch needs {} dimensions, got {}", self.dimensions, embedding.len());
+        }
+        if embedding.iter().any(|v| !v.is_finite()) {
+            bail!("Embedding must be finite");
+        }
+        // Helper data is read from disk, so none of it is trusted to fit
+        self.params.validate()?;
+        let malformed = || anyhow!("Malformed sketch helper data");
+        let seed = decode_hex(&self.seed).filter(|seed| seed.len() == 32).ok_or_else(malformed)?;
+        let offset = decode_hex(&self.offset).ok_or_else(malformed)?;
+        let repetition = self.params.repetition;
+        let kept = self.params.key_bits.checked_mul(repetition).ok_or_else(malformed)?;
+        let count = kept.checked_mul(CANDIDATES_PER_BIT).ok_or_else(malformed)?;
+        if self.positions.len() != kept || offset.len() != kept.div_ceil(8) || self.positions.iter().any(|&p| p as usize >= count) {
+            return Err(malformed());
+        }
+        let projections = project(&seed, embedding, count);
+
+        let mut secret = vec![0u8; self.params.key_bits / 8];
+        for (bit, group) in self.positions.chunks(repetition).enumerate() {
+            let votes = group.iter().enumerate()
+                .filter(|&(j, &position)| (projections[position as usize] > 0.0) ^ get_bit(&offset, bit * repetition + j))
+                .count();
+            set_bit(&mut secret, bit, votes * 2 > repetition);
+        }
+        let key = derive_key(&seed, &secret);
+        Ok((encode_hex(&check_hash(&seed, &key)) == self.check).then_some(key))
+    }
+}
+
+/// Projections of `embedding` onto `count` random ±1 directions from `seed`
+fn project(seed: &[u8], embedding: &[f64], count: usize) -> Vec<f64> {
+    (0..count as u32)
+        .map(|index| {
+            let mut sum = 0.0;
+            for (block, values) in (0u32..).zip(embedding.chunks(256)) {
+                let signs = Sha256::new()
+                    .chain_update(PROJECTION_DOMAIN)
+                    .chain_update(seed)
+                    .chain_update(index.to_le_bytes())
+                    .chain_update(block.to_le_bytes())
+                    .finalize();
+                for (i, value) in values.iter().enumerate() {
+                    sum += if get_bit(&signs, i) { *value } else { -*value };
+                }
+            }
+            sum
+        })
+        .collect()
+}
+
+/// Deterministic Fisher-Yates shuffle keyed by `seed`
+fn shuffle(items: &mut [u32], seed: &[u8]) {
+    for i in (1..items.len()).rev() {
+        let digest = Sha256::new().chain_update(seed).chain_update((i as u64).to_le_bytes()).finalize();
+        let j = u64::from_le_bytes(digest[..8].try_into().expect("digest has 32 bytes")) % (i as u64 + 1);
+        items.swap(i, j as usize);
+    }
+}
+
+fn derive_key(seed: &[u8], secret: &[u8]) -> [u8; KEY_LEN] {
+    let mut mac = <Hmac<Sha256> as KeyInit>::new_from_slice(seed).expect("HMAC accepts keys of any length");
+    mac.update(KEY_DOMAIN);
+    mac.update(secret);
+    mac.finalize().into_bytes().into()
+}
+
+fn check_hash(seed: &[u8], key: &[u8; KEY_LEN]) -> [u8; 32] {
+    Sha256::new().chain_update(CHECK_DOMAIN).chain_update(seed).chain_update(key).finalize().into()
+}
+
+fn get_bit(bytes: &[u8], index: usize) -> bool {
+    bytes[index / 8] >> (index % 8) & 1 == 1
+}
+
+fn set_bit(bytes: &mut [u8], index: usize, value: bool) {
+    if value {
+        bytes[index / 8] |= 1 << (index % 8);
+    } else {
+        bytes[index / 8] &= !(1 << (index % 8));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn embedding(seed: u64) -> Vec<f64> {
+        let mut state = seed;
+        (0..128).map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 33) as f64 / (1u64 << 31) as f64 - 0.5
+        }).collect()
+    }
+
+    #[test]
+    fn test_only_close_embeddings_reproduce_the_key() {
+        let enrolled = embedding(1);
+        let (key, sketch) = SecureSketch::enroll(&enrolled, SketchParams::default()).unwrap();
+        let json = serde_json::to_string(&sketch).unwrap();
+        assert!(!json.contains(&format!("{}", enrolled[0])));
+        let sketch: SecureSketch = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(sketch.reproduce(&enrolled).unwrap(), Some(key));
+        let noise = embedding(2);
+        let close: Vec<f64> = enrolled.iter().zip(&noise).map(|(v, n)| v + 0.1 * n).collect();
+        assert_eq!(sketch.reproduce(&close).unwrap(), Some(key));
+        assert_eq!(sketch.reproduce(&embedding(3)).unwrap(), None);
+        assert!(sketch.reproduce(&enrolled[..64]).is_err());
+
+        let (other, _) = SecureSketch::enroll(&enrolled, SketchParams::default()).unwrap();
+        assert_ne!(key, other, "every enrollment draws a new key");
+        assert!(SketchParams { key_bits: 128, repetition: 4 }.validate().is_err());
+
+        // Tampered helper data is an error, never a panic or a huge allocation
+        let tampered = |edit: fn(&mut SecureSketch)| {
+            let mut sketch = sketch.clone();
+            edit(&mut sketch);
+            sketch.reproduce(&enrolled)
+        };
+        assert!(tampered(|s| s.params.repetition = 0).is_err());
+        assert!(tampered(|s| s.params.key_bits = usize::MAX / 8 * 8).is_err());
+        assert!(tampered(|s| s.positions[0] = u32::MAX).is_err());
+        assert!(tampered(|s| s.seed = "00".into()).is_err());
+        assert!(tampered(|s| { s.positions.pop(); }).is_err());
+        assert!(sketch.reproduce(&vec![f64::NAN; enrolled.len()]).is_err());
+    }
+}
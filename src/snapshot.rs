@@ -0,0 +1,274 @@
+use anyhow::{Context, Result, anyhow, bail};
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::Read;
+use std::ops::{Deref, Range};
+use std::path::Path;
+
+use crate::compression::{self, Compression};
+use crate::face_storage::{write_atomic, FaceDatabase, ENCODING_DIMENSIONS};
+use crate::matching::{self, MatchCandidate};
+
+const MAGIC: &[u8; 8] = b"FACESNAP";
+const VERSION: u32 = 1;
+const HEADER_LEN: usize = 24;
+
+// Layout, little-endian:
+//   magic[8] version:u32 dimensions:u32 samples:u32 users:u32
+//   values: f32 * samples * dimensions
+//   owners: u32 * samples (index into the user table)
+//   user table: (len:u32, utf8 bytes) * users
+
+/// Write the samples of `db` as a snapshot for [`DatabaseSnapshot::open`]
+pub fn write_snapshot(db: &FaceDatabase, path: &Path) -> Result<()> {
+    write_snapshot_with(db, path, Compression::None)
+}
+
+/// [`write_snapshot`], compressed as given
+///
+/// A compressed snapshot is smaller on disk but is decompressed into memory
+/// when opened instead of being mapped.
+pub fn write_snapshot_with(db: &FaceDatabase, path: &Path, compression: Compression) -> Result<()> {
+    let dimensions = db.users.values()
+        .flat_map(|p| p.face_encodings.first())
+        .map(|s| s.encoding.len())
+        .next()
+        .unwrap_or(ENCODING_DIMENSIONS);
+
+    let mut values = Vec::new();
+    let mut owners = Vec::new();
+    for (slot, profile) in db.users.values().enumerate() {
+        for sample in profile.face_encodings.iter().filter(|s| !s.is_quarantined()) {
+            if sample.encoding.len() != dimensions {
+                tracing::warn!("Skipping sample {}: {} dimensions, snapshot has {}", sample.sample_id, sample.encoding.len(), dimensions);
+                continue;
+            }
+            values.extend(sample.encoding.iter().map(|&x| x as f32));
+            owners.push(slot as u32);
+        }
+    }
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + values.len() * 4 + owners.len() * 4);
+    bytes.extend_from_slice(MAGIC);
+    for field in [VERSION, dimensions as u32, owners.len() as u32, db.users.len() as u32] {
+        bytes.extend_from_slice(&field.to_le_bytes());
+    }
+    for value in values {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    for owner in owners {
+        bytes.extend_from_slice(&owner.to_le_bytes());
+    }
+    for user_id in db.users.keys() {
+        bytes.extend_from_slice(&(user_id.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(user_id.as_bytes());
+    }
+    write_atomic(path, &compression.compress(&bytes)?)
+}
+
+/// Bytes of an open snapshot
+enum Contents {
+    Mapped(Mmap),
+    /// A decompressed snapshot, in words so the embeddings are aligned for
+    /// casting to `f32`
+    Decompressed { words: Vec<u32>, len: usize },
+}
+
+impl Deref for Contents {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Contents::Mapped(map) => map,
+            Contents::Decompressed { words, len } => &bytemuck::cast_slice(words)[..*len],
+        }
+    }
+}
+
+impl Contents {
+    fn decompressed(bytes: &[u8]) -> Self {
+        let mut words = vec![0u32; bytes.len().div_ceil(4)];
+        bytemuck::cast_slice_mut(&mut words)[..bytes.len()].copy_from_slice(bytes);
+        Contents::Decompressed { words, len: bytes.len() }
+    }
+}
+
+/// Read-only, memory-mapped view of a database's samples
+///
+/// Only the user names are decoded at open; embeddings are matched straight
+/// from the mapping, so startup cost and heap use don't grow with the
+/// number of samples.
+pub struct DatabaseSnapshot {
+    map: Contents,
+    dimensions: usize,
+    values: Range<usize>,
+    owners: Range<usize>,
+    user_ids: Vec<String>,
+}
+
+impl DatabaseSnapshot {
+    pub fn open(path: &Path) -> Result<Self> {
+        if cfg!(target_endian = "big") {
+            bail!("Database snapshots are only supported on little-endian targets");
+        }
+        let mut file = File::open(path).with_context(|| format!("Failed to open snapshot {}", path.display()))?;
+        let mut magic = [0u8; 4];
+        if file.read_exact(&mut magic).is_ok() && compression::is_compressed(&magic) {
+            let bytes = std::fs::read(path)?;
+            let contents = Contents::decompressed(&compression::decompress(&bytes)?);
+            return Self::parse(contents).with_context(|| format!("Invalid snapshot {}", path.display()));
+        }
+        // SAFETY: snapshots are written atomically (temp file + rename) and never
+        // modified in place, so the mapped file doesn't change underneath us.
+        let map = unsafe { Mmap::map(&file)? };
+        Self::parse(Contents::Mapped(map)).with_context(|| format!("Invalid snapshot {}", path.display()))
+    }
+
+    fn parse(map: Contents) -> Result<Self> {
+        if map.len() < HEADER_LEN || &map[..8] != MAGIC {
+            bail!("not a face database snapshot");
+        }
+        let header = |i: usize| u32::from_le_bytes(map[8 + i * 4..12 + i * 4].try_into().unwrap()) as usize;
+        let (version, dimensions, samples, users) = (header(0), header(1), header(2), header(3));
+        if version != VERSION as usize {
+            bail!("unsupported snapshot version {}", version);
+        }
+
+        // Counts come from the file, so every size is checked against its length
+        let end_of = |start: usize, count: usize, width: usize| {
+            count.checked_mul(width)
+                .and_then(|len| start.checked_add(len))
+                .filter(|&end| end <= map.len())
+                .ok_or_else(|| anyhow!("truncated snapshot"))
+        };
+        let sample_len = dimensions.checked_mul(4).ok_or_else(|| anyhow!("too many dimensions"))?;
+        let values = HEADER_LEN..end_of(HEADER_LEN, samples, sample_len)?;
+        let owners = values.end..end_of(values.end, samples, 4)?;
+        // Each user takes at least its 4-byte length
+        let mut user_ids = Vec::with_capacity(users.min((map.len() - owners.end) / 4));
+        let mut offset = owners.end;
+        for _ in 0..users {
+            let len_end = end_of(offset, 1, 4).context("truncated user table")?;
+            let len = u32::from_le_bytes(map[offset..len_end].try_into()?) as usize;
+            let end = end_of(len_end, len, 1).context("truncated user table")?;
+            user_ids.push(String::from_utf8(map[len_end..end].to_vec()).context("user id is not UTF-8")?);
+            offset = end;
+        }
+
+        let snapshot = Self { map, dimensions, values, owners, user_ids };
+        if snapshot.owner_slots().iter().any(|&slot| slot as usize >= users) {
+            bail!("sample refers to a missing user");
+        }
+        Ok(snapshot)
+    }
+
+    fn embedding_values(&self) -> &[f32] {
+        // Both contents are 4-byte aligned and values start at a multiple of 4
+        bytemuck::cast_slice(&self.map[self.values.clone()])
+    }
+
+    fn owner_slots(&self) -> &[u32] {
+        bytemuck::cast_slice(&self.map[self.owners.clone()])
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    /// Number of samples
+    pub fn len(&self) -> usize {
+        self.owners.len() / 4
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn users(&self) -> &[String] {
+        &self.user_ids
+    }
+
+    /// Closest user to `probe`, like [`crate::matching::best_match`]
+    pub fn find_best_match(&self, probe: &[f64], tolerance: f64) -> Option<MatchCandidate> {
+        if probe.len() != self.dimensions || self.dimensions == 0 {
+            return None;
+        }
+        let probe: Vec<f32> = probe.iter().map(|&x| x as f32).collect();
+
+        self.embedding_values()
+            .chunks_exact(self.dimensions)
+            .enumerate()
+            .map(|(i, sample)| (i, matching::squared_euclidean_f32(sample, &probe)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, squared)| {
+                let distance = f64::from(squared.sqrt());
+                MatchCandidate {
+                    user_id: self.user_ids[self.owner_slots()[i] as usize].clone(),
+                    distance,
+                    is_match: distance <= tolerance,
+                }
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::face_storage::{FaceSample, UserProfile};
+
+    #[test]
+    fn test_snapshot_roundtrip_matches_database() {
+        let mut db = FaceDatabase::default();
+        for (user, offset) in [("ann", 0.1), ("bob", -0.1)] {
+            let mut profile: UserProfile = serde_json::from_value(serde_json::json!({ "user_id": user, "face_encodings": [] })).unwrap();
+            profile.face_encodings.push(FaceSample {
+                encoding: vec![offset; ENCODING_DIMENSIONS],
+                timestamp: String::new(),
+                image_path: None,
+                sample_id: format!("{}_0", user),
+                quality: None,
+                template_version: None,
+                quarantine: None,
+            });
+            db.users.insert(user.to_string(), profile);
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db.snap");
+        write_snapshot(&db, &path).unwrap();
+        let snapshot = DatabaseSnapshot::open(&path).unwrap();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot.users(), ["ann", "bob"]);
+
+        let probe = vec![-0.09; ENCODING_DIMENSIONS];
+        let expected = db.find_best_match(&probe, 0.6).unwrap();
+        let candidate = snapshot.find_best_match(&probe, 0.6).unwrap();
+        assert_eq!(candidate.user_id, expected.user_id);
+        assert!((candidate.distance - expected.distance).abs() < 1e-5);
+
+        write_snapshot_with(&db, &path, Compression::zstd_default()).unwrap();
+        assert!(compression::is_compressed(&std::fs::read(&path).unwrap()));
+        let compressed = DatabaseSnapshot::open(&path).unwrap();
+        assert_eq!(compressed.find_best_match(&probe, 0.6).unwrap(), candidate);
+
+        std::fs::write(&path, b"FACESNAP").unwrap();
+        assert!(DatabaseSnapshot::open(&path).is_err());
+
+        // Counts that overflow or point past the end are rejected, not trusted
+        let header = |dimensions: u32, samples: u32, users: u32, tail: &[u8]| {
+            let mut bytes = MAGIC.to_vec();
+            for field in [VERSION, dimensions, samples, users] {
+                bytes.extend_from_slice(&field.to_le_bytes());
+            }
+            bytes.extend_from_slice(tail);
+            std::fs::write(&path, bytes).unwrap();
+            DatabaseSnapshot::open(&path)
+        };
+        assert!(header(u32::MAX, u32::MAX, 0, &[]).is_err());
+        assert!(header(128, 2, 0, &[0; 16]).is_err());
+        assert!(header(0, 0, u32::MAX, &[]).is_err());
+        assert!(header(0, 0, 1, &[5, 0, 0, 0, b'a']).is_err());
+        assert!(header(0, 0, 1, &[1, 0, 0, 0, 0xff]).is_err());
+        assert_eq!(header(0, 0, 1, &[1, 0, 0, 0, b'a']).unwrap().users(), ["a"]);
+    }
+}
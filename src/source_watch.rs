@@ -0,0 +1,146 @@
+//! Pick up credentials added to or removed from the source directory by
+//! another process, e.g. a sync job, without restarting
+//!
+//! Authentications read the source directory on every attempt, so new
+//! credentials are matched right away; what a running daemon misses is that
+//! anything changed. A [`SourceWatcher`] watches the directory, waits until
+//! writes have been quiet for the debounce interval (a sync often writes a
+//! file in several steps), compares the valid credentials with the previous
+//! scan and reports each difference as a [`SourceEvent`].
+
+use anyhow::Result;
+use notify::{RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::face_storage;
+
+/// Quiet time after the last change before the directory is scanned
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// A difference between two scans of the source directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SourceEvent {
+    UserAdded { user_id: String },
+    UserRemoved { user_id: String },
+    /// The user's credential was replaced, e.g. after re-enrollment
+    UserUpdated { user_id: String },
+}
+
+/// Fingerprint of every valid credential in `dir`, by user
+fn scan(dir: &Path) -> Result<BTreeMap<String, [u8; 32]>> {
+    face_storage::load_credentials_dir(dir)?
+        .into_iter()
+        .map(|profile| Ok((profile.user_id.clone(), Sha256::digest(serde_json::to_vec(&profile)?).into())))
+        .collect()
+}
+
+/// Events turning `before` into `after`, in user order
+fn diff(before: &BTreeMap<String, [u8; 32]>, after: &BTreeMap<String, [u8; 32]>) -> Vec<SourceEvent> {
+    let removed = before.keys()
+        .filter(|user| !after.contains_key(*user))
+        .map(|user| SourceEvent::UserRemoved { user_id: user.clone() });
+    let changed = after.iter().filter_map(|(user, fingerprint)| match before.get(user) {
+        None => Some(SourceEvent::UserAdded { user_id: user.clone() }),
+        Some(previous) if previous != fingerprint => Some(SourceEvent::UserUpdated { user_id: user.clone() }),
+        Some(_) => None,
+    });
+    removed.chain(changed).collect()
+}
+
+/// Watches a source directory, see the [module docs](self)
+///
+/// Stops when dropped.
+pub struct SourceWatcher {
+    dir: PathBuf,
+    watcher: Option<notify::RecommendedWatcher>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl std::fmt::Debug for SourceWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SourceWatcher").field("dir", &self.dir).finish_non_exhaustive()
+    }
+}
+
+impl SourceWatcher {
+    /// Watch `dir`, creating it if needed, and pass every change to
+    /// `on_event` on a background thread
+    ///
+    /// Credentials present at the start are the baseline, not events.
+    pub fn start(dir: impl Into<PathBuf>, debounce: Duration, on_event: impl Fn(&SourceEvent) + Send + 'static) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        let mut known = scan(&dir)?;
+        let (changed, changes) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) if event.kind.is_access() => {}
+            Ok(_) => {
+                let _ = changed.send(());
+            }
+            Err(e) => tracing::warn!("Watching the source directory failed: {}", e),
+        })?;
+        watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+
+        let thread = thread::spawn({
+            let dir = dir.clone();
+            // Ends when the watcher, and with it the sender, is dropped
+            move || while changes.recv().is_ok() {
+                loop {
+                    match changes.recv_timeout(debounce) {
+                        Ok(()) => continue,
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+                match scan(&dir) {
+                    Ok(current) => {
+                        for event in diff(&known, &current) {
+                            on_event(&event);
+                        }
+                        known = current;
+                    }
+                    Err(e) => tracing::warn!("Failed to rescan {}: {:#}", dir.display(), e),
+                }
+            }
+        });
+        Ok(Self { dir, watcher: Some(watcher), thread: Some(thread) })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for SourceWatcher {
+    fn drop(&mut self) {
+        drop(self.watcher.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_reports_added_removed_and_updated_users() {
+        let before = BTreeMap::from([("ann".to_string(), [1; 32]), ("bob".to_string(), [2; 32])]);
+        let after = BTreeMap::from([("bob".to_string(), [3; 32]), ("cat".to_string(), [4; 32])]);
+        assert_eq!(diff(&before, &after), [
+            SourceEvent::UserRemoved { user_id: "ann".into() },
+            SourceEvent::UserUpdated { user_id: "bob".into() },
+            SourceEvent::UserAdded { user_id: "cat".into() },
+        ]);
+        assert!(diff(&after, &after).is_empty());
+        assert_eq!(serde_json::to_value(&diff(&before, &after)[0]).unwrap(), serde_json::json!({"event": "user_removed", "user_id": "ann"}));
+    }
+}
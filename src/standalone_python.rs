@@ -1,16 +1,76 @@
-use anyhow::{Result, anyhow};
-use std::process::Command;
-use std::path::Path;
-
+use anyhow::{Context, Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, ExitStatus, Stdio};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::Instant;
+
+use crate::auth::FrameBlur;
+use crate::camera::CameraSource;
+use crate::error::FaceAuthError;
+use crate::face_storage::ExportedCredential;
+use crate::assurance::Liveness;
+use crate::denial::DenialReason;
+use crate::kyc::DocumentFace;
+use crate::matching::{MatchCandidate, SampleDistance};
+use crate::profiles::Preprocessing;
+use crate::redact::{DetectedFace, Redaction, RedactionPolicy, RedactionStyle};
+use crate::registration::{RegistrationOutcome, SampleQuality};
+use crate::scene::{SceneGate, SceneStats};
+use crate::tracking::{TrackEvent, TrackingConfig};
+#[cfg(feature = "attention")]
+use crate::attention::{AttentionTracker, FaceLandmarks, HeadPose};
+use crate::timing::TimingBreakdown;
+
+/// Python packages the script needs, pinned so a reinstall gets the versions
+/// the script was tested with
+const PINNED_PACKAGES: [&str; 7] = [
+    "numpy==1.26.4",
+    "Pillow==10.4.0",
+    "cmake==3.30.2",
+    "dlib==19.24.6",
+    "opencv-python==4.10.0.84",
+    "face_recognition==1.3.0",
+    "face_recognition_models==0.3.0",
+];
+
+#[derive(Debug, Clone)]
 pub struct StandalonePythonFaceAuth {
     executable_path: String,
     script_path: String,
+    data_dir: PathBuf,
+    /// Shared with clones, such as the worker pool's
+    preprocessing: Arc<Mutex<Preprocessing>>,
+    /// Keep frames in the script's memory instead of the captures directory
+    strict_privacy: Arc<AtomicBool>,
+    frame_blur: Arc<Mutex<FrameBlur>>,
+    /// Camera or video to capture from; the system default camera when `None`
+    source: Option<CameraSource>,
+    /// Frames of the video file read so far, so every capture continues where
+    /// the previous one stopped. Shared with clones
+    video_position: Arc<AtomicU64>,
 }
 
 impl StandalonePythonFaceAuth {
+    /// Create an instance whose data directory is the current working directory
     pub fn new() -> Result<Self> {
+        Self::with_data_dir(std::env::current_dir()?)
+    }
+
+    /// Create an instance that keeps the script's database, captured images
+    /// and exports under `data_dir`
+    ///
+    /// Every subprocess runs with `data_dir` as its working directory, so the
+    /// script's relative paths no longer depend on where the host app was started.
+    pub fn with_data_dir(data_dir: impl AsRef<Path>) -> Result<Self> {
         // Find Python script first
-        let script_path = Self::find_script_path()?;
+        let script_path = absolute_path(&Self::find_script_path()?);
 
         // Try to find or setup Python environment
         let executable_path = Self::find_or_setup_python()?;
@@ -18,14 +78,82 @@ impl StandalonePythonFaceAuth {
         // Verify dependencies are installed
         Self::ensure_dependencies(&executable_path)?;
 
+        // Bare commands like `python3` are resolved through PATH, anything else
+        // must survive the change of working directory
+        let executable_path = if executable_path.contains(std::path::MAIN_SEPARATOR) {
+            absolute_path(&executable_path)
+        } else {
+            executable_path
+        };
+
+        let data_dir = std::path::absolute(data_dir.as_ref())?;
+        std::fs::create_dir_all(&data_dir)?;
+
         Ok(Self {
             executable_path,
             script_path,
+            data_dir,
+            preprocessing: Arc::default(),
+            strict_privacy: Arc::default(),
+            frame_blur: Arc::default(),
+            source: None,
+            video_position: Arc::default(),
         })
     }
 
+    /// Instance around an arbitrary interpreter and script, skipping discovery
+    #[cfg(test)]
+    pub(crate) fn from_parts(executable_path: &str, script_path: &str, data_dir: &Path) -> Self {
+        Self {
+            executable_path: executable_path.to_string(),
+            script_path: script_path.to_string(),
+            data_dir: data_dir.to_path_buf(),
+            preprocessing: Arc::default(),
+            strict_privacy: Arc::default(),
+            frame_blur: Arc::default(),
+            source: None,
+            video_position: Arc::default(),
+        }
+    }
+
+    /// Directory the script reads and writes its own files in
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    /// Same script and data directory, capturing from OpenCV camera `index`
+    pub fn with_camera(&self, index: u32) -> Self {
+        self.with_camera_source(CameraSource::Device(index))
+    }
+
+    /// Same script and data directory, capturing from `source`, e.g. a
+    /// prerecorded video read from its first frame
+    pub fn with_camera_source(&self, source: CameraSource) -> Self {
+        Self { source: Some(source), video_position: Arc::default(), ..self.clone() }
+    }
+
+    /// Adjust authentication frames this way before detection
+    pub fn set_preprocessing(&self, preprocessing: Preprocessing) {
+        *self.preprocessing.lock().unwrap_or_else(PoisonError::into_inner) = preprocessing;
+    }
+
+    pub fn preprocessing(&self) -> Preprocessing {
+        self.preprocessing.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Never write captured frames, thumbnails or sample paths; frames stay
+    /// in the script's memory from capture to match
+    pub fn set_strict_privacy(&self, strict: bool) {
+        self.strict_privacy.store(strict, Ordering::Relaxed);
+    }
+
+    /// Blur faces in captured frames once they've been matched
+    pub fn set_frame_blur(&self, blur: FrameBlur) {
+        *self.frame_blur.lock().unwrap_or_else(PoisonError::into_inner) = blur;
+    }
+
     fn find_script_path() -> Result<String> {
-        let script_paths = vec![
+        let script_paths = [
             "python_face_auth_simple.py",
             "../python_face_auth_simple.py",
             "../../python_face_auth_simple.py",
@@ -44,10 +172,10 @@ impl StandalonePythonFaceAuth {
     }
 
     fn find_or_setup_python() -> Result<String> {
-        println!("🔍 Searching for Python environment...");
+        tracing::debug!("Searching for a Python environment");
 
         // First, try to find existing virtual environment
-        let venv_paths = vec![
+        let venv_paths = [
             "./face_auth_env/bin/python",
             "../face_auth_env/bin/python",
             "../../face_auth_env/bin/python",
@@ -55,21 +183,19 @@ impl StandalonePythonFaceAuth {
 
         for path in &venv_paths {
             if Path::new(path).exists() {
-                println!("✅ Found virtual environment at: {}", path);
+                tracing::info!("Found virtual environment at {}", path);
                 return Ok(path.to_string());
             }
         }
 
-        println!("⚠️  Virtual environment not found");
-        println!("🔧 Attempting to create virtual environment automatically...");
+        tracing::info!("Virtual environment not found, creating one");
 
         // Try to create virtual environment
         if let Ok(python_path) = Self::create_virtual_environment() {
             return Ok(python_path);
         }
 
-        println!("⚠️  Could not create virtual environment");
-        println!("🔍 Falling back to system Python...");
+        tracing::warn!("Could not create a virtual environment, falling back to the system Python");
 
         // Fallback to system Python
         Self::find_system_python()
@@ -85,11 +211,11 @@ impl StandalonePythonFaceAuth {
             return Err(anyhow!("python3 not found in system"));
         }
 
-        println!("📦 Creating virtual environment at ./face_auth_env...");
+        tracing::info!("Creating virtual environment at ./face_auth_env");
 
         // Create virtual environment
         let output = Command::new("python3")
-            .args(&["-m", "venv", "face_auth_env"])
+            .args(["-m", "venv", "face_auth_env"])
             .output()?;
 
         if !output.status.success() {
@@ -99,7 +225,7 @@ impl StandalonePythonFaceAuth {
 
         let venv_python = "./face_auth_env/bin/python";
         if Path::new(venv_python).exists() {
-            println!("✅ Virtual environment created successfully");
+            tracing::info!("Virtual environment created");
             Ok(venv_python.to_string())
         } else {
             Err(anyhow!("Virtual environment created but python not found"))
@@ -108,7 +234,7 @@ impl StandalonePythonFaceAuth {
 
     fn find_system_python() -> Result<String> {
         // Try different Python commands
-        let python_commands = vec!["python3", "python"];
+        let python_commands = ["python3", "python"];
 
         for cmd in python_commands {
             let check = Command::new(cmd)
@@ -118,7 +244,7 @@ impl StandalonePythonFaceAuth {
             if let Ok(output) = check {
                 if output.status.success() {
                     let version = String::from_utf8_lossy(&output.stdout);
-                    println!("✅ Found system Python: {} ({})", cmd, version.trim());
+                    tracing::info!("Found system Python: {} ({})", cmd, version.trim());
                     return Ok(cmd.to_string());
                 }
             }
@@ -133,54 +259,48 @@ impl StandalonePythonFaceAuth {
     }
 
     fn ensure_dependencies(python_path: &str) -> Result<()> {
-        println!("🔍 Checking Python dependencies...");
+        tracing::debug!("Checking Python dependencies");
 
         // Check if all required packages are installed
         let check = Command::new(python_path)
-            .args(&["-c", "import face_recognition; import cv2; import face_recognition_models; print('OK')"])
+            .args(["-c", "import face_recognition; import cv2; import face_recognition_models; print('OK')"])
             .output();
 
         if let Ok(output) = check {
             if output.status.success() && String::from_utf8_lossy(&output.stdout).contains("OK") {
-                println!("✅ All dependencies are installed");
+                tracing::debug!("All Python dependencies are installed");
                 return Ok(());
             }
         }
 
-        println!("⚠️  Required dependencies not found");
-        println!("📦 Installing dependencies automatically...");
+        tracing::warn!("Required Python dependencies not found, installing them");
 
         Self::install_dependencies(python_path)
     }
 
+    /// Reinstall the pinned Python dependencies into this instance's
+    /// interpreter, after a worker failed to import them
+    pub(crate) fn repair_dependencies(&self) -> Result<()> {
+        Self::install_dependencies(&self.executable_path)
+    }
+
     fn install_dependencies(python_path: &str) -> Result<()> {
-        // First ensure pip is up to date
-        println!("📦 Upgrading pip...");
+        // First ensure pip knows the pinned wheels
+        tracing::info!("Upgrading pip");
         let _ = Command::new(python_path)
-            .args(&["-m", "pip", "install", "--upgrade", "pip"])
+            .args(["-m", "pip", "install", "pip==24.2"])
             .output();
 
-        // Install each required package
-        let packages = vec![
-            "numpy>=1.21.0",
-            "Pillow>=9.0.0",
-            "cmake>=3.18.0",
-            "dlib>=19.24.0",
-            "opencv-python>=4.8.0",
-            "face_recognition>=1.3.0",
-            "git+https://github.com/ageitgey/face_recognition_models",
-        ];
-
-        for (i, package) in packages.iter().enumerate() {
-            println!("📦 Installing {}/{}: {}", i + 1, packages.len(), package);
+        for (i, package) in PINNED_PACKAGES.iter().enumerate() {
+            tracing::info!("Installing {}/{}: {}", i + 1, PINNED_PACKAGES.len(), package);
 
             let output = Command::new(python_path)
-                .args(&["-m", "pip", "install", package])
+                .args(["-m", "pip", "install", package])
                 .output()?;
 
             if !output.status.success() {
                 let stderr = String::from_utf8_lossy(&output.stderr);
-                eprintln!("⚠️  Warning: Failed to install {}: {}", package, stderr);
+                tracing::warn!("Failed to install {}: {}", package, stderr);
 
                 // For critical packages, fail
                 if package.contains("face_recognition") {
@@ -191,152 +311,553 @@ impl StandalonePythonFaceAuth {
                     ));
                 }
             } else {
-                println!("✅ Installed {}", package);
+                tracing::info!("Installed {}", package);
             }
         }
 
-        println!("✅ All dependencies installed successfully!");
+        tracing::info!("All Python dependencies installed");
         Ok(())
     }
 
-    pub fn register_user(&self, username: &str, samples: u32, generated_dir: &str) -> Result<bool> {
-        println!("🦀 Using standalone Python executable (NO Python install required)");
-        println!("📦 Executable: {}", self.executable_path);
-
-        let output = Command::new(&self.executable_path)
+    /// Run the Python script, forwarding its output line-by-line into `tracing`
+    /// Script invocation with the data directory applied
+    pub(crate) fn command(&self, args: &[String]) -> Command {
+        let mut command = Command::new(&self.executable_path);
+        command
             .arg(&self.script_path)
-            .arg("--mode")
-            .arg("register")
-            .arg("--user")
-            .arg(username)
-            .arg("--samples")
-            .arg(&samples.to_string())
-            .arg("--generated-dir")
-            .arg(generated_dir)
-            .output()?;
+            .args(args)
+            .arg("--data-dir")
+            .arg(&self.data_dir)
+            .current_dir(&self.data_dir);
+        match &self.source {
+            Some(CameraSource::Device(index)) => {
+                command.arg("--camera-index").arg(index.to_string());
+            }
+            Some(CameraSource::VideoFile(path)) => {
+                command
+                    .arg("--video-file")
+                    .arg(path)
+                    .arg("--video-start")
+                    .arg(self.video_position.load(Ordering::Relaxed).to_string());
+            }
+            None => {}
+        }
+        if self.strict_privacy.load(Ordering::Relaxed) {
+            command.arg("--strict-privacy");
+        }
+        match *self.frame_blur.lock().unwrap_or_else(PoisonError::into_inner) {
+            FrameBlur::Off => {}
+            FrameBlur::Bystanders => {
+                command.arg("--blur").arg("bystanders");
+            }
+            FrameBlur::AllFaces => {
+                command.arg("--blur").arg("all");
+            }
+        }
+        command
+    }
 
-        if output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            println!("📱 Standalone Python output:\n{}", stdout);
+    fn run_script(&self, operation: &'static str, args: &[String]) -> Result<ScriptOutput> {
+        self.run_script_with_input(operation, args, None)
+    }
 
-            // Check if registration was successful
-            if stdout.contains("Registration complete") || stdout.contains("samples stored") {
-                Ok(true)
-            } else {
-                Ok(false)
-            }
-        } else {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            Err(anyhow!("Standalone Python registration failed: {}", stderr))
+    /// Run the script with `input` on its stdin
+    fn run_script_with_input(&self, operation: &'static str, args: &[String], input: Option<&[u8]>) -> Result<ScriptOutput> {
+        tracing::debug!(operation, executable = %self.executable_path, "running standalone Python script");
+
+        let mut child = self.command(args)
+            .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        // Written from a thread so a script printing before it reads can't deadlock
+        let writer = input.zip(child.stdin.take()).map(|(input, mut stdin)| {
+            let input = input.to_vec();
+            thread::spawn(move || {
+                use std::io::Write;
+                let _ = stdin.write_all(&input);
+            })
+        });
+
+        let (tx, rx) = mpsc::channel();
+        let readers = [
+            child.stdout.take().map(|out| spawn_line_reader(out, OutputStream::Stdout, tx.clone())),
+            child.stderr.take().map(|err| spawn_line_reader(err, OutputStream::Stderr, tx.clone())),
+        ];
+        drop(tx);
+
+        let mut output = ScriptOutput::default();
+        for (stream, line) in rx {
+            forward_line(operation, stream, &line);
+            self.note_video_position(stream, &line);
+            output.push(stream, line);
+        }
+        for reader in readers.into_iter().flatten().chain(writer) {
+            let _ = reader.join();
+        }
+        output.status = Some(child.wait()?);
+
+        Ok(output)
+    }
+
+    pub fn register_user(&self, username: &str, samples: u32, generated_dir: &str) -> Result<RegistrationOutcome> {
+        let output = self.run_script("register", &[
+            "--mode".into(), "register".into(),
+            "--user".into(), username.into(),
+            "--samples".into(), samples.to_string(),
+            "--generated-dir".into(), absolute_path(generated_dir),
+        ])?;
+
+        // A reported outcome covers failed captures too; without one the script crashed
+        match output.result::<RegistrationOutcome>() {
+            Some(outcome) => outcome,
+            None => Err(output.into_error("registration")),
         }
     }
 
     pub fn authenticate_user(&self, tolerance: f64, source_dir: &str) -> Result<StandaloneAuthResult> {
-        println!("🦀 Using standalone Python executable (NO Python install required)");
-        println!("📦 Executable: {}", self.executable_path);
+        self.authenticate(tolerance, source_dir, None)
+    }
 
-        let output = Command::new(&self.executable_path)
-            .arg(&self.script_path)
-            .arg("--mode")
-            .arg("auth")
-            .arg("--tolerance")
-            .arg(&tolerance.to_string())
-            .arg("--source-dir")
-            .arg(source_dir)
-            .output()?;
+    /// Match a previously captured frame instead of capturing from the camera
+    pub fn authenticate_image(&self, tolerance: f64, source_dir: &str, image: &Path) -> Result<StandaloneAuthResult> {
+        self.authenticate(tolerance, source_dir, Some(image))
+    }
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
+    /// Match an encoded image passed to the script on stdin, never written to disk
+    pub fn authenticate_image_bytes(&self, tolerance: f64, source_dir: &str, image: &[u8]) -> Result<StandaloneAuthResult> {
+        self.authenticate_with(tolerance, source_dir, Some("-".into()), Some(image))
+    }
 
-        println!("📱 Standalone Python output:\n{}", stdout);
+    fn authenticate(&self, tolerance: f64, source_dir: &str, image: Option<&Path>) -> Result<StandaloneAuthResult> {
+        self.authenticate_with(tolerance, source_dir, image.map(absolute_path), None)
+    }
 
-        if !stderr.is_empty() {
-            println!("⚠️ Standalone Python stderr:\n{}", stderr);
+    fn authenticate_with(&self, tolerance: f64, source_dir: &str, image: Option<String>, input: Option<&[u8]>) -> Result<StandaloneAuthResult> {
+        let started = Instant::now();
+        let mut args: Vec<String> = vec![
+            "--mode".into(), "auth".into(),
+            "--tolerance".into(), tolerance.to_string(),
+            "--source-dir".into(), absolute_path(source_dir),
+        ];
+        if let Some(image) = image {
+            args.push("--image".into());
+            args.push(image);
         }
+        self.push_preprocessing_args(&mut args);
+        let output = self.run_script_with_input("authentication", &args, input)?;
+        let elapsed_ms = started.elapsed().as_millis() as u32;
 
-        // Parse the output to determine authentication result
-        let success = output.status.success();
-        let is_match = stdout.contains("Authentication successful") || stdout.contains("✅");
-        let confidence = extract_confidence_from_output(&stdout);
-        let distance = extract_distance_from_output(&stdout);
-        let matched_user = extract_matched_user_from_output(&stdout);
-        let processing_time = extract_processing_time_from_output(&stdout);
+        output.auth_result(tolerance, elapsed_ms)
+    }
 
-        Ok(StandaloneAuthResult {
-            success,
-            is_match: Some(is_match),
-            confidence,
-            distance,
-            threshold: Some(tolerance),
-            matched_user,
-            processing_time_ms: processing_time,
-            raw_output: stdout.to_string(),
-        })
+    fn push_preprocessing_args(&self, args: &mut Vec<String>) {
+        let preprocessing = self.preprocessing();
+        if preprocessing.equalize {
+            args.push("--equalize".into());
+        }
+        if let Some(gamma) = preprocessing.gamma {
+            args.push("--gamma".into());
+            args.push(gamma.to_string());
+        }
     }
 
-    pub fn export_user(&self, username: &str, filename: &str) -> Result<bool> {
-        let mut cmd = Command::new(&self.executable_path);
-        cmd.arg(&self.script_path)
-            .arg("--mode")
-            .arg("export")
-            .arg("--user")
-            .arg(username);
+    /// Authenticate continuously from the camera until `on_result` returns
+    /// `false`, matching only frames that pass `gate`
+    ///
+    /// The script keeps the camera open and reports one result per attempt.
+    /// A video file stops it at its end. Returns the frame counts as of the
+    /// last attempt.
+    pub fn watch(
+        &self,
+        tolerance: f64,
+        source_dir: &str,
+        gate: &SceneGate,
+        on_result: &mut dyn FnMut(StandaloneAuthResult, &SceneStats) -> Result<bool>,
+    ) -> Result<SceneStats> {
+        let args = self.watch_args(tolerance, source_dir, gate)?;
+        let mut stats = SceneStats::default();
+        let mut started = Instant::now();
+        self.stream_script("watch", &args, &mut |json, raw| {
+            let line = parse_line::<WatchLine>(json)?;
+            stats = line.scene;
+            if line.end {
+                return Ok(false);
+            }
+            let result = parse_line::<ReportedAuthResult>(json)?
+                .into_result(tolerance, started.elapsed().as_millis() as u32, true, raw)?;
+            started = Instant::now();
+            on_result(result, &stats)
+        })?;
+        Ok(stats)
+    }
 
-        // Only add --file argument if filename is not empty
-        if !filename.is_empty() {
-            cmd.arg("--file").arg(filename);
+    /// Like [`StandalonePythonFaceAuth::watch`], but following each face
+    /// across frames and identifying it once when it enters the view
+    pub fn track_faces(
+        &self,
+        tolerance: f64,
+        source_dir: &str,
+        gate: &SceneGate,
+        tracking: &TrackingConfig,
+        on_event: &mut dyn FnMut(TrackEvent<StandaloneAuthResult>, &SceneStats) -> Result<bool>,
+    ) -> Result<SceneStats> {
+        tracking.validate()?;
+        let mut args = self.watch_args(tolerance, source_dir, gate)?;
+        args.extend([
+            "--track".into(),
+            "--track-overlap".into(), tracking.min_overlap.to_string(),
+            "--track-max-missed".into(), tracking.max_missed_frames.to_string(),
+        ]);
+        #[cfg(feature = "attention")]
+        let mut attention = tracking.attention.clone().map(|config| {
+            args.push("--attention".into());
+            AttentionTracker::new(config)
+        });
+        let mut stats = SceneStats::default();
+        let mut started = Instant::now();
+        self.stream_script("tracking", &args, &mut |json, raw| {
+            let line = parse_line::<WatchLine>(json)?;
+            stats = line.scene;
+            if line.end {
+                return Ok(false);
+            }
+            let event = match line.track {
+                #[cfg(feature = "attention")]
+                Some(ReportedTrack::Landmarks { track_id, landmarks }) => {
+                    let pose = HeadPose::from_landmarks(&landmarks);
+                    match (attention.as_mut(), pose) {
+                        (Some(tracker), Some(pose)) => match tracker.update(track_id, &pose) {
+                            Some(attention) => TrackEvent::AttentionChanged { track_id, attention, pose },
+                            None => return Ok(true),
+                        },
+                        _ => return Ok(true),
+                    }
+                }
+                Some(ReportedTrack::Entered { track_id }) => {
+                    let result = parse_line::<ReportedAuthResult>(json)?
+                        .into_result(tolerance, started.elapsed().as_millis() as u32, true, raw)?;
+                    started = Instant::now();
+                    TrackEvent::Entered { track_id, result }
+                }
+                Some(ReportedTrack::Exited { track_id, user_id, frames }) => {
+                    #[cfg(feature = "attention")]
+                    if let Some(tracker) = attention.as_mut() {
+                        tracker.remove(track_id);
+                    }
+                    TrackEvent::Exited { track_id, user_id, frames }
+                }
+                None => return Err(anyhow!("Malformed result from Python script: missing track event")),
+            };
+            on_event(event, &stats)
+        })?;
+        Ok(stats)
+    }
+
+    fn watch_args(&self, tolerance: f64, source_dir: &str, gate: &SceneGate) -> Result<Vec<String>> {
+        gate.validate()?;
+        let mut args: Vec<String> = vec![
+            "--mode".into(), "watch".into(),
+            "--tolerance".into(), tolerance.to_string(),
+            "--source-dir".into(), absolute_path(source_dir),
+            "--min-change".into(), gate.min_changed_fraction.to_string(),
+            "--pixel-threshold".into(), gate.pixel_threshold.to_string(),
+            "--cooldown".into(), gate.cooldown.as_secs_f64().to_string(),
+        ];
+        if !gate.face_check {
+            args.push("--no-face-check".into());
         }
+        self.push_preprocessing_args(&mut args);
+        Ok(args)
+    }
 
-        let output = cmd.output()?;
+    /// Run a mode that keeps going until stopped, handing each result line and
+    /// the output printed since the previous one to `on_line` until it returns
+    /// `false`
+    fn stream_script(
+        &self,
+        operation: &'static str,
+        args: &[String],
+        on_line: &mut dyn FnMut(&str, String) -> Result<bool>,
+    ) -> Result<()> {
+        tracing::debug!(operation, executable = %self.executable_path, "starting streaming Python script");
+        let mut child = self.command(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let (tx, rx) = mpsc::channel();
+        let readers = [
+            child.stdout.take().map(|out| spawn_line_reader(out, OutputStream::Stdout, tx.clone())),
+            child.stderr.take().map(|err| spawn_line_reader(err, OutputStream::Stderr, tx.clone())),
+        ];
+        drop(tx);
+
+        let mut output = ScriptOutput::default();
+        let mut outcome = None;
+        for (stream, line) in rx {
+            forward_line(operation, stream, &line);
+            self.note_video_position(stream, &line);
+            let Some(json) = line.strip_prefix(RESULT_PREFIX) else {
+                output.push(stream, line);
+                continue;
+            };
+            match on_line(json, std::mem::take(&mut output).stdout) {
+                Ok(true) => {}
+                stop => {
+                    outcome = Some(stop.map(|_| ()));
+                    break;
+                }
+            }
+        }
 
-        if output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            println!("{}", stdout);
-            Ok(stdout.contains("exported successfully"))
-        } else {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            println!("{}", stderr);
-            Ok(false)
+        let _ = child.kill();
+        for reader in readers.into_iter().flatten() {
+            let _ = reader.join();
         }
+        output.status = Some(child.wait()?);
+        // The script only stops on its own when the camera fails, or after
+        // the end line of a video file
+        outcome.unwrap_or_else(|| Err(output.into_error(operation)))
     }
 
-    pub fn import_user(&self, filename: &str) -> Result<bool> {
-        let output = Command::new(&self.executable_path)
-            .arg(&self.script_path)
-            .arg("--mode")
-            .arg("import")
-            .arg("--file")
-            .arg(filename)
-            .output()?;
+    /// Continue the next capture after the frame the script reported reading last
+    fn note_video_position(&self, stream: OutputStream, line: &str) {
+        if stream != OutputStream::Stdout {
+            return;
+        }
+        if let Some(position) = line.strip_prefix(VIDEO_POSITION_PREFIX).and_then(|p| p.trim().parse().ok()) {
+            self.video_position.store(position, Ordering::Relaxed);
+        }
+    }
 
-        if output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            println!("{}", stdout);
-            Ok(stdout.contains("imported successfully"))
-        } else {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            println!("{}", stderr);
-            Ok(false)
+    /// Load the detector and encoder models once, optionally opening the camera
+    pub fn warm_up(&self, camera: bool) -> Result<WarmUpOutcome> {
+        let mut args: Vec<String> = vec!["--mode".into(), "warm".into()];
+        if camera {
+            args.push("--camera".into());
+        }
+        let output = self.run_script("warm-up", &args)?;
+        match output.result::<WarmUpOutcome>() {
+            Some(outcome) if output.success() => outcome,
+            _ => Err(output.into_error("warm-up")),
         }
     }
 
-    pub fn list_users(&self) -> Result<()> {
-        let output = Command::new(&self.executable_path)
-            .arg(&self.script_path)
-            .arg("--mode")
-            .arg("list")
-            .output()?;
+    /// Capture `count` frames from the camera without encoding them, `None`
+    /// where a capture failed
+    ///
+    /// Frames are saved to the captures directory with `label` as prefix.
+    pub fn capture_frames(&self, label: &str, count: u32) -> Result<Vec<Option<PathBuf>>> {
+        #[derive(Deserialize)]
+        struct Captured {
+            images: Vec<Option<PathBuf>>,
+        }
+
+        let output = self.run_script("capture", &[
+            "--mode".into(), "capture".into(),
+            "--user".into(), label.into(),
+            "--samples".into(), count.to_string(),
+        ])?;
+        match output.result::<Captured>() {
+            Some(Ok(captured)) if captured.images.len() == count as usize => Ok(captured.images),
+            Some(Ok(_)) => Err(anyhow!("Python script returned a different number of frames than requested")),
+            Some(Err(e)) => Err(e),
+            None => Err(output.into_error("capture")),
+        }
+    }
+
+    /// Detect and score the face in `image`, then encode it (`embed`) or
+    /// write an aligned chip of it to `out_dir`
+    pub fn prefilter(&self, image: &Path, embed: bool, out_dir: &Path, size: u32) -> Result<Prefiltered> {
+        let mut args: Vec<String> = vec![
+            "--mode".into(), "prefilter".into(),
+            "--image".into(), absolute_path(image),
+            "--out-dir".into(), absolute_path(out_dir),
+            "--size".into(), size.to_string(),
+        ];
+        if embed {
+            args.push("--embed".into());
+        }
+        let output = self.run_script("prefilter", &args)?;
+        match output.result::<Prefiltered>() {
+            Some(result) if output.success() => result,
+            _ => Err(output.into_error("prefilter")),
+        }
+    }
+
+    /// Encoding of the first face in each image, `None` where no face was found
+    pub fn encode_images(&self, images: &[PathBuf]) -> Result<Vec<Option<Vec<f64>>>> {
+        #[derive(Deserialize)]
+        struct Encoded {
+            encodings: Vec<Option<Vec<f64>>>,
+        }
+
+        let mut args: Vec<String> = vec!["--mode".into(), "encode".into(), "--images".into()];
+        args.extend(images.iter().map(absolute_path));
+        let output = self.run_script("encoding", &args)?;
+        match output.result::<Encoded>() {
+            Some(Ok(encoded)) if encoded.encodings.len() == images.len() => Ok(encoded.encodings),
+            Some(Ok(_)) => Err(anyhow!("Python script returned a different number of encodings than images")),
+            Some(Err(e)) => Err(e),
+            None => Err(output.into_error("encoding")),
+        }
+    }
+
+    /// Blur or pixelate faces in an image passed on stdin, see [`crate::redact`]
+    ///
+    /// The script sends the redacted image back in its result line, so
+    /// neither the image nor the face it may keep is ever written to disk.
+    pub fn redact_faces(&self, image: &[u8], policy: &RedactionPolicy) -> Result<Redaction> {
+        #[derive(Deserialize)]
+        struct Redacted {
+            faces: Vec<DetectedFace>,
+            image: String,
+        }
+
+        let format = if image.starts_with(b"\x89PNG") { "png" } else { "jpg" };
+        let mut args: Vec<String> = vec![
+            "--mode".into(), "redact".into(),
+            "--image".into(), "-".into(),
+            "--format".into(), format.into(),
+            "--padding".into(), policy.padding.to_string(),
+        ];
+        if policy.keep_largest {
+            args.push("--keep-largest".into());
+        }
+        if let RedactionStyle::Pixelate { block } = policy.style {
+            args.extend(["--style".into(), "pixelate".into(), "--block".into(), block.to_string()]);
+        }
+        let output = self.run_script_with_input("redaction", &args, Some(image))?;
+        let redacted = match output.result::<Redacted>() {
+            Some(result) if output.success() => result?,
+            _ => return Err(output.into_error("redaction")),
+        };
+        let image = BASE64.decode(&redacted.image).context("Python script returned an invalid redacted image")?;
+        Ok(Redaction { image, faces: redacted.faces })
+    }
+
+    /// Encoding of the portrait on an identity document photo, see [`DocumentFace`]
+    pub fn encode_document(&self, document: &Path) -> Result<DocumentFace> {
+        let args: Vec<String> = vec!["--mode".into(), "document".into(), "--image".into(), absolute_path(document)];
+        let output = self.run_script("document encoding", &args)?;
+        match output.result::<DocumentFace>() {
+            Some(result) if output.success() => result,
+            _ => Err(output.into_error("document encoding")),
+        }
+    }
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
+    /// Write a padded crop of every face in `image` to `out_dir`, largest first
+    pub fn crop_faces(&self, image: &Path, out_dir: &Path) -> Result<Vec<PathBuf>> {
+        #[derive(Deserialize)]
+        struct Crops {
+            crops: Vec<PathBuf>,
+        }
 
-        if !stdout.is_empty() {
-            println!("{}", stdout);
+        let args: Vec<String> = vec![
+            "--mode".into(), "crops".into(),
+            "--image".into(), absolute_path(image),
+            "--out-dir".into(), absolute_path(out_dir),
+        ];
+        let output = self.run_script("face crops", &args)?;
+        match output.result::<Crops>() {
+            Some(result) if output.success() => result.map(|crops| crops.crops),
+            _ => Err(output.into_error("face crops")),
         }
-        if !stderr.is_empty() {
-            println!("{}", stderr);
+    }
+
+    /// Write a dlib-style aligned chip of the first face in each image to
+    /// `out_dir`, returning the chip paths (`None` where no face was found)
+    pub fn face_chips(&self, images: &[PathBuf], out_dir: &Path, size: u32) -> Result<Vec<Option<PathBuf>>> {
+        #[derive(Deserialize)]
+        struct Chips {
+            chips: Vec<Option<PathBuf>>,
         }
 
+        let mut args: Vec<String> = vec![
+            "--mode".into(), "chips".into(),
+            "--out-dir".into(), absolute_path(out_dir),
+            "--size".into(), size.to_string(),
+            "--images".into(),
+        ];
+        args.extend(images.iter().map(absolute_path));
+        let output = self.run_script("face chips", &args)?;
+        match output.result::<Chips>() {
+            Some(Ok(chips)) if chips.chips.len() == images.len() => Ok(chips.chips),
+            Some(Ok(_)) => Err(anyhow!("Python script returned a different number of chips than images")),
+            Some(Err(e)) => Err(e),
+            None => Err(output.into_error("face chips")),
+        }
+    }
+
+    pub fn export_user(&self, username: &str, filename: &str) -> Result<bool> {
+        let mut args: Vec<String> = vec![
+            "--mode".into(), "export".into(),
+            "--user".into(), username.into(),
+        ];
+
+        // Only add --file argument if filename is not empty
+        if !filename.is_empty() {
+            args.push("--file".into());
+            args.push(absolute_path(filename));
+        }
+
+        let output = self.run_script("export", &args)?;
+        Ok(output.success() && output.stdout.contains("exported successfully"))
+    }
+
+    /// Import an exported credential, rejecting malformed files before the script sees them
+    pub fn import_user(&self, filename: &str) -> Result<bool> {
+        let path = resolve_import_path(filename);
+        ExportedCredential::load(self.data_dir.join(&path))?;
+        let output = self.run_script("import", &[
+            "--mode".into(), "import".into(),
+            "--file".into(), path,
+        ])?;
+        Ok(output.success() && output.stdout.contains("imported successfully"))
+    }
+
+    /// Load a pickle with the script's interpreter and return it as JSON,
+    /// with numpy arrays converted to lists
+    ///
+    /// Unpickling runs arbitrary code; only load files you trust.
+    pub fn load_pickle(&self, path: &Path) -> Result<serde_json::Value> {
+        const CONVERT: &str = r#"
+import json, pickle, sys
+
+def plain(x):
+    if hasattr(x, "tolist"):
+        return x.tolist()
+    if isinstance(x, dict):
+        return {str(k): plain(v) for k, v in x.items()}
+    if isinstance(x, (list, tuple)):
+        return [plain(v) for v in x]
+    return x
+
+with open(sys.argv[1], "rb") as f:
+    print(json.dumps(plain(pickle.load(f))))
+"#;
+
+        let output = Command::new(&self.executable_path)
+            .arg("-c")
+            .arg(CONVERT)
+            .arg(absolute_path(path))
+            .output()
+            .map_err(|e| anyhow!("Failed to run Python: {}", e))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(FaceAuthError::ScriptFailed {
+                operation: "pickle conversion",
+                exit_code: output.status.code(),
+                output_tail: tail_lines(stderr.lines(), OUTPUT_TAIL_LINES),
+            }.into());
+        }
+        serde_json::from_slice(&output.stdout)
+            .map_err(|e| anyhow!("Unreadable pickle conversion output: {}", e))
+    }
+
+    pub fn list_users(&self) -> Result<()> {
+        self.run_script("list", &["--mode".into(), "list".into()])?;
         Ok(())
     }
 
@@ -349,23 +870,198 @@ impl StandalonePythonFaceAuth {
         let output = Command::new(&self.executable_path)
             .arg(&self.script_path)
             .arg("--help")
+            .current_dir(&self.data_dir)
             .output()?;
 
         if output.status.success() {
-            println!("✅ Standalone Python executable is working");
-            println!("📏 File size: {} MB",
-                std::fs::metadata(&self.executable_path)
-                    .map(|m| m.len() / 1024 / 1024)
-                    .unwrap_or(0)
+            tracing::info!(
+                size_mb = std::fs::metadata(&self.executable_path).map(|m| m.len() / 1024 / 1024).unwrap_or(0),
+                "Standalone Python executable is working"
             );
             Ok(())
         } else {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            Err(anyhow!("Standalone executable test failed: {}", stderr))
+            Err(FaceAuthError::ScriptFailed {
+                operation: "self-test",
+                exit_code: output.status.code(),
+                output_tail: tail_lines(stderr.lines(), OUTPUT_TAIL_LINES),
+            }.into())
         }
     }
 }
 
+/// Prefix of the machine-readable result line printed by the script
+pub const RESULT_PREFIX: &str = "FACE_AUTH_RESULT ";
+
+/// Prefix of the line the script prints with the number of frames of a video
+/// file read once it closes the video
+pub const VIDEO_POSITION_PREFIX: &str = "FACE_AUTH_VIDEO_POSITION ";
+
+/// Number of trailing output lines attached to script errors
+pub const OUTPUT_TAIL_LINES: usize = 20;
+
+/// Stream of the child process a line was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Output captured from one script invocation
+#[derive(Debug, Default)]
+pub struct ScriptOutput {
+    pub status: Option<ExitStatus>,
+    pub stdout: String,
+    pub stderr: String,
+    /// Both streams interleaved in arrival order
+    lines: Vec<String>,
+}
+
+impl ScriptOutput {
+    fn push(&mut self, stream: OutputStream, line: String) {
+        let buffer = match stream {
+            OutputStream::Stdout => &mut self.stdout,
+            OutputStream::Stderr => &mut self.stderr,
+        };
+        buffer.push_str(&line);
+        buffer.push('\n');
+        self.lines.push(line);
+    }
+
+    /// Output consisting of `stdout` only, without an exit status
+    pub fn from_stdout(stdout: &str) -> Self {
+        let mut output = Self::default();
+        for line in stdout.lines() {
+            output.push(OutputStream::Stdout, line.to_string());
+        }
+        output
+    }
+
+    pub fn success(&self) -> bool {
+        self.status.map(|s| s.success()).unwrap_or(false)
+    }
+
+    /// Parse the script's result line, if it printed one
+    pub fn result<T: DeserializeOwned>(&self) -> Option<Result<T>> {
+        let json = self.stdout.lines().rev().find_map(|l| l.strip_prefix(RESULT_PREFIX))?;
+        Some(serde_json::from_str(json).map_err(|e| anyhow!("Malformed result from Python script: {}", e)))
+    }
+
+    /// Last `n` lines of combined stdout/stderr output
+    pub fn tail(&self, n: usize) -> Vec<String> {
+        tail_lines(self.lines.iter().map(String::as_str), n)
+    }
+
+    /// Authentication result from the result line, or from the text output of older scripts
+    pub fn auth_result(self, tolerance: f64, elapsed_ms: u32) -> Result<StandaloneAuthResult> {
+        let success = self.success();
+        if let Some(reported) = self.result::<ReportedAuthResult>() {
+            return reported?.into_result(tolerance, elapsed_ms, success, self.stdout);
+        }
+
+        // Older scripts only print text, so parse the output to determine the result
+        let stdout = &self.stdout;
+        let is_match = stdout.contains("Authentication successful") || stdout.contains("✅");
+        let confidence = extract_confidence_from_output(stdout);
+        let distance = extract_distance_from_output(stdout);
+        let matched_user = extract_matched_user_from_output(stdout);
+        let processing_time = extract_processing_time_from_output(stdout);
+
+        Ok(StandaloneAuthResult {
+            success,
+            is_match: Some(is_match),
+            confidence,
+            distance,
+            threshold: Some(tolerance),
+            closest_user: matched_user.clone(),
+            matched_user,
+            runner_up: None,
+            sample_distances: Vec::new(),
+            brightness: None,
+            image_path: None,
+            processing_time_ms: processing_time.or(Some(elapsed_ms)),
+            timings: TimingBreakdown::default().with_backend_elapsed(elapsed_ms.into()),
+            liveness: None,
+            denial: stdout.contains("No face detected").then_some(DenialReason::NoFace),
+            raw_output: self.stdout,
+        })
+    }
+
+    fn into_error(self, operation: &'static str) -> anyhow::Error {
+        FaceAuthError::ScriptFailed {
+            operation,
+            exit_code: self.status.and_then(|s| s.code()),
+            output_tail: self.tail(OUTPUT_TAIL_LINES),
+        }.into()
+    }
+}
+
+/// Resolve `path` against the host's working directory
+pub(crate) fn absolute_path(path: impl AsRef<Path>) -> String {
+    let path = path.as_ref();
+    std::path::absolute(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Import files may be given relative to the host's working directory or,
+/// like `exported_credentials/<file>`, relative to the data directory
+fn resolve_import_path(path: &str) -> String {
+    if Path::new(path).exists() {
+        absolute_path(path)
+    } else {
+        path.to_string()
+    }
+}
+
+fn tail_lines<'a>(lines: impl Iterator<Item = &'a str>, n: usize) -> Vec<String> {
+    let lines: Vec<&str> = lines.filter(|l| !l.trim().is_empty()).collect();
+    lines[lines.len().saturating_sub(n)..].iter().map(|l| l.to_string()).collect()
+}
+
+fn spawn_line_reader<R: Read + Send + 'static>(
+    reader: R,
+    stream: OutputStream,
+    tx: mpsc::Sender<(OutputStream, String)>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(reader).lines() {
+            let Ok(line) = line else { break };
+            if tx.send((stream, line)).is_err() {
+                break;
+            }
+        }
+    })
+}
+
+/// Log level implied by the script's message prefixes
+pub fn line_level(stream: OutputStream, line: &str) -> tracing::Level {
+    let line = line.trim_start();
+    if line.starts_with("Error") || line.starts_with('❌') || line.starts_with("Traceback") {
+        tracing::Level::ERROR
+    } else if line.starts_with("Warning") || line.starts_with('⚠') || stream == OutputStream::Stderr {
+        tracing::Level::WARN
+    } else {
+        tracing::Level::INFO
+    }
+}
+
+pub(crate) fn forward_line(operation: &'static str, stream: OutputStream, line: &str) {
+    if line.trim().is_empty() {
+        return;
+    }
+    if line.starts_with(RESULT_PREFIX) {
+        tracing::debug!(target: "face_auth::python", operation, "{}", line);
+        return;
+    }
+    match line_level(stream, line) {
+        tracing::Level::ERROR => tracing::error!(target: "face_auth::python", operation, ?stream, "{}", line),
+        tracing::Level::WARN => tracing::warn!(target: "face_auth::python", operation, ?stream, "{}", line),
+        _ => tracing::info!(target: "face_auth::python", operation, ?stream, "{}", line),
+    }
+}
+
 #[derive(Debug)]
 pub struct StandaloneAuthResult {
     pub success: bool,
@@ -374,10 +1070,141 @@ pub struct StandaloneAuthResult {
     pub distance: Option<f64>,
     pub threshold: Option<f64>,
     pub matched_user: Option<String>,
+    /// Best candidate even when it was above the threshold
+    pub closest_user: Option<String>,
+    /// Second-closest user, for telling lookalikes apart
+    pub runner_up: Option<MatchCandidate>,
+    /// Distance to each sample of the closest user, closest first
+    pub sample_distances: Vec<SampleDistance>,
+    /// Mean gray level of the frame (0-255), for picking a threshold profile
+    pub brightness: Option<f64>,
+    /// Frame the decision was made on
+    pub image_path: Option<PathBuf>,
     pub processing_time_ms: Option<u32>,
+    pub timings: TimingBreakdown,
+    /// Liveness check the frame passed, if the backend ran one
+    pub liveness: Option<Liveness>,
+    /// Set when the backend rejected the frame before matching
+    pub denial: Option<DenialReason>,
     pub raw_output: String,
 }
 
+/// Result line printed by the script's warm mode
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct WarmUpOutcome {
+    /// Whether a frame could be read, `None` when the camera was not opened
+    pub camera_ready: Option<bool>,
+    pub elapsed_ms: u64,
+}
+
+/// Device-side analysis of one frame, see [`StandalonePythonFaceAuth::prefilter`]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Prefiltered {
+    pub face: bool,
+    pub quality: Option<SampleQuality>,
+    pub encoding: Option<Vec<f64>>,
+    pub chip: Option<PathBuf>,
+}
+
+/// Frame counts and track event the script adds to result lines in watch mode
+#[derive(Debug, Deserialize)]
+struct WatchLine {
+    scene: SceneStats,
+    #[serde(default)]
+    track: Option<ReportedTrack>,
+    /// Set on the last line, when a video file ran out of frames
+    #[serde(default)]
+    end: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum ReportedTrack {
+    Entered { track_id: u64 },
+    Exited { track_id: u64, user_id: Option<String>, frames: u64 },
+    /// Landmarks of a tracked face in one frame, with `--attention`
+    #[cfg(feature = "attention")]
+    Landmarks { track_id: u64, landmarks: FaceLandmarks },
+}
+
+fn parse_line<T: DeserializeOwned>(json: &str) -> Result<T> {
+    serde_json::from_str(json).map_err(|e| anyhow!("Malformed result from Python script: {}", e))
+}
+
+/// Result line printed by the script's auth mode
+#[derive(Debug, Deserialize)]
+pub(crate) struct ReportedAuthResult {
+    is_match: bool,
+    matched_user: Option<String>,
+    closest_user: Option<String>,
+    distance: Option<f64>,
+    confidence: Option<f64>,
+    threshold: Option<f64>,
+    #[serde(default)]
+    runner_up: Option<MatchCandidate>,
+    #[serde(default)]
+    sample_distances: Vec<SampleDistance>,
+    #[serde(default)]
+    brightness: Option<f64>,
+    #[serde(default)]
+    image_path: Option<PathBuf>,
+    #[serde(default)]
+    timings: TimingBreakdown,
+    #[serde(default)]
+    liveness: Option<Liveness>,
+    #[serde(default)]
+    denial: Option<DenialReason>,
+}
+
+impl ReportedAuthResult {
+    /// Reject results that parse but can't describe a real decision
+    fn validate(&self) -> Result<()> {
+        if self.is_match && self.matched_user.is_none() {
+            return Err(anyhow!("Malformed result from Python script: match without a matched user"));
+        }
+        if [&self.matched_user, &self.closest_user].into_iter().flatten().any(|user| user.is_empty()) {
+            return Err(anyhow!("Malformed result from Python script: empty username"));
+        }
+        if self.distance.is_some_and(|d| !is_distance(d)) || self.threshold.is_some_and(|t| !is_distance(t)) {
+            return Err(anyhow!("Malformed result from Python script: invalid distance or threshold"));
+        }
+        if self.confidence.is_some_and(|c| !is_confidence(c)) {
+            return Err(anyhow!("Malformed result from Python script: confidence outside 0-1"));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn into_result(self, tolerance: f64, elapsed_ms: u32, success: bool, raw_output: String) -> Result<StandaloneAuthResult> {
+        self.validate()?;
+        Ok(StandaloneAuthResult {
+            success,
+            is_match: Some(self.is_match),
+            confidence: self.confidence,
+            distance: self.distance,
+            threshold: self.threshold.or(Some(tolerance)),
+            matched_user: self.matched_user,
+            closest_user: self.closest_user,
+            runner_up: self.runner_up,
+            sample_distances: self.sample_distances,
+            brightness: self.brightness,
+            image_path: self.image_path,
+            processing_time_ms: Some(elapsed_ms),
+            timings: self.timings.with_backend_elapsed(elapsed_ms.into()),
+            liveness: self.liveness,
+            denial: self.denial,
+            raw_output,
+        })
+    }
+}
+
+fn is_distance(value: f64) -> bool {
+    value.is_finite() && value >= 0.0
+}
+
+fn is_confidence(value: f64) -> bool {
+    (0.0..=1.0).contains(&value)
+}
+
 // Helper functions to parse output
 fn extract_confidence_from_output(output: &str) -> Option<f64> {
     // Look for patterns like "Confidence: 95.2%" or "confidence: 0.952"
@@ -385,11 +1212,11 @@ fn extract_confidence_from_output(output: &str) -> Option<f64> {
         if let Some(start) = line.find("onfidence: ") {
             let substr = &line[start + 11..];
             if let Some(end) = substr.find('%') {
-                if let Ok(val) = substr[..end].parse::<f64>() {
-                    return Some(val / 100.0);
+                if let Some(val) = substr[..end].parse::<f64>().ok().map(|v| v / 100.0).filter(|v| is_confidence(*v)) {
+                    return Some(val);
                 }
             } else if let Some(space_end) = substr.find(' ') {
-                if let Ok(val) = substr[..space_end].parse::<f64>() {
+                if let Some(val) = substr[..space_end].parse::<f64>().ok().filter(|v| is_confidence(*v)) {
                     return Some(val);
                 }
             }
@@ -403,11 +1230,11 @@ fn extract_distance_from_output(output: &str) -> Option<f64> {
     for line in output.lines() {
         if let Some(start) = line.find("istance: ") {
             let substr = &line[start + 9..];
-            if let Some(space_end) = substr.find(' ') {
-                if let Ok(val) = substr[..space_end].parse::<f64>() {
-                    return Some(val);
-                }
-            } else if let Ok(val) = substr.trim().parse::<f64>() {
+            let value = match substr.find(' ') {
+                Some(space_end) => &substr[..space_end],
+                None => substr.trim(),
+            };
+            if let Some(val) = value.parse::<f64>().ok().filter(|v| is_distance(*v)) {
                 return Some(val);
             }
         }
@@ -419,11 +1246,9 @@ fn extract_matched_user_from_output(output: &str) -> Option<String> {
     // Look for patterns like "User: username" or "Matched user: username"
     for line in output.lines() {
         if let Some(start) = line.find("ser: ") {
-            let substr = &line[start + 5..];
-            if let Some(end) = substr.find('\n') {
-                return Some(substr[..end].trim().to_string());
-            } else {
-                return Some(substr.trim().to_string());
+            let user = line[start + 5..].trim();
+            if !user.is_empty() {
+                return Some(user.to_string());
             }
         }
     }
@@ -450,4 +1275,92 @@ fn extract_processing_time_from_output(output: &str) -> Option<u32> {
         }
     }
     None
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_registration_result_line_is_parsed() {
+        let mut output = ScriptOutput::default();
+        output.push(OutputStream::Stdout, "Registration complete! 1 samples stored for ann".into());
+        output.push(OutputStream::Stdout, format!(
+            "{}{}",
+            RESULT_PREFIX,
+            r#"{"username": "ann", "samples_requested": 2, "samples_captured": 1,
+                "samples": [{"index": 1, "status": "stored", "quality": {"score": 0.8, "sharpness": 120.0, "face_ratio": 0.2}},
+                            {"index": 2, "status": "no_face", "quality": null}],
+                "generated_file": "/tmp/generated/ann.json", "fully_enrolled": false}"#.replace('\n', "")
+        ));
+
+        let outcome: RegistrationOutcome = output.result().unwrap().unwrap();
+        assert_eq!(outcome.samples_captured, 1);
+        assert!(outcome.is_registered());
+        assert!(!outcome.fully_enrolled);
+    }
+
+    #[test]
+    fn test_inconsistent_result_line_is_rejected() {
+        let parse = |json: &str| ScriptOutput::from_stdout(&format!("{}{}", RESULT_PREFIX, json)).auth_result(0.6, 10);
+        assert!(parse(r#"{"is_match": true, "matched_user": "ann", "closest_user": "ann", "distance": 0.3, "confidence": 0.7, "threshold": 0.6}"#).is_ok());
+        assert!(parse(r#"{"is_match": true, "matched_user": null, "closest_user": null, "distance": 0.3, "confidence": 0.7, "threshold": 0.6}"#).is_err());
+        assert!(parse(r#"{"is_match": false, "matched_user": null, "closest_user": "ann", "distance": -1.0, "confidence": null, "threshold": 0.6}"#).is_err());
+        assert!(parse(r#"{"is_match": false, "matched_user": null, "closest_user": "ann", "distance": 0.9, "confidence": 7.5, "threshold": 0.6}"#).is_err());
+
+        let legacy = ScriptOutput::from_stdout("Confidence: NaN%\nDistance: inf\nUser: \n").auth_result(0.6, 10).unwrap();
+        assert_eq!((legacy.confidence, legacy.distance, legacy.matched_user), (None, None, None));
+    }
+
+    /// Captures the frame `--video-start` names, reading two frames of the video
+    const VIDEO_SCRIPT: &str = r#"
+frame=camera
+while [ $# -gt 0 ]; do
+  case "$1" in --video-start) frame=$2; echo "FACE_AUTH_VIDEO_POSITION $(($2 + 2))" ;; esac
+  shift
+done
+echo "FACE_AUTH_RESULT {\"images\": [\"$frame.jpg\"]}"
+"#;
+
+    #[test]
+    fn test_video_captures_continue_where_the_previous_one_stopped() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("capture.sh");
+        std::fs::write(&script, VIDEO_SCRIPT).unwrap();
+        let camera = StandalonePythonFaceAuth::from_parts("sh", script.to_str().unwrap(), dir.path());
+        let capture = |backend: &StandalonePythonFaceAuth| backend.capture_frames("auth", 1).unwrap()[0].clone().unwrap();
+
+        let video = camera.with_camera_source(CameraSource::VideoFile("hallway.mp4".into()));
+        assert_eq!(capture(&video), Path::new("0.jpg"));
+        assert_eq!(capture(&video), Path::new("2.jpg"));
+        assert_eq!(capture(&video.clone()), Path::new("4.jpg"));
+
+        // The source belongs to the instance, and another one starts over
+        assert_eq!(capture(&camera), Path::new("camera.jpg"));
+        let replay = camera.with_camera_source(CameraSource::VideoFile("hallway.mp4".into()));
+        assert_eq!(capture(&replay), Path::new("0.jpg"));
+        assert_eq!(capture(&video), Path::new("6.jpg"));
+    }
+
+    proptest! {
+        #[test]
+        fn test_script_output_parsing_never_panics(lines in proptest::collection::vec(prop_oneof![
+            ".*",
+            ".*(C|c)onfidence: .*",
+            ".*(D|d)istance: .*",
+            ".*(U|u)ser: .*",
+            ".*(rocessing time: |took ).*ms.*",
+            ".*".prop_map(|json| format!("{}{}", RESULT_PREFIX, json)),
+            r#"\{"is_match": (true|false), "matched_user": (null|"[a-z]*"), "distance": -?[0-9.e+]{1,8}, "confidence": -?[0-9.e+]{1,8}\}"#
+                .prop_map(|json| format!("{}{}", RESULT_PREFIX, json)),
+        ], 0..6)) {
+            let stdout = lines.join("\n");
+            if let Ok(result) = ScriptOutput::from_stdout(&stdout).auth_result(0.6, 10) {
+                prop_assert!(result.confidence.is_none_or(|c| (0.0..=1.0).contains(&c)));
+                prop_assert!(result.distance.is_none_or(|d| d.is_finite() && d >= 0.0));
+                prop_assert!(result.matched_user.is_none_or(|u| !u.is_empty()));
+            }
+            let _ = ScriptOutput::from_stdout(&stdout).result::<RegistrationOutcome>();
+        }
+    }
+}
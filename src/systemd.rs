@@ -0,0 +1,90 @@
+//! systemd integration for `face_auth serve`: readiness and watchdog
+//! notifications and socket activation, without linking libsystemd
+//!
+//! Everything here is a no-op when the process wasn't started by systemd.
+
+use anyhow::{Result, anyhow};
+use std::os::fd::{FromRawFd, OwnedFd};
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::Duration;
+
+/// First file descriptor systemd passes sockets on
+const LISTEN_FDS_START: i32 = 3;
+
+/// Send `state`, e.g. `READY=1`, to the service manager
+///
+/// Returns whether systemd asked for notifications via `NOTIFY_SOCKET`.
+pub fn notify(state: &str) -> Result<bool> {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return Ok(false);
+    };
+    let path = path.to_string_lossy();
+    let addr = match path.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        Some(name) => <SocketAddr as std::os::linux::net::SocketAddrExt>::from_abstract_name(name)?,
+        #[cfg(not(target_os = "linux"))]
+        Some(_) => return Err(anyhow!("Abstract notification sockets need Linux")),
+        None => SocketAddr::from_pathname(path.as_ref())?,
+    };
+    let socket = UnixDatagram::unbound()?;
+    socket.send_to_addr(state.as_bytes(), &addr)
+        .map_err(|e| anyhow!("Failed to notify systemd at {}: {}", path, e))?;
+    Ok(true)
+}
+
+/// How often systemd expects `WATCHDOG=1`, if the unit sets `WatchdogSec`
+pub fn watchdog_interval() -> Option<Duration> {
+    if !for_this_process("WATCHDOG_PID") {
+        return None;
+    }
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+/// Sockets passed by a `.socket` unit, taken once: later calls return none
+pub fn listen_fds() -> Vec<OwnedFd> {
+    if !for_this_process("LISTEN_PID") {
+        return Vec::new();
+    }
+    let count: i32 = std::env::var("LISTEN_FDS").ok().and_then(|n| n.parse().ok()).unwrap_or(0);
+    for var in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        std::env::remove_var(var);
+    }
+    // SAFETY: systemd hands these descriptors to this process, and removing
+    // the variables above ensures they are only taken once
+    (LISTEN_FDS_START..LISTEN_FDS_START + count).map(|fd| unsafe { OwnedFd::from_raw_fd(fd) }).collect()
+}
+
+/// Whether the variable holds this process's id; systemd sets it so that
+/// children don't act on their parent's sockets or watchdog
+fn for_this_process(var: &str) -> bool {
+    std::env::var(var).ok().and_then(|pid| pid.parse::<u32>().ok()) == Some(std::process::id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notify_sends_state_to_notify_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify");
+        let receiver = UnixDatagram::bind(&path).unwrap();
+        receiver.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+
+        std::env::set_var("NOTIFY_SOCKET", &path);
+        assert!(notify("READY=1").unwrap());
+        std::env::remove_var("NOTIFY_SOCKET");
+        let mut buffer = [0u8; 64];
+        let received = receiver.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..received], b"READY=1");
+        assert!(!notify("READY=1").unwrap());
+
+        std::env::set_var("WATCHDOG_USEC", "2000000");
+        std::env::set_var("WATCHDOG_PID", "1");
+        assert_eq!(watchdog_interval(), None, "meant for another process");
+        std::env::set_var("WATCHDOG_PID", std::process::id().to_string());
+        assert_eq!(watchdog_interval(), Some(Duration::from_secs(2)));
+        assert!(listen_fds().is_empty());
+    }
+}
@@ -0,0 +1,207 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Where and how often to report; telemetry is only sent once configured
+#[derive(Debug, Clone, PartialEq)]
+pub struct TelemetryConfig {
+    /// URL the reports are POSTed to as JSON
+    pub endpoint: String,
+    pub interval: Duration,
+    /// Reported as-is, e.g. `dlib-128` or an ONNX template version
+    pub model_version: String,
+    pub timeout: Duration,
+}
+
+impl TelemetryConfig {
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            interval: Duration::from_secs(60 * 60),
+            model_version: crate::face_storage::DLIB_TEMPLATE_VERSION.to_string(),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Aggregate counters for one reporting period
+///
+/// Deliberately contains no user names, device ids, distances or images.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TelemetryReport {
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub successes: u64,
+    pub failures: u64,
+    pub average_latency_ms: Option<f64>,
+    pub model_version: String,
+    pub library_version: String,
+}
+
+#[derive(Debug)]
+struct Counters {
+    since: DateTime<Utc>,
+    successes: u64,
+    failures: u64,
+    latency_ms: u64,
+}
+
+impl Counters {
+    fn new() -> Self {
+        Self { since: Utc::now(), successes: 0, failures: 0, latency_ms: 0 }
+    }
+}
+
+/// Authentication outcomes counted since the last report
+#[derive(Debug)]
+pub struct TelemetryCounters {
+    counters: Mutex<Counters>,
+}
+
+impl Default for TelemetryCounters {
+    fn default() -> Self {
+        Self { counters: Mutex::new(Counters::new()) }
+    }
+}
+
+impl TelemetryCounters {
+    pub fn record(&self, granted: bool, latency_ms: u64) {
+        let mut counters = self.counters.lock().unwrap_or_else(PoisonError::into_inner);
+        if granted {
+            counters.successes += 1;
+        } else {
+            counters.failures += 1;
+        }
+        counters.latency_ms += latency_ms;
+    }
+
+    /// Report of the counts so far, starting a new period; `None` if nothing happened
+    pub fn take(&self, model_version: &str) -> Option<TelemetryReport> {
+        let mut counters = self.counters.lock().unwrap_or_else(PoisonError::into_inner);
+        let attempts = counters.successes + counters.failures;
+        if attempts == 0 {
+            return None;
+        }
+        let counters = std::mem::replace(&mut *counters, Counters::new());
+        Some(TelemetryReport {
+            period_start: counters.since,
+            period_end: Utc::now(),
+            successes: counters.successes,
+            failures: counters.failures,
+            average_latency_ms: Some(counters.latency_ms as f64 / attempts as f64),
+            model_version: model_version.to_string(),
+            library_version: env!("CARGO_PKG_VERSION").to_string(),
+        })
+    }
+
+    /// Add an unsent report back so its counts go out with the next one
+    fn restore(&self, report: &TelemetryReport) {
+        let mut counters = self.counters.lock().unwrap_or_else(PoisonError::into_inner);
+        let attempts = report.successes + report.failures;
+        counters.since = counters.since.min(report.period_start);
+        counters.successes += report.successes;
+        counters.failures += report.failures;
+        counters.latency_ms += (report.average_latency_ms.unwrap_or(0.0) * attempts as f64).round() as u64;
+    }
+}
+
+/// Background thread POSTing a [`TelemetryReport`] every interval
+///
+/// Periods without authentications are skipped, and reports that fail to send
+/// are folded into the next one. Stops when dropped.
+pub struct TelemetryReporter {
+    counters: Arc<TelemetryCounters>,
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TelemetryReporter {
+    pub fn start(config: TelemetryConfig) -> Self {
+        let counters = Arc::new(TelemetryCounters::default());
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let agent: ureq::Agent = ureq::Agent::config_builder()
+            .http_status_as_error(false)
+            .timeout_global(Some(config.timeout))
+            .build()
+            .into();
+        let thread = thread::spawn({
+            let (counters, stop) = (counters.clone(), stop.clone());
+            move || loop {
+                let (stopped, wake) = &*stop;
+                let stopped = stopped.lock().unwrap_or_else(PoisonError::into_inner);
+                let (stopped, _) = wake.wait_timeout_while(stopped, config.interval, |stopped| !*stopped)
+                    .unwrap_or_else(PoisonError::into_inner);
+                if *stopped {
+                    break;
+                }
+                drop(stopped);
+                if let Some(report) = counters.take(&config.model_version) {
+                    if let Err(e) = send(&agent, &config.endpoint, &report) {
+                        tracing::warn!("Failed to send telemetry: {:#}", e);
+                        counters.restore(&report);
+                    }
+                }
+            }
+        });
+        Self { counters, stop, thread: Some(thread) }
+    }
+
+    pub fn counters(&self) -> &TelemetryCounters {
+        &self.counters
+    }
+}
+
+impl std::fmt::Debug for TelemetryReporter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TelemetryReporter").field("counters", &self.counters).finish_non_exhaustive()
+    }
+}
+
+fn send(agent: &ureq::Agent, endpoint: &str, report: &TelemetryReport) -> Result<()> {
+    let mut response = agent.post(endpoint).send_json(report).map_err(|e| anyhow!("{} unreachable: {}", endpoint, e))?;
+    if !response.status().is_success() {
+        let body = response.body_mut().read_to_string().unwrap_or_default();
+        return Err(anyhow!("{} answered {}: {}", endpoint, response.status(), body.trim()));
+    }
+    Ok(())
+}
+
+impl Drop for TelemetryReporter {
+    fn drop(&mut self) {
+        let (stopped, wake) = &*self.stop;
+        *stopped.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        wake.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reports_aggregate_and_reset() {
+        let counters = TelemetryCounters::default();
+        assert!(counters.take("dlib-128").is_none());
+        counters.record(true, 100);
+        counters.record(true, 200);
+        counters.record(false, 300);
+
+        let report = counters.take("dlib-128").unwrap();
+        assert_eq!((report.successes, report.failures), (2, 1));
+        assert_eq!(report.average_latency_ms, Some(200.0));
+        let json = serde_json::to_value(&report).unwrap();
+        let mut fields: Vec<_> = json.as_object().unwrap().keys().cloned().collect();
+        fields.sort();
+        assert_eq!(fields, ["average_latency_ms", "failures", "library_version", "model_version", "period_end", "period_start", "successes"]);
+        assert!(counters.take("dlib-128").is_none());
+
+        counters.restore(&report);
+        assert_eq!(counters.take("dlib-128").unwrap().successes, 2);
+    }
+}
@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+/// Where the time of one authentication went, in milliseconds
+///
+/// Stages a backend doesn't report stay at zero.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimingBreakdown {
+    /// Camera start-up and frame capture
+    pub capture_ms: u64,
+    /// Image decoding and face detection
+    pub detect_ms: u64,
+    /// Embedding computation
+    pub encode_ms: u64,
+    /// Comparison against the enrolled users
+    pub match_ms: u64,
+    /// Enforcement, statistics and auditing on the Rust side
+    pub policy_ms: u64,
+    /// Time not attributed to a stage: process start-up, model loading, IPC
+    pub overhead_ms: u64,
+}
+
+impl TimingBreakdown {
+    /// Time spent inside the backend's stages
+    pub fn backend_stages_ms(&self) -> u64 {
+        self.capture_ms + self.detect_ms + self.encode_ms + self.match_ms
+    }
+
+    pub fn total_ms(&self) -> u64 {
+        self.backend_stages_ms() + self.policy_ms + self.overhead_ms
+    }
+
+    /// Attribute whatever the backend's wall time doesn't cover to overhead
+    pub fn with_backend_elapsed(mut self, elapsed_ms: u64) -> Self {
+        self.overhead_ms = elapsed_ms.saturating_sub(self.backend_stages_ms());
+        self
+    }
+}
@@ -0,0 +1,118 @@
+use anyhow::{Result, bail};
+use std::time::Duration;
+
+#[cfg(feature = "attention")]
+use crate::attention::{Attention, AttentionConfig, HeadPose};
+
+/// Frames a track survives without a matching detection before it counts as
+/// having left, so a blink or a turned head doesn't start a new track
+pub const DEFAULT_MAX_MISSED_FRAMES: u32 = 10;
+
+/// How detections in consecutive frames are linked into tracks
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackingConfig {
+    /// Overlap (intersection over union, 0.0-1.0) at which a detection
+    /// continues an existing track
+    pub min_overlap: f64,
+    pub max_missed_frames: u32,
+    /// Report when tracked faces start or stop looking at the camera; off
+    /// unless set, as it runs landmark detection on every frame
+    #[cfg(feature = "attention")]
+    pub attention: Option<AttentionConfig>,
+}
+
+impl Default for TrackingConfig {
+    fn default() -> Self {
+        Self {
+            min_overlap: 0.3,
+            max_missed_frames: DEFAULT_MAX_MISSED_FRAMES,
+            #[cfg(feature = "attention")]
+            attention: None,
+        }
+    }
+}
+
+impl TrackingConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !(self.min_overlap > 0.0 && self.min_overlap <= 1.0) {
+            bail!("Track overlap {} is not in (0, 1]", self.min_overlap);
+        }
+        #[cfg(feature = "attention")]
+        if let Some(attention) = &self.attention {
+            attention.validate()?;
+        }
+        Ok(())
+    }
+}
+
+/// Someone entering or leaving the camera's view
+///
+/// Each track is identified once, when it enters, instead of on every frame.
+/// `R` is the identification result.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrackEvent<R> {
+    Entered { track_id: u64, result: R },
+    Exited {
+        track_id: u64,
+        /// User identified when the track entered, if any
+        user_id: Option<String>,
+        /// Frames the face was detected in
+        frames: u64,
+    },
+    /// A face that wasn't granted access entered shortly after someone who
+    /// was, at the same device; see `FaceAuthBuilder::tailgating_window`
+    TailgatingSuspected {
+        track_id: u64,
+        /// User granted access just before
+        followed_user: String,
+        /// Time since that user was granted access
+        after: Duration,
+        device_id: Option<String>,
+    },
+    /// A tracked face started or stopped looking at the camera, see
+    /// [`TrackingConfig::attention`]
+    #[cfg(feature = "attention")]
+    AttentionChanged { track_id: u64, attention: Attention, pose: HeadPose },
+}
+
+impl<R> TrackEvent<R> {
+    pub fn track_id(&self) -> u64 {
+        match self {
+            TrackEvent::Entered { track_id, .. }
+            | TrackEvent::Exited { track_id, .. }
+            | TrackEvent::TailgatingSuspected { track_id, .. } => *track_id,
+            #[cfg(feature = "attention")]
+            TrackEvent::AttentionChanged { track_id, .. } => *track_id,
+        }
+    }
+
+    /// Same event with the identification result converted
+    pub fn try_map<S>(self, f: impl FnOnce(R) -> Result<S>) -> Result<TrackEvent<S>> {
+        Ok(match self {
+            TrackEvent::Entered { track_id, result } => TrackEvent::Entered { track_id, result: f(result)? },
+            TrackEvent::Exited { track_id, user_id, frames } => TrackEvent::Exited { track_id, user_id, frames },
+            TrackEvent::TailgatingSuspected { track_id, followed_user, after, device_id } => {
+                TrackEvent::TailgatingSuspected { track_id, followed_user, after, device_id }
+            }
+            #[cfg(feature = "attention")]
+            TrackEvent::AttentionChanged { track_id, attention, pose } => TrackEvent::AttentionChanged { track_id, attention, pose },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_track_events_map_results() {
+        let entered = TrackEvent::Entered { track_id: 3, result: "0.42" }.try_map(|r| Ok(r.parse::<f64>()?)).unwrap();
+        assert_eq!(entered, TrackEvent::Entered { track_id: 3, result: 0.42 });
+        let exited: TrackEvent<f64> = TrackEvent::<&str>::Exited { track_id: 3, user_id: None, frames: 12 }
+            .try_map(|_| unreachable!()).unwrap();
+        assert_eq!(exited.track_id(), 3);
+
+        assert!(TrackingConfig::default().validate().is_ok());
+        assert!(TrackingConfig { min_overlap: 0.0, ..Default::default() }.validate().is_err());
+    }
+}
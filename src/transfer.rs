@@ -0,0 +1,135 @@
+use anyhow::{Context, Result, anyhow};
+use sha2::{Digest, Sha256};
+use spake2::{Ed25519Group, Identity, Password, Spake2};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::crypto::{self, KEY_LEN};
+use crate::error::FaceAuthError;
+
+/// Largest frame accepted from a peer
+const MAX_FRAME_LEN: usize = 16 << 20;
+
+/// Binds the key exchange to this protocol so messages can't be replayed into another
+const IDENTITY: &[u8] = b"face_auth enrollment transfer v1";
+
+const CONFIRMATION: &[u8] = b"face_auth key confirmation";
+
+/// Side of the connection; each direction is encrypted with its own key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Sender,
+    Receiver,
+}
+
+impl Role {
+    fn label(self) -> &'static [u8] {
+        match self {
+            Role::Sender => b"sender",
+            Role::Receiver => b"receiver",
+        }
+    }
+
+    fn peer(self) -> Role {
+        match self {
+            Role::Sender => Role::Receiver,
+            Role::Receiver => Role::Sender,
+        }
+    }
+}
+
+/// Encrypted connection to a peer that proved it knows the same passphrase
+///
+/// Both sides run a symmetric SPAKE2 exchange over the passphrase, so an
+/// eavesdropper learns nothing and an active attacker gets a single online
+/// guess per connection. Messages are then sealed with ChaCha20-Poly1305
+/// under per-direction keys derived from the shared secret.
+pub struct SecureChannel<S> {
+    stream: S,
+    send_key: [u8; KEY_LEN],
+    receive_key: [u8; KEY_LEN],
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> SecureChannel<S> {
+    /// Run the key exchange and confirm both sides derived the same keys
+    ///
+    /// Fails with [`FaceAuthError::PassphraseMismatch`] when the peer used a
+    /// different passphrase.
+    pub async fn handshake(mut stream: S, role: Role, passphrase: &str) -> Result<Self> {
+        let (spake, outbound) = Spake2::<Ed25519Group>::start_symmetric(&Password::new(passphrase), &Identity::new(IDENTITY));
+        write_frame(&mut stream, &outbound).await?;
+        let inbound = read_frame(&mut stream).await?;
+        let shared = spake.finish(&inbound).map_err(|e| anyhow!("Invalid key exchange message: {:?}", e))?;
+
+        let mut channel = Self {
+            stream,
+            send_key: derive_key(&shared, role),
+            receive_key: derive_key(&shared, role.peer()),
+        };
+        channel.send(CONFIRMATION).await?;
+        let confirmation = read_frame(&mut channel.stream).await?;
+        match crypto::open(&channel.receive_key, &confirmation) {
+            Ok(confirmation) if confirmation == CONFIRMATION => Ok(channel),
+            _ => Err(FaceAuthError::PassphraseMismatch.into()),
+        }
+    }
+
+    pub async fn send(&mut self, message: &[u8]) -> Result<()> {
+        let sealed = crypto::seal(&self.send_key, message)?;
+        write_frame(&mut self.stream, &sealed).await
+    }
+
+    pub async fn receive(&mut self) -> Result<Vec<u8>> {
+        let sealed = read_frame(&mut self.stream).await?;
+        crypto::open(&self.receive_key, &sealed)
+    }
+}
+
+fn derive_key(shared: &[u8], role: Role) -> [u8; KEY_LEN] {
+    let mut hasher = Sha256::new();
+    hasher.update(IDENTITY);
+    hasher.update(role.label());
+    hasher.update(shared);
+    hasher.finalize().into()
+}
+
+async fn write_frame<S: AsyncWrite + Unpin>(stream: &mut S, frame: &[u8]) -> Result<()> {
+    stream.write_all(&(frame.len() as u32).to_be_bytes()).await?;
+    stream.write_all(frame).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+async fn read_frame<S: AsyncRead + Unpin>(stream: &mut S) -> Result<Vec<u8>> {
+    let len = stream.read_u32().await.context("Peer closed the connection")? as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(anyhow!("Peer sent a {} byte frame (limit {})", len, MAX_FRAME_LEN));
+    }
+    let mut frame = vec![0; len];
+    stream.read_exact(&mut frame).await.context("Peer closed the connection")?;
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_handshake_requires_matching_passphrase() {
+        let (a, b) = tokio::io::duplex(4096);
+        let (sender, receiver) = tokio::join!(
+            SecureChannel::handshake(a, Role::Sender, "correct horse"),
+            SecureChannel::handshake(b, Role::Receiver, "correct horse"),
+        );
+        let (mut sender, mut receiver) = (sender.unwrap(), receiver.unwrap());
+        sender.send(b"credential").await.unwrap();
+        assert_eq!(receiver.receive().await.unwrap(), b"credential");
+
+        let (a, b) = tokio::io::duplex(4096);
+        let (sender, _) = tokio::join!(
+            SecureChannel::handshake(a, Role::Sender, "correct horse"),
+            SecureChannel::handshake(b, Role::Receiver, "battery staple"),
+        );
+        let error = sender.err().unwrap();
+        assert!(matches!(error.downcast_ref::<FaceAuthError>(), Some(FaceAuthError::PassphraseMismatch)));
+    }
+}
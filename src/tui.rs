@@ -0,0 +1,409 @@
+//! Terminal UI shown when `face_auth` runs without a subcommand
+
+use anyhow::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Gauge, List, ListItem, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use face_auth::{AuditEntry, AuditOperation, AuditOutcome, AuthFlowConfig, AuthState, CaptureTip, DenialReason, EnrollmentFlow, EnrollmentStep, FaceAuth, HealthStatus, MessageCatalog, UserSummary};
+
+/// How often users, events and the camera status are reloaded while idle
+const REFRESH: Duration = Duration::from_secs(2);
+
+/// Authentication events listed, newest first
+const RECENT_EVENTS: usize = 50;
+
+/// Rounds of retaking rejected poses before an enrollment is given up
+const MAX_RETAKE_ROUNDS: usize = 2;
+
+const TOLERANCE_STEP: f64 = 0.01;
+
+/// What typed keys currently go to
+enum Input {
+    Commands,
+    Search,
+    Username(String),
+    ImportFile(String),
+    Consent(Box<EnrollmentFlow>),
+}
+
+struct Enrollment {
+    username: String,
+    captured: usize,
+    poses: usize,
+    label: String,
+}
+
+struct App {
+    auth: FaceAuth,
+    messages: MessageCatalog,
+    tolerance: f64,
+    users: Vec<UserSummary>,
+    table: TableState,
+    search: String,
+    events: Vec<AuditEntry>,
+    health: Option<HealthStatus>,
+    input: Input,
+    enrollment: Option<Enrollment>,
+    status: Line<'static>,
+}
+
+/// Run the UI until the user quits, restoring the terminal afterwards
+pub async fn run(auth: FaceAuth) -> Result<()> {
+    let mut app = App {
+        auth,
+        messages: MessageCatalog::english(),
+        tolerance: 0.4,
+        users: Vec::new(),
+        table: TableState::default().with_selected(Some(0)),
+        search: String::new(),
+        events: Vec::new(),
+        health: None,
+        input: Input::Commands,
+        enrollment: None,
+        status: Line::from("Press r to register, a to authenticate, q to quit"),
+    };
+    app.refresh().await;
+    let mut terminal = ratatui::try_init()?;
+    let result = app.run(&mut terminal).await;
+    ratatui::restore();
+    result
+}
+
+impl App {
+    async fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        let mut refreshed = Instant::now();
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if event::poll(REFRESH.saturating_sub(refreshed.elapsed()))? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press && !self.handle_key(key, terminal).await? {
+                        return Ok(());
+                    }
+                }
+            }
+            if refreshed.elapsed() >= REFRESH {
+                self.refresh().await;
+                refreshed = Instant::now();
+            }
+        }
+    }
+
+    /// Handle one key press; false to quit
+    async fn handle_key(&mut self, key: KeyEvent, terminal: &mut DefaultTerminal) -> Result<bool> {
+        match std::mem::replace(&mut self.input, Input::Commands) {
+            Input::Commands => return self.command(key.code, terminal).await,
+            Input::Search => match key.code {
+                KeyCode::Esc => self.search.clear(),
+                KeyCode::Enter => {}
+                code => {
+                    edit(&mut self.search, code);
+                    self.table.select(Some(0));
+                    self.input = Input::Search;
+                }
+            },
+            Input::Username(mut username) => match key.code {
+                KeyCode::Esc => {}
+                KeyCode::Enter => self.start_enrollment(username.trim()),
+                code => {
+                    edit(&mut username, code);
+                    self.input = Input::Username(username);
+                }
+            },
+            Input::ImportFile(mut file) => match key.code {
+                KeyCode::Esc => {}
+                KeyCode::Enter => self.import(file.trim()).await,
+                code => {
+                    edit(&mut file, code);
+                    self.input = Input::ImportFile(file);
+                }
+            },
+            Input::Consent(mut flow) => match key.code {
+                KeyCode::Char('y') => {
+                    if let Err(e) = flow.give_consent() {
+                        self.fail("Enrollment", e);
+                    } else {
+                        self.enroll(*flow, terminal).await?;
+                    }
+                }
+                KeyCode::Char('n') | KeyCode::Esc => {
+                    let _ = flow.decline_consent();
+                    self.status = Line::from("Enrollment cancelled; nothing was stored");
+                }
+                _ => self.input = Input::Consent(flow),
+            },
+        }
+        Ok(true)
+    }
+
+    async fn command(&mut self, code: KeyCode, terminal: &mut DefaultTerminal) -> Result<bool> {
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+            KeyCode::Char('/') => self.input = Input::Search,
+            KeyCode::Char('r') => self.input = Input::Username(String::new()),
+            KeyCode::Char('i') => self.input = Input::ImportFile(String::new()),
+            KeyCode::Char('a') => self.authenticate(terminal).await?,
+            KeyCode::Char('e') => self.export().await,
+            KeyCode::Down | KeyCode::Char('j') => self.select(1),
+            KeyCode::Up | KeyCode::Char('k') => self.select(-1),
+            KeyCode::Right | KeyCode::Char('+') => self.tolerance = (self.tolerance + TOLERANCE_STEP).min(1.0),
+            KeyCode::Left | KeyCode::Char('-') => self.tolerance = (self.tolerance - TOLERANCE_STEP).max(0.0),
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    async fn refresh(&mut self) {
+        if let Ok(users) = self.auth.list_users().await {
+            self.users = users;
+            self.users.sort_by(|a, b| a.username.cmp(&b.username));
+        }
+        if let Ok(entries) = self.auth.audit_entries().await {
+            self.events = entries.into_iter()
+                .rev()
+                .filter(|entry| entry.operation == AuditOperation::Authentication)
+                .take(RECENT_EVENTS)
+                .collect();
+        }
+        self.health = self.auth.health().await.ok();
+    }
+
+    fn visible_users(&self) -> Vec<&UserSummary> {
+        let search = self.search.to_lowercase();
+        self.users.iter()
+            .filter(|user| {
+                user.username.to_lowercase().contains(&search)
+                    || user.metadata.display_name.as_ref().is_some_and(|name| name.to_lowercase().contains(&search))
+            })
+            .collect()
+    }
+
+    fn select(&mut self, offset: isize) {
+        let count = self.visible_users().len();
+        if count > 0 {
+            let current = self.table.selected().unwrap_or(0).min(count - 1);
+            self.table.select(Some(current.saturating_add_signed(offset).min(count - 1)));
+        }
+    }
+
+    fn selected_user(&self) -> Option<String> {
+        self.visible_users().get(self.table.selected()?).map(|user| user.username.clone())
+    }
+
+    async fn authenticate(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        self.status = Line::from(self.messages.tip(CaptureTip::LookAtCamera)).yellow();
+        terminal.draw(|frame| self.draw(frame))?;
+
+        let phases = Arc::new(Mutex::new(Vec::new()));
+        let mut flow = self.auth.auth_flow(AuthFlowConfig::default().with_tolerance(self.tolerance).with_cooldown(Duration::ZERO));
+        let recorded = phases.clone();
+        flow.on_state(move |state| recorded.lock().unwrap_or_else(|e| e.into_inner()).push(state.clone()));
+        let state = match flow.attempt().await {
+            Ok(state) => state.clone(),
+            Err(e) => {
+                self.fail("Authentication", e);
+                return Ok(());
+            }
+        };
+        let retries = phases.lock().unwrap_or_else(|e| e.into_inner()).iter().filter(|s| matches!(s, AuthState::Retrying { .. })).count();
+        let distance = flow.result().and_then(|result| result.distance).map(|d| format!(" (distance {:.3})", d)).unwrap_or_default();
+        let retried = if retries > 0 { format!(" after {} retr{}", retries, if retries == 1 { "y" } else { "ies" }) } else { String::new() };
+        let text = format!("{}{}{}", state.message(&self.messages), distance, retried);
+        self.status = match state {
+            AuthState::Granted { .. } => Line::from(text).green(),
+            AuthState::Denied { reason: Some(DenialReason::NoUsersEnrolled) } => {
+                Line::from(format!("{}: press r to register", self.messages.tip(CaptureTip::RegisterFirst))).yellow()
+            }
+            _ => Line::from(text).red(),
+        };
+        self.refresh().await;
+        Ok(())
+    }
+
+    fn start_enrollment(&mut self, username: &str) {
+        if username.is_empty() {
+            return;
+        }
+        let started = self.auth.enrollment_flow(username).start().and_then(|mut flow| flow.begin().map(|_| flow));
+        match started {
+            Ok(flow) => self.input = Input::Consent(Box::new(flow)),
+            Err(e) => self.fail("Enrollment", e),
+        }
+    }
+
+    /// Capture every pose with a progress bar, retake rejected ones and confirm
+    async fn enroll(&mut self, mut flow: EnrollmentFlow, terminal: &mut DefaultTerminal) -> Result<()> {
+        let username = flow.state().username.clone();
+        let poses = flow.poses().len();
+        let mut rounds = 0;
+        loop {
+            while let EnrollmentStep::Capture { index } = flow.step() {
+                let pose = flow.poses()[index];
+                self.enrollment = Some(Enrollment {
+                    username: username.clone(),
+                    captured: flow.captures().filter(|capture| capture.sample.is_some()).count(),
+                    poses,
+                    label: format!("{:?}: {}", pose, self.messages.tip(pose.tip())),
+                });
+                terminal.draw(|frame| self.draw(frame))?;
+                if let Err(e) = flow.capture().await {
+                    self.enrollment = None;
+                    let _ = flow.cancel();
+                    self.fail("Enrollment", e);
+                    return Ok(());
+                }
+            }
+            let retakes = flow.needs_retake();
+            if retakes.is_empty() || rounds == MAX_RETAKE_ROUNDS {
+                break;
+            }
+            rounds += 1;
+            flow.retake(retakes[0])?;
+        }
+        self.enrollment = None;
+        match flow.confirm().await {
+            Ok(outcome) => {
+                self.status = Line::from(format!("Enrolled {} with {}/{} samples", username, outcome.samples_captured, outcome.samples_requested)).green();
+            }
+            Err(e) => {
+                let _ = flow.cancel();
+                self.fail("Enrollment", e);
+            }
+        }
+        self.refresh().await;
+        Ok(())
+    }
+
+    async fn export(&mut self) {
+        let Some(username) = self.selected_user() else {
+            self.status = Line::from("Select a user to export").yellow();
+            return;
+        };
+        self.status = match self.auth.export_user(&username, "").await {
+            Ok(true) => Line::from(format!("Exported {} to exported_credentials/", username)).green(),
+            Ok(false) => Line::from(format!("Export of {} failed", username)).red(),
+            Err(e) => Line::from(format!("Export failed: {:#}", e)).red(),
+        };
+    }
+
+    async fn import(&mut self, file: &str) {
+        if file.is_empty() {
+            return;
+        }
+        self.status = match self.auth.import_user(file).await {
+            Ok(true) => Line::from(format!("Imported {}", file)).green(),
+            Ok(false) => Line::from(format!("{} is missing or not a valid export", file)).red(),
+            Err(e) => Line::from(format!("Import failed: {:#}", e)).red(),
+        };
+        self.refresh().await;
+    }
+
+    fn fail(&mut self, operation: &str, error: anyhow::Error) {
+        self.status = Line::from(format!("{} failed: {:#}", operation, error)).red();
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header, body, sliders, status, help] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(6),
+            Constraint::Length(3),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [users_area, events_area] = Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)]).areas(body);
+
+        let (camera, camera_style) = match self.health.as_ref().and_then(|health| health.camera_ready) {
+            Some(true) => ("ready", Style::new().green()),
+            Some(false) => ("unavailable", Style::new().red()),
+            None => ("not used yet", Style::new().dark_gray()),
+        };
+        frame.render_widget(Line::from(vec![
+            Span::from(" Face Authentication ").bold(),
+            Span::from("│ camera "),
+            Span::styled(camera, camera_style),
+            Span::from(format!(" │ {} users │ threshold {:.2}", self.users.len(), self.tolerance)),
+        ]), header);
+
+        let users = self.visible_users();
+        let rows: Vec<Row> = users.iter().map(|user| Row::new(vec![
+            user.username.clone(),
+            user.metadata.display_name.clone().unwrap_or_default(),
+            user.sample_count.to_string(),
+            user.enrollment_date.as_deref().map(|date| date.chars().take(10).collect()).unwrap_or_default(),
+        ])).collect();
+        let title = match (&self.input, self.search.is_empty()) {
+            (Input::Search, _) => format!(" Users │ search: {}▏", self.search),
+            (_, false) => format!(" Users │ search: {} ", self.search),
+            _ => " Users ".to_string(),
+        };
+        let table = Table::new(rows, [Constraint::Fill(2), Constraint::Fill(2), Constraint::Length(7), Constraint::Length(10)])
+            .header(Row::new(["User", "Name", "Samples", "Enrolled"]).bold())
+            .block(Block::bordered().title(title))
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("▶ ");
+        frame.render_stateful_widget(table, users_area, &mut self.table);
+
+        let events: Vec<ListItem> = self.events.iter().map(|entry| {
+            let (mark, style) = match entry.outcome {
+                AuditOutcome::Granted => ("✓", Style::new().green()),
+                AuditOutcome::Denied => ("✗", Style::new().red()),
+                AuditOutcome::Failed => ("!", Style::new().yellow()),
+            };
+            let distance = entry.distance.map(|d| format!(" {:.3}", d)).unwrap_or_default();
+            let reason = entry.denial.as_ref().map(|denial| format!(" {}", denial.code())).unwrap_or_default();
+            ListItem::new(Line::from(vec![
+                Span::from(entry.timestamp.with_timezone(&chrono::Local).format("%H:%M:%S ").to_string()).dark_gray(),
+                Span::styled(mark, style),
+                Span::from(format!(" {}{}{}", entry.user.as_deref().unwrap_or("unknown"), distance, reason)),
+            ]))
+        }).collect();
+        frame.render_widget(List::new(events).block(Block::bordered().title(" Recent authentications ")), events_area);
+
+        let gauge = match &self.enrollment {
+            Some(enrollment) => Gauge::default()
+                .block(Block::bordered().title(format!(" Enrolling {} ", enrollment.username)))
+                .gauge_style(Style::new().cyan())
+                .ratio(enrollment.captured as f64 / enrollment.poses.max(1) as f64)
+                .label(format!("{}/{} │ {}", enrollment.captured, enrollment.poses, enrollment.label)),
+            None => Gauge::default()
+                .block(Block::bordered().title(" Match threshold (← stricter │ looser →) "))
+                .gauge_style(Style::new().magenta())
+                .ratio(self.tolerance.clamp(0.0, 1.0))
+                .label(format!("{:.2}", self.tolerance)),
+        };
+        frame.render_widget(gauge, sliders);
+
+        let prompt = match &self.input {
+            Input::Username(name) => Some(format!("Username to register: {}▏", name)),
+            Input::ImportFile(file) => Some(format!("Export file to import: {}▏", file)),
+            Input::Consent(flow) => Some(format!("Store a face template of {} for authentication? [y/n]", flow.state().username)),
+            _ => None,
+        };
+        match prompt {
+            Some(prompt) => frame.render_widget(Line::from(prompt).bold(), status),
+            None => frame.render_widget(Paragraph::new(self.status.clone()), status),
+        }
+        let keys = match self.input {
+            Input::Commands => "r register  a authenticate  e export  i import  / search  ←→ threshold  ↑↓ select  q quit",
+            Input::Consent(_) => "y consent  n decline",
+            _ => "enter confirm  esc cancel",
+        };
+        frame.render_widget(Line::from(keys).dark_gray(), help);
+    }
+}
+
+fn edit(text: &mut String, code: KeyCode) {
+    match code {
+        KeyCode::Char(c) => text.push(c),
+        KeyCode::Backspace => {
+            text.pop();
+        }
+        _ => {}
+    }
+}
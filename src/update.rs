@@ -0,0 +1,431 @@
+//! Updating bundled recognizer scripts, executables and ONNX models from a
+//! signed channel, so kiosks don't need someone to log in to them
+//!
+//! A channel is a [`SignedManifest`] served over HTTP(S) or from a file,
+//! signed with the publisher's Ed25519 key. Artifacts are downloaded and
+//! checked against the manifest's hashes before anything is replaced, then
+//! swapped in by renaming, and swapped back if the health check fails.
+
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Utc};
+use ring::signature::{ED25519, Ed25519KeyPair, KeyPair, UnparsedPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::face_storage::write_atomic;
+use crate::signing::{decode_hex, encode_hex};
+
+/// Prefix of the signed bytes, so other signatures by the same key can't be
+/// passed off as a manifest
+const DOMAIN: &[u8] = b"face_auth.update.v1\0";
+
+/// Largest artifact downloaded, to fit ONNX models
+const MAX_ARTIFACT_BYTES: u64 = 1 << 30;
+
+/// Directory in the install directory holding update state
+const STATE_DIR: &str = ".face_auth_update";
+
+/// A bundled file as published on a channel
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Artifact {
+    pub name: String,
+    pub version: String,
+    /// Where to download it, relative to the manifest unless absolute
+    pub url: String,
+    /// Hex SHA-256 of the file
+    pub sha256: String,
+    /// Where it's installed, relative to the install directory
+    pub path: PathBuf,
+    #[serde(default)]
+    pub executable: bool,
+}
+
+/// The artifacts currently published on a channel
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub channel: String,
+    pub published_at: DateTime<Utc>,
+    pub artifacts: Vec<Artifact>,
+}
+
+/// A manifest with a hex Ed25519 signature by the channel's publisher
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedManifest {
+    #[serde(flatten)]
+    pub manifest: Manifest,
+    pub signature: String,
+}
+
+impl Manifest {
+    /// Sign with the publisher's 32-byte Ed25519 seed
+    pub fn sign(self, seed: &[u8]) -> Result<SignedManifest> {
+        let key = Ed25519KeyPair::from_seed_unchecked(seed).map_err(|e| anyhow!("Invalid signing key: {}", e))?;
+        let signature = encode_hex(key.sign(&signed_bytes(&self)?).as_ref());
+        Ok(SignedManifest { manifest: self, signature })
+    }
+}
+
+impl SignedManifest {
+    /// Check the signature against the publisher's public key
+    pub fn verify(&self, public_key: &[u8]) -> Result<&Manifest> {
+        let signature = decode_hex(&self.signature).ok_or_else(|| anyhow!("Malformed manifest signature"))?;
+        UnparsedPublicKey::new(&ED25519, public_key)
+            .verify(&signed_bytes(&self.manifest)?, &signature)
+            .map_err(|_| anyhow!("Manifest of channel {} isn't signed by the configured key", self.manifest.channel))?;
+        Ok(&self.manifest)
+    }
+}
+
+/// The public key matching an Ed25519 seed, to configure on devices
+pub fn public_key(seed: &[u8]) -> Result<[u8; 32]> {
+    let key = Ed25519KeyPair::from_seed_unchecked(seed).map_err(|e| anyhow!("Invalid signing key: {}", e))?;
+    key.public_key().as_ref().try_into().map_err(|_| anyhow!("Unexpected public key length"))
+}
+
+/// Result of [`Updater::update`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum UpdateOutcome {
+    /// Nothing newer on the channel
+    UpToDate,
+    Installed(Vec<Artifact>),
+    /// The new artifacts failed the health check and the previous ones were
+    /// put back
+    RolledBack { attempted: Vec<Artifact>, reason: String },
+}
+
+type Fetcher = Arc<dyn Fn(&str) -> Result<Vec<u8>> + Send + Sync>;
+
+/// Checks a channel and installs newer artifacts into a directory
+#[derive(Clone)]
+pub struct Updater {
+    /// Channel the device follows; manifests of other channels are refused
+    channel: String,
+    channel_url: String,
+    public_key: Vec<u8>,
+    install_dir: PathBuf,
+    fetch: Fetcher,
+}
+
+impl std::fmt::Debug for Updater {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Updater").field("channel", &self.channel).field("channel_url", &self.channel_url).field("install_dir", &self.install_dir).finish()
+    }
+}
+
+impl Updater {
+    /// Follow `channel`, e.g. `stable`, published at `channel_url`, an
+    /// `http(s)://` URL or a file path, e.g. on a USB stick
+    pub fn new(channel: impl Into<String>, channel_url: impl Into<String>, public_key: &[u8], install_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            channel: channel.into(),
+            channel_url: channel_url.into(),
+            public_key: public_key.to_vec(),
+            install_dir: install_dir.into(),
+            fetch: Arc::new(fetch),
+        }
+    }
+
+    /// Download with `fetch` instead, e.g. through a proxy
+    pub fn with_fetcher(mut self, fetch: impl Fn(&str) -> Result<Vec<u8>> + Send + Sync + 'static) -> Self {
+        self.fetch = Arc::new(fetch);
+        self
+    }
+
+    /// Versions installed by previous updates, by artifact name
+    pub fn installed(&self) -> Result<BTreeMap<String, String>> {
+        let path = self.state_dir().join("installed.json");
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Artifacts on the channel newer than the installed ones; artifacts
+    /// never installed by an update count as older
+    pub fn check(&self) -> Result<Vec<Artifact>> {
+        let signed: SignedManifest = serde_json::from_slice(&(self.fetch)(&self.channel_url)?)
+            .with_context(|| format!("Invalid manifest at {}", self.channel_url))?;
+        let manifest = signed.verify(&self.public_key)?;
+        // The publisher signs every channel with the same key, so a beta
+        // manifest served at the stable URL would verify too
+        if manifest.channel != self.channel {
+            bail!("Manifest at {} is for channel {}, not {}", self.channel_url, manifest.channel, self.channel);
+        }
+        let installed = self.installed()?;
+        let mut newer = Vec::new();
+        for artifact in &manifest.artifacts {
+            if !is_safe_relative(&artifact.path) {
+                bail!("Artifact {} would be installed outside the install directory: {}", artifact.name, artifact.path.display());
+            }
+            let current = installed.get(&artifact.name);
+            if current.is_none_or(|current| compare_versions(&artifact.version, current) == Ordering::Greater) {
+                newer.push(artifact.clone());
+            }
+        }
+        Ok(newer)
+    }
+
+    /// Install newer artifacts, keeping them only if `health_check` passes
+    /// afterwards
+    ///
+    /// The replaced files are kept until the update is recorded as installed.
+    /// An update interrupted before that is rolled back by the next one.
+    pub fn update(&self, health_check: impl FnOnce() -> Result<()>) -> Result<UpdateOutcome> {
+        let staging = self.state_dir().join("staging");
+        let backup = self.state_dir().join("backup");
+        if backup.exists() {
+            tracing::warn!("Rolling back an interrupted update");
+            self.restore_backup(&backup, &backup)?;
+            fs::remove_dir_all(&backup)?;
+        }
+        if staging.exists() {
+            fs::remove_dir_all(&staging)?;
+        }
+        let artifacts = self.check()?;
+        if artifacts.is_empty() {
+            return Ok(UpdateOutcome::UpToDate);
+        }
+        self.stage(&artifacts, &staging)?;
+
+        let mut swapped = Vec::new();
+        if let Err(e) = self.swap_in(&artifacts, &staging, &backup, &mut swapped) {
+            self.restore(&swapped, &backup)?;
+            fs::remove_dir_all(&backup)?;
+            return Err(e);
+        }
+        if let Err(e) = health_check() {
+            self.restore(&swapped, &backup)?;
+            fs::remove_dir_all(&backup)?;
+            fs::remove_dir_all(&staging)?;
+            tracing::warn!("Rolled back update: {:#}", e);
+            return Ok(UpdateOutcome::RolledBack { attempted: artifacts, reason: format!("{:#}", e) });
+        }
+
+        let mut installed = self.installed()?;
+        installed.extend(artifacts.iter().map(|artifact| (artifact.name.clone(), artifact.version.clone())));
+        write_atomic(&self.state_dir().join("installed.json"), serde_json::to_string_pretty(&installed)?.as_bytes())?;
+        fs::remove_dir_all(&staging)?;
+        fs::remove_dir_all(&backup)?;
+        tracing::info!("Installed {} updated artifact(s)", artifacts.len());
+        Ok(UpdateOutcome::Installed(artifacts))
+    }
+
+    /// Download every artifact and check its hash before anything is replaced
+    fn stage(&self, artifacts: &[Artifact], staging: &Path) -> Result<()> {
+        for artifact in artifacts {
+            let url = resolve_url(&self.channel_url, &artifact.url);
+            let bytes = (self.fetch)(&url).with_context(|| format!("Failed to download {}", artifact.name))?;
+            if encode_hex(&Sha256::digest(&bytes)) != artifact.sha256.to_lowercase() {
+                bail!("{} {} doesn't match the manifest's hash", artifact.name, artifact.version);
+            }
+            let path = staging.join(&artifact.path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Move current files to `backup` and staged ones into place, recording
+    /// which were swapped
+    fn swap_in(&self, artifacts: &[Artifact], staging: &Path, backup: &Path, swapped: &mut Vec<PathBuf>) -> Result<()> {
+        for artifact in artifacts {
+            let target = self.install_dir.join(&artifact.path);
+            let staged = staging.join(&artifact.path);
+            if target.exists() {
+                copy_permissions(&target, &staged)?;
+                let saved = backup.join(&artifact.path);
+                if let Some(parent) = saved.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::rename(&target, &saved)?;
+            } else if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            swapped.push(artifact.path.clone());
+            if artifact.executable {
+                make_executable(&staged)?;
+            }
+            fs::rename(&staged, &target)?;
+        }
+        Ok(())
+    }
+
+    /// Put back the files `swap_in` replaced, removing ones that were new
+    fn restore(&self, swapped: &[PathBuf], backup: &Path) -> Result<()> {
+        for path in swapped.iter().rev() {
+            let target = self.install_dir.join(path);
+            let saved = backup.join(path);
+            if saved.exists() {
+                fs::rename(&saved, &target)?;
+            } else if target.exists() {
+                fs::remove_file(&target)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Put back every file under `dir` of an update's `backup`
+    fn restore_backup(&self, backup: &Path, dir: &Path) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.restore_backup(backup, &path)?;
+                continue;
+            }
+            let target = self.install_dir.join(path.strip_prefix(backup)?);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(&path, &target)?;
+        }
+        Ok(())
+    }
+
+    fn state_dir(&self) -> PathBuf {
+        self.install_dir.join(STATE_DIR)
+    }
+}
+
+fn signed_bytes(manifest: &Manifest) -> Result<Vec<u8>> {
+    let mut bytes = DOMAIN.to_vec();
+    bytes.extend(serde_json::to_vec(manifest)?);
+    Ok(bytes)
+}
+
+/// Download over HTTP(S), or read a local file
+fn fetch(url: &str) -> Result<Vec<u8>> {
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        return fs::read(url).with_context(|| format!("Failed to read {}", url));
+    }
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(Duration::from_secs(600)))
+        .build()
+        .into();
+    let mut response = agent.get(url).call().map_err(|e| anyhow!("{} unreachable: {}", url, e))?;
+    Ok(response.body_mut().with_config().limit(MAX_ARTIFACT_BYTES).read_to_vec()?)
+}
+
+fn resolve_url(channel_url: &str, url: &str) -> String {
+    if url.contains("://") || Path::new(url).is_absolute() {
+        return url.to_string();
+    }
+    match channel_url.rfind('/') {
+        Some(end) => format!("{}/{}", &channel_url[..end], url),
+        None => url.to_string(),
+    }
+}
+
+fn is_safe_relative(path: &Path) -> bool {
+    path.components().next().is_some() && path.components().all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// Compare dotted versions numerically where both parts are numbers
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let mut a_parts = a.split('.');
+    let mut b_parts = b.split('.');
+    loop {
+        match (a_parts.next(), b_parts.next()) {
+            (None, None) => return Ordering::Equal,
+            (Some(_), None) => return Ordering::Greater,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(a), Some(b)) => {
+                let order = match (a.parse::<u64>(), b.parse::<u64>()) {
+                    (Ok(a), Ok(b)) => a.cmp(&b),
+                    _ => a.cmp(b),
+                };
+                if order != Ordering::Equal {
+                    return order;
+                }
+            }
+        }
+    }
+}
+
+fn copy_permissions(from: &Path, to: &Path) -> Result<()> {
+    fs::set_permissions(to, fs::metadata(from)?.permissions())?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_mode(permissions.mode() | 0o755);
+    fs::set_permissions(path, permissions)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: [u8; 32] = [7; 32];
+
+    fn publish(dir: &Path, version: &str, contents: &[u8]) {
+        fs::write(dir.join(format!("recognizer-{}.py", version)), contents).unwrap();
+        let manifest = Manifest {
+            channel: "stable".to_string(),
+            published_at: Utc::now(),
+            artifacts: vec![Artifact {
+                name: "recognizer".to_string(),
+                version: version.to_string(),
+                url: format!("recognizer-{}.py", version),
+                sha256: encode_hex(&Sha256::digest(contents)),
+                path: PathBuf::from("python_face_auth_simple.py"),
+                executable: true,
+            }],
+        };
+        fs::write(dir.join("manifest.json"), serde_json::to_string(&manifest.sign(&SEED).unwrap()).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_update_installs_verified_artifacts_and_rolls_back_unhealthy_ones() {
+        let channel = tempfile::tempdir().unwrap();
+        let install = tempfile::tempdir().unwrap();
+        let script = install.path().join("python_face_auth_simple.py");
+        fs::write(&script, "v1").unwrap();
+        let manifest = channel.path().join("manifest.json").to_string_lossy().into_owned();
+        let updater = Updater::new("stable", &manifest, &public_key(&SEED).unwrap(), install.path());
+
+        publish(channel.path(), "1.1", b"v1.1");
+        assert!(matches!(updater.update(|| Ok(())).unwrap(), UpdateOutcome::Installed(artifacts) if artifacts.len() == 1));
+        assert_eq!(fs::read_to_string(&script).unwrap(), "v1.1");
+        assert_eq!(updater.update(|| Ok(())).unwrap(), UpdateOutcome::UpToDate);
+
+        publish(channel.path(), "1.10", b"broken");
+        assert!(matches!(updater.update(|| bail!("no faces found")).unwrap(), UpdateOutcome::RolledBack { .. }));
+        assert_eq!(fs::read_to_string(&script).unwrap(), "v1.1");
+        assert_eq!(updater.installed().unwrap()["recognizer"], "1.1");
+
+        let stranger = Updater::new("stable", &manifest, &public_key(&[8; 32]).unwrap(), install.path());
+        assert!(stranger.check().is_err(), "signed by another key");
+        let beta = Updater::new("beta", &manifest, &public_key(&SEED).unwrap(), install.path());
+        assert!(beta.check().is_err(), "published on another channel");
+        fs::write(channel.path().join("recognizer-1.10.py"), "tampered").unwrap();
+        assert!(updater.update(|| Ok(())).is_err(), "hash mismatch");
+        assert_eq!(fs::read_to_string(&script).unwrap(), "v1.1");
+
+        // A crash after swapping in leaves the backup, which the next update puts back
+        let backup = install.path().join(STATE_DIR).join("backup");
+        fs::create_dir_all(&backup).unwrap();
+        fs::rename(&script, backup.join("python_face_auth_simple.py")).unwrap();
+        fs::write(&script, "half-installed").unwrap();
+        assert!(updater.update(|| Ok(())).is_err(), "still the tampered artifact");
+        assert_eq!(fs::read_to_string(&script).unwrap(), "v1.1");
+        assert!(!backup.exists());
+        assert_eq!(updater.installed().unwrap()["recognizer"], "1.1");
+    }
+}
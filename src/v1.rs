@@ -0,0 +1,105 @@
+//! Version 1 of the public API
+//!
+//! `use face_auth::v1::*` brings in what an application needs to enroll and
+//! authenticate users. Names here keep their meaning until a `v2` module
+//! replaces them; the crate root also exports experimental modules that may
+//! change between minor versions.
+//!
+//! Results, configurations and denial reasons are `#[non_exhaustive]`, so
+//! later releases can add fields (more candidates, liveness details) and
+//! reasons without breaking downstream builds: match enums with a wildcard
+//! arm and build configurations from `Default` and their `with_*` methods.
+//!
+//! ```no_run
+//! use face_auth::v1::*;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let auth = FaceAuth::builder().data_dir("face_auth_data").build()?;
+//! let result = auth.authenticate(&AuthRequest::camera(0.6)).await?;
+//! match result.denial {
+//!     None => println!("Welcome, {}!", result.user_id.unwrap_or_default()),
+//!     Some(DenialReason::NoFace) => println!("No face in view"),
+//!     Some(reason) => println!("Denied: {}", reason),
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::PathBuf;
+
+pub use crate::assurance::{AssuranceLevel, Liveness, Session};
+pub use crate::auth::{EnforcementMode, FaceAuth, FaceAuthBuilder, FaceAuthResult, PrivacyMode, StorageMode, WarmUpReport};
+pub use crate::auth_flow::{AuthFlow, AuthFlowConfig, AuthState};
+pub use crate::backend::FaceBackend;
+pub use crate::context::AuthContext;
+pub use crate::denial::DenialReason;
+pub use crate::device::{DeviceContention, DeviceOperation};
+pub use crate::error::FaceAuthError;
+pub use crate::explain::Explanation;
+pub use crate::face_storage::{StorageLayout, UserMetadata, UserSummary};
+pub use crate::matching::{MatchCandidate, SampleDistance, DEFAULT_TOLERANCE};
+pub use crate::notify::AlertRules;
+pub use crate::outbox::OutboxConfig;
+pub use crate::registration::{EnrollmentProgress, RegistrationOutcome, SampleOutcome, SampleStatus};
+
+/// Where the face of an [`AuthRequest`] comes from
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Frame {
+    /// Captured from the configured cameras
+    Camera,
+    /// An image file, e.g. saved by a door terminal
+    File(PathBuf),
+    /// An image received as bytes, e.g. over REST, from `source`
+    Upload { image: Vec<u8>, source: String },
+}
+
+/// One authentication attempt, for [`FaceAuth::authenticate`]
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct AuthRequest {
+    /// Face matching tolerance (0.0-1.0, lower = stricter)
+    pub tolerance: f64,
+    pub frame: Frame,
+    /// Credentials to match against; the instance's source directory if unset
+    pub source_dir: Option<PathBuf>,
+    /// Reaches the decision policy and hooks, and is recorded in the audit
+    /// log and the result
+    pub context: AuthContext,
+    /// Camera matches granted below [`AssuranceLevel::L3`](crate::AssuranceLevel::L3)
+    /// take no effect until an [`AuthFlow`](crate::AuthFlow)'s liveness
+    /// challenge resolves them
+    pub(crate) hold_for_challenge: bool,
+    /// Only this user can be granted, see [`FaceAuth::verify_user`](crate::FaceAuth::verify_user)
+    pub(crate) claimed_user: Option<String>,
+    /// Only report the decision, see [`FaceAuth::test_match`](crate::FaceAuth::test_match)
+    pub(crate) match_only: bool,
+}
+
+impl AuthRequest {
+    pub fn new(tolerance: f64, frame: Frame) -> Self {
+        Self { tolerance, frame, source_dir: None, context: AuthContext::new(), hold_for_challenge: false, claimed_user: None, match_only: false }
+    }
+
+    pub fn camera(tolerance: f64) -> Self {
+        Self::new(tolerance, Frame::Camera)
+    }
+
+    pub fn file(tolerance: f64, image: impl Into<PathBuf>) -> Self {
+        Self::new(tolerance, Frame::File(image.into()))
+    }
+
+    pub fn upload(tolerance: f64, image: impl Into<Vec<u8>>, source: impl Into<String>) -> Self {
+        Self::new(tolerance, Frame::Upload { image: image.into(), source: source.into() })
+    }
+
+    pub fn with_source_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.source_dir = Some(dir.into());
+        self
+    }
+
+    pub fn with_context(mut self, context: AuthContext) -> Self {
+        self.context = context;
+        self
+    }
+}
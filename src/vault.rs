@@ -0,0 +1,128 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, KeyInit, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fs;
+use std::path::Path;
+
+use crate::assurance::AssuranceLevel;
+use crate::crypto::{self, KEY_LEN};
+use crate::face_storage;
+use crate::signing::{decode_hex, encode_hex};
+use crate::sketch::{SecureSketch, SketchParams};
+
+/// Prefix of the hashed bytes of a sealing key
+const SEALING_DOMAIN: &[u8] = b"face_auth vault sealing key v1";
+
+/// A secret, such as a disk-encryption passphrase, encrypted under a key
+/// that needs both a face close to the enrolled one and the vault key
+///
+/// The face-derived key comes from the fuzzy extractor's random secret. It is
+/// combined with a vault key kept outside the data directory, e.g. in the OS
+/// keyring, so the stored samples alone can't open the secret.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SealedSecret {
+    pub sketch: SecureSketch,
+    /// Hex of the secret sealed with the face-derived key
+    pub ciphertext: String,
+    pub sealed_at: DateTime<Utc>,
+}
+
+impl SealedSecret {
+    pub fn seal(embedding: &[f64], secret: &[u8], params: SketchParams, vault_key: &[u8; KEY_LEN]) -> Result<Self> {
+        let (face_key, sketch) = SecureSketch::enroll(embedding, params)?;
+        let ciphertext = encode_hex(&crypto::seal(&sealing_key(vault_key, &face_key), secret)?);
+        Ok(Self { sketch, ciphertext, sealed_at: Utc::now() })
+    }
+
+    /// The secret, if `embedding` reproduces the face-derived key
+    ///
+    /// Fails if it does but the secret was sealed under another vault key.
+    pub fn open(&self, embedding: &[f64], vault_key: &[u8; KEY_LEN]) -> Result<Option<Vec<u8>>> {
+        let Some(face_key) = self.sketch.reproduce(embedding)? else {
+            return Ok(None);
+        };
+        let ciphertext = decode_hex(&self.ciphertext).ok_or_else(|| anyhow!("Malformed sealed secret"))?;
+        crypto::open(&sealing_key(vault_key, &face_key), &ciphertext).map(Some)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Write atomically, readable by the owner only
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        face_storage::write_private(path.as_ref(), serde_json::to_string_pretty(self)?.as_bytes())
+    }
+}
+
+fn sealing_key(vault_key: &[u8; KEY_LEN], face_key: &[u8; KEY_LEN]) -> [u8; KEY_LEN] {
+    let mut mac = <Hmac<Sha256> as KeyInit>::new_from_slice(vault_key).expect("HMAC accepts keys of any length");
+    mac.update(SEALING_DOMAIN);
+    mac.update(face_key);
+    mac.finalize().into_bytes().into()
+}
+
+/// How confident a live match must be before a sealed secret is released
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnlockPolicy {
+    /// Matching tolerance, stricter than for opening a door
+    pub tolerance: f64,
+    /// Raised to [`UnlockPolicy::MIN_ASSURANCE`] if lower
+    pub min_assurance: AssuranceLevel,
+    pub params: SketchParams,
+}
+
+impl UnlockPolicy {
+    /// A photo held up to the camera must never release a secret, so a
+    /// liveness check is always required
+    pub const MIN_ASSURANCE: AssuranceLevel = AssuranceLevel::L2;
+
+    /// Assurance level a match must reach, never below [`Self::MIN_ASSURANCE`]
+    pub fn required_assurance(&self) -> AssuranceLevel {
+        self.min_assurance.max(Self::MIN_ASSURANCE)
+    }
+}
+
+impl Default for UnlockPolicy {
+    fn default() -> Self {
+        Self { tolerance: 0.4, min_assurance: Self::MIN_ASSURANCE, params: SketchParams::default() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sealed_secret_opens_only_for_the_enrolled_face_and_vault_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ann.json");
+        let face: Vec<f64> = (0..128).map(|i| ((i * 37 % 101) as f64 / 101.0) - 0.5).collect();
+        // Uncorrelated with `face`; a reversed copy shares enough direction to
+        // reproduce the key now and then
+        let other: Vec<f64> = (0..128).map(|i| ((i * 61 % 89) as f64 / 89.0) - 0.5).collect();
+        let key = [7u8; KEY_LEN];
+        SealedSecret::seal(&face, b"correct horse", SketchParams::default(), &key).unwrap().save(&path).unwrap();
+
+        let sealed = SealedSecret::load(&path).unwrap();
+        assert!(!sealed.ciphertext.contains(&encode_hex(b"correct horse")));
+        assert_eq!(sealed.open(&face, &key).unwrap().as_deref(), Some(&b"correct horse"[..]));
+        assert_eq!(sealed.open(&other, &key).unwrap(), None);
+        assert!(sealed.open(&face, &[8u8; KEY_LEN]).is_err());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_unlock_needs_liveness() {
+        let policy = UnlockPolicy { min_assurance: AssuranceLevel::L1, ..Default::default() };
+        assert_eq!(policy.required_assurance(), AssuranceLevel::L2);
+        let policy = UnlockPolicy { min_assurance: AssuranceLevel::L3, ..Default::default() };
+        assert_eq!(policy.required_assurance(), AssuranceLevel::L3);
+    }
+}
@@ -0,0 +1,105 @@
+//! Sandboxed WASM plugins
+//!
+//! Decision policies and hooks can ship as WebAssembly modules, see
+//! [`WasmPolicy`](crate::policy::WasmPolicy) and
+//! [`Hook::wasm`](crate::hooks::Hook::wasm). A module runs in wasmtime
+//! without any imports, so it can't reach files, the network or the clock.
+//! Each call gets a fresh instance with a fuel budget and a memory cap, so a
+//! module can neither keep state between decisions nor hang or exhaust the
+//! device.
+//!
+//! Input and output are JSON exchanged through the module's linear memory.
+//! The module exports `memory`, `alloc(len: i32) -> i32` returning where the
+//! host may write `len` bytes of input, and the entry point `(ptr: i32, len:
+//! i32) -> i64` returning where its output is, the pointer in the high and the
+//! length in the low 32 bits.
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::path::Path;
+use wasmtime::{Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+/// Instructions a call may run by default, a few milliseconds of work
+pub const DEFAULT_FUEL: u64 = 10_000_000;
+
+/// Linear memory a module may grow to by default
+pub const DEFAULT_MEMORY_LIMIT: usize = 16 * 1024 * 1024;
+
+/// A compiled WASM module, instantiated afresh for every call
+#[derive(Clone)]
+pub struct WasmModule {
+    engine: Engine,
+    module: Module,
+    fuel: u64,
+    memory_limit: usize,
+}
+
+impl fmt::Debug for WasmModule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WasmModule")
+            .field("name", &self.module.name())
+            .field("fuel", &self.fuel)
+            .field("memory_limit", &self.memory_limit)
+            .finish()
+    }
+}
+
+impl WasmModule {
+    /// Compile the module in `path`, binary or in the text format
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).with_context(|| format!("Failed to read WASM module {}", path.display()))?;
+        Self::from_bytes(&bytes).with_context(|| format!("Invalid WASM module {}", path.display()))
+    }
+
+    /// Compile a module, binary or in the text format
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config)?;
+        let module = Module::new(&engine, bytes)?;
+        if let Some(import) = module.imports().next() {
+            bail!("WASM plugins can't import anything, but the module imports {}::{}", import.module(), import.name());
+        }
+        Ok(Self { engine, module, fuel: DEFAULT_FUEL, memory_limit: DEFAULT_MEMORY_LIMIT })
+    }
+
+    /// Stop a call after `fuel` instructions instead of [`DEFAULT_FUEL`]
+    pub fn with_fuel(mut self, fuel: u64) -> Self {
+        self.fuel = fuel;
+        self
+    }
+
+    /// Let the module grow its memory to `bytes` instead of
+    /// [`DEFAULT_MEMORY_LIMIT`]
+    pub fn with_memory_limit(mut self, bytes: usize) -> Self {
+        self.memory_limit = bytes;
+        self
+    }
+
+    /// Call the export `entry` with `input` as JSON and parse the JSON it returns
+    pub fn call_json<I: Serialize, O: DeserializeOwned>(&self, entry: &str, input: &I) -> Result<O> {
+        let limits = StoreLimitsBuilder::new().memory_size(self.memory_limit).instances(1).build();
+        let mut store: Store<StoreLimits> = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(self.fuel)?;
+        let instance = Linker::new(&self.engine).instantiate(&mut store, &self.module)?;
+        let memory = instance.get_memory(&mut store, "memory").ok_or_else(|| anyhow!("The WASM module exports no memory"))?;
+        let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc")?;
+        let call = instance.get_typed_func::<(i32, i32), i64>(&mut store, entry)?;
+
+        let input = serde_json::to_vec(input)?;
+        let len = i32::try_from(input.len()).context("Input too large for a WASM module")?;
+        let ptr = alloc.call(&mut store, len).context("WASM module failed to allocate its input")?;
+        memory.write(&mut store, ptr as u32 as usize, &input).context("WASM module allocated its input out of bounds")?;
+        let packed = call.call(&mut store, (ptr, len)).with_context(|| format!("WASM module failed in {}", entry))? as u64;
+
+        let (start, len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+        let output = start.checked_add(len)
+            .and_then(|end| memory.data(&store).get(start..end))
+            .ok_or_else(|| anyhow!("WASM module returned output out of bounds"))?;
+        serde_json::from_slice(output).context("WASM module returned malformed output")
+    }
+}
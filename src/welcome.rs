@@ -0,0 +1,57 @@
+use chrono::{DateTime, Local, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::face_storage::{UserMetadata, UserStats};
+
+/// Part of the day, for choosing "Good morning" vs "Good evening"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeOfDay {
+    Morning,
+    Afternoon,
+    Evening,
+    Night,
+}
+
+impl TimeOfDay {
+    pub fn from_hour(hour: u32) -> Self {
+        match hour {
+            5..=11 => TimeOfDay::Morning,
+            12..=16 => TimeOfDay::Afternoon,
+            17..=21 => TimeOfDay::Evening,
+            _ => TimeOfDay::Night,
+        }
+    }
+}
+
+/// Data for a welcome screen after a successful match
+///
+/// Contains no rendered text; the UI picks the wording for `locale`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Greeting {
+    /// Display name from the user's metadata, falling back to the username
+    pub display_name: String,
+    /// Previous successful authentication, `None` on the first visit
+    pub last_seen: Option<DateTime<Utc>>,
+    /// Number of successful authentications including this one
+    pub visit_count: u64,
+    pub time_of_day: TimeOfDay,
+    pub locale: String,
+}
+
+impl Greeting {
+    /// Build the greeting from the user's statistics as they were before this visit
+    pub fn new(username: &str, metadata: Option<&UserMetadata>, previous: &UserStats, locale: &str) -> Self {
+        let display_name = metadata
+            .and_then(|m| m.display_name.clone())
+            .unwrap_or_else(|| username.to_string());
+
+        Self {
+            display_name,
+            last_seen: previous.last_authentication,
+            visit_count: previous.authentication_count + 1,
+            time_of_day: TimeOfDay::from_hour(Local::now().hour()),
+            locale: locale.to_string(),
+        }
+    }
+}
@@ -0,0 +1,331 @@
+//! Running `face_auth serve` as a Windows service, e.g. on reception kiosks
+//!
+//! [`ServiceInstaller`] registers the service with `sc.exe`, including its
+//! restart policy and event-log source. The installed command line runs
+//! `face_auth serve --windows-service <name>`, which hands control to the
+//! service control manager through [`run`].
+
+use anyhow::{Result, anyhow, bail};
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+/// Name the service is installed under unless told otherwise
+pub const DEFAULT_NAME: &str = "face_auth";
+
+/// Registry key event-log sources of the Application log live under
+const EVENT_SOURCES_KEY: &str = r"HKLM\SYSTEM\CurrentControlSet\Services\EventLog\Application";
+
+/// Message file shipped with .NET whose messages pass the event's text through
+const EVENT_MESSAGE_FILE: &str = r"%SystemRoot%\Microsoft.NET\Framework64\v4.0.30319\EventLogMessages.dll";
+
+/// What the service control manager does when the service fails
+#[derive(Debug, Clone, PartialEq)]
+pub struct RestartPolicy {
+    /// Delay before each restart; failures beyond the list reuse the last one
+    pub delays: Vec<Duration>,
+    /// Period without failures after which the count starts over
+    pub reset_after: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            delays: vec![Duration::from_secs(5), Duration::from_secs(5), Duration::from_secs(60)],
+            reset_after: Duration::from_secs(24 * 3600),
+        }
+    }
+}
+
+/// Installs and removes the server as an auto-start Windows service
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceInstaller {
+    pub name: String,
+    pub display_name: String,
+    pub description: String,
+    /// The `face_auth` executable
+    pub program: PathBuf,
+    /// Arguments passed to `face_auth serve`, e.g. `--data-dir`
+    pub serve_args: Vec<String>,
+    pub restart: RestartPolicy,
+}
+
+impl ServiceInstaller {
+    pub fn new(name: impl Into<String>, program: impl Into<PathBuf>) -> Self {
+        Self {
+            name: name.into(),
+            display_name: "Face Auth verification server".to_string(),
+            description: "Verifies faces for doors and kiosks over HTTP".to_string(),
+            program: program.into(),
+            serve_args: Vec::new(),
+            restart: RestartPolicy::default(),
+        }
+    }
+
+    pub fn with_serve_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.serve_args = args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_restart_policy(mut self, restart: RestartPolicy) -> Self {
+        self.restart = restart;
+        self
+    }
+
+    /// The command line the service control manager starts
+    pub fn command_line(&self) -> String {
+        let program = self.program.to_string_lossy();
+        let mut parts = vec![quote(&program), "serve".to_string(), "--windows-service".to_string(), quote(&self.name)];
+        parts.extend(self.serve_args.iter().map(|arg| quote(arg)));
+        parts.join(" ")
+    }
+
+    /// The `sc.exe` and `reg.exe` invocations [`install`](Self::install) runs
+    pub fn install_commands(&self) -> Vec<Vec<String>> {
+        let actions = self.restart.delays.iter()
+            .map(|delay| format!("restart/{}", delay.as_millis()))
+            .collect::<Vec<_>>()
+            .join("/");
+        let source_key = format!(r"{}\{}", EVENT_SOURCES_KEY, self.name);
+        vec![
+            args(["sc.exe", "create", &self.name, "binPath=", &self.command_line(), "start=", "auto", "DisplayName=", &self.display_name]),
+            args(["sc.exe", "description", &self.name, &self.description]),
+            args(["sc.exe", "failure", &self.name, "reset=", &self.restart.reset_after.as_secs().to_string(), "actions=", &actions]),
+            // Also restart when the server exits with an error rather than crashing
+            args(["sc.exe", "failureflag", &self.name, "1"]),
+            args(["reg.exe", "add", &source_key, "/v", "EventMessageFile", "/t", "REG_EXPAND_SZ", "/d", EVENT_MESSAGE_FILE, "/f"]),
+            args(["reg.exe", "add", &source_key, "/v", "TypesSupported", "/t", "REG_DWORD", "/d", "7", "/f"]),
+        ]
+    }
+
+    /// Register the service; needs an elevated prompt
+    pub fn install(&self) -> Result<()> {
+        for command in self.install_commands() {
+            run_command(&command)?;
+        }
+        Ok(())
+    }
+
+    /// Stop and remove the service `name` and its event-log source
+    pub fn uninstall(name: &str) -> Result<()> {
+        // Fails when the service isn't running, which is fine
+        let _ = run_command(&args(["sc.exe", "stop", name]));
+        run_command(&args(["sc.exe", "delete", name]))?;
+        let _ = run_command(&args(["reg.exe", "delete", &format!(r"{}\{}", EVENT_SOURCES_KEY, name), "/f"]));
+        Ok(())
+    }
+}
+
+/// Resolves once the service control manager asks the service to stop,
+/// including when Windows shuts down
+#[derive(Debug)]
+pub struct StopRequest(tokio::sync::oneshot::Receiver<()>);
+
+impl StopRequest {
+    pub async fn requested(self) {
+        let _ = self.0.await;
+    }
+}
+
+/// Run `serve` as the service `name` until it returns, reporting state
+/// changes to the service control manager and failures to the event log
+///
+/// Blocks the calling thread; `serve` runs on a thread of the service
+/// control manager's. Returning an error marks the service as failed, which
+/// triggers the [`RestartPolicy`].
+pub fn run<F>(name: &str, serve: F) -> Result<()>
+where
+    F: FnOnce(StopRequest) -> Result<()> + Send + 'static,
+{
+    #[cfg(windows)]
+    return ffi::run(name, Box::new(serve));
+    #[cfg(not(windows))]
+    {
+        let _ = serve;
+        bail!("{} can only run as a service on Windows", name)
+    }
+}
+
+fn args<const N: usize>(parts: [&str; N]) -> Vec<String> {
+    parts.iter().map(|part| part.to_string()).collect()
+}
+
+fn quote(arg: &str) -> String {
+    if arg.is_empty() || arg.contains([' ', '\t', '"']) {
+        format!("\"{}\"", arg.replace('"', "\\\""))
+    } else {
+        arg.to_string()
+    }
+}
+
+fn run_command(command: &[String]) -> Result<()> {
+    if !cfg!(windows) {
+        bail!("Windows services can only be managed on Windows");
+    }
+    let status = Command::new(&command[0]).args(&command[1..]).status()
+        .map_err(|e| anyhow!("Failed to run {}: {}", command[0], e))?;
+    if !status.success() {
+        bail!("{} failed ({})", command.join(" "), status);
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+mod ffi {
+    use anyhow::{Result, bail};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Mutex, OnceLock};
+    use windows_sys::Win32::System::EventLog::{
+        DeregisterEventSource, EVENTLOG_ERROR_TYPE, EVENTLOG_INFORMATION_TYPE, RegisterEventSourceW, ReportEventW,
+    };
+    use windows_sys::Win32::System::Services::{
+        RegisterServiceCtrlHandlerExW, SERVICE_ACCEPT_SHUTDOWN, SERVICE_ACCEPT_STOP, SERVICE_CONTROL_INTERROGATE,
+        SERVICE_CONTROL_SHUTDOWN, SERVICE_CONTROL_STOP, SERVICE_RUNNING, SERVICE_START_PENDING, SERVICE_STATUS,
+        SERVICE_STATUS_HANDLE, SERVICE_STOP_PENDING, SERVICE_STOPPED, SERVICE_TABLE_ENTRYW, SERVICE_WIN32_OWN_PROCESS,
+        SetServiceStatus, StartServiceCtrlDispatcherW,
+    };
+
+    use super::StopRequest;
+
+    const NO_ERROR: u32 = 0;
+    const ERROR_CALL_NOT_IMPLEMENTED: u32 = 120;
+    const ERROR_SERVICE_SPECIFIC_ERROR: u32 = 1066;
+
+    type Serve = Box<dyn FnOnce(StopRequest) -> Result<()> + Send>;
+
+    static NAME: OnceLock<Vec<u16>> = OnceLock::new();
+    static SERVE: Mutex<Option<Serve>> = Mutex::new(None);
+    static STOP: Mutex<Option<tokio::sync::oneshot::Sender<()>>> = Mutex::new(None);
+    static STATUS_HANDLE: AtomicUsize = AtomicUsize::new(0);
+
+    pub fn run(name: &str, serve: Serve) -> Result<()> {
+        let name = NAME.get_or_init(|| wide(name));
+        *SERVE.lock().unwrap() = Some(serve);
+        let table = [
+            SERVICE_TABLE_ENTRYW { lpServiceName: name.as_ptr() as *mut u16, lpServiceProc: Some(service_main) },
+            SERVICE_TABLE_ENTRYW { lpServiceName: std::ptr::null_mut(), lpServiceProc: None },
+        ];
+        // SAFETY: the table is null-terminated and outlives the call, which
+        // returns once the service has stopped
+        if unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) } == 0 {
+            bail!("Not started by the service control manager: {}", std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    unsafe extern "system" fn service_main(_argc: u32, _argv: *mut *mut u16) {
+        let name = NAME.get().expect("service name is set before dispatching");
+        let handle = unsafe { RegisterServiceCtrlHandlerExW(name.as_ptr(), Some(control_handler), std::ptr::null()) };
+        if handle.is_null() {
+            report(EVENTLOG_ERROR_TYPE, &format!("Failed to register the control handler: {}", std::io::Error::last_os_error()));
+            return;
+        }
+        STATUS_HANDLE.store(handle as usize, Ordering::SeqCst);
+        set_status(SERVICE_START_PENDING, NO_ERROR);
+
+        let (stop, stopped) = tokio::sync::oneshot::channel();
+        *STOP.lock().unwrap() = Some(stop);
+        let Some(serve) = SERVE.lock().unwrap().take() else {
+            set_status(SERVICE_STOPPED, ERROR_SERVICE_SPECIFIC_ERROR);
+            return;
+        };
+        set_status(SERVICE_RUNNING, NO_ERROR);
+        report(EVENTLOG_INFORMATION_TYPE, "Started");
+        match serve(StopRequest(stopped)) {
+            Ok(()) => {
+                report(EVENTLOG_INFORMATION_TYPE, "Stopped");
+                set_status(SERVICE_STOPPED, NO_ERROR);
+            }
+            Err(e) => {
+                report(EVENTLOG_ERROR_TYPE, &format!("Stopped after an error: {:#}", e));
+                set_status(SERVICE_STOPPED, ERROR_SERVICE_SPECIFIC_ERROR);
+            }
+        }
+    }
+
+    unsafe extern "system" fn control_handler(
+        control: u32,
+        _event_type: u32,
+        _event_data: *mut core::ffi::c_void,
+        _context: *mut core::ffi::c_void,
+    ) -> u32 {
+        match control {
+            SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+                set_status(SERVICE_STOP_PENDING, NO_ERROR);
+                if let Some(stop) = STOP.lock().unwrap().take() {
+                    let _ = stop.send(());
+                }
+                NO_ERROR
+            }
+            SERVICE_CONTROL_INTERROGATE => NO_ERROR,
+            _ => ERROR_CALL_NOT_IMPLEMENTED,
+        }
+    }
+
+    fn set_status(state: u32, exit_code: u32) {
+        let accepted = if state == SERVICE_RUNNING { SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN } else { 0 };
+        let status = SERVICE_STATUS {
+            dwServiceType: SERVICE_WIN32_OWN_PROCESS,
+            dwCurrentState: state,
+            dwControlsAccepted: accepted,
+            dwWin32ExitCode: exit_code,
+            dwServiceSpecificExitCode: (exit_code == ERROR_SERVICE_SPECIFIC_ERROR) as u32,
+            dwCheckPoint: 0,
+            // Stopping waits for requests in progress and the camera to close
+            dwWaitHint: 30_000,
+        };
+        let handle = STATUS_HANDLE.load(Ordering::SeqCst) as SERVICE_STATUS_HANDLE;
+        // SAFETY: the handle came from RegisterServiceCtrlHandlerExW
+        unsafe { SetServiceStatus(handle, &status) };
+    }
+
+    /// Write `message` to the Application event log under the service's name
+    fn report(kind: u16, message: &str) {
+        let Some(name) = NAME.get() else {
+            return;
+        };
+        let message = wide(message);
+        let strings = [message.as_ptr()];
+        // SAFETY: the strings are null-terminated and outlive the calls
+        unsafe {
+            let source = RegisterEventSourceW(std::ptr::null(), name.as_ptr());
+            if source.is_null() {
+                return;
+            }
+            ReportEventW(source, kind, 0, 1, std::ptr::null_mut(), 1, 0, strings.as_ptr(), std::ptr::null());
+            DeregisterEventSource(source);
+        }
+    }
+
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_install_commands_register_restarts_and_event_source() {
+        let installer = ServiceInstaller::new("face_auth", r"C:\Program Files\face_auth\face_auth.exe")
+            .with_serve_args(["--data-dir", r"C:\ProgramData\face_auth", "--addr", "0.0.0.0:8080"]);
+        assert_eq!(
+            installer.command_line(),
+            r#""C:\Program Files\face_auth\face_auth.exe" serve --windows-service face_auth --data-dir C:\ProgramData\face_auth --addr 0.0.0.0:8080"#
+        );
+
+        let commands = installer.install_commands();
+        assert_eq!(commands[0][..3], ["sc.exe", "create", "face_auth"]);
+        assert_eq!(commands[0][4], installer.command_line());
+        assert!(commands.contains(&args(["sc.exe", "failure", "face_auth", "reset=", "86400", "actions=", "restart/5000/restart/5000/restart/60000"])));
+        assert!(commands.iter().any(|command| command[0] == "reg.exe" && command[2].ends_with(r"Application\face_auth")));
+        if !cfg!(windows) {
+            assert!(installer.install().is_err());
+        }
+    }
+}
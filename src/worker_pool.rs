@@ -0,0 +1,855 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Barrier, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::clock::{Clock, SystemClock};
+use crate::profiles::Preprocessing;
+use crate::standalone_python::{
+    absolute_path, forward_line, OutputStream, ReportedAuthResult, StandaloneAuthResult,
+    StandalonePythonFaceAuth, RESULT_PREFIX,
+};
+
+/// Request sent to a worker as one JSON line
+#[derive(Debug, Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum WorkerRequest {
+    Ping,
+    Warm { camera: bool },
+    Auth { tolerance: f64, source_dir: String, image: String, preprocessing: Preprocessing },
+}
+
+/// Error reported by the script for one request; the worker itself is still usable
+#[derive(Debug)]
+struct RequestFailed(String);
+
+impl std::fmt::Display for RequestFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Python worker request failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for RequestFailed {}
+
+/// The script exited before it was ready because a package couldn't be imported
+#[derive(Debug)]
+struct MissingDependency(String);
+
+impl std::fmt::Display for MissingDependency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Python worker is missing a dependency: {}", self.0)
+    }
+}
+
+impl std::error::Error for MissingDependency {}
+
+/// The worker didn't answer within its deadline and was killed
+#[derive(Debug)]
+struct Unanswered {
+    id: usize,
+    timeout: Duration,
+}
+
+impl std::fmt::Display for Unanswered {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Python worker {} didn't answer within {:?}", self.id, self.timeout)
+    }
+}
+
+impl std::error::Error for Unanswered {}
+
+/// Stderr lines kept per worker, to tell why it failed
+const STDERR_TAIL_LINES: usize = 20;
+
+/// Consecutive slow responses after which a worker is replaced
+const SLOW_STRIKES: u32 = 3;
+
+/// How long to wait for a worker whose output closed to be reaped
+const EXIT_WAIT: Duration = Duration::from_secs(1);
+
+/// How long a worker may take to import its packages and load its models,
+/// when started and when warmed up
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Default of [`WorkerPool::with_request_timeout`]
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Dependencies are reinstalled at most this often
+const REPAIR_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Reinstalls the Python dependencies
+type Repair = Arc<dyn Fn() -> Result<()> + Send + Sync>;
+
+/// Why a worker was discarded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkerFailure {
+    /// Exited on its own
+    Crashed,
+    /// Killed with SIGKILL, as the kernel's out-of-memory killer does, or
+    /// reported a `MemoryError`
+    OutOfMemory,
+    /// Answered slower than the pool's slow threshold several times in a row
+    Slow,
+    /// Didn't answer a request or health check in time, and was killed
+    Unresponsive,
+    /// Couldn't start because a Python package failed to import
+    MissingDependency,
+}
+
+/// A persistent `--mode serve` process of the Python script
+///
+/// Each request is answered with log lines followed by exactly one result line.
+struct PythonWorker {
+    id: usize,
+    child: Child,
+    stdin: ChildStdin,
+    /// Stdout lines, read on a thread of their own so a hung worker can't
+    /// block its caller past the deadline
+    stdout: Receiver<String>,
+    requests_served: u64,
+    /// Consecutive responses slower than the pool's threshold
+    slow_strikes: u32,
+    /// Last lines the script wrote to stderr
+    stderr_tail: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl PythonWorker {
+    fn spawn(backend: &StandalonePythonFaceAuth, id: usize) -> Result<Self> {
+        let mut child = backend.command(&["--mode".into(), "serve".into()])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let stdin = child.stdin.take().ok_or_else(|| anyhow!("Worker stdin unavailable"))?;
+        let stdout = child.stdout.take().ok_or_else(|| anyhow!("Worker stdout unavailable"))?;
+        let stderr_tail = Arc::new(Mutex::new(VecDeque::with_capacity(STDERR_TAIL_LINES)));
+        let stderr_reader = child.stderr.take().map(|stderr| {
+            let tail = stderr_tail.clone();
+            thread::spawn(move || {
+                for line in BufReader::new(stderr).lines().map_while(|l| l.ok()) {
+                    forward_line("worker", OutputStream::Stderr, &line);
+                    let mut tail = tail.lock().unwrap_or_else(PoisonError::into_inner);
+                    if tail.len() == STDERR_TAIL_LINES {
+                        tail.pop_front();
+                    }
+                    tail.push_back(line);
+                }
+            })
+        });
+
+        let (lines, stdout_lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(|l| l.ok()) {
+                if lines.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut worker = Self { id, child, stdin, stdout: stdout_lines, requests_served: 0, slow_strikes: 0, stderr_tail };
+        // The ready line arrives once the script's imports (and models) are loaded
+        if let Err(e) = worker.read_result::<serde_json::Value>(STARTUP_TIMEOUT) {
+            // Read the rest of stderr before looking for the import error
+            let _ = worker.child.kill();
+            let _ = worker.child.wait();
+            if let Some(reader) = stderr_reader {
+                let _ = reader.join();
+            }
+            return Err(match worker.import_error() {
+                Some(line) => MissingDependency(line).into(),
+                None => e,
+            });
+        }
+        tracing::debug!(worker = id, "Python worker ready");
+        Ok(worker)
+    }
+
+    fn import_error(&self) -> Option<String> {
+        self.stderr_tail.lock().unwrap_or_else(PoisonError::into_inner).iter()
+            .find(|line| line.contains("ModuleNotFoundError") || line.contains("ImportError"))
+            .cloned()
+    }
+
+    /// Why the worker has to go after a request failed with `error`
+    fn failure(&mut self, error: &anyhow::Error) -> WorkerFailure {
+        if error.is::<Unanswered>() {
+            return WorkerFailure::Unresponsive;
+        }
+        if error.downcast_ref::<RequestFailed>().is_some_and(|e| e.0.contains("MemoryError")) {
+            return WorkerFailure::OutOfMemory;
+        }
+        self.exit_failure()
+    }
+
+    /// Why the worker exited, or [`WorkerFailure::Unresponsive`] if it is
+    /// still running
+    fn exit_failure(&mut self) -> WorkerFailure {
+        // A worker whose output just closed may not have been reaped yet
+        let deadline = Instant::now() + EXIT_WAIT;
+        loop {
+            match self.child.try_wait() {
+                Ok(Some(status)) if killed(status) => return WorkerFailure::OutOfMemory,
+                Ok(Some(_)) => return WorkerFailure::Crashed,
+                Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(20)),
+                _ => return WorkerFailure::Unresponsive,
+            }
+        }
+    }
+
+    fn call<T: DeserializeOwned>(&mut self, request: &WorkerRequest, timeout: Duration) -> Result<(T, String)> {
+        writeln!(self.stdin, "{}", serde_json::to_string(request)?)?;
+        self.stdin.flush()?;
+        let result = self.read_result(timeout)?;
+        self.requests_served += 1;
+        Ok(result)
+    }
+
+    /// Read until the next result line, forwarding log lines on the way
+    ///
+    /// A worker without a result line after `timeout` is killed.
+    fn read_result<T: DeserializeOwned>(&mut self, timeout: Duration) -> Result<(T, String)> {
+        let deadline = Instant::now() + timeout;
+        let mut log = String::new();
+        loop {
+            let line = match self.stdout.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Disconnected) => return Err(anyhow!("Python worker {} exited", self.id)),
+                Err(RecvTimeoutError::Timeout) => {
+                    let _ = self.child.kill();
+                    return Err(Unanswered { id: self.id, timeout }.into());
+                }
+            };
+            let line = line.trim_end();
+            let Some(json) = line.strip_prefix(RESULT_PREFIX) else {
+                forward_line("worker", OutputStream::Stdout, line);
+                log.push_str(line);
+                log.push('\n');
+                continue;
+            };
+
+            let value: serde_json::Value = serde_json::from_str(json)
+                .map_err(|e| anyhow!("Malformed result from Python worker: {}", e))?;
+            if let Some(error) = value.get("error").and_then(|e| e.as_str()) {
+                return Err(RequestFailed(error.to_string()).into());
+            }
+            return Ok((serde_json::from_value(value)?, log));
+        }
+    }
+
+    fn is_alive(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+}
+
+/// Whether the process was killed with SIGKILL, which the out-of-memory
+/// killer sends
+#[cfg(unix)]
+fn killed(status: ExitStatus) -> bool {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal() == Some(libc::SIGKILL)
+}
+
+#[cfg(not(unix))]
+fn killed(_status: ExitStatus) -> bool {
+    false
+}
+
+impl Drop for PythonWorker {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Snapshot of a pool's occupancy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolStatus {
+    pub idle: usize,
+    pub busy: usize,
+    pub max_workers: usize,
+}
+
+/// Occupancy of a pool and what happened to its workers since it started,
+/// for a daemon to report and act on
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkerStatus {
+    pub pool: PoolStatus,
+    /// Workers started, replacements included
+    pub started: u64,
+    pub crashes: u64,
+    pub out_of_memory: u64,
+    /// Workers killed for not answering in time
+    #[serde(default)]
+    pub unresponsive: u64,
+    /// Requests answered slower than the slow threshold
+    pub slow_responses: u64,
+    /// Discarded workers replaced by [`WorkerPool::health_check`]
+    pub restarts: u64,
+    /// Reinstalls of the Python dependencies, see
+    /// [`WorkerPool::with_dependency_repair`]
+    pub dependency_repairs: u64,
+    pub last_failure: Option<WorkerFailure>,
+    pub last_failure_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+struct PoolState {
+    /// Idle workers, least recently used first
+    idle: VecDeque<PythonWorker>,
+    /// Idle, busy and starting workers
+    spawned: usize,
+    next_id: usize,
+    /// Workers discarded for a failure and not replaced yet
+    pending_restarts: usize,
+    /// A worker failed to import the script's packages since the last repair
+    missing_dependency: bool,
+    last_repair: Option<Instant>,
+    /// Counters; the occupancy is filled in when read
+    status: WorkerStatus,
+}
+
+/// Bounded pool of warm Python workers
+///
+/// Workers are started on demand up to `max_workers` and reused in
+/// round-robin order; callers beyond the limit wait for a free worker. A
+/// worker that crashes, runs out of memory, doesn't answer within the
+/// request timeout or keeps answering slowly is discarded; the next request or [`WorkerPool::health_check`] starts a
+/// replacement. A worker that can't import the script's packages fails the
+/// request; they are only reinstalled out of band, by the health check with
+/// [`WorkerPool::with_dependency_repair`] or by
+/// [`WorkerPool::repair_dependencies`]. [`WorkerPool::status`] counts all of it.
+///
+/// Callers waiting for a worker block their thread, so the pool must only be
+/// used from blocking threads; async code hands requests to it through
+/// `tokio::task::spawn_blocking`, as the HTTP server does, or it stalls the
+/// runtime's threads instead of queueing for the pool.
+pub struct WorkerPool {
+    backend: StandalonePythonFaceAuth,
+    max_workers: usize,
+    /// Reinstalls the Python dependencies; set by [`Self::with_dependency_repair`]
+    repair: Option<Repair>,
+    slow_threshold: Option<Duration>,
+    request_timeout: Duration,
+    clock: Arc<dyn Clock>,
+    state: Mutex<PoolState>,
+    available: Condvar,
+}
+
+impl WorkerPool {
+    pub fn new(backend: StandalonePythonFaceAuth, max_workers: usize) -> Self {
+        Self {
+            backend,
+            max_workers: max_workers.max(1),
+            repair: None,
+            slow_threshold: None,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            clock: Arc::new(SystemClock),
+            state: Mutex::new(PoolState::default()),
+            available: Condvar::new(),
+        }
+    }
+
+    /// Count requests slower than `threshold`, and replace a worker after
+    /// several in a row
+    pub fn with_slow_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_threshold = Some(threshold);
+        self
+    }
+
+    /// Kill a worker that hasn't answered a request or a health check's ping
+    /// after `timeout`, [`DEFAULT_REQUEST_TIMEOUT`] unless set
+    ///
+    /// The worker is counted as [`WorkerFailure::Unresponsive`] and replaced
+    /// like a crashed one.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Have [`Self::health_check`] reinstall the pinned Python dependencies,
+    /// at most once an hour, after a worker failed to import them
+    pub fn with_dependency_repair(mut self) -> Self {
+        let backend = self.backend.clone();
+        self.repair = Some(Arc::new(move || backend.repair_dependencies()));
+        self
+    }
+
+    /// Repair dependencies with `repair` instead of the installer
+    #[cfg(test)]
+    fn with_repair(mut self, repair: impl Fn() -> Result<()> + Send + Sync + 'static) -> Self {
+        self.repair = Some(Arc::new(repair));
+        self
+    }
+
+    /// Clock the failure times in [`WorkerStatus`] are taken from
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn max_workers(&self) -> usize {
+        self.max_workers
+    }
+
+    pub fn status(&self) -> WorkerStatus {
+        let state = self.state();
+        WorkerStatus {
+            pool: PoolStatus {
+                idle: state.idle.len(),
+                busy: state.spawned - state.idle.len(),
+                max_workers: self.max_workers,
+            },
+            ..state.status.clone()
+        }
+    }
+
+    /// Match a frame on the next free worker
+    pub fn authenticate_image(&self, tolerance: f64, source_dir: &str, image: &Path) -> Result<StandaloneAuthResult> {
+        let started = Instant::now();
+        let request = WorkerRequest::Auth {
+            tolerance,
+            source_dir: absolute_path(source_dir),
+            image: absolute_path(image),
+            preprocessing: self.backend.preprocessing(),
+        };
+        let (reported, log) = self.with_worker(|worker| worker.call::<ReportedAuthResult>(&request, self.request_timeout))?;
+        let elapsed_ms = started.elapsed().as_millis() as u32;
+        reported.into_result(tolerance, elapsed_ms, true, log)
+    }
+
+    /// Start every worker and have it load its models
+    ///
+    /// Returns the number of workers ready; fails only when none started.
+    pub fn warm_up(&self) -> Result<usize> {
+        let barrier = Barrier::new(self.max_workers);
+        let results: Vec<Result<()>> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..self.max_workers)
+                .map(|_| scope.spawn(|| {
+                    let worker = self.check_out().and_then(|mut worker| {
+                        match worker.call::<serde_json::Value>(&WorkerRequest::Warm { camera: false }, STARTUP_TIMEOUT) {
+                            Ok(_) => Ok(worker),
+                            Err(e) => {
+                                self.check_in(None);
+                                Err(e)
+                            }
+                        }
+                    });
+                    // Hold every worker until all have started, so none is warmed twice
+                    barrier.wait();
+                    worker.map(|worker| self.check_in(Some(worker)))
+                }))
+                .collect();
+            handles.into_iter()
+                .map(|h| h.join().unwrap_or_else(|_| Err(anyhow!("Worker warm-up panicked"))))
+                .collect()
+        });
+
+        let ready = results.iter().filter(|r| r.is_ok()).count();
+        let mut errors = results.into_iter().filter_map(Result::err);
+        if ready == 0 {
+            return Err(errors.next().unwrap_or_else(|| anyhow!("No Python workers started")));
+        }
+        for e in errors {
+            tracing::warn!("Python worker failed to warm up: {}", e);
+        }
+        Ok(ready)
+    }
+
+    /// Stop the idle workers, releasing their models and camera; later
+    /// requests start new ones
+    pub fn shutdown(&self) {
+        let stopped: Vec<PythonWorker> = {
+            let mut state = self.state();
+            let stopped: Vec<PythonWorker> = state.idle.drain(..).collect();
+            state.spawned -= stopped.len();
+            // Stopped on purpose, nothing to replace
+            state.pending_restarts = 0;
+            stopped
+        };
+        tracing::debug!(workers = stopped.len(), "Stopping idle Python workers");
+        self.available.notify_all();
+    }
+
+    /// Ping every idle worker, discarding the ones that don't answer, then
+    /// start and warm up replacements for every worker discarded since the
+    /// last check
+    ///
+    /// With [`Self::with_dependency_repair`], the Python dependencies are
+    /// reinstalled first if a worker couldn't import them and the last repair
+    /// was over an hour ago.
+    ///
+    /// Returns the number of healthy idle workers.
+    pub fn health_check(&self) -> usize {
+        let workers: Vec<PythonWorker> = self.state().idle.drain(..).collect();
+        let mut healthy = 0;
+        for mut worker in workers {
+            match worker.call::<serde_json::Value>(&WorkerRequest::Ping, self.request_timeout) {
+                Ok(_) => {
+                    healthy += 1;
+                    self.check_in(Some(worker));
+                }
+                Err(e) => {
+                    tracing::warn!(worker = worker.id, "Discarding unhealthy Python worker: {}", e);
+                    let failure = worker.failure(&e);
+                    self.discard(failure);
+                }
+            }
+        }
+
+        if self.repair.is_some() {
+            let due = {
+                let state = self.state();
+                state.missing_dependency && state.last_repair.is_none_or(|at| at.elapsed() >= REPAIR_INTERVAL)
+            };
+            if due {
+                if let Err(e) = self.repair_dependencies() {
+                    tracing::error!("Failed to reinstall the Python dependencies: {:#}", e);
+                }
+            }
+        }
+
+        let restarts = {
+            let mut state = self.state();
+            let restarts = state.pending_restarts.min(self.max_workers - state.spawned);
+            state.pending_restarts = 0;
+            state.spawned += restarts;
+            restarts
+        };
+        for _ in 0..restarts {
+            let id = {
+                let mut state = self.state();
+                state.next_id += 1;
+                state.next_id
+            };
+            let worker = self.start_worker(id).and_then(|mut worker| {
+                worker.call::<serde_json::Value>(&WorkerRequest::Warm { camera: false }, STARTUP_TIMEOUT)?;
+                Ok(worker)
+            });
+            match worker {
+                Ok(worker) => {
+                    tracing::info!(worker = id, "Restarted Python worker");
+                    self.state().status.restarts += 1;
+                    healthy += 1;
+                    self.check_in(Some(worker));
+                }
+                Err(e) => {
+                    tracing::warn!(worker = id, "Failed to restart Python worker: {:#}", e);
+                    self.state().pending_restarts += 1;
+                    self.check_in(None);
+                }
+            }
+        }
+        healthy
+    }
+
+    fn with_worker<T>(&self, f: impl FnOnce(&mut PythonWorker) -> Result<T>) -> Result<T> {
+        let mut worker = self.check_out()?;
+        let started = Instant::now();
+        let result = f(&mut worker);
+        if self.slow_threshold.is_some_and(|threshold| started.elapsed() > threshold) {
+            tracing::warn!(worker = worker.id, elapsed_ms = started.elapsed().as_millis() as u64, "Slow Python worker response");
+            self.state().status.slow_responses += 1;
+            worker.slow_strikes += 1;
+        } else {
+            worker.slow_strikes = 0;
+        }
+        let failure = match &result {
+            Err(e) if e.is::<Unanswered>() => Some(WorkerFailure::Unresponsive),
+            _ if worker.slow_strikes >= SLOW_STRIKES => Some(WorkerFailure::Slow),
+            Ok(_) => None,
+            Err(e) if e.downcast_ref::<RequestFailed>().is_some_and(|e| !e.0.contains("MemoryError")) && worker.is_alive() => None,
+            Err(e) => Some(worker.failure(e)),
+        };
+        match failure {
+            None => self.check_in(Some(worker)),
+            Some(failure) => {
+                tracing::warn!(worker = worker.id, served = worker.requests_served, ?failure, "Discarding failed Python worker");
+                self.discard(failure);
+            }
+        }
+        result
+    }
+
+    /// Reinstall the pinned Python dependencies now, e.g. from the command
+    /// line after [`WorkerFailure::MissingDependency`]
+    ///
+    /// Takes minutes; never call it on the authentication path.
+    pub fn repair_dependencies(&self) -> Result<()> {
+        {
+            let mut state = self.state();
+            state.last_repair = Some(Instant::now());
+            state.missing_dependency = false;
+            state.status.dependency_repairs += 1;
+        }
+        tracing::warn!("Reinstalling the Python dependencies");
+        match &self.repair {
+            Some(repair) => repair(),
+            None => self.backend.repair_dependencies(),
+        }
+    }
+
+    /// Start a worker, remembering for the next repair if the script can't
+    /// import its packages
+    fn start_worker(&self, id: usize) -> Result<PythonWorker> {
+        let worker = PythonWorker::spawn(&self.backend, id).inspect_err(|e| {
+            if e.is::<MissingDependency>() {
+                tracing::error!(worker = id, "{}", e);
+                self.record_failure(WorkerFailure::MissingDependency);
+                self.state().missing_dependency = true;
+            }
+        })?;
+        self.state().status.started += 1;
+        Ok(worker)
+    }
+
+    /// Release the slot of a worker discarded for `failure`, to be replaced
+    /// by the next health check
+    fn discard(&self, failure: WorkerFailure) {
+        self.record_failure(failure);
+        self.state().pending_restarts += 1;
+        self.check_in(None);
+    }
+
+    fn record_failure(&self, failure: WorkerFailure) {
+        let at = self.clock.now();
+        let mut state = self.state();
+        match failure {
+            WorkerFailure::Crashed => state.status.crashes += 1,
+            WorkerFailure::OutOfMemory => state.status.out_of_memory += 1,
+            WorkerFailure::Unresponsive => state.status.unresponsive += 1,
+            _ => {}
+        }
+        state.status.last_failure = Some(failure);
+        state.status.last_failure_at = Some(at);
+    }
+
+    /// Take an idle worker or start one, blocking on `available` while all
+    /// `max_workers` are busy; never call this on an async runtime's thread
+    fn check_out(&self) -> Result<PythonWorker> {
+        let mut state = self.state();
+        loop {
+            if let Some(mut worker) = state.idle.pop_front() {
+                if worker.is_alive() {
+                    return Ok(worker);
+                }
+                tracing::warn!(worker = worker.id, "Python worker died while idle");
+                drop(state);
+                let failure = worker.exit_failure();
+                self.record_failure(failure);
+                state = self.state();
+                state.spawned -= 1;
+                continue;
+            }
+
+            if state.spawned < self.max_workers {
+                state.spawned += 1;
+                state.next_id += 1;
+                let id = state.next_id;
+                drop(state);
+                return self.start_worker(id).inspect_err(|_| self.check_in(None));
+            }
+
+            state = self.available.wait(state).unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Return a worker to the pool, or release its slot when it was discarded
+    fn check_in(&self, worker: Option<PythonWorker>) {
+        let mut state = self.state();
+        match worker {
+            Some(worker) => state.idle.push_back(worker),
+            None => state.spawned -= 1,
+        }
+        self.available.notify_one();
+    }
+
+    fn state(&self) -> MutexGuard<'_, PoolState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAKE_WORKER: &str = r#"
+echo 'FACE_AUTH_RESULT {"ready": true}'
+while read -r line; do
+  case "$line" in
+    *ping*|*warm*) echo 'FACE_AUTH_RESULT {"ok": true}' ;;
+    *) echo "Comparing against users"
+       echo 'FACE_AUTH_RESULT {"is_match": true, "matched_user": "ann", "closest_user": "ann", "distance": 0.3, "confidence": 0.7, "threshold": 0.6}' ;;
+  esac
+done
+"#;
+
+    #[test]
+    fn test_pool_serves_concurrent_requests_within_bound() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("worker.sh");
+        std::fs::write(&script, FAKE_WORKER).unwrap();
+        let backend = StandalonePythonFaceAuth::from_parts("sh", script.to_str().unwrap(), dir.path());
+        let pool = WorkerPool::new(backend, 2);
+        assert_eq!(pool.warm_up().unwrap(), 2);
+
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    let result = pool.authenticate_image(0.6, "source", Path::new("frame.jpg")).unwrap();
+                    assert_eq!(result.matched_user.as_deref(), Some("ann"));
+                    assert!(result.raw_output.contains("Comparing"));
+                });
+            }
+        });
+
+        let status = pool.status().pool;
+        assert_eq!(status.busy, 0);
+        assert_eq!(status.idle, 2);
+        assert_eq!(pool.health_check(), 2);
+    }
+
+    /// Fails the way the frame's name says
+    const FAILING_WORKER: &str = r#"
+echo 'FACE_AUTH_RESULT {"ready": true}'
+while read -r line; do
+  case "$line" in
+    *ping*|*warm*) echo 'FACE_AUTH_RESULT {"ok": true}' ;;
+    *crash*) exit 1 ;;
+    *oom*) kill -9 $$ ;;
+    *memory*) echo 'FACE_AUTH_RESULT {"error": "MemoryError"}' ;;
+    *) case "$line" in *slow*) sleep 0.2 ;; esac
+       echo 'FACE_AUTH_RESULT {"is_match": false, "distance": 0.8, "confidence": 0.2, "threshold": 0.6}' ;;
+  esac
+done
+"#;
+
+    fn pool(dir: &Path, script: &str) -> WorkerPool {
+        let path = dir.join("worker.sh");
+        std::fs::write(&path, script).unwrap();
+        WorkerPool::new(StandalonePythonFaceAuth::from_parts("sh", path.to_str().unwrap(), dir), 1)
+    }
+
+    #[test]
+    fn test_failed_workers_are_counted_and_restarted() {
+        let dir = tempfile::tempdir().unwrap();
+        let clock = Arc::new(crate::clock::ManualClock::new(Utc::now()));
+        let pool = pool(dir.path(), FAILING_WORKER).with_slow_threshold(Duration::from_millis(100)).with_clock(clock.clone());
+        let auth = |frame: &str| pool.authenticate_image(0.6, "source", Path::new(frame));
+
+        assert!(auth("crash.jpg").is_err());
+        assert!(auth("oom.jpg").is_err());
+        assert!(auth("memory.jpg").is_err());
+        let status = pool.status();
+        assert_eq!((status.crashes, status.out_of_memory), (1, 2));
+        assert_eq!(status.last_failure, Some(WorkerFailure::OutOfMemory));
+        assert_eq!(status.last_failure_at, Some(clock.now()));
+
+        // A slow response is tolerated, a slow worker is not
+        auth("slow.jpg").unwrap();
+        auth("frame.jpg").unwrap();
+        assert_eq!(pool.status().pool.idle, 1);
+        for _ in 0..SLOW_STRIKES {
+            auth("slow.jpg").unwrap();
+        }
+        let status = pool.status();
+        assert_eq!(status.slow_responses, 1 + SLOW_STRIKES as u64);
+        assert_eq!(status.last_failure, Some(WorkerFailure::Slow));
+        assert_eq!(status.pool.idle, 0);
+
+        // The health check brings the pool back to a warm worker
+        assert_eq!(pool.health_check(), 1);
+        let status = pool.status();
+        assert_eq!(status.pool.idle, 1);
+        assert_eq!(status.restarts, 1);
+        assert_eq!(status.started, 5);
+    }
+
+    #[test]
+    fn test_hung_workers_are_killed_and_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let hang = dir.path().join("hang");
+        let script = format!(r#"
+echo 'FACE_AUTH_RESULT {{"ready": true}}'
+while read -r line; do
+  case "$line" in
+    *ping*) [ -e '{}' ] && sleep 5
+            echo 'FACE_AUTH_RESULT {{"ok": true}}' ;;
+    *warm*) echo 'FACE_AUTH_RESULT {{"ok": true}}' ;;
+    *) case "$line" in *hang*) sleep 5 ;; esac
+       echo 'FACE_AUTH_RESULT {{"is_match": false, "distance": 0.8, "confidence": 0.2, "threshold": 0.6}}' ;;
+  esac
+done
+"#, hang.display());
+        let pool = pool(dir.path(), &script).with_request_timeout(Duration::from_millis(200));
+        let auth = |frame: &str| pool.authenticate_image(0.6, "source", Path::new(frame));
+
+        let started = Instant::now();
+        let error = auth("hang.jpg").unwrap_err();
+        assert!(error.is::<Unanswered>(), "{:#}", error);
+        assert!(started.elapsed() < Duration::from_secs(2));
+        let status = pool.status();
+        assert_eq!((status.unresponsive, status.last_failure, status.pool.busy), (1, Some(WorkerFailure::Unresponsive), 0));
+        assert_eq!(pool.health_check(), 1);
+
+        // A worker hanging on the health check's ping is replaced in the same check
+        std::fs::write(&hang, "").unwrap();
+        let started = Instant::now();
+        assert_eq!(pool.health_check(), 1);
+        assert!(started.elapsed() < Duration::from_secs(2));
+        std::fs::remove_file(&hang).unwrap();
+        auth("frame.jpg").unwrap();
+        let status = pool.status();
+        assert_eq!((status.unresponsive, status.restarts), (2, 2));
+    }
+
+    #[test]
+    fn test_import_errors_are_detected() {
+        let dir = tempfile::tempdir().unwrap();
+        let repairs = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = repairs.clone();
+        let pool = pool(dir.path(), "echo \"ModuleNotFoundError: No module named 'cv2'\" >&2\nexit 1\n")
+            .with_repair(move || {
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Ok(())
+            });
+
+        // The request fails without waiting for a reinstall
+        let error = pool.authenticate_image(0.6, "source", Path::new("frame.jpg")).unwrap_err();
+        assert!(format!("{:#}", error).contains("cv2"), "{:#}", error);
+        let status = pool.status();
+        assert_eq!(status.last_failure, Some(WorkerFailure::MissingDependency));
+        assert_eq!(status.dependency_repairs, 0);
+        assert_eq!(status.pool.busy, 0);
+        let error = pool.authenticate_image(0.6, "source", Path::new("frame.jpg")).unwrap_err();
+        assert!(error.is::<MissingDependency>(), "{:#}", error);
+
+        // The health check repairs, but not on every run
+        pool.health_check();
+        assert_eq!(pool.status().dependency_repairs, 1);
+        pool.authenticate_image(0.6, "source", Path::new("frame.jpg")).unwrap_err();
+        pool.health_check();
+        assert_eq!(pool.status().dependency_repairs, 1);
+        assert_eq!(repairs.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_shutdown_forgets_discarded_workers() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = pool(dir.path(), FAILING_WORKER);
+        assert!(pool.authenticate_image(0.6, "source", Path::new("crash.jpg")).is_err());
+
+        pool.shutdown();
+        assert_eq!(pool.health_check(), 0);
+        assert_eq!(pool.status().restarts, 0);
+    }
+}
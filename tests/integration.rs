@@ -0,0 +1,1781 @@
+//! End-to-end flows against [`FakeBackend`] fixtures; no camera or Python needed
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use face_auth::{AmbiguityPolicy, AssuranceLevel, AuditOutcome, AuthContext, AuthRequest, DecisionFlip, DenialReason, DocumentIssue, FaceAuth, FaceAuthError, FaceDatabase, FakeBackend, FeedbackEvent, FusionStrategy, Hook, HookPoint, Liveness, PolicyDecision, PolicyInput, PrivacyMode, SampleStatus, SceneGate, StorageMode, ThresholdProfile, ThresholdProfiles, TrackEvent, TrackingConfig};
+use tempfile::TempDir;
+
+const FIXTURES: &str = "tests/fixtures/faces.json";
+
+struct Setup {
+    _root: TempDir,
+    backend: FakeBackend,
+    auth: FaceAuth,
+    generated: PathBuf,
+    source: PathBuf,
+}
+
+impl Setup {
+    fn new(configure: impl FnOnce(face_auth::FaceAuthBuilder) -> face_auth::FaceAuthBuilder) -> Self {
+        let root = tempfile::tempdir().unwrap();
+        let generated = root.path().join("generated");
+        let source = root.path().join("source");
+        let backend = FakeBackend::from_fixtures(root.path().join("data"), FIXTURES).unwrap();
+        let builder = FaceAuth::builder()
+            .backend(backend.clone())
+            .generated_dir(&generated)
+            .source_dir(&source)
+            .auto_promote(true);
+        let auth = configure(builder).build().unwrap();
+        Self { _root: root, backend, auth, generated, source }
+    }
+
+    fn database_path(&self) -> PathBuf {
+        face_auth::FaceBackend::data_dir(&self.backend).join("python_face_database.json")
+    }
+
+    async fn register(&self, user: &str) {
+        self.backend.queue_frames((1..=3).map(|i| format!("{}_{}", user, i)));
+        let outcome = self.auth.register_user(user, 3, dir(&self.generated)).await.unwrap();
+        assert!(outcome.fully_enrolled);
+    }
+
+    async fn authenticate(&self, frame: &str) -> face_auth::FaceAuthResult {
+        self.backend.queue_frames([frame]);
+        self.auth.authenticate(&AuthRequest::camera(0.6)).await.unwrap()
+    }
+}
+
+fn dir(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
+
+#[tokio::test]
+async fn register_then_authenticate() {
+    let setup = Setup::new(|b| b);
+    setup.register("ann").await;
+    setup.register("bob").await;
+
+    let ann = setup.authenticate("ann_probe").await;
+    assert!(ann.is_authenticated);
+    assert_eq!(ann.user_id.as_deref(), Some("ann"));
+    assert!(ann.distance.unwrap() < 0.2);
+    assert!(ann.greeting.is_some());
+
+    assert!(ann.denial.is_none());
+
+    let stranger = setup.authenticate("stranger_probe").await;
+    assert!(!stranger.is_authenticated);
+    assert!(matches!(stranger.denial, Some(DenialReason::BelowThreshold { threshold, .. }) if threshold == 0.6));
+    let no_face = setup.authenticate("no_face").await;
+    assert!(!no_face.is_authenticated);
+    assert_eq!(no_face.denial, Some(DenialReason::NoFace));
+
+    let stats = setup.auth.stats().await.unwrap();
+    assert_eq!(stats.total_authentications, 1);
+    // The stranger's denial is attributed to the closest user
+    let history = setup.auth.auth_history("ann", ..).await.unwrap();
+    let outcomes: Vec<_> = history.iter().map(|e| e.outcome).collect();
+    assert_eq!(outcomes, [AuditOutcome::Granted, AuditOutcome::Denied]);
+}
+
+#[tokio::test]
+async fn decisions_are_explained_sample_by_sample() {
+    let setup = Setup::new(|b| b);
+    setup.register("ann").await;
+
+    let denied = setup.authenticate("stranger_probe").await;
+    let explanation = setup.auth.explain(&denied);
+    assert!(!explanation.granted);
+    assert_eq!(explanation.user_id.as_deref(), Some("ann"));
+    assert_eq!(explanation.samples.len(), 3);
+    assert!(explanation.samples.windows(2).all(|pair| pair[0].distance <= pair[1].distance));
+    assert_eq!(Some(explanation.samples[0].distance), denied.distance);
+    assert!(explanation.confidence.unwrap() < 0.5);
+    let Some(DecisionFlip::RaiseThreshold { threshold, .. }) = explanation.flip else {
+        panic!("expected a threshold to raise: {}", explanation);
+    };
+    // The suggested threshold is exactly enough
+    setup.backend.queue_frames(["stranger_probe"]);
+    assert!(setup.auth.authenticate(&AuthRequest::camera(threshold)).await.unwrap().is_authenticated);
+
+    let granted = setup.authenticate("ann_probe").await;
+    let explanation = setup.auth.explain(&granted);
+    assert!(explanation.granted && explanation.confidence.unwrap() > 0.5);
+    assert!(matches!(explanation.flip, Some(DecisionFlip::LowerThreshold { .. })));
+    assert!(explanation.to_string().starts_with("Granted: ann at distance"));
+}
+
+#[tokio::test]
+async fn feedback_follows_authentication() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let setup = Setup::new(|b| {
+        let events = events.clone();
+        b.feedback(move |event: &FeedbackEvent| events.lock().unwrap().push(event.clone()))
+    });
+    setup.register("ann").await;
+    events.lock().unwrap().clear();
+
+    setup.authenticate("ann_probe").await;
+    setup.authenticate("no_face").await;
+    assert_eq!(*events.lock().unwrap(), [
+        FeedbackEvent::LookAtCamera,
+        FeedbackEvent::CaptureDone,
+        FeedbackEvent::AccessGranted { display_name: "ann".to_string() },
+        FeedbackEvent::LookAtCamera,
+        FeedbackEvent::CaptureDone,
+        FeedbackEvent::AccessDenied { reason: Some(DenialReason::NoFace) },
+    ]);
+}
+
+#[tokio::test]
+async fn lookalikes_need_a_second_factor() {
+    let setup = Setup::new(|b| b.ambiguity_policy(AmbiguityPolicy::new(0.05).with_second_factor()));
+    setup.register("ann").await;
+    setup.register("bob").await;
+    // A twin enrolled with the same face
+    setup.backend.queue_frames(["ann_1", "ann_2", "ann_3"]);
+    setup.auth.register_user("ann_twin", 3, dir(&setup.generated)).await.unwrap();
+
+    let result = setup.authenticate("ann_probe").await;
+    assert!(!result.is_authenticated && result.user_id.is_none());
+    assert!(matches!(result.denial, Some(DenialReason::Ambiguous { .. })));
+    assert!(result.needs_second_factor());
+    let mut candidates = result.candidates.clone();
+    candidates.sort();
+    assert_eq!(candidates, ["ann", "ann_twin"]);
+
+    let bob = setup.authenticate("bob_probe").await;
+    assert!(bob.is_authenticated && bob.candidates.is_empty());
+}
+
+#[tokio::test]
+async fn hooks_can_refuse_a_matched_user() {
+    let booking = Hook::new(HookPoint::AfterDecision, "sh")
+        .with_args(["-c", r#"grep -q '"user_id":"ann"' || { echo "No booking today"; exit 1; }"#]);
+    let setup = Setup::new(|b| b.hook(booking));
+    setup.register("ann").await;
+    setup.register("bob").await;
+
+    assert!(setup.authenticate("ann_probe").await.is_authenticated);
+    let bob = setup.authenticate("bob_probe").await;
+    assert!(!bob.is_authenticated && bob.user_id.is_none());
+    assert_eq!(bob.denial, Some(DenialReason::HookDenied { reason: "No booking today".into() }));
+}
+
+/// Asks contractors for their badge
+#[derive(Debug)]
+struct ContractorPolicy;
+
+impl face_auth::DecisionPolicy for ContractorPolicy {
+    fn decide(&self, input: &PolicyInput) -> anyhow::Result<PolicyDecision> {
+        Ok(match input.candidates[0].user_id.as_str() {
+            "bob" => PolicyDecision::StepUp { reason: "Contractors show their badge".into() },
+            _ => PolicyDecision::Accept,
+        })
+    }
+}
+
+#[tokio::test]
+async fn decision_policy_can_require_a_second_factor() {
+    let setup = Setup::new(|b| b.decision_policy(ContractorPolicy));
+    setup.register("ann").await;
+    setup.register("bob").await;
+
+    assert!(setup.authenticate("ann_probe").await.is_authenticated);
+    let bob = setup.authenticate("bob_probe").await;
+    assert!(!bob.is_authenticated && bob.needs_second_factor());
+    assert_eq!(bob.candidates, ["bob"]);
+}
+
+/// Wants a very close match for large transactions
+#[derive(Debug)]
+struct TransactionPolicy;
+
+impl face_auth::DecisionPolicy for TransactionPolicy {
+    fn decide(&self, input: &PolicyInput) -> anyhow::Result<PolicyDecision> {
+        Ok(match input.context.number("amount") {
+            Some(amount) if amount > 1000.0 && input.candidates[0].distance > 0.01 => {
+                PolicyDecision::StepUp { reason: "Large transactions need a PIN".into() }
+            }
+            _ => PolicyDecision::Accept,
+        })
+    }
+}
+
+#[tokio::test]
+async fn context_reaches_policy_result_and_audit_log() {
+    let setup = Setup::new(|b| b.decision_policy(TransactionPolicy));
+    setup.register("ann").await;
+
+    let small = AuthContext::new().with("amount", 20).with("till", 3);
+    setup.backend.queue_frames(["ann_probe"]);
+    let result = setup.auth.authenticate(&AuthRequest::camera(0.6).with_context(small.clone())).await.unwrap();
+    assert!(result.is_authenticated);
+    assert_eq!(result.context.get("till"), Some("3"));
+
+    setup.backend.queue_frames(["ann_probe"]);
+    let large = setup.auth.authenticate(&AuthRequest::camera(0.6).with_context(small.with("amount", 2500))).await.unwrap();
+    assert!(large.needs_second_factor());
+
+    let history = setup.auth.auth_history("ann", ..).await.unwrap();
+    let amounts: Vec<_> = history.iter().filter_map(|entry| entry.context.get("amount")).collect();
+    assert_eq!(amounts, ["20", "2500"]);
+}
+
+#[tokio::test]
+async fn assurance_level_follows_liveness_evidence() {
+    let setup = Setup::new(|b| b);
+    setup.register("ann").await;
+
+    let plain = setup.authenticate("ann_probe").await;
+    assert_eq!(plain.assurance, Some(AssuranceLevel::L1));
+    setup.backend.set_liveness(Some(Liveness::Passive));
+    let live = setup.authenticate("ann_probe").await;
+    assert_eq!(live.assurance, Some(AssuranceLevel::L2));
+    assert_eq!(setup.authenticate("stranger_probe").await.assurance, None);
+
+    let session = live.session(Duration::from_secs(300)).unwrap();
+    assert!(session.require(AssuranceLevel::L2).is_ok());
+    let error = session.require(AssuranceLevel::L3).unwrap_err();
+    assert!(matches!(error.downcast_ref(), Some(FaceAuthError::InsufficientAssurance { .. })));
+}
+
+/// Records the liveness verdict each policy decision saw
+#[derive(Debug, Clone, Default)]
+struct LivenessPolicy(std::sync::Arc<std::sync::Mutex<Vec<Option<bool>>>>);
+
+impl face_auth::DecisionPolicy for LivenessPolicy {
+    fn decide(&self, input: &PolicyInput) -> anyhow::Result<PolicyDecision> {
+        self.0.lock().unwrap().push(input.liveness);
+        Ok(PolicyDecision::Accept)
+    }
+}
+
+#[tokio::test]
+async fn decision_policy_sees_the_liveness_verdict() {
+    let policy = LivenessPolicy::default();
+    let setup = Setup::new(|b| b.decision_policy(policy.clone()));
+    setup.register("ann").await;
+
+    setup.authenticate("ann_probe").await;
+    setup.backend.set_liveness(Some(Liveness::Passive));
+    setup.authenticate("ann_probe").await;
+    assert_eq!(*policy.0.lock().unwrap(), [None, Some(true)]);
+}
+
+#[tokio::test]
+async fn signed_sessions_come_only_from_unaltered_results() {
+    let key = [6; face_auth::crypto::KEY_LEN];
+    let setup = Setup::new(|b| b.signing_key(key));
+    setup.register("ann").await;
+    setup.backend.set_liveness(Some(Liveness::Passive));
+
+    let granted = setup.authenticate("ann_probe").await;
+    let signed = setup.auth.signed_session(&granted, Duration::from_secs(300)).unwrap().unwrap();
+    assert!(signed.require_at(&key, AssuranceLevel::L2, setup.auth.now()).is_ok());
+    let mut raised = signed.clone();
+    raised.session.assurance = AssuranceLevel::L3;
+    assert!(raised.require_at(&key, AssuranceLevel::L3, setup.auth.now()).is_err());
+
+    let mut altered = granted.clone();
+    altered.assurance = Some(AssuranceLevel::L3);
+    let error = setup.auth.signed_session(&altered, Duration::from_secs(300)).unwrap_err();
+    assert!(matches!(error.downcast_ref(), Some(FaceAuthError::InvalidSignature { .. })));
+    let denied = setup.authenticate("stranger_probe").await;
+    assert!(setup.auth.signed_session(&denied, Duration::from_secs(300)).unwrap().is_none());
+
+    let unsigned = Setup::new(|b| b);
+    assert!(unsigned.auth.signed_session(&granted, Duration::from_secs(300)).is_err());
+}
+
+#[tokio::test]
+async fn signed_results_verify_with_the_device_key() {
+    let key = [6; face_auth::crypto::KEY_LEN];
+    let setup = Setup::new(|b| b.device_id("kiosk-7").signing_key(key));
+    setup.register("ann").await;
+
+    let granted = setup.authenticate("ann_probe").await;
+    let json = serde_json::to_string(&granted.signed).unwrap();
+    let claims = face_auth::signing::verify_result(&json, |device| (device == Some("kiosk-7")).then_some(key)).unwrap();
+    assert_eq!(claims.user_id.as_deref(), Some("ann"));
+    assert_eq!(claims.assurance, Some(AssuranceLevel::L1));
+
+    let denied = setup.authenticate("stranger_probe").await.signed.unwrap();
+    assert!(!denied.claims.authenticated && denied.claims.user_id.is_none());
+    let mut forged = denied;
+    forged.claims.authenticated = true;
+    assert!(forged.verify(&key).is_err());
+}
+
+/// JPEG whose EXIF says it was taken at `taken` ("YYYY:MM:DD HH:MM:SS")
+fn jpeg_taken_at(taken: &str) -> Vec<u8> {
+    let mut exif = b"Exif\0\0II*\0\x08\0\0\0\x01\0\x32\x01\x02\0\x14\0\0\0\x1a\0\0\0\0\0\0\0".to_vec();
+    exif.extend_from_slice(taken.as_bytes());
+    exif.push(0);
+    let length = (exif.len() as u16 + 2).to_be_bytes();
+    [&[0xFF, 0xD8, 0xFF, 0xE1, length[0], length[1]][..], &exif, &[0xFF, 0xDA, 0, 2, 0xFF, 0xD9]].concat()
+}
+
+#[tokio::test]
+async fn uploads_are_scrubbed_and_audited_with_provenance() {
+    let setup = Setup::new(|b| b.max_image_age(Duration::from_secs(3600)));
+    setup.register("ann").await;
+
+    let result = setup.auth.authenticate(&AuthRequest::upload(0.6, b"ann_probe", "kiosk-3")).await.unwrap();
+    assert!(result.is_authenticated);
+    let history = setup.auth.auth_history("ann", ..).await.unwrap();
+    let provenance = history.last().unwrap().provenance.as_ref().unwrap();
+    assert_eq!(provenance.source, "kiosk-3");
+    assert_eq!(provenance.sha256, "edbbd928109d410e19d037f54b910c0db82a70a32d78ea4d787c575a8f7f4565");
+    assert!(!provenance.metadata_stripped);
+
+    let stale = setup.auth.authenticate(&AuthRequest::upload(0.6, jpeg_taken_at("2019:06:01 12:00:00"), "kiosk-3")).await.unwrap();
+    assert!(matches!(stale.denial, Some(DenialReason::ImageTooOld { .. })));
+}
+
+#[tokio::test]
+async fn image_age_is_judged_by_the_configured_clock() {
+    // Taken in the future by the wall clock, but a day old by the test's clock
+    let clock = face_auth::ManualClock::new("2100-06-02T12:00:00Z".parse().unwrap());
+    let setup = Setup::new(|b| b.max_image_age(Duration::from_secs(3600)).clock(clock));
+
+    let stale = setup.auth.authenticate(&AuthRequest::upload(0.6, jpeg_taken_at("2100:06:01 12:00:00"), "kiosk-3")).await.unwrap();
+    assert!(matches!(stale.denial, Some(DenialReason::ImageTooOld { .. })), "{:?}", stale.denial);
+}
+
+#[tokio::test]
+#[allow(deprecated)]
+async fn deprecated_entry_points_still_authenticate() {
+    let setup = Setup::new(|b| b);
+    setup.register("ann").await;
+
+    setup.backend.queue_frames(["ann_probe"]);
+    assert!(setup.auth.authenticate_user(0.6, dir(&setup.source)).await.unwrap().is_authenticated);
+    let door = AuthContext::new().with("door", "lobby");
+    setup.backend.queue_frames(["stranger_probe"]);
+    let denied = setup.auth.authenticate_user_with_context(0.6, dir(&setup.source), &door).await.unwrap();
+    assert_eq!((denied.is_authenticated, denied.context), (false, door));
+    let upload = setup.auth.authenticate_upload(0.6, dir(&setup.source), b"ann_probe", "kiosk-3").await.unwrap();
+    assert_eq!(upload.user_id.as_deref(), Some("ann"));
+
+    // Uploads now carry a context too
+    let context = AuthContext::new().with("till", 3);
+    let request = AuthRequest::upload(0.6, b"ann_probe", "kiosk-3").with_context(context.clone());
+    assert_eq!(setup.auth.authenticate(&request).await.unwrap().context, context);
+}
+
+/// Detector scoring every face crop the same
+#[derive(Debug)]
+struct FixedScore(f64);
+
+impl face_auth::ManipulationDetector for FixedScore {
+    fn score(&self, image: &Path) -> anyhow::Result<f64> {
+        anyhow::ensure!(std::fs::read_to_string(image)?.ends_with("\ncropped"), "Scored a whole frame instead of a face");
+        Ok(self.0)
+    }
+}
+
+#[tokio::test]
+async fn manipulated_uploads_are_denied_above_the_maximum_score() {
+    let lenient = Setup::new(|b| b.manipulation_detector(FixedScore(0.3), Some(0.5)));
+    lenient.register("ann").await;
+    let genuine = lenient.auth.authenticate(&AuthRequest::upload(0.6, b"ann_probe", "portal")).await.unwrap();
+    assert!(genuine.is_authenticated);
+    assert_eq!(genuine.manipulation_score, Some(0.3));
+
+    let strict = Setup::new(|b| b.manipulation_detector(FixedScore(0.9), Some(0.5)));
+    strict.register("ann").await;
+    let deepfake = strict.auth.authenticate(&AuthRequest::upload(0.6, b"ann_probe", "portal")).await.unwrap();
+    assert!(!deepfake.is_authenticated);
+    assert_eq!(deepfake.denial, Some(DenialReason::ManipulationSuspected { score: 0.9, maximum: 0.5 }));
+    assert_eq!(deepfake.manipulation_score, Some(0.9));
+    let audited = strict.auth.audit_entries().await.unwrap().pop().unwrap();
+    assert_eq!(audited.manipulation_score, Some(0.9));
+
+    // Nothing to score without a face, which isn't matched anyway
+    let faceless = strict.auth.authenticate(&AuthRequest::upload(0.6, b"no_face", "portal")).await.unwrap();
+    assert_eq!((faceless.denial, faceless.manipulation_score), (Some(DenialReason::NoFace), None));
+
+    let broken = Setup::new(|b| b.manipulation_detector(FixedScore(f64::NAN), Some(0.5)));
+    broken.register("ann").await;
+    let unscored = broken.auth.authenticate(&AuthRequest::upload(0.6, b"ann_probe", "portal")).await.unwrap();
+    assert!(!unscored.is_authenticated);
+    assert_eq!(unscored.denial, Some(DenialReason::ManipulationSuspected { score: 1.0, maximum: 0.5 }));
+}
+
+/// Estimator reporting the same apparent age for every face, and bystanders
+/// as children
+#[derive(Debug)]
+struct FixedAge(f64, f64);
+
+impl face_auth::AgeEstimator for FixedAge {
+    fn estimate(&self, image: &Path) -> anyhow::Result<face_auth::AgeEstimate> {
+        let crop = std::fs::read_to_string(image)?;
+        anyhow::ensure!(crop.ends_with("\ncropped"), "Estimated a whole frame instead of a face");
+        let (low, high) = if crop.starts_with("bystander") { (6.0, 10.0) } else { (self.0, self.1) };
+        Ok(face_auth::AgeEstimate { years: (low + high) / 2.0, low, high, confidence: 0.9 })
+    }
+}
+
+#[tokio::test]
+async fn minimum_age_policy_steps_up_when_the_estimate_is_uncertain() {
+    let adult = Setup::new(|b| b.age_estimator(FixedAge(24.0, 34.0)).decision_policy(face_auth::MinimumAgePolicy::new(18.0)));
+    adult.register("ann").await;
+    let result = adult.authenticate("ann_probe").await;
+    assert!(result.is_authenticated);
+    assert_eq!(result.age.map(|age| age.low), Some(24.0));
+
+    // Every face is estimated, the user's first
+    adult.backend.set_bystanders(1);
+    let result = adult.authenticate("ann_probe").await;
+    assert!(result.is_authenticated);
+    let lows: Vec<_> = result.ages.iter().map(|age| age.low).collect();
+    assert_eq!(lows, [24.0, 6.0]);
+    assert!(adult.authenticate("no_face").await.ages.is_empty());
+
+    let borderline = Setup::new(|b| b.age_estimator(FixedAge(15.0, 22.0)).decision_policy(face_auth::MinimumAgePolicy::new(18.0)));
+    borderline.register("ann").await;
+    let result = borderline.authenticate("ann_probe").await;
+    assert!(!result.is_authenticated && result.needs_second_factor());
+    assert!(matches!(result.denial, Some(DenialReason::StepUpRequired { .. })));
+}
+
+/// Voice engine scoring every caller the same
+#[derive(Debug)]
+struct FixedVoice(Option<f64>);
+
+impl face_auth::SecondaryBiometric for FixedVoice {
+    fn name(&self) -> &str {
+        "voice"
+    }
+
+    fn score(&self, _user_id: &str, _context: &AuthContext) -> anyhow::Result<Option<f64>> {
+        Ok(self.0)
+    }
+}
+
+#[tokio::test]
+async fn voice_score_is_fused_with_the_face_score() {
+    let fused = |voice| Setup::new(move |b| b.secondary_biometric(FixedVoice(voice)).decision_policy(face_auth::MultiModalPolicy::default()));
+    let caller = fused(Some(0.95));
+    caller.register("ann").await;
+    let result = caller.authenticate("ann_probe").await;
+    assert!(result.is_authenticated, "{:?}", result.denial);
+    let scores = result.modalities.unwrap();
+    assert_eq!((scores.modality.as_str(), scores.secondary), ("voice", Some(0.95)));
+    assert!(scores.face > 0.0 && scores.face < 1.0);
+
+    let impostor = fused(Some(0.0));
+    impostor.register("ann").await;
+    let result = impostor.authenticate("ann_probe").await;
+    assert!(!result.is_authenticated);
+    assert!(matches!(result.denial, Some(DenialReason::PolicyDenied { .. })));
+    assert!(result.user_id.is_none());
+
+    let silent = fused(None);
+    silent.register("ann").await;
+    let result = silent.authenticate("ann_probe").await;
+    assert!(!result.is_authenticated && result.needs_second_factor());
+}
+
+#[tokio::test]
+async fn selfies_are_matched_against_identity_documents() {
+    let setup = Setup::new(|b| b);
+    let photo = |name: &str| {
+        let path = setup.generated.with_file_name(format!("{}.jpg", name));
+        std::fs::write(&path, name).unwrap();
+        path
+    };
+    let selfie = photo("ann_probe");
+
+    let genuine = setup.auth.match_document(&selfie, &photo("ann_1")).await.unwrap();
+    assert!(genuine.is_match && genuine.score > 0.9, "{:?}", genuine);
+    let impostor = setup.auth.match_document(&selfie, &photo("bob_1")).await.unwrap();
+    assert!(!impostor.is_match && impostor.score < 0.5, "{:?}", impostor);
+    let blank = setup.auth.match_document(&selfie, &photo("no_face")).await.unwrap();
+    assert_eq!(blank.issue, Some(DocumentIssue::NoDocumentFace));
+
+    let audit = face_auth::AuditLog::new(face_auth::FaceBackend::data_dir(&setup.backend).join("audit.log")).entries().unwrap();
+    assert_eq!(audit.iter().filter(|e| e.operation == face_auth::AuditOperation::DocumentVerification).count(), 3);
+}
+
+/// Files under `root`, relative to it
+fn files_under(root: &Path) -> Vec<String> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                files.push(path.strip_prefix(root).unwrap().to_string_lossy().into_owned());
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+#[tokio::test]
+async fn strict_privacy_never_writes_images() {
+    let standard = Setup::new(|b| b);
+    standard.register("ann").await;
+    standard.authenticate("ann_probe").await;
+    let data_dir = face_auth::FaceBackend::data_dir(&standard.backend);
+    assert!(files_under(data_dir).iter().any(|file| file.ends_with(".jpg")), "standard mode keeps captures");
+
+    let strict = Setup::new(|b| b.privacy_mode(PrivacyMode::Strict));
+    let data_dir = face_auth::FaceBackend::data_dir(&strict.backend).to_path_buf();
+    assert!(files_under(&data_dir).is_empty());
+    strict.register("ann").await;
+    let records = files_under(&data_dir);
+    assert!(strict.authenticate("ann_probe").await.is_authenticated);
+    let upload = strict.auth.authenticate(&AuthRequest::upload(0.6, b"ann_probe", "portal")).await.unwrap();
+    assert!(upload.is_authenticated);
+    strict.backend.queue_frames(["ann_probe", "no_face", "stranger_probe"]);
+    strict.auth.track_faces(0.6, dir(&strict.source), &SceneGate::default(), &TrackingConfig::default(), |_| true).await.unwrap();
+
+    // Only the database, credentials, audit records, the camera's and the
+    // journal's locks, never a frame or a thumbnail
+    assert_eq!(files_under(&data_dir), records);
+    assert_eq!(records, [".camera.json", ".camera.lock", "audit.log", "journal/.recovery.lock", "python_face_database.json"]);
+    let database = FaceDatabase::load(strict.database_path()).unwrap().unwrap();
+    assert!(database.users["ann"].face_encodings.iter().all(|sample| sample.image_path.is_none()));
+
+    let error = strict.auth.submit_enrollment("bob", &[b"bob_1".to_vec()], None, Default::default()).await.unwrap_err();
+    assert!(matches!(error.downcast_ref::<FaceAuthError>(), Some(FaceAuthError::PrivacyStrict { .. })));
+    assert!(FaceAuth::builder().backend(strict.backend.clone()).privacy_mode(PrivacyMode::Strict).record_sessions(true).build().is_err());
+}
+
+#[tokio::test]
+async fn strict_privacy_redaction_writes_no_images() {
+    let strict = Setup::new(|b| b.privacy_mode(PrivacyMode::Strict));
+    let data_dir = face_auth::FaceBackend::data_dir(&strict.backend).to_path_buf();
+
+    // Keeping the largest face leaves it recognizable, so neither policy may
+    // put the result anywhere but the returned buffer
+    let everyone = strict.auth.redact_faces(b"stranger_probe", &face_auth::RedactionPolicy::all_faces()).await.unwrap();
+    assert_eq!(everyone.image, b"stranger_probe\nredacted: blur");
+    let bystanders = strict.auth.redact_faces(b"stranger_probe", &face_auth::RedactionPolicy::bystanders()).await.unwrap();
+    assert_eq!(bystanders.image, b"stranger_probe");
+    assert!(files_under(&data_dir).is_empty(), "{:?}", files_under(&data_dir));
+}
+
+#[tokio::test]
+async fn threshold_profile_overrides_tolerance() {
+    let profiles = ThresholdProfiles::new(ThresholdProfile::new("indoor-kiosk", 0.6))
+        .with_profile(ThresholdProfile::new("strict", 0.01));
+    let setup = Setup::new(|b| b.threshold_profiles(profiles));
+    setup.register("ann").await;
+
+    let indoor = setup.authenticate("ann_probe").await;
+    assert!(indoor.is_authenticated);
+    assert_eq!(indoor.profile.as_deref(), Some("indoor-kiosk"));
+
+    setup.auth.set_threshold_profile(Some("strict")).unwrap();
+    let strict = setup.authenticate("ann_probe").await;
+    assert!(!strict.is_authenticated);
+    assert!(matches!(strict.denial, Some(DenialReason::BelowThreshold { threshold, .. }) if threshold == 0.01));
+    assert!(setup.auth.set_threshold_profile(Some("beach")).is_err());
+}
+
+#[tokio::test]
+async fn cameras_fuse_into_one_decision() {
+    let setup = Setup::new(|b| b.cameras([0, 1, 2], FusionStrategy::Best));
+    setup.register("ann").await;
+    // Camera 0 is blinded by glare and camera 2 delivers nothing
+    setup.backend.queue_camera_frames(0, ["no_face"]);
+    setup.backend.queue_camera_frames(1, ["ann_probe"]);
+
+    let result = setup.auth.authenticate(&AuthRequest::camera(0.6)).await.unwrap();
+    assert!(result.is_authenticated);
+    assert_eq!(result.user_id.as_deref(), Some("ann"));
+    let seen: Vec<_> = result.cameras.iter().map(|c| (c.camera, c.closest_user.as_deref())).collect();
+    assert_eq!(seen, [(0, None), (1, Some("ann"))]);
+
+    assert!(setup.auth.authenticate(&AuthRequest::camera(0.6)).await.is_err(), "fails when every camera does");
+}
+
+#[tokio::test]
+async fn watch_only_matches_scene_changes_with_faces() {
+    let setup = Setup::new(|b| b);
+    setup.register("ann").await;
+    setup.backend.queue_frames(["no_face", "no_face", "no_face", "ann_probe", "ann_probe", "no_face", "stranger_probe", "bob_probe"]);
+
+    let mut users = Vec::new();
+    let stats = setup.auth.watch(0.6, dir(&setup.source), &SceneGate::default(), |result| {
+        users.push((result.is_authenticated, result.user_id));
+        users.len() < 2
+    }).await.unwrap();
+
+    assert_eq!(users, [(true, Some("ann".to_string())), (false, None)]);
+    assert_eq!((stats.frames, stats.skipped_still, stats.skipped_empty, stats.attempts), (7, 3, 2, 2));
+    assert_eq!(setup.backend.queued_frames(), 1, "watching stops when asked");
+}
+
+#[tokio::test]
+async fn tracked_faces_are_identified_once_per_visit() {
+    let setup = Setup::new(|b| b);
+    setup.register("ann").await;
+    setup.backend.queue_frames(["ann_probe", "ann_probe", "no_face", "ann_probe", "ann_probe", "stranger_probe", "no_face", "no_face", "no_face"]);
+
+    let tracking = TrackingConfig { max_missed_frames: 1, ..Default::default() };
+    let mut events = Vec::new();
+    let stats = setup.auth.track_faces(0.6, dir(&setup.source), &SceneGate::default(), &tracking, |event| {
+        events.push(match event {
+            TrackEvent::Entered { track_id, result } => (track_id, "entered", result.user_id, 0),
+            TrackEvent::Exited { track_id, user_id, frames } => (track_id, "exited", user_id, frames),
+            TrackEvent::TailgatingSuspected { track_id, .. } => (track_id, "tailgating", None, 0),
+            #[cfg(feature = "attention")]
+            TrackEvent::AttentionChanged { track_id, .. } => (track_id, "attention", None, 0),
+        });
+        true
+    }).await.unwrap();
+
+    assert_eq!(events, [
+        (1, "entered", Some("ann".to_string()), 0),
+        (1, "exited", Some("ann".to_string()), 4),
+        (2, "entered", None, 0),
+        (2, "exited", None, 1),
+    ]);
+    assert_eq!((stats.attempts, stats.tracked), (2, 3));
+    assert_eq!(setup.auth.stats().await.unwrap().total_authentications, 1);
+}
+
+#[cfg(all(feature = "actuator", target_os = "linux"))]
+#[tokio::test]
+async fn doors_only_open_for_audited_grants() {
+    let marker_dir = tempfile::tempdir().unwrap();
+    let marker = marker_dir.path().join("opened");
+    let config: face_auth::ActuatorConfig = serde_json::from_value(serde_json::json!({
+        "outputs": { "log": { "kind": "command", "program": "sh", "args": ["-c", format!("echo {{user}} >> {}", marker.display())] } },
+        "default": ["log"]
+    })).unwrap();
+    let setup = Setup::new(|b| b.actuator(face_auth::Actuator::new(config).unwrap()));
+    setup.register("ann").await;
+
+    // An audit log that can't be written fails the attempt before the door opens
+    let audit_log = face_auth::FaceBackend::data_dir(&setup.backend).join("audit.log");
+    std::fs::remove_file(&audit_log).unwrap();
+    std::fs::create_dir(&audit_log).unwrap();
+    setup.backend.queue_frames(["ann_probe"]);
+    assert!(setup.auth.authenticate(&AuthRequest::camera(0.6)).await.is_err());
+    std::thread::sleep(Duration::from_millis(200));
+    assert!(!marker.exists());
+
+    std::fs::remove_dir(&audit_log).unwrap();
+    assert!(setup.authenticate("ann_probe").await.is_authenticated);
+    for _ in 0..50 {
+        if marker.exists() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    assert_eq!(std::fs::read_to_string(&marker).unwrap(), "ann\n");
+}
+
+#[tokio::test]
+async fn recorded_video_runs_through_the_streaming_pipeline() {
+    let setup = Setup::new(|b| b);
+    setup.register("ann").await;
+    // The fake backend's videos name one fixture per frame
+    let video = setup.generated.with_file_name("hallway.video");
+    std::fs::write(&video, "no_face\nann_probe\nann_probe\nno_face\nstranger_probe\n").unwrap();
+    let replay = || FaceAuth::builder()
+        .backend(setup.backend.clone())
+        .generated_dir(&setup.generated)
+        .source_dir(&setup.source)
+        .camera_source(face_auth::CameraSource::VideoFile(video.clone()))
+        .build()
+        .unwrap();
+
+    let mut users = Vec::new();
+    let stats = replay().watch(0.6, dir(&setup.source), &SceneGate::default(), |result| {
+        users.push(result.user_id);
+        true
+    }).await.unwrap();
+    assert_eq!(users, [Some("ann".to_string()), None]);
+    assert_eq!((stats.frames, stats.skipped_still, stats.skipped_empty, stats.attempts), (5, 1, 2, 2));
+
+    // The same footage gives the same decisions with tracking
+    let mut entered = Vec::new();
+    replay().track_faces(0.6, dir(&setup.source), &SceneGate::default(), &TrackingConfig::default(), |event| {
+        if let TrackEvent::Entered { result, .. } = event {
+            entered.push(result.user_id);
+        }
+        true
+    }).await.unwrap();
+    assert_eq!(entered, users);
+
+    // One-shot captures continue through the video, and the backend shared
+    // with other instances keeps its own camera
+    let replay = replay();
+    let request = AuthRequest::camera(0.6).with_source_dir(dir(&setup.source));
+    assert_eq!(replay.authenticate(&request).await.unwrap().denial, Some(DenialReason::NoFace));
+    assert_eq!(replay.authenticate(&request).await.unwrap().user_id.as_deref(), Some("ann"));
+    assert_eq!(setup.backend.queued_frames(), 0);
+    assert!(!setup.authenticate("stranger_probe").await.is_authenticated);
+    assert_eq!(replay.authenticate(&request).await.unwrap().user_id.as_deref(), Some("ann"));
+}
+
+#[cfg(feature = "attention")]
+#[tokio::test]
+async fn tracked_faces_report_when_they_look_at_the_camera() {
+    use face_auth::{Attention, AttentionConfig, HeadPose};
+
+    let setup = Setup::new(|b| b);
+    setup.register("ann").await;
+    setup.backend.queue_frames(["ann_probe"; 6]);
+    let facing = HeadPose { yaw: 5.0, pitch: -3.0 };
+    let turned = HeadPose { yaw: 50.0, pitch: 0.0 };
+    setup.backend.queue_head_poses([facing, facing, turned, facing, turned, turned]);
+
+    let mut changes = Vec::new();
+    let record = |changes: &mut Vec<(u64, Attention)>, event| {
+        if let TrackEvent::AttentionChanged { track_id, attention, .. } = event {
+            changes.push((track_id, attention));
+        }
+        true
+    };
+    let tracking = TrackingConfig { attention: Some(AttentionConfig { min_frames: 2, ..Default::default() }), ..Default::default() };
+    setup.auth.track_faces(0.6, dir(&setup.source), &SceneGate::default(), &tracking, |event| record(&mut changes, event)).await.unwrap();
+    assert_eq!(changes, [(1, Attention::LookingAtCamera), (1, Attention::LookingAway)]);
+
+    // Off unless asked for
+    setup.backend.queue_frames(["ann_probe"; 3]);
+    setup.backend.queue_head_poses([facing; 3]);
+    changes.clear();
+    setup.auth.track_faces(0.6, dir(&setup.source), &SceneGate::default(), &TrackingConfig::default(), |event| record(&mut changes, event)).await.unwrap();
+    assert!(changes.is_empty());
+}
+
+#[tokio::test]
+async fn unrecognized_face_right_behind_a_user_is_tailgating() {
+    let setup = Setup::new(|b| b.tailgating_window(Duration::from_secs(30)).device_id("gate-1"));
+    setup.register("ann").await;
+    setup.backend.queue_frames(["ann_probe", "stranger_probe", "ann_probe"]);
+
+    let mut alerts = Vec::new();
+    setup.auth.track_faces(0.6, dir(&setup.source), &SceneGate::default(), &TrackingConfig::default(), |event| {
+        if let TrackEvent::TailgatingSuspected { track_id, followed_user, device_id, .. } = event {
+            alerts.push((track_id, followed_user, device_id));
+        }
+        true
+    }).await.unwrap();
+
+    assert_eq!(alerts, [(2, "ann".to_string(), Some("gate-1".to_string()))]);
+}
+
+#[tokio::test]
+async fn registration_reports_missing_faces() {
+    let setup = Setup::new(|b| b);
+    setup.backend.queue_frames(["ann_1", "no_face"]);
+
+    let outcome = setup.auth.register_user("ann", 3, dir(&setup.generated)).await.unwrap();
+    let statuses: Vec<_> = outcome.samples.iter().map(|s| s.status).collect();
+    assert_eq!(statuses, [SampleStatus::Stored, SampleStatus::NoFace, SampleStatus::CaptureFailed]);
+    assert!(outcome.is_registered() && !outcome.fully_enrolled);
+    assert!(outcome.promoted_file.is_some());
+}
+
+#[tokio::test]
+async fn registration_retries_repeated_frames() {
+    let setup = Setup::new(|b| b);
+    setup.backend.queue_frames(["ann_1", "ann_1", "ann_2", "ann_2", "ann_2", "ann_2"]);
+
+    let outcome = setup.auth.register_user("ann", 3, dir(&setup.generated)).await.unwrap();
+    let statuses: Vec<_> = outcome.samples.iter().map(|s| s.status).collect();
+    assert_eq!(statuses, [SampleStatus::Stored, SampleStatus::Stored, SampleStatus::Duplicate]);
+    assert_eq!(outcome.samples_captured, 2);
+    assert_eq!(setup.backend.queued_frames(), 0);
+}
+
+#[tokio::test]
+async fn interrupted_registration_resumes_after_the_stored_samples() {
+    let setup = Setup::new(|b| b);
+    // The backend dies on the third sample, after two were stored
+    setup.backend.queue_frames(["ann_1", "ann_2", "unreadable"]);
+    assert!(setup.auth.register_user("ann", 3, dir(&setup.generated)).await.is_err());
+    let progress = setup.auth.get_enrollment_progress("ann").await.unwrap().unwrap();
+    assert_eq!((progress.next_index(), progress.face_encodings.len()), (3, 2));
+
+    setup.backend.queue_frames(["ann_3"]);
+    let outcome = setup.auth.register_user("ann", 3, dir(&setup.generated)).await.unwrap();
+    assert!(outcome.fully_enrolled);
+    assert_eq!(outcome.samples.iter().map(|s| s.index).collect::<Vec<_>>(), [1, 2, 3]);
+    assert!(setup.auth.get_enrollment_progress("ann").await.unwrap().is_none());
+    assert!(setup.authenticate("ann_probe").await.is_authenticated);
+
+    // A different number of samples starts over
+    setup.backend.queue_frames(["bob_1", "unreadable"]);
+    assert!(setup.auth.register_user("bob", 3, dir(&setup.generated)).await.is_err());
+    setup.backend.queue_frames(["bob_2", "bob_3"]);
+    let outcome = setup.auth.register_user("bob", 2, dir(&setup.generated)).await.unwrap();
+    assert!(outcome.fully_enrolled);
+    assert_eq!(setup.backend.queued_frames(), 0);
+}
+
+#[tokio::test]
+async fn stale_corrupt_or_mismatched_registration_progress_is_not_resumed() {
+    let setup = Setup::new(|b| b);
+    let progress_path = face_auth::FaceBackend::data_dir(&setup.backend).join("registration_progress/ann.json");
+
+    // Unreadable progress is discarded
+    std::fs::create_dir_all(progress_path.parent().unwrap()).unwrap();
+    std::fs::write(&progress_path, "{").unwrap();
+    setup.backend.queue_frames(["ann_1", "unreadable"]);
+    assert!(setup.auth.register_user("ann", 3, dir(&setup.generated)).await.is_err());
+    assert_eq!(setup.auth.get_enrollment_progress("ann").await.unwrap().unwrap().face_encodings.len(), 1);
+
+    // Progress past its time to live starts over
+    let mut progress: serde_json::Value = serde_json::from_slice(&std::fs::read(&progress_path).unwrap()).unwrap();
+    progress["updated_at"] = "2024-03-01T09:00:00.000000".into();
+    std::fs::write(&progress_path, progress.to_string()).unwrap();
+    setup.backend.queue_frames(["bob_1", "unreadable"]);
+    assert!(setup.auth.register_user("ann", 3, dir(&setup.generated)).await.is_err());
+    assert_eq!(setup.auth.get_enrollment_progress("ann").await.unwrap().unwrap().face_encodings.len(), 1);
+
+    // Someone else finishing the registration stores nothing
+    setup.backend.queue_frames(["ann_1", "ann_2"]);
+    let error = setup.auth.register_user("ann", 3, dir(&setup.generated)).await.unwrap_err();
+    assert!(error.to_string().contains("don't match"), "{:#}", error);
+    assert!(FaceDatabase::load(setup.database_path()).unwrap().is_none_or(|db| !db.users.contains_key("ann")));
+    assert!(!setup.generated.join("ann.json").exists());
+    assert!(setup.auth.get_enrollment_progress("ann").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn export_and_import_between_devices() {
+    let first = Setup::new(|b| b);
+    first.register("ann").await;
+    let export = first.generated.join("ann_export.json");
+    assert!(first.auth.export_user("ann", dir(&export)).await.unwrap());
+    assert!(!first.auth.export_user("nobody", "").await.unwrap());
+
+    let second = Setup::new(|b| b);
+    assert!(second.auth.import_user(dir(&export)).await.unwrap());
+    let db = FaceDatabase::load(second.database_path()).unwrap().unwrap();
+    assert_eq!(db.users["ann"].face_encodings.len(), 3);
+
+    // An export claiming another user's profile is rejected
+    let tampered = std::fs::read_to_string(&export).unwrap().replacen("\"user_id\": \"ann\"", "\"user_id\": \"eve\"", 1);
+    std::fs::write(&export, tampered).unwrap();
+    assert!(second.auth.import_user(dir(&export)).await.is_err());
+}
+
+#[tokio::test]
+async fn compressed_exports_and_frames_read_back_transparently() {
+    use face_auth::Compression;
+
+    let first = Setup::new(|b| b.compression(Compression::zstd_default()).record_sessions(true));
+    first.register("ann").await;
+    let plain = first.generated.join("ann_plain.json");
+    face_auth::FaceBackend::export_user(&first.backend, "ann", dir(&plain)).unwrap();
+    let export = first.generated.join("ann_export.json.zst");
+    assert!(first.auth.export_user("ann", dir(&export)).await.unwrap());
+    let compressed = std::fs::read(&export).unwrap();
+    assert!(face_auth::compression::is_compressed(&compressed));
+    assert!(compressed.len() * 2 < std::fs::metadata(&plain).unwrap().len() as usize);
+
+    let second = Setup::new(|b| b);
+    assert!(second.auth.import_user(dir(&export)).await.unwrap());
+    assert!(second.auth.import_user(dir(&plain)).await.unwrap());
+    let db = FaceDatabase::load(second.database_path()).unwrap().unwrap();
+    assert_eq!(db.users["ann"].face_encodings.len(), 3);
+
+    assert!(first.authenticate("ann_probe").await.is_authenticated);
+    let report = first.auth.replay_sessions(0.6, dir(&first.source)).await.unwrap();
+    assert_eq!(report.comparisons.len(), 1);
+    assert!(report.comparisons[0].record.frame.to_string_lossy().ends_with(".zst"));
+    assert_eq!(report.changed().count(), 0);
+}
+
+#[tokio::test]
+async fn legacy_sample_ids_are_migrated_in_every_copy() {
+    use face_auth::UserProfile;
+
+    let setup = Setup::new(|b| b);
+    setup.register("ann").await;
+    let legacy = |profile: &mut UserProfile| profile.face_encodings.iter_mut().for_each(|s| s.sample_id = "ann_20240501_080000".into());
+    let mut db = FaceDatabase::load(setup.database_path()).unwrap().unwrap();
+    legacy(db.users.get_mut("ann").unwrap());
+    db.save(setup.database_path()).unwrap();
+    for path in [setup.generated.join("ann.json"), setup.source.join("ann.json")] {
+        let mut profile = UserProfile::load(&path).unwrap();
+        legacy(&mut profile);
+        profile.save(&path).unwrap();
+    }
+
+    let rewrites = setup.auth.migrate_sample_ids().await.unwrap();
+    assert_eq!(rewrites.len(), 3);
+    let ids = |profile: &UserProfile| profile.face_encodings.iter().map(|s| s.sample_id.clone()).collect::<Vec<_>>();
+    let migrated = ids(&FaceDatabase::load(setup.database_path()).unwrap().unwrap().users["ann"]);
+    assert_eq!(migrated, rewrites.iter().map(|r| r.new_id.clone()).collect::<Vec<_>>());
+    assert!(migrated.iter().all(|id| !face_auth::sample_id::is_legacy(id)));
+    assert_eq!(migrated.iter().collect::<std::collections::HashSet<_>>().len(), 3);
+    assert_eq!(ids(&UserProfile::load(setup.generated.join("ann.json")).unwrap()), migrated);
+    assert_eq!(ids(&UserProfile::load(setup.source.join("ann.json")).unwrap()), migrated);
+    assert!(setup.auth.migrate_sample_ids().await.unwrap().is_empty());
+
+    setup.auth.remove_sample("ann", &migrated[0]).await.unwrap();
+    assert_eq!(setup.auth.list_samples("ann").await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn read_only_device_never_writes() {
+    let setup = Setup::new(|b| b);
+    setup.register("ann").await;
+    let door = FaceAuth::builder()
+        .backend(setup.backend.clone())
+        .source_dir(&setup.source)
+        .storage_mode(StorageMode::ReadOnly)
+        .build()
+        .unwrap();
+    let database = std::fs::read(setup.database_path()).unwrap();
+
+    setup.backend.queue_frames(["ann_probe"]);
+    assert!(door.authenticate(&AuthRequest::camera(0.6)).await.unwrap().is_authenticated);
+    assert_eq!(std::fs::read(setup.database_path()).unwrap(), database);
+    let captures = face_auth::FaceBackend::data_dir(&setup.backend).join("captured_images");
+    assert_eq!(std::fs::read_dir(captures).unwrap().count(), 3, "only the enrollment frames remain");
+
+    let error = door.register_user("bob", 3, dir(&setup.generated)).await.unwrap_err();
+    assert!(matches!(error.downcast_ref::<FaceAuthError>(), Some(FaceAuthError::ReadOnly { .. })));
+    assert!(door.rename_user("ann", "anne").await.is_err());
+}
+
+#[tokio::test]
+async fn reenrollment_requires_the_enrolled_face() {
+    let setup = Setup::new(|b| b);
+    setup.register("ann").await;
+    setup.register("bob").await;
+
+    // A camera that only fails while re-enrolling still shows in the health status
+    assert!(setup.auth.reenroll("ann", 0.6).await.is_err());
+    assert_eq!(setup.auth.health().await.unwrap().camera_ready, Some(false));
+
+    setup.backend.queue_frames(["bob_probe"]);
+    let error = setup.auth.reenroll("ann", 0.6).await.unwrap_err();
+    assert!(matches!(error.downcast_ref::<FaceAuthError>(), Some(FaceAuthError::NotVerified { .. })));
+    assert_eq!(setup.backend.queued_frames(), 0);
+
+    // Bob stepping in after Ann was verified doesn't get his face added
+    setup.backend.queue_frames(["ann_probe", "ann_1", "bob_1", "ann_2"]);
+    let outcome = setup.auth.reenroll("ann", 0.6).await.unwrap();
+    assert_eq!(outcome.samples_captured, 2);
+    assert_eq!(outcome.samples.len(), 2);
+    assert!(outcome.samples.iter().all(|sample| sample.status == SampleStatus::Stored));
+    let db = FaceDatabase::load(setup.database_path()).unwrap().unwrap();
+    assert_eq!(db.users["ann"].face_encodings.len(), 5);
+    assert_eq!(setup.authenticate("bob_probe").await.user_id.as_deref(), Some("bob"));
+}
+
+#[tokio::test]
+async fn remote_enrollment_is_moderated() {
+    let setup = Setup::new(|b| b);
+    setup.register("bob").await;
+    let photo = |frame: &str| frame.as_bytes().to_vec();
+
+    let ann = setup.auth.submit_enrollment("ann", &[photo("ann_1"), photo("no_face"), photo("ann_1")], Some("hr".into()), Default::default()).await.unwrap();
+    let eve = setup.auth.submit_enrollment("eve", &[photo("bob_2")], None, Default::default()).await.unwrap();
+    let takeover = setup.auth.submit_enrollment("bob", &[photo("stranger_1")], None, Default::default()).await.unwrap();
+    assert_eq!(setup.auth.pending_enrollments().await.unwrap().len(), 3);
+    assert_eq!(setup.authenticate("ann_probe").await.user_id, None);
+
+    let review = setup.auth.review_enrollment(&eve.id, 0.6).await.unwrap();
+    assert_eq!(review.duplicate_of.unwrap().user_id, "bob");
+    assert!(setup.auth.approve_enrollment(&eve.id, 0.6, dir(&setup.generated)).await.is_err());
+    setup.auth.reject_enrollment(&eve.id).await.unwrap();
+
+    // Approving a submission under an enrolled name would replace that user
+    let review = setup.auth.review_enrollment(&takeover.id, 0.6).await.unwrap();
+    assert!(review.name_taken && !review.passed());
+    assert!(setup.auth.approve_enrollment(&takeover.id, 0.6, dir(&setup.generated)).await.is_err());
+    setup.auth.reject_enrollment(&takeover.id).await.unwrap();
+    assert_eq!(setup.authenticate("bob_probe").await.user_id.as_deref(), Some("bob"));
+
+    let outcome = setup.auth.approve_enrollment(&ann.id, 0.6, dir(&setup.generated)).await.unwrap();
+    // The repeated photo is dropped by the quality gate
+    assert_eq!((outcome.samples_captured, outcome.samples_requested), (1, 3));
+    assert_eq!(outcome.samples[2].status, SampleStatus::Duplicate);
+    assert!(setup.auth.pending_enrollments().await.unwrap().is_empty());
+    assert_eq!(setup.authenticate("ann_probe").await.user_id.as_deref(), Some("ann"));
+}
+
+#[tokio::test]
+async fn demo_walkthrough_enrolls_and_authenticates_from_files() {
+    let setup = Setup::new(|b| b);
+    // The fake backend reads photos as fixture names, so the cast is played by fixtures
+    let photo = |name: &str| {
+        let path = setup.generated.with_file_name(format!("{}.bmp", name));
+        std::fs::write(&path, name).unwrap();
+        path
+    };
+    let probe = |name: &str, expected: Option<&str>| face_auth::DemoProbe { image: photo(name), expected: expected.map(String::from) };
+    let images = face_auth::DemoImages {
+        enrollments: ["ann", "bob"].iter().map(|user| (user.to_string(), (1..=3).map(|i| photo(&format!("{}_{}", user, i))).collect())).collect(),
+        probes: vec![probe("ann_probe", Some("ann")), probe("bob_probe", Some("bob")), probe("stranger_probe", None)],
+    };
+
+    let mut seen = 0;
+    let steps = face_auth::demo::run(&setup.auth, &images, 0.6, |_| seen += 1).await.unwrap();
+    assert_eq!((steps.len(), seen), (5, 5));
+    assert!(steps.iter().all(face_auth::DemoStep::passed), "{:?}", steps);
+    assert_eq!(setup.authenticate("bob_probe").await.user_id.as_deref(), Some("bob"));
+
+    // Photos without a detected face fail their step, not the walkthrough
+    let faceless = face_auth::DemoImages { enrollments: vec![("cat".to_string(), vec![photo("no_face")])], probes: Vec::new() };
+    let steps = face_auth::demo::run(&setup.auth, &faceless, 0.6, |_| ()).await.unwrap();
+    assert!(matches!(steps[..], [face_auth::DemoStep::Enrolled { usable: 0, .. }]));
+    assert!(!steps[0].passed());
+    assert!(setup.auth.pending_enrollments().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn embeddings_enroll_without_images() {
+    let setup = Setup::new(|b| b.privacy_mode(PrivacyMode::Strict));
+    let fixtures: std::collections::HashMap<String, Vec<f64>> = serde_json::from_str(&std::fs::read_to_string(FIXTURES).unwrap()).unwrap();
+
+    let embeddings = |frames: &[&str]| frames.iter().map(|frame| fixtures[*frame].clone()).collect::<Vec<_>>();
+    let error = setup.auth.enroll_embeddings("ann", &embeddings(&["ann_1"]), "onnx-arcface-sha256:00").await.unwrap_err();
+    assert!(matches!(error.downcast_ref::<FaceAuthError>(), Some(FaceAuthError::IncompatibleModel { .. })));
+    assert!(setup.auth.enroll_embeddings("ann", &[fixtures["ann_1"][..64].to_vec()], "dlib-128").await.is_err());
+
+    let outcome = setup.auth.enroll_embeddings("ann", &embeddings(&["ann_1", "ann_2", "ann_1"]), "dlib-128").await.unwrap();
+    assert!(outcome.promoted_file.is_some());
+    assert_eq!(outcome.samples[2].status, SampleStatus::Duplicate);
+    let db = FaceDatabase::load(setup.database_path()).unwrap().unwrap();
+    assert_eq!(db.users["ann"].sample_count, 2);
+    assert!(db.users["ann"].face_encodings.iter().all(|s| s.image_path.is_none()));
+    assert_eq!(setup.authenticate("ann_probe").await.user_id.as_deref(), Some("ann"));
+
+    // Enrolled users can't be extended, nor can someone enroll an enrolled face
+    assert!(setup.auth.enroll_embeddings("ann", &embeddings(&["stranger_1"]), "dlib-128").await.is_err());
+    assert!(setup.auth.enroll_embeddings("eve", &embeddings(&["ann_3"]), "dlib-128").await.is_err());
+    assert_eq!(FaceDatabase::load(setup.database_path()).unwrap().unwrap().users.len(), 1);
+}
+
+#[tokio::test]
+async fn plugged_in_clock_and_random_source_make_runs_repeatable() {
+    use face_auth::{AuthFlowConfig, AuthState, ManualClock, SeededRandom};
+
+    let start = chrono::DateTime::parse_from_rfc3339("2024-05-01T08:00:00Z").unwrap().with_timezone(&chrono::Utc);
+    let fixtures: std::collections::HashMap<String, Vec<f64>> = serde_json::from_str(&std::fs::read_to_string(FIXTURES).unwrap()).unwrap();
+    let clock = ManualClock::new(start);
+    let run = || async {
+        let setup = Setup::new(|b| b.clock(clock.clone()).random(SeededRandom::new(42)));
+        setup.auth.enroll_embeddings("ann", &[fixtures["ann_1"].clone(), fixtures["ann_2"].clone()], "dlib-128").await.unwrap();
+        let db = FaceDatabase::load(setup.database_path()).unwrap().unwrap();
+        let ids: Vec<String> = db.users["ann"].face_encodings.iter().map(|s| s.sample_id.clone()).collect();
+        (setup, ids)
+    };
+    let (setup, ids) = run().await;
+    // Enrolled in the same instant, yet apart, and the same on every run
+    assert_ne!(ids[0], ids[1]);
+    assert_eq!(run().await.1, ids);
+
+    let granted = setup.authenticate("ann_probe").await;
+    assert_eq!(granted.decided_at, start);
+    assert!(setup.auth.audit_entries().await.unwrap().iter().all(|entry| entry.timestamp == start));
+    let session = granted.session(Duration::from_secs(60)).unwrap();
+    clock.advance(Duration::from_secs(60));
+    assert!(session.require_at(AssuranceLevel::L1, setup.auth.now()).is_err());
+
+    let mut flow = setup.auth.auth_flow(AuthFlowConfig::default().with_cooldown(Duration::from_secs(30)));
+    setup.backend.queue_frames(["stranger_probe"]);
+    let cooling = flow.attempt().await.unwrap().clone();
+    assert_eq!(cooling, AuthState::CoolingDown { until: setup.auth.now() + chrono::Duration::seconds(30) });
+    assert_eq!(*flow.attempt().await.unwrap(), cooling);
+    clock.advance(Duration::from_secs(30));
+    setup.backend.queue_frames(["ann_probe"]);
+    assert!(matches!(flow.attempt().await.unwrap(), AuthState::Granted { .. }));
+}
+
+/// Stands in for Windows Hello, answering from a queue
+#[derive(Debug, Clone, Default)]
+struct FakePlatform(Arc<Mutex<Vec<anyhow::Result<bool>>>>);
+
+impl face_auth::PlatformBiometrics for FakePlatform {
+    fn name(&self) -> &str {
+        "Fake Hello"
+    }
+
+    fn verify(&self, _reason: &str) -> anyhow::Result<bool> {
+        self.0.lock().unwrap().remove(0)
+    }
+}
+
+#[tokio::test]
+async fn platform_biometrics_stand_in_when_the_camera_fails() {
+    let platform = FakePlatform::default();
+    let setup = Setup::new(|b| b.platform_fallback(platform.clone(), "ann"));
+    setup.register("ann").await;
+
+    let face = setup.authenticate("ann_probe").await;
+    assert!(face.is_authenticated && face.platform.is_none());
+
+    // No frame queued: the fake camera fails
+    platform.0.lock().unwrap().extend([Ok(true), Ok(false), Err(anyhow::anyhow!("no sensor"))]);
+    let granted = setup.auth.authenticate(&AuthRequest::camera(0.6)).await.unwrap();
+    assert!(granted.is_authenticated);
+    assert_eq!((granted.user_id.as_deref(), granted.platform.as_deref()), (Some("ann"), Some("Fake Hello")));
+    assert_eq!(granted.assurance, Some(AssuranceLevel::L1));
+    let refused = setup.auth.authenticate(&AuthRequest::camera(0.6)).await.unwrap();
+    assert!(!refused.is_authenticated);
+    assert_eq!(refused.denial, Some(DenialReason::PlatformRefused { platform: "Fake Hello".into() }));
+    let error = setup.auth.authenticate(&AuthRequest::camera(0.6)).await.unwrap_err();
+    assert!(format!("{:#}", error).contains("no sensor"));
+
+    let outcomes: Vec<_> = setup.auth.auth_history("ann", ..).await.unwrap().iter().map(|e| e.outcome).collect();
+    assert_eq!(outcomes, [AuditOutcome::Granted, AuditOutcome::Granted, AuditOutcome::Denied]);
+}
+
+#[cfg(feature = "secure-sketch")]
+#[tokio::test]
+async fn sealed_secrets_unlock_only_for_a_confident_live_match() {
+    use face_auth::UnlockPolicy;
+
+    // A policy asking for less than liveness still gets it
+    let setup = Setup::new(|b| b.unlock_policy(UnlockPolicy { min_assurance: AssuranceLevel::L1, ..Default::default() }).vault_key([9; 32]));
+    setup.register("ann").await;
+    setup.register("bob").await;
+    setup.backend.queue_frames(["ann_probe"]);
+    let error = setup.auth.seal_secret("ann", b"disk passphrase").await.unwrap_err();
+    assert!(matches!(error.downcast_ref::<FaceAuthError>(), Some(FaceAuthError::InsufficientAssurance { .. })));
+    setup.backend.set_liveness(Some(Liveness::Passive));
+    setup.backend.queue_frames(["bob_probe"]);
+    assert!(setup.auth.seal_secret("ann", b"disk passphrase").await.is_err(), "only ann can seal for ann");
+    setup.backend.queue_frames(["ann_probe"]);
+    setup.auth.seal_secret("ann", b"disk passphrase").await.unwrap();
+    let data_dir = face_auth::FaceBackend::data_dir(&setup.backend);
+    let vault = std::fs::read_to_string(data_dir.join("vault/ann.json")).unwrap();
+    assert!(!vault.contains("disk passphrase"));
+
+    setup.backend.set_liveness(None);
+    setup.backend.queue_frames(["ann_probe"]);
+    let error = setup.auth.unlock_secret("ann").await.unwrap_err();
+    assert!(matches!(error.downcast_ref::<FaceAuthError>(), Some(FaceAuthError::InsufficientAssurance { .. })));
+
+    setup.backend.set_liveness(Some(Liveness::Passive));
+    setup.backend.queue_frames(["bob_probe"]);
+    let error = setup.auth.unlock_secret("ann").await.unwrap_err();
+    assert!(matches!(error.downcast_ref::<FaceAuthError>(), Some(FaceAuthError::NotVerified { .. })));
+    setup.backend.queue_frames(["ann_probe"]);
+    assert_eq!(setup.auth.unlock_secret("ann").await.unwrap(), b"disk passphrase");
+    assert!(setup.auth.unlock_secret("bob").await.is_err(), "bob has no sealed secret");
+
+    let audit = face_auth::AuditLog::new(data_dir.join("audit.log")).entries().unwrap();
+    let outcomes: Vec<_> = audit.iter()
+        .filter(|e| e.operation == face_auth::AuditOperation::SecretUnlock)
+        .map(|e| e.outcome)
+        .collect();
+    assert_eq!(outcomes, [AuditOutcome::Denied, AuditOutcome::Denied, AuditOutcome::Granted]);
+    // Sealing and unlocking never count as getting in
+    assert!(!audit.iter().any(|e| e.operation == face_auth::AuditOperation::Authentication && e.outcome == AuditOutcome::Granted));
+}
+
+#[cfg(feature = "source-watch")]
+#[tokio::test]
+async fn synced_credentials_are_reported_without_a_restart() {
+    use face_auth::SourceEvent;
+
+    let setup = Setup::new(|b| b.auto_promote(false));
+    setup.register("ann").await;
+    let (sent, events) = std::sync::mpsc::channel();
+    let _watcher = setup.auth.watch_source_dir(Duration::from_millis(50), move |event| sent.send(event.clone()).unwrap()).unwrap();
+
+    // A sync job copying the credential in
+    std::fs::copy(setup.generated.join("ann.json"), setup.source.join("ann.json")).unwrap();
+    let event = events.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert_eq!(event, SourceEvent::UserAdded { user_id: "ann".into() });
+    assert_eq!(setup.authenticate("ann_probe").await.user_id.as_deref(), Some("ann"));
+
+    std::fs::remove_file(setup.source.join("ann.json")).unwrap();
+    let event = events.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert_eq!(event, SourceEvent::UserRemoved { user_id: "ann".into() });
+    assert!(events.recv_timeout(Duration::from_millis(200)).is_err());
+}
+
+#[cfg(feature = "transfer")]
+#[tokio::test]
+async fn transfer_user_between_devices() {
+    let first = Setup::new(|b| b);
+    first.register("ann").await;
+    let second = Setup::new(|b| b);
+    let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+    let receiving = tokio::spawn({
+        let auth = second.auth.clone();
+        async move { auth.receive_user(addr, "orange-kite-42").await }
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    first.auth.send_user("ann", addr, "orange-kite-42").await.unwrap();
+    assert_eq!(receiving.await.unwrap().unwrap(), "ann");
+    let db = FaceDatabase::load(second.database_path()).unwrap().unwrap();
+    assert_eq!(db.users["ann"].face_encodings.len(), 3);
+}
+
+#[cfg(feature = "nfc")]
+#[tokio::test]
+async fn nfc_tags_carry_encrypted_credentials_between_kiosks() {
+    use face_auth::{ManualClock, nfc};
+
+    let key = [9u8; 32];
+    let clock = ManualClock::new(chrono::Utc::now());
+    let desk = Setup::new(|b| b.provisioning_key(key).nfc_tag_lifetime(Duration::from_secs(3600)).clock(clock.clone()));
+    desk.register("ann").await;
+    let tag = desk.auth.nfc_tag_data("ann", nfc::NTAG216_CAPACITY).await.unwrap();
+    assert!(tag.data.len() <= nfc::NTAG216_CAPACITY);
+    assert_eq!(tag.samples, 3);
+    assert!(desk.auth.nfc_tag_data("nobody", nfc::NTAG216_CAPACITY).await.is_err());
+    assert!(desk.auth.nfc_tag_data("ann", nfc::NTAG213_CAPACITY).await.is_err());
+
+    let kiosk = Setup::new(|b| b.provisioning_key(key).clock(clock.clone()));
+    assert_eq!(kiosk.auth.import_nfc_tag_data(&tag.data).await.unwrap(), "ann");
+    let db = FaceDatabase::load(kiosk.database_path()).unwrap().unwrap();
+    assert_eq!(db.users["ann"].face_encodings.len(), 3);
+    let original = FaceDatabase::load(desk.database_path()).unwrap().unwrap();
+    let (read, written) = (&db.users["ann"].face_encodings[0].encoding, &original.users["ann"].face_encodings[2].encoding);
+    assert!(read.iter().zip(written).all(|(a, b)| (a - b).abs() < 0.01));
+
+    // Another site's key, or none, doesn't import it
+    assert!(Setup::new(|b| b.provisioning_key([1u8; 32])).auth.import_nfc_tag_data(&tag.data).await.is_err());
+    assert!(Setup::new(|b| b).auth.import_nfc_tag_data(&tag.data).await.is_err());
+
+    // Nor does a kiosk that revoked it, nor any once it expired
+    let revoking = Setup::new(|b| b.provisioning_key(key).clock(clock.clone()));
+    revoking.auth.revoke_nfc_tag(&tag.serial).unwrap();
+    assert!(revoking.auth.import_nfc_tag_data(&tag.data).await.unwrap_err().to_string().contains("revoked"));
+    clock.advance(Duration::from_secs(3600));
+    assert!(kiosk.auth.import_nfc_tag_data(&tag.data).await.unwrap_err().to_string().contains("expired"));
+}
+
+#[tokio::test]
+async fn verify_user_compares_with_the_claimed_user_only() {
+    let setup = Setup::new(|b| b);
+    setup.register("ann").await;
+    setup.register("bob").await;
+
+    setup.backend.queue_frames(["ann_probe"]);
+    let result = setup.auth.verify_user("ann", &AuthRequest::camera(0.6)).await.unwrap();
+    assert_eq!(result.user_id.as_deref(), Some("ann"));
+
+    // Ann's face doesn't pass for Bob's badge, though 1:N would have let her in
+    setup.backend.queue_frames(["ann_probe"]);
+    let result = setup.auth.verify_user("bob", &AuthRequest::camera(0.6)).await.unwrap();
+    assert!(!result.is_authenticated);
+    assert_eq!(result.denial, Some(DenialReason::ClaimNotVerified));
+
+    // Claiming someone not enrolled looks the same, so badges can't probe who is
+    setup.backend.queue_frames(["ann_probe"]);
+    let result = setup.auth.verify_user("zoe", &AuthRequest::camera(0.6)).await.unwrap();
+    assert_eq!((result.is_authenticated, result.denial), (false, Some(DenialReason::ClaimNotVerified)));
+    let last = setup.auth.audit_entries().await.unwrap().pop().unwrap();
+    assert_eq!(last.denial, Some(DenialReason::ClaimNotVerified));
+    assert!(setup.auth.verify_user("../ann", &AuthRequest::camera(0.6)).await.is_err());
+    assert!(!face_auth::FaceBackend::data_dir(&setup.backend).join(".claims").read_dir().unwrap().any(|_| true));
+}
+
+#[cfg(feature = "qr")]
+#[tokio::test]
+async fn badge_qr_code_names_the_user_to_verify() {
+    let setup = Setup::new(|b| b);
+    setup.register("ann").await;
+    let code = qrcode::QrCode::new("ann").unwrap();
+    let modules = code.width() as u32;
+    let badge = image::GrayImage::from_fn((modules + 8) * 5, (modules + 8) * 5, |x, y| {
+        let (x, y) = ((x / 5).wrapping_sub(4), (y / 5).wrapping_sub(4));
+        let dark = x < modules && y < modules && code[(x as usize, y as usize)] == qrcode::Color::Dark;
+        image::Luma([if dark { 0 } else { 255 }])
+    });
+    let mut png = Vec::new();
+    badge.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png).unwrap();
+
+    setup.backend.queue_frames(["ann_probe"]);
+    let result = setup.auth.verify_badge(&png, &AuthRequest::camera(0.6)).await.unwrap();
+    assert_eq!(result.user_id.as_deref(), Some("ann"));
+    assert!(setup.auth.verify_badge(b"not a badge", &AuthRequest::camera(0.6)).await.is_err());
+}
+
+#[tokio::test]
+async fn replay_recorded_sessions_with_stricter_tolerance() {
+    let setup = Setup::new(|b| b.record_sessions(true));
+    setup.register("ann").await;
+    assert!(setup.authenticate("ann_probe").await.is_authenticated);
+    assert!(!setup.authenticate("bob_probe").await.is_authenticated);
+
+    let report = setup.auth.replay_sessions(0.05, dir(&setup.source)).await.unwrap();
+    assert_eq!(report.comparisons.len(), 2);
+    assert_eq!(report.newly_denied(), 1);
+    assert_eq!(report.newly_granted(), 0);
+}
+
+const CRASH_DATA_DIR: &str = "FACE_AUTH_TEST_CRASH_DATA_DIR";
+const CRASH_SOURCE_DIR: &str = "FACE_AUTH_TEST_CRASH_SOURCE_DIR";
+
+/// Half of a registration in a process that exits before finishing it; run by
+/// `operations_interrupted_by_a_crash_roll_back_on_start`
+#[test]
+#[ignore = "run in a child process by operations_interrupted_by_a_crash_roll_back_on_start"]
+fn crash_during_registration() {
+    let (Some(data_dir), Some(source)) = (std::env::var_os(CRASH_DATA_DIR), std::env::var_os(CRASH_SOURCE_DIR)) else {
+        return;
+    };
+    let (data_dir, source) = (PathBuf::from(data_dir), PathBuf::from(source));
+    let database = data_dir.join("python_face_database.json");
+    let mut transaction = face_auth::Journal::new(data_dir.join("journal")).begin("registration", Some("bob")).unwrap();
+    transaction.protect(&database).unwrap();
+    transaction.protect(source.join("bob.json")).unwrap();
+    std::fs::write(&database, "{ half written").unwrap();
+    std::process::exit(0);
+}
+
+#[tokio::test]
+async fn operations_interrupted_by_a_crash_roll_back_on_start() {
+    let setup = Setup::new(|b| b);
+    setup.register("ann").await;
+    let data_dir = face_auth::FaceBackend::data_dir(&setup.backend).to_path_buf();
+    let before = std::fs::read(setup.database_path()).unwrap();
+
+    // The process dies after the database was rewritten but before the
+    // credential files were
+    let status = std::process::Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "crash_during_registration", "--ignored", "--quiet"])
+        .env(CRASH_DATA_DIR, &data_dir)
+        .env(CRASH_SOURCE_DIR, &setup.source)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_ne!(std::fs::read(setup.database_path()).unwrap(), before);
+
+    let restarted = FaceAuth::builder()
+        .backend(setup.backend.clone())
+        .generated_dir(&setup.generated)
+        .source_dir(&setup.source)
+        .build()
+        .unwrap();
+    let recovered = restarted.recovered_operations();
+    assert_eq!(recovered.len(), 1);
+    assert_eq!((recovered[0].operation.as_str(), recovered[0].username.as_deref()), ("registration", Some("bob")));
+    assert_eq!(std::fs::read(setup.database_path()).unwrap(), before);
+    assert!(!setup.source.join("bob.json").exists());
+    let audit = face_auth::AuditLog::new(data_dir.join("audit.log")).entries().unwrap();
+    assert_eq!(audit.last().unwrap().operation, face_auth::AuditOperation::Recovery);
+
+    setup.backend.queue_frames(["ann_probe"]);
+    assert!(restarted.authenticate(&AuthRequest::camera(0.6)).await.unwrap().is_authenticated);
+}
+
+#[tokio::test]
+async fn access_events_are_delivered_after_an_outage() {
+    let online = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let delivered = Arc::new(Mutex::new(Vec::new()));
+    let setup = Setup::new(|b| {
+        let (online, delivered) = (online.clone(), delivered.clone());
+        b.outbox_config(face_auth::OutboxConfig::default().with_backoff(Duration::from_millis(10), Duration::from_millis(20)))
+            .event_sink(face_auth::CallbackSink::new("fleet", move |event: &face_auth::AccessEvent| {
+                if !online.load(std::sync::atomic::Ordering::SeqCst) {
+                    anyhow::bail!("network down");
+                }
+                delivered.lock().unwrap().push((event.user_id.clone(), event.granted));
+                Ok(())
+            }))
+    });
+    setup.register("ann").await;
+    setup.authenticate("ann_probe").await;
+    setup.authenticate("stranger_probe").await;
+    assert_eq!(setup.auth.pending_events().unwrap(), 2);
+
+    online.store(true, std::sync::atomic::Ordering::SeqCst);
+    for _ in 0..200 {
+        if setup.auth.pending_events().unwrap() == 0 {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(*delivered.lock().unwrap(), [(Some("ann".to_string()), true), (None, false)]);
+}
+
+#[tokio::test]
+async fn repeated_failures_alert_the_operators() {
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let setup = Setup::new(|b| {
+        let sent = sent.clone();
+        b.alert_rules(face_auth::AlertRules::default().with_failed_attempts(2, Duration::from_secs(5 * 60)))
+            .notifier(face_auth::CallbackNotifier::new("ops", move |notification: &face_auth::Notification| {
+                sent.lock().unwrap().push(notification.alert.clone());
+                Ok(())
+            }))
+    });
+    setup.register("ann").await;
+    setup.authenticate("stranger_probe").await;
+    setup.authenticate("ann_probe").await;
+    setup.authenticate("stranger_probe").await;
+
+    for _ in 0..200 {
+        if !sent.lock().unwrap().is_empty() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    {
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert!(matches!(sent[0], face_auth::Alert::RepeatedFailures { failures: 2, .. }));
+    }
+
+    // A capture failing, here with no frame to deliver, means the camera is offline
+    assert!(setup.auth.authenticate(&AuthRequest::camera(0.6)).await.is_err());
+    for _ in 0..200 {
+        if sent.lock().unwrap().len() > 1 {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(sent.lock().unwrap()[1..], [face_auth::Alert::CameraOffline]);
+}
+
+#[tokio::test]
+async fn loose_tolerance_exhausts_the_false_accept_budget() {
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let setup = Setup::new(|b| {
+        let sent = sent.clone();
+        b.far_budget(face_auth::FarBudget::new(0.5, Duration::from_secs(3600)).with_min_impostor_scores(4))
+            .notifier(face_auth::CallbackNotifier::new("ops", move |notification: &face_auth::Notification| {
+                sent.lock().unwrap().push(notification.alert.clone());
+                Ok(())
+            }))
+    });
+    setup.register("ann").await;
+    setup.register("bob").await;
+    // Budgets are estimated from the credentials, the first report reads them
+    assert_eq!(setup.auth.far_report(0.6).await.unwrap().enrolled_users, 2);
+
+    // A tolerance every stranger passes
+    setup.backend.queue_frames(["stranger_probe"]);
+    let result = setup.auth.authenticate(&AuthRequest::camera(2.0)).await.unwrap();
+    assert!(result.is_authenticated);
+
+    for _ in 0..200 {
+        if !sent.lock().unwrap().is_empty() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    let sent = sent.lock().unwrap().clone();
+    let [face_auth::Alert::FalseAcceptBudget { tolerance, recommended_tolerance: Some(recommended), .. }] = sent.as_slice() else {
+        panic!("expected a false-accept budget alert: {:?}", sent);
+    };
+    assert_eq!(*tolerance, 2.0);
+    assert!(*recommended < 2.0);
+    let report = setup.auth.far_report(*recommended).await.unwrap();
+    assert_eq!((report.attempts, report.far_per_comparison), (1, 0.0));
+    assert!(face_auth::FaceBackend::data_dir(&setup.backend).join("far_budget.json").exists());
+}
+
+#[tokio::test]
+async fn retained_frames_have_bystanders_blurred() {
+    let setup = Setup::new(|b| b.record_sessions(true).frame_blur(face_auth::FrameBlur::Bystanders));
+    setup.register("ann").await;
+    let denied = setup.authenticate("stranger_probe").await;
+    assert!(!denied.is_authenticated);
+
+    let data_dir = face_auth::FaceBackend::data_dir(&setup.backend).to_path_buf();
+    let recorder = face_auth::SessionRecorder::new(data_dir.join("recordings"));
+    let records = recorder.records().unwrap();
+    assert_eq!(records.len(), 1);
+    let frame = std::fs::read_to_string(recorder.frame_path(&records[0])).unwrap();
+    assert_eq!(frame, "stranger_probe\nblurred: bystanders");
+    let samples = setup.auth.list_samples("ann").await.unwrap();
+    let sample = std::fs::read_to_string(samples[0].image_path.as_ref().unwrap()).unwrap();
+    assert!(sample.ends_with("blurred: bystanders"));
+
+    // Blurring leaves the decision on the recorded frame intact
+    let report = setup.auth.replay_sessions(0.6, dir(&setup.source)).await.unwrap();
+    assert_eq!(report.changed().count(), 0);
+}
+
+#[tokio::test]
+async fn redaction_follows_the_policy() {
+    let setup = Setup::new(|b| b);
+    let policy = face_auth::RedactionPolicy::all_faces().with_style(face_auth::RedactionStyle::Pixelate { block: 8 });
+    let redacted = setup.auth.redact_faces(b"stranger_probe", &policy).await.unwrap();
+    assert_eq!(redacted.redacted(), 1);
+    assert_eq!(redacted.image, b"stranger_probe\nredacted: pixelate");
+
+    let kept = setup.auth.redact_faces(b"stranger_probe", &face_auth::RedactionPolicy::bystanders()).await.unwrap();
+    assert_eq!((kept.faces.len(), kept.redacted()), (1, 0));
+    let no_face = setup.auth.redact_faces(b"no_face", &policy).await.unwrap();
+    assert!(no_face.faces.is_empty());
+    assert_eq!(no_face.image, b"no_face");
+}
+
+#[tokio::test]
+async fn kiosk_flow_enrolls_after_review() {
+    use face_auth::{EnrollmentEvent, EnrollmentStep, Pose};
+
+    let setup = Setup::new(|b| b);
+    let steps = Arc::new(Mutex::new(Vec::new()));
+    let mut flow = setup.auth.enrollment_flow("ann").poses([Pose::Frontal, Pose::TurnLeft]).start().unwrap();
+    flow.begin().unwrap();
+    // A restarted kiosk picks the flow up where it stopped
+    let mut flow = setup.auth.resume_enrollment(flow.id()).unwrap();
+    assert_eq!(flow.step(), EnrollmentStep::Consent);
+    let recorded = steps.clone();
+    flow.on_event(move |event| if let EnrollmentEvent::StepChanged(step) = event {
+        recorded.lock().unwrap().push(*step);
+    });
+    assert!(flow.capture().await.is_err());
+    flow.give_consent().unwrap();
+
+    setup.backend.queue_frames(["ann_1", "no_face"]);
+    flow.capture().await.unwrap();
+    assert_eq!(flow.current_pose(), Some(Pose::TurnLeft));
+    flow.capture().await.unwrap();
+    assert_eq!(flow.step(), EnrollmentStep::Review);
+    assert_eq!(flow.needs_retake(), [1]);
+    assert!(flow.confirm().await.is_err());
+    // Nothing is enrolled while the flow runs
+    assert!(setup.auth.list_users().await.unwrap().is_empty());
+
+    flow.retake(1).unwrap();
+    setup.backend.queue_frames(["ann_2"]);
+    flow.capture().await.unwrap();
+    let outcome = flow.confirm().await.unwrap();
+    assert!(outcome.fully_enrolled && outcome.promoted_file.is_some());
+    assert_eq!(*steps.lock().unwrap(), [
+        EnrollmentStep::Capture { index: 0 },
+        EnrollmentStep::Capture { index: 1 },
+        EnrollmentStep::Review,
+        EnrollmentStep::Capture { index: 1 },
+        EnrollmentStep::Review,
+        EnrollmentStep::Completed,
+    ]);
+    assert!(setup.auth.unfinished_enrollments().unwrap().is_empty());
+    let profile = face_auth::UserProfile::load(outcome.generated_file.unwrap()).unwrap();
+    assert_eq!(profile.sample_count, 2);
+    assert!(profile.extra.contains_key("consented_at"));
+    assert!(setup.authenticate("ann_probe").await.is_authenticated);
+    assert!(setup.auth.enrollment_flow("ann").start().is_err());
+}
+
+#[tokio::test]
+async fn kiosk_flow_never_replaces_an_enrolled_user() {
+    let setup = Setup::new(|b| b);
+    let mut flow = setup.auth.enrollment_flow("ann").poses([face_auth::Pose::Frontal]).start().unwrap();
+    flow.begin().unwrap();
+    flow.give_consent().unwrap();
+    setup.backend.queue_frames(["bob_1"]);
+    flow.capture().await.unwrap();
+
+    // Someone else is enrolled as "ann" before the kiosk confirms
+    setup.register("ann").await;
+    let enrolled = FaceDatabase::load(setup.database_path()).unwrap().unwrap().users["ann"].clone();
+    assert!(flow.confirm().await.unwrap_err().to_string().contains("already enrolled"));
+    assert_eq!(FaceDatabase::load(setup.database_path()).unwrap().unwrap().users["ann"], enrolled);
+    assert!(setup.authenticate("ann_probe").await.is_authenticated);
+}
+
+#[tokio::test]
+async fn declined_consent_cancels_the_kiosk_flow() {
+    let setup = Setup::new(|b| b);
+    let mut flow = setup.auth.enrollment_flow("ann").start().unwrap();
+    assert_eq!(setup.auth.unfinished_enrollments().unwrap().len(), 1);
+    flow.begin().unwrap();
+    flow.decline_consent().unwrap();
+    assert_eq!(flow.step(), face_auth::EnrollmentStep::Cancelled);
+    assert!(flow.give_consent().is_err());
+    assert!(setup.auth.resume_enrollment(flow.id()).is_err());
+    assert!(setup.auth.unfinished_enrollments().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn empty_source_directory_is_a_typed_denial() {
+    use face_auth::{AuthFlowConfig, AuthState};
+
+    let setup = Setup::new(|b| b);
+    let result = setup.authenticate("ann_probe").await;
+    assert!(!result.is_authenticated);
+    assert_eq!(result.denial, Some(DenialReason::NoUsersEnrolled));
+    // Even a frame without a face: nobody could have been matched anyway
+    assert_eq!(setup.authenticate("no_face").await.denial, Some(DenialReason::NoUsersEnrolled));
+    let embedding = setup.auth.authenticate_embedding(0.6, dir(&setup.source), &[0.0; 128]).await.unwrap();
+    assert_eq!(embedding.denial, Some(DenialReason::NoUsersEnrolled));
+
+    // Nobody to match is no reason to make the user wait
+    let mut flow = setup.auth.auth_flow(AuthFlowConfig::default().with_cooldown(Duration::from_secs(60)));
+    setup.backend.queue_frames(["ann_probe"]);
+    assert_eq!(*flow.attempt().await.unwrap(), AuthState::Denied { reason: Some(DenialReason::NoUsersEnrolled) });
+
+    setup.register("ann").await;
+    assert_eq!(setup.authenticate("ann_probe").await.user_id.as_deref(), Some("ann"));
+    // The embeddings indexed by the first call pick up the enrollment
+    let fixtures: std::collections::HashMap<String, Vec<f64>> = serde_json::from_str(&std::fs::read_to_string(FIXTURES).unwrap()).unwrap();
+    let embedding = setup.auth.authenticate_embedding(0.6, dir(&setup.source), &fixtures["ann_probe"]).await.unwrap();
+    assert_eq!(embedding.user_id.as_deref(), Some("ann"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn captures_take_turns_at_the_camera() {
+    use face_auth::DeviceOperation;
+    use std::sync::OnceLock;
+
+    /// What a second caller got while the camera was taken
+    #[derive(Debug, Default)]
+    struct Contended {
+        denial: Option<DenialReason>,
+        registration_error: Option<DeviceOperation>,
+    }
+
+    let auth = Arc::new(OnceLock::<FaceAuth>::new());
+    let armed = Arc::new(Mutex::new(false));
+    let contended = Arc::new(Mutex::new(Vec::new()));
+    let setup = Setup::new(|b| {
+        let (auth, armed, contended) = (auth.clone(), armed.clone(), contended.clone());
+        b.feedback(move |event: &FeedbackEvent| {
+            if *event != FeedbackEvent::LookAtCamera || !std::mem::take(&mut *armed.lock().unwrap()) {
+                return;
+            }
+            let auth = auth.get().unwrap();
+            let runtime = tokio::runtime::Handle::current();
+            let (denial, registration) = tokio::task::block_in_place(|| {
+                (runtime.block_on(auth.authenticate(&AuthRequest::camera(0.6))), runtime.block_on(auth.register_user("cat", 3, "unused")))
+            });
+            let denial = denial.unwrap().denial;
+            let registration_error = match registration.unwrap_err().downcast::<FaceAuthError>() {
+                Ok(FaceAuthError::DeviceBusy { operation, .. }) => Some(operation),
+                other => panic!("expected a busy camera: {:?}", other),
+            };
+            contended.lock().unwrap().push(Contended { denial, registration_error });
+        })
+    });
+    assert!(auth.set(setup.auth.clone()).is_ok());
+
+    *armed.lock().unwrap() = true;
+    setup.register("ann").await;
+    *armed.lock().unwrap() = true;
+    assert!(setup.authenticate("ann_probe").await.is_authenticated, "the camera is free again");
+    // Past registrations tell when the camera should be free
+    *armed.lock().unwrap() = true;
+    setup.register("bob").await;
+
+    let contended = std::mem::take(&mut *contended.lock().unwrap());
+    assert_eq!(contended.len(), 3);
+    assert_eq!(contended[0].denial, Some(DenialReason::DeviceBusy { operation: DeviceOperation::Enrollment, eta: None }));
+    assert_eq!(contended[0].registration_error, Some(DeviceOperation::Enrollment));
+    assert!(matches!(contended[1].denial, Some(DenialReason::DeviceBusy { operation: DeviceOperation::Authentication, .. })));
+    assert_eq!(contended[1].registration_error, Some(DeviceOperation::Authentication));
+    assert!(matches!(contended[2].denial, Some(DenialReason::DeviceBusy { operation: DeviceOperation::Enrollment, eta: Some(_) })));
+
+    // Refusals saw no face: not audited as denials
+    let audit = setup.auth.audit_entries().await.unwrap();
+    assert!(audit.iter().all(|entry| !matches!(entry.denial, Some(DenialReason::DeviceBusy { .. }))));
+}
+
+#[tokio::test]
+async fn auth_flow_retries_unusable_captures() {
+    use face_auth::{AuthFlowConfig, AuthState};
+
+    let setup = Setup::new(|b| b);
+    setup.register("ann").await;
+    let states = Arc::new(Mutex::new(Vec::new()));
+    let mut flow = setup.auth.auth_flow(AuthFlowConfig::default().with_max_quality_retries(1).with_cooldown(Duration::from_secs(60)));
+    let recorded = states.clone();
+    flow.on_state(move |state| recorded.lock().unwrap().push(state.clone()));
+
+    setup.backend.queue_frames(["no_face", "ann_probe"]);
+    assert!(matches!(flow.attempt().await.unwrap(), AuthState::Granted { user_id, .. } if user_id == "ann"));
+    assert_eq!(states.lock().unwrap()[..3], [
+        AuthState::Capturing { retry: 0 },
+        AuthState::Retrying { retry: 1, reason: DenialReason::NoFace },
+        AuthState::Capturing { retry: 1 },
+    ]);
+
+    // Out of retries, the unusable capture is the decision
+    setup.backend.queue_frames(["no_face", "no_face"]);
+    assert!(matches!(flow.attempt().await.unwrap(), AuthState::CoolingDown { .. }));
+    assert_eq!(flow.result().unwrap().denial, Some(DenialReason::NoFace));
+    states.lock().unwrap().clear();
+    assert!(matches!(flow.attempt().await.unwrap(), AuthState::CoolingDown { .. }));
+    assert!(states.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn auth_flow_challenges_before_granting() {
+    use face_auth::{AssuranceLevel, AuthFlowConfig, AuthState};
+
+    let setup = Setup::new(|b| b);
+    setup.register("ann").await;
+    let mut flow = setup.auth.auth_flow(AuthFlowConfig::default().with_liveness_challenge(true).with_cooldown(Duration::ZERO));
+    assert!(flow.complete_challenge(true).is_err());
+
+    let audited = setup.auth.audit_entries().await.unwrap().len();
+    setup.backend.queue_frames(["ann_probe"]);
+    assert_eq!(*flow.attempt().await.unwrap(), AuthState::LivenessChallenge { user_id: "ann".to_string() });
+    assert!(flow.attempt().await.is_err());
+    // Nothing takes effect until the challenge is taken
+    let held = flow.result().unwrap();
+    assert!(!held.is_authenticated && held.assurance.is_none() && held.signed.is_none());
+    assert_eq!(setup.auth.audit_entries().await.unwrap().len(), audited);
+    assert_eq!(*flow.complete_challenge(true).unwrap(), AuthState::Granted { user_id: "ann".to_string(), assurance: AssuranceLevel::L3 });
+    assert!(flow.result().unwrap().is_authenticated);
+    let entries = setup.auth.audit_entries().await.unwrap();
+    assert_eq!((entries.len(), entries[audited].outcome), (audited + 1, AuditOutcome::Granted));
+
+    setup.backend.queue_frames(["ann_probe"]);
+    flow.attempt().await.unwrap();
+    assert_eq!(*flow.complete_challenge(false).unwrap(), AuthState::Denied { reason: Some(DenialReason::LivenessFailed) });
+    assert!(!flow.result().unwrap().is_authenticated);
+    let entries = setup.auth.audit_entries().await.unwrap();
+    assert_eq!(entries.len(), audited + 2);
+    let last = entries.last().unwrap();
+    assert_eq!((last.outcome, last.denial.clone()), (AuditOutcome::Denied, Some(DenialReason::LivenessFailed)));
+}
+
+#[tokio::test]
+async fn gc_removes_orphaned_files() {
+    use face_auth::Orphan;
+
+    let setup = Setup::new(|b| b.export_retention(Duration::from_secs(86_400)));
+    setup.register("ann").await;
+    assert!(setup.authenticate("ann_probe").await.is_authenticated);
+
+    let data = face_auth::FaceBackend::data_dir(&setup.backend);
+    let stale = |path: PathBuf, contents: &str| {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, contents).unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(std::time::SystemTime::now() - Duration::from_secs(3 * 86_400)).unwrap();
+        path
+    };
+    // The attempt's own frame is named by its audit entry, however old it gets
+    let captures: Vec<_> = std::fs::read_dir(data.join("captured_images")).unwrap().map(|entry| entry.unwrap().path()).collect();
+    for capture in &captures {
+        std::fs::File::options().write(true).open(capture).unwrap()
+            .set_modified(std::time::SystemTime::now() - Duration::from_secs(3 * 86_400)).unwrap();
+    }
+    let frame = stale(data.join("captured_images").join("authentication_crashed.jpg"), "frame");
+    let claim = stale(data.join(".claims").join("0a1b2c3d").join("ann.json"), "{}");
+    let unpacked = stale(data.join(".import_k2v9.json"), "{}");
+    let export = stale(data.join("exported_credentials").join("ann_credentials_old.json"), "{}");
+    let mut gone = face_auth::UserProfile::load(setup.generated.join("ann.json")).unwrap();
+    gone.rename("gone");
+    gone.save(setup.generated.join("gone.json")).unwrap();
+
+    let report = setup.auth.gc().await.unwrap();
+    assert_eq!((report.count(Orphan::Capture), report.count(Orphan::GeneratedCredential), report.count(Orphan::Export)), (1, 1, 1));
+    assert!(report.reclaimed_bytes() > 0);
+    assert_eq!((report.count(Orphan::Claim), report.count(Orphan::TempFile)), (1, 1));
+    assert!(!frame.exists() && !export.exists() && !setup.generated.join("gone.json").exists());
+    assert!(!claim.exists() && !unpacked.exists());
+    assert!(captures.iter().all(|capture| capture.exists()));
+    assert!(data.join(".camera.lock").exists());
+    // Fresh captures and live users' credentials stay
+    assert!(setup.generated.join("ann.json").exists());
+    assert!(setup.authenticate("ann_probe").await.is_authenticated);
+    assert!(setup.auth.gc().await.unwrap().removed.is_empty());
+}
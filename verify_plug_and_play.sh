@@ -31,7 +31,7 @@ if [ -f "./target/release/face_auth" ]; then
     echo "  📏 Size: $(du -h ./target/release/face_auth | cut -f1)"
 else
     echo "  ⚠️  Building release binary..."
-    cargo build --release -q
+    cargo build --release --features cli -q
     echo "  ✅ Build complete"
 fi
 echo ""